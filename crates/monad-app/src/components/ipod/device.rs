@@ -5,17 +5,51 @@ use std::time::Duration;
 use dioxus::prelude::*;
 
 use super::{ClickWheel, Screen};
+use crate::services::hotkeys::use_global_hotkeys;
+use crate::services::LibraryService;
 use crate::state::battery::BatteryState;
-use crate::state::ipod::IPodState;
+use crate::state::hotkeys::HotkeyState;
+use crate::state::ipod::{IPodScreen, IPodState};
+use crate::state::report::ReportState;
+
+/// How often to check whether a weekly report is due.
+const REPORT_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 /// Main iPod device wrapper.
 /// This creates the iconic iPod form factor with metallic body.
 #[component]
 pub fn IPodDevice() -> Element {
     // Initialize iPod navigation state
-    let ipod_state = use_context_provider(IPodState::new);
+    let library = use_context::<LibraryService>();
+    let ipod_state = use_context_provider(|| IPodState::new(&library));
     let theme = *ipod_state.theme.read();
     let theme_class = theme.css_class();
+    let display_class = ipod_state.display.read().css_class();
+
+    // Register global hotkeys (configured in Settings)
+    use_context_provider(|| HotkeyState::new(&library));
+    use_global_hotkeys();
+
+    // Show last week's listening report once a new week starts
+    let report_state = use_context_provider(|| ReportState::new(&library));
+    let ipod_nav = ipod_state.clone();
+    use_future(move || {
+        let library = library.clone();
+        let mut ipod_nav = ipod_nav.clone();
+        let mut report = report_state.report;
+        async move {
+            loop {
+                let config = *report_state.config.peek();
+                if config.enabled {
+                    if let Some(due) = library.due_weekly_report(config.week_start) {
+                        report.set(Some(due));
+                        ipod_nav.navigate(IPodScreen::WeeklyReport);
+                    }
+                }
+                tokio::time::sleep(REPORT_CHECK_INTERVAL).await;
+            }
+        }
+    });
 
     // Initialize battery state
     let battery_state = use_context_provider(BatteryState::new);
@@ -32,7 +66,7 @@ pub fn IPodDevice() -> Element {
     });
 
     rsx! {
-        div { class: "ipod-device {theme_class}",
+        div { class: "ipod-device {theme_class} {display_class}",
             // Metallic body background (handled by CSS)
 
             // Screen section (top)
@@ -2,7 +2,7 @@
 
 use dioxus::prelude::*;
 
-use super::views::{MenuView, NowPlayingView, SearchView, SettingsView};
+use super::views::{MenuView, NowPlayingView, SearchView, SettingsView, WeeklyReportView};
 use super::StatusBar;
 use crate::state::ipod::{IPodScreen, IPodState};
 
@@ -26,6 +26,7 @@ pub fn Screen() -> Element {
                         IPodScreen::Menu => rsx! { MenuView {} },
                         IPodScreen::Search => rsx! { SearchView {} },
                         IPodScreen::Settings => rsx! { SettingsView {} },
+                        IPodScreen::WeeklyReport => rsx! { WeeklyReportView {} },
                     }
                 }
             }
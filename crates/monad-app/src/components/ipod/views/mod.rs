@@ -2,10 +2,12 @@
 
 mod menu;
 mod now_playing;
+mod report;
 mod search;
 mod settings;
 
 pub use menu::MenuView;
 pub use now_playing::NowPlayingView;
+pub use report::WeeklyReportView;
 pub use search::SearchView;
 pub use settings::SettingsView;
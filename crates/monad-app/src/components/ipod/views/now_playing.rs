@@ -2,9 +2,12 @@
 
 use dioxus::document::eval;
 use dioxus::prelude::*;
-use monad_lyrics::{Lyrics, LyricsClient};
+use monad_core::{Queue, Track};
+use monad_lyrics::{LyricLine, Lyrics, LyricsClient, LyricsError, LyricsQuery};
 use tracing::{debug, info};
 
+use crate::services::{AudioService, LibraryService};
+use crate::state::ipod::{IPodState, LyricsScript};
 use crate::state::player::PlaybackStatus;
 use crate::state::AppState;
 
@@ -12,12 +15,22 @@ use crate::state::AppState;
 #[component]
 pub fn NowPlayingView() -> Element {
     let app_state = use_context::<AppState>();
+    let lyrics_client = use_context::<LyricsClient>();
+    let library = use_context::<LibraryService>();
+    let audio = use_context::<Signal<AudioService>>();
     let current_track = app_state.player.current_track.read();
     let status = *app_state.player.status.read();
     let position = *app_state.player.position.read();
+    let download_progress = *app_state.player.download_progress.read();
 
-    // State for toggling between artwork and lyrics
-    let mut show_lyrics = use_signal(|| false);
+    // State for toggling between artwork and lyrics (shared with the global hotkey)
+    let ipod_state = use_context::<IPodState>();
+    let mut show_lyrics = ipod_state.show_lyrics;
+    let mut lyrics_offset_ms = ipod_state.lyrics_offset_ms;
+
+    // Bars of the audio playing, shown over the artwork
+    let spectrum = (status == PlaybackStatus::Playing && !*show_lyrics.read())
+        .then(|| app_state.player.spectrum.read().clone());
 
     // State for fetched lyrics
     let mut lyrics: Signal<Option<Lyrics>> = use_signal(|| None);
@@ -26,20 +39,23 @@ pub fn NowPlayingView() -> Element {
 
     // Track the last track ID we fetched lyrics for
     let mut last_track_id: Signal<Option<String>> = use_signal(|| None);
+    // Lyrics fetch of the current track, cancelled when the track changes
+    let mut lyrics_task: Signal<Option<Task>> = use_signal(|| None);
+
+    // Cover art from the cache, preferred over the network thumbnail
+    let mut cached_artwork: Signal<Option<String>> = use_signal(|| None);
 
     // Check if buffering
     let is_buffering = status == PlaybackStatus::Buffering;
 
     // Clone track data to release borrow
     let track_data = current_track.as_ref().map(|t| {
-        // Strip "Song, " or "Video, " prefix from artist display
-        let artist = t.artists_display();
-        let artist = artist
-            .strip_prefix("Song, ")
-            .or_else(|| artist.strip_prefix("Video, "))
-            .unwrap_or(&artist)
-            .to_string();
-        (t.id.clone(), t.title.clone(), artist, t.hq_thumbnail_url())
+        (
+            t.id.clone(),
+            t.title.clone(),
+            display_artist(t),
+            t.hq_thumbnail_url(),
+        )
     });
 
     // Fetch lyrics when track changes
@@ -48,33 +64,82 @@ pub fn NowPlayingView() -> Element {
 
         if should_fetch {
             *last_track_id.write() = Some(track_id.clone());
-            *lyrics.write() = None;
+            if let Some(task) = lyrics_task.write().take() {
+                task.cancel();
+            }
+            *cached_artwork.write() = audio.read().cached_artwork(track_id);
+            lyrics_offset_ms.set(library.lyrics_offset_ms(track_id));
             *lyrics_error.write() = None;
-            *lyrics_loading.write() = true;
 
-            let title = title.clone();
-            let artist = artist.clone();
+            // Lyrics played before are served from the cache, also offline,
+            // unless they were translated into another language
+            let translation = ipod_state.lyrics_translation.read().clone();
+            let cached = current_track
+                .as_ref()
+                .and_then(|track| library.cached_lyrics(track));
+            let current = cached
+                .as_ref()
+                .filter(|cached| cached.translation_language == translation)
+                .cloned();
+            *lyrics_loading.write() = current.is_none();
+            let queue = app_state.queue;
+            if current.is_some() {
+                *lyrics.write() = current;
+                let client = lyrics_client.clone();
+                let library = library.clone();
+                spawn(async move {
+                    prefetch_next_lyrics(&client, &library, queue, translation.as_deref());
+                });
+            } else if let Some(track) = current_track.clone() {
+                *lyrics.write() = None;
 
-            spawn(async move {
-                info!("Fetching lyrics for: {} - {}", artist, title);
-                let client = LyricsClient::new();
-
-                match client.fetch(&artist, &title, None, None).await {
-                    Ok(fetched_lyrics) => {
-                        info!("Got {} lyric lines", fetched_lyrics.lines.len());
-                        *lyrics.write() = Some(fetched_lyrics);
-                        *lyrics_error.write() = None;
-                    }
-                    Err(e) => {
-                        debug!("Failed to fetch lyrics: {}", e);
-                        *lyrics_error.write() = Some("Lyrics not available".to_string());
+                let title = title.clone();
+                let artist = artist.clone();
+                let client = lyrics_client.clone();
+                let library = library.clone();
+
+                let task = spawn(async move {
+                    info!("Fetching lyrics for: {} - {}", artist, title);
+
+                    let query = lyrics_query(&track, &artist, translation.as_deref());
+                    // Fetches for tracks skipped past are aborted with this task
+                    match client.start_fetch(&query).result().await {
+                        Ok(fetched_lyrics) => {
+                            info!("Got {} lyric lines", fetched_lyrics.lines.len());
+                            library.cache_lyrics(&track, &fetched_lyrics);
+                            *lyrics.write() = Some(fetched_lyrics);
+                            *lyrics_error.write() = None;
+                        }
+                        // Untranslated lyrics are better than none
+                        Err(e) if cached.is_some() => {
+                            debug!("Showing cached lyrics after failing to fetch: {}", e);
+                            *lyrics.write() = cached;
+                        }
+                        Err(e) => {
+                            debug!("Failed to fetch lyrics: {}", e);
+                            let message = match e {
+                                // No provider having the song is not a failure to retry later
+                                LyricsError::NotFound(_) => "No lyrics found",
+                                LyricsError::RateLimited { .. } => "Lyrics busy, try later",
+                                _ => "Couldn't load lyrics",
+                            };
+                            *lyrics_error.write() = Some(message.to_string());
+                        }
                     }
-                }
-                *lyrics_loading.write() = false;
-            });
+                    *lyrics_loading.write() = false;
+                    prefetch_next_lyrics(&client, &library, queue, translation.as_deref());
+                });
+                lyrics_task.set(Some(task));
+            }
         }
     }
 
+    // Cover art from the cache is preferred, so it shows offline
+    let track_data = track_data.map(|(id, title, artist, thumbnail)| {
+        let thumbnail = cached_artwork.read().clone().unwrap_or(thumbnail);
+        (id, title, artist, thumbnail)
+    });
+
     rsx! {
         div { class: "ipod-now-playing",
             if let Some((_, title, artist, thumbnail)) = track_data {
@@ -89,10 +154,14 @@ pub fn NowPlayingView() -> Element {
                     if *show_lyrics.read() {
                         // Lyrics view
                         LyricsView {
-                            lyrics: lyrics.read().clone(),
+                            lyrics: lyrics
+                                .read()
+                                .clone()
+                                .map(|l| l.with_offset_ms(*lyrics_offset_ms.read())),
                             loading: *lyrics_loading.read(),
                             error: lyrics_error.read().clone(),
                             position: position,
+                            script: *ipod_state.lyrics_script.read(),
                         }
                     } else {
                         // Album artwork
@@ -112,12 +181,32 @@ pub fn NowPlayingView() -> Element {
                             if is_buffering {
                                 div { class: "ipod-now-playing__buffering",
                                     div { class: "ipod-now-playing__buffering-spinner" }
+                                    if let Some(percent) = download_progress {
+                                        div { class: "ipod-now-playing__download",
+                                            div {
+                                                class: "ipod-now-playing__download-fill",
+                                                style: "width: {percent}%",
+                                            }
+                                        }
+                                    }
                                 }
                             }
                         }
                     }
                 }
 
+                if let Some(bands) = spectrum {
+                    div { class: "ipod-now-playing__spectrum",
+                        for (i, height) in bands.iter().map(|level| level * 100.0).enumerate() {
+                            div {
+                                key: "{i}",
+                                class: "ipod-now-playing__spectrum-bar",
+                                style: "height: {height}%",
+                            }
+                        }
+                    }
+                }
+
                 // Song info overlay
                 div { class: "ipod-now-playing__info",
                     h2 { class: "ipod-now-playing__title", "{title}" }
@@ -138,12 +227,66 @@ pub fn NowPlayingView() -> Element {
 }
 
 /// Lyrics display component.
+/// Get the artist of a track as shown, without a "Song, " or "Video, " prefix.
+fn display_artist(track: &Track) -> String {
+    let artist = track.artists_display();
+    artist
+        .strip_prefix("Song, ")
+        .or_else(|| artist.strip_prefix("Video, "))
+        .unwrap_or(&artist)
+        .to_string()
+}
+
+/// Build the lyrics query of a track, the same for fetches and prefetches.
+fn lyrics_query<'a>(
+    track: &'a Track,
+    artist: &'a str,
+    translation: Option<&'a str>,
+) -> LyricsQuery<'a> {
+    // The video ID lets YouTube Music match the exact version
+    let mut query = LyricsQuery::new(artist, &track.title).with_video_id(&track.id);
+    if let Some(language) = translation {
+        query = query.with_translation_language(language);
+    }
+    // The duration picks the right version among matches
+    if track.duration.0 > 0 {
+        query = query.with_duration(track.duration.0 as f64);
+    }
+    query
+}
+
+/// Fetch the lyrics of the next track in the queue ahead of time, unless
+/// they are cached, so they show as soon as it plays.
+fn prefetch_next_lyrics(
+    client: &LyricsClient,
+    library: &LibraryService,
+    queue: Signal<Queue>,
+    translation: Option<&str>,
+) {
+    let Some(next) = queue
+        .peek()
+        .upcoming(1)
+        .first()
+        .map(|item| item.track.clone())
+    else {
+        return;
+    };
+    let cached = library
+        .cached_lyrics(&next)
+        .is_some_and(|cached| cached.translation_language.as_deref() == translation);
+    if !cached {
+        let artist = display_artist(&next);
+        client.prefetch(&lyrics_query(&next, &artist, translation));
+    }
+}
+
 #[component]
 fn LyricsView(
     lyrics: Option<Lyrics>,
     loading: bool,
     error: Option<String>,
     position: f64,
+    script: LyricsScript,
 ) -> Element {
     // Track the last scrolled-to index to avoid excessive scrolling
     let mut last_scroll_index: Signal<Option<usize>> = use_signal(|| None);
@@ -172,6 +315,14 @@ fn LyricsView(
         };
     };
 
+    if lyrics.is_instrumental() {
+        return rsx! {
+            div { class: "ipod-lyrics ipod-lyrics--instrumental",
+                div { class: "ipod-lyrics__message", "Instrumental" }
+            }
+        };
+    }
+
     // Find the current line index
     let current_index = lyrics.line_index_at(position);
 
@@ -196,9 +347,23 @@ fn LyricsView(
     }
 
     let lines = &lyrics.lines;
+    #[allow(clippy::cast_precision_loss)]
+    let offset = (lyrics.is_synced() && lyrics.offset_ms != 0)
+        .then(|| format!("{:+.2}s", lyrics.offset_ms as f64 / 1000.0));
+    // Plain lyrics have no current line, so every line is shown as readable
+    let view_class = if lyrics.is_synced() {
+        "ipod-lyrics"
+    } else {
+        "ipod-lyrics ipod-lyrics--plain"
+    };
 
     rsx! {
-        div { class: "ipod-lyrics",
+        div { class: "{view_class}",
+            // Timing adjustment of this track, if any
+            if let Some(offset) = offset {
+                div { class: "ipod-lyrics__offset", "{offset}" }
+            }
+
             // Spacer to allow first line to be centered
             div { class: "ipod-lyrics__spacer" }
 
@@ -214,12 +379,45 @@ fn LyricsView(
                         "ipod-lyrics__line"
                     };
 
+                    // Word timings only apply to the original script
+                    let romanized = line
+                        .romanized
+                        .clone()
+                        .filter(|_| script != LyricsScript::Original);
+                    let segments = match &romanized {
+                        Some(text) if script == LyricsScript::Romanized => {
+                            vec![(text.clone(), None)]
+                        }
+                        // The current line fills word by word when words are timed
+                        _ if is_current && !line.words.is_empty() => {
+                            word_segments(line, lyrics.synced_position(position))
+                        }
+                        _ => vec![(line.text.clone(), None)],
+                    };
+                    let subtitle = romanized.filter(|_| script == LyricsScript::Both);
+
                     rsx! {
                         div {
                             key: "{i}",
                             id: "lyric-line-{i}",
                             class: "{class}",
-                            "{line.text}"
+                            for (text, fill) in segments {
+                                if let Some(fill) = fill {
+                                    span {
+                                        class: "ipod-lyrics__word",
+                                        style: "--fill: {fill}%",
+                                        "{text}"
+                                    }
+                                } else {
+                                    "{text}"
+                                }
+                            }
+                            if let Some(subtitle) = subtitle {
+                                div { class: "ipod-lyrics__romanized", "{subtitle}" }
+                            }
+                            if let Some(translation) = &line.translation {
+                                div { class: "ipod-lyrics__translation", "{translation}" }
+                            }
                         }
                     }
                 }
@@ -231,6 +429,29 @@ fn LyricsView(
     }
 }
 
+/// Split a line into its words, with the percentage of each word sung, and
+/// the text between them.
+fn word_segments(line: &LyricLine, position: f64) -> Vec<(String, Option<f64>)> {
+    let mut segments = Vec::new();
+    let mut rest = line.text.as_str();
+    for word in &line.words {
+        let fill = Some((word.progress_at(position) * 100.0).round());
+        let Some(index) = rest.find(word.text.as_str()) else {
+            segments.push((word.text.clone(), fill));
+            continue;
+        };
+        if index > 0 {
+            segments.push((rest[..index].to_string(), None));
+        }
+        segments.push((word.text.clone(), fill));
+        rest = &rest[index + word.text.len()..];
+    }
+    if !rest.is_empty() {
+        segments.push((rest.to_string(), None));
+    }
+    segments
+}
+
 /// Music note icon.
 #[component]
 fn MusicIcon() -> Element {
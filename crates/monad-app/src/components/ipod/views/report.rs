@@ -0,0 +1,107 @@
+//! Weekly listening report view for iPod.
+
+use dioxus::prelude::*;
+use monad_cache::WeeklyReport;
+use monad_core::Locale;
+
+use crate::services::LibraryService;
+use crate::state::report::ReportState;
+
+/// Last week's top tracks and artists, hours listened and discoveries.
+#[component]
+pub fn WeeklyReportView() -> Element {
+    let library = use_context::<LibraryService>();
+    let report_state = use_context::<ReportState>();
+    let week_start = report_state.config.read().week_start;
+
+    let report = use_hook(|| {
+        let report = report_state
+            .report
+            .peek()
+            .clone()
+            .or_else(|| library.last_week_report(week_start));
+        if let Some(report) = &report {
+            library.mark_report_shown(report);
+        }
+        report
+    });
+
+    let Some(report) = report else {
+        return rsx! {
+            div { class: "ipod-settings",
+                div { class: "ipod-settings__item",
+                    span { class: "ipod-settings__item-label", "Nothing played last week" }
+                }
+            }
+        };
+    };
+
+    rsx! {
+        div { class: "ipod-settings",
+            ReportSummary { report: report.clone() }
+
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Top Tracks" }
+                div { class: "ipod-settings__list",
+                    for (i, track) in report.top_tracks.iter().enumerate() {
+                        div { key: "{track.video_id}", class: "ipod-settings__item",
+                            span { class: "ipod-settings__item-label",
+                                "{i + 1}. {track.title}"
+                                if let Some(artist) = &track.artist {
+                                    span { class: "ipod-report__artist", " – {artist}" }
+                                }
+                            }
+                            span { class: "ipod-settings__toggle-value", "{track.plays}" }
+                        }
+                    }
+                }
+            }
+
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Top Artists" }
+                div { class: "ipod-settings__list",
+                    for (i, artist) in report.top_artists.iter().enumerate() {
+                        div { key: "{artist.name}", class: "ipod-settings__item",
+                            span { class: "ipod-settings__item-label", "{i + 1}. {artist.name}" }
+                            span { class: "ipod-settings__toggle-value", "{artist.plays}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Week covered, plays, hours listened and discoveries.
+#[component]
+fn ReportSummary(report: WeeklyReport) -> Element {
+    let locale = Locale::system();
+    let week = format!(
+        "{} – {}",
+        report.week_start.format("%b %-d"),
+        report.week_end().format("%b %-d")
+    );
+    let hours = locale.format_number(report.listening_hours(), 1);
+    let plays = locale.format_count(report.plays);
+    let discoveries = locale.format_count(report.discoveries);
+
+    rsx! {
+        div { class: "ipod-settings__section",
+            div { class: "ipod-settings__header", "{week}" }
+            div { class: "ipod-settings__list",
+                div { class: "ipod-settings__item",
+                    span { class: "ipod-settings__item-label", "Hours Listened" }
+                    span { class: "ipod-settings__toggle-value", "{hours}" }
+                }
+                div { class: "ipod-settings__item",
+                    span { class: "ipod-settings__item-label", "Plays" }
+                    span { class: "ipod-settings__toggle-value", "{plays}" }
+                }
+                div { class: "ipod-settings__item",
+                    span { class: "ipod-settings__item-label", "Discoveries" }
+                    span { class: "ipod-settings__toggle-value", "{discoveries}" }
+                }
+            }
+        }
+    }
+}
@@ -5,24 +5,65 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use dioxus::prelude::*;
-use monad_core::Track;
+use monad_cache::OfflineResult;
+use monad_core::format::parse_count;
+use monad_core::{Album, Locale, Playlist, QueueItem, QueueSource, Track};
 use monad_innertube::{InnerTubeClient, SearchFilter, SearchResults};
 use tokio::time::sleep;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::services::AudioService;
+use crate::services::{AudioService, LibraryService};
 use crate::state::ipod::{IPodScreen, IPodState};
 use crate::state::player::PlaybackStatus;
 use crate::state::AppState;
 
+/// Wait after the last keystroke before searching.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Signals updated by a running search.
+#[derive(Clone, Copy)]
+struct SearchSignals {
+    search_id: Signal<Arc<AtomicUsize>>,
+    results: Signal<SearchResults>,
+    loading: Signal<bool>,
+    error: Signal<Option<String>>,
+}
+
 /// Search view with input and categorized results.
 #[component]
 pub fn SearchView() -> Element {
-    let mut query = use_signal(String::new);
-    let results = use_signal(SearchResults::default);
-    let loading = use_signal(|| false);
-    let error = use_signal(|| Option::<String>::None);
-    let search_id = use_signal(|| Arc::new(AtomicUsize::new(0)));
+    let mut query = use_context::<IPodState>().search_query;
+    let client = use_context::<InnerTubeClient>();
+    let signals = SearchSignals {
+        search_id: use_signal(|| Arc::new(AtomicUsize::new(0))),
+        results: use_signal(SearchResults::default),
+        loading: use_signal(|| false),
+        error: use_signal(|| Option::<String>::None),
+    };
+    let SearchSignals {
+        results,
+        loading,
+        error,
+        ..
+    } = signals;
+    let library = use_context::<LibraryService>();
+
+    // Returning to Search shows the last query's cached results straight away
+    let initial_client = client.clone();
+    let initial_library = library.clone();
+    use_hook(move || {
+        let initial_query = query.peek().clone();
+        if !initial_query.is_empty() {
+            spawn(run_search(
+                initial_client,
+                initial_library,
+                initial_query,
+                Duration::ZERO,
+                signals,
+            ));
+        }
+    });
+    let search_library = library.clone();
 
     rsx! {
         div { class: "ipod-search",
@@ -36,49 +77,13 @@ pub fn SearchView() -> Element {
                     oninput: move |evt| {
                         let new_query = evt.value();
                         query.set(new_query.clone());
-
-                        let current_id = search_id.read().load(Ordering::SeqCst);
-                        // Signals are Copy in Dioxus 0.6
-                        let mut search_id = search_id;
-                        let mut results = results;
-                        let mut loading = loading;
-                        let mut error = error;
-
-                        spawn(async move {
-                            let task_id = current_id + 1;
-                            search_id.write().store(task_id, Ordering::SeqCst);
-
-                            sleep(Duration::from_millis(300)).await;
-
-                            if search_id.read().load(Ordering::SeqCst) != task_id {
-                                return;
-                            }
-
-                            if new_query.is_empty() {
-                                *results.write() = SearchResults::default();
-                                *loading.write() = false;
-                                *error.write() = None;
-                                return;
-                            }
-
-                            *loading.write() = true;
-                            *error.write() = None;
-
-                            match perform_search(&new_query).await {
-                                Ok(search_results) => {
-                                    if search_id.read().load(Ordering::SeqCst) == task_id {
-                                        *results.write() = search_results;
-                                    }
-                                }
-                                Err(e) => {
-                                    if search_id.read().load(Ordering::SeqCst) == task_id {
-                                        *error.write() = Some(e.to_string());
-                                    }
-                                }
-                            }
-
-                            *loading.write() = false;
-                        });
+                        spawn(run_search(
+                            client.clone(),
+                            search_library.clone(),
+                            new_query,
+                            SEARCH_DEBOUNCE,
+                            signals,
+                        ));
                     },
                 }
             }
@@ -129,14 +134,7 @@ pub fn SearchView() -> Element {
                         div { class: "ipod-search__category",
                             div { class: "ipod-search__category-header", "Albums" }
                             for album in results.read().albums.iter() {
-                                div { class: "ipod-search__item ipod-search__item--album",
-                                    div { class: "ipod-search__item-title", "{album.title}" }
-                                    div { class: "ipod-search__item-artist",
-                                        if let Some(year) = album.year {
-                                            "{year}"
-                                        }
-                                    }
-                                }
+                                AlbumItem { key: "{album.id}", album: album.clone() }
                             }
                         }
                     }
@@ -147,9 +145,12 @@ pub fn SearchView() -> Element {
                             div { class: "ipod-search__category-header", "Artists" }
                             for artist in results.read().artists.iter() {
                                 div { class: "ipod-search__item ipod-search__item--artist",
-                                    div { class: "ipod-search__item-title", "{artist.name}" }
+                                    div { class: "ipod-search__item-row",
+                                        div { class: "ipod-search__item-title", "{artist.name}" }
+                                        PlayCountBadge { count: library.artist_play_count(&artist.name) }
+                                    }
                                     if let Some(subs) = &artist.subscriber_count {
-                                        div { class: "ipod-search__item-artist", "{subs} subscribers" }
+                                        div { class: "ipod-search__item-artist", "{format_subscribers(subs)} subscribers" }
                                     }
                                 }
                             }
@@ -161,12 +162,7 @@ pub fn SearchView() -> Element {
                         div { class: "ipod-search__category",
                             div { class: "ipod-search__category-header", "Playlists" }
                             for playlist in results.read().playlists.iter() {
-                                div { class: "ipod-search__item ipod-search__item--playlist",
-                                    div { class: "ipod-search__item-title", "{playlist.title}" }
-                                    if let Some(count) = playlist.track_count {
-                                        div { class: "ipod-search__item-artist", "{count} tracks" }
-                                    }
-                                }
+                                PlaylistItem { key: "{playlist.id}", playlist: playlist.clone() }
                             }
                         }
                     }
@@ -176,20 +172,259 @@ pub fn SearchView() -> Element {
     }
 }
 
-/// Perform search using `InnerTube`.
-async fn perform_search(query: &str) -> Result<SearchResults, monad_core::Error> {
+/// Search after a debounce, showing cached results while fresh ones load.
+///
+/// Superseded by any later search.
+async fn run_search(
+    client: InnerTubeClient,
+    library: LibraryService,
+    query: String,
+    debounce: Duration,
+    mut signals: SearchSignals,
+) {
+    let task_id = signals.search_id.read().fetch_add(1, Ordering::SeqCst) + 1;
+    let is_current = move || signals.search_id.read().load(Ordering::SeqCst) == task_id;
+
+    sleep(debounce).await;
+    if !is_current() {
+        return;
+    }
+
+    if query.is_empty() {
+        *signals.results.write() = SearchResults::default();
+        *signals.loading.write() = false;
+        *signals.error.write() = None;
+        return;
+    }
+
+    *signals.loading.write() = true;
+    *signals.error.write() = None;
+
     info!("Performing search for: {}", query);
-    let client = InnerTubeClient::new()?;
-    let results = client.search(query, SearchFilter::All).await?;
-    info!(
-        "Search returned: {} songs, {} videos, {} albums, {} artists, {} playlists",
-        results.songs.len(),
-        results.videos.len(),
-        results.albums.len(),
-        results.artists.len(),
-        results.playlists.len()
-    );
-    Ok(results)
+    let mut updates = client.search_revalidating(&query, SearchFilter::All);
+    let mut showing_cached = false;
+    while let Some(update) = updates.recv().await {
+        if !is_current() {
+            return;
+        }
+        match update {
+            Ok(update) => {
+                showing_cached = update.is_stale();
+                let results = update.into_results();
+                info!(
+                    "Search returned{}: {} songs, {} videos, {} albums, {} artists, {} playlists",
+                    if showing_cached { " (cached)" } else { "" },
+                    results.songs.len(),
+                    results.videos.len(),
+                    results.albums.len(),
+                    results.artists.len(),
+                    results.playlists.len()
+                );
+                *signals.results.write() = results;
+            }
+            // Cached results stay up if refreshing them fails
+            Err(e) if showing_cached => warn!("Failed to refresh search results: {e}"),
+            Err(e) => {
+                // Without the network, fall back to what has been played or opened
+                let results = offline_results(library.search_offline(&query));
+                if results.is_empty() {
+                    *signals.error.write() = Some(e.to_string());
+                } else {
+                    warn!("Search failed, showing offline results: {e}");
+                    *signals.results.write() = results;
+                }
+            }
+        }
+        *signals.loading.write() = false;
+    }
+}
+
+/// Group offline search results like online ones.
+fn offline_results(found: Vec<OfflineResult>) -> SearchResults {
+    let mut results = SearchResults::default();
+    for item in found {
+        match item {
+            OfflineResult::Track(track) => results.songs.push(track),
+            OfflineResult::Album(album) => results.albums.push(album),
+            OfflineResult::Playlist(playlist) => results.playlists.push(playlist),
+        }
+    }
+    results
+}
+
+/// Small "▶ N" badge showing how often an artist or album was played.
+#[component]
+fn PlayCountBadge(count: u64) -> Element {
+    if count == 0 {
+        return rsx! {};
+    }
+
+    rsx! {
+        span { class: "ipod-search__play-count", "▶ {count}" }
+    }
+}
+
+/// Album item; selecting it plays the album.
+#[component]
+fn AlbumItem(album: Album) -> Element {
+    let library = use_context::<LibraryService>();
+    let play_collection = use_collection_player();
+
+    let new_tracks = library.new_track_count(&album.id, album.track_count);
+    let source = QueueSource::Album {
+        id: album.id.clone(),
+        name: album.title.clone(),
+    };
+    let badge_source = source.clone();
+
+    rsx! {
+        div {
+            class: "ipod-search__item ipod-search__item--album",
+            onclick: move |_| play_collection(source.clone(), false),
+            div { class: "ipod-search__item-row",
+                div { class: "ipod-search__item-title", "{album.title}" }
+                NewTracksBadge { count: new_tracks, source: badge_source }
+                PlayCountBadge { count: library.album_play_count(album.artist_name(), &album.title) }
+            }
+            div { class: "ipod-search__item-artist",
+                if let Some(year) = album.year {
+                    "{year}"
+                }
+            }
+        }
+    }
+}
+
+/// Playlist item; selecting it plays the playlist.
+#[component]
+fn PlaylistItem(playlist: Playlist) -> Element {
+    let library = use_context::<LibraryService>();
+    let play_collection = use_collection_player();
+
+    let new_tracks = library.new_track_count(&playlist.id, playlist.track_count);
+    let source = QueueSource::Playlist {
+        id: playlist.id.clone(),
+        name: playlist.title.clone(),
+    };
+    let badge_source = source.clone();
+
+    rsx! {
+        div {
+            class: "ipod-search__item ipod-search__item--playlist",
+            onclick: move |_| play_collection(source.clone(), false),
+            div { class: "ipod-search__item-row",
+                div { class: "ipod-search__item-title", "{playlist.title}" }
+                NewTracksBadge { count: new_tracks, source: badge_source }
+            }
+            if let Some(count) = playlist.track_count {
+                div { class: "ipod-search__item-artist", "{Locale::system().format_number(f64::from(count), 0)} tracks" }
+            }
+        }
+    }
+}
+
+/// "+N new" badge for an album or playlist; selecting it plays only the new tracks.
+#[component]
+fn NewTracksBadge(count: usize, source: QueueSource) -> Element {
+    let play_collection = use_collection_player();
+    if count == 0 {
+        return rsx! {};
+    }
+
+    rsx! {
+        span {
+            class: "ipod-search__new-tracks",
+            title: "Play only the new tracks",
+            onclick: move |evt: MouseEvent| {
+                evt.stop_propagation();
+                play_collection(source.clone(), true);
+            },
+            "+{count} new"
+        }
+    }
+}
+
+/// Get a function that plays an album or playlist (`true` for only its new tracks).
+fn use_collection_player() -> impl Fn(QueueSource, bool) + Clone {
+    let client = use_context::<InnerTubeClient>();
+    let library = use_context::<LibraryService>();
+    let audio = use_context::<Signal<AudioService>>();
+    let app_state = use_context::<AppState>();
+    let ipod_state = use_context::<IPodState>();
+
+    move |source, only_new| {
+        spawn(play_collection(
+            client.clone(),
+            library.clone(),
+            audio.read().clone(),
+            app_state.clone(),
+            ipod_state.clone(),
+            source,
+            only_new,
+        ));
+    }
+}
+
+/// Fetch an album or playlist, record the visit and play it from the start.
+///
+/// With `only_new`, only tracks added since the last visit are queued.
+async fn play_collection(
+    client: InnerTubeClient,
+    library: LibraryService,
+    audio_service: AudioService,
+    mut app_state: AppState,
+    mut ipod_state: IPodState,
+    source: QueueSource,
+    only_new: bool,
+) {
+    let (id, tracks) = match &source {
+        QueueSource::Album { id, .. } => (
+            id,
+            client.get_album(id).await.map(|album| {
+                library.index_album(&album);
+                album.tracks
+            }),
+        ),
+        QueueSource::Playlist { id, .. } => (
+            id,
+            client.get_playlist(id).await.map(|playlist| {
+                library.index_playlist(&playlist);
+                playlist.tracks
+            }),
+        ),
+        _ => return,
+    };
+    let mut tracks = match tracks {
+        Ok(tracks) => tracks,
+        Err(e) => {
+            warn!("Failed to load {id}: {e}");
+            return;
+        }
+    };
+
+    let diff = library.record_visit(id, &tracks);
+    if only_new {
+        tracks.retain(|track| diff.added.contains(&track.id));
+    }
+    info!("Playing {} tracks from {id}", tracks.len());
+
+    let items = tracks
+        .into_iter()
+        .map(|track| QueueItem::new(track, source.clone()))
+        .collect();
+    let first = {
+        let mut queue = app_state.queue.write();
+        queue.set(items, 0);
+        queue.current().map(|item| item.track.clone())
+    };
+    let Some(track) = first else {
+        return;
+    };
+
+    app_state.player.set_track(Some(track.clone()));
+    *app_state.player.status.write() = PlaybackStatus::Buffering;
+    *ipod_state.screen.write() = IPodScreen::NowPlaying;
+    audio_service.play_track(&track).await;
 }
 
 /// Playable track item (song or video).
@@ -227,3 +462,8 @@ fn TrackItem(track: Track) -> Element {
         }
     }
 }
+
+/// Re-format a subscriber count from `InnerTube` in the user's locale.
+fn format_subscribers(subs: &str) -> String {
+    parse_count(subs).map_or_else(|| subs.to_string(), |n| Locale::system().format_count(n))
+}
@@ -1,14 +1,51 @@
 //! Settings view for iPod.
 
 use dioxus::prelude::*;
+use monad_audio::TransitionRecord;
+use monad_cache::{CacheEvent, CacheStats};
+use monad_core::Locale;
+use monad_lyrics::{BreakerState, LyricsClient};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::state::ipod::{ColorTheme, IPodState};
+use crate::services::{AudioService, LibraryService};
+use crate::state::audio::{next_volume, volume_name, AudioConfig};
+use crate::state::hotkeys::{accelerator_from_key, HotkeyAction, HotkeyState};
+use crate::state::ipod::{
+    next_translation_language, translation_language_name, ColorTheme, DisplayMode, IPodState,
+};
+use crate::state::report::{weekday_name, ReportState};
 
-/// Settings view with theme options.
+/// Settings view with theme options and diagnostics.
 #[component]
 pub fn SettingsView() -> Element {
     let ipod_state = use_context::<IPodState>();
     let current_theme = *ipod_state.theme.read();
+    let current_display = *ipod_state.display.read();
+    let lyrics_providers = use_context::<LyricsClient>().provider_health();
+    let audio_service = use_context::<Signal<AudioService>>();
+    let transitions = audio_service.read().transitions();
+    let bandwidth = audio_service.read().bandwidth_estimate();
+    let measured_speed = if bandwidth.samples == 0 {
+        "Unknown".to_string()
+    } else {
+        format!("{:.0} kbps", bandwidth.bytes_per_sec * 8.0 / 1000.0)
+    };
+    let mut hotkeys = use_context::<HotkeyState>();
+    let hotkeys_enabled = hotkeys.config.read().enabled;
+    let mut report = use_context::<ReportState>();
+    let report_config = *report.config.read();
+    let mut audio_config = use_signal(|| AudioConfig::load(audio_service.read().library()));
+    let thread_priority = audio_config.read().thread_priority;
+    let stream_quality = audio_config.read().stream_quality_name();
+    let crossfade = audio_config.read().crossfade_name();
+    let eq_preset = audio_config.read().eq_preset;
+    let loudness = audio_config.read().loudness_name();
+    let pitch = audio_config.read().pitch_name();
+    let mono = audio_config.read().channels.mono;
+    let balance = audio_config.read().balance_name();
+    let muted_channel = audio_config.read().muted_channel_name();
+    let mut volume = use_signal(|| audio_service.read().volume());
+    let volume_label = volume_name(*volume.read());
 
     rsx! {
         div { class: "ipod-settings",
@@ -26,6 +63,366 @@ pub fn SettingsView() -> Element {
                 }
             }
 
+            // Display Section
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Display" }
+                div { class: "ipod-settings__list",
+                    for mode in DisplayMode::all().iter() {
+                        SettingsDisplayItem {
+                            key: "{mode.name()}",
+                            mode: *mode,
+                            is_current: *mode == current_display,
+                        }
+                    }
+                }
+            }
+
+            // Global Hotkeys Section
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Global Hotkeys" }
+                div { class: "ipod-settings__list",
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            hotkeys.update(audio_service.read().library(), |config| {
+                                config.enabled = !config.enabled;
+                            });
+                        },
+                        span { class: "ipod-settings__item-label", "Enabled" }
+                        span { class: "ipod-settings__toggle-value",
+                            if hotkeys_enabled { "On" } else { "Off" }
+                        }
+                    }
+                    for action in HotkeyAction::all().iter() {
+                        SettingsHotkeyItem { key: "{action.name()}", action: *action }
+                    }
+                }
+            }
+
+            // Audio Section
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Audio" }
+                div { class: "ipod-settings__list",
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let next = next_volume(*volume.read());
+                            volume.set(next);
+                            audio_service.read().set_volume(next);
+                        },
+                        span { class: "ipod-settings__item-label", "Volume" }
+                        span { class: "ipod-settings__toggle-value", "{volume_label}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_thread_priority();
+                            config.save(audio_service.read().library());
+                        },
+                        span { class: "ipod-settings__item-label", "Thread Priority (restart)" }
+                        span { class: "ipod-settings__toggle-value", "{thread_priority.name()}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_stream_quality();
+                            config.save(audio_service.read().library());
+                        },
+                        span { class: "ipod-settings__item-label", "Stream Quality (restart)" }
+                        span { class: "ipod-settings__toggle-value", "{stream_quality}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_crossfade();
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_crossfade(config.crossfade());
+                        },
+                        span { class: "ipod-settings__item-label", "Crossfade" }
+                        span { class: "ipod-settings__toggle-value", "{crossfade}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_eq_preset();
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_eq(config.eq_preset.settings());
+                        },
+                        span { class: "ipod-settings__item-label", "Equalizer" }
+                        span { class: "ipod-settings__toggle-value", "{eq_preset.name()}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_loudness();
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_loudness(config.loudness);
+                        },
+                        span { class: "ipod-settings__item-label", "Normalize Volume" }
+                        span { class: "ipod-settings__toggle-value", "{loudness}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_pitch();
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_pitch(config.pitch_semitones);
+                        },
+                        span { class: "ipod-settings__item-label", "Pitch" }
+                        span { class: "ipod-settings__toggle-value", "{pitch}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.channels.mono = !config.channels.mono;
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_channels(config.channels);
+                        },
+                        span { class: "ipod-settings__item-label", "Mono Audio" }
+                        span { class: "ipod-settings__toggle-value",
+                            if mono { "On" } else { "Off" }
+                        }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_balance();
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_channels(config.channels);
+                        },
+                        span { class: "ipod-settings__item-label", "Balance" }
+                        span { class: "ipod-settings__toggle-value", "{balance}" }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            let mut config = audio_config.write();
+                            config.cycle_muted_channel();
+                            config.save(audio_service.read().library());
+                            audio_service.read().set_channels(config.channels);
+                        },
+                        span { class: "ipod-settings__item-label", "Mute Channel" }
+                        span { class: "ipod-settings__toggle-value", "{muted_channel}" }
+                    }
+                    div { class: "ipod-settings__item",
+                        span { class: "ipod-settings__item-label", "Measured Speed" }
+                        span { class: "ipod-settings__toggle-value", "{measured_speed}" }
+                    }
+                }
+            }
+
+            SettingsStorageSection {}
+
+            // Weekly Report Section
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Weekly Report" }
+                div { class: "ipod-settings__list",
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            report.update(audio_service.read().library(), |config| {
+                                config.enabled = !config.enabled;
+                            });
+                        },
+                        span { class: "ipod-settings__item-label", "Show Each Week" }
+                        span { class: "ipod-settings__toggle-value",
+                            if report_config.enabled { "On" } else { "Off" }
+                        }
+                    }
+                    div {
+                        class: "ipod-settings__item",
+                        onclick: move |_| {
+                            report.update(audio_service.read().library(), |config| {
+                                config.week_start = config.week_start.succ();
+                            });
+                        },
+                        span { class: "ipod-settings__item-label", "Week Starts" }
+                        span { class: "ipod-settings__toggle-value",
+                            "{weekday_name(report_config.week_start)}"
+                        }
+                    }
+                }
+            }
+
+            // Only romanizing and translating providers are configurable
+            if cfg!(any(feature = "romanize", feature = "musixmatch")) {
+                div { class: "ipod-settings__section",
+                    div { class: "ipod-settings__header", "Lyrics" }
+                    div { class: "ipod-settings__list",
+                        if cfg!(feature = "romanize") {
+                            SettingsLyricsScriptItem {}
+                        }
+                        if cfg!(feature = "musixmatch") {
+                            SettingsLyricsTranslationItem {}
+                        }
+                    }
+                }
+            }
+
+            // Diagnostics Section
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Lyrics Providers" }
+                div { class: "ipod-settings__list",
+                    for provider in lyrics_providers {
+                        div {
+                            key: "{provider.name}",
+                            class: "ipod-settings__item",
+                            span { class: "ipod-settings__item-label", "{provider.name}" }
+                            span {
+                                class: status_class(provider.state),
+                                "{provider.state.name()}"
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "ipod-settings__section",
+                div { class: "ipod-settings__header", "Track Transitions" }
+                div { class: "ipod-settings__list",
+                    if transitions.is_empty() {
+                        div { class: "ipod-settings__item",
+                            span { class: "ipod-settings__item-label", "None yet" }
+                        }
+                    }
+                    for (i, transition) in transitions.iter().rev().enumerate() {
+                        SettingsTransitionItem { key: "{i}", transition: transition.clone() }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Storage section, refreshed whenever the cache changes.
+#[component]
+fn SettingsStorageSection() -> Element {
+    let library = use_context::<LibraryService>();
+    let mut changes = use_signal(|| 0_u64);
+    let cache = library.cache().cloned();
+    use_future(move || {
+        let cache = cache.clone();
+        async move {
+            let Some(mut events) = cache.map(|cache| cache.subscribe()) else {
+                return;
+            };
+            loop {
+                match events.recv().await {
+                    Ok(CacheEvent::SettingChanged { .. }) => {}
+                    Ok(_) | Err(RecvError::Lagged(_)) => changes += 1,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    });
+    let storage = use_memo(move || {
+        let _ = changes.read();
+        library
+            .cache()
+            .map(|cache| storage_rows(&cache.stats()))
+            .unwrap_or_default()
+    });
+
+    rsx! {
+        div { class: "ipod-settings__section",
+            div { class: "ipod-settings__header", "Storage" }
+            div { class: "ipod-settings__list",
+                if storage.read().is_empty() {
+                    div { class: "ipod-settings__item",
+                        span { class: "ipod-settings__item-label", "Cache unavailable" }
+                    }
+                }
+                for (label, value) in storage.read().iter() {
+                    div { key: "{label}", class: "ipod-settings__item",
+                        span { class: "ipod-settings__item-label", "{label}" }
+                        span { class: "ipod-settings__toggle-value", "{value}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Label and value rows breaking down the cache's disk usage.
+#[allow(clippy::cast_precision_loss)]
+fn storage_rows(stats: &CacheStats) -> Vec<(String, String)> {
+    let locale = Locale::system();
+    let mut rows = vec![
+        (
+            format!(
+                "Audio ({})",
+                locale.format_number(stats.audio_count as f64, 0)
+            ),
+            locale.format_size(stats.audio_size_bytes),
+        ),
+        (
+            format!(
+                "Artwork ({})",
+                locale.format_number(stats.thumbnail_count as f64, 0)
+            ),
+            locale.format_size(stats.thumbnail_size_bytes),
+        ),
+        ("Pinned".to_string(), locale.format_size(stats.pinned_bytes)),
+        (
+            "Evictable".to_string(),
+            locale.format_size(stats.evictable_bytes),
+        ),
+        (
+            "Hit Rate".to_string(),
+            stats.hit_rate().map_or_else(
+                || "Unknown".to_string(),
+                |rate| format!("{}%", locale.format_number(rate * 100.0, 0)),
+            ),
+        ),
+    ];
+    rows.extend(
+        stats
+            .by_format
+            .iter()
+            .map(|format| (format.name.clone(), locale.format_size(format.size_bytes))),
+    );
+    rows
+}
+
+/// CSS class for a lyrics provider's circuit breaker state.
+const fn status_class(state: BreakerState) -> &'static str {
+    match state {
+        BreakerState::Closed => "ipod-settings__status",
+        BreakerState::Open | BreakerState::HalfOpen => {
+            "ipod-settings__status ipod-settings__status--down"
+        }
+    }
+}
+
+/// Track transition item: the gap between the tracks and any underruns.
+#[component]
+fn SettingsTransitionItem(transition: TransitionRecord) -> Element {
+    let kind = if transition.finished { "End" } else { "Skip" };
+    let gap_ms = transition.actual_gap.as_millis();
+    let fade = transition
+        .fade
+        .map(|fade| format!(", fade {} ms", fade.as_millis()))
+        .unwrap_or_default();
+    let (status, class) = match transition.underruns {
+        0 => ("OK".to_string(), "ipod-settings__status"),
+        n => (
+            format!("{n} underruns"),
+            "ipod-settings__status ipod-settings__status--down",
+        ),
+    };
+
+    rsx! {
+        div { class: "ipod-settings__item",
+            span { class: "ipod-settings__item-label", "{kind}: gap {gap_ms} ms{fade}" }
+            span { class: class, "{status}" }
         }
     }
 }
@@ -33,17 +430,15 @@ pub fn SettingsView() -> Element {
 /// Individual theme item in settings.
 #[component]
 fn SettingsThemeItem(theme: ColorTheme, is_current: bool) -> Element {
-    let ipod_state = use_context::<IPodState>();
-    let mut ipod_theme = ipod_state.theme;
+    let mut ipod_state = use_context::<IPodState>();
+    let library = use_context::<LibraryService>();
 
     let name = theme.name();
 
     rsx! {
         div {
             class: "ipod-settings__item",
-            onclick: move |_| {
-                *ipod_theme.write() = theme;
-            },
+            onclick: move |_| ipod_state.set_theme(theme, &library),
             div { class: "ipod-settings__item-content",
                 span { class: "ipod-settings__color-preview ipod-settings__color-preview--{name.to_lowercase()}" }
                 span { class: "ipod-settings__item-label", "{name}" }
@@ -54,3 +449,121 @@ fn SettingsThemeItem(theme: ColorTheme, is_current: bool) -> Element {
         }
     }
 }
+
+/// Lyrics script item, cycling through the scripts.
+#[component]
+fn SettingsLyricsScriptItem() -> Element {
+    let mut ipod_state = use_context::<IPodState>();
+    let library = use_context::<LibraryService>();
+    let script = *ipod_state.lyrics_script.read();
+
+    rsx! {
+        div {
+            class: "ipod-settings__item",
+            onclick: move |_| ipod_state.set_lyrics_script(script.next(), &library),
+            span { class: "ipod-settings__item-label", "Script" }
+            span { class: "ipod-settings__toggle-value", "{script.name()}" }
+        }
+    }
+}
+
+/// Lyrics translation item, cycling through the languages and off.
+#[component]
+fn SettingsLyricsTranslationItem() -> Element {
+    let mut ipod_state = use_context::<IPodState>();
+    let library = use_context::<LibraryService>();
+    let language = ipod_state.lyrics_translation.read().clone();
+    let name = language
+        .as_deref()
+        .map_or("Off", translation_language_name)
+        .to_string();
+
+    rsx! {
+        div {
+            class: "ipod-settings__item",
+            onclick: move |_| {
+                let next = next_translation_language(language.as_deref());
+                ipod_state.set_lyrics_translation(next, &library);
+            },
+            span { class: "ipod-settings__item-label", "Translate To" }
+            span { class: "ipod-settings__toggle-value", "{name}" }
+        }
+    }
+}
+
+/// Individual display mode item in settings.
+#[component]
+fn SettingsDisplayItem(mode: DisplayMode, is_current: bool) -> Element {
+    let mut ipod_state = use_context::<IPodState>();
+    let library = use_context::<LibraryService>();
+
+    rsx! {
+        div {
+            class: "ipod-settings__item",
+            onclick: move |_| ipod_state.set_display(mode, &library),
+            span { class: "ipod-settings__item-label", "{mode.name()}" }
+            if is_current {
+                span { class: "ipod-settings__checkmark", "✓" }
+            }
+        }
+    }
+}
+
+/// Hotkey binding item; click it, then press the new key combination.
+#[component]
+fn SettingsHotkeyItem(action: HotkeyAction) -> Element {
+    let mut hotkeys = use_context::<HotkeyState>();
+    let library = use_context::<LibraryService>();
+    let is_recording = *hotkeys.recording.read() == Some(action);
+    let accelerator = hotkeys
+        .config
+        .read()
+        .accelerator(action)
+        .unwrap_or("None")
+        .to_string();
+    let item_class = if is_recording {
+        "ipod-settings__item ipod-settings__item--selected"
+    } else {
+        "ipod-settings__item"
+    };
+
+    rsx! {
+        div {
+            class: item_class,
+            tabindex: "0",
+            onclick: move |_| {
+                hotkeys.recording.set(Some(action));
+            },
+            onkeydown: move |evt| {
+                if *hotkeys.recording.peek() != Some(action) {
+                    return;
+                }
+                evt.prevent_default();
+                if evt.key() == Key::Escape {
+                    hotkeys.recording.set(None);
+                    return;
+                }
+                let modifiers = evt.modifiers();
+                if let Some(accelerator) = accelerator_from_key(
+                    &evt.code().to_string(),
+                    modifiers.ctrl(),
+                    modifiers.alt(),
+                    modifiers.shift(),
+                    modifiers.meta(),
+                ) {
+                    hotkeys.update(&library, |config| config.bind(action, accelerator));
+                    hotkeys.recording.set(None);
+                }
+            },
+            onfocusout: move |_| {
+                if *hotkeys.recording.peek() == Some(action) {
+                    hotkeys.recording.set(None);
+                }
+            },
+            span { class: "ipod-settings__item-label", "{action.name()}" }
+            span { class: "ipod-settings__toggle-value",
+                if is_recording { "Press keys…" } else { "{accelerator}" }
+            }
+        }
+    }
+}
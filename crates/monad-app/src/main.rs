@@ -16,7 +16,11 @@ use components::IPodDevice;
 use dioxus::desktop::tao::window::Icon;
 use dioxus::desktop::{Config, WindowBuilder};
 use dioxus::prelude::*;
+use monad_innertube::InnerTubeClient;
+use monad_lyrics::LyricsClient;
 use services::audio::{use_audio_event_sync, use_audio_service};
+use services::session::use_session_restore;
+use services::widgets::use_widget_snapshots;
 use state::AppState;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -85,8 +89,44 @@ fn App() -> Element {
     // Provide audio service to context for other components
     use_context_provider(|| audio_service);
 
+    // Share the audio service's library for play counts in browse views
+    let library = use_context_provider(|| audio_service.read().library().clone());
+
+    // Share one lyrics client so provider health persists across tracks
+    use_context_provider(|| {
+        let mut client = LyricsClient::new();
+        // Self-hosted lyrics APIs or proxies, when configured
+        if let Some(user_agent) = library.setting::<String>("lyrics_user_agent") {
+            client = client.with_user_agent(user_agent);
+        }
+        if let Some(url) = library.setting::<String>("better_lyrics_url") {
+            client = client.with_better_lyrics_url(url);
+        }
+        if let Some(url) = library.setting::<String>("lrclib_url") {
+            client = client.with_lrclib_url(url);
+        }
+        // Languages to pick when a song's lyrics come in several, like ["ja", "en"]
+        if let Some(languages) = library.setting::<Vec<String>>("lyrics_languages") {
+            client = client.with_preferred_languages(languages);
+        }
+        #[cfg(feature = "musixmatch")]
+        if let Some(token) = library.setting::<String>("musixmatch_token") {
+            return client.with_musixmatch(token);
+        }
+        client
+    });
+
+    // Share one InnerTube client so recent searches are shown instantly on return
+    use_context_provider(InnerTubeClient::default);
+
     // Set up audio event synchronization
-    use_audio_event_sync(audio_service, app_state);
+    use_audio_event_sync(audio_service, app_state.clone());
+
+    // Restore the last session's queue, and keep saving it
+    use_session_restore(app_state.clone(), audio_service);
+
+    // Publish now-playing snapshots for external widgets
+    use_widget_snapshots(app_state, library);
 
     rsx! {
         // Inject CSS
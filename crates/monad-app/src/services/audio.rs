@@ -1,29 +1,87 @@
 //! Audio service connecting UI to the audio engine.
 
+use crate::services::playback::{LoadId, PlayQueue, Playback, Reaction};
+use crate::services::LibraryService;
+use crate::state::audio::AudioConfig;
 use crate::state::player::PlaybackStatus;
 use crate::state::AppState;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
 use dioxus::prelude::*;
-use monad_audio::{AudioEngine, EngineCommand, EngineEvent, PlaybackState as EnginePlaybackState};
-use monad_core::Track;
-use monad_extractor::Extractor;
+use monad_audio::{
+    AudioEngine, ChannelSettings, EngineCommand, EngineEvent, EqSettings, LoudnessSettings,
+    OutputBackend, TransitionRecord,
+};
+use monad_core::{PlaybackSpan, Queue, QueueItem, QueueSource, SkipSegment, Track};
+use monad_extractor::{AuthMethod, BandwidthEstimate, BatchOptions, Extractor, SpanResolver};
 use parking_lot::Mutex;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+/// Host probed to check whether the network is reachable.
+const CONNECTIVITY_HOST: &str = "music.youtube.com:443";
+
+/// Treat the network as down if the probe takes longer than this.
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of tracks added by an offline mix.
+const OFFLINE_MIX_SIZE: usize = 25;
+
+/// Setting the volume is saved under.
+const VOLUME_SETTING: &str = "volume";
+
+/// Setting the cache size limit in bytes is saved under.
+const CACHE_LIMIT_SETTING: &str = "cache_limit";
+
+/// Setting the yt-dlp authentication method is saved under.
+const AUTH_METHOD_SETTING: &str = "auth_method";
+
 /// Audio service that manages the connection between UI and audio playback.
 #[derive(Clone)]
 pub struct AudioService {
     engine: Arc<Mutex<Option<AudioEngine>>>,
     extractor: Arc<Extractor>,
+    /// Finds intros and outros to skip in music videos.
+    spans: Option<SpanResolver>,
+    library: LibraryService,
+    /// Which load the engine is playing, deciding how to react to its events.
+    playback: Arc<Mutex<Playback>>,
+    /// Crossfade into preloaded tracks, which are only preloaded when set.
+    crossfade: Arc<Mutex<Duration>>,
+    /// Video ID of the track preloaded to follow the current one.
+    preloaded: Arc<Mutex<Option<String>>>,
 }
 
 impl AudioService {
     /// Create a new audio service.
     pub fn new() -> Self {
-        let engine = match AudioEngine::new() {
+        let library = LibraryService::new();
+        let config = AudioConfig::load(&library);
+        let priority = config.thread_priority;
+        let engine = match AudioEngine::with_priority(OutputBackend::default(), priority) {
             Ok(engine) => {
                 info!("Audio engine initialized successfully");
+                if let Err(e) = engine.set_crossfade(config.crossfade()) {
+                    warn!("Failed to set crossfade: {e}");
+                }
+                if let Err(e) = engine.set_eq(config.eq_preset.settings()) {
+                    warn!("Failed to set equalizer: {e}");
+                }
+                if let Err(e) = engine.set_loudness(config.loudness) {
+                    warn!("Failed to set loudness normalization: {e}");
+                }
+                if let Err(e) = engine.set_pitch(config.pitch_semitones) {
+                    warn!("Failed to set pitch shift: {e}");
+                }
+                if let Err(e) = engine.set_channels(config.channels) {
+                    warn!("Failed to set channels: {e}");
+                }
+                if let Some(volume) = library.setting(VOLUME_SETTING) {
+                    if let Err(e) = engine.set_volume(volume) {
+                        warn!("Failed to set volume: {e}");
+                    }
+                }
                 Some(engine)
             }
             Err(e) => {
@@ -32,13 +90,34 @@ impl AudioService {
             }
         };
 
-        let extractor = Extractor::new();
+        let mut extractor = Extractor::new().with_audio_quality(config.stream_quality);
+        if let Some(auth_method) = library.setting::<AuthMethod>(AUTH_METHOD_SETTING) {
+            extractor = extractor.with_auth_method(auth_method);
+        }
+        if let Some(max_bytes) = library.setting::<u64>(CACHE_LIMIT_SETTING) {
+            extractor = extractor.with_max_cache_size(max_bytes);
+            if let Some(cache) = library.cache() {
+                cache.set_size_limit(Some(max_bytes));
+            }
+        }
+        if let Some(cache) = library.cache() {
+            extractor = extractor.with_cache_index(Arc::clone(cache));
+        }
         // Keep disk cache - instant playback for previously played songs
         info!("Extractor initialized with disk caching");
 
+        let spans = SpanResolver::new()
+            .map_err(|e| warn!("Intro skipping unavailable: {e}"))
+            .ok();
+
         Self {
             engine: Arc::new(Mutex::new(engine)),
             extractor: Arc::new(extractor),
+            spans,
+            library,
+            playback: Arc::new(Mutex::new(Playback::default())),
+            crossfade: Arc::new(Mutex::new(config.crossfade())),
+            preloaded: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -47,6 +126,10 @@ impl AudioService {
     /// Uses instant path for cached tracks.
     pub async fn play_track(&self, track: &Track) {
         info!("Playing track: {} - {}", track.title, track.artist_name());
+        self.library.record_play(track);
+        // Loading a track drops the one preloaded by the engine
+        *self.preloaded.lock() = None;
+        let load = self.playback.lock().start_load(track);
 
         // Check if track is cached for instant playback
         if self.extractor.is_cached(&track.id) {
@@ -59,10 +142,17 @@ impl AudioService {
                         audio.data.len(),
                         audio.mime_type
                     );
+                    let span = self.playback_span(track).await;
+                    if !self.finish_load(load, track, false) {
+                        return;
+                    }
                     self.send_command(EngineCommand::LoadData(audio.data, Some(audio.mime_type)));
+                    self.set_span(span);
+                    self.set_skip_segments(audio.metadata.skip_segments);
                 }
                 Err(e) => {
                     error!("Failed to load cached audio for track {}: {}", track.id, e);
+                    self.playback.lock().fail_load(load);
                 }
             }
         } else {
@@ -71,11 +161,24 @@ impl AudioService {
             // Use streaming extraction for uncached tracks
             match self.extractor.extract_streaming(&track.id) {
                 Ok(mut extraction) => {
+                    // The download is already running while the span and
+                    // skip segments are looked up
+                    let (span, segments) = tokio::join!(
+                        self.playback_span(track),
+                        self.extractor.skip_segments(&track.id)
+                    );
+                    if !self.finish_load(load, track, true) {
+                        extraction.abort();
+                        return;
+                    }
+
                     // Create a channel to bridge extractor chunks to engine
                     let (engine_tx, engine_rx) = mpsc::channel(64);
 
                     // Send streaming command to engine
                     self.send_command(EngineCommand::LoadStreaming(engine_rx));
+                    self.set_span(span);
+                    self.set_skip_segments(segments);
 
                     // Spawn task to forward chunks from extractor to engine
                     // Since both use the same StreamChunk type, we can forward directly
@@ -94,11 +197,170 @@ impl AudioService {
                         "Failed to start streaming extraction for track {}: {}",
                         track.id, e
                     );
+                    self.playback.lock().fail_load(load);
                 }
             }
         }
     }
 
+    /// Check whether a load is still the latest, dropping it if another track
+    /// was picked while it extracted.
+    fn finish_load(&self, load: LoadId, track: &Track, streaming: bool) -> bool {
+        let latest = self.playback.lock().finish_load(load, streaming);
+        if !latest {
+            debug!("Dropping load of {}, skipped while loading", track.id);
+        }
+        latest
+    }
+
+    /// Get the part of a track to play, skipping music video intros and outros.
+    async fn playback_span(&self, track: &Track) -> PlaybackSpan {
+        match &self.spans {
+            Some(spans) => spans.resolve(track).await,
+            None => PlaybackSpan::default(),
+        }
+    }
+
+    /// Limit playback of the loaded track to a span.
+    fn set_span(&self, span: PlaybackSpan) {
+        if !span.is_full() {
+            self.send_command(EngineCommand::SetSpan(span));
+        }
+    }
+
+    /// Auto-skip segments of the loaded track.
+    fn set_skip_segments(&self, segments: Vec<SkipSegment>) {
+        if !segments.is_empty() {
+            self.send_command(EngineCommand::SetSkipSegments(segments));
+        }
+    }
+
+    /// Start caching a track in the background so it plays instantly later.
+    pub fn prefetch(&self, track: &Track) {
+        if let Err(e) = self.extractor.prefetch(&track.id) {
+            warn!("Failed to prefetch track {}: {e}", track.id);
+        }
+    }
+
+    /// Set the crossfade between tracks, zero to play them back to back.
+    pub fn set_crossfade(&self, crossfade: Duration) {
+        *self.crossfade.lock() = crossfade;
+        self.send_command(EngineCommand::SetCrossfade(crossfade));
+    }
+
+    /// Set the equalizer band gains.
+    pub fn set_eq(&self, settings: EqSettings) {
+        self.send_command(EngineCommand::SetEq(settings));
+    }
+
+    /// Set loudness normalization.
+    pub fn set_loudness(&self, settings: LoudnessSettings) {
+        self.send_command(EngineCommand::SetLoudness(settings));
+    }
+
+    /// Shift the pitch by semitones without changing speed, zero for none.
+    pub fn set_pitch(&self, semitones: f32) {
+        self.send_command(EngineCommand::SetPitch(semitones));
+    }
+
+    /// Set the balance, mono downmix and muted channels.
+    pub fn set_channels(&self, settings: ChannelSettings) {
+        self.send_command(EngineCommand::SetChannels(settings));
+    }
+
+    /// Get the volume (0.0 to 1.0).
+    pub fn volume(&self) -> f32 {
+        self.engine.lock().as_ref().map_or(0.0, AudioEngine::volume)
+    }
+
+    /// Set the volume (0.0 to 1.0) and save it.
+    pub fn set_volume(&self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        self.send_command(EngineCommand::SetVolume(volume));
+        self.library.set_setting(VOLUME_SETTING, &volume);
+    }
+
+    /// Preload a cached track in the engine to crossfade into once the
+    /// current one ends.
+    ///
+    /// Intros and skip segments of preloaded tracks are played.
+    pub async fn preload(&self, track: &Track) {
+        if self.crossfade.lock().is_zero() || !self.extractor.is_cached(&track.id) {
+            return;
+        }
+        {
+            let mut preloaded = self.preloaded.lock();
+            if preloaded.as_deref() == Some(track.id.as_str()) {
+                return;
+            }
+            *preloaded = Some(track.id.clone());
+        }
+
+        match self.extractor.extract(&track.id).await {
+            Ok(audio) => {
+                debug!("Preloading track {}", track.id);
+                self.send_command(EngineCommand::Preload(audio.data, Some(audio.mime_type)));
+            }
+            Err(e) => warn!("Failed to preload track {}: {e}", track.id),
+        }
+    }
+
+    /// Note that playback moved on to the preloaded track.
+    fn preloaded_track_started(&self, track: &Track) {
+        info!(
+            "Crossfaded into track: {} - {}",
+            track.title,
+            track.artist_name()
+        );
+        self.library.record_play(track);
+        *self.preloaded.lock() = None;
+    }
+
+    /// Check whether the network is reachable.
+    pub async fn is_online() -> bool {
+        let connect = tokio::net::TcpStream::connect(CONNECTIVITY_HOST);
+        matches!(
+            tokio::time::timeout(CONNECTIVITY_TIMEOUT, connect).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Build a mix continuing from `seed` out of cached tracks only.
+    pub fn offline_mix(&self, seed: &Track) -> Vec<Track> {
+        self.library
+            .instant_mix(seed, OFFLINE_MIX_SIZE, |id| self.extractor.is_cached(id))
+    }
+
+    /// Get the cached cover art of a track as a data URL, so it shows offline.
+    pub fn cached_artwork(&self, video_id: &str) -> Option<String> {
+        let image = std::fs::read(self.extractor.artwork_path(video_id)?).ok()?;
+        Some(format!(
+            "data:image/jpeg;base64,{}",
+            BASE64_STANDARD.encode(image)
+        ))
+    }
+
+    /// Finish offline downloads interrupted when the app last quit.
+    pub async fn resume_downloads(&self) {
+        if let Err(e) = self
+            .extractor
+            .resume_downloads(BatchOptions::default())
+            .await
+        {
+            warn!("Failed to resume downloads: {e}");
+        }
+    }
+
+    /// Seek to `position_secs` when `video_id` next loads, resuming a restored session.
+    pub fn resume_at(&self, video_id: &str, position_secs: f64) {
+        self.playback.lock().resume_at(video_id, position_secs);
+    }
+
+    /// Get the library service used to record plays.
+    pub const fn library(&self) -> &LibraryService {
+        &self.library
+    }
+
     /// Send a command to the audio engine.
     pub fn send_command(&self, command: EngineCommand) {
         if let Some(engine) = self.engine.lock().as_ref() {
@@ -120,10 +382,33 @@ impl AudioService {
         self.send_command(EngineCommand::Pause);
     }
 
+    /// Get the most recent track transitions, oldest first.
+    pub fn transitions(&self) -> Vec<TransitionRecord> {
+        self.engine
+            .lock()
+            .as_ref()
+            .map_or_else(Vec::new, AudioEngine::transitions)
+    }
+
+    /// Get the measured download throughput, which picks the automatic stream quality.
+    pub fn bandwidth_estimate(&self) -> BandwidthEstimate {
+        self.extractor.bandwidth_estimate()
+    }
+
     /// Try to receive an event from the audio engine.
     pub fn try_recv_event(&self) -> Option<EngineEvent> {
         self.engine.lock().as_ref()?.try_recv_event()
     }
+
+    /// Handle the engine's pending events, getting what to do about them and
+    /// about finished loads.
+    fn poll_events(&self, queue: &mut impl PlayQueue) -> Vec<Reaction> {
+        let mut playback = self.playback.lock();
+        while let Some(event) = self.try_recv_event() {
+            playback.handle(event, queue);
+        }
+        playback.take_reactions()
+    }
 }
 
 impl Default for AudioService {
@@ -134,7 +419,26 @@ impl Default for AudioService {
 
 /// Hook to initialize and use the audio service.
 pub fn use_audio_service() -> Signal<AudioService> {
-    use_context_provider(|| Signal::new(AudioService::new()))
+    let audio = use_context_provider(|| Signal::new(AudioService::new()));
+    use_future(move || async move {
+        let service = audio.peek().clone();
+        service.resume_downloads().await;
+    });
+    audio
+}
+
+/// The app's queue, as playback sees it.
+impl PlayQueue for Signal<Queue> {
+    fn next(&self) -> Option<Track> {
+        self.peek()
+            .upcoming(1)
+            .first()
+            .map(|item| item.track.clone())
+    }
+
+    fn advance(&mut self) -> Option<Track> {
+        self.write().advance().map(|item| item.track.clone())
+    }
 }
 
 /// Hook to sync audio engine events with app state.
@@ -144,6 +448,8 @@ pub fn use_audio_event_sync(audio: Signal<AudioService>, app_state: AppState) {
     let mut player_status = app_state.player.status;
     let mut player_position = app_state.player.position;
     let mut player_duration = app_state.player.duration;
+    let mut player_download_progress = app_state.player.download_progress;
+    let mut player_spectrum = app_state.player.spectrum;
     let mut queue = app_state.queue;
     let mut player_current_track = app_state.player.current_track;
 
@@ -151,63 +457,39 @@ pub fn use_audio_event_sync(audio: Signal<AudioService>, app_state: AppState) {
         loop {
             // Poll for events from the audio engine
             let service = audio.read();
-            while let Some(event) = service.try_recv_event() {
-                match event {
-                    EngineEvent::StateChanged(state) => {
-                        debug!("Playback state changed: {:?}", state);
-                        let status = match state {
-                            EnginePlaybackState::Stopped => PlaybackStatus::Stopped,
-                            EnginePlaybackState::Playing => PlaybackStatus::Playing,
-                            EnginePlaybackState::Paused => PlaybackStatus::Paused,
-                            EnginePlaybackState::Buffering => PlaybackStatus::Buffering,
-                        };
-                        *player_status.write() = status;
+            for reaction in service.poll_events(&mut queue) {
+                match reaction {
+                    Reaction::Status(status) => *player_status.write() = status,
+                    Reaction::Position(pos) => *player_position.write() = pos,
+                    Reaction::Duration(dur) => *player_duration.write() = dur,
+                    Reaction::DownloadProgress(percent) => {
+                        *player_download_progress.write() = percent;
                     }
-                    EngineEvent::PositionUpdate(pos) => {
-                        *player_position.write() = pos;
+                    Reaction::Spectrum(bands) => *player_spectrum.write() = bands,
+                    Reaction::CurrentTrack(track) => *player_current_track.write() = Some(track),
+                    Reaction::Play => service.play(),
+                    Reaction::Stop => service.send_command(EngineCommand::Stop),
+                    Reaction::Seek(pos) => service.send_command(EngineCommand::Seek(pos)),
+                    Reaction::Load(track) => {
+                        let audio_clone = audio;
+                        spawn(async move {
+                            audio_clone.read().play_track(&track).await;
+                        });
                     }
-                    EngineEvent::DurationUpdate(dur) => {
-                        *player_duration.write() = dur;
+                    Reaction::Prefetch(track) => service.prefetch(&track),
+                    Reaction::Preload(track) => {
+                        let service = AudioService::clone(&service);
+                        spawn(async move { service.preload(&track).await });
                     }
-                    EngineEvent::BufferingProgress(progress) => {
-                        debug!("Buffering: {:.0}%", progress * 100.0);
-                    }
-                    EngineEvent::TrackLoaded => {
-                        debug!("Track loaded, starting playback");
-                        // Auto-play when track is loaded
-                        service.play();
-                    }
-                    EngineEvent::PlaybackFinished => {
-                        info!("Playback finished, advancing to next track");
-                        // Auto-advance to next track
-                        if let Some(item) = queue.write().advance() {
-                            let track = item.track.clone();
-                            *player_current_track.write() = Some(track.clone());
-                            *player_status.write() = PlaybackStatus::Buffering;
-                            // Play the next track
-                            let audio_clone = audio;
-                            spawn(async move {
-                                audio_clone.read().play_track(&track).await;
-                            });
-                        } else {
-                            *player_status.write() = PlaybackStatus::Stopped;
-                        }
-                    }
-                    EngineEvent::Error(err) => {
-                        error!("Playback error: {err}");
-                    }
-                    EngineEvent::DownloadProgress(bytes) => {
-                        debug!("Download progress: {} KB", bytes / 1024);
-                    }
-                    EngineEvent::StreamBuffering => {
-                        debug!("Stream rebuffering");
-                        *player_status.write() = PlaybackStatus::Buffering;
-                    }
-                    EngineEvent::StreamBufferHealthy => {
-                        debug!("Stream buffer healthy");
-                    }
-                    EngineEvent::StreamDownloadComplete => {
-                        info!("Stream download complete, seeking now enabled");
+                    Reaction::Crossfaded(track) => service.preloaded_track_started(&track),
+                    Reaction::QueueEnded(seed) => {
+                        spawn(play_offline_mix(
+                            AudioService::clone(&service),
+                            seed,
+                            queue,
+                            player_current_track,
+                            player_status,
+                        ));
                     }
                 }
             }
@@ -218,3 +500,39 @@ pub fn use_audio_event_sync(audio: Signal<AudioService>, app_state: AppState) {
         }
     });
 }
+
+/// Keep playing from the cache when the queue ends while offline.
+///
+/// Online there is nothing to do here: playback stops at the end of the queue.
+async fn play_offline_mix(
+    service: AudioService,
+    seed: Track,
+    mut queue: Signal<Queue>,
+    mut current_track: Signal<Option<Track>>,
+    mut status: Signal<PlaybackStatus>,
+) {
+    if AudioService::is_online().await {
+        return;
+    }
+
+    let mix = service.offline_mix(&seed);
+    if mix.is_empty() {
+        info!("Offline with no cached tracks to continue with");
+        return;
+    }
+    info!("Offline: continuing with {} cached tracks", mix.len());
+
+    let next = {
+        let mut queue = queue.write();
+        for track in mix {
+            queue.push(QueueItem::new(track, QueueSource::AutoPlay));
+        }
+        queue.advance().map(|item| item.track.clone())
+    };
+    let Some(track) = next else {
+        return;
+    };
+    *current_track.write() = Some(track.clone());
+    *status.write() = PlaybackStatus::Buffering;
+    service.play_track(&track).await;
+}
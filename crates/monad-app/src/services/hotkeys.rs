@@ -0,0 +1,135 @@
+//! Global hotkeys registered with the OS through the desktop runtime.
+
+use std::str::FromStr;
+
+use dioxus::desktop::window;
+use dioxus::prelude::*;
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::HotKeyState;
+use tracing::{debug, warn};
+
+use crate::services::AudioService;
+use crate::state::hotkeys::{HotkeyAction, HotkeyState};
+use crate::state::ipod::{IPodScreen, IPodState, LYRICS_OFFSET_STEP_MS};
+use crate::state::player::PlaybackStatus;
+use crate::state::AppState;
+
+/// Register the configured global hotkeys, re-registering when they change.
+pub fn use_global_hotkeys() {
+    let app_state = use_context::<AppState>();
+    let ipod_state = use_context::<IPodState>();
+    let audio = use_context::<Signal<AudioService>>();
+    let hotkeys = use_context::<HotkeyState>();
+
+    // A callback runs in the component's scope, so it can spawn playback tasks
+    let on_hotkey = use_callback(move |action: HotkeyAction| {
+        run_action(action, app_state.clone(), ipod_state.clone(), audio);
+    });
+
+    use_effect(move || {
+        let config = hotkeys.config.read().clone();
+        let recording = hotkeys.recording.read().is_some();
+
+        let desktop = window();
+        desktop.remove_all_shortcuts();
+
+        // Release everything while recording so the new combination reaches the window
+        if !config.enabled || recording {
+            return;
+        }
+
+        for binding in config.bindings {
+            let hotkey = match HotKey::from_str(&binding.accelerator) {
+                Ok(hotkey) => hotkey,
+                Err(e) => {
+                    warn!("Invalid hotkey {:?}: {e}", binding.accelerator);
+                    continue;
+                }
+            };
+
+            let action = binding.action;
+            let result = desktop.create_shortcut(hotkey, move |state| {
+                if state == HotKeyState::Pressed {
+                    on_hotkey.call(action);
+                }
+            });
+            match result {
+                Ok(_) => debug!("Registered {} for {}", binding.accelerator, action.name()),
+                Err(e) => warn!(
+                    "Failed to register {} for {}: {e:?}",
+                    binding.accelerator,
+                    action.name()
+                ),
+            }
+        }
+    });
+}
+
+/// Perform a hotkey action.
+fn run_action(
+    action: HotkeyAction,
+    mut app_state: AppState,
+    mut ipod_state: IPodState,
+    audio: Signal<AudioService>,
+) {
+    match action {
+        HotkeyAction::PlayPause => {
+            let status = *app_state.player.status.peek();
+            match status {
+                PlaybackStatus::Playing => {
+                    audio.read().pause();
+                    app_state.player.pause();
+                }
+                PlaybackStatus::Paused => {
+                    audio.read().play();
+                    app_state.player.play();
+                }
+                _ => play_current(&mut app_state, audio),
+            }
+        }
+        HotkeyAction::NextTrack => {
+            app_state.next_track();
+            play_current(&mut app_state, audio);
+        }
+        HotkeyAction::PreviousTrack => {
+            app_state.previous_track();
+            play_current(&mut app_state, audio);
+        }
+        HotkeyAction::ToggleLyrics => {
+            if *ipod_state.screen.peek() != IPodScreen::NowPlaying {
+                ipod_state.navigate(IPodScreen::NowPlaying);
+            }
+            let show = *ipod_state.show_lyrics.peek();
+            ipod_state.show_lyrics.set(!show);
+        }
+        HotkeyAction::LyricsEarlier | HotkeyAction::LyricsLater => {
+            let Some(track_id) = app_state
+                .player
+                .current_track
+                .peek()
+                .as_ref()
+                .map(|t| t.id.clone())
+            else {
+                return;
+            };
+            let delta_ms = if action == HotkeyAction::LyricsEarlier {
+                -LYRICS_OFFSET_STEP_MS
+            } else {
+                LYRICS_OFFSET_STEP_MS
+            };
+            let library = audio.read().library().clone();
+            ipod_state.adjust_lyrics_offset(&track_id, delta_ms, &library);
+        }
+    }
+}
+
+/// Start playing the current track.
+fn play_current(app_state: &mut AppState, audio: Signal<AudioService>) {
+    let Some(track) = app_state.player.current_track.peek().clone() else {
+        return;
+    };
+    *app_state.player.status.write() = PlaybackStatus::Buffering;
+    spawn(async move {
+        audio.read().play_track(&track).await;
+    });
+}
@@ -0,0 +1,305 @@
+//! Library service backed by the local cache database.
+
+use std::fs;
+use std::sync::Arc;
+
+use chrono::{Local, Weekday};
+use directories::ProjectDirs;
+use monad_cache::{
+    last_full_week, CacheManager, CollectionDiff, OfflineResult, PlayRecord, SavedQueue,
+    WeeklyReport, OFFLINE_SEARCH_LIMIT,
+};
+use monad_core::{Album, Playlist, Queue, Track};
+use monad_lyrics::Lyrics;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::state::audio::AUDIO_SETTING;
+use crate::state::hotkeys::HOTKEYS_SETTING;
+use crate::state::ipod::DISPLAY_SETTING;
+use crate::state::report::REPORT_SETTING;
+
+/// Config files from before settings were kept in the library, by the
+/// setting each is imported as.
+const CONFIG_FILES: [(&str, &str); 4] = [
+    ("audio.json", AUDIO_SETTING),
+    ("hotkeys.json", HOTKEYS_SETTING),
+    ("display.json", DISPLAY_SETTING),
+    ("report.json", REPORT_SETTING),
+];
+
+/// Library service for play history and play counts.
+#[derive(Clone)]
+pub struct LibraryService {
+    cache: Option<Arc<CacheManager>>,
+}
+
+impl LibraryService {
+    /// Create a new library service.
+    pub fn new() -> Self {
+        let cache = match CacheManager::new() {
+            Ok(cache) => Some(Arc::new(cache)),
+            Err(e) => {
+                error!("Failed to open library database: {e}");
+                None
+            }
+        };
+
+        let library = Self { cache };
+        library.import_config_files();
+        library
+    }
+
+    /// Move settings from the old config files into the library, once.
+    ///
+    /// A file is only removed after it's imported, and never overwrites a
+    /// setting saved since.
+    fn import_config_files(&self) {
+        let (Some(cache), Some(dirs)) = (&self.cache, ProjectDirs::from("", "", "monad")) else {
+            return;
+        };
+        for (file, key) in CONFIG_FILES {
+            let path = dirs.config_dir().join(file);
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            match serde_json::from_str::<serde_json::Value>(&contents) {
+                Ok(value) if self.setting::<serde_json::Value>(key).is_none() => {
+                    if let Err(e) = cache.set_setting(key, &value) {
+                        warn!("Failed to import {file}: {e}");
+                        continue;
+                    }
+                    info!("Imported {file} into settings");
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Dropping invalid {file}: {e}"),
+            }
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to remove {file}: {e}");
+            }
+        }
+    }
+
+    /// Get the cache database, if it could be opened.
+    pub const fn cache(&self) -> Option<&Arc<CacheManager>> {
+        self.cache.as_ref()
+    }
+
+    /// Record a play of a track.
+    pub fn record_play(&self, track: &Track) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.record_play(track) {
+                warn!("Failed to record play for {}: {e}", track.id);
+            }
+        }
+    }
+
+    /// Get the number of plays for an artist.
+    pub fn artist_play_count(&self, artist: &str) -> u64 {
+        self.cache
+            .as_ref()
+            .map_or(0, |cache| cache.artist_play_count(artist))
+    }
+
+    /// Get the number of plays for an album by an artist.
+    pub fn album_play_count(&self, artist: &str, album: &str) -> u64 {
+        self.cache
+            .as_ref()
+            .map_or(0, |cache| cache.album_play_count(artist, album))
+    }
+
+    /// Build an offline mix continuing from `seed` out of available tracks.
+    pub fn instant_mix(
+        &self,
+        seed: &Track,
+        limit: usize,
+        is_available: impl Fn(&str) -> bool,
+    ) -> Vec<Track> {
+        let Some(cache) = &self.cache else {
+            return Vec::new();
+        };
+        cache
+            .instant_mix(seed, limit, is_available)
+            .unwrap_or_else(|e| {
+                warn!("Failed to build instant mix: {e}");
+                Vec::new()
+            })
+    }
+
+    /// Record a visit to an album or playlist, returning the changes since the last one.
+    pub fn record_visit(&self, collection_id: &str, tracks: &[Track]) -> CollectionDiff {
+        let Some(cache) = &self.cache else {
+            return CollectionDiff::default();
+        };
+        let track_ids: Vec<String> = tracks.iter().map(|t| t.id.clone()).collect();
+        cache
+            .record_visit(collection_id, &track_ids)
+            .unwrap_or_else(|e| {
+                warn!("Failed to record visit to {collection_id}: {e}");
+                CollectionDiff::default()
+            })
+    }
+
+    /// Get how many tracks a visited collection gained, from its current track count.
+    pub fn new_track_count(&self, collection_id: &str, track_count: Option<u32>) -> usize {
+        let (Some(cache), Some(track_count)) = (&self.cache, track_count) else {
+            return 0;
+        };
+        match cache.last_visit(collection_id) {
+            Ok(Some(visit)) => (track_count as usize).saturating_sub(visit.track_ids.len()),
+            Ok(None) => 0,
+            Err(e) => {
+                warn!("Failed to load visit to {collection_id}: {e}");
+                0
+            }
+        }
+    }
+
+    /// Make an album and its tracks findable by offline search.
+    pub fn index_album(&self, album: &Album) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.index_album(album) {
+                warn!("{e}");
+            }
+        }
+    }
+
+    /// Make a playlist and its tracks findable by offline search.
+    pub fn index_playlist(&self, playlist: &Playlist) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.index_playlist(playlist) {
+                warn!("{e}");
+            }
+        }
+    }
+
+    /// Search played and opened tracks, albums and playlists without the network.
+    pub fn search_offline(&self, query: &str) -> Vec<OfflineResult> {
+        let Some(cache) = &self.cache else {
+            return Vec::new();
+        };
+        cache
+            .search_offline(query, OFFLINE_SEARCH_LIMIT)
+            .unwrap_or_else(|e| {
+                warn!("{e}");
+                Vec::new()
+            })
+    }
+
+    /// Get the most recent plays, newest first.
+    pub fn recent_plays(&self, limit: usize) -> Vec<PlayRecord> {
+        let Some(cache) = &self.cache else {
+            return Vec::new();
+        };
+        cache.recent_plays(limit).unwrap_or_else(|e| {
+            warn!("Failed to load recent plays: {e}");
+            Vec::new()
+        })
+    }
+
+    /// Save the queue and playback position for the next launch.
+    pub fn save_queue(&self, queue: &Queue, position_secs: f64) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.save_queue(queue, position_secs) {
+                warn!("{e}");
+            }
+        }
+    }
+
+    /// Load the queue saved by the last session, if any.
+    pub fn load_queue(&self) -> Option<SavedQueue> {
+        let cache = self.cache.as_ref()?;
+        cache
+            .load_queue()
+            .map_err(|e| warn!("Failed to load saved queue: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Get a saved setting, if it was ever set.
+    pub fn setting<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let cache = self.cache.as_ref()?;
+        cache
+            .setting(key)
+            .map_err(|e| warn!("Ignoring invalid setting {key}: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Save a setting.
+    pub fn set_setting<T: Serialize + ?Sized>(&self, key: &str, value: &T) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.set_setting(key, value) {
+                warn!("{e}");
+            }
+        }
+    }
+
+    /// Get the cached lyrics of a track.
+    pub fn cached_lyrics(&self, track: &Track) -> Option<Lyrics> {
+        self.cache.as_ref()?.cached_lyrics(track)
+    }
+
+    /// Cache the lyrics of a track.
+    pub fn cache_lyrics(&self, track: &Track, lyrics: &Lyrics) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.cache_lyrics(track, lyrics) {
+                warn!("Failed to cache lyrics for {}: {e}", track.id);
+            }
+        }
+    }
+
+    /// Get the lyrics timing offset of a track in milliseconds.
+    pub fn lyrics_offset_ms(&self, video_id: &str) -> i64 {
+        self.cache
+            .as_ref()
+            .map_or(0, |cache| cache.lyrics_offset_ms(video_id))
+    }
+
+    /// Save the lyrics timing offset of a track in milliseconds.
+    pub fn set_lyrics_offset_ms(&self, video_id: &str, offset_ms: i64) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.set_lyrics_offset_ms(video_id, offset_ms) {
+                warn!("Failed to save lyrics offset for {video_id}: {e}");
+            }
+        }
+    }
+
+    /// Get last week's listening report, if anything was played.
+    pub fn last_week_report(&self, week_start: Weekday) -> Option<WeeklyReport> {
+        let cache = self.cache.as_ref()?;
+        let (start, _) = last_full_week(Local::now().date_naive(), week_start);
+        cache
+            .weekly_report(start)
+            .map_err(|e| warn!("Failed to build weekly report: {e}"))
+            .ok()
+            .filter(|report| !report.is_empty())
+    }
+
+    /// Get last week's listening report if it has not been shown yet.
+    pub fn due_weekly_report(&self, week_start: Weekday) -> Option<WeeklyReport> {
+        let cache = self.cache.as_ref()?;
+        let today = Local::now().date_naive();
+        cache
+            .due_weekly_report(today, week_start)
+            .map_err(|e| warn!("Failed to check weekly report: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Remember that a report was shown.
+    pub fn mark_report_shown(&self, report: &WeeklyReport) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.mark_report_shown(report) {
+                warn!("Failed to save weekly report state: {e}");
+            }
+        }
+    }
+}
+
+impl Default for LibraryService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,8 +2,19 @@
 //!
 //! This module connects the UI to the backend services:
 //! - Audio engine for playback
+//! - Playback orchestration between the queue, loads and the engine
 //! - Stream extractor for getting playable URLs
+//! - Local library (play history and play counts)
+//! - Global hotkeys
+//! - Session restore (queue and playback position)
+//! - Widget snapshots for external now-playing displays
 
 pub mod audio;
+pub mod hotkeys;
+pub mod library;
+pub mod playback;
+pub mod session;
+pub mod widgets;
 
 pub use audio::AudioService;
+pub use library::LibraryService;
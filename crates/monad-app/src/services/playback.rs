@@ -0,0 +1,692 @@
+//! Playback orchestration between the queue, track loads and the engine.
+//!
+//! [`Playback`] decides how to react to loads finishing and to engine events,
+//! without the UI or any I/O. [`AudioService`](super::AudioService) and the
+//! event sync hook carry out its reactions, and the simulation in its tests
+//! scripts extraction results, engine events and the engine's clock instead.
+
+use monad_audio::{EngineEvent, PlaybackState};
+use monad_core::{Queue, Track};
+use tracing::{debug, error, info};
+
+use crate::state::player::PlaybackStatus;
+
+/// Preload the next track this many seconds before the current one ends.
+const PRELOAD_LEAD_SECS: f64 = 30.0;
+
+/// Identifies a load of a track, so one overtaken by a newer load is dropped.
+pub type LoadId = u64;
+
+/// The play queue, as playback sees it.
+pub trait PlayQueue {
+    /// Get the track after the current one.
+    fn next(&self) -> Option<Track>;
+
+    /// Move on to the next track and get it.
+    fn advance(&mut self) -> Option<Track>;
+}
+
+impl PlayQueue for Queue {
+    fn next(&self) -> Option<Track> {
+        self.upcoming(1).first().map(|item| item.track.clone())
+    }
+
+    fn advance(&mut self) -> Option<Track> {
+        Self::advance(self).map(|item| item.track.clone())
+    }
+}
+
+/// Something to do in response to a load or engine event.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Reaction {
+    /// Show a playback status.
+    Status(PlaybackStatus),
+    /// Show the position in seconds.
+    Position(f64),
+    /// Show the duration in seconds.
+    Duration(f64),
+    /// Show the streaming download progress, 0.0 to 100.0 (if known).
+    DownloadProgress(Option<f32>),
+    /// Show the levels of the frequency bands playing.
+    Spectrum(Vec<f32>),
+    /// Show the track the queue moved on to.
+    CurrentTrack(Track),
+    /// Start playback in the engine.
+    Play,
+    /// Stop playback in the engine.
+    Stop,
+    /// Seek the engine to a position in seconds.
+    Seek(f64),
+    /// Load and play a track.
+    Load(Track),
+    /// Cache a track in the background.
+    Prefetch(Track),
+    /// Preload a track in the engine to crossfade into.
+    Preload(Track),
+    /// The engine crossfaded into the preloaded track.
+    Crossfaded(Track),
+    /// The queue ended after this track.
+    QueueEnded(Track),
+}
+
+/// Playback of the current track, from its load to its end.
+///
+/// Engine events are taken to be about the track of the latest load once it
+/// is handed to the engine. Until then they are about the track it replaces,
+/// which is still playing, so they are ignored.
+#[derive(Default)]
+pub struct Playback {
+    /// Track of the latest load.
+    track: Option<Track>,
+    /// Latest load started.
+    requested: LoadId,
+    /// Latest load handed to the engine, or that failed.
+    settled: LoadId,
+    /// Whether the latest load is streamed rather than played from the cache.
+    streaming: bool,
+    /// Whether the latest stream finished downloading.
+    download_complete: bool,
+    /// Duration of the track playing, once known.
+    duration: Option<f64>,
+    /// Track and position to seek to once it loads, when resuming a session.
+    resume_at: Option<(String, f64)>,
+    /// Position to seek to once the stream playing can seek.
+    pending_seek: Option<f64>,
+    /// Reactions not yet taken.
+    reactions: Vec<Reaction>,
+}
+
+impl Playback {
+    /// Seek to `position_secs` when `video_id` next loads.
+    pub fn resume_at(&mut self, video_id: &str, position_secs: f64) {
+        self.resume_at = Some((video_id.to_string(), position_secs));
+    }
+
+    /// Start loading a track, overtaking any load still in progress.
+    pub fn start_load(&mut self, track: &Track) -> LoadId {
+        self.requested += 1;
+        self.track = Some(track.clone());
+        self.requested
+    }
+
+    /// Check whether a load is still the latest, and if so take it as handed
+    /// to the engine.
+    ///
+    /// A load overtaken while extracting is dropped, so a skipped track never
+    /// replaces the one skipped to.
+    pub fn finish_load(&mut self, load: LoadId, streaming: bool) -> bool {
+        if load != self.requested {
+            return false;
+        }
+        self.settled = load;
+        self.streaming = streaming;
+        self.download_complete = false;
+        self.duration = None;
+        self.pending_seek = None;
+        true
+    }
+
+    /// Note that a load failed, stopping playback if it was the latest.
+    pub fn fail_load(&mut self, load: LoadId) {
+        if load != self.requested {
+            return;
+        }
+        self.settled = load;
+        self.reactions
+            .extend([Reaction::Stop, Reaction::Status(PlaybackStatus::Stopped)]);
+    }
+
+    /// Check whether a load is on its way to the engine.
+    pub const fn is_loading(&self) -> bool {
+        self.requested != self.settled
+    }
+
+    /// React to an event from the engine.
+    pub fn handle(&mut self, event: EngineEvent, queue: &mut impl PlayQueue) {
+        if self.is_loading() && is_track_event(&event) {
+            return;
+        }
+        match event {
+            // Still buffering while waiting to seek, though the engine stopped
+            EngineEvent::StateChanged(_) if self.pending_seek.is_some() => {}
+            EngineEvent::StateChanged(state) => {
+                debug!("Playback state changed: {:?}", state);
+                let status = match state {
+                    PlaybackState::Stopped => PlaybackStatus::Stopped,
+                    PlaybackState::Playing => PlaybackStatus::Playing,
+                    PlaybackState::Paused => PlaybackStatus::Paused,
+                    PlaybackState::Buffering => PlaybackStatus::Buffering,
+                };
+                self.reactions.push(Reaction::Status(status));
+            }
+            EngineEvent::PositionUpdate(pos) => {
+                self.reactions.push(Reaction::Position(pos));
+                // The next track is usually cached by the time this one nears its end
+                if self
+                    .duration
+                    .is_some_and(|dur| dur - pos < PRELOAD_LEAD_SECS)
+                {
+                    if let Some(next) = queue.next() {
+                        self.reactions.push(Reaction::Preload(next));
+                    }
+                }
+            }
+            EngineEvent::DurationUpdate(dur) => {
+                self.duration = Some(dur);
+                self.reactions.push(Reaction::Duration(dur));
+            }
+            EngineEvent::BufferingProgress(progress) => {
+                debug!("Buffering: {:.0}%", progress * 100.0);
+            }
+            EngineEvent::TrackLoaded => {
+                debug!("Track loaded, starting playback");
+                let resume = self.take_resume_position();
+                if self.streaming && !self.download_complete && resume.is_some() {
+                    // Streams can't seek until downloaded, so wait rather
+                    // than play from the start
+                    self.pending_seek = resume;
+                } else {
+                    self.start(resume, queue);
+                }
+            }
+            EngineEvent::PlaybackFinished => {
+                info!("Playback finished, advancing to next track");
+                self.advance(queue);
+            }
+            EngineEvent::NextTrackStarted => {
+                if let Some(track) = queue.advance() {
+                    self.track = Some(track.clone());
+                    self.reactions.extend([
+                        Reaction::Crossfaded(track.clone()),
+                        Reaction::CurrentTrack(track),
+                        Reaction::DownloadProgress(None),
+                        Reaction::Position(0.0),
+                    ]);
+                }
+                if let Some(next) = queue.next() {
+                    self.reactions.push(Reaction::Prefetch(next));
+                }
+            }
+            EngineEvent::Error(err) => {
+                error!("Playback error: {err}");
+            }
+            EngineEvent::DownloadProgress(progress) => {
+                let percent = progress.percent();
+                debug!(
+                    "Download progress: {} KB ({:?}%)",
+                    progress.completed / 1024,
+                    percent
+                );
+                self.reactions.push(Reaction::DownloadProgress(percent));
+            }
+            EngineEvent::StreamBuffering => {
+                debug!("Stream rebuffering");
+                self.reactions
+                    .push(Reaction::Status(PlaybackStatus::Buffering));
+            }
+            EngineEvent::StreamBufferHealthy => {
+                debug!("Stream buffer healthy");
+            }
+            EngineEvent::StreamDownloadComplete => {
+                info!("Stream download complete, seeking now enabled");
+                self.download_complete = true;
+                if let Some(position) = self.pending_seek.take() {
+                    self.start(Some(position), queue);
+                }
+            }
+            EngineEvent::DeviceChanged(device) => {
+                info!("Audio output moved to {device}");
+            }
+            EngineEvent::Spectrum(bands) => {
+                self.reactions.push(Reaction::Spectrum(bands));
+            }
+            EngineEvent::NearEnd(_)
+            | EngineEvent::Levels(_)
+            | EngineEvent::CommandCompleted { .. } => {}
+        }
+    }
+
+    /// Take the reactions so far, in order.
+    pub fn take_reactions(&mut self) -> Vec<Reaction> {
+        std::mem::take(&mut self.reactions)
+    }
+
+    /// Take the position to resume the loaded track at, if one was set for it.
+    fn take_resume_position(&mut self) -> Option<f64> {
+        let (video_id, position_secs) = self.resume_at.take()?;
+        let track = self.track.as_ref()?;
+        (track.id == video_id).then_some(position_secs)
+    }
+
+    /// Start playing the loaded track, from `position` if given.
+    ///
+    /// A position past the end finishes the track instead.
+    fn start(&mut self, position: Option<f64>, queue: &mut impl PlayQueue) {
+        if let Some(position) = position {
+            if self.duration.is_some_and(|dur| position >= dur) {
+                info!("Resume position {position:.0}s is past the end, advancing");
+                self.advance(queue);
+                return;
+            }
+            if position > 0.0 {
+                self.reactions.push(Reaction::Seek(position));
+            }
+        }
+        self.reactions.push(Reaction::Play);
+        // Cache the next track while this one plays
+        if let Some(next) = queue.next() {
+            self.reactions.push(Reaction::Prefetch(next));
+        }
+    }
+
+    /// Move on to the next track in the queue, or end it.
+    fn advance(&mut self, queue: &mut impl PlayQueue) {
+        if let Some(track) = queue.advance() {
+            // Events until the next track loads are about the finished one
+            self.start_load(&track);
+            self.reactions.extend([
+                Reaction::CurrentTrack(track.clone()),
+                Reaction::DownloadProgress(None),
+                Reaction::Status(PlaybackStatus::Buffering),
+                Reaction::Load(track),
+            ]);
+        } else {
+            self.reactions
+                .push(Reaction::Status(PlaybackStatus::Stopped));
+            if let Some(seed) = self.track.clone() {
+                self.reactions.push(Reaction::QueueEnded(seed));
+            }
+        }
+    }
+}
+
+/// Check whether an event is about the track playing, rather than the output.
+const fn is_track_event(event: &EngineEvent) -> bool {
+    !matches!(
+        event,
+        EngineEvent::Error(_)
+            | EngineEvent::DeviceChanged(_)
+            | EngineEvent::Levels(_)
+            | EngineEvent::CommandCompleted { .. }
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used, clippy::float_cmp)]
+mod tests {
+    use super::*;
+    use monad_core::QueueItem;
+
+    /// Length of every simulated track, in seconds.
+    const DURATION: f64 = 200.0;
+
+    /// Engine playing on a virtual clock, which the test advances.
+    #[derive(Default)]
+    struct SimEngine {
+        /// Whether the loaded track is streamed, and if it finished downloading.
+        stream: Option<bool>,
+        playing: bool,
+        position: f64,
+        /// Events not yet delivered.
+        events: Vec<EngineEvent>,
+        /// Commands received, as `Load(id)`, `Play`, `Stop` and `Seek(secs)`.
+        commands: Vec<String>,
+    }
+
+    impl SimEngine {
+        fn load(&mut self, id: &str, streaming: bool) {
+            self.commands.push(format!("Load({id})"));
+            self.stream = streaming.then_some(false);
+            self.playing = false;
+            self.position = 0.0;
+            if streaming {
+                self.events
+                    .push(EngineEvent::StateChanged(PlaybackState::Buffering));
+            } else {
+                self.events.extend([
+                    EngineEvent::DurationUpdate(DURATION),
+                    EngineEvent::TrackLoaded,
+                    EngineEvent::StateChanged(PlaybackState::Stopped),
+                ]);
+            }
+        }
+
+        fn seek(&mut self, position: f64) {
+            self.commands.push(format!("Seek({position})"));
+            if self.stream == Some(false) {
+                self.events.push(EngineEvent::Error(
+                    "Seeking disabled during download".into(),
+                ));
+                return;
+            }
+            self.position = position;
+            self.events.push(EngineEvent::PositionUpdate(position));
+        }
+
+        fn set_playing(&mut self, playing: bool) {
+            self.commands
+                .push(if playing { "Play" } else { "Stop" }.to_string());
+            self.playing = playing;
+            let state = if playing {
+                PlaybackState::Playing
+            } else {
+                PlaybackState::Stopped
+            };
+            self.events.push(EngineEvent::StateChanged(state));
+        }
+    }
+
+    /// Playback wired to a queue, a scripted extractor and a simulated engine,
+    /// showing what the UI would.
+    struct Sim {
+        playback: Playback,
+        queue: Queue,
+        engine: SimEngine,
+        /// Loads still extracting, with the ID of their track.
+        extracting: Vec<(String, LoadId)>,
+        status: PlaybackStatus,
+        current: Option<String>,
+        position: f64,
+        /// Other reactions, as `Prefetch(id)`, `Preload(id)` and `QueueEnded(id)`,
+        /// each noted once.
+        effects: Vec<String>,
+    }
+
+    impl Sim {
+        fn new(ids: &[&str]) -> Self {
+            let mut queue = Queue::new();
+            let items = ids
+                .iter()
+                .map(|id| QueueItem::from_track(Track::new(*id, *id)))
+                .collect();
+            queue.set(items, 0);
+            Self {
+                playback: Playback::default(),
+                queue,
+                engine: SimEngine::default(),
+                extracting: Vec::new(),
+                status: PlaybackStatus::Stopped,
+                current: None,
+                position: 0.0,
+                effects: Vec::new(),
+            }
+        }
+
+        /// Pick the current track of the queue, as the click wheel does.
+        fn play_current(&mut self) {
+            let track = self.queue.current().map(|item| item.track.clone());
+            if let Some(track) = track {
+                self.status = PlaybackStatus::Buffering;
+                self.load(&track);
+            }
+        }
+
+        /// Skip to the next track, as the click wheel does.
+        fn skip(&mut self) {
+            if Queue::advance(&mut self.queue).is_some() {
+                self.play_current();
+            }
+        }
+
+        /// Start loading a track, as `AudioService::play_track` does.
+        fn load(&mut self, track: &Track) {
+            self.current = Some(track.id.clone());
+            self.position = 0.0;
+            let load = self.playback.start_load(track);
+            self.extracting.push((track.id.clone(), load));
+        }
+
+        fn take_load(&mut self, id: &str) -> LoadId {
+            let index = self
+                .extracting
+                .iter()
+                .position(|(extracting, _)| extracting == id)
+                .expect("track is not extracting");
+            self.extracting.remove(index).1
+        }
+
+        /// Finish extracting a track from the cache or as a stream.
+        fn extracted(&mut self, id: &str, streaming: bool) {
+            let load = self.take_load(id);
+            if self.playback.finish_load(load, streaming) {
+                self.engine.load(id, streaming);
+            }
+            self.deliver();
+        }
+
+        /// Fail to extract a track.
+        fn extraction_failed(&mut self, id: &str) {
+            let load = self.take_load(id);
+            self.playback.fail_load(load);
+            self.deliver();
+        }
+
+        /// Buffer enough of the stream loaded to start playing it.
+        fn buffered(&mut self) {
+            self.engine.events.extend([
+                EngineEvent::DurationUpdate(DURATION),
+                EngineEvent::StreamBufferHealthy,
+                EngineEvent::TrackLoaded,
+                EngineEvent::StateChanged(PlaybackState::Stopped),
+            ]);
+            self.deliver();
+        }
+
+        /// Finish downloading the stream loaded.
+        fn downloaded(&mut self) {
+            self.engine.stream = Some(true);
+            self.engine.events.push(EngineEvent::StreamDownloadComplete);
+            self.deliver();
+        }
+
+        /// Let the engine play for `secs` seconds.
+        fn run(&mut self, secs: u32) {
+            for _ in 0..secs {
+                if self.engine.playing {
+                    self.engine.position = (self.engine.position + 1.0).min(DURATION);
+                    self.engine
+                        .events
+                        .push(EngineEvent::PositionUpdate(self.engine.position));
+                    if self.engine.position >= DURATION {
+                        self.engine.playing = false;
+                        self.engine.events.push(EngineEvent::PlaybackFinished);
+                    }
+                }
+                self.deliver();
+            }
+        }
+
+        /// Lose the output device for `secs` seconds, then move to another.
+        fn lose_device(&mut self, secs: u32) {
+            let playing = std::mem::replace(&mut self.engine.playing, false);
+            self.engine
+                .events
+                .push(EngineEvent::Error("Audio stream error".into()));
+            self.run(secs);
+            self.engine.playing = playing;
+            // A new sample rate restarts decoding from the same position
+            self.engine.events.extend([
+                EngineEvent::DeviceChanged("Speakers".into()),
+                EngineEvent::PositionUpdate(self.engine.position),
+            ]);
+            self.deliver();
+        }
+
+        /// Deliver the engine's events until it has nothing more to say.
+        fn deliver(&mut self) {
+            loop {
+                for event in std::mem::take(&mut self.engine.events) {
+                    self.playback.handle(event, &mut self.queue);
+                }
+                let reactions = self.playback.take_reactions();
+                if reactions.is_empty() && self.engine.events.is_empty() {
+                    break;
+                }
+                for reaction in reactions {
+                    self.apply(reaction);
+                }
+            }
+        }
+
+        fn apply(&mut self, reaction: Reaction) {
+            match reaction {
+                Reaction::Status(status) => self.status = status,
+                Reaction::Position(pos) => self.position = pos,
+                Reaction::CurrentTrack(track) => self.current = Some(track.id),
+                Reaction::Play => self.engine.set_playing(true),
+                Reaction::Stop => self.engine.set_playing(false),
+                Reaction::Seek(pos) => self.engine.seek(pos),
+                Reaction::Load(track) => self.load(&track),
+                Reaction::Prefetch(track) => self.effect(format!("Prefetch({})", track.id)),
+                Reaction::Preload(track) => self.effect(format!("Preload({})", track.id)),
+                Reaction::QueueEnded(seed) => self.effect(format!("QueueEnded({})", seed.id)),
+                Reaction::Duration(_)
+                | Reaction::DownloadProgress(_)
+                | Reaction::Spectrum(_)
+                | Reaction::Crossfaded(_) => {}
+            }
+        }
+
+        /// Note an effect, unless it already was.
+        fn effect(&mut self, effect: String) {
+            if !self.effects.contains(&effect) {
+                self.effects.push(effect);
+            }
+        }
+
+        /// Take the commands the engine received since the last call.
+        fn commands(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.engine.commands)
+        }
+    }
+
+    #[test]
+    fn test_skip_during_buffering_drops_stale_load() {
+        let mut sim = Sim::new(&["a", "b", "c", "d"]);
+        sim.play_current();
+        sim.skip();
+
+        // "b" extracts first, and "a" finishes after it was skipped
+        sim.extracted("b", false);
+        sim.extracted("a", false);
+        assert_eq!(sim.commands(), ["Load(b)", "Play"]);
+        assert_eq!(sim.current.as_deref(), Some("b"));
+        assert_eq!(sim.status, PlaybackStatus::Playing);
+
+        // Failing to extract a skipped track leaves the one skipped to alone
+        sim.skip();
+        sim.skip();
+        sim.extraction_failed("c");
+        assert!(sim.commands().is_empty());
+        assert_eq!(sim.status, PlaybackStatus::Buffering);
+    }
+
+    #[test]
+    fn test_skip_during_buffering_ignores_replaced_track() {
+        let mut sim = Sim::new(&["a", "b", "c"]);
+        sim.play_current();
+        sim.extracted("a", false);
+        sim.run(190);
+        sim.commands();
+
+        // "a" finishes while "b" is still extracting
+        sim.skip();
+        sim.run(20);
+        assert_eq!(sim.current.as_deref(), Some("b"));
+        assert_eq!(sim.status, PlaybackStatus::Buffering);
+        assert_eq!(sim.position, 0.0);
+        assert!(sim.commands().is_empty());
+
+        sim.extracted("b", true);
+        sim.buffered();
+        assert_eq!(sim.commands(), ["Load(b)", "Play"]);
+        assert_eq!(sim.current.as_deref(), Some("b"));
+        assert_eq!(sim.status, PlaybackStatus::Playing);
+        assert_eq!(
+            sim.queue.current().map(|item| item.track.id.as_str()),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn test_extraction_failure_stops() {
+        let mut sim = Sim::new(&["a", "b"]);
+        sim.play_current();
+        sim.extracted("a", false);
+        sim.run(10);
+        sim.commands();
+
+        sim.skip();
+        sim.extraction_failed("b");
+        assert_eq!(sim.commands(), ["Stop"]);
+        assert_eq!(sim.status, PlaybackStatus::Stopped);
+        assert_eq!(sim.current.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_seek_past_end_on_resume_moves_on() {
+        let mut sim = Sim::new(&["a", "b"]);
+        sim.playback.resume_at("a", 42.0);
+        sim.play_current();
+        sim.extracted("a", false);
+        assert_eq!(sim.commands(), ["Load(a)", "Seek(42)", "Play"]);
+        assert_eq!(sim.position, 42.0);
+
+        let mut sim = Sim::new(&["a", "b"]);
+        sim.playback.resume_at("a", 250.0);
+        sim.play_current();
+        sim.extracted("a", false);
+        assert_eq!(sim.current.as_deref(), Some("b"));
+        assert_eq!(sim.status, PlaybackStatus::Buffering);
+        sim.extracted("b", false);
+        assert_eq!(sim.commands(), ["Load(a)", "Load(b)", "Play"]);
+
+        // Past the end of the last track ends the queue
+        let mut sim = Sim::new(&["a"]);
+        sim.playback.resume_at("a", DURATION);
+        sim.play_current();
+        sim.extracted("a", false);
+        assert_eq!(sim.commands(), ["Load(a)"]);
+        assert_eq!(sim.status, PlaybackStatus::Stopped);
+        assert_eq!(sim.effects, ["QueueEnded(a)"]);
+    }
+
+    #[test]
+    fn test_resume_in_stream_waits_for_download() {
+        let mut sim = Sim::new(&["a", "b"]);
+        sim.playback.resume_at("a", 42.0);
+        sim.play_current();
+        sim.extracted("a", true);
+        sim.buffered();
+        assert_eq!(sim.commands(), ["Load(a)"]);
+        assert_eq!(sim.status, PlaybackStatus::Buffering);
+
+        sim.downloaded();
+        assert_eq!(sim.commands(), ["Seek(42)", "Play"]);
+        assert_eq!(sim.status, PlaybackStatus::Playing);
+        sim.run(1);
+        assert_eq!(sim.position, 43.0);
+    }
+
+    #[test]
+    fn test_device_loss_mid_track_carries_on() {
+        let mut sim = Sim::new(&["a", "b"]);
+        sim.play_current();
+        sim.extracted("a", false);
+        sim.run(100);
+        sim.commands();
+
+        sim.lose_device(5);
+        assert!(sim.commands().is_empty());
+        assert_eq!(sim.current.as_deref(), Some("a"));
+        assert_eq!(sim.status, PlaybackStatus::Playing);
+        assert_eq!(sim.position, 100.0);
+
+        // Playback still moves on once the track ends
+        sim.run(100);
+        assert_eq!(sim.effects, ["Prefetch(b)", "Preload(b)"]);
+        assert_eq!(sim.current.as_deref(), Some("b"));
+        sim.extracted("b", false);
+        assert_eq!(sim.commands(), ["Load(b)", "Play"]);
+    }
+}
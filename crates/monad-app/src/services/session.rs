@@ -0,0 +1,53 @@
+//! Saves the playback queue and restores it on the next launch.
+
+use std::time::Duration;
+
+use dioxus::prelude::*;
+use tracing::info;
+
+use crate::services::AudioService;
+use crate::state::AppState;
+
+/// How often the queue and playback position are saved.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Restore the queue saved by the last session, then keep saving it.
+///
+/// The restored track starts paused; playing it resumes at the saved position.
+pub fn use_session_restore(app_state: AppState, audio: Signal<AudioService>) {
+    use_future(move || {
+        let mut app_state = app_state.clone();
+        async move {
+            let library = audio.peek().library().clone();
+            if let Some(saved) = library.load_queue().filter(|saved| !saved.queue.is_empty()) {
+                let track = saved.queue.current().map(|item| item.track.clone());
+                if let Some(track) = &track {
+                    audio.peek().resume_at(&track.id, saved.position_secs);
+                }
+                info!("Restored a queue of {} tracks", saved.queue.len());
+                app_state.queue.set(saved.queue);
+                app_state.player.set_track(track);
+                app_state.player.position.set(saved.position_secs);
+            }
+
+            let mut last_saved = None;
+            loop {
+                tokio::time::sleep(SAVE_INTERVAL).await;
+                let queue = app_state.queue.peek();
+                let position_secs = *app_state.player.position.peek();
+                // Skip saving while nothing changes, such as when paused
+                let state = (
+                    queue.items().iter().map(|item| item.id).collect::<Vec<_>>(),
+                    queue.current_index(),
+                    queue.repeat_mode(),
+                    queue.is_shuffle(),
+                    position_secs,
+                );
+                if last_saved.as_ref() != Some(&state) {
+                    library.save_queue(&queue, position_secs);
+                    last_saved = Some(state);
+                }
+            }
+        }
+    });
+}
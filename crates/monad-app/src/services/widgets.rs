@@ -0,0 +1,89 @@
+//! Publishes playback snapshots for home-screen and desktop widgets.
+
+use std::time::Duration;
+
+use dioxus::prelude::*;
+use directories::ProjectDirs;
+use monad_core::{FileSink, SnapshotSink, SnapshotTrack, WidgetSnapshot};
+use tracing::warn;
+
+use crate::services::LibraryService;
+use crate::state::player::PlaybackStatus;
+use crate::state::AppState;
+
+/// How often snapshots are published.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of upcoming and recent tracks included in a snapshot.
+const SNAPSHOT_TRACKS: usize = 5;
+
+/// Default sinks: a JSON file in the local data directory.
+fn default_sinks() -> Vec<Box<dyn SnapshotSink>> {
+    let mut sinks: Vec<Box<dyn SnapshotSink>> = Vec::new();
+    if let Some(dirs) = ProjectDirs::from("", "", "monad") {
+        let path = dirs.data_local_dir().join("now-playing.json");
+        sinks.push(Box::new(FileSink::new(path)));
+    }
+    sinks
+}
+
+/// Build a snapshot of the current playback state.
+fn build_snapshot(app_state: &AppState, library: &LibraryService) -> WidgetSnapshot {
+    let status = match *app_state.player.status.peek() {
+        PlaybackStatus::Stopped => "stopped",
+        PlaybackStatus::Playing => "playing",
+        PlaybackStatus::Paused => "paused",
+        PlaybackStatus::Buffering => "buffering",
+    };
+
+    let mut snapshot = WidgetSnapshot::new(status);
+    snapshot.position_secs = *app_state.player.position.peek();
+    let duration = *app_state.player.duration.peek();
+    snapshot.duration_secs = (duration > 0.0).then_some(duration);
+    snapshot.now_playing = app_state
+        .player
+        .current_track
+        .peek()
+        .as_ref()
+        .map(SnapshotTrack::from);
+    snapshot.next_up = app_state
+        .queue
+        .peek()
+        .upcoming(SNAPSHOT_TRACKS)
+        .into_iter()
+        .map(|item| SnapshotTrack::from(&item.track))
+        .collect();
+    snapshot.recent = library
+        .recent_plays(SNAPSHOT_TRACKS)
+        .into_iter()
+        .map(|play| SnapshotTrack {
+            id: play.video_id,
+            title: play.title,
+            artist: play.artist.unwrap_or_default(),
+            album: play.album,
+            artwork_url: None,
+            played_at: Some(play.played_at),
+        })
+        .collect();
+    snapshot
+}
+
+/// Periodically publish widget snapshots to the default sinks.
+pub fn use_widget_snapshots(app_state: AppState, library: LibraryService) {
+    use_future(move || {
+        let app_state = app_state.clone();
+        let library = library.clone();
+        async move {
+            let sinks = default_sinks();
+            loop {
+                let snapshot = build_snapshot(&app_state, &library);
+                for sink in &sinks {
+                    if let Err(e) = sink.publish(&snapshot) {
+                        warn!("Failed to publish widget snapshot to {}: {e}", sink.name());
+                    }
+                }
+                tokio::time::sleep(PUBLISH_INTERVAL).await;
+            }
+        }
+    });
+}
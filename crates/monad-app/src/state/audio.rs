@@ -0,0 +1,233 @@
+//! Audio engine and streaming settings.
+//!
+//! Saved in the library's settings and applied when the engine starts, so
+//! changes take effect after a restart.
+
+use std::time::Duration;
+
+use monad_audio::{
+    ChannelSettings, EqPreset, LoudnessSettings, ThreadPriority, MAX_CROSSFADE, MAX_PITCH_SEMITONES,
+};
+use monad_core::AudioQuality;
+use serde::{Deserialize, Serialize};
+
+use crate::services::LibraryService;
+
+/// Setting the audio configuration is saved under.
+pub const AUDIO_SETTING: &str = "audio";
+
+/// Step between crossfade lengths when cycling through them.
+const CROSSFADE_STEP_SECS: u64 = 2;
+
+/// Step between balance positions when cycling through them.
+const BALANCE_STEP: f32 = 0.25;
+
+/// Step between volumes when cycling through them.
+const VOLUME_STEP: f32 = 0.1;
+
+/// Target loudness levels to cycle through, in LUFS.
+const LOUDNESS_TARGETS: [f32; 3] = [-14.0, -18.0, -23.0];
+
+/// Get the volume a step louder, wrapping around from full to the quietest.
+pub fn next_volume(volume: f32) -> f32 {
+    let next = (volume / VOLUME_STEP).round() * VOLUME_STEP + VOLUME_STEP;
+    if next > 1.0 + f32::EPSILON {
+        VOLUME_STEP
+    } else {
+        next.min(1.0)
+    }
+}
+
+/// Get the display name of a volume.
+pub fn volume_name(volume: f32) -> String {
+    format!("{}%", (volume * 100.0).round())
+}
+
+/// Saved audio engine settings.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct AudioConfig {
+    /// Scheduling priority requested for the audio threads.
+    pub thread_priority: ThreadPriority,
+    /// Fixed stream quality, or `None` to pick it from the measured bandwidth.
+    #[serde(default)]
+    pub stream_quality: Option<AudioQuality>,
+    /// Seconds to crossfade between tracks, zero for none.
+    #[serde(default)]
+    pub crossfade_secs: u64,
+    /// Equalizer preset.
+    #[serde(default)]
+    pub eq_preset: EqPreset,
+    /// Loudness normalization, with a pre-amp that has no menu item.
+    #[serde(default)]
+    pub loudness: LoudnessSettings,
+    /// Semitones to shift the pitch by without changing speed, zero for none.
+    #[serde(default)]
+    pub pitch_semitones: f32,
+    /// Balance, mono downmix and muted channels.
+    #[serde(default)]
+    pub channels: ChannelSettings,
+}
+
+impl AudioConfig {
+    /// Load the configuration saved in `library`, falling back to the
+    /// defaults.
+    pub fn load(library: &LibraryService) -> Self {
+        library.setting(AUDIO_SETTING).unwrap_or_default()
+    }
+
+    /// Save the configuration in `library`.
+    pub fn save(&self, library: &LibraryService) {
+        library.set_setting(AUDIO_SETTING, self);
+    }
+
+    /// Switch to the next thread priority, wrapping around.
+    pub fn cycle_thread_priority(&mut self) {
+        let all = ThreadPriority::all();
+        let index = all
+            .iter()
+            .position(|&p| p == self.thread_priority)
+            .map_or(0, |i| (i + 1) % all.len());
+        self.thread_priority = all[index];
+    }
+
+    /// Switch to the next stream quality: automatic, then low to high.
+    pub fn cycle_stream_quality(&mut self) {
+        self.stream_quality = match self.stream_quality {
+            None => Some(AudioQuality::Low),
+            Some(AudioQuality::Low) => Some(AudioQuality::Medium),
+            Some(AudioQuality::Medium) => Some(AudioQuality::High),
+            Some(AudioQuality::High | AudioQuality::Max) => None,
+        };
+    }
+
+    /// Switch to the next equalizer preset, wrapping around.
+    pub fn cycle_eq_preset(&mut self) {
+        let all = EqPreset::all();
+        let index = all
+            .iter()
+            .position(|&p| p == self.eq_preset)
+            .map_or(0, |i| (i + 1) % all.len());
+        self.eq_preset = all[index];
+    }
+
+    /// Switch to the next loudness target, turning normalization off after
+    /// the quietest.
+    pub fn cycle_loudness(&mut self) {
+        let loudness = &mut self.loudness;
+        if !loudness.enabled {
+            loudness.enabled = true;
+            loudness.target_lufs = LOUDNESS_TARGETS[0];
+            return;
+        }
+        let next = LOUDNESS_TARGETS
+            .iter()
+            .position(|&target| (target - loudness.target_lufs).abs() < 0.5)
+            .and_then(|i| LOUDNESS_TARGETS.get(i + 1));
+        match next {
+            Some(&target) => loudness.target_lufs = target,
+            None => loudness.enabled = false,
+        }
+    }
+
+    /// Display name of the loudness normalization setting.
+    pub fn loudness_name(&self) -> String {
+        if self.loudness.enabled {
+            format!("{} LUFS", self.loudness.target_lufs)
+        } else {
+            "Off".to_string()
+        }
+    }
+
+    /// Switch to the next crossfade length, wrapping around to none.
+    pub fn cycle_crossfade(&mut self) {
+        let next = self.crossfade_secs + CROSSFADE_STEP_SECS;
+        self.crossfade_secs = if next > MAX_CROSSFADE.as_secs() {
+            0
+        } else {
+            next
+        };
+    }
+
+    /// Get the crossfade between tracks.
+    pub fn crossfade(&self) -> Duration {
+        Duration::from_secs(self.crossfade_secs).min(MAX_CROSSFADE)
+    }
+
+    /// Display name of the crossfade setting.
+    pub fn crossfade_name(&self) -> String {
+        match self.crossfade_secs {
+            0 => "Off".to_string(),
+            secs => format!("{secs} s"),
+        }
+    }
+
+    /// Raise the pitch shift a semitone, wrapping around from the highest to
+    /// the lowest.
+    pub fn cycle_pitch(&mut self) {
+        let next = self.pitch_semitones.round() + 1.0;
+        self.pitch_semitones = if next > MAX_PITCH_SEMITONES {
+            -MAX_PITCH_SEMITONES
+        } else {
+            next
+        };
+    }
+
+    /// Display name of the pitch shift setting.
+    pub fn pitch_name(&self) -> String {
+        if self.pitch_semitones == 0.0 {
+            "Off".to_string()
+        } else {
+            format!("{:+} st", self.pitch_semitones)
+        }
+    }
+
+    /// Move the balance a step to the right, wrapping around from fully right
+    /// to fully left.
+    pub fn cycle_balance(&mut self) {
+        let next = (self.channels.balance / BALANCE_STEP).round() * BALANCE_STEP + BALANCE_STEP;
+        self.channels.balance = if next > 1.0 { -1.0 } else { next };
+    }
+
+    /// Display name of the balance setting.
+    pub fn balance_name(&self) -> String {
+        let balance = self.channels.balance;
+        let percent = (balance.abs() * 100.0).round();
+        if balance < 0.0 {
+            format!("L {percent}%")
+        } else if balance > 0.0 {
+            format!("R {percent}%")
+        } else {
+            "Centre".to_string()
+        }
+    }
+
+    /// Switch to muting the next channel: none, left, then right.
+    pub fn cycle_muted_channel(&mut self) {
+        self.channels.muted = match self.channels.muted {
+            [false, false] => [true, false],
+            [true, false] => [false, true],
+            _ => [false, false],
+        };
+    }
+
+    /// Display name of the muted channel setting.
+    pub const fn muted_channel_name(&self) -> &'static str {
+        match self.channels.muted {
+            [false, false] => "None",
+            [true, false] => "Left",
+            [false, true] => "Right",
+            [true, true] => "Both",
+        }
+    }
+
+    /// Display name of the stream quality setting.
+    pub const fn stream_quality_name(&self) -> &'static str {
+        match self.stream_quality {
+            None => "Auto",
+            Some(AudioQuality::Low) => "Low",
+            Some(AudioQuality::Medium) => "Medium",
+            Some(AudioQuality::High) => "High",
+            Some(AudioQuality::Max) => "Max",
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Global (OS-wide) hotkey configuration.
+//!
+//! These shortcuts work while the window is unfocused, unlike in-window
+//! shortcuts. Bindings are saved in the library's settings.
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::services::LibraryService;
+
+/// Setting the hotkey configuration is saved under.
+pub const HOTKEYS_SETTING: &str = "hotkeys";
+
+/// Action triggered by a global hotkey.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum HotkeyAction {
+    PlayPause,
+    NextTrack,
+    PreviousTrack,
+    ToggleLyrics,
+    LyricsEarlier,
+    LyricsLater,
+}
+
+impl HotkeyAction {
+    /// Get all actions.
+    pub const fn all() -> &'static [HotkeyAction] {
+        &[
+            HotkeyAction::PlayPause,
+            HotkeyAction::NextTrack,
+            HotkeyAction::PreviousTrack,
+            HotkeyAction::ToggleLyrics,
+            HotkeyAction::LyricsEarlier,
+            HotkeyAction::LyricsLater,
+        ]
+    }
+
+    /// Get display name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            HotkeyAction::PlayPause => "Play/Pause",
+            HotkeyAction::NextTrack => "Next Track",
+            HotkeyAction::PreviousTrack => "Previous Track",
+            HotkeyAction::ToggleLyrics => "Toggle Lyrics",
+            HotkeyAction::LyricsEarlier => "Lyrics Earlier",
+            HotkeyAction::LyricsLater => "Lyrics Later",
+        }
+    }
+
+    /// Get the default accelerator.
+    pub const fn default_accelerator(self) -> &'static str {
+        match self {
+            HotkeyAction::PlayPause => "Ctrl+Alt+P",
+            HotkeyAction::NextTrack => "Ctrl+Alt+Right",
+            HotkeyAction::PreviousTrack => "Ctrl+Alt+Left",
+            HotkeyAction::ToggleLyrics => "Ctrl+Alt+L",
+            HotkeyAction::LyricsEarlier => "Ctrl+Alt+Minus",
+            HotkeyAction::LyricsLater => "Ctrl+Alt+Equal",
+        }
+    }
+}
+
+/// A hotkey bound to an action.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub action: HotkeyAction,
+    /// Accelerator such as `Ctrl+Alt+P`.
+    pub accelerator: String,
+}
+
+/// Saved global hotkey settings.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct HotkeyConfig {
+    /// Whether global hotkeys are registered at all.
+    pub enabled: bool,
+    pub bindings: Vec<HotkeyBinding>,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bindings: HotkeyAction::all()
+                .iter()
+                .map(|&action| HotkeyBinding {
+                    action,
+                    accelerator: action.default_accelerator().to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl HotkeyConfig {
+    /// Load the configuration saved in `library`, falling back to the
+    /// defaults.
+    pub fn load(library: &LibraryService) -> Self {
+        library.setting(HOTKEYS_SETTING).unwrap_or_default()
+    }
+
+    /// Save the configuration in `library`.
+    pub fn save(&self, library: &LibraryService) {
+        library.set_setting(HOTKEYS_SETTING, self);
+    }
+
+    /// Get the accelerator bound to an action.
+    pub fn accelerator(&self, action: HotkeyAction) -> Option<&str> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.action == action)
+            .map(|binding| binding.accelerator.as_str())
+    }
+
+    /// Bind an accelerator to an action, replacing any existing binding.
+    ///
+    /// An action already bound to the same accelerator is left unbound, since
+    /// the OS only registers a shortcut once.
+    pub fn bind(&mut self, action: HotkeyAction, accelerator: String) {
+        self.bindings.retain(|binding| {
+            binding.action != action && !binding.accelerator.eq_ignore_ascii_case(&accelerator)
+        });
+        self.bindings.push(HotkeyBinding {
+            action,
+            accelerator,
+        });
+    }
+}
+
+/// Build an accelerator from a key press, or `None` for a lone modifier.
+///
+/// Global hotkeys need at least one modifier so they don't swallow typing.
+pub fn accelerator_from_key(
+    code: &str,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    meta: bool,
+) -> Option<String> {
+    let is_modifier = ["Control", "Alt", "Shift", "Meta", "Super"]
+        .iter()
+        .any(|modifier| code.starts_with(modifier));
+    if is_modifier || !(ctrl || alt || shift || meta) {
+        return None;
+    }
+
+    let key = code
+        .strip_prefix("Key")
+        .or_else(|| code.strip_prefix("Digit"))
+        .or_else(|| code.strip_prefix("Arrow"))
+        .unwrap_or(code);
+
+    let mut parts = Vec::new();
+    if ctrl {
+        parts.push("Ctrl");
+    }
+    if alt {
+        parts.push("Alt");
+    }
+    if shift {
+        parts.push("Shift");
+    }
+    if meta {
+        parts.push("Super");
+    }
+    parts.push(key);
+    Some(parts.join("+"))
+}
+
+/// Global hotkey state for the application.
+#[derive(Clone, Copy)]
+pub struct HotkeyState {
+    /// Current configuration.
+    pub config: Signal<HotkeyConfig>,
+    /// Action whose new accelerator is being recorded in Settings.
+    pub recording: Signal<Option<HotkeyAction>>,
+}
+
+impl HotkeyState {
+    /// Create hotkey state from the configuration saved in `library`.
+    pub fn new(library: &LibraryService) -> Self {
+        Self {
+            config: Signal::new(HotkeyConfig::load(library)),
+            recording: Signal::new(None),
+        }
+    }
+
+    /// Update the configuration and save it.
+    pub fn update(&mut self, library: &LibraryService, f: impl FnOnce(&mut HotkeyConfig)) {
+        let mut config = self.config.write();
+        f(&mut config);
+        config.save(library);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accelerator_from_key() {
+        assert_eq!(
+            accelerator_from_key("KeyP", true, true, false, false).as_deref(),
+            Some("Ctrl+Alt+P")
+        );
+        assert_eq!(
+            accelerator_from_key("ArrowRight", false, false, true, true).as_deref(),
+            Some("Shift+Super+Right")
+        );
+        assert_eq!(
+            accelerator_from_key("Digit1", true, false, false, false).as_deref(),
+            Some("Ctrl+1")
+        );
+        assert_eq!(
+            accelerator_from_key("KeyP", false, false, false, false),
+            None
+        );
+        assert_eq!(
+            accelerator_from_key("ControlLeft", true, false, false, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bind_replaces_binding() {
+        let mut config = HotkeyConfig::default();
+        config.bind(HotkeyAction::PlayPause, "Ctrl+Shift+Space".to_string());
+        assert_eq!(
+            config.accelerator(HotkeyAction::PlayPause),
+            Some("Ctrl+Shift+Space")
+        );
+        assert_eq!(config.bindings.len(), HotkeyAction::all().len());
+    }
+
+    #[test]
+    fn test_bind_clears_duplicate_accelerator() {
+        let mut config = HotkeyConfig::default();
+        config.bind(HotkeyAction::PlayPause, "ctrl+alt+l".to_string());
+        assert_eq!(
+            config.accelerator(HotkeyAction::PlayPause),
+            Some("ctrl+alt+l")
+        );
+        assert_eq!(config.accelerator(HotkeyAction::ToggleLyrics), None);
+        assert_eq!(
+            config.accelerator(HotkeyAction::NextTrack),
+            Some(HotkeyAction::NextTrack.default_accelerator())
+        );
+    }
+}
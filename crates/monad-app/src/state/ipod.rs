@@ -1,9 +1,63 @@
 //! iPod navigation state.
 
 use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::services::LibraryService;
+
+/// Setting the color theme is saved under.
+const THEME_SETTING: &str = "theme";
+
+/// Setting the display mode is saved under.
+pub const DISPLAY_SETTING: &str = "display";
+
+/// Setting the lyrics script is saved under.
+const LYRICS_SCRIPT_SETTING: &str = "lyrics_script";
+
+/// Setting the lyrics translation language is saved under.
+const LYRICS_TRANSLATION_SETTING: &str = "lyrics_translation";
+
+/// Languages lyrics can be translated into, by code.
+const TRANSLATION_LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("pt", "Portuguese"),
+    ("fr", "French"),
+    ("de", "German"),
+    ("it", "Italian"),
+    ("ru", "Russian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("zh", "Chinese"),
+];
+
+/// Get the name of a translation language, or its code if unknown.
+pub fn translation_language_name(code: &str) -> &str {
+    TRANSLATION_LANGUAGES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map_or(code, |(_, name)| name)
+}
+
+/// Get the translation language after `code`, with `None` for off.
+pub fn next_translation_language(code: Option<&str>) -> Option<String> {
+    let next = match code {
+        None => 0,
+        Some(code) => TRANSLATION_LANGUAGES
+            .iter()
+            .position(|(c, _)| *c == code)
+            .map_or(0, |i| i + 1),
+    };
+    TRANSLATION_LANGUAGES
+        .get(next)
+        .map(|(code, _)| (*code).to_string())
+}
+
+/// Milliseconds the lyrics timing moves per adjustment.
+pub const LYRICS_OFFSET_STEP_MS: i64 = 250;
 
 /// iPod color themes.
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
 pub enum ColorTheme {
     #[default]
     Silver,
@@ -48,6 +102,71 @@ impl ColorTheme {
     }
 }
 
+/// Script lyrics are shown in, for lines that have a romanized version.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum LyricsScript {
+    /// The script the lyrics are written in.
+    #[default]
+    Original,
+    /// Latin script only.
+    Romanized,
+    /// The original with the romanized version below.
+    Both,
+}
+
+impl LyricsScript {
+    /// Get display name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            LyricsScript::Original => "Original",
+            LyricsScript::Romanized => "Romanized",
+            LyricsScript::Both => "Both",
+        }
+    }
+
+    /// Get the next script, wrapping around.
+    pub const fn next(self) -> Self {
+        match self {
+            LyricsScript::Original => LyricsScript::Romanized,
+            LyricsScript::Romanized => LyricsScript::Both,
+            LyricsScript::Both => LyricsScript::Original,
+        }
+    }
+}
+
+/// Display presets for the iPod screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum DisplayMode {
+    /// Classic look with LCD shading.
+    #[default]
+    Standard,
+    /// Larger, high-contrast text without LCD shading.
+    HighContrast,
+}
+
+impl DisplayMode {
+    /// Get all available display modes.
+    pub const fn all() -> &'static [DisplayMode] {
+        &[DisplayMode::Standard, DisplayMode::HighContrast]
+    }
+
+    /// Get display name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            DisplayMode::Standard => "Standard",
+            DisplayMode::HighContrast => "High Contrast",
+        }
+    }
+
+    /// Get CSS class name for this display mode.
+    pub const fn css_class(self) -> &'static str {
+        match self {
+            DisplayMode::Standard => "display-standard",
+            DisplayMode::HighContrast => "display-high-contrast",
+        }
+    }
+}
+
 /// iPod screen states.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub enum IPodScreen {
@@ -60,6 +179,8 @@ pub enum IPodScreen {
     Search,
     /// Settings screen.
     Settings,
+    /// Weekly listening report.
+    WeeklyReport,
 }
 
 /// Menu item definition.
@@ -82,6 +203,10 @@ impl IPodScreen {
                     label: "Search",
                     target: IPodScreen::Search,
                 },
+                MenuItem {
+                    label: "Weekly Report",
+                    target: IPodScreen::WeeklyReport,
+                },
                 MenuItem {
                     label: "Settings",
                     target: IPodScreen::Settings,
@@ -98,6 +223,7 @@ impl IPodScreen {
             IPodScreen::Menu => "iPod",
             IPodScreen::Search => "Search",
             IPodScreen::Settings => "Settings",
+            IPodScreen::WeeklyReport => "Weekly Report",
         }
     }
 }
@@ -111,21 +237,79 @@ pub struct IPodState {
     pub menu_index: Signal<usize>,
     /// Screen history for back navigation.
     pub history: Signal<Vec<IPodScreen>>,
-    /// Current color theme.
+    /// Current color theme (saved across restarts).
     pub theme: Signal<ColorTheme>,
+    /// Current display mode (saved across restarts).
+    pub display: Signal<DisplayMode>,
+    /// Whether Now Playing shows lyrics instead of artwork.
+    pub show_lyrics: Signal<bool>,
+    /// Lyrics timing offset of the current track in milliseconds.
+    pub lyrics_offset_ms: Signal<i64>,
+    /// Script lyrics are shown in (saved across restarts).
+    pub lyrics_script: Signal<LyricsScript>,
+    /// Language code lyrics are translated into, if any (saved across restarts).
+    pub lyrics_translation: Signal<Option<String>>,
+    /// Last search query, restored when returning to Search.
+    pub search_query: Signal<String>,
 }
 
 impl IPodState {
-    /// Create new iPod state.
-    pub fn new() -> Self {
+    /// Create new iPod state, with the settings saved in `library`.
+    pub fn new(library: &LibraryService) -> Self {
         Self {
             screen: Signal::new(IPodScreen::NowPlaying),
             menu_index: Signal::new(0),
             history: Signal::new(Vec::new()),
-            theme: Signal::new(ColorTheme::default()),
+            theme: Signal::new(library.setting(THEME_SETTING).unwrap_or_default()),
+            display: Signal::new(library.setting(DISPLAY_SETTING).unwrap_or_default()),
+            show_lyrics: Signal::new(false),
+            lyrics_offset_ms: Signal::new(0),
+            lyrics_script: Signal::new(library.setting(LYRICS_SCRIPT_SETTING).unwrap_or_default()),
+            lyrics_translation: Signal::new(
+                library
+                    .setting::<Option<String>>(LYRICS_TRANSLATION_SETTING)
+                    .flatten(),
+            ),
+            search_query: Signal::new(String::new()),
         }
     }
 
+    /// Switch color theme and save it.
+    pub fn set_theme(&mut self, theme: ColorTheme, library: &LibraryService) {
+        self.theme.set(theme);
+        library.set_setting(THEME_SETTING, &theme);
+    }
+
+    /// Switch display mode and save it.
+    pub fn set_display(&mut self, mode: DisplayMode, library: &LibraryService) {
+        self.display.set(mode);
+        library.set_setting(DISPLAY_SETTING, &mode);
+    }
+
+    /// Switch the lyrics script and save it.
+    pub fn set_lyrics_script(&mut self, script: LyricsScript, library: &LibraryService) {
+        self.lyrics_script.set(script);
+        library.set_setting(LYRICS_SCRIPT_SETTING, &script);
+    }
+
+    /// Switch the lyrics translation language, or turn it off, and save it.
+    pub fn set_lyrics_translation(&mut self, language: Option<String>, library: &LibraryService) {
+        library.set_setting(LYRICS_TRANSLATION_SETTING, &language);
+        self.lyrics_translation.set(language);
+    }
+
+    /// Move the lyrics timing of a track by `delta_ms` and save it.
+    pub fn adjust_lyrics_offset(
+        &mut self,
+        video_id: &str,
+        delta_ms: i64,
+        library: &LibraryService,
+    ) {
+        let offset_ms = library.lyrics_offset_ms(video_id) + delta_ms;
+        self.lyrics_offset_ms.set(offset_ms);
+        library.set_lyrics_offset_ms(video_id, offset_ms);
+    }
+
     /// Navigate to a screen.
     pub fn navigate(&mut self, screen: IPodScreen) {
         let current = *self.screen.read();
@@ -169,9 +353,3 @@ impl IPodState {
         }
     }
 }
-
-impl Default for IPodState {
-    fn default() -> Self {
-        Self::new()
-    }
-}
@@ -1,8 +1,11 @@
 //! Application state management.
 
+pub mod audio;
 pub mod battery;
+pub mod hotkeys;
 pub mod ipod;
 pub mod player;
+pub mod report;
 
 pub use player::PlayerState;
 
@@ -24,6 +24,10 @@ pub struct PlayerState {
     pub position: Signal<f64>,
     /// Total duration in seconds.
     pub duration: Signal<f64>,
+    /// Streaming download progress, 0.0 to 100.0 (if known).
+    pub download_progress: Signal<Option<f32>>,
+    /// Levels of the frequency bands playing, 0.0 to 1.0, for the visualizer.
+    pub spectrum: Signal<Vec<f32>>,
 }
 
 impl PlayerState {
@@ -34,6 +38,8 @@ impl PlayerState {
             status: Signal::new(PlaybackStatus::Stopped),
             position: Signal::new(0.0),
             duration: Signal::new(0.0),
+            download_progress: Signal::new(None),
+            spectrum: Signal::new(Vec::new()),
         }
     }
 
@@ -41,6 +47,7 @@ impl PlayerState {
     pub fn set_track(&mut self, track: Option<Track>) {
         *self.current_track.write() = track;
         *self.position.write() = 0.0;
+        *self.download_progress.write() = None;
     }
 
     /// Start or resume playback.
@@ -0,0 +1,84 @@
+//! Weekly listening report settings.
+//!
+//! Last week's report is shown once the configured first day of the week
+//! arrives. Settings are saved in the library's settings.
+
+use chrono::Weekday;
+use dioxus::prelude::*;
+use monad_cache::WeeklyReport;
+use serde::{Deserialize, Serialize};
+
+use crate::services::LibraryService;
+
+/// Setting the report configuration is saved under.
+pub const REPORT_SETTING: &str = "report";
+
+/// Saved weekly report settings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ReportConfig {
+    /// Whether the report is shown automatically each week.
+    pub enabled: bool,
+    /// First day of the week, when the report is shown.
+    pub week_start: Weekday,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            week_start: Weekday::Mon,
+        }
+    }
+}
+
+impl ReportConfig {
+    /// Load the configuration saved in `library`, falling back to the
+    /// defaults.
+    pub fn load(library: &LibraryService) -> Self {
+        library.setting(REPORT_SETTING).unwrap_or_default()
+    }
+
+    /// Save the configuration in `library`.
+    pub fn save(&self, library: &LibraryService) {
+        library.set_setting(REPORT_SETTING, self);
+    }
+}
+
+/// Get the display name of a weekday.
+pub const fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// Weekly report state for the application.
+#[derive(Clone, Copy)]
+pub struct ReportState {
+    /// Current configuration.
+    pub config: Signal<ReportConfig>,
+    /// Report shown on the Weekly Report screen.
+    pub report: Signal<Option<WeeklyReport>>,
+}
+
+impl ReportState {
+    /// Create report state from the configuration saved in `library`.
+    pub fn new(library: &LibraryService) -> Self {
+        Self {
+            config: Signal::new(ReportConfig::load(library)),
+            report: Signal::new(None),
+        }
+    }
+
+    /// Update the configuration and save it.
+    pub fn update(&mut self, library: &LibraryService, f: impl FnOnce(&mut ReportConfig)) {
+        let mut config = self.config.write();
+        f(&mut config);
+        config.save(library);
+    }
+}
@@ -23,6 +23,10 @@ pub struct RingBuffer {
     capacity: usize,
     /// Mask for efficient modulo (capacity - 1).
     mask: usize,
+    /// Number of reads that ran out of samples.
+    underruns: AtomicUsize,
+    /// Number of times clearing the buffer discarded samples.
+    clears: AtomicUsize,
 }
 
 impl RingBuffer {
@@ -39,6 +43,8 @@ impl RingBuffer {
             write_pos: AtomicUsize::new(0),
             capacity,
             mask: capacity - 1,
+            underruns: AtomicUsize::new(0),
+            clears: AtomicUsize::new(0),
         }
     }
 
@@ -242,7 +248,26 @@ impl RingBuffer {
     /// Clear the buffer.
     pub fn clear(&self) {
         let write_pos = self.write_pos.load(Ordering::Relaxed);
-        self.read_pos.store(write_pos, Ordering::Release);
+        let read_pos = self.read_pos.swap(write_pos, Ordering::AcqRel);
+        if read_pos != write_pos {
+            self.clears.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    /// Get the number of times clearing the buffer discarded samples, for the
+    /// reader to notice jumps in the samples.
+    pub fn clears(&self) -> usize {
+        self.clears.load(Ordering::Acquire)
+    }
+
+    /// Count a read that ran out of samples while playing.
+    pub fn record_underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the number of underruns recorded so far.
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
     }
 }
 
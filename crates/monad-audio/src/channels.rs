@@ -0,0 +1,93 @@
+//! Balance and channel controls.
+//!
+//! Applied by the output to the samples it plays, after volume, so changes
+//! are heard at once. Mono plays the mix of both channels on each, for
+//! listeners hearing with one ear, and either channel can be muted, such as
+//! for a broken earbud. In surround, the settings apply to each side, leaving
+//! the centre and LFE alone.
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::ChannelLayout;
+
+/// Balance, mono and mute settings of the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelSettings {
+    /// Balance from -1.0 (left only) through 0.0 (centre) to 1.0 (right only).
+    pub balance: f32,
+    /// Whether both channels play the mix of the two.
+    pub mono: bool,
+    /// Whether each channel is muted, left first.
+    pub muted: [bool; 2],
+}
+
+impl ChannelSettings {
+    /// Get the gain of each channel, left first, turning the quieter side
+    /// down as the balance moves away from the centre.
+    pub fn gains(&self) -> [f32; 2] {
+        let balance = self.balance.clamp(-1.0, 1.0);
+        let mut gains = [(1.0 - balance).min(1.0), (1.0 + balance).min(1.0)];
+        for (gain, muted) in gains.iter_mut().zip(self.muted) {
+            if muted {
+                *gain = 0.0;
+            }
+        }
+        gains
+    }
+
+    /// Apply the settings to a frame in `layout`.
+    pub(crate) fn apply(self, frame: &mut [f32], layout: ChannelLayout) {
+        if self.mono {
+            // Left channels are followed by their right counterparts
+            for channel in 0..frame.len().saturating_sub(1) {
+                if layout.side(channel) == Some(0) && layout.side(channel + 1) == Some(1) {
+                    let mixed = (frame[channel] + frame[channel + 1]) / 2.0;
+                    frame[channel..=channel + 1].fill(mixed);
+                }
+            }
+        }
+        let gains = self.gains();
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            if let Some(side) = layout.side(channel) {
+                *sample *= gains[side];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_mono_and_mute() {
+        let mut frame = [0.8, 0.2];
+        ChannelSettings::default().apply(&mut frame, ChannelLayout::Stereo);
+        assert!((frame[0] - 0.8).abs() < f32::EPSILON && (frame[1] - 0.2).abs() < f32::EPSILON);
+
+        // Halfway right leaves the right channel alone and halves the left
+        let right = ChannelSettings {
+            balance: 0.5,
+            ..ChannelSettings::default()
+        };
+        let mut frame = [0.8, 0.2];
+        right.apply(&mut frame, ChannelLayout::Stereo);
+        assert!((frame[0] - 0.4).abs() < f32::EPSILON && (frame[1] - 0.2).abs() < f32::EPSILON);
+
+        let mono_left = ChannelSettings {
+            mono: true,
+            muted: [false, true],
+            ..ChannelSettings::default()
+        };
+        let mut frame = [0.8, 0.2];
+        mono_left.apply(&mut frame, ChannelLayout::Stereo);
+        assert!((frame[0] - 0.5).abs() < f32::EPSILON && frame[1] == 0.0);
+
+        // The centre and LFE are neither mixed nor muted
+        let mut frame = [0.8, 0.2, 0.6, 0.4, 0.1, 0.3];
+        mono_left.apply(&mut frame, ChannelLayout::Surround51);
+        assert!((frame[0] - 0.5).abs() < f32::EPSILON && frame[1] == 0.0);
+        assert!((frame[2] - 0.6).abs() < f32::EPSILON && (frame[3] - 0.4).abs() < f32::EPSILON);
+        assert!((frame[4] - 0.2).abs() < f32::EPSILON && frame[5] == 0.0);
+    }
+}
@@ -0,0 +1,118 @@
+//! Engine buffering configuration.
+//!
+//! Decoded samples wait in a ring buffer for the output, and streams start
+//! once enough of them are buffered. The defaults suit most systems: a
+//! smaller buffer reacts faster and uses less memory, while a larger one
+//! rides out a flaky network or a busy machine.
+
+/// Default capacity of the ring buffer in samples (about 4 seconds at 48kHz
+/// stereo, before rounding up).
+pub const DEFAULT_RING_BUFFER_SIZE: usize = 48000 * 2 * 4;
+
+/// Smallest capacity of the ring buffer in samples (about 170ms at 48kHz
+/// stereo).
+pub const MIN_RING_BUFFER_SIZE: usize = 16384;
+
+/// Room left in the ring buffer by the fill a stream starts at, as decoding
+/// stops a little short of filling it, in samples.
+const FILL_HEADROOM: usize = 8192;
+
+/// Default fill below which a stream rebuffers, in samples.
+const DEFAULT_MIN_BUFFER_FILL: usize = 8192;
+
+/// Default fill before a stream starts playing - 5 seconds at 48kHz stereo.
+const DEFAULT_STREAMING_START_FILL: usize = 48000 * 2 * 5;
+
+/// Buffering configuration of the audio engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Capacity of the ring buffer in samples.
+    pub ring_buffer_size: usize,
+    /// Samples buffered below which a stream rebuffers. Twice as many are
+    /// decoded ahead after a seek.
+    pub min_buffer_fill: usize,
+    /// Samples buffered before a stream starts playing.
+    pub streaming_start_fill: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            ring_buffer_size: DEFAULT_RING_BUFFER_SIZE,
+            min_buffer_fill: DEFAULT_MIN_BUFFER_FILL,
+            streaming_start_fill: DEFAULT_STREAMING_START_FILL,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Hold up to `samples` in the ring buffer.
+    #[must_use]
+    pub const fn with_ring_buffer_size(mut self, samples: usize) -> Self {
+        self.ring_buffer_size = samples;
+        self
+    }
+
+    /// Rebuffer streams when fewer than `samples` are buffered.
+    #[must_use]
+    pub const fn with_min_buffer_fill(mut self, samples: usize) -> Self {
+        self.min_buffer_fill = samples;
+        self
+    }
+
+    /// Start streams once `samples` are buffered.
+    #[must_use]
+    pub const fn with_streaming_start_fill(mut self, samples: usize) -> Self {
+        self.streaming_start_fill = samples;
+        self
+    }
+
+    /// Get the values the engine uses: the ring buffer at least
+    /// [`MIN_RING_BUFFER_SIZE`] and rounded up to a power of two, and the
+    /// fills limited so that they fit in it.
+    #[must_use]
+    pub fn effective(self) -> Self {
+        let ring_buffer_size = self
+            .ring_buffer_size
+            .max(MIN_RING_BUFFER_SIZE)
+            .next_power_of_two();
+        let streaming_start_fill = self
+            .streaming_start_fill
+            .min(ring_buffer_size - FILL_HEADROOM);
+        Self {
+            ring_buffer_size,
+            min_buffer_fill: self
+                .min_buffer_fill
+                .min(ring_buffer_size / 4)
+                .min(streaming_start_fill),
+            streaming_start_fill,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_values_fit_the_buffer() {
+        let default = EngineConfig::default().effective();
+        assert_eq!(default.ring_buffer_size, 524_288);
+        assert_eq!(default.min_buffer_fill, DEFAULT_MIN_BUFFER_FILL);
+        assert_eq!(default.streaming_start_fill, DEFAULT_STREAMING_START_FILL);
+
+        let small = EngineConfig::default()
+            .with_ring_buffer_size(20_000)
+            .effective();
+        assert_eq!(small.ring_buffer_size, 32_768);
+        assert_eq!(small.streaming_start_fill, 24_576);
+        assert_eq!(small.min_buffer_fill, 8192);
+
+        let tiny = EngineConfig::default()
+            .with_ring_buffer_size(0)
+            .with_streaming_start_fill(1000)
+            .effective();
+        assert_eq!(tiny.ring_buffer_size, MIN_RING_BUFFER_SIZE);
+        assert_eq!(tiny.min_buffer_fill, 1000);
+    }
+}
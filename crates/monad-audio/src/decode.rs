@@ -1,5 +1,10 @@
 //! Audio decoding using symphonia.
+//!
+//! The engine decodes with ffmpeg where it is installed, and with symphonia
+//! otherwise, through [`PlaybackDecoder`]. Symphonia covers AAC, MP3, FLAC and
+//! Vorbis, but not Opus.
 
+use std::collections::VecDeque;
 use std::io::Cursor;
 
 use bytes::Bytes;
@@ -14,6 +19,9 @@ use symphonia::core::{
 };
 use tracing::{debug, error, trace};
 
+use crate::layout::ChannelLayout;
+use crate::resample::Resampler;
+
 /// Audio decoder wrapping symphonia.
 pub struct AudioDecoder {
     format: Box<dyn FormatReader>,
@@ -21,13 +29,14 @@ pub struct AudioDecoder {
     track_id: u32,
     sample_rate: u32,
     channels: u16,
+    /// Frames to drop before the position seeked to.
+    skip_frames: usize,
 }
 
 impl AudioDecoder {
     /// Create a new decoder from a byte buffer.
-    #[allow(clippy::needless_pass_by_value)] // Bytes is cheaply cloneable
     pub fn from_bytes(data: Bytes, mime_hint: Option<&str>) -> Result<Self> {
-        let cursor = Cursor::new(data.to_vec());
+        let cursor = Cursor::new(data);
         let mss = MediaSourceStream::new(Box::new(cursor), MediaSourceStreamOptions::default());
 
         let mut hint = Hint::new();
@@ -85,6 +94,7 @@ impl AudioDecoder {
             track_id,
             sample_rate,
             channels,
+            skip_frames: 0,
         })
     }
 
@@ -120,7 +130,16 @@ impl AudioDecoder {
 
             match self.decoder.decode(&packet) {
                 Ok(decoded) => {
-                    let samples = audio_buffer_to_f32(&decoded);
+                    let mut samples = audio_buffer_to_f32(&decoded);
+                    if self.skip_frames > 0 {
+                        let frames = decoded.frames().min(self.skip_frames);
+                        self.skip_frames -= frames;
+                        samples
+                            .drain(..(frames * decoded.spec().channels.count()).min(samples.len()));
+                        if samples.is_empty() {
+                            continue;
+                        }
+                    }
                     return Ok(Some(samples));
                 }
                 Err(symphonia::core::errors::Error::DecodeError(e)) => {
@@ -276,7 +295,7 @@ impl StreamingDecoder {
     }
 
     /// Get the decoder (if ready).
-    pub const fn decoder(&mut self) -> Option<&mut AudioDecoder> {
+    pub fn decoder(&mut self) -> Option<&mut AudioDecoder> {
         self.decoder.as_mut()
     }
 
@@ -299,7 +318,8 @@ impl AudioDecoder {
 
         let time = Time::from(position_secs);
 
-        self.format
+        let seeked = self
+            .format
             .seek(
                 SeekMode::Accurate,
                 symphonia::core::formats::SeekTo::Time {
@@ -309,6 +329,19 @@ impl AudioDecoder {
             )
             .map_err(|e| Error::AudioDecode(format!("Seek failed: {e}")))?;
 
+        // Decoding resumes at a packet boundary, before the position seeked to
+        let before = seeked.required_ts.saturating_sub(seeked.actual_ts);
+        self.skip_frames = self.time_base().map_or(0, |time_base| {
+            let time = time_base.calc_time(before);
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let frames = ((time.seconds as f64 + time.frac) * f64::from(self.sample_rate)) as usize;
+            frames
+        });
+
         // Reset decoder state
         self.decoder.reset();
 
@@ -317,17 +350,86 @@ impl AudioDecoder {
 
     /// Get the total duration in seconds (if known).
     pub fn duration(&self) -> Option<f64> {
-        let track = self
-            .format
-            .tracks()
-            .iter()
-            .find(|t| t.id == self.track_id)?;
-
-        let time_base = track.codec_params.time_base?;
-        let n_frames = track.codec_params.n_frames?;
+        let n_frames = self.track()?.codec_params.n_frames?;
+        let time = self.time_base()?.calc_time(n_frames);
 
         #[allow(clippy::cast_precision_loss)]
-        Some(time_base.calc_time(n_frames).seconds as f64)
+        Some(time.seconds as f64 + time.frac)
+    }
+
+    fn track(&self) -> Option<&symphonia::core::formats::Track> {
+        self.format.tracks().iter().find(|t| t.id == self.track_id)
+    }
+
+    fn time_base(&self) -> Option<symphonia::core::units::TimeBase> {
+        self.track()?.codec_params.time_base
+    }
+}
+
+/// Symphonia decoder giving interleaved samples at a fixed rate and channel
+/// layout, for playback without ffmpeg.
+pub(crate) struct PlaybackDecoder {
+    decoder: AudioDecoder,
+    layout: ChannelLayout,
+    resampler: Resampler,
+    /// Converted samples not read yet.
+    pending: VecDeque<f32>,
+    finished: bool,
+}
+
+impl PlaybackDecoder {
+    /// Start decoding `data` from `start_secs`, converting to `sample_rate`
+    /// and `layout`.
+    pub(crate) fn new(
+        data: Bytes,
+        mime_hint: Option<&str>,
+        sample_rate: u32,
+        layout: ChannelLayout,
+        start_secs: f64,
+    ) -> Result<Self> {
+        let mut decoder = AudioDecoder::from_bytes(data, mime_hint)?;
+        if start_secs > 0.0 {
+            decoder.seek(start_secs)?;
+        }
+        let resampler = Resampler::new(
+            decoder.sample_rate(),
+            sample_rate,
+            usize::from(layout.channels()),
+        )?;
+        Ok(Self {
+            decoder,
+            layout,
+            resampler,
+            pending: VecDeque::new(),
+            finished: false,
+        })
+    }
+
+    /// Get the total duration in seconds, if known.
+    pub(crate) fn duration(&self) -> Option<f64> {
+        self.decoder.duration()
+    }
+
+    /// Get the number of channels of the source.
+    pub(crate) const fn source_channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+
+    /// Read up to `count` samples, fewer only at the end of the track.
+    pub(crate) fn read(&mut self, count: usize) -> Result<Vec<f32>> {
+        while self.pending.len() < count && !self.finished {
+            if let Some(samples) = self.decoder.decode_next()? {
+                let remixed = self
+                    .layout
+                    .remix(&samples, usize::from(self.decoder.channels()));
+                self.pending.extend(self.resampler.process(&remixed)?);
+            } else {
+                self.pending.extend(self.resampler.flush()?);
+                self.finished = true;
+            }
+        }
+        let count = count.min(self.pending.len());
+        Ok(self.pending.drain(..count).collect())
     }
 }
 
@@ -340,4 +442,52 @@ mod tests {
         let decoder = StreamingDecoder::new(Some("audio/webm"));
         assert!(!decoder.is_ready());
     }
+
+    /// A WAV file of one second of a mono 16-bit ramp at 44.1kHz.
+    fn wav() -> Bytes {
+        let samples: Vec<i16> = (0..44_100).map(|i| (i % 1000) as i16).collect();
+        let data_len = (samples.len() * 2) as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&44_100u32.to_le_bytes());
+        wav.extend_from_slice(&88_200u32.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        wav.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+        wav.into()
+    }
+
+    #[test]
+    fn test_playback_decoder_converts_to_output_format() -> Result<()> {
+        let mut decoder =
+            PlaybackDecoder::new(wav(), Some("audio/wav"), 48000, ChannelLayout::Stereo, 0.0)?;
+        assert_eq!(decoder.source_channels(), 1);
+        assert_eq!(decoder.duration(), Some(1.0));
+        let samples = decoder.read(200_000)?;
+        // One second of stereo at 48kHz, give or take the resampler's padding
+        assert!(samples.len().abs_diff(96_000) < 2048, "{}", samples.len());
+        assert!(decoder.read(2048)?.is_empty());
+
+        // Seeking drops the frames before the position in the packet
+        let mut decoder = PlaybackDecoder::new(wav(), None, 44_100, ChannelLayout::Stereo, 0.5)?;
+        let samples = decoder.read(200_000)?;
+        assert_eq!(samples.len(), 44_100);
+        assert!((samples[0] - 50.0 / f32::from(i16::MAX)).abs() < 1e-6);
+        assert!((samples[1] - samples[0]).abs() < f32::EPSILON);
+
+        // Mono plays on the front pair of surround layouts
+        let mut decoder =
+            PlaybackDecoder::new(wav(), None, 44_100, ChannelLayout::Surround51, 0.5)?;
+        let samples = decoder.read(6)?;
+        assert!((samples[1] - samples[0]).abs() < f32::EPSILON);
+        assert!(samples[2..].iter().all(|&sample| sample == 0.0));
+        Ok(())
+    }
 }
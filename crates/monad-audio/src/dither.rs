@@ -0,0 +1,147 @@
+//! Sample formats of the output and dithering.
+//!
+//! The output renders float samples, and devices taking 16-bit integers get
+//! them quantized with TPDF dither: two uniform random values of one LSB each
+//! are added before rounding, turning the distortion of quiet passages and
+//! fades into a steady noise floor far below hearing.
+
+use cpal::{SampleFormat, SizedSample};
+
+/// Sample format the output plays, in order of preference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OutputFormat {
+    /// 32-bit float, played as rendered.
+    #[default]
+    F32,
+    /// Signed 16-bit integer, dithered.
+    I16,
+    /// Unsigned 16-bit integer, dithered.
+    U16,
+}
+
+impl OutputFormat {
+    /// Get the format of a cpal sample format, if the output can play it.
+    pub const fn from_cpal(format: SampleFormat) -> Option<Self> {
+        match format {
+            SampleFormat::F32 => Some(Self::F32),
+            SampleFormat::I16 => Some(Self::I16),
+            SampleFormat::U16 => Some(Self::U16),
+            _ => None,
+        }
+    }
+
+    /// Get the bits per sample.
+    pub const fn bits(self) -> u16 {
+        match self {
+            Self::F32 => 32,
+            Self::I16 | Self::U16 => 16,
+        }
+    }
+
+    /// Check whether samples are dithered as they are converted to the format.
+    pub const fn is_dithered(self) -> bool {
+        !matches!(self, Self::F32)
+    }
+
+    /// Get the name of the format for display.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::F32 => "32-bit float",
+            Self::I16 => "16-bit",
+            Self::U16 => "16-bit unsigned",
+        }
+    }
+}
+
+/// Source of TPDF dither noise.
+#[derive(Debug, Clone)]
+pub(crate) struct Dither {
+    /// State of the xorshift generator, never zero.
+    state: u32,
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+}
+
+impl Dither {
+    /// Get the next uniform random value from -0.5 to 0.5.
+    #[allow(clippy::cast_precision_loss)]
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32 - 0.5
+    }
+
+    /// Get the next noise value in LSBs, triangularly distributed from -1.0
+    /// to 1.0.
+    fn noise(&mut self) -> f32 {
+        self.uniform() + self.uniform()
+    }
+
+    /// Quantize a sample to a signed 16-bit integer.
+    #[allow(clippy::cast_possible_truncation)]
+    fn quantize(&mut self, sample: f32) -> i16 {
+        sample
+            .mul_add(32768.0, self.noise())
+            .round()
+            .clamp(-32768.0, 32767.0) as i16
+    }
+}
+
+/// Sample type the output converts rendered samples to.
+pub(crate) trait OutputSample: SizedSample {
+    /// Convert a rendered sample, dithering it if the type is quantized.
+    fn from_rendered(sample: f32, dither: &mut Dither) -> Self;
+}
+
+impl OutputSample for f32 {
+    fn from_rendered(sample: f32, _dither: &mut Dither) -> Self {
+        sample
+    }
+}
+
+impl OutputSample for i16 {
+    fn from_rendered(sample: f32, dither: &mut Dither) -> Self {
+        dither.quantize(sample)
+    }
+}
+
+impl OutputSample for u16 {
+    #[allow(clippy::cast_sign_loss)]
+    fn from_rendered(sample: f32, dither: &mut Dither) -> Self {
+        // Offset binary: flipping the sign bit adds 32768
+        (dither.quantize(sample) as Self) ^ 0x8000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn test_dithered_conversion() {
+        let mut dither = Dither::default();
+        assert_eq!(i16::from_rendered(2.0, &mut dither), i16::MAX);
+        assert_eq!(i16::from_rendered(-2.0, &mut dither), i16::MIN);
+        assert!((f32::from_rendered(0.25, &mut dither) - 0.25).abs() < f32::EPSILON);
+        assert!(u16::from_rendered(0.0, &mut dither).abs_diff(32768) <= 1);
+
+        // A level between two steps averages out to it, never more than one
+        // step from rounding
+        let level = 100.25 / 32768.0;
+        let samples: Vec<i16> = (0..10_000)
+            .map(|_| i16::from_rendered(level, &mut dither))
+            .collect();
+        assert!(samples.iter().all(|&s| (99..=101).contains(&s)));
+        let mean = samples.iter().map(|&s| f64::from(s)).sum::<f64>() / samples.len() as f64;
+        assert!((mean - 100.25).abs() < 0.02);
+
+        assert!(OutputFormat::from_cpal(SampleFormat::I16).is_some_and(OutputFormat::is_dithered));
+        assert_eq!(OutputFormat::from_cpal(SampleFormat::F64), None);
+    }
+}
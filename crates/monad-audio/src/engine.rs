@@ -1,22 +1,47 @@
 //! Audio playback engine coordinating decode, resample, and output.
 
 use crate::buffer::{shared_ring_buffer, SharedRingBuffer};
-use crate::ffmpeg_decode::{FfmpegDecoder, StreamingFfmpegDecoder};
-use crate::output::AudioOutput;
-use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
-use monad_core::{Error, Result, StreamChunk};
+use crate::channels::ChannelSettings;
+use crate::config::EngineConfig;
+use crate::eq::{EqSettings, Equalizer};
+use crate::ffmpeg_decode::{ffmpeg_available, FfmpegDecoder, StreamingFfmpegDecoder};
+use crate::layout::ChannelLayout;
+use crate::levels::{count_clipped, Levels};
+use crate::loudness::{LoudnessMeter, LoudnessSettings, TrackLoudness};
+use crate::mixer::{Crossfade, MAX_CROSSFADE};
+use crate::output::{AudioOutput, OutputBackend};
+use crate::pitch::{PitchShifter, MAX_PITCH_SEMITONES};
+use crate::priority::ThreadPriority;
+use crate::spectrum::Spectrum;
+use crate::transition::{PendingTransition, TransitionLog, TransitionRecord};
+use bytes::Bytes;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
+use monad_core::{Error, PlaybackSpan, Progress, Result, SkipSegment, StreamChunk};
 use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, error, info, trace, warn};
 
-/// Size of the ring buffer in samples (about 2 seconds at 48kHz stereo).
-const RING_BUFFER_SIZE: usize = 48000 * 2 * 4;
+/// How often to check whether the output device was lost or changed.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
-/// Minimum buffer fill before starting playback (in samples).
-const MIN_BUFFER_FILL: usize = 8192;
+/// Most streamed bytes fed to the streaming decoder at once.
+const FEED_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of the buffer of samples played, read back for analysis (about 85ms
+/// at 48kHz stereo).
+const MONITOR_SIZE: usize = 8192;
+
+/// Time left in a track when [`EngineEvent::NearEnd`] is sent in seconds.
+const NEAR_END_SECS: f64 = 10.0;
+
+/// How often to send the levels and spectrum of the audio playing (about 30
+/// times a second).
+const ANALYSIS_INTERVAL: Duration = Duration::from_millis(33);
 
 /// Playback state of the audio engine.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -43,9 +68,31 @@ pub enum EngineCommand {
     /// Load a new track from URL with optional HTTP headers.
     LoadUrl(String, Option<HashMap<String, String>>),
     /// Load audio data directly.
-    LoadData(Vec<u8>, Option<String>),
+    LoadData(Bytes, Option<String>),
     /// Load audio from a streaming source (enables playback before download completes).
     LoadStreaming(mpsc::Receiver<StreamChunk>),
+    /// Limit playback of the loaded track to a span (reset by each load).
+    SetSpan(PlaybackSpan),
+    /// Skip sections of the loaded track (reset by each load).
+    SetSkipSegments(Vec<SkipSegment>),
+    /// Decode the next track ahead of time, to follow the loaded one without a
+    /// gap (reset by each load).
+    Preload(Bytes, Option<String>),
+    /// Crossfade into the preloaded next track (zero for none, at most
+    /// [`MAX_CROSSFADE`]).
+    SetCrossfade(Duration),
+    /// Set the equalizer band gains.
+    SetEq(EqSettings),
+    /// Shift the pitch by semitones without changing speed (zero for none, at
+    /// most [`MAX_PITCH_SEMITONES`] either way).
+    SetPitch(f32),
+    /// Set loudness normalization.
+    SetLoudness(LoudnessSettings),
+    /// Set the loudness of the loaded track, such as from `ReplayGain` tags,
+    /// instead of measuring it (reset by each load).
+    SetTrackLoudness(TrackLoudness),
+    /// Set the balance, mono downmix and muted channels of the output.
+    SetChannels(ChannelSettings),
     /// Shutdown the engine.
     Shutdown,
 }
@@ -61,11 +108,31 @@ impl std::fmt::Debug for EngineCommand {
             Self::LoadUrl(url, _) => write!(f, "LoadUrl({url})"),
             Self::LoadData(data, mime) => write!(f, "LoadData({} bytes, {:?})", data.len(), mime),
             Self::LoadStreaming(_) => write!(f, "LoadStreaming(...)"),
+            Self::SetSpan(span) => write!(f, "SetSpan({span:?})"),
+            Self::SetSkipSegments(segments) => write!(f, "SetSkipSegments({})", segments.len()),
+            Self::Preload(data, mime) => write!(f, "Preload({} bytes, {:?})", data.len(), mime),
+            Self::SetCrossfade(duration) => write!(f, "SetCrossfade({duration:?})"),
+            Self::SetEq(settings) => write!(f, "SetEq({:?})", settings.gains_db),
+            Self::SetPitch(semitones) => write!(f, "SetPitch({semitones})"),
+            Self::SetLoudness(settings) => write!(f, "SetLoudness({settings:?})"),
+            Self::SetTrackLoudness(loudness) => write!(f, "SetTrackLoudness({loudness:?})"),
+            Self::SetChannels(settings) => write!(f, "SetChannels({settings:?})"),
             Self::Shutdown => write!(f, "Shutdown"),
         }
     }
 }
 
+/// Identifier of a command sent with [`AudioEngine::send_command_with_reply`],
+/// matching it to its [`EngineEvent::CommandCompleted`].
+pub type CommandId = u64;
+
+/// Command on its way to the engine worker.
+struct Request {
+    command: EngineCommand,
+    /// Where to acknowledge the command once handled, if anywhere.
+    reply: Option<(CommandId, oneshot::Sender<Result<()>>)>,
+}
+
 /// Events emitted by the audio engine.
 #[derive(Debug, Clone)]
 pub enum EngineEvent {
@@ -79,18 +146,41 @@ pub enum EngineEvent {
     BufferingProgress(f32),
     /// Track loaded successfully.
     TrackLoaded,
-    /// Playback finished.
+    /// The track ends in this many seconds, sent once when ten seconds or
+    /// less of it are left, to prepare what follows or scrobble it.
+    NearEnd(f64),
+    /// Playback finished, once the output played the last sample.
     PlaybackFinished,
+    /// Playback moved on to the preloaded next track, without any load events.
+    NextTrackStarted,
     /// Error occurred.
     Error(String),
-    /// Download progress for streaming (bytes downloaded).
-    DownloadProgress(u64),
+    /// Download progress for streaming, in bytes.
+    DownloadProgress(Progress),
     /// Stream is buffering (waiting for more data).
     StreamBuffering,
     /// Stream buffer is healthy (enough data to play).
     StreamBufferHealthy,
     /// Stream download completed (seeking now enabled).
     StreamDownloadComplete,
+    /// Output moved to the named device, after the previous one was lost or
+    /// the default device changed. Playback carries on from where it was.
+    DeviceChanged(String),
+    /// Levels of the [`SPECTRUM_BANDS`](crate::spectrum::SPECTRUM_BANDS)
+    /// frequency bands of the audio playing, after volume, lowest first and
+    /// each from 0.0 to 1.0. Sent about 30 times a second while playing.
+    Spectrum(Vec<f32>),
+    /// Levels of the audio playing, after volume, for VU meters. Sent about
+    /// 30 times a second while playing.
+    Levels(Levels),
+    /// A command sent with [`AudioEngine::send_command_with_reply`] was
+    /// handled, after the events it caused.
+    CommandCompleted {
+        /// Identifier the command was sent with.
+        id: CommandId,
+        /// Error the command failed with, if it did.
+        error: Option<String>,
+    },
 }
 
 /// High-performance audio playback engine.
@@ -99,54 +189,109 @@ pub struct AudioEngine {
     state: Arc<RwLock<PlaybackState>>,
     /// Current volume (0.0 to 1.0).
     volume: Arc<Mutex<f32>>,
+    /// Balance and channel settings of the output.
+    channels: Arc<Mutex<ChannelSettings>>,
     /// Current position in seconds.
     position: Arc<RwLock<f64>>,
     /// Total duration in seconds.
     duration: Arc<RwLock<Option<f64>>>,
     /// Command sender.
-    command_tx: Sender<EngineCommand>,
+    command_tx: Sender<Request>,
+    /// Identifier of the next command sent with a reply.
+    next_command_id: AtomicU64,
     /// Event receiver.
     event_rx: Receiver<EngineEvent>,
     /// Ring buffer shared with audio output.
     ring_buffer: SharedRingBuffer,
+    /// Recent track transitions.
+    transitions: TransitionLog,
+    /// Equalizer settings in effect.
+    eq: Arc<RwLock<EqSettings>>,
+    /// Buffering configuration in effect.
+    config: EngineConfig,
 }
 
 impl AudioEngine {
-    /// Create a new audio engine.
+    /// Create a new audio engine on the default output device.
     pub fn new() -> Result<Self> {
+        Self::with_output(OutputBackend::default())
+    }
+
+    /// Create a new audio engine rendering to the given output backend.
+    pub fn with_output(backend: OutputBackend) -> Result<Self> {
+        Self::with_priority(backend, ThreadPriority::default())
+    }
+
+    /// Create a new audio engine whose output thread runs at the given priority.
+    ///
+    /// The engine thread, which decodes, is raised to at most [`ThreadPriority::High`].
+    pub fn with_priority(backend: OutputBackend, priority: ThreadPriority) -> Result<Self> {
+        Self::with_config(backend, priority, EngineConfig::default())
+    }
+
+    /// Create a new audio engine buffering as configured, as far as the
+    /// values fit (see [`EngineConfig::effective`]).
+    pub fn with_config(
+        backend: OutputBackend,
+        priority: ThreadPriority,
+        config: EngineConfig,
+    ) -> Result<Self> {
+        let config = config.effective();
+        info!(
+            "Engine buffering: {} samples, rebuffering below {}, streams starting at {}",
+            config.ring_buffer_size, config.min_buffer_fill, config.streaming_start_fill
+        );
         let (command_tx, command_rx) = unbounded();
         let (event_tx, event_rx) = unbounded();
 
         let state = Arc::new(RwLock::new(PlaybackState::Stopped));
         let volume = Arc::new(Mutex::new(0.85f32)); // Slightly below max for headroom
+        let channels = Arc::new(Mutex::new(ChannelSettings::default()));
         let position = Arc::new(RwLock::new(0.0f64));
         let duration = Arc::new(RwLock::new(None));
-        let ring_buffer = shared_ring_buffer(RING_BUFFER_SIZE);
+        let ring_buffer = shared_ring_buffer(config.ring_buffer_size);
+        let transitions = TransitionLog::default();
+        let eq = Arc::new(RwLock::new(EqSettings::default()));
 
         // Spawn the engine worker thread - it will create the audio output
+        let monitor = shared_ring_buffer(MONITOR_SIZE);
         let state_clone = state.clone();
         let volume_clone = volume.clone();
+        let channels_clone = channels.clone();
         let position_clone = position.clone();
         let duration_clone = duration.clone();
         let ring_buffer_clone = ring_buffer.clone();
+        let transitions_clone = transitions.clone();
+        let eq_clone = eq.clone();
 
         std::thread::Builder::new()
             .name("audio-engine".to_string())
             .spawn(move || {
+                priority
+                    .at_most(ThreadPriority::High)
+                    .apply_to_current_thread("audio-engine");
+
                 // Create audio output inside the worker thread (cpal::Stream is not Send)
-                match AudioOutput::new(
+                match AudioOutput::open(
+                    &backend,
+                    None,
+                    ChannelLayout::Stereo,
+                    priority,
                     ring_buffer_clone.clone(),
+                    monitor.clone(),
                     volume_clone.clone(),
+                    channels_clone.clone(),
                     state_clone.clone(),
                 ) {
                     Ok(output) => {
                         let output_sample_rate = output.sample_rate();
-                        let output_channels = output.channels();
+                        let layout = output.layout();
 
                         info!(
-                            "Audio output initialized: {} Hz, {} channels, device: {}",
+                            "Audio output initialized: {} Hz, {}, {}, device: {}",
                             output_sample_rate,
-                            output_channels,
+                            layout.name(),
+                            output.format().name(),
                             output.device_name()
                         );
 
@@ -155,12 +300,19 @@ impl AudioEngine {
                             event_tx.clone(),
                             state_clone,
                             volume_clone,
+                            channels_clone,
                             position_clone,
                             duration_clone,
                             ring_buffer_clone,
+                            monitor,
+                            transitions_clone,
+                            eq_clone,
+                            config,
                             output,
+                            backend,
+                            priority,
                             output_sample_rate,
-                            output_channels,
+                            layout,
                         );
                         worker.run();
                     }
@@ -177,11 +329,16 @@ impl AudioEngine {
         Ok(Self {
             state,
             volume,
+            channels,
             position,
             duration,
             command_tx,
+            next_command_id: AtomicU64::new(0),
             event_rx,
             ring_buffer,
+            transitions,
+            eq,
+            config,
         })
     }
 
@@ -195,6 +352,16 @@ impl AudioEngine {
         *self.volume.lock()
     }
 
+    /// Get the current balance and channel settings.
+    pub fn channels(&self) -> ChannelSettings {
+        *self.channels.lock()
+    }
+
+    /// Get the buffering configuration in effect.
+    pub const fn config(&self) -> EngineConfig {
+        self.config
+    }
+
     /// Get the ring buffer fill level (0.0 to 1.0).
     #[allow(clippy::cast_precision_loss)]
     pub fn buffer_fill(&self) -> f32 {
@@ -203,6 +370,16 @@ impl AudioEngine {
         available as f32 / capacity as f32
     }
 
+    /// Get the most recent track transitions, oldest first.
+    pub fn transitions(&self) -> Vec<TransitionRecord> {
+        self.transitions.records()
+    }
+
+    /// Get the equalizer settings in effect.
+    pub fn eq(&self) -> EqSettings {
+        *self.eq.read()
+    }
+
     /// Get the current position in seconds.
     pub fn position(&self) -> f64 {
         *self.position.read()
@@ -215,8 +392,42 @@ impl AudioEngine {
 
     /// Send a command to the engine.
     pub fn send_command(&self, command: EngineCommand) -> Result<()> {
+        self.send_request(Request {
+            command,
+            reply: None,
+        })
+    }
+
+    /// Send a command to the engine, getting the identifier of its
+    /// [`EngineEvent::CommandCompleted`] and a receiver of its outcome.
+    ///
+    /// Commands complete once the engine has handled them: loads once the
+    /// track is decoded, or for streams once decoding starts, and seeks once
+    /// the buffer is filled from the new position.
+    pub fn send_command_with_reply(
+        &self,
+        command: EngineCommand,
+    ) -> Result<(CommandId, oneshot::Receiver<Result<()>>)> {
+        let id = self.next_command_id.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.send_request(Request {
+            command,
+            reply: Some((id, reply_tx)),
+        })?;
+        Ok((id, reply_rx))
+    }
+
+    /// Send a command to the engine and wait until it has handled it.
+    pub async fn request(&self, command: EngineCommand) -> Result<()> {
+        let (_, reply) = self.send_command_with_reply(command)?;
+        reply.await.map_err(|_| {
+            Error::AudioOutput("Engine stopped before handling the command".to_string())
+        })?
+    }
+
+    fn send_request(&self, request: Request) -> Result<()> {
         self.command_tx
-            .send(command)
+            .send(request)
             .map_err(|e| Error::AudioOutput(format!("Failed to send command: {e}")))
     }
 
@@ -240,16 +451,34 @@ impl AudioEngine {
         self.send_command(EngineCommand::Seek(position))
     }
 
+    /// Seek to a position in seconds, waiting until the engine has.
+    pub async fn seek_async(&self, position: f64) -> Result<()> {
+        self.request(EngineCommand::Seek(position)).await
+    }
+
     /// Set the volume (0.0 to 1.0).
     pub fn set_volume(&self, volume: f32) -> Result<()> {
         self.send_command(EngineCommand::SetVolume(volume.clamp(0.0, 1.0)))
     }
 
+    /// Set the balance, mono downmix and muted channels of the output.
+    pub fn set_channels(&self, settings: ChannelSettings) -> Result<()> {
+        self.send_command(EngineCommand::SetChannels(ChannelSettings {
+            balance: settings.balance.clamp(-1.0, 1.0),
+            ..settings
+        }))
+    }
+
     /// Load a track from a URL.
     pub fn load_url(&self, url: impl Into<String>) -> Result<()> {
         self.send_command(EngineCommand::LoadUrl(url.into(), None))
     }
 
+    /// Load a track from a URL, waiting until it is loaded.
+    pub async fn load_url_async(&self, url: impl Into<String>) -> Result<()> {
+        self.request(EngineCommand::LoadUrl(url.into(), None)).await
+    }
+
     /// Load a track from a URL with custom HTTP headers.
     pub fn load_url_with_headers(
         &self,
@@ -260,8 +489,75 @@ impl AudioEngine {
     }
 
     /// Load audio data directly.
-    pub fn load_data(&self, data: Vec<u8>, mime_hint: Option<&str>) -> Result<()> {
-        self.send_command(EngineCommand::LoadData(data, mime_hint.map(String::from)))
+    pub fn load_data(&self, data: impl Into<Bytes>, mime_hint: Option<&str>) -> Result<()> {
+        self.send_command(EngineCommand::LoadData(
+            data.into(),
+            mime_hint.map(String::from),
+        ))
+    }
+
+    /// Load audio data directly, waiting until it is loaded.
+    pub async fn load_data_async(
+        &self,
+        data: impl Into<Bytes>,
+        mime_hint: Option<&str>,
+    ) -> Result<()> {
+        self.request(EngineCommand::LoadData(
+            data.into(),
+            mime_hint.map(String::from),
+        ))
+        .await
+    }
+
+    /// Load audio from a download in progress, starting playback once enough
+    /// of it is buffered.
+    pub fn load_streaming(&self, rx: mpsc::Receiver<StreamChunk>) -> Result<()> {
+        self.send_command(EngineCommand::LoadStreaming(rx))
+    }
+
+    /// Limit playback of the loaded track to a span.
+    pub fn set_span(&self, span: PlaybackSpan) -> Result<()> {
+        self.send_command(EngineCommand::SetSpan(span))
+    }
+
+    /// Skip sections of the loaded track.
+    pub fn set_skip_segments(&self, segments: Vec<SkipSegment>) -> Result<()> {
+        self.send_command(EngineCommand::SetSkipSegments(segments))
+    }
+
+    /// Decode the next track ahead of time, to follow the loaded one without a gap.
+    pub fn preload(&self, data: impl Into<Bytes>, mime_hint: Option<&str>) -> Result<()> {
+        self.send_command(EngineCommand::Preload(
+            data.into(),
+            mime_hint.map(String::from),
+        ))
+    }
+
+    /// Crossfade into the preloaded next track over `duration`, zero for none.
+    pub fn set_crossfade(&self, duration: Duration) -> Result<()> {
+        self.send_command(EngineCommand::SetCrossfade(duration.min(MAX_CROSSFADE)))
+    }
+
+    /// Set the equalizer band gains.
+    pub fn set_eq(&self, settings: EqSettings) -> Result<()> {
+        self.send_command(EngineCommand::SetEq(settings))
+    }
+
+    /// Shift the pitch by `semitones` without changing speed, zero for none.
+    pub fn set_pitch(&self, semitones: f32) -> Result<()> {
+        self.send_command(EngineCommand::SetPitch(
+            semitones.clamp(-MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES),
+        ))
+    }
+
+    /// Set loudness normalization.
+    pub fn set_loudness(&self, settings: LoudnessSettings) -> Result<()> {
+        self.send_command(EngineCommand::SetLoudness(settings))
+    }
+
+    /// Set the loudness of the loaded track, such as from `ReplayGain` tags.
+    pub fn set_track_loudness(&self, loudness: TrackLoudness) -> Result<()> {
+        self.send_command(EngineCommand::SetTrackLoudness(loudness))
     }
 
     /// Try to receive an event without blocking.
@@ -289,24 +585,118 @@ impl Default for AudioEngine {
     }
 }
 
-/// Streaming buffer threshold - 5 seconds at 48kHz stereo (480,000 samples).
-const STREAMING_BUFFER_THRESHOLD: usize = 48000 * 2 * 5;
+/// Convert a position to a sample count in `layout`, aligned to whole frames.
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn samples_at(position_secs: f64, sample_rate: u32, layout: ChannelLayout) -> u64 {
+    ((position_secs * f64::from(sample_rate)) as u64) * u64::from(layout.channels())
+}
+
+/// Convert a sample count in `layout` to a position in seconds.
+#[allow(clippy::cast_precision_loss)]
+fn secs_at(samples: u64, sample_rate: u32, layout: ChannelLayout) -> f64 {
+    samples as f64 / (f64::from(sample_rate) * f64::from(layout.channels()))
+}
+
+/// Measure the loudness of a track, decoding it through from the beginning.
+fn measure_track(decoder: &FfmpegDecoder) -> Result<Option<TrackLoudness>> {
+    let mut decoder = decoder.restarted();
+    let mut meter = LoudnessMeter::with_layout(decoder.sample_rate(), decoder.layout());
+    while let Some(samples) = decoder.decode_next()? {
+        meter.push(&samples);
+    }
+    Ok(meter.finish())
+}
+
+/// Write decoded samples to the ring buffer, dropping those in skipped ranges.
+///
+/// `written` is the track position (in samples) of the first sample. Skipped
+/// samples still count as written so position tracking stays in track time.
+/// Returns the number of samples written to the buffer.
+#[allow(clippy::cast_possible_truncation)]
+fn write_skipping(
+    ring_buffer: &SharedRingBuffer,
+    written: &mut u64,
+    skips: &[Range<u64>],
+    samples: &[f32],
+) -> usize {
+    let mut offset = 0;
+    let mut total = 0;
+    while offset < samples.len() {
+        let position = *written;
+        let remaining = (samples.len() - offset) as u64;
+
+        if let Some(skip) = skips.iter().find(|r| r.contains(&position)) {
+            let n = (skip.end - position).min(remaining) as usize;
+            *written += n as u64;
+            offset += n;
+            continue;
+        }
+
+        let next_skip = skips
+            .iter()
+            .filter(|r| r.start > position)
+            .map(|r| r.start)
+            .min();
+        let n = next_skip.map_or(remaining, |start| (start - position).min(remaining)) as usize;
+        let count = ring_buffer.write(&samples[offset..offset + n]);
+        *written += count as u64;
+        total += count;
+        if count < n {
+            break;
+        }
+        offset += n;
+    }
+    total
+}
+
+/// Scale samples by a linear gain.
+fn apply_gain(samples: &mut [f32], gain: f32) {
+    if (gain - 1.0).abs() > f32::EPSILON {
+        for sample in samples {
+            *sample *= gain;
+        }
+    }
+}
 
 /// Internal worker that runs the audio processing loop.
 struct EngineWorker {
-    command_rx: Receiver<EngineCommand>,
+    command_rx: Receiver<Request>,
     event_tx: Sender<EngineEvent>,
     state: Arc<RwLock<PlaybackState>>,
     volume: Arc<Mutex<f32>>,
+    channels: Arc<Mutex<ChannelSettings>>,
     position: Arc<RwLock<f64>>,
     duration: Arc<RwLock<Option<f64>>>,
     ring_buffer: SharedRingBuffer,
+    /// Samples played by the output, after volume.
+    monitor: SharedRingBuffer,
+    /// Spectrum of the samples played.
+    spectrum: Spectrum,
+    /// Samples past full scale decoded since the last levels were sent.
+    clipped: usize,
+    /// When the output will have played the last samples of the track, once
+    /// the ring buffer ran dry at its end.
+    drain_until: Option<Instant>,
+    /// Whether [`EngineEvent::NearEnd`] was sent for the current track.
+    near_end_sent: bool,
+    /// Log of completed transitions.
+    transitions: TransitionLog,
+    /// Transition into the current track, while it is being measured.
+    transition: Option<PendingTransition>,
+    /// Ring buffer underruns already accounted for.
+    underruns_seen: usize,
+    /// Buffering configuration.
+    config: EngineConfig,
     /// Keep output alive for the duration of the worker.
-    _output: AudioOutput,
-    #[allow(dead_code)] // Kept for potential future output configuration
+    output: AudioOutput,
+    /// Backend the output was opened for, to reopen it on device changes.
+    backend: OutputBackend,
+    /// Priority of the output thread.
+    priority: ThreadPriority,
+    /// Sample rate of the output, which tracks are decoded at.
     output_sample_rate: u32,
-    #[allow(dead_code)] // Kept for potential future output configuration
-    output_channels: u16,
+    /// Channel layout of the output, which tracks are decoded to.
+    layout: ChannelLayout,
     /// Current decoder (FFmpeg-based for reliable timing).
     decoder: Option<FfmpegDecoder>,
     /// Samples written since start (for position tracking).
@@ -317,50 +707,111 @@ struct EngineWorker {
     stream_rx: Option<mpsc::Receiver<StreamChunk>>,
     /// Total bytes downloaded during streaming.
     bytes_downloaded: u64,
-    /// Last reported download progress (for threshold-based reporting).
-    last_progress_reported: u64,
     /// Whether the stream download is complete.
     stream_download_complete: bool,
     /// Whether we're in streaming mode.
     is_streaming: bool,
     /// All data accumulated during streaming (for caching and seeking after complete).
     streaming_data: Vec<u8>,
+    /// Bytes of the streamed data fed to the streaming decoder.
+    streaming_fed: usize,
+    /// Ranges of track samples dropped instead of played.
+    skip_ranges: Vec<Range<u64>>,
+    /// Position where playback of the span ends.
+    span_end: Option<f64>,
+    /// Next track, decoded ahead to follow the current one.
+    next: Option<FfmpegDecoder>,
+    /// Loudness of the next track.
+    next_loudness: Option<TrackLoudness>,
+    /// Next track still being decoded and measured in the background.
+    preload_rx: Option<Receiver<Result<(FfmpegDecoder, Option<TrackLoudness>)>>>,
+    /// Length of crossfades into the next track.
+    crossfade: Duration,
+    /// Crossfade into the next track in progress.
+    fade: Option<Crossfade>,
+    /// Equalizer applied to decoded samples.
+    eq: Equalizer,
+    /// Equalizer settings shared with the engine handle.
+    eq_settings: Arc<RwLock<EqSettings>>,
+    /// Pitch shift applied after the equalizer.
+    pitch: PitchShifter,
+    /// Loudness normalization settings.
+    loudness: LoudnessSettings,
+    /// Loudness of the current track, if known.
+    track_loudness: Option<TrackLoudness>,
+    /// Loudness of the current track still being measured in the background.
+    loudness_rx: Option<Receiver<Result<Option<TrackLoudness>>>>,
+    /// Error reported while handling the current command.
+    failure: Option<String>,
 }
 
 impl EngineWorker {
     #[allow(clippy::too_many_arguments)]
     fn new(
-        command_rx: Receiver<EngineCommand>,
+        command_rx: Receiver<Request>,
         event_tx: Sender<EngineEvent>,
         state: Arc<RwLock<PlaybackState>>,
         volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
         position: Arc<RwLock<f64>>,
         duration: Arc<RwLock<Option<f64>>>,
         ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
+        transitions: TransitionLog,
+        eq_settings: Arc<RwLock<EqSettings>>,
+        config: EngineConfig,
         output: AudioOutput,
+        backend: OutputBackend,
+        priority: ThreadPriority,
         output_sample_rate: u32,
-        output_channels: u16,
+        layout: ChannelLayout,
     ) -> Self {
         Self {
             command_rx,
             event_tx,
             state,
             volume,
+            channels,
             position,
             duration,
             ring_buffer,
-            _output: output,
+            monitor,
+            spectrum: Spectrum::new(output_sample_rate),
+            clipped: 0,
+            drain_until: None,
+            near_end_sent: false,
+            transitions,
+            transition: None,
+            underruns_seen: 0,
+            config,
+            output,
+            backend,
+            priority,
             output_sample_rate,
-            output_channels,
+            layout,
             decoder: None,
             samples_written: 0,
             streaming_decoder: None,
             stream_rx: None,
             bytes_downloaded: 0,
-            last_progress_reported: 0,
             stream_download_complete: false,
             is_streaming: false,
             streaming_data: Vec::new(),
+            streaming_fed: 0,
+            skip_ranges: Vec::new(),
+            span_end: None,
+            next: None,
+            next_loudness: None,
+            preload_rx: None,
+            crossfade: Duration::ZERO,
+            fade: None,
+            eq: Equalizer::new(output_sample_rate, layout),
+            eq_settings,
+            pitch: PitchShifter::new(output_sample_rate, layout),
+            loudness: LoudnessSettings::default(),
+            track_loudness: None,
+            loudness_rx: None,
+            failure: None,
         }
     }
 
@@ -369,6 +820,8 @@ impl EngineWorker {
 
         let mut last_position_update = Instant::now();
         let position_update_interval = Duration::from_millis(100);
+        let mut last_device_check = Instant::now();
+        let mut last_analysis = Instant::now();
 
         loop {
             // Check for commands (non-blocking when playing or streaming)
@@ -376,9 +829,9 @@ impl EngineWorker {
                 || *self.state.read() == PlaybackState::Buffering
                 || self.is_streaming;
 
-            let command = if is_active {
+            let request = if is_active {
                 match self.command_rx.try_recv() {
-                    Ok(cmd) => Some(cmd),
+                    Ok(request) => Some(request),
                     Err(TryRecvError::Empty) => None,
                     Err(TryRecvError::Disconnected) => {
                         debug!("Command channel closed, shutting down");
@@ -388,7 +841,7 @@ impl EngineWorker {
             } else {
                 // Block when not playing
                 match self.command_rx.recv_timeout(Duration::from_millis(50)) {
-                    Ok(cmd) => Some(cmd),
+                    Ok(request) => Some(request),
                     Err(crossbeam_channel::RecvTimeoutError::Timeout) => None,
                     Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
                         debug!("Command channel closed, shutting down");
@@ -397,12 +850,13 @@ impl EngineWorker {
                 }
             };
 
-            if let Some(cmd) = command {
-                if matches!(cmd, EngineCommand::Shutdown) {
+            if let Some(request) = request {
+                let shutdown = matches!(request.command, EngineCommand::Shutdown);
+                self.handle_request(request);
+                if shutdown {
                     info!("Audio engine shutting down");
                     break;
                 }
-                self.handle_command(cmd);
             }
 
             // Process streaming if active
@@ -410,6 +864,18 @@ impl EngineWorker {
                 self.process_streaming();
             }
 
+            if self.preload_rx.is_some() {
+                self.poll_preload();
+            }
+            if self.loudness_rx.is_some() {
+                self.poll_loudness(false);
+            }
+
+            if last_device_check.elapsed() >= DEVICE_CHECK_INTERVAL {
+                self.check_output();
+                last_device_check = Instant::now();
+            }
+
             // Process audio if playing
             if *self.state.read() == PlaybackState::Playing {
                 if self.is_streaming {
@@ -418,11 +884,19 @@ impl EngineWorker {
                     self.process_audio();
                 }
 
+                self.update_transition();
+
                 // Update position periodically
                 if last_position_update.elapsed() >= position_update_interval {
                     self.update_position();
+                    self.check_near_end();
+                    self.check_span_end();
                     last_position_update = Instant::now();
                 }
+                if last_analysis.elapsed() >= ANALYSIS_INTERVAL {
+                    self.analyze_output();
+                    last_analysis = Instant::now();
+                }
             }
 
             // Small sleep to prevent busy-waiting
@@ -432,6 +906,170 @@ impl EngineWorker {
         }
     }
 
+    /// Reopen the output if its device was lost, or if the default device it
+    /// follows changed.
+    ///
+    /// Buffered samples stay in the ring buffer, so playback resumes from the
+    /// current position. A failed reopen is retried on the next check.
+    fn check_output(&mut self) {
+        if !self.output.needs_reopen() {
+            return;
+        }
+        // Staying at the current rate and layout if the new device supports them
+        match self.open_output(self.layout) {
+            Ok(output) => {
+                let device_name = output.device_name().to_string();
+                info!("Audio output moved to {device_name}");
+                let (sample_rate, layout) = (output.sample_rate(), output.layout());
+                self.output = output;
+                if sample_rate != self.output_sample_rate || layout != self.layout {
+                    let position = *self.position.read();
+                    self.set_format(sample_rate, layout);
+                    self.restart_decoding(position);
+                }
+                let _ = self.event_tx.send(EngineEvent::DeviceChanged(device_name));
+            }
+            Err(e) => debug!("Failed to reopen audio output: {e}"),
+        }
+    }
+
+    /// Open the output at the current sample rate, playing `layout` if it can.
+    fn open_output(&self, layout: ChannelLayout) -> Result<AudioOutput> {
+        AudioOutput::open(
+            &self.backend,
+            Some(self.output_sample_rate),
+            layout,
+            self.priority,
+            self.ring_buffer.clone(),
+            self.monitor.clone(),
+            self.volume.clone(),
+            self.channels.clone(),
+            self.state.clone(),
+        )
+    }
+
+    /// Play a track with `source_channels` channels in its own layout if the
+    /// output supports it, or in stereo, reopening the output if that changes
+    /// its layout. Called as a track loads, before it is decoded.
+    fn negotiate_layout(&mut self, source_channels: u16) {
+        let layout = ChannelLayout::negotiate(source_channels, self.output.supported_layouts());
+        if layout == self.layout {
+            return;
+        }
+        match self.open_output(layout) {
+            Ok(output) => {
+                let (sample_rate, layout) = (output.sample_rate(), output.layout());
+                self.output = output;
+                self.set_format(sample_rate, layout);
+            }
+            Err(e) => warn!("Failed to reopen audio output in {}: {e}", layout.name()),
+        }
+    }
+
+    /// Analyze the samples played since the last update, sending their
+    /// levels and spectrum.
+    fn analyze_output(&mut self) {
+        let mut played = vec![0.0; self.monitor.available()];
+        let read = self.monitor.read(&mut played);
+        let played = &played[..read];
+
+        let levels = Levels {
+            clipped: std::mem::take(&mut self.clipped),
+            ..Levels::measure(played, self.layout)
+        };
+        if levels.clipped > 0 {
+            debug!(
+                "{} samples clipped after the equalizer and normalization",
+                levels.clipped
+            );
+        }
+        let _ = self.event_tx.send(EngineEvent::Levels(levels));
+
+        self.spectrum.push(played, self.layout);
+        match self.spectrum.bands() {
+            Ok(bands) => {
+                let _ = self.event_tx.send(EngineEvent::Spectrum(bands));
+            }
+            Err(e) => debug!("Failed to analyze spectrum: {e}"),
+        }
+    }
+
+    /// Decode at a new output sample rate and channel layout, converting the
+    /// skipped ranges to them.
+    fn set_format(&mut self, sample_rate: u32, layout: ChannelLayout) {
+        info!(
+            "Output changed from {} Hz in {} to {sample_rate} Hz in {}",
+            self.output_sample_rate,
+            self.layout.name(),
+            layout.name()
+        );
+        let old_rate = std::mem::replace(&mut self.output_sample_rate, sample_rate);
+        let old_layout = std::mem::replace(&mut self.layout, layout);
+        self.eq.set_sample_rate(sample_rate);
+        self.eq.set_layout(layout);
+        self.pitch.set_sample_rate(sample_rate);
+        self.pitch.set_layout(layout);
+        self.spectrum.set_sample_rate(sample_rate);
+        for range in &mut self.skip_ranges {
+            *range = samples_at(
+                secs_at(range.start, old_rate, old_layout),
+                sample_rate,
+                layout,
+            )
+                ..samples_at(
+                    secs_at(range.end, old_rate, old_layout),
+                    sample_rate,
+                    layout,
+                );
+        }
+        self.cancel_crossfade();
+        for decoder in self.decoder.iter_mut().chain(&mut self.next) {
+            if let Err(e) = decoder.set_format(sample_rate, layout) {
+                warn!("Failed to change decoder format: {e}");
+            }
+        }
+    }
+
+    /// Decode again from `position`, such as after the output format changed.
+    fn restart_decoding(&mut self, position: f64) {
+        if !self.is_streaming || self.stream_download_complete {
+            self.seek_to(position);
+        } else if self.streaming_decoder.is_some() {
+            // A live stream can't seek, so decode it again, dropping what was played
+            match StreamingFfmpegDecoder::new(self.output_sample_rate, self.layout) {
+                Ok(decoder) => {
+                    self.streaming_decoder = Some(decoder);
+                    self.streaming_fed = 0;
+                    self.ring_buffer.clear();
+                    self.samples_written = 0;
+                    self.skip_ranges
+                        .push(0..samples_at(position, self.output_sample_rate, self.layout));
+                }
+                Err(e) => warn!("Failed to restart streaming decoder: {e}"),
+            }
+        }
+    }
+
+    /// Handle a request, acknowledging it if it asked to be.
+    fn handle_request(&mut self, request: Request) {
+        self.failure = None;
+        self.handle_command(request.command);
+        if let Some((id, reply)) = request.reply {
+            let error = self.failure.take();
+            let _ = self.event_tx.send(EngineEvent::CommandCompleted {
+                id,
+                error: error.clone(),
+            });
+            let _ = reply.send(error.map_or(Ok(()), |e| Err(Error::AudioOutput(e))));
+        }
+    }
+
+    /// Send an error event, failing the command being handled.
+    fn report_error(&mut self, message: String) {
+        self.failure = Some(message.clone());
+        let _ = self.event_tx.send(EngineEvent::Error(message));
+    }
+
     fn handle_command(&mut self, command: EngineCommand) {
         match command {
             EngineCommand::Play => {
@@ -439,18 +1077,20 @@ impl EngineWorker {
                     self.set_state(PlaybackState::Playing);
                 } else {
                     warn!("Cannot play: no track loaded");
-                    let _ = self
-                        .event_tx
-                        .send(EngineEvent::Error("No track loaded".to_string()));
+                    self.report_error("No track loaded".to_string());
                 }
             }
             EngineCommand::Pause => {
                 self.set_state(PlaybackState::Paused);
             }
             EngineCommand::Stop => {
+                self.transition = None;
+                self.cancel_crossfade();
                 self.set_state(PlaybackState::Stopped);
                 self.ring_buffer.clear();
                 self.samples_written = 0;
+                self.drain_until = None;
+                self.near_end_sent = false;
                 *self.position.write() = 0.0;
                 let _ = self.event_tx.send(EngineEvent::PositionUpdate(0.0));
             }
@@ -460,15 +1100,54 @@ impl EngineWorker {
             EngineCommand::SetVolume(vol) => {
                 *self.volume.lock() = vol;
             }
+            EngineCommand::SetChannels(settings) => {
+                debug!("Channels: {settings:?}");
+                *self.channels.lock() = settings;
+            }
             EngineCommand::LoadUrl(url, headers) => {
+                self.replace_track();
                 self.load_url_internal(&url, headers.as_ref());
             }
             EngineCommand::LoadData(data, mime_hint) => {
+                self.replace_track();
                 self.load_data(data, mime_hint.as_deref());
             }
             EngineCommand::LoadStreaming(rx) => {
+                self.replace_track();
                 self.load_streaming(rx);
             }
+            EngineCommand::SetSpan(span) => {
+                self.set_span(span);
+            }
+            EngineCommand::SetSkipSegments(segments) => {
+                self.set_skip_segments(&segments);
+            }
+            EngineCommand::Preload(data, mime_hint) => {
+                self.preload(data, mime_hint);
+            }
+            EngineCommand::SetCrossfade(duration) => {
+                debug!("Crossfade: {duration:?}");
+                self.crossfade = duration.min(MAX_CROSSFADE);
+            }
+            EngineCommand::SetLoudness(settings) => {
+                debug!("Loudness normalization: {settings:?}");
+                self.loudness = settings;
+                self.measure_loudness();
+            }
+            EngineCommand::SetTrackLoudness(loudness) => {
+                debug!("Track loudness: {loudness:?}");
+                self.track_loudness = Some(loudness);
+                self.loudness_rx = None;
+            }
+            EngineCommand::SetEq(settings) => {
+                debug!("Equalizer: {:?}", settings.gains_db);
+                self.eq.set(EqSettings::new(settings.gains_db));
+                *self.eq_settings.write() = self.eq.settings();
+            }
+            EngineCommand::SetPitch(semitones) => {
+                debug!("Pitch shift: {semitones} semitones");
+                self.pitch.set_semitones(semitones);
+            }
             EngineCommand::Shutdown => {
                 // Handled in the main loop
             }
@@ -481,13 +1160,11 @@ impl EngineWorker {
 
         match self.fetch_url(url, headers) {
             Ok((data, mime_type)) => {
-                self.load_data(data, mime_type.as_deref());
+                self.load_data(data.into(), mime_type.as_deref());
             }
             Err(e) => {
                 error!("Failed to fetch URL: {e}");
-                let _ = self
-                    .event_tx
-                    .send(EngineEvent::Error(format!("Failed to fetch: {e}")));
+                self.report_error(format!("Failed to fetch: {e}"));
                 self.set_state(PlaybackState::Stopped);
             }
         }
@@ -530,7 +1207,7 @@ impl EngineWorker {
         Ok((data, mime_type))
     }
 
-    fn load_data(&mut self, data: Vec<u8>, mime_hint: Option<&str>) {
+    fn load_data(&mut self, data: Bytes, mime_hint: Option<&str>) {
         debug!("Loading {} bytes of audio data", data.len());
         self.set_state(PlaybackState::Buffering);
         let _ = self.event_tx.send(EngineEvent::BufferingProgress(0.1));
@@ -539,16 +1216,33 @@ impl EngineWorker {
         self.ring_buffer.clear();
         self.samples_written = 0;
         self.decoder = None;
+        self.skip_ranges.clear();
+        self.span_end = None;
+        self.next = None;
+        self.preload_rx = None;
+        self.fade = None;
+        self.drain_until = None;
+        self.near_end_sent = false;
+        self.eq.reset();
+        self.pitch.reset();
+        self.track_loudness = None;
+        self.loudness_rx = None;
         *self.position.write() = 0.0;
         *self.duration.write() = None;
 
-        // Create FFmpeg decoder (always outputs 48kHz stereo f32le)
-        match FfmpegDecoder::from_bytes(data, mime_hint) {
-            Ok(decoder) => {
+        // Create the decoder (outputs f32 at the output rate, in its layout)
+        match FfmpegDecoder::from_bytes(data, mime_hint, self.output_sample_rate, self.layout) {
+            Ok(mut decoder) => {
+                self.negotiate_layout(decoder.source_channels());
+                if let Err(e) = decoder.set_format(self.output_sample_rate, self.layout) {
+                    warn!("Failed to change decoder format: {e}");
+                }
                 debug!(
-                    "FFmpeg decoder created: {} Hz, {} channels",
+                    "{:?} decoder created: {} Hz, {} source channels played in {}",
+                    decoder.backend(),
                     decoder.sample_rate(),
-                    decoder.channels()
+                    decoder.source_channels(),
+                    decoder.layout().name()
                 );
 
                 // Get duration
@@ -559,6 +1253,9 @@ impl EngineWorker {
                 }
 
                 self.decoder = Some(decoder);
+                // Loaded tracks start at their normalized level
+                self.measure_loudness();
+                self.poll_loudness(true);
 
                 // Pre-fill buffer before playback
                 self.prefill_buffer();
@@ -571,9 +1268,7 @@ impl EngineWorker {
             }
             Err(e) => {
                 error!("Failed to create decoder: {e}");
-                let _ = self
-                    .event_tx
-                    .send(EngineEvent::Error(format!("Failed to decode: {e}")));
+                self.report_error(format!("Failed to decode: {e}"));
                 self.set_state(PlaybackState::Stopped);
             }
         }
@@ -582,7 +1277,7 @@ impl EngineWorker {
     fn prefill_buffer(&mut self) {
         debug!("Pre-filling buffer...");
 
-        let target_fill = MIN_BUFFER_FILL * 2;
+        let target_fill = self.config.min_buffer_fill * 2;
         let mut filled = 0;
 
         while filled < target_fill {
@@ -605,32 +1300,48 @@ impl EngineWorker {
         // Decode and write samples
         if !self.decode_and_write() {
             // End of stream
-            if self.ring_buffer.is_empty() {
+            if self.ring_buffer.is_empty() && self.played_out() {
                 info!("Playback finished");
-                self.set_state(PlaybackState::Stopped);
-                let _ = self.event_tx.send(EngineEvent::PlaybackFinished);
+                self.finish_playback();
             }
         }
     }
 
     fn decode_and_write(&mut self) -> bool {
+        self.start_crossfade();
+        let until_crossfade = self.samples_until_crossfade();
         let Some(decoder) = &mut self.decoder else {
             return false;
         };
 
-        match decoder.decode_next() {
-            Ok(Some(samples)) => {
-                // FFmpeg already outputs 48kHz stereo, write directly
-                if !samples.is_empty() {
-                    let written = self.ring_buffer.write(&samples);
-                    self.samples_written += written as u64;
-                    trace!("Wrote {} samples to ring buffer", written);
+        // Stop short of a crossfade, so that it starts on time
+        let decoded = match until_crossfade {
+            Some(count) if count < 2048 => decoder.read(count).map(Some),
+            _ => decoder.decode_next(),
+        };
+        match decoded {
+            Ok(Some(mut samples)) => {
+                if let (Some(fade), Some(next)) = (&mut self.fade, &mut self.next) {
+                    let mut incoming = next.read(samples.len()).unwrap_or_else(|e| {
+                        warn!("Failed to decode next track: {e}");
+                        Vec::new()
+                    });
+                    incoming.resize(samples.len(), 0.0);
+                    // The mix is written at the current track's gain
+                    let gain = self.loudness.gain(self.next_loudness.as_ref())
+                        / self.loudness.gain(self.track_loudness.as_ref());
+                    apply_gain(&mut incoming, gain);
+                    fade.mix(&mut samples, &incoming);
                 }
+
+                // FFmpeg already outputs 48kHz stereo, write directly
+                let written = self.write(&mut samples);
+                trace!("Wrote {} samples to ring buffer", written);
                 true
             }
             Ok(None) => {
-                // End of stream
-                false
+                // End of stream, unless the next track follows
+                self.start_next()
             }
             Err(e) => {
                 error!("Decode error: {e}");
@@ -639,15 +1350,206 @@ impl EngineWorker {
         }
     }
 
+    /// Normalize, equalize and pitch shift decoded samples and write them to the
+    /// ring buffer.
+    ///
+    /// Returns the number of samples written to the buffer.
+    fn write(&mut self, samples: &mut [f32]) -> usize {
+        apply_gain(samples, self.loudness.gain(self.track_loudness.as_ref()));
+        self.eq.process(samples);
+        self.pitch.process(samples);
+        self.clipped += count_clipped(samples);
+        write_skipping(
+            &self.ring_buffer,
+            &mut self.samples_written,
+            &self.skip_ranges,
+            samples,
+        )
+    }
+
+    /// Decode the next track on another thread, replacing any preloaded one.
+    fn preload(&mut self, data: Bytes, mime_hint: Option<String>) {
+        debug!("Preloading {} bytes of audio data", data.len());
+        self.cancel_crossfade();
+        self.next = None;
+
+        let measure = self.loudness.enabled;
+        let (sample_rate, layout) = (self.output_sample_rate, self.layout);
+        let (tx, rx) = bounded(1);
+        let spawned = std::thread::Builder::new()
+            .name("audio-preload".to_string())
+            .spawn(move || {
+                // Played in the current layout, so that it can follow without a gap
+                let preloaded =
+                    FfmpegDecoder::from_bytes(data, mime_hint.as_deref(), sample_rate, layout)
+                        .and_then(|decoder| {
+                            let loudness = if measure {
+                                measure_track(&decoder)?
+                            } else {
+                                None
+                            };
+                            Ok((decoder, loudness))
+                        });
+                let _ = tx.send(preloaded);
+            });
+        match spawned {
+            Ok(_) => self.preload_rx = Some(rx),
+            Err(e) => warn!("Failed to spawn preload thread: {e}"),
+        }
+    }
+
+    /// Take the next track once it has been decoded.
+    fn poll_preload(&mut self) {
+        let Some(rx) = &self.preload_rx else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok((mut decoder, loudness))) => {
+                info!("Next track preloaded");
+                // In case the output changed format while it was decoded
+                if let Err(e) = decoder.set_format(self.output_sample_rate, self.layout) {
+                    warn!("Failed to change decoder format: {e}");
+                }
+                self.next = Some(decoder);
+                self.next_loudness = loudness;
+                self.preload_rx = None;
+            }
+            Ok(Err(e)) => {
+                // The next track is loaded as usual once this one finishes
+                warn!("Failed to preload next track: {e}");
+                self.preload_rx = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.preload_rx = None,
+        }
+    }
+
+    /// Get the number of samples of the current track left before a crossfade
+    /// into the next one would start.
+    #[allow(clippy::cast_possible_truncation)]
+    fn samples_until_crossfade(&self) -> Option<usize> {
+        if self.fade.is_some() || self.next.is_none() || self.crossfade.is_zero() {
+            return None;
+        }
+        let remaining = self.decoder.as_ref()?.remaining()?;
+        let crossfade = samples_at(
+            self.crossfade.as_secs_f64(),
+            self.output_sample_rate,
+            self.layout,
+        );
+        Some(remaining.saturating_sub(crossfade as usize))
+    }
+
+    /// Start crossfading into the next track once the current one reaches the
+    /// crossfade before its end.
+    fn start_crossfade(&mut self) {
+        if self.samples_until_crossfade() != Some(0) {
+            return;
+        }
+        if let Some(remaining) = self.decoder.as_ref().and_then(FfmpegDecoder::remaining) {
+            debug!("Crossfading into the next track");
+            self.fade = Some(Crossfade::new(
+                remaining,
+                self.output_sample_rate,
+                self.layout,
+            ));
+        }
+    }
+
+    /// Stop a crossfade, leaving the next track to start from the beginning.
+    fn cancel_crossfade(&mut self) {
+        if self.fade.take().is_some() {
+            if let Some(next) = &mut self.next {
+                next.reset();
+            }
+        }
+    }
+
+    /// Measure the loudness of the loaded track on another thread if it is to
+    /// be normalized.
+    ///
+    /// Streamed tracks are only normalized with a loudness set for them.
+    fn measure_loudness(&mut self) {
+        self.loudness_rx = None;
+        if !self.loudness.enabled || self.track_loudness.is_some() {
+            return;
+        }
+        let Some(decoder) = &self.decoder else {
+            return;
+        };
+
+        let decoder = decoder.restarted();
+        let (tx, rx) = bounded(1);
+        let spawned = std::thread::Builder::new()
+            .name("audio-loudness".to_string())
+            .spawn(move || {
+                let _ = tx.send(measure_track(&decoder));
+            });
+        match spawned {
+            Ok(_) => self.loudness_rx = Some(rx),
+            Err(e) => warn!("Failed to spawn loudness thread: {e}"),
+        }
+    }
+
+    /// Take the loudness of the current track once it has been measured,
+    /// waiting for it if `wait` is set.
+    fn poll_loudness(&mut self, wait: bool) {
+        let Some(rx) = &self.loudness_rx else {
+            return;
+        };
+        let measured = if wait {
+            rx.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            rx.try_recv()
+        };
+        match measured {
+            Ok(Ok(loudness)) => {
+                debug!("Measured track loudness: {loudness:?}");
+                self.track_loudness = loudness;
+                self.loudness_rx = None;
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to measure track loudness: {e}");
+                self.loudness_rx = None;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.loudness_rx = None,
+        }
+    }
+
+    /// Move on to the preloaded next track, returning false if there is none.
+    fn start_next(&mut self) -> bool {
+        let Some(next) = self.next.take() else {
+            return false;
+        };
+        let fade = self.fade.take();
+        info!("Starting preloaded track");
+
+        // The part of the next track already mixed in has been written
+        self.samples_written = fade.as_ref().map_or(0, |fade| fade.mixed() as u64);
+        self.skip_ranges.clear();
+        self.span_end = None;
+        self.track_loudness = self.next_loudness.take();
+        *self.duration.write() = next.duration();
+        if let Some(dur) = next.duration() {
+            let _ = self.event_tx.send(EngineEvent::DurationUpdate(dur));
+        }
+        self.decoder = Some(next);
+        self.near_end_sent = false;
+        self.measure_loudness();
+        let _ = self.event_tx.send(EngineEvent::NextTrackStarted);
+        self.begin_transition(true, fade.as_ref().map(Crossfade::duration));
+        true
+    }
+
     fn seek_to(&mut self, position_secs: f64) {
         debug!("Seeking to {:.2} seconds", position_secs);
+        self.cancel_crossfade();
 
         // Disable seeking during streaming download
         if self.is_streaming && !self.stream_download_complete {
             warn!("Seeking disabled during streaming download");
-            let _ = self.event_tx.send(EngineEvent::Error(
-                "Seeking disabled during download".to_string(),
-            ));
+            self.report_error("Seeking disabled during download".to_string());
             return;
         }
 
@@ -658,7 +1560,12 @@ impl EngineWorker {
             self.is_streaming = false;
 
             // Create regular decoder from accumulated data
-            match FfmpegDecoder::from_bytes(std::mem::take(&mut self.streaming_data), None) {
+            match FfmpegDecoder::from_bytes(
+                std::mem::take(&mut self.streaming_data).into(),
+                None,
+                self.output_sample_rate,
+                self.layout,
+            ) {
                 Ok(decoder) => {
                     if let Some(dur) = decoder.duration() {
                         *self.duration.write() = Some(dur);
@@ -668,9 +1575,7 @@ impl EngineWorker {
                 }
                 Err(e) => {
                     error!("Failed to create decoder from streaming data: {e}");
-                    let _ = self
-                        .event_tx
-                        .send(EngineEvent::Error(format!("Seek failed: {e}")));
+                    self.report_error(format!("Seek failed: {e}"));
                     return;
                 }
             }
@@ -679,20 +1584,20 @@ impl EngineWorker {
         if let Some(decoder) = &mut self.decoder {
             // Clear buffer
             self.ring_buffer.clear();
+            self.eq.reset();
+            self.pitch.reset();
 
             // Seek decoder
             if let Err(e) = decoder.seek(position_secs) {
                 warn!("Seek failed: {e}");
-                let _ = self
-                    .event_tx
-                    .send(EngineEvent::Error(format!("Seek failed: {e}")));
+                self.report_error(format!("Seek failed: {e}"));
                 return;
             }
 
-            // Update position tracking (48kHz stereo = 2 samples per frame)
-            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-            let samples_at_position = (position_secs * 48000.0 * 2.0) as u64;
-            self.samples_written = samples_at_position;
+            // Update position tracking
+            self.samples_written = samples_at(position_secs, self.output_sample_rate, self.layout);
+            self.drain_until = None;
+            self.near_end_sent = false;
 
             *self.position.write() = position_secs;
             let _ = self
@@ -701,17 +1606,25 @@ impl EngineWorker {
 
             // Pre-fill buffer after seek
             self.prefill_buffer();
+        } else {
+            // Not worth an error event, but the seek did nothing
+            self.failure = Some("No track loaded".to_string());
         }
     }
 
-    #[allow(clippy::cast_precision_loss)]
     fn update_position(&self) {
-        // Calculate position from samples written and consumed
+        // Calculate position from samples written and consumed, less those
+        // still on their way through the output
         let samples_in_buffer = self.ring_buffer.available() as u64;
-        let samples_consumed = self.samples_written.saturating_sub(samples_in_buffer);
-
-        // Convert to seconds (FFmpeg outputs 48kHz stereo = 96000 samples/sec)
-        let position_secs = samples_consumed as f64 / (48000.0 * 2.0);
+        let samples_in_output = samples_at(
+            self.output.latency().as_secs_f64(),
+            self.output_sample_rate,
+            self.layout,
+        );
+        let samples_heard = self
+            .samples_written
+            .saturating_sub(samples_in_buffer + samples_in_output);
+        let position_secs = secs_at(samples_heard, self.output_sample_rate, self.layout);
 
         *self.position.write() = position_secs;
         let _ = self
@@ -719,6 +1632,175 @@ impl EngineWorker {
             .send(EngineEvent::PositionUpdate(position_secs));
     }
 
+    /// Limit playback to a span of the loaded track.
+    fn set_span(&mut self, span: PlaybackSpan) {
+        debug!("Playback span: {span:?}");
+        self.span_end = span.end;
+        if span.start <= 0.0 {
+            return;
+        }
+
+        if self.is_streaming && !self.stream_download_complete {
+            // A live stream can't seek, so discard audio up to the start instead
+            let start = samples_at(span.start, self.output_sample_rate, self.layout);
+            if self.samples_written > start {
+                warn!("Span start already decoded, playing from the beginning");
+                return;
+            }
+            self.ring_buffer.clear();
+            self.skip_ranges.push(0..start);
+        } else {
+            self.seek_to(span.start);
+        }
+    }
+
+    /// Skip sections of the loaded track as they are decoded.
+    fn set_skip_segments(&mut self, segments: &[SkipSegment]) {
+        debug!("Skipping {} segments", segments.len());
+        self.skip_ranges.extend(
+            segments
+                .iter()
+                .filter(|segment| segment.end > segment.start)
+                .map(|segment| {
+                    samples_at(segment.start, self.output_sample_rate, self.layout)
+                        ..samples_at(segment.end, self.output_sample_rate, self.layout)
+                }),
+        );
+
+        // Audio already buffered is played unless it starts inside a segment
+        let playing = self
+            .samples_written
+            .saturating_sub(self.ring_buffer.available() as u64);
+        let Some(current) = self.skip_ranges.iter().find(|r| r.contains(&playing)) else {
+            return;
+        };
+        if self.is_streaming && !self.stream_download_complete {
+            // Decoding carries on past the segment from where it left off
+            self.ring_buffer.clear();
+        } else {
+            let end = secs_at(current.end, self.output_sample_rate, self.layout);
+            self.seek_to(end);
+        }
+    }
+
+    /// Finish playback once the position passes the span end.
+    fn check_span_end(&mut self) {
+        let Some(end) = self.span_end else {
+            return;
+        };
+        if *self.position.read() < end {
+            return;
+        }
+
+        info!("Reached end of playback span at {end:.2}s");
+        self.span_end = None;
+        self.ring_buffer.clear();
+        self.finish_playback();
+    }
+
+    /// Check whether the output has played the last samples it took from the
+    /// ring buffer, which ran dry at the end of the track.
+    ///
+    /// The first call starts waiting for the output latency to pass.
+    fn played_out(&mut self) -> bool {
+        let latency = self.output.latency();
+        let deadline = *self
+            .drain_until
+            .get_or_insert_with(|| Instant::now() + latency);
+        if Instant::now() < deadline {
+            return false;
+        }
+        self.drain_until = None;
+        true
+    }
+
+    /// Send [`EngineEvent::NearEnd`] once the time left in the track, or the
+    /// span of it played, drops to [`NEAR_END_SECS`].
+    fn check_near_end(&mut self) {
+        let end = [*self.duration.read(), self.span_end]
+            .into_iter()
+            .flatten()
+            .reduce(f64::min);
+        let Some(end) = end.filter(|_| !self.near_end_sent) else {
+            return;
+        };
+        let secs_left = end - *self.position.read();
+        if secs_left <= NEAR_END_SECS {
+            self.near_end_sent = true;
+            let _ = self.event_tx.send(EngineEvent::NearEnd(secs_left.max(0.0)));
+        }
+    }
+
+    /// Stop at the end of the track and start measuring the transition.
+    ///
+    /// A preloaded next track starts playing instead.
+    fn finish_playback(&mut self) {
+        if self.start_next() {
+            return;
+        }
+        self.set_state(PlaybackState::Stopped);
+        let _ = self.event_tx.send(EngineEvent::PlaybackFinished);
+        self.begin_transition(true, None);
+    }
+
+    /// Note that a new track is being loaded.
+    ///
+    /// Replacing a playing track starts a transition, the same as finishing it.
+    fn replace_track(&mut self) {
+        if *self.state.read() == PlaybackState::Playing {
+            self.begin_transition(false, None);
+        } else if self
+            .transition
+            .as_ref()
+            .is_some_and(PendingTransition::has_started)
+        {
+            // The next track was replaced before it played long enough
+            self.end_transition();
+        }
+    }
+
+    fn begin_transition(&mut self, finished: bool, fade: Option<Duration>) {
+        if self
+            .transition
+            .as_ref()
+            .is_some_and(PendingTransition::has_started)
+        {
+            self.end_transition();
+        }
+        let pending = PendingTransition::new(finished);
+        self.transition = Some(match fade {
+            Some(fade) => pending.with_fade(fade),
+            None => pending,
+        });
+    }
+
+    fn end_transition(&mut self) {
+        if let Some(pending) = self.transition.take() {
+            let record = pending.finish();
+            debug!("Track transition: {record:?}");
+            self.transitions.push(record);
+        }
+    }
+
+    /// Measure the transition into the current track while it plays.
+    fn update_transition(&mut self) {
+        let underruns = self.ring_buffer.underruns();
+        let new_underruns = underruns.saturating_sub(self.underruns_seen);
+        self.underruns_seen = underruns;
+
+        let Some(pending) = &mut self.transition else {
+            return;
+        };
+        if !pending.start() {
+            self.transition = None;
+            return;
+        }
+        pending.add_underruns(u32::try_from(new_underruns).unwrap_or(u32::MAX));
+        if pending.is_complete() {
+            self.end_transition();
+        }
+    }
+
     /// Load audio from a streaming source.
     fn load_streaming(&mut self, rx: mpsc::Receiver<StreamChunk>) {
         info!("Loading streaming audio");
@@ -732,15 +1814,36 @@ impl EngineWorker {
         self.streaming_decoder = None;
         self.stream_rx = None;
         self.bytes_downloaded = 0;
-        self.last_progress_reported = 0;
         self.stream_download_complete = false;
         self.is_streaming = true;
         self.streaming_data.clear();
+        self.streaming_fed = 0;
+        self.skip_ranges.clear();
+        self.span_end = None;
+        self.next = None;
+        self.preload_rx = None;
+        self.fade = None;
+        self.drain_until = None;
+        self.near_end_sent = false;
+        self.eq.reset();
+        self.pitch.reset();
+        self.track_loudness = None;
+        self.loudness_rx = None;
         *self.position.write() = 0.0;
         *self.duration.write() = None;
 
+        if !ffmpeg_available() {
+            // Symphonia can't decode a partial download, so wait for all of it
+            info!("ffmpeg not found, decoding the stream once downloaded");
+            self.stream_rx = Some(rx);
+            return;
+        }
+
+        // A stream's channels are only known once it decodes, so it plays in stereo
+        self.negotiate_layout(ChannelLayout::Stereo.channels());
+
         // Create streaming decoder
-        match StreamingFfmpegDecoder::new() {
+        match StreamingFfmpegDecoder::new(self.output_sample_rate, self.layout) {
             Ok(decoder) => {
                 self.streaming_decoder = Some(decoder);
                 self.stream_rx = Some(rx);
@@ -748,9 +1851,7 @@ impl EngineWorker {
             }
             Err(e) => {
                 error!("Failed to create streaming decoder: {e}");
-                let _ = self
-                    .event_tx
-                    .send(EngineEvent::Error(format!("Failed to create decoder: {e}")));
+                self.report_error(format!("Failed to create decoder: {e}"));
                 self.set_state(PlaybackState::Stopped);
                 self.is_streaming = false;
             }
@@ -783,42 +1884,18 @@ impl EngineWorker {
                 StreamChunk::Data(data) => {
                     self.bytes_downloaded += data.len() as u64;
                     self.streaming_data.extend_from_slice(&data);
-
-                    // Feed to streaming decoder
-                    if let Some(ref decoder) = self.streaming_decoder {
-                        if let Err(e) = decoder.feed(data) {
-                            warn!("Error feeding decoder: {e}");
-                        }
-                    }
-
-                    // Report download progress using threshold-based approach (every 128KB)
-                    if self.bytes_downloaded >= self.last_progress_reported + 128 * 1024 {
-                        self.last_progress_reported = self.bytes_downloaded;
-                        let _ = self
-                            .event_tx
-                            .send(EngineEvent::DownloadProgress(self.bytes_downloaded));
-                    }
+                }
+                StreamChunk::Progress(progress) => {
+                    let _ = self.event_tx.send(EngineEvent::DownloadProgress(progress));
                 }
                 StreamChunk::Complete => {
                     info!("Stream download complete: {} bytes", self.bytes_downloaded);
                     self.stream_download_complete = true;
-
-                    // Signal EOF to decoder
-                    if let Some(ref mut decoder) = self.streaming_decoder {
-                        decoder.finish_input();
-                    }
-
                     let _ = self.event_tx.send(EngineEvent::StreamDownloadComplete);
                 }
-                StreamChunk::Error(err) => {
-                    error!("Stream error: {err}");
-                    let _ = self.event_tx.send(EngineEvent::Error(err));
-
-                    // If we have enough buffered data, continue with what we have
-                    if self.ring_buffer.available() < MIN_BUFFER_FILL {
-                        self.set_state(PlaybackState::Stopped);
-                        self.is_streaming = false;
-                    }
+                StreamChunk::Error(err) => self.stream_failed(err),
+                StreamChunk::Stalled(reason) => {
+                    self.stream_failed(format!("Download stalled: {reason}"));
                 }
             }
         }
@@ -826,27 +1903,29 @@ impl EngineWorker {
         // Handle channel disconnection
         if channel_disconnected {
             debug!("Stream channel disconnected");
-            if !self.stream_download_complete {
-                self.stream_download_complete = true;
-                if let Some(ref mut decoder) = self.streaming_decoder {
-                    decoder.finish_input();
-                }
+            self.stream_download_complete = true;
+        }
+
+        if self.streaming_decoder.is_none() {
+            if self.stream_download_complete && self.is_streaming {
+                self.load_downloaded();
             }
+            return;
         }
 
+        self.feed_streaming_decoder();
+
         // Read decoded PCM from streaming decoder and fill buffer
-        if let Some(ref mut decoder) = self.streaming_decoder {
-            while self.ring_buffer.free() >= 4096 {
-                if let Some(samples) = decoder.try_decode_next() {
-                    if !samples.is_empty() {
-                        let written = self.ring_buffer.write(&samples);
-                        self.samples_written += written as u64;
-                        trace!("Streaming: wrote {} samples to buffer", written);
-                    }
-                } else {
-                    break;
-                }
-            }
+        while self.ring_buffer.free() >= 4096 {
+            let Some(mut samples) = self
+                .streaming_decoder
+                .as_mut()
+                .and_then(StreamingFfmpegDecoder::try_decode_next)
+            else {
+                break;
+            };
+            let written = self.write(&mut samples);
+            trace!("Streaming: wrote {} samples to buffer", written);
         }
 
         // Check if we should start playback
@@ -855,7 +1934,7 @@ impl EngineWorker {
             let buffer_fill = self.ring_buffer.available();
 
             // Start playback when we have enough buffer OR download is complete
-            if buffer_fill >= STREAMING_BUFFER_THRESHOLD || self.stream_download_complete {
+            if buffer_fill >= self.config.streaming_start_fill || self.stream_download_complete {
                 info!(
                     "Starting streaming playback: {} samples buffered",
                     buffer_fill
@@ -866,7 +1945,7 @@ impl EngineWorker {
             } else {
                 // Report buffering progress
                 #[allow(clippy::cast_precision_loss)]
-                let progress = buffer_fill as f32 / STREAMING_BUFFER_THRESHOLD as f32;
+                let progress = buffer_fill as f32 / self.config.streaming_start_fill as f32;
                 let _ = self
                     .event_tx
                     .send(EngineEvent::BufferingProgress(progress.min(1.0)));
@@ -879,9 +1958,11 @@ impl EngineWorker {
                 if decoder.is_complete() && self.ring_buffer.is_empty() {
                     // All data has been processed
                     if *self.state.read() == PlaybackState::Playing {
+                        if !self.played_out() {
+                            return;
+                        }
                         info!("Streaming playback finished");
-                        self.set_state(PlaybackState::Stopped);
-                        let _ = self.event_tx.send(EngineEvent::PlaybackFinished);
+                        self.finish_playback();
                     }
                     self.is_streaming = false;
                 }
@@ -889,26 +1970,74 @@ impl EngineWorker {
         }
     }
 
+    /// Load a stream downloaded without a streaming decoder as a whole track.
+    fn load_downloaded(&mut self) {
+        self.is_streaming = false;
+        self.stream_rx = None;
+        let data = std::mem::take(&mut self.streaming_data);
+        self.load_data(data.into(), None);
+    }
+
+    /// Feed downloaded data to the streaming decoder as fast as it decodes,
+    /// signalling EOF once the download is complete and all of it is fed.
+    ///
+    /// Data the decoder is not ready for stays in `streaming_data`, so the
+    /// worker never waits on a decoder paused by unread audio.
+    fn feed_streaming_decoder(&mut self) {
+        let Some(decoder) = &mut self.streaming_decoder else {
+            return;
+        };
+        while self.streaming_fed < self.streaming_data.len() {
+            let end = (self.streaming_fed + FEED_CHUNK_SIZE).min(self.streaming_data.len());
+            match decoder.try_feed(&self.streaming_data[self.streaming_fed..end]) {
+                Ok(true) => self.streaming_fed = end,
+                Ok(false) => return,
+                Err(e) => {
+                    warn!("Error feeding decoder: {e}");
+                    return;
+                }
+            }
+        }
+        if self.stream_download_complete {
+            decoder.finish_input();
+        }
+    }
+
+    /// Report a failed stream download, stopping unless enough audio is buffered.
+    fn stream_failed(&mut self, err: String) {
+        error!("Stream error: {err}");
+        let _ = self.event_tx.send(EngineEvent::Error(err));
+
+        // If we have enough buffered data, continue with what we have
+        if self.ring_buffer.available() < self.config.min_buffer_fill {
+            self.set_state(PlaybackState::Stopped);
+            self.is_streaming = false;
+        }
+    }
+
     /// Process audio during streaming playback.
     fn process_streaming_audio(&mut self) {
         // Read more decoded samples from streaming decoder
-        if let Some(ref mut decoder) = self.streaming_decoder {
-            while self.ring_buffer.free() >= 2048 {
-                if let Some(samples) = decoder.try_decode_next() {
-                    if !samples.is_empty() {
-                        let written = self.ring_buffer.write(&samples);
-                        self.samples_written += written as u64;
-                    }
-                } else {
-                    break;
-                }
-            }
+        while self.ring_buffer.free() >= 2048 {
+            let Some(mut samples) = self
+                .streaming_decoder
+                .as_mut()
+                .and_then(StreamingFfmpegDecoder::try_decode_next)
+            else {
+                break;
+            };
+            self.write(&mut samples);
         }
 
         // Check for buffer underrun
-        if self.ring_buffer.available() < MIN_BUFFER_FILL && !self.stream_download_complete {
+        if self.ring_buffer.available() < self.config.min_buffer_fill
+            && !self.stream_download_complete
+        {
             // Need to rebuffer
             info!("Buffer underrun during streaming, rebuffering...");
+            if let Some(pending) = &mut self.transition {
+                pending.add_underruns(1);
+            }
             let _ = self.event_tx.send(EngineEvent::StreamBuffering);
             self.set_state(PlaybackState::Buffering);
         }
@@ -916,10 +2045,9 @@ impl EngineWorker {
         // Check for end of stream
         if self.stream_download_complete {
             if let Some(ref decoder) = self.streaming_decoder {
-                if decoder.is_complete() && self.ring_buffer.is_empty() {
+                if decoder.is_complete() && self.ring_buffer.is_empty() && self.played_out() {
                     info!("Streaming playback finished");
-                    self.set_state(PlaybackState::Stopped);
-                    let _ = self.event_tx.send(EngineEvent::PlaybackFinished);
+                    self.finish_playback();
                     self.is_streaming = false;
                 }
             }
@@ -950,6 +2078,28 @@ mod tests {
         assert_eq!(PlaybackState::default(), PlaybackState::Stopped);
     }
 
+    #[test]
+    fn test_write_skipping() {
+        let ring_buffer = shared_ring_buffer(64);
+        let samples: Vec<f32> = (0..8u8).map(f32::from).collect();
+        let mut written = 10;
+
+        // Track samples 12..14 and 16..20 are skipped
+        let skips = [12..14, 16..20];
+        assert_eq!(
+            write_skipping(&ring_buffer, &mut written, &skips, &samples),
+            4
+        );
+        assert_eq!(written, 18);
+
+        let mut output = [0.0; 4];
+        ring_buffer.read(&mut output);
+        assert!(output.iter().eq(&[0.0, 1.0, 4.0, 5.0]));
+        assert_eq!(samples_at(1.5, 48000, ChannelLayout::Stereo), 144_000);
+        assert_eq!(samples_at(1.5, 48000, ChannelLayout::Surround51), 432_000);
+        assert!((secs_at(132_300, 44100, ChannelLayout::Stereo) - 1.5).abs() < f64::EPSILON);
+    }
+
     // Note: Engine creation test requires audio hardware
     // and may fail in CI environments without audio devices
 }
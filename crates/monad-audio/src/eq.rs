@@ -0,0 +1,324 @@
+//! Ten-band graphic equalizer.
+//!
+//! Each band is a peaking biquad filter centred an octave above the last,
+//! applied to decoded samples before they reach the ring buffer. Bands left
+//! at 0 dB are skipped, so a flat equalizer costs nothing.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::ChannelLayout;
+
+/// Centre frequencies of the bands in Hz.
+pub const EQ_FREQUENCIES: [f64; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Largest boost or cut of a band in dB.
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+/// Sample rate the response of the equalizer is drawn at.
+const SAMPLE_RATE: f64 = 48000.0;
+
+/// Quality factor giving each band a bandwidth of about an octave.
+const BAND_Q: f64 = std::f64::consts::SQRT_2;
+
+/// Gains of the equalizer bands.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct EqSettings {
+    /// Gain of each band in dB, lowest frequency first.
+    pub gains_db: [f32; 10],
+}
+
+impl EqSettings {
+    /// Create settings from band gains, clamped to [`MAX_GAIN_DB`].
+    pub fn new(gains_db: [f32; 10]) -> Self {
+        Self {
+            gains_db: gains_db.map(|gain| gain.clamp(-MAX_GAIN_DB, MAX_GAIN_DB)),
+        }
+    }
+
+    /// Check whether every band is at 0 dB.
+    pub fn is_flat(&self) -> bool {
+        self.gains_db.iter().all(|&gain| gain == 0.0)
+    }
+
+    /// Get the gain of the equalizer at a frequency in dB, to draw its curve.
+    pub fn response_db(&self, frequency: f64) -> f64 {
+        EQ_FREQUENCIES
+            .iter()
+            .zip(self.gains_db)
+            .filter(|(_, gain)| *gain != 0.0)
+            .map(|(&centre, gain)| {
+                Biquad::peaking(centre, f64::from(gain), SAMPLE_RATE).response_db(frequency)
+            })
+            .sum()
+    }
+}
+
+/// Built-in equalizer settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EqPreset {
+    /// All bands at 0 dB.
+    #[default]
+    Flat,
+    /// Boosted lows.
+    BassBoost,
+    /// Boosted highs.
+    TrebleBoost,
+    /// Boosted mids, for voices.
+    Vocal,
+    /// Scooped mids.
+    Rock,
+    /// Gently boosted lows and highs.
+    Pop,
+    /// Warm lows and soft highs.
+    Jazz,
+    /// Slightly lifted highs.
+    Classical,
+    /// Strong lows and crisp highs.
+    Electronic,
+}
+
+impl EqPreset {
+    /// Get all presets.
+    pub const fn all() -> &'static [Self] {
+        &[
+            Self::Flat,
+            Self::BassBoost,
+            Self::TrebleBoost,
+            Self::Vocal,
+            Self::Rock,
+            Self::Pop,
+            Self::Jazz,
+            Self::Classical,
+            Self::Electronic,
+        ]
+    }
+
+    /// Get display name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "Flat",
+            Self::BassBoost => "Bass Boost",
+            Self::TrebleBoost => "Treble Boost",
+            Self::Vocal => "Vocal",
+            Self::Rock => "Rock",
+            Self::Pop => "Pop",
+            Self::Jazz => "Jazz",
+            Self::Classical => "Classical",
+            Self::Electronic => "Electronic",
+        }
+    }
+
+    /// Get the band gains of the preset.
+    pub fn settings(self) -> EqSettings {
+        EqSettings::new(match self {
+            Self::Flat => [0.0; 10],
+            Self::BassBoost => [6.0, 5.0, 4.0, 2.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Self::TrebleBoost => [0.0, 0.0, 0.0, 0.0, 0.0, 0.5, 2.0, 4.0, 5.0, 6.0],
+            Self::Vocal => [-2.0, -1.5, -1.0, 1.0, 3.0, 3.5, 3.0, 1.5, 0.0, -1.0],
+            Self::Rock => [4.5, 3.5, 2.0, -0.5, -1.5, -1.0, 1.0, 2.5, 3.5, 4.0],
+            Self::Pop => [-1.0, 1.0, 2.5, 3.0, 2.0, 0.0, -0.5, -0.5, 1.0, 1.5],
+            Self::Jazz => [3.0, 2.0, 1.0, 1.5, -1.0, -1.0, 0.0, 1.0, 2.0, 2.5],
+            Self::Classical => [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0, -1.0, 1.5, 3.0],
+            Self::Electronic => [5.0, 4.0, 1.5, 0.0, -1.5, 1.0, 0.5, 1.5, 4.0, 4.5],
+        })
+    }
+}
+
+/// Biquad filter coefficients, normalized so that `a0` is 1.
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad {
+    /// Create a peaking filter boosting or cutting around `centre` Hz.
+    fn peaking(centre: f64, gain_db: f64, sample_rate: f64) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * centre / sample_rate;
+        let alpha = w0.sin() / (2.0 * BAND_Q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha / a;
+        Self {
+            b0: alpha.mul_add(a, 1.0) / a0,
+            b1: -2.0 * cos / a0,
+            b2: (-alpha).mul_add(a, 1.0) / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+
+    /// Get the gain of the filter at a frequency in dB.
+    fn response_db(&self, frequency: f64) -> f64 {
+        let w = 2.0 * PI * frequency / SAMPLE_RATE;
+        let (sin, cos) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+        let num_re = self.b2.mul_add(cos2, self.b1.mul_add(cos, self.b0));
+        let num_im = self.b2.mul_add(sin2, self.b1 * sin);
+        let den_re = self.a2.mul_add(cos2, self.a1.mul_add(cos, 1.0));
+        let den_im = self.a2.mul_add(sin2, self.a1 * sin);
+        let power =
+            num_re.mul_add(num_re, num_im * num_im) / den_re.mul_add(den_re, den_im * den_im);
+        10.0 * power.log10()
+    }
+}
+
+/// Filter memory of one band on one channel.
+#[derive(Debug, Clone, Copy, Default)]
+struct FilterState {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl FilterState {
+    fn process(&mut self, filter: &Biquad, x: f64) -> f64 {
+        let feedforward = filter
+            .b2
+            .mul_add(self.x2, filter.b1.mul_add(self.x1, filter.b0 * x));
+        let y = filter
+            .a2
+            .mul_add(-self.y2, filter.a1.mul_add(-self.y1, feedforward));
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Equalizer applied to interleaved samples.
+#[derive(Debug)]
+pub(crate) struct Equalizer {
+    settings: EqSettings,
+    sample_rate: f64,
+    /// Channels of the samples to filter.
+    channels: usize,
+    /// Filters of the bands that are not flat, with their state per channel.
+    bands: Vec<(Biquad, Vec<FilterState>)>,
+}
+
+impl Equalizer {
+    /// Create a flat equalizer for samples at `sample_rate` in `layout`.
+    pub(crate) fn new(sample_rate: u32, layout: ChannelLayout) -> Self {
+        Self {
+            settings: EqSettings::default(),
+            sample_rate: f64::from(sample_rate),
+            channels: usize::from(layout.channels()),
+            bands: Vec::new(),
+        }
+    }
+
+    /// Change the sample rate of the samples to filter.
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = f64::from(sample_rate);
+        self.set(self.settings);
+    }
+
+    /// Change the channel layout of the samples to filter.
+    pub(crate) fn set_layout(&mut self, layout: ChannelLayout) {
+        self.channels = usize::from(layout.channels());
+        self.set(self.settings);
+    }
+
+    /// Get the current settings.
+    pub(crate) const fn settings(&self) -> EqSettings {
+        self.settings
+    }
+
+    /// Change the band gains.
+    pub(crate) fn set(&mut self, settings: EqSettings) {
+        self.settings = settings;
+        self.bands = EQ_FREQUENCIES
+            .iter()
+            .zip(settings.gains_db)
+            .filter(|(_, gain)| *gain != 0.0)
+            .map(|(&centre, gain)| {
+                (
+                    Biquad::peaking(centre, f64::from(gain), self.sample_rate),
+                    vec![FilterState::default(); self.channels],
+                )
+            })
+            .collect();
+    }
+
+    /// Forget past samples, such as after a seek.
+    pub(crate) fn reset(&mut self) {
+        for (_, state) in &mut self.bands {
+            state.fill(FilterState::default());
+        }
+    }
+
+    /// Filter samples in place.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn process(&mut self, samples: &mut [f32]) {
+        if self.bands.is_empty() {
+            return;
+        }
+        for frame in samples.chunks_mut(self.channels) {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mut value = f64::from(*sample);
+                for (filter, state) in &mut self.bands {
+                    value = state[channel].process(filter, value);
+                }
+                *sample = value as f32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak level of a stereo sine at `frequency` after the equalizer settles.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn peak_after(settings: EqSettings, frequency: f64) -> f32 {
+        let mut eq = Equalizer::new(48000, ChannelLayout::Stereo);
+        eq.set(settings);
+        let mut samples: Vec<f32> = (0..48_000)
+            .flat_map(|i| {
+                let value = (2.0 * PI * frequency * f64::from(i) / SAMPLE_RATE).sin() as f32;
+                [value * 0.25, value * 0.25]
+            })
+            .collect();
+        eq.process(&mut samples);
+        samples[48_000..]
+            .iter()
+            .fold(0.0, |peak, s| s.abs().max(peak))
+    }
+
+    #[test]
+    fn test_bands_boost_their_frequency() {
+        let flat = EqSettings::default();
+        assert!(flat.is_flat());
+        assert!((peak_after(flat, 1000.0) - 0.25).abs() < 1e-3);
+
+        let mut gains = [0.0; 10];
+        gains[5] = 6.0;
+        let boosted = EqSettings::new(gains);
+        // 6 dB doubles the amplitude at the centre, leaving far bands alone
+        assert!((peak_after(boosted, 1000.0) - 0.5).abs() < 0.01);
+        assert!((peak_after(boosted, 62.0) - 0.25).abs() < 0.01);
+        assert!((boosted.response_db(1000.0) - 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_presets_stay_in_range() {
+        for preset in EqPreset::all() {
+            let settings = preset.settings();
+            assert!(settings.gains_db.iter().all(|g| g.abs() <= MAX_GAIN_DB));
+            assert_eq!(settings.is_flat(), *preset == EqPreset::Flat);
+        }
+        let clamped = EqSettings::new([20.0; 10]);
+        assert!(clamped.gains_db.iter().all(|&g| g == MAX_GAIN_DB));
+    }
+}
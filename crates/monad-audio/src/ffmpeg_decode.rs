@@ -1,25 +1,111 @@
 //! FFmpeg-based audio decoding for maximum compatibility and quality.
+//!
+//! Without an ffmpeg binary, tracks are decoded with symphonia instead, so
+//! playback works out of the box for the formats it supports.
 
-use std::io::Write;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
-use std::process::{Child, ChildStdin, Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use crossbeam_channel::{bounded, Receiver, Sender, TryRecvError};
+use bytes::Bytes;
+use crossbeam_channel::{bounded, Receiver, Sender, TryRecvError, TrySendError};
 use monad_core::{Error, Result};
 use tracing::{debug, info, warn};
 
+use crate::decode::PlaybackDecoder;
+use crate::layout::ChannelLayout;
+#[cfg(any(test, feature = "test-util"))]
+use crate::resample::Resampler;
+
+/// MIME type for raw interleaved 48kHz stereo f32le PCM, loaded without ffmpeg.
+#[cfg(feature = "test-util")]
+pub const PCM_F32LE_MIME: &str = "audio/x-monad-pcm-f32le";
+
+/// Sample rate of raw PCM sources.
+#[cfg(any(test, feature = "test-util"))]
+const PCM_SAMPLE_RATE: u32 = 48000;
+
+/// Decoded chunks the streaming decoder holds ahead of playback (about 1.4s).
+///
+/// Once they are not read, ffmpeg is paused until they are.
+const PCM_CHUNKS_AHEAD: usize = 16;
+
+/// How long to wait for ffmpeg to report the duration of a track.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Check whether the ffmpeg binary is installed.
+pub fn ffmpeg_available() -> bool {
+    FfmpegDecoder::ffmpeg_path().exists()
+}
+
+/// What a decoder decodes its source with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeBackend {
+    /// Raw PCM, read as it is.
+    #[cfg(any(test, feature = "test-util"))]
+    Pcm,
+    /// An ffmpeg process.
+    Ffmpeg,
+    /// Symphonia, used when ffmpeg is not installed.
+    Symphonia,
+}
+
+/// Decoding state of a [`FfmpegDecoder`] for its backend.
+enum Backend {
+    /// Raw PCM source at the decoder's sample rate
+    #[cfg(any(test, feature = "test-util"))]
+    Pcm(Bytes),
+    /// ffmpeg process decoding from the current position, started on first read
+    Ffmpeg(Option<FfmpegProcess>),
+    /// Symphonia decoding from the current position, started on first read
+    Symphonia {
+        mime_hint: Option<String>,
+        decoder: Option<Box<PlaybackDecoder>>,
+    },
+}
+
+impl Backend {
+    /// Stop decoding, so that it starts again from the current position.
+    fn stop(&mut self) {
+        match self {
+            #[cfg(any(test, feature = "test-util"))]
+            Self::Pcm(_) => {}
+            Self::Ffmpeg(process) => *process = None,
+            Self::Symphonia { decoder, .. } => *decoder = None,
+        }
+    }
+}
+
+/// What ffmpeg reports about its input before decoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Probe {
+    duration: Option<f64>,
+    channels: Option<u16>,
+}
+
 /// FFmpeg decoder that converts any audio format to raw PCM.
+///
+/// The compressed source is kept and decoded as samples are read, so memory
+/// use does not grow with the length of the track. Seeking restarts ffmpeg at
+/// the new position. Without ffmpeg, symphonia decodes instead.
 pub struct FfmpegDecoder {
-    /// Raw PCM samples (f32, interleaved stereo)
-    samples: Vec<f32>,
+    /// Audio data as loaded (compressed, or raw PCM)
+    source: Bytes,
+    backend: Backend,
     /// Current read position
     position: usize,
-    /// Sample rate (always 48000 after ffmpeg processing)
+    /// Total length in samples, if known
+    length: Option<usize>,
+    /// Sample rate ffmpeg decodes to
     sample_rate: u32,
-    /// Number of channels (always 2 after ffmpeg processing)
-    channels: u16,
+    /// Channel layout ffmpeg decodes to
+    layout: ChannelLayout,
+    /// Number of channels of the source
+    source_channels: u16,
     /// Total duration in seconds
     duration: Option<f64>,
 }
@@ -32,98 +118,101 @@ impl FfmpegDecoder {
             .unwrap_or_else(|| PathBuf::from("ffmpeg"))
     }
 
-    /// Create a new decoder from raw audio data.
-    /// FFmpeg handles all format detection and decoding.
+    /// Create a new decoder from raw audio data, decoding to `sample_rate`
+    /// and `layout`. FFmpeg handles all format detection and decoding.
     #[allow(clippy::needless_pass_by_value)]
-    pub fn from_bytes(data: Vec<u8>, _mime_hint: Option<&str>) -> Result<Self> {
-        let ffmpeg_path = Self::ffmpeg_path();
-
-        if !ffmpeg_path.exists() {
-            return Err(Error::AudioDecode(format!(
-                "ffmpeg not found at {:?}",
-                ffmpeg_path
-            )));
+    pub fn from_bytes(
+        data: Bytes,
+        mime_hint: Option<&str>,
+        sample_rate: u32,
+        layout: ChannelLayout,
+    ) -> Result<Self> {
+        // Already-decoded PCM needs no ffmpeg round trip
+        #[cfg(feature = "test-util")]
+        if mime_hint == Some(PCM_F32LE_MIME) {
+            let mut decoder = Self::from_pcm_bytes(data);
+            decoder.set_format(sample_rate, layout)?;
+            return Ok(decoder);
+        }
+        if !ffmpeg_available() {
+            return Self::from_symphonia(data, mime_hint, sample_rate, layout);
         }
 
         info!("Decoding {} bytes with ffmpeg", data.len());
 
-        // Use ffmpeg to decode to raw f32le PCM at 48kHz stereo
-        // -i pipe:0        = read from stdin
-        // -f f32le         = output format: 32-bit float little-endian
-        // -acodec pcm_f32le = PCM codec
-        // -ar 48000        = sample rate 48kHz
-        // -ac 2            = stereo
-        // -v quiet         = suppress output
-        // pipe:1           = write to stdout
-        let mut child = Command::new(&ffmpeg_path)
-            .args([
-                "-i",
-                "pipe:0",
-                "-f",
-                "f32le",
-                "-acodec",
-                "pcm_f32le",
-                "-ar",
-                "48000",
-                "-ac",
-                "2",
-                "-v",
-                "quiet",
-                "pipe:1",
-            ])
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|e| Error::AudioDecode(format!("Failed to spawn ffmpeg: {e}")))?;
-
-        // Write input data to ffmpeg's stdin in a separate thread to avoid deadlock
-        // (ffmpeg may block on stdout while we're still writing to stdin)
-        let stdin = child.stdin.take();
-        let write_thread = std::thread::spawn(move || {
-            if let Some(mut stdin) = stdin {
-                let _ = stdin.write_all(&data);
-                // stdin is dropped here, closing the pipe
-            }
-        });
-
-        // Read decoded PCM from stdout
-        let output = child
-            .wait_with_output()
-            .map_err(|e| Error::AudioDecode(format!("Failed to read ffmpeg output: {e}")))?;
-
-        // Wait for write thread to finish
-        let _ = write_thread.join();
-
-        if !output.status.success() {
-            return Err(Error::AudioDecode("ffmpeg decoding failed".to_string()));
-        }
-
-        let pcm_bytes = output.stdout;
-        if pcm_bytes.is_empty() {
+        // Start decoding from the beginning, reading what ffmpeg reports of the source
+        let (probe_tx, probe_rx) = bounded(1);
+        let mut process =
+            FfmpegProcess::spawn(data.clone(), 0.0, sample_rate, layout, Some(probe_tx))?;
+        let no_output = process
+            .stdout
+            .fill_buf()
+            .map_err(|e| Error::AudioDecode(format!("Failed to read ffmpeg output: {e}")))?
+            .is_empty();
+        if no_output {
             return Err(Error::AudioDecode("ffmpeg produced no output".to_string()));
         }
+        let Probe { duration, channels } = probe_rx.recv_timeout(PROBE_TIMEOUT).unwrap_or_default();
 
-        // Convert bytes to f32 samples
-        let samples = bytes_to_f32(&pcm_bytes);
+        info!(
+            "Decoding with ffmpeg at {sample_rate} Hz, duration {:.2}s, {} source channels",
+            duration.unwrap_or(0.0),
+            channels.unwrap_or(2)
+        );
 
-        // Calculate duration: samples / (sample_rate * channels)
-        #[allow(clippy::cast_precision_loss)]
-        let duration = Some(samples.len() as f64 / (48000.0 * 2.0));
+        let mut decoder = Self {
+            source: data,
+            backend: Backend::Ffmpeg(Some(process)),
+            position: 0,
+            length: None,
+            sample_rate,
+            layout,
+            source_channels: channels.unwrap_or(2),
+            duration,
+        };
+        decoder.length = duration.map(|secs| decoder.samples_at(secs));
+        Ok(decoder)
+    }
 
+    /// Create a decoder using symphonia, for when ffmpeg is not installed.
+    fn from_symphonia(
+        data: Bytes,
+        mime_hint: Option<&str>,
+        sample_rate: u32,
+        layout: ChannelLayout,
+    ) -> Result<Self> {
         info!(
-            "Decoded {} samples ({:.2}s) with ffmpeg",
-            samples.len(),
-            duration.unwrap_or(0.0)
+            "ffmpeg not found, decoding {} bytes with symphonia",
+            data.len()
         );
-
-        Ok(Self {
-            samples,
+        let decoder = PlaybackDecoder::new(data.clone(), mime_hint, sample_rate, layout, 0.0)?;
+        let duration = decoder.duration();
+        let source_channels = decoder.source_channels();
+        let mut decoder = Self {
+            source: data,
+            backend: Backend::Symphonia {
+                mime_hint: mime_hint.map(str::to_string),
+                decoder: Some(Box::new(decoder)),
+            },
             position: 0,
-            sample_rate: 48000,
-            channels: 2,
+            length: None,
+            sample_rate,
+            layout,
+            source_channels,
             duration,
-        })
+        };
+        decoder.length = duration.map(|secs| decoder.samples_at(secs));
+        Ok(decoder)
+    }
+
+    /// Get what the decoder decodes with.
+    pub const fn backend(&self) -> DecodeBackend {
+        match self.backend {
+            #[cfg(any(test, feature = "test-util"))]
+            Backend::Pcm(_) => DecodeBackend::Pcm,
+            Backend::Ffmpeg(_) => DecodeBackend::Ffmpeg,
+            Backend::Symphonia { .. } => DecodeBackend::Symphonia,
+        }
     }
 
     /// Get the sample rate.
@@ -133,7 +222,17 @@ impl FfmpegDecoder {
 
     /// Get the number of channels.
     pub const fn channels(&self) -> u16 {
-        self.channels
+        self.layout.channels()
+    }
+
+    /// Get the channel layout.
+    pub const fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Get the number of channels of the source, before remixing to the layout.
+    pub const fn source_channels(&self) -> u16 {
+        self.source_channels
     }
 
     /// Get the total duration in seconds.
@@ -141,30 +240,174 @@ impl FfmpegDecoder {
         self.duration
     }
 
+    /// Create a decoder from interleaved 48kHz stereo f32 samples.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn from_pcm(samples: Vec<f32>) -> Self {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        Self::from_pcm_bytes(bytes.into())
+    }
+
+    /// Create a decoder from interleaved 48kHz stereo f32le bytes.
+    #[cfg(any(test, feature = "test-util"))]
+    #[allow(clippy::cast_precision_loss)]
+    fn from_pcm_bytes(data: Bytes) -> Self {
+        let length = data.len() / 4;
+        // Duration: samples / (sample_rate * channels)
+        let duration = length as f64 / (f64::from(PCM_SAMPLE_RATE) * 2.0);
+        Self {
+            source: data.clone(),
+            backend: Backend::Pcm(data),
+            position: 0,
+            length: Some(length),
+            sample_rate: PCM_SAMPLE_RATE,
+            layout: ChannelLayout::Stereo,
+            source_channels: 2,
+            duration: Some(duration),
+        }
+    }
+
+    /// Change the rate to decode at, keeping the position.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) -> Result<()> {
+        self.set_format(sample_rate, self.layout)
+    }
+
+    /// Change the channel layout to decode to, keeping the position.
+    pub fn set_layout(&mut self, layout: ChannelLayout) -> Result<()> {
+        self.set_format(self.sample_rate, layout)
+    }
+
+    /// Change the rate and channel layout to decode to, keeping the position.
+    ///
+    /// Raw PCM sources are converted; other decoding restarts in the new format.
+    pub fn set_format(&mut self, sample_rate: u32, layout: ChannelLayout) -> Result<()> {
+        if sample_rate == self.sample_rate && layout == self.layout {
+            return Ok(());
+        }
+        debug!(
+            "Decoding at {sample_rate} Hz in {} instead of {} Hz in {}",
+            layout.name(),
+            self.sample_rate,
+            self.layout.name()
+        );
+        let position_secs = self.secs_at(self.position);
+        self.sample_rate = sample_rate;
+        self.layout = layout;
+        self.backend.stop();
+        self.position = self.samples_at(position_secs);
+        #[cfg(any(test, feature = "test-util"))]
+        if let Backend::Pcm(pcm) = &mut self.backend {
+            *pcm = convert_pcm(&self.source, sample_rate, layout)?;
+            self.length = Some(pcm.len() / 4);
+            return Ok(());
+        }
+        self.length = self.duration.map(|secs| self.samples_at(secs));
+        Ok(())
+    }
+
+    /// Get a decoder of the same track, starting from the beginning.
+    pub fn restarted(&self) -> Self {
+        let backend = match &self.backend {
+            #[cfg(any(test, feature = "test-util"))]
+            Backend::Pcm(pcm) => Backend::Pcm(pcm.clone()),
+            Backend::Ffmpeg(_) => Backend::Ffmpeg(None),
+            Backend::Symphonia { mime_hint, .. } => Backend::Symphonia {
+                mime_hint: mime_hint.clone(),
+                decoder: None,
+            },
+        };
+        Self {
+            source: self.source.clone(),
+            backend,
+            position: 0,
+            length: self.length,
+            sample_rate: self.sample_rate,
+            layout: self.layout,
+            source_channels: self.source_channels,
+            duration: self.duration,
+        }
+    }
+
     /// Decode the next chunk of samples.
     /// Returns None when all samples have been read.
     pub fn decode_next(&mut self) -> Result<Option<Vec<f32>>> {
-        if self.position >= self.samples.len() {
-            return Ok(None);
-        }
+        // Return chunks of 1024 frames
+        let chunk = self.read(1024 * usize::from(self.layout.channels()))?;
+        Ok((!chunk.is_empty()).then_some(chunk))
+    }
 
-        // Return chunks of ~1024 frames (2048 samples for stereo)
-        let chunk_size = 2048;
-        let end = (self.position + chunk_size).min(self.samples.len());
-        let chunk = self.samples[self.position..end].to_vec();
-        self.position = end;
+    /// Read up to `count` samples, fewer at the end of the track.
+    pub fn read(&mut self, count: usize) -> Result<Vec<f32>> {
+        let start_secs = self.secs_at(self.position);
+        let chunk = match &mut self.backend {
+            #[cfg(any(test, feature = "test-util"))]
+            Backend::Pcm(pcm) => {
+                let start = (self.position * 4).min(pcm.len());
+                let end = ((self.position + count) * 4).min(pcm.len());
+                bytes_to_f32(&pcm[start..end])
+            }
+            Backend::Ffmpeg(process) => {
+                let process = if let Some(process) = process {
+                    process
+                } else {
+                    process.insert(FfmpegProcess::spawn(
+                        self.source.clone(),
+                        start_secs,
+                        self.sample_rate,
+                        self.layout,
+                        None,
+                    )?)
+                };
+                process.read(count)?
+            }
+            Backend::Symphonia { mime_hint, decoder } => {
+                let decoder = if let Some(decoder) = decoder {
+                    decoder
+                } else {
+                    decoder.insert(Box::new(PlaybackDecoder::new(
+                        self.source.clone(),
+                        mime_hint.as_deref(),
+                        self.sample_rate,
+                        self.layout,
+                        start_secs,
+                    )?))
+                };
+                decoder.read(count)?
+            }
+        };
+        self.position += chunk.len();
+        Ok(chunk)
+    }
 
-        Ok(Some(chunk))
+    /// Get the number of samples left to read, if the length of the track is
+    /// known.
+    pub fn remaining(&self) -> Option<usize> {
+        self.length
+            .map(|length| length.saturating_sub(self.position))
+    }
+
+    /// Convert a position to a sample count, aligned to whole frames.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn samples_at(&self, position_secs: f64) -> usize {
+        (position_secs * f64::from(self.sample_rate)) as usize * usize::from(self.layout.channels())
+    }
+
+    /// Convert a sample count to a position in seconds.
+    #[allow(clippy::cast_precision_loss)]
+    fn secs_at(&self, samples: usize) -> f64 {
+        samples as f64 / (f64::from(self.sample_rate) * f64::from(self.layout.channels()))
     }
 
     /// Seek to a position in seconds.
     pub fn seek(&mut self, position_secs: f64) -> Result<()> {
-        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-        let sample_position = (position_secs * 48000.0 * 2.0) as usize;
-
-        // Align to frame boundary (stereo = 2 samples per frame)
-        let aligned = (sample_position / 2) * 2;
-        self.position = aligned.min(self.samples.len());
+        let mut aligned = self.samples_at(position_secs);
+        if let Some(length) = self.length {
+            aligned = aligned.min(length);
+        }
+        if aligned != self.position {
+            // Decoding restarts from the new position on the next read
+            self.backend.stop();
+            self.position = aligned;
+        }
 
         debug!(
             "Seeked to position {} (sample {})",
@@ -175,7 +418,212 @@ impl FfmpegDecoder {
 
     /// Reset the decoder to the beginning.
     pub fn reset(&mut self) {
-        self.position = 0;
+        let _ = self.seek(0.0);
+    }
+}
+
+/// Convert raw 48kHz stereo f32le PCM to another rate and channel layout.
+#[cfg(any(test, feature = "test-util"))]
+fn convert_pcm(data: &Bytes, sample_rate: u32, layout: ChannelLayout) -> Result<Bytes> {
+    let mut resampler = Resampler::new(PCM_SAMPLE_RATE, sample_rate, 2)?;
+    if !resampler.needs_resampling() && layout == ChannelLayout::Stereo {
+        return Ok(data.clone());
+    }
+    let mut samples = resampler.process(&bytes_to_f32(data))?;
+    samples.extend(resampler.flush()?);
+    let samples = layout.remix(&samples, 2);
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    Ok(bytes.into())
+}
+
+/// An ffmpeg process decoding a source to f32le PCM.
+///
+/// Decoded samples are read from its stdout as they are needed; while they
+/// are not, the pipe fills up and ffmpeg waits.
+struct FfmpegProcess {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    /// Thread writing the source to ffmpeg's stdin.
+    writer: Option<JoinHandle<()>>,
+}
+
+impl FfmpegProcess {
+    /// Start decoding `source` from `start_secs` at `sample_rate` to `layout`,
+    /// sending what ffmpeg reports of the source to `probe` if given.
+    fn spawn(
+        source: Bytes,
+        start_secs: f64,
+        sample_rate: u32,
+        layout: ChannelLayout,
+        probe: Option<Sender<Probe>>,
+    ) -> Result<Self> {
+        let ffmpeg_path = FfmpegDecoder::ffmpeg_path();
+
+        if !ffmpeg_path.exists() {
+            return Err(Error::AudioDecode(format!(
+                "ffmpeg not found at {}",
+                ffmpeg_path.display()
+            )));
+        }
+
+        // Use ffmpeg to decode to raw f32le PCM
+        // -ss <secs>       = start position (input option, so ffmpeg skips ahead)
+        // -i pipe:0        = read from stdin
+        // -f f32le         = output format: 32-bit float little-endian
+        // -acodec pcm_f32le = PCM codec
+        // -ar <rate>       = output sample rate, resampling only if the source differs
+        // -ac <channels>   = channels of the layout, remixing if the source differs
+        // -v quiet         = suppress output, or info to read the duration and channels
+        // pipe:1           = write to stdout
+        let mut command = Command::new(&ffmpeg_path);
+        if start_secs > 0.0 {
+            command.args(["-ss", &format!("{start_secs:.6}")]);
+        }
+        command
+            .args([
+                "-i",
+                "pipe:0",
+                "-f",
+                "f32le",
+                "-acodec",
+                "pcm_f32le",
+                "-ar",
+                &sample_rate.to_string(),
+                "-ac",
+                &layout.channels().to_string(),
+                "-nostats",
+                "-hide_banner",
+                "-v",
+                if probe.is_some() { "info" } else { "quiet" },
+                "pipe:1",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(if probe.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            });
+        let mut child = command
+            .spawn()
+            .map_err(|e| Error::AudioDecode(format!("Failed to spawn ffmpeg: {e}")))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::AudioDecode("Failed to capture ffmpeg stdout".to_string()))?;
+        if let (Some(probe), Some(stderr)) = (probe, child.stderr.take()) {
+            std::thread::Builder::new()
+                .name("ffmpeg-probe".to_string())
+                .spawn(move || Self::probe_thread(stderr, &probe))
+                .map_err(|e| Error::AudioDecode(format!("Failed to spawn probe thread: {e}")))?;
+        }
+
+        // Write input data to ffmpeg's stdin in a separate thread to avoid deadlock
+        // (ffmpeg may block on stdout while we're still writing to stdin)
+        let stdin = child.stdin.take();
+        let writer = std::thread::Builder::new()
+            .name("ffmpeg-writer".to_string())
+            .spawn(move || {
+                if let Some(mut stdin) = stdin {
+                    // Fails once ffmpeg is stopped early, such as by a seek
+                    let _ = stdin.write_all(&source);
+                    // stdin is dropped here, closing the pipe
+                }
+            })
+            .map_err(|e| Error::AudioDecode(format!("Failed to spawn writer thread: {e}")))?;
+
+        Ok(Self {
+            child,
+            stdout: BufReader::new(stdout),
+            writer: Some(writer),
+        })
+    }
+
+    /// Read ffmpeg's log for the duration and channels of the input, sending
+    /// them once the input has been described.
+    fn probe_thread(stderr: std::process::ChildStderr, probe: &Sender<Probe>) {
+        let mut found = Probe::default();
+        let mut sent = false;
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !sent {
+                found.duration = found.duration.or_else(|| parse_duration(&line));
+                found.channels = found.channels.or_else(|| parse_channels(&line));
+                if line.starts_with("Stream mapping") || line.starts_with("Output") {
+                    let _ = probe.send(found);
+                    sent = true;
+                }
+            }
+            // Keep draining the log so that ffmpeg never blocks on it
+        }
+        if !sent {
+            let _ = probe.send(found);
+        }
+    }
+
+    /// Read up to `count` samples, fewer only at the end of the output.
+    fn read(&mut self, count: usize) -> Result<Vec<f32>> {
+        let mut bytes = Vec::with_capacity(count * 4);
+        (&mut self.stdout)
+            .take(count as u64 * 4)
+            .read_to_end(&mut bytes)
+            .map_err(|e| Error::AudioDecode(format!("Failed to read ffmpeg output: {e}")))?;
+        Ok(bytes_to_f32(&bytes))
+    }
+}
+
+impl Drop for FfmpegProcess {
+    fn drop(&mut self) {
+        // Stop ffmpeg if it is still decoding, which also ends the writer
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Parse the duration from an ffmpeg log line such as
+/// `  Duration: 00:03:25.02, start: 0.000000, bitrate: 128 kb/s`.
+fn parse_duration(line: &str) -> Option<f64> {
+    let time = line
+        .trim_start()
+        .strip_prefix("Duration:")?
+        .split(',')
+        .next()?
+        .trim();
+    let mut parts = time.split(':').map(str::parse::<f64>);
+    let (hours, minutes, seconds) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    Some(hours.mul_add(3600.0, minutes.mul_add(60.0, seconds)))
+}
+
+/// Parse the channel count from an ffmpeg log line describing an audio
+/// stream, such as `  Stream #0:0: Audio: aac (LC), 48000 Hz, 5.1, fltp`.
+fn parse_channels(line: &str) -> Option<u16> {
+    let (_, audio) = line
+        .trim_start()
+        .strip_prefix("Stream #")?
+        .split_once("Audio:")?;
+    let layout = audio.split(',').nth(2)?.trim();
+    // Layouts may name their variant, such as `5.1(side)`
+    let name = layout.split('(').next()?;
+    match name {
+        "mono" => Some(1),
+        "stereo" | "downmix" => Some(2),
+        "2.1" | "3.0" => Some(3),
+        "quad" | "4.0" | "3.1" => Some(4),
+        "5.0" | "4.1" => Some(5),
+        "5.1" | "6.0" | "hexagonal" => Some(6),
+        "6.1" | "7.0" => Some(7),
+        "7.1" | "octagonal" => Some(8),
+        _ => name.strip_suffix(" channels")?.parse().ok(),
     }
 }
 
@@ -193,7 +641,9 @@ fn bytes_to_f32(bytes: &[u8]) -> Vec<f32> {
 /// Streaming FFmpeg decoder for decoding audio as it's being downloaded.
 ///
 /// This decoder spawns ffmpeg with piped stdin/stdout, allowing audio to be
-/// fed in chunks and decoded PCM to be read as it becomes available.
+/// fed in chunks and decoded PCM to be read as it becomes available. Only a
+/// little decoded audio is held ahead of playback: while it is not read,
+/// ffmpeg waits, and [`try_feed`](Self::try_feed) turns data away.
 pub struct StreamingFfmpegDecoder {
     /// Sender for input audio data (compressed).
     input_tx: Option<Sender<Vec<u8>>>,
@@ -206,15 +656,15 @@ pub struct StreamingFfmpegDecoder {
     /// Total samples decoded so far.
     samples_decoded: u64,
     /// Handle to writer thread.
-    writer_handle: Option<std::thread::JoinHandle<()>>,
+    writer_handle: Option<JoinHandle<()>>,
     /// Handle to reader thread.
-    reader_handle: Option<std::thread::JoinHandle<()>>,
+    reader_handle: Option<JoinHandle<()>>,
 }
 
 impl StreamingFfmpegDecoder {
-    /// Create a new streaming decoder.
+    /// Create a new streaming decoder, decoding to `sample_rate` and `layout`.
     /// Spawns ffmpeg and starts writer/reader threads.
-    pub fn new() -> Result<Self> {
+    pub fn new(sample_rate: u32, layout: ChannelLayout) -> Result<Self> {
         let ffmpeg_path = FfmpegDecoder::ffmpeg_path();
 
         if !ffmpeg_path.exists() {
@@ -236,9 +686,9 @@ impl StreamingFfmpegDecoder {
                 "-acodec",
                 "pcm_f32le",
                 "-ar",
-                "48000",
+                &sample_rate.to_string(),
                 "-ac",
-                "2",
+                &layout.channels().to_string(),
                 "-v",
                 "quiet",
                 "pipe:1",
@@ -262,7 +712,8 @@ impl StreamingFfmpegDecoder {
         let (input_tx, input_rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = bounded(64);
 
         // Channel for output data (decoded PCM)
-        let (output_tx, output_rx): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = bounded(128);
+        let (output_tx, output_rx): (Sender<Vec<f32>>, Receiver<Vec<f32>>) =
+            bounded(PCM_CHUNKS_AHEAD);
 
         let input_closed = Arc::new(AtomicBool::new(false));
         let decode_complete = Arc::new(AtomicBool::new(false));
@@ -333,13 +784,11 @@ impl StreamingFfmpegDecoder {
 
     /// Reader thread function.
     fn reader_thread(
-        mut stdout: std::process::ChildStdout,
+        mut stdout: ChildStdout,
         output_tx: Sender<Vec<f32>>,
         decode_complete: Arc<AtomicBool>,
         mut child: Child,
     ) {
-        use std::io::Read;
-
         debug!("FFmpeg reader thread started");
         let mut buffer = vec![0u8; 32768]; // 32KB buffer
         let mut total_samples = 0u64;
@@ -386,11 +835,33 @@ impl StreamingFfmpegDecoder {
         }
     }
 
+    /// Feed compressed audio data to the decoder without blocking.
+    ///
+    /// Returns false if the decoder is not ready for more, such as while its
+    /// decoded audio is not being read, in which case the data should be fed
+    /// again later.
+    pub fn try_feed(&self, data: &[u8]) -> Result<bool> {
+        let Some(ref tx) = self.input_tx else {
+            return Err(Error::AudioDecode("Input already finished".to_string()));
+        };
+        if tx.is_full() {
+            return Ok(false);
+        }
+        match tx.try_send(data.to_vec()) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => Ok(false),
+            Err(TrySendError::Disconnected(_)) => Err(Error::AudioDecode(
+                "FFmpeg input channel closed".to_string(),
+            )),
+        }
+    }
+
     /// Signal that no more input data will be sent.
     /// This closes the input channel and allows ffmpeg to finish processing.
     pub fn finish_input(&mut self) {
-        debug!("Finishing FFmpeg input");
-        self.input_tx = None;
+        if self.input_tx.take().is_some() {
+            debug!("Finishing FFmpeg input");
+        }
     }
 
     /// Try to read the next chunk of decoded PCM samples.
@@ -449,4 +920,57 @@ mod tests {
         assert_eq!(samples.len(), 1);
         assert!((samples[0] - 0.5).abs() < 0.0001);
     }
+
+    #[test]
+    fn test_pcm_read_and_seek() -> Result<()> {
+        // Half a second of frames numbered in both channels
+        let samples: Vec<f32> = (0..24_000u16)
+            .flat_map(|i| [f32::from(i), f32::from(i)])
+            .collect();
+        let mut decoder = FfmpegDecoder::from_pcm(samples);
+        assert_eq!(decoder.duration(), Some(0.5));
+        assert_eq!(decoder.read(4)?, [0.0, 0.0, 1.0, 1.0]);
+
+        decoder.seek(0.25)?;
+        assert_eq!(decoder.remaining(), Some(24_000));
+        assert_eq!(decoder.decode_next()?.map(|chunk| chunk[0]), Some(12_000.0));
+        decoder.seek(1.0)?;
+        assert_eq!(decoder.decode_next()?, None);
+
+        let mut restarted = decoder.restarted();
+        assert_eq!(restarted.read(4)?, [0.0, 0.0, 1.0, 1.0]);
+
+        // Resampled to another rate, keeping the position
+        decoder.seek(0.25)?;
+        decoder.set_sample_rate(24_000)?;
+        assert_eq!(decoder.sample_rate(), 24_000);
+        assert!(decoder
+            .remaining()
+            .is_some_and(|remaining| remaining.abs_diff(12_000) < 1_000));
+
+        // Remixed to surround, keeping the position
+        let mut decoder = FfmpegDecoder::from_pcm([0.5, 0.25].repeat(24_000));
+        decoder.seek(0.25)?;
+        decoder.set_layout(ChannelLayout::Surround51)?;
+        assert_eq!(decoder.channels(), 6);
+        assert_eq!(decoder.remaining(), Some(72_000));
+        assert_eq!(decoder.read(6)?, [0.5, 0.25, 0.0, 0.0, 0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let line = "  Duration: 01:02:03.50, start: 0.000000, bitrate: 128 kb/s";
+        assert_eq!(parse_duration(line), Some(3723.5));
+        assert_eq!(parse_duration("  Duration: N/A, bitrate: N/A"), None);
+        assert_eq!(parse_duration("Output #0, f32le, to 'pipe:1':"), None);
+
+        let stream = "  Stream #0:0(und): Audio: aac (LC) (mp4a / 0x6134706D), 48000 Hz, 5.1(side), fltp, 384 kb/s";
+        assert_eq!(parse_channels(stream), Some(6));
+        let stream = "    Stream #0:0: Audio: opus, 48000 Hz, stereo, fltp (default)";
+        assert_eq!(parse_channels(stream), Some(2));
+        let stream = "  Stream #0:0: Audio: pcm_s16le, 44100 Hz, 3 channels, s16";
+        assert_eq!(parse_channels(stream), Some(3));
+        assert_eq!(parse_channels("  Stream #0:1: Video: h264, yuv420p"), None);
+    }
 }
@@ -0,0 +1,215 @@
+//! Channel layouts of the audio played.
+//!
+//! When a track loads, the engine plays it in its own surround layout if the
+//! output device supports that layout, and in stereo otherwise. Decoders then
+//! remix to the chosen layout, folding surround channels into the front pair
+//! for stereo, and the output opens in it.
+
+/// Speaker a channel plays on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Speaker {
+    FrontLeft,
+    FrontRight,
+    Centre,
+    Lfe,
+    BackLeft,
+    BackRight,
+    SideLeft,
+    SideRight,
+}
+
+/// Most channels of any layout.
+pub(crate) const MAX_CHANNELS: usize = 8;
+
+/// Gain of a channel folded into a pair of other channels (-3 dB).
+const FOLD_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Channel layout of interleaved samples, in the usual WAVE channel order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// Front left and right.
+    #[default]
+    Stereo,
+    /// Front left, right and centre, LFE, and back left and right.
+    Surround51,
+    /// 5.1 with side left and right after the back pair.
+    Surround71,
+}
+
+impl ChannelLayout {
+    /// Get the layout with `channels` channels, if there is one.
+    pub const fn from_channels(channels: u16) -> Option<Self> {
+        match channels {
+            2 => Some(Self::Stereo),
+            6 => Some(Self::Surround51),
+            8 => Some(Self::Surround71),
+            _ => None,
+        }
+    }
+
+    /// Choose the layout to play a source with `source_channels` channels in:
+    /// its own if it is one of the layouts in `supported`, or stereo.
+    pub fn negotiate(source_channels: u16, supported: &[Self]) -> Self {
+        Self::from_channels(source_channels)
+            .filter(|layout| supported.contains(layout))
+            .unwrap_or(Self::Stereo)
+    }
+
+    /// Get the number of channels.
+    pub const fn channels(self) -> u16 {
+        match self {
+            Self::Stereo => 2,
+            Self::Surround51 => 6,
+            Self::Surround71 => 8,
+        }
+    }
+
+    /// Get the name of the layout for display.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Stereo => "Stereo",
+            Self::Surround51 => "5.1",
+            Self::Surround71 => "7.1",
+        }
+    }
+
+    /// Get the speaker of each channel.
+    const fn speakers(self) -> &'static [Speaker] {
+        use Speaker::{
+            BackLeft, BackRight, Centre, FrontLeft, FrontRight, Lfe, SideLeft, SideRight,
+        };
+        match self {
+            Self::Stereo => &[FrontLeft, FrontRight],
+            Self::Surround51 => &[FrontLeft, FrontRight, Centre, Lfe, BackLeft, BackRight],
+            Self::Surround71 => &[
+                FrontLeft, FrontRight, Centre, Lfe, BackLeft, BackRight, SideLeft, SideRight,
+            ],
+        }
+    }
+
+    /// Get the side of a channel, 0 for left and 1 for right, or `None` for
+    /// the centre and LFE.
+    pub(crate) fn side(self, channel: usize) -> Option<usize> {
+        match self.speakers().get(channel)? {
+            Speaker::FrontLeft | Speaker::BackLeft | Speaker::SideLeft => Some(0),
+            Speaker::FrontRight | Speaker::BackRight | Speaker::SideRight => Some(1),
+            Speaker::Centre | Speaker::Lfe => None,
+        }
+    }
+
+    /// Get the channels a speaker missing from the layout is folded into,
+    /// with their gains. The LFE is dropped.
+    fn fold(self, speaker: Speaker) -> [Option<(usize, f32)>; 2] {
+        if let Some(channel) = self.speakers().iter().position(|&s| s == speaker) {
+            return [Some((channel, 1.0)), None];
+        }
+        let back = |channel: usize| {
+            if self == Self::Stereo {
+                (channel - 4, FOLD_GAIN)
+            } else {
+                (channel, 1.0)
+            }
+        };
+        match speaker {
+            Speaker::Centre => [Some((0, FOLD_GAIN)), Some((1, FOLD_GAIN))],
+            Speaker::BackLeft | Speaker::SideLeft => [Some(back(4)), None],
+            Speaker::BackRight | Speaker::SideRight => [Some(back(5)), None],
+            Speaker::FrontLeft | Speaker::FrontRight | Speaker::Lfe => [None, None],
+        }
+    }
+
+    /// Get the channels each channel of a source with `source_channels`
+    /// channels plays on in this layout. Mono plays on both front channels,
+    /// and of unknown layouts only the front pair is kept.
+    fn routes(self, source_channels: usize) -> Vec<[Option<(usize, f32)>; 2]> {
+        match u16::try_from(source_channels)
+            .ok()
+            .and_then(Self::from_channels)
+        {
+            Some(source) => source
+                .speakers()
+                .iter()
+                .map(|&speaker| self.fold(speaker))
+                .collect(),
+            None if source_channels == 1 => vec![[Some((0, 1.0)), Some((1, 1.0))]],
+            None => (0..source_channels)
+                .map(|channel| [(channel < 2).then_some((channel, 1.0)), None])
+                .collect(),
+        }
+    }
+
+    /// Remix interleaved samples with `source_channels` channels to this layout.
+    pub(crate) fn remix(self, samples: &[f32], source_channels: usize) -> Vec<f32> {
+        let channels = usize::from(self.channels());
+        if source_channels == channels || source_channels == 0 {
+            return samples.to_vec();
+        }
+        let routes = self.routes(source_channels);
+        let mut remixed = vec![0.0; samples.len() / source_channels * channels];
+        for (frame, out) in samples
+            .chunks_exact(source_channels)
+            .zip(remixed.chunks_exact_mut(channels))
+        {
+            for (&sample, route) in frame.iter().zip(&routes) {
+                for &(channel, gain) in route.iter().flatten() {
+                    out[channel] = sample.mul_add(gain, out[channel]);
+                }
+            }
+        }
+        remixed
+    }
+
+    /// Mix a frame in this layout down to stereo, such as for meters.
+    pub(crate) fn downmix(self, frame: &[f32]) -> [f32; 2] {
+        let mut stereo = [0.0; 2];
+        for (&sample, &speaker) in frame.iter().zip(self.speakers()) {
+            for (channel, gain) in Self::Stereo.fold(speaker).into_iter().flatten() {
+                stereo[channel] = sample.mul_add(gain, stereo[channel]);
+            }
+        }
+        stereo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_and_remix() {
+        let supported = [ChannelLayout::Stereo, ChannelLayout::Surround51];
+        assert_eq!(
+            ChannelLayout::negotiate(6, &supported),
+            ChannelLayout::Surround51
+        );
+        assert_eq!(
+            ChannelLayout::negotiate(8, &supported),
+            ChannelLayout::Stereo
+        );
+        assert_eq!(
+            ChannelLayout::negotiate(1, &supported),
+            ChannelLayout::Stereo
+        );
+
+        // Centre and back channels fold into the front pair, the LFE is dropped
+        let surround = [0.5, 0.25, 0.4, 1.0, 0.2, 0.0];
+        let stereo = ChannelLayout::Stereo.remix(&surround, 6);
+        assert!((stereo[0] - FOLD_GAIN.mul_add(0.4 + 0.2, 0.5)).abs() < 1e-6);
+        assert!((stereo[1] - FOLD_GAIN.mul_add(0.4, 0.25)).abs() < 1e-6);
+        let downmixed = ChannelLayout::Surround51.downmix(&surround);
+        assert!(downmixed
+            .iter()
+            .zip(&stereo)
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+
+        // Side channels join the back ones in 5.1, and mono plays in front
+        let sides = ChannelLayout::Surround51.remix(&[0.0, 0.0, 0.0, 0.0, 0.1, 0.1, 0.2, 0.2], 8);
+        assert!((sides[4] - 0.3).abs() < 1e-6 && (sides[5] - 0.3).abs() < 1e-6);
+        assert_eq!(
+            ChannelLayout::Surround51.remix(&[0.5], 1),
+            [0.5, 0.5, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(ChannelLayout::Surround71.side(6), Some(0));
+        assert_eq!(ChannelLayout::Surround71.side(2), None);
+    }
+}
@@ -0,0 +1,79 @@
+//! Level metering.
+//!
+//! The engine measures the peak and RMS level of each channel of the audio
+//! playing, after volume, from the samples the output played, for VU meters.
+//! Samples pushed past full scale by the equalizer or loudness normalization
+//! are counted as they are decoded, before the output limits them. Surround
+//! audio is measured mixed down to stereo.
+
+use crate::layout::ChannelLayout;
+
+/// Levels of the audio played over a short time.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Levels {
+    /// Largest absolute sample of each channel, left first, 1.0 being full scale.
+    pub peak: [f32; 2],
+    /// Root mean square of each channel, left first.
+    pub rms: [f32; 2],
+    /// Samples past full scale after the equalizer and loudness
+    /// normalization, decoded since the last levels.
+    pub clipped: usize,
+}
+
+impl Levels {
+    /// Measure interleaved samples in `layout`, silent if there are none.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn measure(samples: &[f32], layout: ChannelLayout) -> Self {
+        let channels = usize::from(layout.channels());
+        let mut peak = [0.0f32; 2];
+        let mut sum_squares = [0.0f64; 2];
+        for frame in samples.chunks_exact(channels) {
+            for (channel, sample) in layout.downmix(frame).into_iter().enumerate() {
+                peak[channel] = peak[channel].max(sample.abs());
+                sum_squares[channel] += f64::from(sample) * f64::from(sample);
+            }
+        }
+        let frames = (samples.len() / channels).max(1) as f64;
+        #[allow(clippy::cast_possible_truncation)]
+        let rms = sum_squares.map(|sum| (sum / frames).sqrt() as f32);
+        Self {
+            peak,
+            rms,
+            clipped: 0,
+        }
+    }
+
+    /// Convert a level to dB relative to full scale.
+    pub fn to_db(level: f32) -> f32 {
+        20.0 * level.max(f32::MIN_POSITIVE).log10()
+    }
+}
+
+/// Count the samples past full scale.
+pub(crate) fn count_clipped(samples: &[f32]) -> usize {
+    samples.iter().filter(|sample| sample.abs() > 1.0).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_per_channel() {
+        let levels = Levels::measure(
+            &[0.5, -0.25, -0.5, 0.25, 0.5, 0.0, -0.5, 0.0],
+            ChannelLayout::Stereo,
+        );
+        assert!((levels.peak[0] - 0.5).abs() < f32::EPSILON);
+        assert!((levels.peak[1] - 0.25).abs() < f32::EPSILON);
+        assert!((levels.rms[0] - 0.5).abs() < f32::EPSILON);
+        assert!((levels.rms[1] - 0.176_776_7).abs() < 1e-6);
+        assert!((Levels::to_db(levels.peak[0]) + 6.02).abs() < 0.01);
+
+        assert_eq!(
+            Levels::measure(&[], ChannelLayout::Surround51),
+            Levels::default()
+        );
+        assert_eq!(count_clipped(&[1.0, -1.2, 0.3, 1.01]), 2);
+    }
+}
@@ -3,16 +3,58 @@
 //! High-performance audio playback engine for Monad.
 //!
 //! Features:
-//! - Lock-free ring buffer for decode→output communication
-//! - FFmpeg-based decoding for maximum compatibility
-//! - Low-latency cpal output
+//! - Lock-free ring buffer for decode→output communication, sized to taste
+//! - FFmpeg-based decoding for maximum compatibility, falling back to symphonia
+//!   when ffmpeg is not installed
+//! - Low-latency cpal output, with null/file outputs for running without a sound card
+//! - Float output where supported, dithered 16-bit output otherwise
+//! - 5.1 and 7.1 playback on devices supporting it, downmixed to stereo otherwise
+//! - Experimental playback groups for rough multi-room sync over the LAN
+//! - Ten-band equalizer with presets
+//! - Pitch shift in semitones, independent of speed
+//! - Balance, mono downmix and per-channel mute
+//! - Spectrum and peak/RMS levels of the audio playing for visualizers and meters
+//! - Loudness normalization to a target level (EBU R128)
+//! - Preloading of the next track, with gapless playback or a crossfade into it
+//! - Transition history for debugging gaps between tracks
+//! - Raised (optionally realtime) scheduling priority for the audio threads
 
 pub mod buffer;
+pub mod channels;
+pub mod config;
 pub mod decode;
+pub mod dither;
 pub mod engine;
+pub mod eq;
 pub mod ffmpeg_decode;
+pub mod layout;
+pub mod levels;
+pub mod loudness;
+pub mod mixer;
 pub mod output;
+pub mod pitch;
+pub mod priority;
 pub mod resample;
+pub mod spectrum;
+pub mod sync;
+pub mod transition;
 
-pub use engine::{AudioEngine, EngineCommand, EngineEvent, PlaybackState};
+pub use channels::ChannelSettings;
+pub use config::EngineConfig;
+pub use dither::OutputFormat;
+pub use engine::{AudioEngine, CommandId, EngineCommand, EngineEvent, PlaybackState};
+pub use eq::{EqPreset, EqSettings};
+#[cfg(feature = "test-util")]
+pub use ffmpeg_decode::PCM_F32LE_MIME;
+pub use ffmpeg_decode::{ffmpeg_available, DecodeBackend};
+pub use layout::ChannelLayout;
+pub use levels::Levels;
+pub use loudness::{LoudnessMeter, LoudnessSettings, TrackLoudness};
+pub use mixer::MAX_CROSSFADE;
 pub use monad_core::StreamChunk;
+pub use output::OutputBackend;
+pub use pitch::MAX_PITCH_SEMITONES;
+pub use priority::ThreadPriority;
+pub use spectrum::SPECTRUM_BANDS;
+pub use sync::{SyncAction, SyncFollower, SyncLeader};
+pub use transition::{TransitionLog, TransitionRecord};
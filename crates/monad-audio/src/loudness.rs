@@ -0,0 +1,332 @@
+//! Loudness normalization.
+//!
+//! Tracks are measured for integrated loudness as in EBU R128 (K-weighted and
+//! gated, per ITU-R BS.1770) when they load, or take it from tags such as
+//! `ReplayGain`, and play with a gain bringing them to a target level. The gain
+//! never raises a track's peak above full scale.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::ChannelLayout;
+
+/// Default target loudness in LUFS, as used by most streaming services.
+pub const DEFAULT_TARGET_LUFS: f32 = -14.0;
+
+/// Reference loudness of `ReplayGain` 2.0 in LUFS.
+const REPLAY_GAIN_REFERENCE_LUFS: f32 = -18.0;
+
+/// Steps in a 400 ms gating block.
+const STEPS_PER_BLOCK: usize = 4;
+
+/// Blocks quieter than this are silence.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Blocks this much quieter than the ungated loudness are left out.
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+/// Weight of the surround channels' energy (+1.5 dB).
+const SURROUND_WEIGHT: f64 = 1.41;
+
+/// Loudness normalization settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessSettings {
+    /// Whether tracks are normalized.
+    pub enabled: bool,
+    /// Loudness to bring tracks to in LUFS.
+    pub target_lufs: f32,
+    /// Extra gain on top of the normalization in dB.
+    #[serde(default)]
+    pub preamp_db: f32,
+}
+
+impl Default for LoudnessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_lufs: DEFAULT_TARGET_LUFS,
+            preamp_db: 0.0,
+        }
+    }
+}
+
+impl LoudnessSettings {
+    /// Get the linear gain to play a track at, or unity for a track of
+    /// unknown loudness.
+    pub fn gain(&self, track: Option<&TrackLoudness>) -> f32 {
+        let Some(track) = track.filter(|_| self.enabled) else {
+            return 1.0;
+        };
+        let gain_db = self.target_lufs - track.integrated_lufs + self.preamp_db;
+        let headroom_db = -20.0 * track.peak.max(f32::EPSILON).log10();
+        10f32.powf(gain_db.min(headroom_db) / 20.0)
+    }
+}
+
+/// Measured or tagged loudness of a track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackLoudness {
+    /// Integrated loudness in LUFS.
+    pub integrated_lufs: f32,
+    /// Largest absolute sample value, 1.0 being full scale.
+    pub peak: f32,
+}
+
+impl TrackLoudness {
+    /// Get the loudness from a `ReplayGain` 2.0 track gain and peak.
+    pub fn from_replay_gain(gain_db: f32, peak: f32) -> Self {
+        Self {
+            integrated_lufs: REPLAY_GAIN_REFERENCE_LUFS - gain_db,
+            peak,
+        }
+    }
+
+    /// Measure interleaved 48kHz stereo samples, or `None` if they are
+    /// shorter than a gating block or silent.
+    pub fn measure(samples: &[f32]) -> Option<Self> {
+        let mut meter = LoudnessMeter::default();
+        meter.push(samples);
+        meter.finish()
+    }
+}
+
+/// Loudness measurement of a track fed to it as it is decoded.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    filters: Vec<KWeighting>,
+    /// Weight of each channel's energy, none for the LFE.
+    weights: Vec<f64>,
+    /// Samples in a 100 ms step between gating blocks.
+    step_samples: usize,
+    peak: f32,
+    /// Mean square power of each 100 ms step so far.
+    steps: Vec<f64>,
+    /// Summed channel energy of the step being measured.
+    energy: f64,
+    /// Samples of the step being measured.
+    counted: usize,
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        Self::new(48000)
+    }
+}
+
+impl LoudnessMeter {
+    /// Create a meter for stereo samples at `sample_rate`.
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_layout(sample_rate, ChannelLayout::Stereo)
+    }
+
+    /// Create a meter for samples at `sample_rate` in `layout`.
+    pub fn with_layout(sample_rate: u32, layout: ChannelLayout) -> Self {
+        let channels = usize::from(layout.channels());
+        // Channels are in WAVE order: the front three, the LFE, then surrounds
+        let weights = (0..channels)
+            .map(|channel| match channel {
+                0..=2 => 1.0,
+                3 => 0.0,
+                _ => SURROUND_WEIGHT,
+            })
+            .collect();
+        Self {
+            filters: vec![KWeighting::new(f64::from(sample_rate)); channels],
+            weights,
+            step_samples: sample_rate as usize / 10 * channels,
+            peak: 0.0,
+            steps: Vec::new(),
+            energy: 0.0,
+            counted: 0,
+        }
+    }
+
+    /// Measure the next interleaved samples.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn push(&mut self, samples: &[f32]) {
+        let channels = self.filters.len();
+        for &sample in samples {
+            self.peak = self.peak.max(sample.abs());
+            let channel = self.counted % channels;
+            let weighted = self.filters[channel].process(f64::from(sample));
+            self.energy += self.weights[channel] * weighted * weighted;
+            self.counted += 1;
+            if self.counted == self.step_samples {
+                // Channel energies are summed, each averaged over its frames
+                self.steps
+                    .push(self.energy / (self.step_samples / channels) as f64);
+                self.energy = 0.0;
+                self.counted = 0;
+            }
+        }
+    }
+
+    /// Get the loudness of the samples measured, or `None` if they are shorter
+    /// than a gating block or silent.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn finish(&self) -> Option<TrackLoudness> {
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(STEPS_PER_BLOCK)
+            .map(|window| window.iter().sum::<f64>() / STEPS_PER_BLOCK as f64)
+            .filter(|&power| loudness(power) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        let ungated = mean(&blocks)?;
+        let threshold = loudness(ungated) - RELATIVE_GATE_LU;
+        let gated: Vec<f64> = blocks
+            .into_iter()
+            .filter(|&power| loudness(power) > threshold)
+            .collect();
+        Some(TrackLoudness {
+            integrated_lufs: loudness(mean(&gated)?) as f32,
+            peak: self.peak,
+        })
+    }
+}
+
+/// Get the loudness of a mean square power in LUFS.
+fn loudness(power: f64) -> f64 {
+    10.0f64.mul_add(power.log10(), -0.691)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// The K-weighting filter of BS.1770: a high shelf modelling the head, then
+/// a high-pass.
+///
+/// BS.1770 gives the coefficients at 48kHz; those at other rates come from
+/// the analog filters they were derived from.
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf_b: [f64; 3],
+    shelf_a: [f64; 2],
+    high_pass_a: [f64; 2],
+    shelf: [f64; 4],
+    high_pass: [f64; 4],
+}
+
+impl KWeighting {
+    const SHELF_FREQUENCY: f64 = 1_681.974_450_955_533;
+    const SHELF_GAIN_DB: f64 = 3.999_843_853_973_347;
+    const SHELF_Q: f64 = 0.707_175_236_955_419_6;
+    const HIGH_PASS_FREQUENCY: f64 = 38.135_470_876_024_44;
+    const HIGH_PASS_Q: f64 = 0.500_327_037_323_877_3;
+    const HIGH_PASS_B: [f64; 3] = [1.0, -2.0, 1.0];
+
+    fn new(sample_rate: f64) -> Self {
+        let k = (PI * Self::SHELF_FREQUENCY / sample_rate).tan();
+        let vh = 10f64.powf(Self::SHELF_GAIN_DB / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = k.mul_add(k, 1.0 + k / Self::SHELF_Q);
+        let shelf_b = [
+            k.mul_add(k, vh + vb * k / Self::SHELF_Q) / a0,
+            2.0 * k.mul_add(k, -vh) / a0,
+            k.mul_add(k, vh - vb * k / Self::SHELF_Q) / a0,
+        ];
+        let shelf_a = [
+            2.0 * k.mul_add(k, -1.0) / a0,
+            k.mul_add(k, 1.0 - k / Self::SHELF_Q) / a0,
+        ];
+
+        let k = (PI * Self::HIGH_PASS_FREQUENCY / sample_rate).tan();
+        let a0 = k.mul_add(k, 1.0 + k / Self::HIGH_PASS_Q);
+        let high_pass_a = [
+            2.0 * k.mul_add(k, -1.0) / a0,
+            k.mul_add(k, 1.0 - k / Self::HIGH_PASS_Q) / a0,
+        ];
+        Self {
+            shelf_b,
+            shelf_a,
+            high_pass_a,
+            shelf: [0.0; 4],
+            high_pass: [0.0; 4],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let shelved = biquad(&mut self.shelf, self.shelf_b, self.shelf_a, x);
+        biquad(
+            &mut self.high_pass,
+            Self::HIGH_PASS_B,
+            self.high_pass_a,
+            shelved,
+        )
+    }
+}
+
+/// Run a direct form I biquad with state `[x1, x2, y1, y2]`.
+fn biquad(state: &mut [f64; 4], b: [f64; 3], a: [f64; 2], x: f64) -> f64 {
+    let [x1, x2, y1, y2] = *state;
+    let y = b[2].mul_add(x2, b[1].mul_add(x1, b[0] * x)) - a[1].mul_add(y2, a[0] * y1);
+    *state = [x, x1, y, y1];
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three seconds of a 1 kHz stereo sine.
+    #[allow(clippy::cast_possible_truncation)]
+    fn sine(amplitude: f32) -> Vec<f32> {
+        (0..144_000)
+            .flat_map(|i| {
+                let phase = 2.0 * PI * 1000.0 * f64::from(i) / 48000.0;
+                let value = phase.sin() as f32 * amplitude;
+                [value, value]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_loudness() {
+        // A full-scale 1 kHz sine in both channels measures 0 LUFS
+        let loudness = TrackLoudness::measure(&sine(0.5));
+        let lufs = loudness.map_or(0.0, |l| l.integrated_lufs);
+        assert!((lufs + 6.02).abs() < 0.1, "{lufs}");
+        assert_eq!(loudness.map(|l| l.peak), Some(0.5));
+
+        assert_eq!(TrackLoudness::measure(&vec![0.0; 96_000]), None);
+        assert_eq!(TrackLoudness::measure(&sine(0.5)[..1000]), None);
+
+        // Measuring in chunks gives the same loudness
+        let mut meter = LoudnessMeter::default();
+        for chunk in sine(0.5).chunks(2048) {
+            meter.push(chunk);
+        }
+        assert_eq!(meter.finish(), loudness);
+
+        // The filter matches the coefficients BS.1770 gives at 48kHz
+        let filter = KWeighting::new(48000.0);
+        assert!((filter.shelf_b[0] - 1.535_124_859_586_97).abs() < 1e-9);
+        assert!((filter.shelf_a[0] + 1.690_659_293_182_41).abs() < 1e-9);
+        assert!((filter.high_pass_a[1] - 0.990_072_250_366_21).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_gain_reaches_target_without_clipping() {
+        let settings = LoudnessSettings {
+            enabled: true,
+            ..LoudnessSettings::default()
+        };
+        let loud = TrackLoudness {
+            integrated_lufs: -6.0,
+            peak: 1.0,
+        };
+        // 8 dB down
+        assert!((settings.gain(Some(&loud)) - 0.398).abs() < 0.001);
+
+        // A quiet track is only raised until its peak reaches full scale
+        let quiet = TrackLoudness::from_replay_gain(12.0, 0.5);
+        assert!((quiet.integrated_lufs + 30.0).abs() < f32::EPSILON);
+        assert!((settings.gain(Some(&quiet)) - 2.0).abs() < 0.01);
+
+        assert!((settings.gain(None) - 1.0).abs() < f32::EPSILON);
+        let disabled = LoudnessSettings::default();
+        assert!((disabled.gain(Some(&loud)) - 1.0).abs() < f32::EPSILON);
+    }
+}
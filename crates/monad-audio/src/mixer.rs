@@ -0,0 +1,85 @@
+//! Crossfading from the end of one track into the next.
+//!
+//! While a crossfade runs, the engine decodes both the outgoing track and the
+//! preloaded next one, and the mixer combines them before they reach the ring
+//! buffer. Equal-power curves keep the loudness steady through the fade.
+
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+use crate::layout::ChannelLayout;
+
+/// Longest crossfade the engine allows.
+pub const MAX_CROSSFADE: Duration = Duration::from_secs(12);
+
+/// A crossfade in progress, over interleaved samples.
+#[derive(Debug)]
+pub(crate) struct Crossfade {
+    /// Length of the fade in samples.
+    length: usize,
+    /// Samples mixed so far.
+    mixed: usize,
+    sample_rate: u32,
+    layout: ChannelLayout,
+}
+
+impl Crossfade {
+    /// Start a crossfade lasting `length` samples at `sample_rate` in `layout`.
+    pub(crate) const fn new(length: usize, sample_rate: u32, layout: ChannelLayout) -> Self {
+        Self {
+            length,
+            mixed: 0,
+            sample_rate,
+            layout,
+        }
+    }
+
+    /// Get the number of samples mixed so far.
+    pub(crate) const fn mixed(&self) -> usize {
+        self.mixed
+    }
+
+    /// Get the length of the fade.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn duration(&self) -> Duration {
+        let channels = f64::from(self.layout.channels());
+        Duration::from_secs_f64(self.length as f64 / (f64::from(self.sample_rate) * channels))
+    }
+
+    /// Mix the next samples of the incoming track into the outgoing ones.
+    ///
+    /// Samples past the end of the fade are taken from the incoming track alone.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn mix(&mut self, outgoing: &mut [f32], incoming: &[f32]) {
+        let channels = usize::from(self.layout.channels());
+        for (frame, next) in outgoing.chunks_mut(channels).zip(incoming.chunks(channels)) {
+            let progress = (self.mixed as f32 / self.length.max(1) as f32).min(1.0);
+            let (fade_in, fade_out) = (progress * FRAC_PI_2).sin_cos();
+            for (sample, next) in frame.iter_mut().zip(next) {
+                *sample = sample.mul_add(fade_out, next * fade_in);
+            }
+            self.mixed += frame.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossfade_mixes_tracks() {
+        let mut fade = Crossfade::new(8, 48000, ChannelLayout::Stereo);
+        let mut outgoing = [1.0; 12];
+        fade.mix(&mut outgoing, &[0.5; 12]);
+        assert_eq!(fade.mixed(), 12);
+        assert_eq!(fade.duration().as_nanos(), 83_333);
+
+        // Starts with only the outgoing track and ends with only the incoming one
+        assert!((outgoing[0] - 1.0).abs() < 1e-6);
+        assert!((outgoing[10] - 0.5).abs() < 1e-6);
+        // Equal power halfway through
+        let expected = 1.5 * std::f32::consts::FRAC_1_SQRT_2;
+        assert!((outgoing[4] - expected).abs() < 1e-6);
+    }
+}
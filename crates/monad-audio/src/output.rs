@@ -1,22 +1,51 @@
-//! Audio output using cpal.
+//! Audio output using cpal, plus virtual outputs for running without a sound card.
 
-use crate::buffer::SharedRingBuffer;
+use crate::buffer::{RingBuffer, SharedRingBuffer};
+use crate::channels::ChannelSettings;
+use crate::dither::{Dither, OutputFormat, OutputSample};
+use crate::layout::{ChannelLayout, MAX_CHANNELS};
+use crate::priority::ThreadPriority;
 use crate::PlaybackState;
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, SampleFormat, Stream, StreamConfig,
+    Device, SampleRate, Stream, StreamConfig, SupportedStreamConfig,
 };
 use monad_core::{Error, Result};
 use parking_lot::{Mutex, RwLock};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+/// Length of the ramps smoothing starts, stops and jumps in playback in seconds.
+const FADE_SECS: f32 = 0.03;
+
+/// Output backend the engine renders audio to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OutputBackend {
+    /// The system's default output device.
+    #[default]
+    Device,
+    /// Virtual output that renders in real time and discards the samples.
+    Null,
+    /// Virtual output that renders in real time and writes raw f32le PCM to a file.
+    ///
+    /// Only periods rendered while playing are written, so the file holds exactly
+    /// what would have been heard.
+    File(PathBuf),
+}
+
 /// Audio output stream configuration.
 #[derive(Debug, Clone)]
 pub struct OutputConfig {
     pub sample_rate: u32,
     pub channels: u16,
     pub buffer_size: u32,
+    pub format: OutputFormat,
 }
 
 impl Default for OutputConfig {
@@ -25,22 +54,111 @@ impl Default for OutputConfig {
             sample_rate: 48000,
             channels: 2,
             buffer_size: 1024,
+            format: OutputFormat::F32,
         }
     }
 }
 
 /// Audio output stream wrapper.
 pub struct AudioOutput {
-    _stream: Stream,
+    _stream: OutputStream,
     config: OutputConfig,
     device_name: String,
+    /// Set once the stream reports an error, such as its device going away.
+    failed: Arc<AtomicBool>,
+    /// Whether the output was opened on the default device and should move
+    /// when the default changes.
+    follows_default: bool,
+    /// Time between samples leaving the ring buffer and being heard in
+    /// microseconds, as last measured by the stream.
+    latency: Arc<AtomicU64>,
+    /// Channel layout the output plays.
+    layout: ChannelLayout,
+    /// Channel layouts the device supports.
+    supported_layouts: Vec<ChannelLayout>,
+}
+
+/// Backing stream kept alive for the lifetime of an [`AudioOutput`].
+#[allow(dead_code)] // Held only so the stream is dropped with the output
+enum OutputStream {
+    Device(Stream),
+    Virtual(VirtualOutput),
 }
 
 impl AudioOutput {
-    /// Create a new audio output with the default device.
+    /// Create a new audio output for the given backend, rendering at the given priority.
+    ///
+    /// Devices render at `sample_rate` if they support it, or at their native
+    /// rate otherwise; virtual outputs at `sample_rate` or 48kHz. Both play
+    /// `layout` if they can, or stereo.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open(
+        backend: &OutputBackend,
+        sample_rate: Option<u32>,
+        layout: ChannelLayout,
+        priority: ThreadPriority,
+        ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
+        volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
+        state: Arc<RwLock<PlaybackState>>,
+    ) -> Result<Self> {
+        let virtual_config = OutputConfig {
+            sample_rate: sample_rate.unwrap_or(48000),
+            channels: layout.channels(),
+            ..OutputConfig::default()
+        };
+        match backend {
+            OutputBackend::Device => Self::new(
+                sample_rate,
+                layout,
+                priority,
+                ring_buffer,
+                monitor,
+                volume,
+                channels,
+                state,
+            ),
+            OutputBackend::Null => Self::with_virtual(
+                VirtualSink::Null,
+                virtual_config,
+                priority,
+                ring_buffer,
+                monitor,
+                volume,
+                channels,
+                state,
+            ),
+            OutputBackend::File(path) => {
+                let file = File::create(path).map_err(|e| {
+                    Error::AudioOutput(format!("Failed to create {}: {e}", path.display()))
+                })?;
+                let sink = VirtualSink::File(BufWriter::new(file));
+                Self::with_virtual(
+                    sink,
+                    virtual_config,
+                    priority,
+                    ring_buffer,
+                    monitor,
+                    volume,
+                    channels,
+                    state,
+                )
+            }
+        }
+    }
+
+    /// Create a new audio output with the default device, at `sample_rate` if
+    /// it supports it or its native rate otherwise, playing `layout` if it can.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        sample_rate: Option<u32>,
+        layout: ChannelLayout,
+        priority: ThreadPriority,
         ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
         volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
         state: Arc<RwLock<PlaybackState>>,
     ) -> Result<Self> {
         let host = cpal::default_host();
@@ -52,55 +170,117 @@ impl AudioOutput {
         let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
         info!("Using audio output device: {device_name}");
 
-        Self::with_device(device, ring_buffer, volume, state)
+        let mut output = Self::with_device(
+            device,
+            sample_rate,
+            layout,
+            priority,
+            ring_buffer,
+            monitor,
+            volume,
+            channels,
+            state,
+        )?;
+        output.follows_default = true;
+        Ok(output)
     }
 
-    /// Create a new audio output with a specific device.
+    /// Create a new audio output with a specific device, at `sample_rate` if
+    /// it supports it or its native rate otherwise, playing `layout` if it can.
     #[allow(clippy::needless_pass_by_value)] // Device is typically moved
+    #[allow(clippy::too_many_arguments)]
     pub fn with_device(
         device: Device,
+        sample_rate: Option<u32>,
+        layout: ChannelLayout,
+        priority: ThreadPriority,
         ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
         volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
         state: Arc<RwLock<PlaybackState>>,
     ) -> Result<Self> {
         let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
 
         // Get supported config
-        let supported_config = device
-            .default_output_config()
-            .map_err(|e| Error::AudioOutput(format!("Failed to get output config: {e}")))?;
+        let supported_config = negotiate_config(&device, sample_rate, layout)?;
 
         debug!("Supported output config: {:?}", supported_config);
 
         let sample_format = supported_config.sample_format();
+        let format = OutputFormat::from_cpal(sample_format).ok_or_else(|| {
+            Error::AudioOutput(format!("Unsupported sample format: {sample_format:?}"))
+        })?;
         let config: StreamConfig = supported_config.into();
+        let layout = ChannelLayout::from_channels(config.channels).ok_or_else(|| {
+            Error::AudioOutput(format!("Unsupported channel count: {}", config.channels))
+        })?;
+        let mut supported_layouts = supported_layouts(&device);
+        if !supported_layouts.contains(&layout) {
+            supported_layouts.push(layout);
+        }
 
         let output_config = OutputConfig {
             sample_rate: config.sample_rate.0,
             channels: config.channels,
             buffer_size: 1024,
+            format,
         };
 
         debug!(
-            "Output config: {}Hz, {} channels",
-            output_config.sample_rate, output_config.channels
+            "Output config: {}Hz, {} channels, {}",
+            output_config.sample_rate,
+            output_config.channels,
+            format.name()
         );
 
-        let stream = match sample_format {
-            SampleFormat::F32 => {
-                Self::build_stream::<f32>(&device, &config, ring_buffer, volume, state)?
-            }
-            SampleFormat::I16 => {
-                Self::build_stream::<i16>(&device, &config, ring_buffer, volume, state)?
-            }
-            SampleFormat::U16 => {
-                Self::build_stream::<u16>(&device, &config, ring_buffer, volume, state)?
-            }
-            _ => {
-                return Err(Error::AudioOutput(format!(
-                    "Unsupported sample format: {sample_format:?}"
-                )));
-            }
+        let failed = Arc::new(AtomicBool::new(false));
+        // Until the stream measures it, samples read by a callback are taken
+        // to be heard by the time of the next one
+        let period = Duration::from_secs_f64(
+            f64::from(output_config.buffer_size) / f64::from(output_config.sample_rate),
+        );
+        let latency = Arc::new(AtomicU64::new(duration_micros(period)));
+        let stream = match format {
+            OutputFormat::F32 => Self::build_stream::<f32>(
+                &device,
+                &config,
+                layout,
+                priority,
+                ring_buffer,
+                monitor,
+                volume,
+                channels,
+                state,
+                failed.clone(),
+                latency.clone(),
+            )?,
+            OutputFormat::I16 => Self::build_stream::<i16>(
+                &device,
+                &config,
+                layout,
+                priority,
+                ring_buffer,
+                monitor,
+                volume,
+                channels,
+                state,
+                failed.clone(),
+                latency.clone(),
+            )?,
+            OutputFormat::U16 => Self::build_stream::<u16>(
+                &device,
+                &config,
+                layout,
+                priority,
+                ring_buffer,
+                monitor,
+                volume,
+                channels,
+                state,
+                failed.clone(),
+                latency.clone(),
+            )?,
         };
 
         stream
@@ -108,64 +288,133 @@ impl AudioOutput {
             .map_err(|e| Error::AudioOutput(format!("Failed to start stream: {e}")))?;
 
         Ok(Self {
-            _stream: stream,
+            _stream: OutputStream::Device(stream),
             config: output_config,
             device_name,
+            failed,
+            follows_default: false,
+            latency,
+            layout,
+            supported_layouts,
+        })
+    }
+
+    /// Create a virtual output rendering on its own thread.
+    #[allow(clippy::too_many_arguments)]
+    fn with_virtual(
+        sink: VirtualSink,
+        config: OutputConfig,
+        priority: ThreadPriority,
+        ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
+        volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
+        state: Arc<RwLock<PlaybackState>>,
+    ) -> Result<Self> {
+        let device_name = match sink {
+            VirtualSink::Null => "Null Output",
+            VirtualSink::File(_) => "File Output",
+        }
+        .to_string();
+        info!("Using virtual audio output: {device_name}");
+
+        let layout = ChannelLayout::from_channels(config.channels).unwrap_or_default();
+        let output = VirtualOutput::spawn(
+            &config,
+            layout,
+            sink,
+            priority,
+            ring_buffer,
+            monitor,
+            volume,
+            channels,
+            state,
+        )?;
+
+        Ok(Self {
+            _stream: OutputStream::Virtual(output),
+            config,
+            device_name,
+            failed: Arc::new(AtomicBool::new(false)),
+            follows_default: false,
+            // Samples are written out as soon as they are rendered
+            latency: Arc::new(AtomicU64::new(0)),
+            layout,
+            // Any layout can be rendered without a device
+            supported_layouts: vec![
+                ChannelLayout::Stereo,
+                ChannelLayout::Surround51,
+                ChannelLayout::Surround71,
+            ],
         })
     }
 
-    fn build_stream<T: cpal::SizedSample + cpal::FromSample<f32>>(
+    #[allow(clippy::too_many_arguments)]
+    fn build_stream<T: OutputSample>(
         device: &Device,
         config: &StreamConfig,
+        layout: ChannelLayout,
+        priority: ThreadPriority,
         ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
         volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
         state: Arc<RwLock<PlaybackState>>,
+        failed: Arc<AtomicBool>,
+        latency: Arc<AtomicU64>,
     ) -> Result<Stream> {
-        let _channels = usize::from(config.channels);
+        let frame_rate = f64::from(config.sample_rate.0) * f64::from(config.channels);
 
-        let err_fn = |err| {
+        // The stream stops on errors such as its device being unplugged, so
+        // the engine is left to reopen the output
+        let err_fn = move |err| {
             error!("Audio stream error: {err}");
+            failed.store(true, Ordering::Release);
         };
 
+        // The callback runs on a thread owned by the audio backend, so it is
+        // raised on its first call
+        let mut promoted = false;
+        let mut fader = Fader::new(config.sample_rate.0, layout, &ring_buffer);
+        let mut dither = Dither::default();
+
         let stream = device
             .build_output_stream(
                 config,
-                move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-                    // Check playback state - only output audio when playing
-                    let current_state = *state.read();
-                    if current_state != PlaybackState::Playing {
-                        // Output silence when not playing
-                        for sample in data.iter_mut() {
-                            *sample = T::from_sample(0.0f32);
-                        }
-                        return;
+                move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+                    if !promoted {
+                        promoted = true;
+                        priority.apply_to_current_thread("audio-output");
                     }
-
-                    let vol = *volume.lock();
-                    let samples_needed = data.len();
-
-                    // Read from ring buffer
-                    let mut temp_buffer = vec![0.0f32; samples_needed];
-                    let samples_read = ring_buffer.read(&mut temp_buffer);
-
-                    // Convert and apply volume with soft limiting to prevent distortion
-                    for (i, sample) in data.iter_mut().enumerate() {
-                        if i < samples_read {
-                            let s = temp_buffer[i] * vol;
-                            // Soft clipping using tanh for smooth limiting
-                            let limited = if s.abs() > 0.9 { s.tanh() } else { s };
-                            *sample = T::from_sample(limited);
-                        } else {
-                            // Fill with silence if buffer underrun
-                            *sample = T::from_sample(0.0f32);
+                    // The samples read now start playing after the stream's
+                    // delay, and the last of them a buffer later
+                    let timestamp = info.timestamp();
+                    let delay = timestamp
+                        .playback
+                        .duration_since(&timestamp.callback)
+                        .unwrap_or_default();
+                    #[allow(clippy::cast_precision_loss)]
+                    let buffered = Duration::from_secs_f64(data.len() as f64 / frame_rate);
+                    latency.store(duration_micros(delay + buffered), Ordering::Relaxed);
+
+                    let mut temp_buffer = vec![0.0f32; data.len()];
+                    let active = render(
+                        &mut temp_buffer,
+                        &ring_buffer,
+                        &monitor,
+                        &volume,
+                        &channels,
+                        &state,
+                        &mut fader,
+                    );
+
+                    if active {
+                        for (sample, &s) in data.iter_mut().zip(&temp_buffer) {
+                            *sample = T::from_rendered(s, &mut dither);
                         }
-                    }
-
-                    if samples_read < samples_needed && samples_read > 0 {
-                        warn!(
-                            "Buffer underrun: needed {}, got {}",
-                            samples_needed, samples_read
-                        );
+                    } else {
+                        // Silence stays silent rather than playing dither noise
+                        data.fill(T::EQUILIBRIUM);
                     }
                 },
                 err_fn,
@@ -186,6 +435,14 @@ impl AudioOutput {
         &self.device_name
     }
 
+    /// Check whether the output should be reopened, because its stream
+    /// failed or the default device it follows changed.
+    pub fn needs_reopen(&self) -> bool {
+        self.failed.load(Ordering::Acquire)
+            || (self.follows_default
+                && default_device_name().is_some_and(|name| name != self.device_name))
+    }
+
     /// Get the sample rate.
     pub const fn sample_rate(&self) -> u32 {
         self.config.sample_rate
@@ -195,6 +452,296 @@ impl AudioOutput {
     pub const fn channels(&self) -> u16 {
         self.config.channels
     }
+
+    /// Get the sample format the output plays.
+    pub const fn format(&self) -> OutputFormat {
+        self.config.format
+    }
+
+    /// Get the channel layout the output plays.
+    pub const fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Get the channel layouts the device supports.
+    pub fn supported_layouts(&self) -> &[ChannelLayout] {
+        &self.supported_layouts
+    }
+
+    /// Get the time between samples leaving the ring buffer and being heard.
+    pub fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency.load(Ordering::Relaxed))
+    }
+}
+
+/// Choose a config of a device playing `layout`, or stereo if it can't, at
+/// `sample_rate` or at the device's native rate, so that the system mixer
+/// need not resample or remix. Float samples are preferred over 16-bit ones,
+/// which are dithered.
+///
+/// Falls back to the device's default config if it has no such config.
+fn negotiate_config(
+    device: &Device,
+    sample_rate: Option<u32>,
+    layout: ChannelLayout,
+) -> Result<SupportedStreamConfig> {
+    let default = device
+        .default_output_config()
+        .map_err(|e| Error::AudioOutput(format!("Failed to get output config: {e}")))?;
+    let native_rate = default.sample_rate().0;
+
+    let Ok(supported) = device.supported_output_configs() else {
+        return Ok(default);
+    };
+    let playable: Vec<_> = supported
+        .filter(|config| OutputFormat::from_cpal(config.sample_format()).is_some())
+        .collect();
+    let at_rate = |channels: u16, rate: u32| {
+        playable
+            .iter()
+            .filter(|config| {
+                config.channels() == channels
+                    && (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&rate)
+            })
+            .min_by_key(|config| OutputFormat::from_cpal(config.sample_format()))
+            .map(|config| config.with_sample_rate(SampleRate(rate)))
+    };
+    let in_layout = |channels: u16| {
+        sample_rate
+            .and_then(|rate| at_rate(channels, rate))
+            .or_else(|| at_rate(channels, native_rate))
+    };
+    Ok(in_layout(layout.channels())
+        .or_else(|| in_layout(ChannelLayout::Stereo.channels()))
+        .unwrap_or(default))
+}
+
+/// Get the channel layouts a device has configs for.
+fn supported_layouts(device: &Device) -> Vec<ChannelLayout> {
+    let mut layouts = Vec::new();
+    if let Ok(supported) = device.supported_output_configs() {
+        for layout in supported.filter_map(|config| ChannelLayout::from_channels(config.channels()))
+        {
+            if !layouts.contains(&layout) {
+                layouts.push(layout);
+            }
+        }
+    }
+    layouts
+}
+
+/// Get a duration in whole microseconds.
+fn duration_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Gain ramps keeping playback from starting, stopping or jumping with a click.
+///
+/// Playing fades in, as does playback resuming after the buffer ran dry, and
+/// pausing or stopping fades out over the samples still buffered before
+/// falling silent. When the engine clears the buffer, such as to seek, the
+/// last frame played fades out while the new samples fade in.
+struct Fader {
+    /// Change in gain per frame.
+    step: f32,
+    /// Gain of the samples read.
+    gain: f32,
+    /// Channel layout of the samples.
+    layout: ChannelLayout,
+    /// Last frame played before a jump.
+    last: [f32; MAX_CHANNELS],
+    /// Gain of `last`, fading out after a jump.
+    tail: f32,
+    /// Clears of the ring buffer seen so far.
+    clears: usize,
+}
+
+impl Fader {
+    /// Create a silent fader for samples at `sample_rate` in `layout`.
+    #[allow(clippy::cast_precision_loss)]
+    fn new(sample_rate: u32, layout: ChannelLayout, ring_buffer: &RingBuffer) -> Self {
+        Self {
+            step: 1.0 / (FADE_SECS * sample_rate as f32),
+            gain: 0.0,
+            layout,
+            last: [0.0; MAX_CHANNELS],
+            tail: 0.0,
+            clears: ring_buffer.clears(),
+        }
+    }
+
+    /// Get the number of samples left to fade out.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn samples_to_silence(&self) -> usize {
+        (self.gain / self.step).ceil() as usize * usize::from(self.layout.channels())
+    }
+}
+
+/// Fill `data` with the next rendered samples, returning whether playback is active.
+///
+/// Outputs silence unless playing, or fading out; applies volume and the
+/// channel settings with soft limiting to prevent distortion. The samples played are copied to `monitor`
+/// for analysis, as far as it has room.
+fn render(
+    data: &mut [f32],
+    ring_buffer: &SharedRingBuffer,
+    monitor: &SharedRingBuffer,
+    volume: &Mutex<f32>,
+    channels: &Mutex<ChannelSettings>,
+    state: &RwLock<PlaybackState>,
+    fader: &mut Fader,
+) -> bool {
+    let playing = *state.read() == PlaybackState::Playing;
+    let clears = ring_buffer.clears();
+    if clears != fader.clears {
+        fader.clears = clears;
+        fader.gain = 0.0;
+        fader.tail = 1.0;
+    }
+    if !playing && fader.tail == 0.0 && (fader.gain == 0.0 || ring_buffer.is_empty()) {
+        // Nothing left to fade out
+        fader.gain = 0.0;
+        data.fill(0.0);
+        return false;
+    }
+
+    // Once paused, only the samples left to fade out are read
+    let samples_needed = if playing {
+        data.len()
+    } else {
+        fader.samples_to_silence().min(data.len())
+    };
+    let samples_read = ring_buffer.read(&mut data[..samples_needed]);
+    // Fill with silence if buffer underrun
+    data[samples_read..].fill(0.0);
+
+    let vol = *volume.lock();
+    let channels = *channels.lock();
+    for frame in data.chunks_exact_mut(usize::from(fader.layout.channels())) {
+        fader.gain = if playing {
+            (fader.gain + fader.step).min(1.0)
+        } else {
+            (fader.gain - fader.step).max(0.0)
+        };
+        fader.tail = (fader.tail - fader.step).max(0.0);
+        let mut mixed = [0.0; MAX_CHANNELS];
+        let mixed = &mut mixed[..frame.len()];
+        for ((mixed, sample), last) in mixed.iter_mut().zip(&*frame).zip(fader.last) {
+            *mixed = (sample * vol).mul_add(fader.gain, last * fader.tail);
+        }
+        if fader.tail == 0.0 {
+            fader.last[..mixed.len()].copy_from_slice(mixed);
+        }
+        channels.apply(mixed, fader.layout);
+        for (sample, &s) in frame.iter_mut().zip(&*mixed) {
+            // Soft clipping using tanh for smooth limiting
+            *sample = if s.abs() > 0.9 { s.tanh() } else { s };
+        }
+    }
+    if samples_read < samples_needed {
+        // Whatever plays after running dry fades in
+        fader.gain = 0.0;
+    }
+    monitor.write(&data[..samples_read]);
+
+    if playing && samples_read < samples_needed && samples_read > 0 {
+        ring_buffer.record_underrun();
+        warn!(
+            "Buffer underrun: needed {}, got {}",
+            samples_needed, samples_read
+        );
+    }
+
+    true
+}
+
+/// Destination for samples rendered by a [`VirtualOutput`].
+enum VirtualSink {
+    Null,
+    File(BufWriter<File>),
+}
+
+impl VirtualSink {
+    fn write(&mut self, samples: &[f32]) {
+        let Self::File(writer) = self else {
+            return;
+        };
+
+        let result = samples
+            .iter()
+            .try_for_each(|s| writer.write_all(&s.to_le_bytes()))
+            .and_then(|()| writer.flush());
+        if let Err(e) = result {
+            error!("Failed to write rendered audio: {e}");
+        }
+    }
+}
+
+/// Real-time render thread standing in for a sound card.
+struct VirtualOutput {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl VirtualOutput {
+    #[allow(clippy::too_many_arguments)]
+    fn spawn(
+        config: &OutputConfig,
+        layout: ChannelLayout,
+        mut sink: VirtualSink,
+        priority: ThreadPriority,
+        ring_buffer: SharedRingBuffer,
+        monitor: SharedRingBuffer,
+        volume: Arc<Mutex<f32>>,
+        channels: Arc<Mutex<ChannelSettings>>,
+        state: Arc<RwLock<PlaybackState>>,
+    ) -> Result<Self> {
+        let running = Arc::new(AtomicBool::new(true));
+        let period =
+            Duration::from_secs_f64(f64::from(config.buffer_size) / f64::from(config.sample_rate));
+        let mut buffer = vec![0.0f32; config.buffer_size as usize * usize::from(config.channels)];
+        let mut fader = Fader::new(config.sample_rate, layout, &ring_buffer);
+
+        let running_clone = running.clone();
+        let thread = std::thread::Builder::new()
+            .name("audio-virtual-output".to_string())
+            .spawn(move || {
+                priority.apply_to_current_thread("audio-virtual-output");
+                let mut next_tick = Instant::now();
+                while running_clone.load(Ordering::Acquire) {
+                    let active = render(
+                        &mut buffer,
+                        &ring_buffer,
+                        &monitor,
+                        &volume,
+                        &channels,
+                        &state,
+                        &mut fader,
+                    );
+                    if active {
+                        sink.write(&buffer);
+                    }
+
+                    next_tick += period;
+                    std::thread::sleep(next_tick.saturating_duration_since(Instant::now()));
+                }
+            })
+            .map_err(|e| Error::AudioOutput(format!("Failed to spawn output thread: {e}")))?;
+
+        Ok(Self {
+            running,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for VirtualOutput {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
 }
 
 /// List available output devices.
@@ -228,6 +775,115 @@ mod tests {
         let _ = result;
     }
 
+    #[test]
+    fn test_render_applies_volume_when_playing() {
+        let ring_buffer = crate::buffer::shared_ring_buffer(64);
+        let monitor = crate::buffer::shared_ring_buffer(64);
+        let volume = Mutex::new(0.5);
+        let channels = Mutex::new(ChannelSettings::default());
+        let state = RwLock::new(PlaybackState::Paused);
+        let mut fader = Fader::new(48000, ChannelLayout::Stereo, &ring_buffer);
+        ring_buffer.write(&[0.4, -0.4, 0.2, -0.2]);
+
+        let mut data = [1.0f32; 6];
+        assert!(!render(
+            &mut data,
+            &ring_buffer,
+            &monitor,
+            &volume,
+            &channels,
+            &state,
+            &mut fader
+        ));
+        assert!(data.iter().all(|&s| s == 0.0));
+
+        // Already faded in
+        fader.gain = 1.0;
+        *state.write() = PlaybackState::Playing;
+        assert!(render(
+            &mut data,
+            &ring_buffer,
+            &monitor,
+            &volume,
+            &channels,
+            &state,
+            &mut fader
+        ));
+        let expected = [0.2, -0.2, 0.1, -0.1, 0.0, 0.0];
+        assert!(data
+            .iter()
+            .zip(expected)
+            .all(|(s, e)| (s - e).abs() < f32::EPSILON));
+        // Only what was played reaches the monitor
+        assert_eq!(monitor.available(), 4);
+    }
+
+    #[test]
+    fn test_render_fades_without_clicks() {
+        let ring_buffer = crate::buffer::shared_ring_buffer(16384);
+        let monitor = crate::buffer::shared_ring_buffer(64);
+        let volume = Mutex::new(0.5);
+        let channels = Mutex::new(ChannelSettings::default());
+        let state = RwLock::new(PlaybackState::Playing);
+        let mut fader = Fader::new(48000, ChannelLayout::Stereo, &ring_buffer);
+        let mut played = Vec::new();
+        let mut render_all = |state: &RwLock<PlaybackState>, fader: &mut Fader| {
+            let mut data = [0.0f32; 1024];
+            for _ in 0..4 {
+                render(
+                    &mut data,
+                    &ring_buffer,
+                    &monitor,
+                    &volume,
+                    &channels,
+                    state,
+                    fader,
+                );
+                played.extend_from_slice(&data);
+            }
+        };
+
+        // Fade in, then jump to samples of the opposite sign as a seek does
+        ring_buffer.write(&vec![1.0; 8192]);
+        render_all(&state, &mut fader);
+        ring_buffer.clear();
+        ring_buffer.write(&vec![-1.0; 8192]);
+        render_all(&state, &mut fader);
+        *state.write() = PlaybackState::Paused;
+        render_all(&state, &mut fader);
+
+        assert!((played[4094] - 0.5).abs() < f32::EPSILON);
+        assert!((played[8190] + 0.5).abs() < f32::EPSILON);
+        assert!(played[played.len() - 2..].iter().all(|&s| s == 0.0));
+        // Pausing only used up the samples it faded out
+        assert_eq!(ring_buffer.available(), 8192 - 4096 - 2880);
+        // Neighbouring frames of a channel never move by more than the ramps do
+        assert!(played
+            .windows(3)
+            .all(|samples| (samples[2] - samples[0]).abs() < 0.001));
+    }
+
+    #[test]
+    fn test_virtual_output_never_reopens() -> Result<()> {
+        let output = AudioOutput::open(
+            &OutputBackend::Null,
+            Some(44100),
+            ChannelLayout::Surround51,
+            ThreadPriority::Normal,
+            crate::buffer::shared_ring_buffer(64),
+            crate::buffer::shared_ring_buffer(64),
+            Arc::new(Mutex::new(1.0)),
+            Arc::new(Mutex::new(ChannelSettings::default())),
+            Arc::new(RwLock::new(PlaybackState::Stopped)),
+        )?;
+        assert_eq!(output.device_name(), "Null Output");
+        assert_eq!(output.sample_rate(), 44100);
+        assert_eq!(output.layout(), ChannelLayout::Surround51);
+        assert_eq!(output.channels(), 6);
+        assert!(!output.needs_reopen());
+        Ok(())
+    }
+
     #[test]
     fn test_default_config() {
         let config = OutputConfig::default();
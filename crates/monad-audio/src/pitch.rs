@@ -0,0 +1,168 @@
+//! Pitch shifting without changing speed.
+//!
+//! Samples pass through a short delay line read by two taps, each sweeping
+//! the delay at a rate that plays it back faster or slower. When a tap runs
+//! out of delay it jumps back across the window while faded out, and the
+//! other tap, half a window apart, takes over. Like the equalizer, it filters
+//! decoded samples before they reach the ring buffer.
+
+use std::f64::consts::PI;
+
+use crate::layout::ChannelLayout;
+
+/// Largest pitch shift either way in semitones.
+pub const MAX_PITCH_SEMITONES: f32 = 12.0;
+
+/// Length of the delay swept by each tap in seconds.
+const WINDOW_SECS: f64 = 0.05;
+
+/// Pitch shifter applied to interleaved samples.
+#[derive(Debug)]
+pub(crate) struct PitchShifter {
+    /// Rate the taps play the delay line back at, 1.0 for no shift.
+    ratio: f64,
+    /// Length of the delay swept by each tap in frames.
+    window: f64,
+    /// Channels of the samples to shift.
+    channels: usize,
+    /// Recent frames, interleaved and written in a circle.
+    history: Vec<f32>,
+    /// Frame the next one is written at.
+    write: usize,
+    /// Progress of the first tap through the window, from 0.0 to 1.0.
+    phase: f64,
+}
+
+impl PitchShifter {
+    /// Create a shifter leaving the pitch of samples at `sample_rate` in
+    /// `layout` alone.
+    pub(crate) fn new(sample_rate: u32, layout: ChannelLayout) -> Self {
+        let mut shifter = Self {
+            ratio: 1.0,
+            window: 0.0,
+            channels: usize::from(layout.channels()),
+            history: Vec::new(),
+            write: 0,
+            phase: 0.0,
+        };
+        shifter.set_sample_rate(sample_rate);
+        shifter
+    }
+
+    /// Change the sample rate of the samples to shift.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.window = (f64::from(sample_rate) * WINDOW_SECS).round();
+        self.history = vec![0.0; (self.window as usize + 2) * self.channels];
+        self.reset();
+    }
+
+    /// Change the channel layout of the samples to shift.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn set_layout(&mut self, layout: ChannelLayout) {
+        self.channels = usize::from(layout.channels());
+        self.history = vec![0.0; (self.window as usize + 2) * self.channels];
+        self.reset();
+    }
+
+    /// Shift by `semitones`, clamped to [`MAX_PITCH_SEMITONES`] either way.
+    pub(crate) fn set_semitones(&mut self, semitones: f32) {
+        let semitones = semitones.clamp(-MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES);
+        self.ratio = (f64::from(semitones) / 12.0).exp2();
+    }
+
+    /// Forget past samples, such as after a seek.
+    pub(crate) fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.write = 0;
+        self.phase = 0.0;
+    }
+
+    /// Shift samples in place.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn process(&mut self, samples: &mut [f32]) {
+        if (self.ratio - 1.0).abs() < f64::EPSILON {
+            return;
+        }
+        let step = (1.0 - self.ratio) / self.window;
+        let frames = self.history.len() / self.channels;
+        for frame in samples.chunks_exact_mut(self.channels) {
+            let start = self.write * self.channels;
+            self.history[start..start + self.channels].copy_from_slice(frame);
+            let first = self.phase * self.window;
+            let second = (self.phase + 0.5).fract() * self.window;
+            // Each tap is silent as it jumps, and the gains always sum to one
+            let gain = (PI * self.phase).sin().powi(2);
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mixed = f64::from(self.tap(first, channel))
+                    .mul_add(gain, f64::from(self.tap(second, channel)) * (1.0 - gain));
+                *sample = mixed as f32;
+            }
+            self.write = (self.write + 1) % frames;
+            self.phase = (self.phase + step).rem_euclid(1.0);
+        }
+    }
+
+    /// Read a channel of the frame `delay` frames before the last one
+    /// written, interpolating between frames.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn tap(&self, delay: f64, channel: usize) -> f32 {
+        let frames = self.history.len() / self.channels;
+        let position = (self.write as f64 - delay).rem_euclid(frames as f64);
+        let index = position as usize % frames;
+        let fraction = (position - position.floor()) as f32;
+        let current = self.history[index * self.channels + channel];
+        let next = self.history[(index + 1) % frames * self.channels + channel];
+        (next - current).mul_add(fraction, current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Magnitude of a frequency in the left channel of stereo samples at 48kHz.
+    fn magnitude(samples: &[f32], frequency: f64) -> f64 {
+        let (re, im) =
+            samples
+                .iter()
+                .step_by(2)
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (i, &sample)| {
+                    #[allow(clippy::cast_precision_loss)]
+                    let phase = 2.0 * PI * frequency * i as f64 / 48000.0;
+                    (
+                        f64::from(sample).mul_add(phase.cos(), re),
+                        f64::from(sample).mul_add(-phase.sin(), im),
+                    )
+                });
+        re.hypot(im)
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn test_octave_up_doubles_frequency() {
+        let sine: Vec<f32> = (0..48_000)
+            .flat_map(|i| {
+                let value = (2.0 * PI * 440.0 * f64::from(i) / 48000.0).sin() as f32 * 0.5;
+                [value, value]
+            })
+            .collect();
+
+        let mut shifter = PitchShifter::new(48000, ChannelLayout::Stereo);
+        let mut unshifted = sine.clone();
+        shifter.process(&mut unshifted);
+        assert_eq!(unshifted, sine);
+
+        shifter.set_semitones(12.0);
+        let mut shifted = sine.clone();
+        shifter.process(&mut shifted);
+        assert_eq!(shifted.len(), sine.len());
+        assert!(magnitude(&shifted, 880.0) > 10.0 * magnitude(&shifted, 440.0));
+        assert!(shifted.iter().all(|sample| sample.abs() <= 0.5 + 1e-6));
+    }
+}
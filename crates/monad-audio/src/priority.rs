@@ -0,0 +1,188 @@
+//! Scheduling priority for audio threads.
+//!
+//! Raising the priority of the output callback and engine thread reduces
+//! dropouts when the CPU is busy. The OS may refuse (realtime scheduling on
+//! Linux needs `CAP_SYS_NICE` or an `rtprio` limit), in which case the next
+//! lower priority is tried and playback continues either way.
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// Scheduling priority requested for audio threads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ThreadPriority {
+    /// Leave the OS default priority.
+    Normal,
+    /// Above normal threads, without realtime scheduling.
+    #[default]
+    High,
+    /// Realtime scheduling for the output thread, where the OS allows it.
+    Realtime,
+}
+
+impl ThreadPriority {
+    /// Get all priorities.
+    pub const fn all() -> &'static [Self] {
+        &[Self::Normal, Self::High, Self::Realtime]
+    }
+
+    /// Get display name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "Normal",
+            Self::High => "High",
+            Self::Realtime => "Realtime",
+        }
+    }
+
+    /// Get the next lower priority to fall back to.
+    const fn fallback(self) -> Option<Self> {
+        match self {
+            Self::Realtime => Some(Self::High),
+            Self::High => Some(Self::Normal),
+            Self::Normal => None,
+        }
+    }
+
+    /// Cap the priority at `max`.
+    #[must_use]
+    pub fn at_most(self, max: Self) -> Self {
+        self.min(max)
+    }
+
+    /// Raise the calling thread to this priority, falling back to lower ones.
+    ///
+    /// Returns the priority actually applied.
+    pub fn apply_to_current_thread(self, thread: &str) -> Self {
+        let mut priority = self;
+        loop {
+            match set_current_thread(priority) {
+                Ok(()) => {
+                    if priority != Self::Normal {
+                        info!("Running {thread} thread at {} priority", priority.name());
+                    }
+                    return priority;
+                }
+                Err(e) => {
+                    debug!(
+                        "{} priority unavailable for {thread} thread: {e}",
+                        priority.name()
+                    );
+                    let Some(lower) = priority.fallback() else {
+                        return Self::Normal;
+                    };
+                    priority = lower;
+                }
+            }
+        }
+    }
+}
+
+/// Niceness of high priority threads on Linux.
+#[cfg(target_os = "linux")]
+const HIGH_NICENESS: libc::c_int = -10;
+
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn set_current_thread(priority: ThreadPriority) -> std::io::Result<()> {
+    match priority {
+        ThreadPriority::Normal => Ok(()),
+        ThreadPriority::High => set_niceness(),
+        ThreadPriority::Realtime => {
+            // SAFETY: these only read and change the calling thread's scheduling
+            let result = unsafe {
+                let param = libc::sched_param {
+                    sched_priority: libc::sched_get_priority_min(libc::SCHED_FIFO),
+                };
+                libc::pthread_setschedparam(
+                    libc::pthread_self(),
+                    libc::SCHED_FIFO,
+                    std::ptr::addr_of!(param),
+                )
+            };
+            match result {
+                0 => Ok(()),
+                errno => Err(std::io::Error::from_raw_os_error(errno)),
+            }
+        }
+    }
+}
+
+/// Lower the niceness of the calling thread (Linux schedules threads individually).
+#[cfg(target_os = "linux")]
+#[allow(unsafe_code)]
+fn set_niceness() -> std::io::Result<()> {
+    // SAFETY: `who` 0 with PRIO_PROCESS refers to the calling thread on Linux
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, HIGH_NICENESS) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Niceness is per process outside Linux, so only realtime threads are supported.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_niceness() -> std::io::Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+#[cfg(windows)]
+#[allow(unsafe_code)]
+fn set_current_thread(priority: ThreadPriority) -> std::io::Result<()> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_TIME_CRITICAL,
+    };
+
+    let level = match priority {
+        ThreadPriority::Normal => return Ok(()),
+        ThreadPriority::High => THREAD_PRIORITY_HIGHEST,
+        ThreadPriority::Realtime => THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    // SAFETY: the pseudo-handle of the current thread is always valid
+    if unsafe { SetThreadPriority(GetCurrentThread(), level) } == 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_current_thread(priority: ThreadPriority) -> std::io::Result<()> {
+    match priority {
+        ThreadPriority::Normal => Ok(()),
+        _ => Err(std::io::Error::from(std::io::ErrorKind::Unsupported)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_order() {
+        assert!(ThreadPriority::Realtime > ThreadPriority::High);
+        assert_eq!(
+            ThreadPriority::Realtime.at_most(ThreadPriority::High),
+            ThreadPriority::High
+        );
+        assert_eq!(
+            ThreadPriority::Normal.at_most(ThreadPriority::High),
+            ThreadPriority::Normal
+        );
+        assert_eq!(ThreadPriority::Normal.fallback(), None);
+    }
+
+    #[test]
+    fn test_apply_never_fails() {
+        let applied =
+            std::thread::spawn(|| ThreadPriority::Realtime.apply_to_current_thread("test"))
+                .join()
+                .unwrap_or(ThreadPriority::Normal);
+        assert!(applied <= ThreadPriority::Realtime);
+        assert_eq!(
+            ThreadPriority::Normal.apply_to_current_thread("test"),
+            ThreadPriority::Normal
+        );
+    }
+}
@@ -170,7 +170,7 @@ impl Resampler {
                 .iter_mut()
                 .map(|ch| {
                     let mut data: Vec<f32> = std::mem::take(ch);
-                    data.extend(std::iter::repeat_n(0.0, padding));
+                    data.extend(std::iter::repeat(0.0).take(padding));
                     data
                 })
                 .collect();
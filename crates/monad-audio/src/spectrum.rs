@@ -0,0 +1,180 @@
+//! Spectrum analysis for visualizers.
+//!
+//! The output writes the samples it plays, after volume, to a monitor buffer
+//! that the engine reads back. A few dozen times a second the engine takes a
+//! Hann-windowed FFT of the latest of them and groups the magnitudes into
+//! bands spaced evenly in pitch, for drawing bars.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+
+use monad_core::{Error, Result};
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+
+use crate::layout::ChannelLayout;
+
+/// Number of bands in a spectrum.
+pub const SPECTRUM_BANDS: usize = 32;
+
+/// Samples per channel in each FFT (about 43 ms at 48kHz).
+const FFT_SIZE: usize = 2048;
+
+/// Lowest and highest frequencies covered by the bands in Hz.
+const FREQUENCY_RANGE: (f32, f32) = (40.0, 16000.0);
+
+/// Level shown as an empty band in dB below full scale.
+const FLOOR_DB: f32 = -60.0;
+
+/// Spectrum of the latest samples played.
+pub(crate) struct Spectrum {
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    /// Latest samples, mixed down to mono, oldest first.
+    history: VecDeque<f32>,
+    input: Vec<f32>,
+    output: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    /// FFT bins of each band.
+    bands: Vec<Range<usize>>,
+}
+
+impl Spectrum {
+    /// Create an analyzer for samples at `sample_rate`.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        let fft = RealFftPlanner::new().plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * i as f32 / FFT_SIZE as f32;
+                0.5f32.mul_add(-phase.cos(), 0.5)
+            })
+            .collect();
+        Self {
+            input: fft.make_input_vec(),
+            output: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            window,
+            history: VecDeque::with_capacity(FFT_SIZE),
+            bands: band_bins(sample_rate),
+        }
+    }
+
+    /// Change the sample rate of the samples to analyze.
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.bands = band_bins(sample_rate);
+        self.history.clear();
+    }
+
+    /// Add interleaved samples played in `layout`.
+    pub(crate) fn push(&mut self, samples: &[f32], layout: ChannelLayout) {
+        for frame in samples.chunks_exact(usize::from(layout.channels())) {
+            if self.history.len() == FFT_SIZE {
+                self.history.pop_front();
+            }
+            let [left, right] = layout.downmix(frame);
+            self.history.push_back((left + right) / 2.0);
+        }
+    }
+
+    /// Get the level of each band, lowest first, from 0.0 at or below
+    /// [`FLOOR_DB`] to 1.0 at full scale.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn bands(&mut self) -> Result<Vec<f32>> {
+        let start = FFT_SIZE - self.history.len();
+        self.input[..start].fill(0.0);
+        for ((input, sample), weight) in self.input[start..]
+            .iter_mut()
+            .zip(&self.history)
+            .zip(&self.window[start..])
+        {
+            *input = sample * weight;
+        }
+        self.fft
+            .process_with_scratch(&mut self.input, &mut self.output, &mut self.scratch)
+            .map_err(|e| Error::Internal(format!("FFT failed: {e}")))?;
+
+        // A full-scale sine peaks at a quarter of the FFT size through a Hann window
+        let scale = 4.0 / FFT_SIZE as f32;
+        Ok(self
+            .bands
+            .iter()
+            .map(|bins| {
+                let peak = self.output[bins.clone()]
+                    .iter()
+                    .fold(0.0f32, |peak, bin| peak.max(bin.norm()));
+                let db = 20.0 * (peak * scale).max(f32::MIN_POSITIVE).log10();
+                (1.0 - db / FLOOR_DB).clamp(0.0, 1.0)
+            })
+            .collect())
+    }
+}
+
+/// Get the FFT bins of each band at `sample_rate`, with at least one bin in
+/// each.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn band_bins(sample_rate: u32) -> Vec<Range<usize>> {
+    let (lowest, highest) = FREQUENCY_RANGE;
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let last_bin = FFT_SIZE / 2;
+    let edge = |band: usize| {
+        let frequency = lowest * (highest / lowest).powf(band as f32 / SPECTRUM_BANDS as f32);
+        ((frequency / bin_hz).round() as usize).min(last_bin)
+    };
+    (0..SPECTRUM_BANDS)
+        .map(|band| {
+            let start = edge(band).min(last_bin - 1);
+            start..edge(band + 1).max(start + 1)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn test_sine_lights_its_band() -> Result<()> {
+        let mut spectrum = Spectrum::new(48000);
+        assert!(spectrum.bands()?.iter().all(|&level| level == 0.0));
+
+        let sine: Vec<f32> = (0..4096)
+            .flat_map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * 1000.0 * f64::from(i) / 48000.0;
+                let value = (phase.sin() * 0.5) as f32;
+                [value, value]
+            })
+            .collect();
+        spectrum.push(&sine, ChannelLayout::Stereo);
+        let bands = spectrum.bands()?;
+        assert_eq!(bands.len(), SPECTRUM_BANDS);
+
+        // Half scale is 6 dB down, a tenth of the way to the floor
+        let loudest = bands
+            .iter()
+            .enumerate()
+            .fold((0, 0.0), |loudest, (band, &level)| {
+                if level > loudest.1 {
+                    (band, level)
+                } else {
+                    loudest
+                }
+            });
+        let bin = 1000.0 / (48000.0 / FFT_SIZE as f32);
+        assert!(spectrum.bands[loudest.0].contains(&(bin.round() as usize)));
+        assert!((loudest.1 - 0.9).abs() < 0.03, "{}", loudest.1);
+        assert!(bands[0] < 0.01 && bands[SPECTRUM_BANDS - 1] < 0.01);
+        Ok(())
+    }
+}
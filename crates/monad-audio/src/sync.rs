@@ -0,0 +1,512 @@
+//! Experimental playback groups: rough multi-room sync across Monad instances.
+//!
+//! One instance leads, broadcasting its playback state over UDP on the LAN.
+//! Followers playing the same cached track estimate the offset between their
+//! clock and the leader's with NTP-style ping exchanges, project the leader's
+//! position to the present, and seek when they drift too far. Nothing here
+//! owns a thread: the host calls `tick` periodically.
+
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use monad_core::Result;
+use tracing::{debug, info};
+
+/// Default UDP port for playback groups.
+pub const DEFAULT_SYNC_PORT: u16 = 47_321;
+
+/// Followers seek once they are this many seconds away from the leader.
+pub const DEFAULT_DRIFT_TOLERANCE: f64 = 0.25;
+
+/// How often the leader re-announces its state so late joiners catch up.
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often followers ping the leader to refine the clock offset.
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Number of clock samples kept; the one with the lowest round trip wins.
+const MAX_CLOCK_SAMPLES: usize = 8;
+
+/// Protocol tag prefixed to every message.
+const PROTOCOL: &str = "monad-sync/1";
+
+/// Current wall-clock time in microseconds since the Unix epoch.
+pub fn now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_micros()).unwrap_or(i64::MAX))
+}
+
+/// Playback state of the group leader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupState {
+    /// `YouTube` video ID of the track being played.
+    pub video_id: String,
+    /// Whether the leader is playing.
+    pub playing: bool,
+    /// Leader position in seconds at `leader_time`.
+    pub position: f64,
+    /// Leader clock (microseconds since the Unix epoch) when `position` was sampled.
+    pub leader_time: i64,
+}
+
+impl GroupState {
+    /// Project the leader's position to a time on the leader's clock.
+    pub fn position_at(&self, leader_time: i64) -> f64 {
+        if self.playing {
+            (self.position + (leader_time - self.leader_time) as f64 / 1e6).max(0.0)
+        } else {
+            self.position
+        }
+    }
+}
+
+/// Message exchanged between group members.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncMessage {
+    /// Leader playback state (leader to followers).
+    State(GroupState),
+    /// Clock probe sent at follower time `t1` (follower to leader).
+    Ping {
+        /// Follower send time.
+        t1: i64,
+    },
+    /// Clock probe reply (leader to follower).
+    Pong {
+        /// Follower send time, echoed.
+        t1: i64,
+        /// Leader receive time.
+        t2: i64,
+        /// Leader send time.
+        t3: i64,
+    },
+}
+
+impl SyncMessage {
+    /// Encode as a single line of text.
+    pub fn encode(&self) -> String {
+        match self {
+            Self::State(state) => format!(
+                "{PROTOCOL} state {} {} {} {}",
+                state.video_id,
+                u8::from(state.playing),
+                state.position,
+                state.leader_time
+            ),
+            Self::Ping { t1 } => format!("{PROTOCOL} ping {t1}"),
+            Self::Pong { t1, t2, t3 } => format!("{PROTOCOL} pong {t1} {t2} {t3}"),
+        }
+    }
+
+    /// Parse a message produced by [`SyncMessage::encode`].
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        if parts.next()? != PROTOCOL {
+            return None;
+        }
+
+        let message = match parts.next()? {
+            "state" => Self::State(GroupState {
+                video_id: parts.next()?.to_string(),
+                playing: parts.next()? == "1",
+                position: parts.next()?.parse().ok()?,
+                leader_time: parts.next()?.parse().ok()?,
+            }),
+            "ping" => Self::Ping {
+                t1: parts.next()?.parse().ok()?,
+            },
+            "pong" => Self::Pong {
+                t1: parts.next()?.parse().ok()?,
+                t2: parts.next()?.parse().ok()?,
+                t3: parts.next()?.parse().ok()?,
+            },
+            _ => return None,
+        };
+        parts.next().is_none().then_some(message)
+    }
+}
+
+/// NTP-style estimate of the offset between the local and leader clocks.
+#[derive(Debug, Default)]
+pub struct ClockOffset {
+    /// Recent `(offset, round trip delay)` samples in microseconds.
+    samples: VecDeque<(i64, i64)>,
+}
+
+impl ClockOffset {
+    /// Add a ping exchange: follower send `t1`, leader receive `t2`, leader send
+    /// `t3`, follower receive `t4`.
+    pub fn add_sample(&mut self, t1: i64, t2: i64, t3: i64, t4: i64) {
+        let offset = ((t2 - t1) + (t3 - t4)) / 2;
+        let delay = (t4 - t1) - (t3 - t2);
+        if delay < 0 {
+            return;
+        }
+
+        self.samples.push_back((offset, delay));
+        if self.samples.len() > MAX_CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Microseconds to add to local time to get leader time.
+    ///
+    /// The sample with the shortest round trip is the least skewed by queuing.
+    pub fn offset(&self) -> Option<i64> {
+        self.samples
+            .iter()
+            .min_by_key(|(_, delay)| *delay)
+            .map(|(offset, _)| *offset)
+    }
+}
+
+/// Receive the next message, or `None` once the socket has nothing queued.
+fn recv_message(socket: &UdpSocket) -> Result<Option<(SyncMessage, SocketAddr)>> {
+    let mut buf = [0u8; 512];
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((n, from)) => {
+                let text = String::from_utf8_lossy(&buf[..n]);
+                if let Some(message) = SyncMessage::parse(&text) {
+                    return Ok(Some((message, from)));
+                }
+                debug!("Ignoring unrecognised sync message from {from}");
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn send_message(socket: &UdpSocket, message: &SyncMessage, to: SocketAddr) -> Result<()> {
+    socket.send_to(message.encode().as_bytes(), to)?;
+    Ok(())
+}
+
+/// Broadcasts playback state to followers and answers their clock probes.
+pub struct SyncLeader {
+    socket: UdpSocket,
+    target: SocketAddr,
+    state: Option<GroupState>,
+    last_announce: Option<Instant>,
+}
+
+impl SyncLeader {
+    /// Lead a group, broadcasting on the LAN to `port`.
+    pub fn bind(port: u16) -> Result<Self> {
+        Self::bind_to(
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::from((Ipv4Addr::BROADCAST, port)),
+        )
+    }
+
+    /// Lead a group from `local`, sending state to `target`.
+    pub fn bind_to(local: SocketAddr, target: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        info!("Leading playback group on {}", socket.local_addr()?);
+        Ok(Self {
+            socket,
+            target,
+            state: None,
+            last_announce: None,
+        })
+    }
+
+    /// Get the local socket address.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Announce the leader's playback state (after play, pause, seek, or a track change).
+    pub fn announce(&mut self, video_id: &str, playing: bool, position: f64) -> Result<()> {
+        self.state = Some(GroupState {
+            video_id: video_id.to_string(),
+            playing,
+            position,
+            leader_time: now_micros(),
+        });
+        self.broadcast()
+    }
+
+    /// Answer clock probes and periodically re-announce the state.
+    pub fn tick(&mut self) -> Result<()> {
+        while let Some((message, from)) = recv_message(&self.socket)? {
+            if let SyncMessage::Ping { t1 } = message {
+                let t2 = now_micros();
+                let pong = SyncMessage::Pong {
+                    t1,
+                    t2,
+                    t3: now_micros(),
+                };
+                send_message(&self.socket, &pong, from)?;
+            }
+        }
+
+        if self
+            .last_announce
+            .map_or(true, |last| last.elapsed() >= ANNOUNCE_INTERVAL)
+        {
+            self.broadcast()?;
+        }
+        Ok(())
+    }
+
+    fn broadcast(&mut self) -> Result<()> {
+        let Some(state) = &self.state else {
+            return Ok(());
+        };
+
+        // Re-stamp so followers always project from a fresh sample
+        let now = now_micros();
+        let state = GroupState {
+            position: state.position_at(now),
+            leader_time: now,
+            ..state.clone()
+        };
+        send_message(&self.socket, &SyncMessage::State(state), self.target)?;
+        self.last_announce = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Step a follower should take to match the leader.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncAction {
+    /// Load the leader's track (from the cache).
+    Load(String),
+    /// Seek to a position in seconds.
+    Seek(f64),
+    /// Resume playback.
+    Play,
+    /// Pause playback.
+    Pause,
+}
+
+/// Follows a leader's playback state.
+pub struct SyncFollower {
+    socket: UdpSocket,
+    leader: Option<SocketAddr>,
+    clock: ClockOffset,
+    state: Option<GroupState>,
+    last_ping: Option<Instant>,
+    drift_tolerance: f64,
+}
+
+impl SyncFollower {
+    /// Follow a group on `port`.
+    pub fn bind(port: u16) -> Result<Self> {
+        Self::bind_to(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+    }
+
+    /// Follow a group, listening on `local`.
+    pub fn bind_to(local: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        info!("Following playback group on {}", socket.local_addr()?);
+        Ok(Self {
+            socket,
+            leader: None,
+            clock: ClockOffset::default(),
+            state: None,
+            last_ping: None,
+            drift_tolerance: DEFAULT_DRIFT_TOLERANCE,
+        })
+    }
+
+    /// Set how far (in seconds) playback may drift before seeking.
+    #[must_use]
+    pub const fn with_drift_tolerance(mut self, seconds: f64) -> Self {
+        self.drift_tolerance = seconds;
+        self
+    }
+
+    /// Get the local socket address.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Get the last state received from the leader.
+    pub const fn group_state(&self) -> Option<&GroupState> {
+        self.state.as_ref()
+    }
+
+    /// Get the estimated leader clock offset in microseconds.
+    pub fn clock_offset(&self) -> Option<i64> {
+        self.clock.offset()
+    }
+
+    /// Process messages from the leader and probe its clock.
+    pub fn tick(&mut self) -> Result<()> {
+        while let Some((message, from)) = recv_message(&self.socket)? {
+            match message {
+                SyncMessage::State(state) => {
+                    if self.leader != Some(from) {
+                        info!("Following playback group leader at {from}");
+                        self.leader = Some(from);
+                        self.clock = ClockOffset::default();
+                        self.last_ping = None;
+                    }
+                    self.state = Some(state);
+                }
+                SyncMessage::Pong { t1, t2, t3 } => {
+                    self.clock.add_sample(t1, t2, t3, now_micros());
+                }
+                SyncMessage::Ping { .. } => {}
+            }
+        }
+
+        if let Some(leader) = self.leader {
+            if self
+                .last_ping
+                .map_or(true, |last| last.elapsed() >= PING_INTERVAL)
+            {
+                send_message(
+                    &self.socket,
+                    &SyncMessage::Ping { t1: now_micros() },
+                    leader,
+                )?;
+                self.last_ping = Some(Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps to bring local playback in line with the leader.
+    ///
+    /// `video_id`, `playing`, and `position` describe local playback.
+    pub fn correction(
+        &self,
+        video_id: Option<&str>,
+        playing: bool,
+        position: f64,
+    ) -> Vec<SyncAction> {
+        self.correction_at(now_micros(), video_id, playing, position)
+    }
+
+    fn correction_at(
+        &self,
+        local_time: i64,
+        video_id: Option<&str>,
+        playing: bool,
+        position: f64,
+    ) -> Vec<SyncAction> {
+        let Some(state) = &self.state else {
+            return Vec::new();
+        };
+        if video_id != Some(state.video_id.as_str()) {
+            return vec![SyncAction::Load(state.video_id.clone())];
+        }
+        // Without a clock estimate the leader's position cannot be projected
+        let Some(offset) = self.clock.offset() else {
+            return Vec::new();
+        };
+
+        let mut actions = Vec::new();
+        let target = state.position_at(local_time + offset);
+        if (position - target).abs() > self.drift_tolerance {
+            debug!("Drifted {:.3}s from leader, seeking", position - target);
+            actions.push(SyncAction::Seek(target));
+        }
+        if state.playing != playing {
+            actions.push(if state.playing {
+                SyncAction::Play
+            } else {
+                SyncAction::Pause
+            });
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_roundtrip_and_clock_offset() {
+        let state = SyncMessage::State(GroupState {
+            video_id: "abc".to_string(),
+            playing: true,
+            position: 12.5,
+            leader_time: 1_000,
+        });
+        for message in [
+            state,
+            SyncMessage::Ping { t1: 5 },
+            SyncMessage::Pong {
+                t1: 1,
+                t2: -2,
+                t3: 3,
+            },
+        ] {
+            assert_eq!(SyncMessage::parse(&message.encode()), Some(message));
+        }
+        assert_eq!(SyncMessage::parse("other/1 ping 5"), None);
+
+        // Leader clock 5ms ahead; the congested sample is ignored
+        let mut clock = ClockOffset::default();
+        clock.add_sample(0, 6_000, 6_100, 2_100);
+        clock.add_sample(10_000, 25_000, 25_100, 12_100);
+        assert_eq!(clock.offset(), Some(5_000));
+    }
+
+    #[test]
+    fn test_follower_corrections() -> Result<()> {
+        let mut follower = SyncFollower::bind_to(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?;
+        assert!(follower.correction(Some("abc"), false, 0.0).is_empty());
+
+        follower.state = Some(GroupState {
+            video_id: "abc".to_string(),
+            playing: true,
+            position: 10.0,
+            leader_time: 1_000_000,
+        });
+        assert_eq!(
+            follower.correction_at(0, None, false, 0.0),
+            [SyncAction::Load("abc".to_string())]
+        );
+
+        // Local clock 1s behind the leader: leader time 3s is local time 2s
+        follower.clock.add_sample(0, 1_000_000, 1_000_000, 0);
+        assert!(follower
+            .correction_at(2_000_000, Some("abc"), true, 12.1)
+            .is_empty());
+        assert_eq!(
+            follower.correction_at(2_000_000, Some("abc"), false, 11.0),
+            [SyncAction::Seek(12.0), SyncAction::Play]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_leader_and_follower_over_loopback() -> Result<()> {
+        let mut follower = SyncFollower::bind_to(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?;
+        let mut leader = SyncLeader::bind_to(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            follower.local_addr()?,
+        )?;
+        leader.announce("abc", true, 30.0)?;
+
+        for _ in 0..100 {
+            follower.tick()?;
+            leader.tick()?;
+            if follower.clock_offset().is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(
+            follower.group_state().map(|s| s.video_id.as_str()),
+            Some("abc")
+        );
+        // Same machine, so the clocks agree to well within a frame
+        assert!(follower.clock_offset().is_some_and(|o| o.abs() < 50_000));
+        assert!(follower.correction(Some("abc"), true, 30.0).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+//! History of track transitions, for debugging gaps between tracks.
+//!
+//! A transition starts when a track finishes (or is replaced while playing)
+//! and is measured until the next track has played for [`TRANSITION_WINDOW`].
+//! Only the most recent [`TRANSITION_LOG_SIZE`] transitions are kept.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use parking_lot::Mutex;
+
+/// Number of transitions kept in the log.
+pub const TRANSITION_LOG_SIZE: usize = 50;
+
+/// How long into the next track underruns still count towards the transition.
+pub const TRANSITION_WINDOW: Duration = Duration::from_secs(3);
+
+/// Longer pauses between tracks are idle time rather than a transition.
+pub const MAX_TRANSITION_GAP: Duration = Duration::from_secs(30);
+
+/// A measured transition between two tracks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionRecord {
+    /// When the previous track ended.
+    pub at: SystemTime,
+    /// Whether the previous track played to the end (rather than being replaced).
+    pub finished: bool,
+    /// Silence the engine intended between the tracks.
+    pub planned_gap: Duration,
+    /// Time from the end of the previous track until the next one started playing.
+    pub actual_gap: Duration,
+    /// Crossfade applied between the tracks, if any.
+    pub fade: Option<Duration>,
+    /// Buffer underruns during the transition.
+    pub underruns: u32,
+}
+
+impl TransitionRecord {
+    /// Get how much longer the gap was than planned.
+    pub const fn gap_error(&self) -> Duration {
+        self.actual_gap.saturating_sub(self.planned_gap)
+    }
+}
+
+/// A transition still being measured.
+#[derive(Debug)]
+pub(crate) struct PendingTransition {
+    at: SystemTime,
+    ended_at: Instant,
+    finished: bool,
+    /// When the next track started playing.
+    started_at: Option<Instant>,
+    underruns: u32,
+    /// Crossfade into the next track.
+    fade: Option<Duration>,
+}
+
+impl PendingTransition {
+    /// Start measuring a transition from a track that ended now.
+    pub(crate) fn new(finished: bool) -> Self {
+        Self {
+            at: SystemTime::now(),
+            ended_at: Instant::now(),
+            finished,
+            started_at: None,
+            underruns: 0,
+            fade: None,
+        }
+    }
+
+    /// Note that the tracks were crossfaded.
+    #[must_use]
+    pub(crate) const fn with_fade(mut self, fade: Duration) -> Self {
+        self.fade = Some(fade);
+        self
+    }
+
+    /// Note that the next track started playing.
+    ///
+    /// Returns false if playback was idle too long for this to be a transition.
+    pub(crate) fn start(&mut self) -> bool {
+        let started_at = *self.started_at.get_or_insert_with(Instant::now);
+        started_at.saturating_duration_since(self.ended_at) <= MAX_TRANSITION_GAP
+    }
+
+    /// Check whether the next track has started playing.
+    pub(crate) const fn has_started(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    /// Count an underrun during the transition.
+    pub(crate) const fn add_underruns(&mut self, count: u32) {
+        self.underruns = self.underruns.saturating_add(count);
+    }
+
+    /// Check whether the next track has played long enough to finish measuring.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.started_at
+            .is_some_and(|started| started.elapsed() >= TRANSITION_WINDOW)
+    }
+
+    /// Finish measuring, producing the record.
+    ///
+    /// The engine plays tracks back to back, so no gap is planned.
+    pub(crate) fn finish(self) -> TransitionRecord {
+        let started_at = self.started_at.unwrap_or_else(Instant::now);
+        TransitionRecord {
+            at: self.at,
+            finished: self.finished,
+            planned_gap: Duration::ZERO,
+            actual_gap: started_at.saturating_duration_since(self.ended_at),
+            fade: self.fade,
+            underruns: self.underruns,
+        }
+    }
+}
+
+/// Ring log of the most recent transitions, shared with the engine worker.
+#[derive(Debug, Clone, Default)]
+pub struct TransitionLog {
+    records: Arc<Mutex<VecDeque<TransitionRecord>>>,
+}
+
+impl TransitionLog {
+    /// Add a transition, dropping the oldest once the log is full.
+    pub fn push(&self, record: TransitionRecord) {
+        let mut records = self.records.lock();
+        if records.len() == TRANSITION_LOG_SIZE {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Get the logged transitions, oldest first.
+    pub fn records(&self) -> Vec<TransitionRecord> {
+        self.records.lock().iter().cloned().collect()
+    }
+
+    /// Remove all logged transitions.
+    pub fn clear(&self) {
+        self.records.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_keeps_most_recent() {
+        let log = TransitionLog::default();
+        for underruns in 0..60 {
+            let mut pending = PendingTransition::new(true);
+            pending.add_underruns(underruns);
+            log.push(pending.finish());
+        }
+
+        let records = log.records();
+        assert_eq!(records.len(), TRANSITION_LOG_SIZE);
+        assert_eq!(records.first().map(|r| r.underruns), Some(10));
+        assert_eq!(records.last().map(|r| r.underruns), Some(59));
+    }
+
+    #[test]
+    fn test_pending_transition() {
+        let mut pending = PendingTransition::new(false);
+        assert!(!pending.has_started() && !pending.is_complete());
+
+        assert!(pending.start());
+        assert!(pending.has_started());
+        let record = pending.finish();
+        assert!(!record.finished);
+        assert_eq!(record.planned_gap, Duration::ZERO);
+        assert_eq!(record.gap_error(), record.actual_gap);
+        assert_eq!(record.fade, None);
+    }
+}
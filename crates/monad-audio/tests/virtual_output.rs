@@ -0,0 +1,256 @@
+//! Engine tests rendering to virtual outputs, so they run without a sound card.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use monad_audio::{
+    AudioEngine, EngineCommand, EngineEvent, OutputBackend, PlaybackState, PCM_F32LE_MIME,
+};
+
+/// Samples faded in when playback starts (30 ms at 48kHz stereo).
+const FADE_SAMPLES: usize = 2880;
+
+/// Interleaved stereo ramp with a unique, non-zero value per frame.
+#[allow(clippy::cast_precision_loss)]
+fn ramp(frames: usize, scale: f32) -> Vec<f32> {
+    (0..frames)
+        .flat_map(|i| {
+            let value = (i + 1) as f32 / frames as f32 * scale;
+            [value, value]
+        })
+        .collect()
+}
+
+fn pcm_bytes(samples: &[f32]) -> Vec<u8> {
+    samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+}
+
+fn read_pcm(path: &Path) -> Vec<f32> {
+    std::fs::read(path)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Strip the trailing silence rendered after the last sample of a track.
+fn trim_trailing_silence(mut samples: Vec<f32>) -> Vec<f32> {
+    while samples.last() == Some(&0.0) {
+        samples.pop();
+    }
+    samples
+}
+
+/// Check that `rendered` is `track` faded in from silence.
+fn assert_faded_in(rendered: &[f32], track: &[f32]) {
+    assert_eq!(rendered[FADE_SAMPLES..], track[FADE_SAMPLES..]);
+    assert!(rendered[..FADE_SAMPLES]
+        .iter()
+        .zip(track)
+        .all(|(faded, sample)| faded.abs() <= sample.abs()));
+    assert!(rendered[0].abs() < track[0].abs());
+}
+
+/// Poll engine events until `matches` returns true or the timeout elapses.
+fn wait_for(engine: &AudioEngine, matches: impl Fn(&EngineEvent) -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        while let Some(event) = engine.try_recv_event() {
+            if matches(&event) {
+                return true;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+fn load(engine: &AudioEngine, samples: &[f32]) -> monad_core::Result<()> {
+    engine.load_data(pcm_bytes(samples), Some(PCM_F32LE_MIME))?;
+    assert!(wait_for(engine, |e| matches!(e, EngineEvent::TrackLoaded)));
+    Ok(())
+}
+
+fn play_to_end(engine: &AudioEngine) -> monad_core::Result<()> {
+    engine.play()?;
+    assert!(wait_for(engine, |e| matches!(
+        e,
+        EngineEvent::PlaybackFinished
+    )));
+    Ok(())
+}
+
+#[test]
+fn test_play_renders_track_and_finishes() -> monad_core::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.pcm");
+    let engine = AudioEngine::with_output(OutputBackend::File(path.clone()))?;
+    engine.set_volume(1.0)?;
+
+    let track = ramp(12_000, 0.5);
+    load(&engine, &track)?;
+    play_to_end(&engine)?;
+
+    assert_eq!(engine.state(), PlaybackState::Stopped);
+    engine.shutdown()?;
+
+    let rendered = trim_trailing_silence(read_pcm(&path));
+    assert_eq!(rendered.len(), track.len());
+    assert_faded_in(&rendered, &track);
+    Ok(())
+}
+
+#[test]
+fn test_seek_skips_rendered_audio() -> monad_core::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.pcm");
+    let engine = AudioEngine::with_output(OutputBackend::File(path.clone()))?;
+    engine.set_volume(1.0)?;
+
+    let track = ramp(24_000, 0.5);
+    load(&engine, &track)?;
+    engine.seek(0.25)?;
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::PositionUpdate(pos) if (pos - 0.25).abs() < f64::EPSILON
+    )));
+    play_to_end(&engine)?;
+    engine.shutdown()?;
+
+    // 0.25s at 48kHz stereo
+    let rendered = trim_trailing_silence(read_pcm(&path));
+    assert_eq!(rendered.len(), track.len() - 24_000);
+    assert_faded_in(&rendered, &track[24_000..]);
+    Ok(())
+}
+
+#[test]
+fn test_preloaded_track_follows_without_gap() -> monad_core::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.pcm");
+    let engine = AudioEngine::with_output(OutputBackend::File(path.clone()))?;
+    engine.set_volume(1.0)?;
+
+    // Neither track ends on a period boundary
+    let first = ramp(6_001, 0.5);
+    let second = ramp(6_003, -0.5);
+    load(&engine, &first)?;
+    engine.preload(pcm_bytes(&second), Some(PCM_F32LE_MIME))?;
+    std::thread::sleep(Duration::from_millis(100));
+    engine.play()?;
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::NextTrackStarted
+    )));
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::PlaybackFinished
+    )));
+    engine.shutdown()?;
+
+    // The second track starts on the sample after the first ends, unfaded
+    let rendered = trim_trailing_silence(read_pcm(&path));
+    assert_eq!(rendered.len(), first.len() + second.len());
+    assert_faded_in(&rendered[..first.len()], &first);
+    let join = first.len();
+    assert_eq!(
+        rendered[join - 2..join + 2],
+        [0.5, 0.5, second[0], second[1]]
+    );
+    assert_eq!(rendered[join..], second);
+    Ok(())
+}
+
+#[test]
+fn test_null_output_plays_to_end() -> monad_core::Result<()> {
+    let engine = AudioEngine::with_output(OutputBackend::Null)?;
+
+    load(&engine, &ramp(4_800, 0.5))?;
+    assert_eq!(engine.duration(), Some(0.1));
+    engine.play()?;
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::NearEnd(secs_left) if *secs_left <= 0.1
+    )));
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::PlaybackFinished
+    )));
+
+    assert_eq!(engine.state(), PlaybackState::Stopped);
+    engine.shutdown()?;
+    Ok(())
+}
+
+#[test]
+fn test_crossfade_into_preloaded_track() -> monad_core::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.pcm");
+    let engine = AudioEngine::with_output(OutputBackend::File(path.clone()))?;
+    engine.set_volume(1.0)?;
+    engine.set_crossfade(Duration::from_millis(100))?;
+
+    let first = ramp(24_000, 0.5);
+    let second = ramp(12_000, -0.5);
+    load(&engine, &first)?;
+    engine.preload(pcm_bytes(&second), Some(PCM_F32LE_MIME))?;
+    // Preloading raw PCM only takes a moment
+    std::thread::sleep(Duration::from_millis(100));
+    engine.play()?;
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::NextTrackStarted
+    )));
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::PlaybackFinished
+    )));
+    engine.shutdown()?;
+
+    // The last 0.1s of the first track overlaps the start of the second
+    let fade = 9_600;
+    let rendered = trim_trailing_silence(read_pcm(&path));
+    assert_eq!(rendered.len(), first.len() + second.len() - fade);
+    assert_faded_in(
+        &rendered[..first.len() - fade],
+        &first[..first.len() - fade],
+    );
+    assert_eq!(rendered[first.len()..], second[fade..]);
+    assert!(rendered[first.len() - fade..first.len()]
+        .iter()
+        .zip(&second[..fade])
+        .all(|(&mixed, &next)| mixed > next));
+
+    let transition = engine.transitions().pop();
+    assert_eq!(
+        transition.and_then(|t| t.fade),
+        Some(Duration::from_millis(100))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_acknowledged_commands_report_their_outcome() -> monad_core::Result<()> {
+    let engine = AudioEngine::with_output(OutputBackend::Null)?;
+
+    assert!(engine.seek_async(1.0).await.is_err());
+    let mime = Some("audio/x-unknown");
+    assert!(engine.load_data_async(vec![0u8; 16], mime).await.is_err());
+
+    let track = ramp(24_000, 0.5);
+    engine
+        .load_data_async(pcm_bytes(&track), Some(PCM_F32LE_MIME))
+        .await?;
+    engine.seek_async(0.25).await?;
+    assert!((engine.position() - 0.25).abs() < f64::EPSILON);
+
+    // Completion events follow the events the command caused
+    let (id, reply) = engine.send_command_with_reply(EngineCommand::Seek(0.5))?;
+    assert!(reply.await.is_ok_and(|result| result.is_ok()));
+    assert!(wait_for(&engine, |e| matches!(
+        e,
+        EngineEvent::CommandCompleted { id: completed, error: None } if *completed == id
+    )));
+    engine.shutdown()?;
+    Ok(())
+}
@@ -0,0 +1,356 @@
+//! Exporting the cache to a single archive and importing it elsewhere.
+//!
+//! An archive is a zstd-compressed tar holding a snapshot of the database
+//! and every audio file and thumbnail it references. Importing merges the
+//! archive into the current cache rather than replacing it: entries already
+//! cached here are kept, and imported files are moved into this cache's own
+//! directories, so an archive can be taken to another machine or restored as
+//! a backup.
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use monad_core::{Error, Result};
+use rusqlite::{params, Connection, OpenFlags};
+use tracing::info;
+
+use crate::migrations::{self, SCHEMA_VERSION};
+use crate::CacheManager;
+
+/// Name of the database snapshot inside an archive.
+const ARCHIVE_DB: &str = "cache.db";
+/// Directory of audio files inside an archive.
+const ARCHIVE_AUDIO: &str = "audio";
+/// Directory of thumbnails inside an archive.
+const ARCHIVE_THUMBNAILS: &str = "thumbnails";
+/// Snapshot of the database taken while exporting, in the cache directory.
+const EXPORT_SNAPSHOT: &str = "export.db";
+/// Directory an archive is unpacked to while importing, in the cache directory.
+const IMPORT_STAGING: &str = "import";
+
+/// Tables merged as they are, keeping rows that already exist here.
+const MERGED_TABLES: &[&str] = &[
+    "audio_cache",
+    "thumbnail_cache",
+    "metadata_cache",
+    "collection_visits",
+    "pins",
+    "saved_items",
+];
+
+/// Plays and search entries that are not already here; play counts follow
+/// from the history through its triggers.
+const MERGE_HISTORY: &str = "
+    INSERT INTO main.play_history (video_id, title, artist, album, played_at)
+    SELECT video_id, title, artist, album, played_at FROM archive.play_history AS a
+    WHERE NOT EXISTS (
+        SELECT 1 FROM main.play_history AS p
+        WHERE p.video_id = a.video_id AND p.played_at = a.played_at
+    )
+    ORDER BY a.id;
+
+    INSERT INTO main.search_index (kind, id, title, artist, album, data)
+    SELECT kind, id, title, artist, album, data FROM archive.search_index AS a
+    WHERE NOT EXISTS (
+        SELECT 1 FROM main.search_index AS s WHERE s.kind = a.kind AND s.id = a.id
+    );
+";
+
+/// Number of files in an exported or imported archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveContents {
+    /// Audio files.
+    pub audio_files: usize,
+    /// Thumbnails.
+    pub thumbnails: usize,
+}
+
+/// Read the file paths selected by `sql`.
+fn file_paths(db: &Connection, sql: &str) -> Result<Vec<String>> {
+    db.prepare(sql)
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| Error::Cache(format!("Failed to read cached files: {e}")))
+}
+
+/// Add the existing files among `paths` to `dir` in the archive.
+fn append_files(
+    archive: &mut tar::Builder<impl Write>,
+    dir: &str,
+    paths: &[String],
+) -> Result<usize> {
+    let mut count = 0;
+    for path in paths.iter().map(Path::new).filter(|path| path.is_file()) {
+        if let Some(name) = path.file_name() {
+            archive.append_path_with_name(path, Path::new(dir).join(name))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Write an archive of the snapshot at `snapshot` and the files it references.
+fn write_archive(snapshot: &Path, path: &Path) -> Result<ArchiveContents> {
+    let db = Connection::open_with_flags(snapshot, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| Error::Cache(format!("Failed to open database snapshot: {e}")))?;
+    let audio = file_paths(&db, "SELECT file_path FROM audio_cache")?;
+    let thumbnails = file_paths(&db, "SELECT file_path FROM thumbnail_cache")?;
+    drop(db);
+
+    let mut archive = tar::Builder::new(zstd::Encoder::new(File::create(path)?, 0)?);
+    archive.append_path_with_name(snapshot, ARCHIVE_DB)?;
+    let contents = ArchiveContents {
+        audio_files: append_files(&mut archive, ARCHIVE_AUDIO, &audio)?,
+        thumbnails: append_files(&mut archive, ARCHIVE_THUMBNAILS, &thumbnails)?,
+    };
+    archive.into_inner()?.finish()?.sync_all()?;
+    Ok(contents)
+}
+
+/// Merge the database attached as `archive` into the main one.
+fn merge_attached(db: &mut Connection) -> rusqlite::Result<()> {
+    let tx = db.transaction()?;
+    for table in MERGED_TABLES {
+        let columns: Vec<String> = tx
+            .prepare("SELECT name FROM pragma_table_info(?)")?
+            .query_map([table], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let columns = columns.join(", ");
+        tx.execute(
+            &format!(
+                "INSERT OR IGNORE INTO main.{table} ({columns})
+                 SELECT {columns} FROM archive.{table}"
+            ),
+            [],
+        )?;
+    }
+    tx.execute_batch(MERGE_HISTORY)?;
+    tx.commit()
+}
+
+impl CacheManager {
+    /// Export the database and every cached file it references to `path`.
+    ///
+    /// The archive is written under a temporary name and only renamed to
+    /// `path` once complete.
+    pub fn export_archive(&self, path: &Path) -> Result<ArchiveContents> {
+        let snapshot = self.cache_dir.join(EXPORT_SNAPSHOT);
+        let _ = fs::remove_file(&snapshot);
+        self.readers
+            .get()
+            .execute("VACUUM INTO ?", [snapshot.to_string_lossy()])
+            .map_err(|e| Error::Cache(format!("Failed to snapshot the database: {e}")))?;
+
+        let mut part = OsString::from(path);
+        part.push(".part");
+        let part = PathBuf::from(part);
+        let written = write_archive(&snapshot, &part);
+        let _ = fs::remove_file(&snapshot);
+        let contents = written
+            .and_then(|contents| Ok(fs::rename(&part, path).map(|()| contents)?))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&part);
+            })?;
+
+        info!(
+            "Exported {} audio files and {} thumbnails to {}",
+            contents.audio_files,
+            contents.thumbnails,
+            path.display()
+        );
+        Ok(contents)
+    }
+
+    /// Import an archive written by [`CacheManager::export_archive`].
+    ///
+    /// Everything cached here is kept; of the archive, only what is missing
+    /// is added. Returns the number of files imported.
+    pub fn import_archive(&self, path: &Path) -> Result<ArchiveContents> {
+        let staging = self.cache_dir.join(IMPORT_STAGING);
+        let _ = fs::remove_dir_all(&staging);
+        let imported = self.import_staged(path, &staging);
+        let _ = fs::remove_dir_all(&staging);
+        let contents = imported?;
+
+        info!(
+            "Imported {} audio files and {} thumbnails from {}",
+            contents.audio_files,
+            contents.thumbnails,
+            path.display()
+        );
+        self.enforce_limits_after_write();
+        Ok(contents)
+    }
+
+    /// Unpack the archive at `path` to `staging` and merge it in.
+    fn import_staged(&self, path: &Path, staging: &Path) -> Result<ArchiveContents> {
+        tar::Archive::new(zstd::Decoder::new(File::open(path)?)?).unpack(staging)?;
+        let db_path = staging.join(ARCHIVE_DB);
+        if !db_path.is_file() {
+            return Err(Error::Cache(format!(
+                "{} is not a cache archive",
+                path.display()
+            )));
+        }
+
+        let mut imported = Connection::open(&db_path)
+            .map_err(|e| Error::Cache(format!("Failed to open archived database: {e}")))?;
+        let version = migrations::schema_version(&imported)?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::Cache(format!(
+                "Archive schema version {version} is newer than this build supports ({SCHEMA_VERSION})"
+            )));
+        }
+        migrations::migrate(&mut imported)?;
+        let contents = ArchiveContents {
+            audio_files: self.move_files(
+                &imported,
+                "audio_cache",
+                "id",
+                &staging.join(ARCHIVE_AUDIO),
+                &self.audio_dir(),
+            )?,
+            thumbnails: self.move_files(
+                &imported,
+                "thumbnail_cache",
+                "url_hash",
+                &staging.join(ARCHIVE_THUMBNAILS),
+                &self.thumbnail_dir(),
+            )?,
+        };
+        drop(imported);
+
+        let mut db = self.db.lock();
+        db.execute("ATTACH DATABASE ? AS archive", [db_path.to_string_lossy()])
+            .map_err(|e| Error::Cache(format!("Failed to open archived database: {e}")))?;
+        let merged = merge_attached(&mut db);
+        let detached = db.execute_batch("DETACH DATABASE archive");
+        merged
+            .and(detached)
+            .map_err(|e| Error::Cache(format!("Failed to import archive: {e}")))?;
+        Ok(contents)
+    }
+
+    /// Move the unpacked files of `table` that are not cached here to `dest`.
+    ///
+    /// Rows in the archived database are pointed at the moved files, and rows
+    /// that are cached here already or whose file is missing are dropped.
+    fn move_files(
+        &self,
+        imported: &Connection,
+        table: &str,
+        key: &str,
+        unpacked: &Path,
+        dest: &Path,
+    ) -> Result<usize> {
+        let map_err = |e: rusqlite::Error| Error::Cache(format!("Failed to import {table}: {e}"));
+        let rows: Vec<(String, String)> = imported
+            .prepare(&format!("SELECT {key}, file_path FROM {table}"))
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .map_err(map_err)?;
+
+        let mut moved = 0;
+        for (id, file_path) in rows {
+            let cached = self
+                .readers
+                .get()
+                .query_row(
+                    &format!("SELECT 1 FROM {table} WHERE {key} = ?"),
+                    [&id],
+                    |_| Ok(()),
+                )
+                .is_ok();
+            let file = Path::new(&file_path)
+                .file_name()
+                .map(|name| (unpacked.join(name), dest.join(name)))
+                .filter(|(source, _)| source.is_file());
+
+            match file {
+                Some((source, target)) if !cached => {
+                    fs::create_dir_all(dest)?;
+                    fs::rename(&source, &target)?;
+                    imported
+                        .execute(
+                            &format!("UPDATE {table} SET file_path = ? WHERE {key} = ?"),
+                            params![target.to_string_lossy(), id],
+                        )
+                        .map_err(map_err)?;
+                    moved += 1;
+                }
+                _ => {
+                    imported
+                        .execute(&format!("DELETE FROM {table} WHERE {key} = ?"), [&id])
+                        .map_err(map_err)?;
+                }
+            }
+        }
+        Ok(moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::Track;
+
+    #[test]
+    fn test_export_and_import() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source = CacheManager::with_path(dir.path().join("source"))?;
+        source.store_audio("a", "audio/mp4", "128kbps", b"audio a", Some(3.0))?;
+        source.store_thumbnail("https://i.ytimg.com/a.jpg", b"art")?;
+        source.save_track(&Track::new("a", "One More Time"))?;
+        source.record_play(&Track::new("a", "One More Time"))?;
+
+        let path = dir.path().join("backup.tar.zst");
+        let exported = source.export_archive(&path)?;
+        assert_eq!(
+            exported,
+            ArchiveContents {
+                audio_files: 1,
+                thumbnails: 1
+            }
+        );
+        assert!(!dir.path().join("backup.tar.zst.part").exists());
+
+        let target = CacheManager::with_path(dir.path().join("target"))?;
+        target.store_audio("b", "audio/mp4", "128kbps", b"audio b", None)?;
+        assert_eq!(target.import_archive(&path)?, exported);
+        assert!(target
+            .get_audio_path("a")
+            .is_some_and(|p| p.starts_with(target.audio_dir())));
+        assert!(target.has_audio("b"));
+        assert!(target.thumbnail_path("https://i.ytimg.com/a.jpg").is_some());
+        assert!(target.is_track_saved("a"));
+        assert_eq!(target.search_offline("one more", 10)?.len(), 1);
+        assert!(!target.cache_dir().join(IMPORT_STAGING).exists());
+
+        // Importing again adds nothing twice
+        assert_eq!(target.import_archive(&path)?, ArchiveContents::default());
+        assert_eq!(target.recent_plays(10)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_rejects_other_archives() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().join("cache"))?;
+        let path = dir.path().join("other.tar.zst");
+        let mut archive = tar::Builder::new(zstd::Encoder::new(File::create(&path)?, 0)?);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, "notes.txt", &b"hello"[..])?;
+        archive.into_inner()?.finish()?;
+
+        assert!(cache.import_archive(&path).is_err());
+        assert!(cache.import_archive(&dir.path().join("missing")).is_err());
+        assert!(!cache.cache_dir().join(IMPORT_STAGING).exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Audio files stored by the cache itself.
+//!
+//! Audio is written to an `audio` directory under the cache directory, named
+//! by video ID, and registered in the `audio_cache` table. Files are written
+//! under a temporary name first, so a failed download never leaves a
+//! truncated file registered. Reads update `last_accessed`, which eviction
+//! uses to find the least recently played audio. The extractor keeps its disk
+//! cache in the same directory and stores its downloads through here.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Result};
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tracing::debug;
+
+use crate::integrity::sha256;
+use crate::{AudioRecord, CacheManager};
+
+/// Size of the chunks streamed audio is written in.
+const STREAM_CHUNK: usize = 64 * 1024;
+
+/// Check that a video ID is safe to use as a file name.
+fn check_video_id(video_id: &str) -> Result<()> {
+    let valid = !video_id.is_empty()
+        && video_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "Invalid video ID: {video_id:?}"
+        )))
+    }
+}
+
+/// Temporary path a file is written to before it is complete.
+fn part_path(path: &Path) -> PathBuf {
+    path.with_extension("audio.part")
+}
+
+impl CacheManager {
+    /// Get the directory holding stored audio.
+    pub fn audio_dir(&self) -> PathBuf {
+        self.cache_dir.join("audio")
+    }
+
+    /// Get the file audio for a video is stored in.
+    fn audio_file(&self, video_id: &str) -> Result<PathBuf> {
+        check_video_id(video_id)?;
+        Ok(self.audio_dir().join(format!("{video_id}.audio")))
+    }
+
+    /// Store the audio of a video, returning the file it was written to.
+    ///
+    /// Replaces any audio stored for the video before.
+    pub fn store_audio(
+        &self,
+        video_id: &str,
+        format: &str,
+        quality: &str,
+        data: &[u8],
+        duration_secs: Option<f64>,
+    ) -> Result<PathBuf> {
+        let path = self.audio_file(video_id)?;
+        fs::create_dir_all(self.audio_dir())?;
+        let sealed = self.seal_audio(video_id, data)?;
+        let data = sealed.as_deref().unwrap_or(data);
+        let part = part_path(&path);
+        if let Err(e) = fs::write(&part, data).and_then(|()| fs::rename(&part, &path)) {
+            let _ = fs::remove_file(&part);
+            return Err(e.into());
+        }
+
+        self.register_stored_audio(
+            video_id,
+            format,
+            quality,
+            path,
+            (data.len() as u64, sha256(data)),
+            duration_secs,
+        )
+    }
+
+    /// Store audio read from `reader` until it ends, returning the file it was written to.
+    ///
+    /// Nothing is registered if reading fails part of the way.
+    pub async fn store_audio_stream(
+        &self,
+        video_id: &str,
+        format: &str,
+        quality: &str,
+        mut reader: impl AsyncRead + Unpin,
+        duration_secs: Option<f64>,
+    ) -> Result<PathBuf> {
+        // Audio is sealed as a whole, so encrypted audio is read in first
+        if self.is_encryption_enabled() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await?;
+            return self.store_audio(video_id, format, quality, &data, duration_secs);
+        }
+        let path = self.audio_file(video_id)?;
+        tokio::fs::create_dir_all(self.audio_dir()).await?;
+        let part = part_path(&path);
+
+        let written = async {
+            let mut file = tokio::fs::File::create(&part).await?;
+            let mut hasher = Sha256::new();
+            let mut buf = vec![0; STREAM_CHUNK];
+            let mut size_bytes = 0;
+            loop {
+                let read = reader.read(&mut buf).await?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buf[..read]);
+                file.write_all(&buf[..read]).await?;
+                size_bytes += read as u64;
+            }
+            file.flush().await?;
+            drop(file);
+            tokio::fs::rename(&part, &path).await?;
+            Ok::<_, std::io::Error>((size_bytes, hex::encode(hasher.finalize())))
+        }
+        .await;
+        let written = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&part).await;
+                return Err(e.into());
+            }
+        };
+
+        self.register_stored_audio(video_id, format, quality, path, written, duration_secs)
+    }
+
+    /// Register a stored audio file with its size and checksum.
+    fn register_stored_audio(
+        &self,
+        video_id: &str,
+        format: &str,
+        quality: &str,
+        file_path: PathBuf,
+        (size_bytes, checksum): (u64, String),
+        duration_secs: Option<f64>,
+    ) -> Result<PathBuf> {
+        debug!("Stored {size_bytes} bytes of audio for {video_id}");
+        self.register_audio(&AudioRecord {
+            video_id: video_id.to_string(),
+            format: format.to_string(),
+            quality: quality.to_string(),
+            file_path: file_path.clone(),
+            size_bytes,
+            duration_secs,
+        })?;
+        self.record_checksum("audio_cache", &file_path, &checksum)?;
+        Ok(file_path)
+    }
+
+    /// Read the cached audio of a video, marking it as recently played.
+    ///
+    /// Returns `None` if no audio is cached, or its file has gone. Encrypted
+    /// audio is decrypted with the current key.
+    pub async fn read_audio(&self, video_id: &str) -> Result<Option<Bytes>> {
+        let Some(path) = self.get_audio_path(video_id) else {
+            return Ok(None);
+        };
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(Some(self.open_audio(video_id, data)?.into())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Get when the cached audio of a video was last read.
+    pub fn audio_last_accessed(&self, video_id: &str) -> Option<DateTime<Utc>> {
+        let db = self.readers.get();
+        let accessed: String = db
+            .query_row(
+                "SELECT last_accessed FROM audio_cache WHERE video_id = ?",
+                [video_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()??;
+        DateTime::parse_from_rfc3339(&accessed)
+            .ok()
+            .map(|at| at.with_timezone(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_audio() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert!(cache
+            .store_audio("../abc", "audio/mp4", "128kbps", b"audio", None)
+            .is_err());
+
+        let path = cache.store_audio("abc", "audio/mp4", "128kbps", b"audio", Some(1.0))?;
+        assert_eq!(fs::read(&path)?, b"audio");
+        assert!(path.starts_with(cache.audio_dir()));
+        assert!(cache.has_audio("abc"));
+        let stats = cache.stats();
+        assert_eq!((stats.audio_count, stats.audio_size_bytes), (1, 5));
+
+        // Storing again replaces the audio
+        cache.store_audio("abc", "audio/webm", "160kbps", b"new audio", Some(1.0))?;
+        assert_eq!(fs::read(&path)?, b"new audio");
+        assert_eq!(cache.stats().audio_size_bytes, 9);
+
+        // A failed store leaves no part file behind
+        fs::create_dir_all(cache.audio_file("def")?)?;
+        assert!(cache
+            .store_audio("def", "audio/mp4", "128kbps", b"audio", None)
+            .is_err());
+        assert!(!part_path(&cache.audio_file("def")?).exists());
+        assert!(!cache.has_audio("def"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reads_update_access_time() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let path = cache
+            .store_audio_stream("abc", "audio/mp4", "128kbps", &b"streamed"[..], None)
+            .await?;
+        assert!(!part_path(&path).exists());
+        let stored = cache.audio_last_accessed("abc");
+        assert!(stored.is_some());
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        assert_eq!(
+            cache.read_audio("abc").await?.as_deref(),
+            Some(&b"streamed"[..])
+        );
+        assert!(cache.audio_last_accessed("abc") > stored);
+
+        fs::remove_file(&path)?;
+        assert_eq!(cache.read_audio("abc").await?, None);
+        assert_eq!(cache.read_audio("missing").await?, None);
+        Ok(())
+    }
+}
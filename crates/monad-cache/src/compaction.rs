@@ -0,0 +1,180 @@
+//! Compaction of the cache database and directory.
+//!
+//! `SQLite` keeps the pages freed by deletions, so the database file stays
+//! as large as it ever was until it is vacuumed. Compaction vacuums it and
+//! removes directories left empty, such as those of interrupted imports.
+//! With an auto-compaction threshold set, it runs after evictions and
+//! maintenance once that many bytes could be reclaimed.
+
+use std::fs;
+use std::path::Path;
+
+use monad_core::{Error, Result};
+use rusqlite::Connection;
+use tracing::{debug, info, warn};
+
+use crate::CacheManager;
+
+/// What a compaction reclaimed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// Bytes the database and its write-ahead log shrank by.
+    pub reclaimed_bytes: u64,
+    /// Empty directories removed.
+    pub removed_dirs: usize,
+}
+
+/// Get the size of the database file and its write-ahead log.
+fn database_size(path: &Path) -> u64 {
+    let wal = path.with_extension("db-wal");
+    [path, wal.as_path()]
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Get the bytes held by free pages of the database.
+fn free_bytes(db: &Connection) -> rusqlite::Result<u64> {
+    let free_pages: i64 = db.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    let page_size: i64 = db.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok(u64::try_from(free_pages * page_size).unwrap_or(0))
+}
+
+/// Remove the empty directories below `dir` not accepted by `keep`.
+///
+/// Returns whether `dir` itself is now empty.
+fn remove_empty_dirs(dir: &Path, keep: &impl Fn(&Path) -> bool, removed: &mut usize) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    let mut empty = true;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+        if !is_dir || !remove_empty_dirs(&path, keep, removed) || keep(&path) {
+            empty = false;
+            continue;
+        }
+        match fs::remove_dir(&path) {
+            Ok(()) => {
+                debug!("Removed empty cache directory {}", path.display());
+                *removed += 1;
+            }
+            Err(e) => {
+                warn!("Failed to remove {}: {e}", path.display());
+                empty = false;
+            }
+        }
+    }
+    empty
+}
+
+impl CacheManager {
+    /// Compact automatically once `threshold_bytes` could be reclaimed.
+    #[must_use]
+    pub fn with_auto_compaction(self, threshold_bytes: u64) -> Self {
+        self.set_auto_compaction(Some(threshold_bytes));
+        self
+    }
+
+    /// Change the auto-compaction threshold (`None` to only compact on request).
+    pub fn set_auto_compaction(&self, threshold_bytes: Option<u64>) {
+        *self.auto_compaction.lock() = threshold_bytes;
+    }
+
+    /// Get the bytes a compaction could reclaim from the database.
+    pub fn reclaimable_bytes(&self) -> u64 {
+        free_bytes(&self.readers.get()).unwrap_or(0)
+    }
+
+    /// Vacuum the database and remove empty directories.
+    ///
+    /// Blocks writes to the cache while the database is rewritten.
+    pub fn compact(&self) -> Result<Compaction> {
+        let db_path = self.cache_dir.join("cache.db");
+        let map_err = |e: rusqlite::Error| Error::Cache(format!("Cache compaction failed: {e}"));
+
+        let db = self.db.lock();
+        let before = database_size(&db_path);
+        db.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(map_err)?;
+        let after = database_size(&db_path);
+        drop(db);
+
+        // Keep the directories files are written to, so writes never race removal
+        let (audio_dir, thumbnail_dir) = (self.audio_dir(), self.thumbnail_dir());
+        let keep = |path: &Path| path == audio_dir || path == thumbnail_dir;
+        let mut compaction = Compaction {
+            reclaimed_bytes: before.saturating_sub(after),
+            removed_dirs: 0,
+        };
+        remove_empty_dirs(&self.cache_dir, &keep, &mut compaction.removed_dirs);
+
+        info!(
+            "Compacted the cache, reclaiming {} bytes and {} empty directories",
+            compaction.reclaimed_bytes, compaction.removed_dirs
+        );
+        Ok(compaction)
+    }
+
+    /// Compact if the auto-compaction threshold has been reached, logging any failure.
+    pub(crate) fn auto_compact(&self) {
+        let Some(threshold) = *self.auto_compaction.lock() else {
+            return;
+        };
+        if self.reclaimable_bytes() < threshold {
+            return;
+        }
+        if let Err(e) = self.compact() {
+            warn!("{e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fill the database with metadata, then delete it again.
+    fn churn(cache: &CacheManager) -> Result<()> {
+        let value = "x".repeat(4096);
+        for i in 0..100 {
+            cache.set_metadata(&format!("key{i}"), &value, Some(-10))?;
+        }
+        cache.run_maintenance()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_reclaims_space() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        churn(&cache)?;
+        assert!(cache.reclaimable_bytes() > 0);
+        fs::create_dir_all(dir.path().join("import/audio"))?;
+        fs::create_dir_all(cache.thumbnail_dir())?;
+
+        let compaction = cache.compact()?;
+        assert!(compaction.reclaimed_bytes > 0);
+        assert_eq!(compaction.removed_dirs, 2);
+        assert!(!dir.path().join("import").exists());
+        assert!(cache.thumbnail_dir().exists());
+        assert_eq!(cache.reclaimable_bytes(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_compaction_threshold() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache =
+            CacheManager::with_path(dir.path().to_path_buf())?.with_auto_compaction(u64::MAX);
+        churn(&cache)?;
+        assert!(cache.reclaimable_bytes() > 0);
+
+        cache.set_auto_compaction(Some(1));
+        churn(&cache)?;
+        assert_eq!(cache.reclaimable_bytes(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+//! Download state of audio not yet in the cache.
+//!
+//! The extractor queues every track it downloads for offline use and records
+//! its progress here, so a sync interrupted by quitting resumes on the next
+//! start and the UI can list what is still to come. Finished downloads are
+//! removed; the audio is in `audio_cache` from then on. Failed downloads stay
+//! listed with their error until they are queued again.
+
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Result};
+use rusqlite::{params, Row};
+
+use crate::CacheManager;
+
+/// State of a download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DownloadState {
+    /// Waiting to start.
+    Queued,
+    /// Started, possibly by an earlier run of the app.
+    InProgress,
+    /// Stopped with an error.
+    Failed,
+}
+
+impl DownloadState {
+    /// Name of the state in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::InProgress => "in_progress",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// Parse a state stored in the database.
+    fn parse(state: &str) -> Option<Self> {
+        match state {
+            "queued" => Some(Self::Queued),
+            "in_progress" => Some(Self::InProgress),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A download of a track's audio.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    /// `YouTube` video ID.
+    pub video_id: String,
+    /// Current state.
+    pub state: DownloadState,
+    /// Bytes downloaded so far, the offset a resumed download starts at.
+    pub bytes_downloaded: u64,
+    /// Total size in bytes, once known.
+    pub total_bytes: Option<u64>,
+    /// Why the last attempt failed.
+    pub error: Option<String>,
+    /// When the download was first queued.
+    pub queued_at: DateTime<Utc>,
+    /// When the download last changed.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Download {
+    /// Read a download from a row of the `downloads` table.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Option<Self>> {
+        let state: String = row.get(1)?;
+        let Some(state) = DownloadState::parse(&state) else {
+            return Ok(None);
+        };
+        let parse_time = |time: String| {
+            DateTime::parse_from_rfc3339(&time)
+                .map_or_else(|_| Utc::now(), |at| at.with_timezone(&Utc))
+        };
+        Ok(Some(Self {
+            video_id: row.get(0)?,
+            state,
+            bytes_downloaded: u64::try_from(row.get::<_, i64>(2)?).unwrap_or(0),
+            total_bytes: row
+                .get::<_, Option<i64>>(3)?
+                .and_then(|total| u64::try_from(total).ok()),
+            error: row.get(4)?,
+            queued_at: parse_time(row.get(5)?),
+            updated_at: parse_time(row.get(6)?),
+        }))
+    }
+
+    /// Get the share downloaded, if the total size is known.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> Option<f64> {
+        self.total_bytes
+            .filter(|&total| total > 0)
+            .map(|total| (self.bytes_downloaded as f64 / total as f64).min(1.0))
+    }
+}
+
+impl CacheManager {
+    /// Queue downloads of tracks, keeping the progress of any already listed.
+    ///
+    /// Failed downloads are queued again.
+    pub fn queue_downloads(&self, video_ids: &[&str]) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut db = self.db.lock();
+        let tx = db
+            .transaction()
+            .map_err(|e| Error::Cache(format!("Failed to queue downloads: {e}")))?;
+        for video_id in video_ids {
+            tx.execute(
+                "INSERT INTO downloads (video_id, state, bytes_downloaded, queued_at, updated_at)
+                 VALUES (?1, ?2, 0, ?3, ?3)
+                 ON CONFLICT(video_id) DO UPDATE SET
+                    state = CASE WHEN state = ?4 THEN ?2 ELSE state END,
+                    error = NULL,
+                    updated_at = excluded.updated_at",
+                params![
+                    video_id,
+                    DownloadState::Queued.as_str(),
+                    now,
+                    DownloadState::Failed.as_str()
+                ],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to queue download of {video_id}: {e}")))?;
+        }
+        tx.commit()
+            .map_err(|e| Error::Cache(format!("Failed to queue downloads: {e}")))
+    }
+
+    /// Record the progress of a download, marking it in progress.
+    pub fn update_download(
+        &self,
+        video_id: &str,
+        bytes_downloaded: u64,
+        total_bytes: Option<u64>,
+    ) -> Result<()> {
+        let db = self.db.lock();
+        db.execute(
+            "UPDATE downloads SET state = ?, bytes_downloaded = ?,
+                total_bytes = COALESCE(?, total_bytes), error = NULL, updated_at = ?
+             WHERE video_id = ?",
+            params![
+                DownloadState::InProgress.as_str(),
+                i64::try_from(bytes_downloaded).unwrap_or(i64::MAX),
+                total_bytes.and_then(|total| i64::try_from(total).ok()),
+                Utc::now().to_rfc3339(),
+                video_id
+            ],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to update download of {video_id}: {e}")))?;
+        Ok(())
+    }
+
+    /// Mark a download as failed with `error`.
+    pub fn fail_download(&self, video_id: &str, error: &str) -> Result<()> {
+        let db = self.db.lock();
+        db.execute(
+            "UPDATE downloads SET state = ?, error = ?, updated_at = ? WHERE video_id = ?",
+            params![
+                DownloadState::Failed.as_str(),
+                error,
+                Utc::now().to_rfc3339(),
+                video_id
+            ],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to update download of {video_id}: {e}")))?;
+        Ok(())
+    }
+
+    /// Remove a download, once finished or when it is no longer wanted.
+    pub fn remove_download(&self, video_id: &str) -> Result<()> {
+        let db = self.db.lock();
+        db.execute("DELETE FROM downloads WHERE video_id = ?", [video_id])
+            .map_err(|e| Error::Cache(format!("Failed to remove download of {video_id}: {e}")))?;
+        Ok(())
+    }
+
+    /// List downloads, oldest first.
+    pub fn list_downloads(&self) -> Result<Vec<Download>> {
+        let db = self.readers.get();
+        let rows: Vec<Option<Download>> = db
+            .prepare(
+                "SELECT video_id, state, bytes_downloaded, total_bytes, error, queued_at, updated_at
+                 FROM downloads ORDER BY queued_at, rowid",
+            )
+            .and_then(|mut stmt| stmt.query_map([], Download::from_row)?.collect())
+            .map_err(|e| Error::Cache(format!("Failed to list downloads: {e}")))?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// List the video IDs of downloads still to finish, oldest first.
+    ///
+    /// Downloads in progress when the app last quit are included.
+    pub fn pending_downloads(&self) -> Result<Vec<String>> {
+        Ok(self
+            .list_downloads()?
+            .into_iter()
+            .filter(|download| download.state != DownloadState::Failed)
+            .map(|download| download.video_id)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_lifecycle() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.queue_downloads(&["a", "b", "c"])?;
+        cache.update_download("a", 100, Some(400))?;
+        cache.fail_download("b", "Video unavailable")?;
+        cache.remove_download("c")?;
+
+        let downloads = cache.list_downloads()?;
+        assert_eq!(downloads.len(), 2);
+        assert_eq!(downloads[0].state, DownloadState::InProgress);
+        assert_eq!(downloads[0].fraction(), Some(0.25));
+        assert_eq!(downloads[1].state, DownloadState::Failed);
+        assert_eq!(downloads[1].error.as_deref(), Some("Video unavailable"));
+        assert_eq!(cache.pending_downloads()?, ["a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_queueing_again_keeps_progress() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.queue_downloads(&["a", "b"])?;
+        cache.update_download("a", 100, None)?;
+        cache.fail_download("b", "Network error")?;
+
+        cache.queue_downloads(&["a", "b"])?;
+        let downloads = cache.list_downloads()?;
+        assert_eq!(downloads[0].state, DownloadState::InProgress);
+        assert_eq!(downloads[0].bytes_downloaded, 100);
+        assert_eq!(downloads[1].state, DownloadState::Queued);
+        assert_eq!(downloads[1].error, None);
+        assert_eq!(cache.pending_downloads()?, ["a", "b"]);
+        Ok(())
+    }
+}
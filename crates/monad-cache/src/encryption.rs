@@ -0,0 +1,299 @@
+//! Optional at-rest encryption of stored audio.
+//!
+//! When a key is set, audio stored by the cache is sealed with
+//! XChaCha20-Poly1305 and opened again transparently on read. The key comes
+//! from the OS keychain or is derived from a passphrase with Argon2, salted
+//! per cache. Encrypted files start with a magic header, so a cache can mix
+//! them with audio stored before encryption was turned on.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use monad_core::{Error, Result};
+use tracing::info;
+
+use crate::integrity::sha256;
+use crate::CacheManager;
+
+/// Header of an encrypted audio file.
+const MAGIC: &[u8; 8] = b"MONADEN1";
+/// Length of the nonce following the header.
+const NONCE_LEN: usize = 24;
+/// Length of the salt used to derive keys from passphrases.
+const SALT_LEN: usize = 16;
+/// File in the cache directory holding the passphrase salt.
+const SALT_FILE: &str = "encryption.salt";
+/// Keychain service and account the key is stored under.
+const KEYCHAIN_SERVICE: &str = "monad";
+const KEYCHAIN_USER: &str = "cache-encryption-key";
+
+/// Key used to encrypt stored audio.
+#[derive(Clone)]
+pub struct EncryptionKey(Key);
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    /// Generate a random key.
+    pub fn generate() -> Self {
+        Self(XChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    /// Use raw key bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes.into())
+    }
+
+    /// Derive a key from a passphrase.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = Key::default();
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| Error::Cache(format!("Failed to derive encryption key: {e}")))?;
+        Ok(Self(key))
+    }
+
+    /// Load the key from the OS keychain, storing a new one on first use.
+    ///
+    /// Blocks while the keychain is queried; call it off the async runtime.
+    pub fn from_keychain() -> Result<Self> {
+        let map_err = |e: keyring::Error| Error::Cache(format!("Keychain access failed: {e}"));
+        let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_USER).map_err(map_err)?;
+        match entry.get_secret() {
+            Ok(secret) => <[u8; 32]>::try_from(secret.as_slice())
+                .map(Self::from_bytes)
+                .map_err(|_| Error::Cache("Keychain holds an invalid encryption key".to_string())),
+            Err(keyring::Error::NoEntry) => {
+                let key = Self::generate();
+                entry.set_secret(&key.0).map_err(map_err)?;
+                info!("Stored a new cache encryption key in the keychain");
+                Ok(key)
+            }
+            Err(e) => Err(map_err(e)),
+        }
+    }
+
+    /// Encrypt the audio of `video_id`.
+    fn seal(&self, video_id: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = XChaCha20Poly1305::new(&self.0)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: data,
+                    aad: video_id.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Cache(format!("Failed to encrypt audio for {video_id}")))?;
+        let mut file = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&nonce);
+        file.extend_from_slice(&sealed);
+        Ok(file)
+    }
+
+    /// Decrypt an encrypted audio file of `video_id`.
+    fn open(&self, video_id: &str, file: &[u8]) -> Result<Vec<u8>> {
+        let failed = || {
+            Error::Cache(format!(
+                "Failed to decrypt cached audio for {video_id}: wrong key or corrupted file"
+            ))
+        };
+        let rest = file.get(MAGIC.len()..).ok_or_else(failed)?;
+        if rest.len() < NONCE_LEN {
+            return Err(failed());
+        }
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        XChaCha20Poly1305::new(&self.0)
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: sealed,
+                    aad: video_id.as_bytes(),
+                },
+            )
+            .map_err(|_| failed())
+    }
+}
+
+/// Check whether stored audio is encrypted.
+pub fn is_encrypted(file: &[u8]) -> bool {
+    file.starts_with(MAGIC)
+}
+
+impl CacheManager {
+    /// Encrypt audio stored from now on with `key`.
+    #[must_use]
+    pub fn with_encryption(self, key: EncryptionKey) -> Self {
+        self.set_encryption(Some(key));
+        self
+    }
+
+    /// Set or clear the key audio is encrypted with.
+    ///
+    /// Without a key, new audio is stored in the clear and encrypted audio
+    /// can no longer be read.
+    pub fn set_encryption(&self, key: Option<EncryptionKey>) {
+        *self.encryption.lock() = key;
+    }
+
+    /// Check whether stored audio is being encrypted.
+    pub fn is_encryption_enabled(&self) -> bool {
+        self.encryption.lock().is_some()
+    }
+
+    /// Derive a key from a passphrase, salted for this cache.
+    pub fn passphrase_key(&self, passphrase: &str) -> Result<EncryptionKey> {
+        EncryptionKey::from_passphrase(passphrase, &self.passphrase_salt()?)
+    }
+
+    /// Read this cache's passphrase salt, creating it on first use.
+    fn passphrase_salt(&self) -> Result<Vec<u8>> {
+        let path = self.cache_dir.join(SALT_FILE);
+        match fs::read(&path) {
+            Ok(salt) if salt.len() == SALT_LEN => return Ok(salt),
+            Ok(_) => return Err(Error::Cache("Invalid encryption salt".to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut salt = vec![0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        fs::write(&path, &salt)?;
+        Ok(salt)
+    }
+
+    /// Encrypt audio about to be stored, if encryption is enabled.
+    pub(crate) fn seal_audio(&self, video_id: &str, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let key = self.encryption.lock().clone();
+        key.map(|key| key.seal(video_id, data)).transpose()
+    }
+
+    /// Decrypt stored audio if it is encrypted.
+    pub fn open_audio(&self, video_id: &str, file: Vec<u8>) -> Result<Vec<u8>> {
+        if !is_encrypted(&file) {
+            return Ok(file);
+        }
+        let key = self.encryption.lock().clone().ok_or_else(|| {
+            Error::Cache(format!(
+                "Cached audio for {video_id} is encrypted, but no key is set"
+            ))
+        })?;
+        key.open(video_id, &file)
+    }
+
+    /// Encrypt audio this cache stored before encryption was enabled.
+    ///
+    /// Returns the number of files encrypted.
+    pub fn encrypt_stored_audio(&self) -> Result<usize> {
+        if !self.is_encryption_enabled() {
+            return Err(Error::Cache("No encryption key is set".to_string()));
+        }
+        let audio_dir = self.audio_dir();
+        let rows: Vec<(String, String)> = self
+            .readers
+            .get()
+            .prepare("SELECT video_id, file_path FROM audio_cache")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .map_err(|e| Error::Cache(format!("Failed to list stored audio: {e}")))?;
+
+        let mut encrypted = 0;
+        for (video_id, file_path) in rows {
+            let path = PathBuf::from(&file_path);
+            // Files registered from elsewhere belong to their writers
+            if !path.starts_with(&audio_dir) || !path.is_file() {
+                continue;
+            }
+            let data = fs::read(&path)?;
+            if is_encrypted(&data) {
+                continue;
+            }
+            let Some(sealed) = self.seal_audio(&video_id, &data)? else {
+                continue;
+            };
+            let part = path.with_extension("audio.part");
+            fs::write(&part, &sealed)?;
+            fs::rename(&part, &path)?;
+            self.db
+                .lock()
+                .execute(
+                    "UPDATE audio_cache SET size_bytes = ?, sha256 = ? WHERE file_path = ?",
+                    rusqlite::params![sealed.len() as u64, sha256(&sealed), file_path],
+                )
+                .map_err(|e| Error::Cache(format!("Failed to update stored audio: {e}")))?;
+            encrypted += 1;
+        }
+        info!("Encrypted {encrypted} stored audio files");
+        Ok(encrypted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encrypted_audio_round_trips() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?
+            .with_encryption(EncryptionKey::generate());
+        let path = cache.store_audio("abc", "audio/mp4", "128kbps", b"secret audio", None)?;
+        let file = fs::read(&path)?;
+        assert!(is_encrypted(&file));
+        assert!(!file.windows(6).any(|w| w == b"secret"));
+        assert_eq!(cache.stats().audio_size_bytes, file.len() as u64);
+        assert_eq!(
+            cache.read_audio("abc").await?.as_deref(),
+            Some(&b"secret audio"[..])
+        );
+
+        cache
+            .store_audio_stream("def", "audio/mp4", "128kbps", &b"streamed"[..], None)
+            .await?;
+        assert_eq!(
+            cache.read_audio("def").await?.as_deref(),
+            Some(&b"streamed"[..])
+        );
+
+        // Without the right key the audio stays unreadable
+        cache.set_encryption(Some(EncryptionKey::generate()));
+        assert!(cache.read_audio("abc").await.is_err());
+        cache.set_encryption(None);
+        assert!(cache.read_audio("abc").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_passphrase_key_encrypts_stored_audio() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let path = cache.store_audio("abc", "audio/mp4", "128kbps", b"plain audio", None)?;
+        assert!(cache.encrypt_stored_audio().is_err());
+
+        let key = cache.passphrase_key("correct horse")?;
+        cache.set_encryption(Some(key));
+        assert_eq!(cache.encrypt_stored_audio()?, 1);
+        assert_eq!(cache.encrypt_stored_audio()?, 0);
+        assert!(is_encrypted(&fs::read(&path)?));
+
+        // The same passphrase gives the same key for this cache
+        cache.set_encryption(Some(cache.passphrase_key("correct horse")?));
+        assert_eq!(
+            cache.read_audio("abc").await?.as_deref(),
+            Some(&b"plain audio"[..])
+        );
+        cache.set_encryption(Some(cache.passphrase_key("wrong")?));
+        assert!(cache.read_audio("abc").await.is_err());
+        Ok(())
+    }
+}
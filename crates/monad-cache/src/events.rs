@@ -0,0 +1,139 @@
+//! Notifications of changes to the cache.
+//!
+//! Subscribers receive a [`CacheEvent`] whenever audio or a thumbnail is
+//! added, evicted, or the cache is cleared, and whenever a setting changes,
+//! so views can refresh without polling [`CacheManager::stats`]. Subscribers
+//! that fall behind miss events and should refresh in full.
+
+use tokio::sync::broadcast;
+
+use crate::CacheManager;
+
+/// Events buffered for each subscriber before the oldest are dropped.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// A cached audio file or thumbnail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheItem {
+    /// Audio of a video.
+    Audio {
+        /// Video ID.
+        video_id: String,
+    },
+    /// Thumbnail downloaded from a URL.
+    Thumbnail {
+        /// Hash of the URL.
+        url_hash: String,
+    },
+}
+
+/// A change to the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheEvent {
+    /// Audio was stored or registered.
+    AudioAdded {
+        /// Video ID.
+        video_id: String,
+        /// Size of the file in bytes.
+        size_bytes: u64,
+    },
+    /// A thumbnail was stored.
+    ThumbnailAdded {
+        /// URL the thumbnail was downloaded from.
+        url: String,
+    },
+    /// Audio or a thumbnail was evicted to make room or for being stale.
+    Evicted {
+        /// What was evicted.
+        item: CacheItem,
+        /// Bytes freed.
+        freed_bytes: u64,
+    },
+    /// The cache was cleared.
+    Cleared,
+    /// A setting was changed or reset.
+    SettingChanged {
+        /// Key of the setting.
+        key: String,
+    },
+}
+
+impl CacheManager {
+    /// Subscribe to changes to the cache.
+    pub fn subscribe(&self) -> broadcast::Receiver<CacheEvent> {
+        self.events.subscribe()
+    }
+
+    /// Notify subscribers of a change, if there are any.
+    pub(crate) fn notify(&self, event: CacheEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::Result;
+
+    /// Take the events received so far.
+    fn drain(events: &mut broadcast::Receiver<CacheEvent>) -> Vec<CacheEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn test_events_follow_writes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?.with_size_limit(150);
+        let mut events = cache.subscribe();
+
+        cache.store_audio("old", "audio/mp4", "128kbps", &[0; 100], None)?;
+        cache.store_thumbnail("https://example.com/a.jpg", &[0; 20])?;
+        cache.store_audio("new", "audio/mp4", "128kbps", &[0; 100], None)?;
+        cache.clear()?;
+
+        assert_eq!(
+            drain(&mut events),
+            [
+                CacheEvent::AudioAdded {
+                    video_id: "old".to_string(),
+                    size_bytes: 100
+                },
+                CacheEvent::ThumbnailAdded {
+                    url: "https://example.com/a.jpg".to_string()
+                },
+                CacheEvent::AudioAdded {
+                    video_id: "new".to_string(),
+                    size_bytes: 100
+                },
+                CacheEvent::Evicted {
+                    item: CacheItem::Audio {
+                        video_id: "old".to_string()
+                    },
+                    freed_bytes: 100
+                },
+                CacheEvent::Cleared,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_thumbnails_notify() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let path = cache.store_thumbnail("https://example.com/a.jpg", &[0; 20])?;
+        let mut events = cache.subscribe();
+
+        std::fs::remove_file(path)?;
+        cache.evict_stale_thumbnails(std::time::Duration::from_secs(60 * 60))?;
+        let url_hash = CacheManager::hash_url("https://example.com/a.jpg");
+        assert_eq!(
+            drain(&mut events),
+            [CacheEvent::Evicted {
+                item: CacheItem::Thumbnail { url_hash },
+                freed_bytes: 0
+            }]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,412 @@
+//! Checksum-based verification of cached files.
+//!
+//! The SHA-256 of every audio file and thumbnail the cache stores is kept
+//! next to its entry. Verifying rehashes the files to find truncated or
+//! corrupted ones, entries whose file is gone and files no entry refers to.
+//! Files cached before checksums existed are trusted and have one recorded
+//! on their first verification.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use monad_core::{Error, Result};
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::thumbnails::memory_key;
+use crate::CacheManager;
+
+/// Hex-encoded SHA-256 of data.
+pub fn sha256(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Hex-encoded SHA-256 of a file, read in chunks.
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Which cached files to verify.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerifyScope {
+    /// Audio and thumbnails.
+    #[default]
+    All,
+    /// Audio only.
+    Audio,
+    /// Thumbnails only.
+    Thumbnails,
+}
+
+impl VerifyScope {
+    /// Check whether audio is verified.
+    const fn audio(self) -> bool {
+        matches!(self, Self::All | Self::Audio)
+    }
+
+    /// Check whether thumbnails are verified.
+    const fn thumbnails(self) -> bool {
+        matches!(self, Self::All | Self::Thumbnails)
+    }
+}
+
+/// Problems found by verifying the cache, and whether they were repaired.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files checked against their checksum.
+    pub checked: usize,
+    /// Files that had no checksum yet and now have one.
+    pub recorded: usize,
+    /// Video IDs of audio whose file does not match its checksum.
+    pub corrupted_audio: Vec<String>,
+    /// URLs of thumbnails whose file does not match its checksum.
+    pub corrupted_thumbnails: Vec<String>,
+    /// Video IDs of audio entries whose file is gone.
+    pub missing_audio: Vec<String>,
+    /// URLs of thumbnail entries whose file is gone.
+    pub missing_thumbnails: Vec<String>,
+    /// Files written by the cache that no entry refers to.
+    pub unregistered_files: Vec<PathBuf>,
+    /// Whether bad entries and unregistered files were deleted.
+    pub repaired: bool,
+}
+
+impl VerifyReport {
+    /// Check whether verification found no problems.
+    pub fn is_clean(&self) -> bool {
+        self.corrupted_audio.is_empty()
+            && self.corrupted_thumbnails.is_empty()
+            && self.missing_audio.is_empty()
+            && self.missing_thumbnails.is_empty()
+            && self.unregistered_files.is_empty()
+    }
+
+    /// Video IDs whose audio should be downloaded again.
+    pub fn requeue_audio(&self) -> Vec<String> {
+        self.corrupted_audio
+            .iter()
+            .chain(&self.missing_audio)
+            .cloned()
+            .collect()
+    }
+
+    /// URLs of thumbnails that should be downloaded again.
+    pub fn requeue_thumbnails(&self) -> Vec<String> {
+        self.corrupted_thumbnails
+            .iter()
+            .chain(&self.missing_thumbnails)
+            .cloned()
+            .collect()
+    }
+}
+
+/// An entry to verify.
+struct Entry {
+    /// Video ID or thumbnail URL.
+    key: String,
+    path: PathBuf,
+    /// Expected size, where it is recorded.
+    size_bytes: Option<u64>,
+    sha256: Option<String>,
+}
+
+/// Outcome of checking one entry.
+enum Check {
+    Ok,
+    Recorded(String),
+    Corrupted,
+    Missing,
+}
+
+impl Entry {
+    /// Check the entry's file against its checksum.
+    fn check(&self) -> Check {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Check::Missing;
+        };
+        if !metadata.is_file() {
+            return Check::Missing;
+        }
+        if self.size_bytes.is_some_and(|size| size != metadata.len()) {
+            return Check::Corrupted;
+        }
+        let actual = match file_sha256(&self.path) {
+            Ok(actual) => actual,
+            Err(e) => {
+                warn!("Failed to read {}: {e}", self.path.display());
+                return Check::Corrupted;
+            }
+        };
+        match &self.sha256 {
+            None => Check::Recorded(actual),
+            Some(expected) if *expected == actual => Check::Ok,
+            Some(_) => Check::Corrupted,
+        }
+    }
+}
+
+/// Unregistered files in `dir` accepted by `owned`.
+fn unregistered(dir: &Path, owned: impl Fn(&Path) -> bool, entries: &[Entry]) -> Vec<PathBuf> {
+    let referenced: HashSet<&Path> = entries.iter().map(|entry| entry.path.as_path()).collect();
+    let Ok(files) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<PathBuf> = files
+        .flatten()
+        .map(|file| file.path())
+        .filter(|path| path.is_file() && owned(path) && !referenced.contains(path.as_path()))
+        .collect();
+    found.sort();
+    found
+}
+
+impl CacheManager {
+    /// Record the checksum of a stored file.
+    pub(crate) fn record_checksum(&self, table: &str, path: &Path, sha256: &str) -> Result<()> {
+        self.db
+            .lock()
+            .execute(
+                &format!("UPDATE {table} SET sha256 = ? WHERE file_path = ?"),
+                [sha256, &path.to_string_lossy()],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to record checksum: {e}")))?;
+        Ok(())
+    }
+
+    /// Get the size and SHA-256 recorded for stored audio, if it has a checksum.
+    pub fn audio_checksum(&self, video_id: &str) -> Option<(u64, String)> {
+        let db = self.readers.get();
+        db.query_row(
+            "SELECT size_bytes, sha256 FROM audio_cache WHERE video_id = ? AND sha256 IS NOT NULL",
+            [video_id],
+            |row| {
+                Ok((
+                    u64::try_from(row.get::<_, i64>(0)?).unwrap_or(0),
+                    row.get(1)?,
+                ))
+            },
+        )
+        .ok()
+    }
+
+    /// Record the SHA-256 of stored audio that has no checksum yet.
+    pub fn record_audio_checksum(&self, video_id: &str, sha256: &str) -> Result<()> {
+        self.db
+            .lock()
+            .execute(
+                "UPDATE audio_cache SET sha256 = ? WHERE video_id = ? AND sha256 IS NULL",
+                [sha256, video_id],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to record checksum: {e}")))?;
+        Ok(())
+    }
+
+    /// Read the entries of a table to verify.
+    fn entries(&self, sql: &str) -> Result<Vec<Entry>> {
+        let db = self.readers.get();
+        db.prepare(sql)
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok(Entry {
+                        key: row.get(0)?,
+                        path: PathBuf::from(row.get::<_, String>(1)?),
+                        size_bytes: row
+                            .get::<_, Option<i64>>(2)?
+                            .and_then(|size| u64::try_from(size).ok()),
+                        sha256: row.get(3)?,
+                    })
+                })?
+                .collect()
+            })
+            .map_err(|e| Error::Cache(format!("Failed to read cache entries: {e}")))
+    }
+
+    /// Check entries against their checksums, returning the bad ones' keys.
+    fn check_entries(
+        &self,
+        table: &str,
+        entries: &[Entry],
+        report: &mut VerifyReport,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let (mut corrupted, mut missing) = (Vec::new(), Vec::new());
+        for entry in entries {
+            match entry.check() {
+                Check::Ok => report.checked += 1,
+                Check::Recorded(sha256) => {
+                    self.record_checksum(table, &entry.path, &sha256)?;
+                    report.recorded += 1;
+                }
+                Check::Corrupted => {
+                    report.checked += 1;
+                    corrupted.push(entry.key.clone());
+                }
+                Check::Missing => missing.push(entry.key.clone()),
+            }
+        }
+        Ok((corrupted, missing))
+    }
+
+    /// Verify cached files against their checksums.
+    ///
+    /// With `repair`, corrupted files and their entries, entries without a
+    /// file and unregistered files are deleted; [`VerifyReport::requeue_audio`]
+    /// and [`VerifyReport::requeue_thumbnails`] list what to download again.
+    /// Reads every file in scope, so run it off the async runtime.
+    pub fn verify(&self, scope: VerifyScope, repair: bool) -> Result<VerifyReport> {
+        let mut report = VerifyReport {
+            repaired: repair,
+            ..VerifyReport::default()
+        };
+
+        if scope.audio() {
+            let entries =
+                self.entries("SELECT video_id, file_path, size_bytes, sha256 FROM audio_cache")?;
+            (report.corrupted_audio, report.missing_audio) =
+                self.check_entries("audio_cache", &entries, &mut report)?;
+            // Files still being written are left alone
+            let stored = |path: &Path| path.extension().is_some_and(|ext| ext == "audio");
+            report
+                .unregistered_files
+                .extend(unregistered(&self.audio_dir(), stored, &entries));
+        }
+        if scope.thumbnails() {
+            let entries =
+                self.entries("SELECT url, file_path, NULL, sha256 FROM thumbnail_cache")?;
+            (report.corrupted_thumbnails, report.missing_thumbnails) =
+                self.check_entries("thumbnail_cache", &entries, &mut report)?;
+            report.unregistered_files.extend(unregistered(
+                &self.thumbnail_dir(),
+                |_| true,
+                &entries,
+            ));
+        }
+
+        if repair {
+            self.repair(&report)?;
+        }
+        if report.is_clean() {
+            info!("Verified {} cached files", report.checked);
+        } else {
+            warn!(
+                "Cache verification found {} corrupted files, {} entries without files and {} unregistered files",
+                report.corrupted_audio.len() + report.corrupted_thumbnails.len(),
+                report.missing_audio.len() + report.missing_thumbnails.len(),
+                report.unregistered_files.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Delete the bad entries and files found by a verification.
+    fn repair(&self, report: &VerifyReport) -> Result<()> {
+        let map_err = |e: rusqlite::Error| Error::Cache(format!("Failed to repair cache: {e}"));
+        let db = self.db.lock();
+        for video_id in report.requeue_audio() {
+            let path: Option<String> = db
+                .query_row(
+                    "DELETE FROM audio_cache WHERE video_id = ? RETURNING file_path",
+                    [&video_id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(map_err)?;
+            if let Some(path) = path {
+                let _ = fs::remove_file(path);
+            }
+        }
+        for url in report.requeue_thumbnails() {
+            let url_hash = Self::hash_url(&url);
+            db.execute(
+                "DELETE FROM thumbnail_cache WHERE url_hash = ?",
+                [&url_hash],
+            )
+            .map_err(map_err)?;
+            let _ = fs::remove_file(self.thumbnail_dir().join(&url_hash));
+            self.memory_cache.lock().pop(&memory_key(&url_hash));
+        }
+        drop(db);
+        for path in &report.unregistered_files {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to delete {}: {e}", path.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_finds_bad_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let good = cache.store_audio("good", "audio/mp4", "128kbps", b"audio", None)?;
+        let flipped = cache.store_audio("flipped", "audio/mp4", "128kbps", b"audio", None)?;
+        let truncated = cache.store_audio("truncated", "audio/mp4", "128kbps", b"audio", None)?;
+        let gone = cache.store_audio("gone", "audio/mp4", "128kbps", b"audio", None)?;
+        let art = cache.store_thumbnail("https://i.ytimg.com/a.jpg", b"jpeg")?;
+        fs::write(&flipped, b"audia")?;
+        fs::write(&truncated, b"aud")?;
+        fs::remove_file(&gone)?;
+        fs::write(&art, b"jpg")?;
+        let stray = cache.audio_dir().join("stray.audio");
+        fs::write(&stray, b"audio")?;
+
+        assert_eq!(cache.audio_checksum("good"), Some((5, sha256(b"audio"))));
+
+        let report = cache.verify(VerifyScope::Audio, false)?;
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.corrupted_audio, ["flipped", "truncated"]);
+        assert_eq!(report.missing_audio, ["gone"]);
+        assert_eq!(report.unregistered_files, std::slice::from_ref(&stray));
+        assert!(report.corrupted_thumbnails.is_empty());
+        assert!(flipped.exists());
+
+        let report = cache.verify(VerifyScope::All, true)?;
+        assert_eq!(report.requeue_audio().len(), 3);
+        assert_eq!(report.requeue_thumbnails(), ["https://i.ytimg.com/a.jpg"]);
+        assert!(!flipped.exists() && !stray.exists() && !art.exists());
+        assert!(good.exists() && cache.has_audio("good"));
+        assert!(!cache.has_audio("gone"));
+        assert!(cache.verify(VerifyScope::All, false)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_records_missing_checksums() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let path = dir.path().join("external.webm");
+        fs::write(&path, b"audio")?;
+        cache.register_audio(&crate::AudioRecord {
+            video_id: "abc".to_string(),
+            format: "audio/webm".to_string(),
+            quality: "160kbps".to_string(),
+            file_path: path.clone(),
+            size_bytes: 5,
+            duration_secs: None,
+        })?;
+
+        assert_eq!(cache.audio_checksum("abc"), None);
+        let report = cache.verify(VerifyScope::Audio, false)?;
+        assert_eq!((report.checked, report.recorded), (0, 1));
+        assert!(report.is_clean());
+        assert_eq!(cache.verify(VerifyScope::Audio, false)?.checked, 1);
+
+        // A recorded checksum is not replaced
+        cache.record_audio_checksum("abc", &sha256(b"noise"))?;
+        assert_eq!(cache.audio_checksum("abc"), Some((5, sha256(b"audio"))));
+        fs::write(&path, b"noise")?;
+        assert_eq!(
+            cache.verify(VerifyScope::Audio, false)?.corrupted_audio,
+            ["abc"]
+        );
+        Ok(())
+    }
+}
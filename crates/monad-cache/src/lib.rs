@@ -2,10 +2,84 @@
 //!
 //! Offline caching (`SQLite` + filesystem) for Monad.
 //!
+//! The database runs in WAL mode, with a pool of read-only connections so
+//! reads never wait behind writes.
+//!
 //! This crate provides persistent caching for:
-//! - Audio files for offline playback
+//! - Audio files for offline playback, with access times for eviction
+//! - Optional at-rest encryption of stored audio
 //! - Metadata (tracks, albums, playlists)
 //! - Thumbnails and artwork
+//! - Lyrics of played tracks, kept for replaying and playing offline, and
+//!   per-track timing offsets
+//! - An optional size limit, evicting the least recently used files
+//! - Storage statistics by format, quality and pin state, with hit rates
+//! - Tracks, albums and playlists pinned for offline playback
+//! - Warming of album and playlist metadata and artwork, for opening offline
+//! - Download state of offline syncs, so interrupted ones resume
+//! - Periodic cleanup of expired metadata and orphaned files
+//! - Notifications of added, evicted and cleared entries
+//! - Compaction of the database and cache directory, on request or automatically
+//! - Checksum verification of cached files, with optional repair
+//! - A local library of saved tracks, albums and artists
+//! - Play history with per-artist and per-album play counts
+//! - Smart playlists, evaluated from rules over the history and library
+//! - The playback queue and position, for restoring the session
+//! - App settings, with change notifications
+//! - A journal of scrobbles to submit once online or no longer rate-limited
+//! - Offline "instant mix" continuations from cached tracks
+//! - Full-text offline search over played and opened tracks, albums and playlists
+//! - Album and playlist contents per visit, for "new tracks" badges
+//! - Weekly listening reports from the play history
+//! - Export to and import from a single archive, for backups and moving machines
+
+mod archive;
+mod audio;
+mod compaction;
+mod downloads;
+mod encryption;
+mod events;
+mod integrity;
+mod limits;
+mod lyrics;
+mod maintenance;
+mod migrations;
+mod mix;
+mod pins;
+mod pool;
+mod report;
+mod saved;
+mod scrobbles;
+mod search;
+mod session;
+mod settings;
+mod smart_playlists;
+mod stats;
+mod thumbnails;
+mod visits;
+mod warming;
+
+pub use archive::ArchiveContents;
+pub use compaction::Compaction;
+pub use downloads::{Download, DownloadState};
+pub use encryption::{is_encrypted, EncryptionKey};
+pub use events::{CacheEvent, CacheItem, EVENT_CAPACITY};
+pub use integrity::{VerifyReport, VerifyScope};
+pub use limits::Eviction;
+pub use lyrics::LYRICS_TTL_SECS;
+pub use maintenance::MaintenanceReport;
+pub use migrations::SCHEMA_VERSION;
+pub use mix::instant_mix;
+pub use pins::{Pin, PinKind, PinTarget};
+pub use report::{last_full_week, TopArtist, TopTrack, WeeklyReport, REPORT_TOP_LIMIT};
+pub use scrobbles::{Scrobble, MAX_SCROBBLE_ATTEMPTS};
+pub use search::{OfflineResult, OFFLINE_SEARCH_LIMIT};
+pub use session::SavedQueue;
+pub use smart_playlists::{SmartOrder, SmartPlaylist, SmartRule, SMART_PLAYLIST_LIMIT};
+pub use stats::StorageBreakdown;
+pub use thumbnails::DEFAULT_THUMBNAIL_MAX_AGE;
+pub use visits::{content_hash, CollectionDiff, CollectionVisit};
+pub use warming::Warming;
 
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -14,20 +88,42 @@ use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
 use lru::LruCache;
-use monad_core::{Error, Result};
+use monad_core::{Error, Result, Track};
 use parking_lot::Mutex;
 use rusqlite::Connection;
 use sha2::{Digest, Sha256};
-use tracing::info;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::pool::{ReadPool, READ_CONNECTIONS};
+use crate::stats::LookupCounters;
+
+/// Number of history entries considered for an instant mix.
+const MIX_HISTORY_LIMIT: usize = 5000;
 
 /// Cache manager for Monad.
+#[derive(Debug)]
 pub struct CacheManager {
-    /// `SQLite` database connection.
+    /// `SQLite` connection for writes.
     db: Arc<Mutex<Connection>>,
+    /// Read-only `SQLite` connections.
+    readers: Arc<ReadPool>,
     /// Cache directory path.
     cache_dir: PathBuf,
     /// In-memory LRU cache for hot data.
     memory_cache: Arc<Mutex<LruCache<String, Bytes>>>,
+    /// Client for downloading thumbnails.
+    http: reqwest::Client,
+    /// Maximum size of cached audio and thumbnails in bytes.
+    size_limit: Mutex<Option<u64>>,
+    /// Reclaimable bytes at which the cache compacts itself, if any.
+    auto_compaction: Mutex<Option<u64>>,
+    /// Key stored audio is encrypted with, if encryption is enabled.
+    encryption: Mutex<Option<EncryptionKey>>,
+    /// Hits and misses of lookups since the cache was opened.
+    lookups: LookupCounters,
+    /// Sender of change notifications.
+    events: broadcast::Sender<CacheEvent>,
 }
 
 impl CacheManager {
@@ -49,43 +145,10 @@ impl CacheManager {
             .map_err(|e| Error::Cache(format!("Failed to create cache directory: {e}")))?;
 
         let db_path = cache_dir.join("cache.db");
-        let db = Connection::open(&db_path)
-            .map_err(|e| Error::Cache(format!("Failed to open database: {e}")))?;
+        let mut db = pool::open(&db_path, false)?;
 
-        // Initialize database schema
-        db.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS audio_cache (
-                id TEXT PRIMARY KEY,
-                video_id TEXT NOT NULL,
-                format TEXT NOT NULL,
-                quality TEXT NOT NULL,
-                file_path TEXT NOT NULL,
-                size_bytes INTEGER NOT NULL,
-                duration_secs REAL,
-                cached_at TEXT NOT NULL,
-                last_accessed TEXT NOT NULL
-            );
-
-            CREATE TABLE IF NOT EXISTS metadata_cache (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL,
-                cached_at TEXT NOT NULL,
-                expires_at TEXT
-            );
-
-            CREATE TABLE IF NOT EXISTS thumbnail_cache (
-                url_hash TEXT PRIMARY KEY,
-                url TEXT NOT NULL,
-                file_path TEXT NOT NULL,
-                cached_at TEXT NOT NULL
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_audio_video_id ON audio_cache(video_id);
-            CREATE INDEX IF NOT EXISTS idx_metadata_expires ON metadata_cache(expires_at);
-            ",
-        )
-        .map_err(|e| Error::Cache(format!("Failed to initialize database: {e}")))?;
+        migrations::migrate(&mut db)?;
+        let readers = ReadPool::open(&db_path, READ_CONNECTIONS)?;
 
         info!("Cache initialized at {}", cache_dir.display());
 
@@ -95,11 +158,25 @@ impl CacheManager {
 
         Ok(Self {
             db: Arc::new(Mutex::new(db)),
+            readers: Arc::new(readers),
             cache_dir,
             memory_cache: Arc::new(Mutex::new(LruCache::new(cache_size))),
+            http: reqwest::Client::new(),
+            size_limit: Mutex::new(None),
+            auto_compaction: Mutex::new(None),
+            encryption: Mutex::new(None),
+            lookups: LookupCounters::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
         })
     }
 
+    /// Download thumbnails with `client`, e.g. one configured with a proxy.
+    #[must_use]
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http = client;
+        self
+    }
+
     /// Get the cache directory path.
     pub const fn cache_dir(&self) -> &PathBuf {
         &self.cache_dir
@@ -107,7 +184,7 @@ impl CacheManager {
 
     /// Check if audio is cached for a video.
     pub fn has_audio(&self, video_id: &str) -> bool {
-        let db = self.db.lock();
+        let db = self.readers.get();
         db.query_row(
             "SELECT 1 FROM audio_cache WHERE video_id = ? LIMIT 1",
             [video_id],
@@ -116,16 +193,120 @@ impl CacheManager {
         .is_ok()
     }
 
-    /// Get the file path for cached audio.
-    pub fn get_audio_path(&self, video_id: &str) -> Option<PathBuf> {
+    /// Register cached audio, replacing any earlier entry for the video.
+    ///
+    /// The cache time is kept when an entry is updated.
+    pub fn register_audio(&self, audio: &AudioRecord) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
         let db = self.db.lock();
-        db.query_row(
-            "SELECT file_path FROM audio_cache WHERE video_id = ? ORDER BY quality DESC LIMIT 1",
-            [video_id],
-            |row| row.get::<_, String>(0),
+        db.execute(
+            "INSERT INTO audio_cache
+                (id, video_id, format, quality, file_path, size_bytes, duration_secs, cached_at, last_accessed)
+             VALUES (?1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+             ON CONFLICT(id) DO UPDATE SET
+                format = excluded.format,
+                quality = excluded.quality,
+                file_path = excluded.file_path,
+                size_bytes = excluded.size_bytes,
+                duration_secs = excluded.duration_secs,
+                sha256 = NULL",
+            rusqlite::params![
+                audio.video_id,
+                audio.format,
+                audio.quality,
+                audio.file_path.to_string_lossy(),
+                i64::try_from(audio.size_bytes).unwrap_or(i64::MAX),
+                audio.duration_secs,
+                now
+            ],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to register audio: {e}")))?;
+        drop(db);
+        self.notify(CacheEvent::AudioAdded {
+            video_id: audio.video_id.clone(),
+            size_bytes: audio.size_bytes,
+        });
+        self.enforce_limits_after_write();
+        Ok(())
+    }
+
+    /// Mark cached audio as recently played.
+    pub fn touch_audio(&self, video_id: &str) -> Result<()> {
+        let db = self.db.lock();
+        db.execute(
+            "UPDATE audio_cache SET last_accessed = ? WHERE video_id = ?",
+            rusqlite::params![Utc::now().to_rfc3339(), video_id],
         )
-        .ok()
-        .map(PathBuf::from)
+        .map_err(|e| Error::Cache(format!("Failed to update audio access time: {e}")))?;
+        Ok(())
+    }
+
+    /// Remove the entry for cached audio (the file itself is not touched).
+    pub fn remove_audio(&self, video_id: &str) -> Result<()> {
+        let db = self.db.lock();
+        db.execute("DELETE FROM audio_cache WHERE video_id = ?", [video_id])
+            .map_err(|e| Error::Cache(format!("Failed to remove audio: {e}")))?;
+        Ok(())
+    }
+
+    /// Get the video IDs of all registered audio.
+    pub fn audio_video_ids(&self) -> Result<Vec<String>> {
+        let db = self.readers.get();
+        let mut stmt = db
+            .prepare("SELECT video_id FROM audio_cache")
+            .map_err(|e| Error::Cache(format!("Failed to query audio cache: {e}")))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| Error::Cache(format!("Failed to query audio cache: {e}")))?;
+        rows.collect::<std::result::Result<_, _>>()
+            .map_err(|e| Error::Cache(format!("Failed to read audio cache: {e}")))
+    }
+
+    /// Get the video IDs and files of all registered audio.
+    pub fn audio_files(&self) -> Result<Vec<(String, PathBuf)>> {
+        let db = self.readers.get();
+        let mut stmt = db
+            .prepare("SELECT video_id, file_path FROM audio_cache")
+            .map_err(|e| Error::Cache(format!("Failed to query audio cache: {e}")))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+            })
+            .map_err(|e| Error::Cache(format!("Failed to query audio cache: {e}")))?;
+        rows.collect::<std::result::Result<_, _>>()
+            .map_err(|e| Error::Cache(format!("Failed to read audio cache: {e}")))
+    }
+
+    /// Remove all audio entries.
+    pub fn clear_audio(&self) -> Result<()> {
+        let db = self.db.lock();
+        db.execute("DELETE FROM audio_cache", [])
+            .map_err(|e| Error::Cache(format!("Failed to clear audio cache: {e}")))?;
+        drop(db);
+        self.notify(CacheEvent::Cleared);
+        Ok(())
+    }
+
+    /// Get the file path for cached audio, marking it as recently played.
+    ///
+    /// The file may be encrypted; [`CacheManager::read_audio`] decrypts it.
+    pub fn get_audio_path(&self, video_id: &str) -> Option<PathBuf> {
+        let path = {
+            let db = self.readers.get();
+            db.query_row(
+                "SELECT file_path FROM audio_cache WHERE video_id = ? ORDER BY quality DESC LIMIT 1",
+                [video_id],
+                |row| row.get::<_, String>(0),
+            )
+            .ok()
+            .map(PathBuf::from)
+        };
+        self.lookups.record(path.is_some());
+        let path = path?;
+        if let Err(e) = self.touch_audio(video_id) {
+            warn!("Failed to update access time of {video_id}: {e}");
+        }
+        Some(path)
     }
 
     /// Store metadata in the cache.
@@ -150,7 +331,14 @@ impl CacheManager {
 
     /// Get metadata from the cache.
     pub fn get_metadata(&self, key: &str) -> Option<String> {
-        let db = self.db.lock();
+        let value = self.lookup_metadata(key);
+        self.lookups.record(value.is_some());
+        value
+    }
+
+    /// Get unexpired metadata.
+    fn lookup_metadata(&self, key: &str) -> Option<String> {
+        let db = self.readers.get();
         let result: rusqlite::Result<(String, Option<String>)> = db.query_row(
             "SELECT value, expires_at FROM metadata_cache WHERE key = ?",
             [key],
@@ -173,8 +361,118 @@ impl CacheManager {
         }
     }
 
+    /// Record a play of a track in the history.
+    ///
+    /// Artist and album play counts are updated by database triggers.
+    pub fn record_play(&self, track: &Track) -> Result<()> {
+        self.record_play_at(track, Utc::now())
+    }
+
+    /// Record a play of a track at a given time, such as from imported history.
+    pub fn record_play_at(&self, track: &Track, played_at: DateTime<Utc>) -> Result<()> {
+        let artist = Some(track.artist_name()).filter(|name| !name.is_empty());
+
+        let db = self.db.lock();
+        db.execute(
+            "INSERT INTO play_history (video_id, title, artist, album, played_at) VALUES (?, ?, ?, ?, ?)",
+            rusqlite::params![
+                track.id,
+                track.title,
+                artist,
+                track.album_name(),
+                played_at.to_rfc3339()
+            ],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to record play: {e}")))?;
+        drop(db);
+
+        if let Err(e) = self.index_track(track) {
+            warn!("{e}");
+        }
+        Ok(())
+    }
+
+    /// Get the number of plays for an artist (case-insensitive).
+    pub fn artist_play_count(&self, artist: &str) -> u64 {
+        self.play_count(
+            "SELECT play_count FROM artist_play_counts WHERE artist = ?",
+            [artist],
+        )
+    }
+
+    /// Get the number of plays for an album by an artist (case-insensitive),
+    /// so that albums sharing a title are counted apart.
+    pub fn album_play_count(&self, artist: &str, album: &str) -> u64 {
+        self.play_count(
+            "SELECT play_count FROM album_play_counts WHERE artist = ? AND album = ?",
+            [artist, album],
+        )
+    }
+
+    fn play_count(&self, sql: &str, keys: impl rusqlite::Params) -> u64 {
+        let db = self.readers.get();
+        let count: i64 = db.query_row(sql, keys, |row| row.get(0)).unwrap_or(0);
+
+        #[allow(clippy::cast_sign_loss)]
+        {
+            count.max(0) as u64
+        }
+    }
+
+    /// Get the most recent plays, newest first.
+    pub fn recent_plays(&self, limit: usize) -> Result<Vec<PlayRecord>> {
+        let db = self.readers.get();
+        let mut stmt = db
+            .prepare(
+                "SELECT video_id, title, artist, album, played_at FROM play_history
+                 ORDER BY played_at DESC, id DESC LIMIT ?",
+            )
+            .map_err(|e| Error::Cache(format!("Failed to query play history: {e}")))?;
+
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = stmt
+            .query_map([limit], |row| {
+                let played_at: String = row.get(4)?;
+                Ok(PlayRecord {
+                    video_id: row.get(0)?,
+                    title: row.get(1)?,
+                    artist: row.get(2)?,
+                    album: row.get(3)?,
+                    played_at: DateTime::parse_from_rfc3339(&played_at)
+                        .map_or_else(|_| Utc::now(), |t| t.with_timezone(&Utc)),
+                })
+            })
+            .map_err(|e| Error::Cache(format!("Failed to query play history: {e}")))?;
+
+        rows.collect::<std::result::Result<_, _>>()
+            .map_err(|e| Error::Cache(format!("Failed to read play history: {e}")))
+    }
+
+    /// Build an offline mix continuing from `seed`, using only local metadata.
+    ///
+    /// `is_available` decides which tracks can be played (e.g. are cached).
+    pub fn instant_mix(
+        &self,
+        seed: &Track,
+        limit: usize,
+        is_available: impl Fn(&str) -> bool,
+    ) -> Result<Vec<Track>> {
+        let history = self.recent_plays(MIX_HISTORY_LIMIT)?;
+        let rng_seed = Utc::now().timestamp_subsec_nanos().into();
+        Ok(instant_mix(seed, &history, is_available, limit, rng_seed))
+    }
+
+    /// Clear the play history and all play counts.
+    pub fn clear_history(&self) -> Result<()> {
+        let db = self.db.lock();
+        db.execute("DELETE FROM play_history", [])
+            .map_err(|e| Error::Cache(format!("Failed to clear play history: {e}")))?;
+
+        info!("Play history cleared");
+        Ok(())
+    }
+
     /// Generate a hash for a URL.
-    #[allow(dead_code)] // Will be used by thumbnail caching
     fn hash_url(url: &str) -> String {
         let mut hasher = Sha256::new();
         hasher.update(url.as_bytes());
@@ -183,7 +481,7 @@ impl CacheManager {
 
     /// Get cache statistics.
     pub fn stats(&self) -> CacheStats {
-        let db = self.db.lock();
+        let db = self.readers.get();
 
         let audio_count: i64 = db
             .query_row("SELECT COUNT(*) FROM audio_cache", [], |row| row.get(0))
@@ -205,12 +503,26 @@ impl CacheManager {
             .query_row("SELECT COUNT(*) FROM thumbnail_cache", [], |row| row.get(0))
             .unwrap_or(0);
 
+        let (pinned_bytes, evictable_bytes, thumbnail_size_bytes) =
+            limits::usage(&db).unwrap_or_default();
+        let (oldest_cached_at, newest_cached_at) = stats::cached_at_range(&db);
+        let (hits, misses) = self.lookups.get();
+
         #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
         CacheStats {
             audio_count: audio_count as usize,
             audio_size_bytes: audio_size as u64,
             metadata_count: metadata_count as usize,
             thumbnail_count: thumbnail_count as usize,
+            thumbnail_size_bytes,
+            pinned_bytes,
+            evictable_bytes,
+            by_format: stats::breakdown(&db, "format"),
+            by_quality: stats::breakdown(&db, "quality"),
+            oldest_cached_at,
+            newest_cached_at,
+            hits,
+            misses,
         }
     }
 
@@ -226,8 +538,10 @@ impl CacheManager {
         )
         .map_err(|e| Error::Cache(format!("Failed to clear cache: {e}")))?;
 
-        // Clear memory cache
+        // Clear memory cache and thumbnail files
         self.memory_cache.lock().clear();
+        let _ = std::fs::remove_dir_all(self.thumbnail_dir());
+        self.notify(CacheEvent::Cleared);
 
         info!("Cache cleared");
         Ok(())
@@ -243,6 +557,38 @@ impl Default for CacheManager {
     }
 }
 
+/// A single entry in the play history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayRecord {
+    /// `YouTube` video ID.
+    pub video_id: String,
+    /// Track title.
+    pub title: String,
+    /// Artist name (if known).
+    pub artist: Option<String>,
+    /// Album name (if known).
+    pub album: Option<String>,
+    /// When the track was played.
+    pub played_at: DateTime<Utc>,
+}
+
+/// Audio cached on disk, as registered in the `audio_cache` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioRecord {
+    /// `YouTube` video ID.
+    pub video_id: String,
+    /// MIME type of the audio, such as `audio/mp4`.
+    pub format: String,
+    /// Quality label, such as `128kbps`.
+    pub quality: String,
+    /// Path of the audio file.
+    pub file_path: PathBuf,
+    /// Size of the audio in bytes.
+    pub size_bytes: u64,
+    /// Duration of the audio in seconds (if known).
+    pub duration_secs: Option<f64>,
+}
+
 /// Cache statistics.
 #[derive(Debug, Clone)]
 pub struct CacheStats {
@@ -254,6 +600,24 @@ pub struct CacheStats {
     pub metadata_count: usize,
     /// Number of cached thumbnails.
     pub thumbnail_count: usize,
+    /// Total size of cached thumbnails in bytes.
+    pub thumbnail_size_bytes: u64,
+    /// Bytes of audio and thumbnails kept regardless of the size limit.
+    pub pinned_bytes: u64,
+    /// Bytes of audio and thumbnails the size limit may evict.
+    pub evictable_bytes: u64,
+    /// Cached audio by format, largest first.
+    pub by_format: Vec<StorageBreakdown>,
+    /// Cached audio by quality, largest first.
+    pub by_quality: Vec<StorageBreakdown>,
+    /// When the oldest cached audio was stored.
+    pub oldest_cached_at: Option<DateTime<Utc>>,
+    /// When the newest cached audio was stored.
+    pub newest_cached_at: Option<DateTime<Utc>>,
+    /// Lookups served from the cache since it was opened.
+    pub hits: u64,
+    /// Lookups that found nothing cached since it was opened.
+    pub misses: u64,
 }
 
 impl CacheStats {
@@ -262,6 +626,18 @@ impl CacheStats {
     pub fn audio_size_mb(&self) -> f64 {
         self.audio_size_bytes as f64 / (1024.0 * 1024.0)
     }
+
+    /// Get the total size of cached audio and thumbnails in bytes.
+    pub const fn total_size_bytes(&self) -> u64 {
+        self.audio_size_bytes + self.thumbnail_size_bytes
+    }
+
+    /// Get the share of lookups served from the cache, if there were any.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
 }
 
 #[cfg(test)]
@@ -275,4 +651,70 @@ mod tests {
         assert_ne!(hash1, hash2);
         assert_eq!(hash1.len(), 64); // SHA256 hex
     }
+
+    fn test_track(id: &str, artist: &str, album: Option<&str>) -> Track {
+        let mut track = Track::new(id, "Song");
+        track.artists.push(monad_core::TrackArtist::new(artist));
+        track.album = album.map(monad_core::TrackAlbum::new);
+        track
+    }
+
+    #[test]
+    fn test_play_counts_follow_history() -> Result<()> {
+        let dir = tempfile::tempdir().map_err(|e| Error::Cache(e.to_string()))?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+
+        cache.record_play(&test_track("a", "Artist", Some("Album")))?;
+        cache.record_play(&test_track("b", "artist", Some("Album")))?;
+        cache.record_play(&test_track("c", "Other", None))?;
+        cache.record_play(&test_track("d", "Other", Some("album")))?;
+
+        assert_eq!(cache.artist_play_count("ARTIST"), 2);
+        assert_eq!(cache.artist_play_count("Other"), 2);
+        // Albums of the same title by other artists are counted apart
+        assert_eq!(cache.album_play_count("Artist", "Album"), 2);
+        assert_eq!(cache.album_play_count("Other", "ALBUM"), 1);
+        assert_eq!(cache.album_play_count("Artist", "Unknown"), 0);
+
+        let recent = cache.recent_plays(2)?;
+        let ids: Vec<&str> = recent.iter().map(|r| r.video_id.as_str()).collect();
+        assert_eq!(ids, ["d", "c"]);
+        assert_eq!(recent[1].album, None);
+
+        cache.clear_history()?;
+        assert!(cache.recent_plays(10)?.is_empty());
+        assert_eq!(cache.artist_play_count("Artist"), 0);
+        assert_eq!(cache.album_play_count("Artist", "Album"), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_registered_audio() -> Result<()> {
+        let dir = tempfile::tempdir().map_err(|e| Error::Cache(e.to_string()))?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let mut audio = AudioRecord {
+            video_id: "abc".to_string(),
+            format: "audio/mp4".to_string(),
+            quality: "unknown".to_string(),
+            file_path: dir.path().join("abc.audio"),
+            size_bytes: 1000,
+            duration_secs: None,
+        };
+        cache.register_audio(&audio)?;
+        audio.duration_secs = Some(8.0);
+        audio.quality = "1kbps".to_string();
+        cache.register_audio(&audio)?;
+
+        assert!(cache.has_audio("abc"));
+        assert_eq!(cache.get_audio_path("abc"), Some(audio.file_path));
+        assert_eq!(cache.audio_video_ids()?, ["abc"]);
+        let stats = cache.stats();
+        assert_eq!((stats.audio_count, stats.audio_size_bytes), (1, 1000));
+
+        cache.touch_audio("abc")?;
+        cache.remove_audio("abc")?;
+        assert!(!cache.has_audio("abc"));
+        assert_eq!(cache.stats().audio_count, 0);
+        Ok(())
+    }
 }
@@ -0,0 +1,331 @@
+//! Size limit on cached audio and thumbnails.
+//!
+//! When a limit is set, every write is followed by an eviction pass that
+//! deletes the least recently accessed audio and thumbnails until the cache
+//! fits again. Pinned audio and artwork, such as that of albums made
+//! available offline, is never evicted, even if it alone exceeds the limit.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Result};
+use rusqlite::{params, Connection};
+use tracing::{info, warn};
+
+use crate::events::{CacheEvent, CacheItem};
+use crate::CacheManager;
+
+/// A cached file that can be evicted.
+struct Entry {
+    /// Whether this is audio rather than a thumbnail.
+    audio: bool,
+    /// Video ID or URL hash.
+    key: String,
+    path: PathBuf,
+    size_bytes: u64,
+    last_accessed: Option<DateTime<Utc>>,
+    pinned: bool,
+}
+
+/// What an eviction pass removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Eviction {
+    /// Number of audio files and thumbnails deleted.
+    pub evicted: usize,
+    /// Bytes freed.
+    pub freed_bytes: u64,
+}
+
+/// Parse a timestamp stored by the cache.
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Get the video IDs and thumbnail URL hashes kept by pins.
+fn pinned(db: &Connection) -> rusqlite::Result<(HashSet<String>, HashSet<String>)> {
+    let rows: Vec<(String, String)> = db
+        .prepare("SELECT track_ids, thumbnail_urls FROM pins")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let (mut video_ids, mut url_hashes) = (HashSet::new(), HashSet::new());
+    for (track_ids, thumbnail_urls) in rows {
+        video_ids.extend(serde_json::from_str::<Vec<String>>(&track_ids).unwrap_or_default());
+        let urls = serde_json::from_str::<Vec<String>>(&thumbnail_urls).unwrap_or_default();
+        url_hashes.extend(urls.iter().map(|url| CacheManager::hash_url(url)));
+    }
+    Ok((video_ids, url_hashes))
+}
+
+/// Get the video IDs of audio pinned itself or kept by a pin.
+fn pinned_audio(db: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let (mut video_ids, _) = pinned(db)?;
+    let direct: Vec<String> = db
+        .prepare("SELECT video_id FROM audio_cache WHERE pinned")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    video_ids.extend(direct);
+    Ok(video_ids)
+}
+
+/// List every cached audio file and thumbnail.
+fn entries(db: &Connection) -> rusqlite::Result<Vec<Entry>> {
+    let (pinned_videos, pinned_thumbnails) = pinned(db)?;
+    let mut entries: Vec<Entry> = db
+        .prepare("SELECT video_id, file_path, size_bytes, last_accessed, pinned FROM audio_cache")?
+        .query_map([], |row| {
+            let key: String = row.get(0)?;
+            Ok(Entry {
+                audio: true,
+                path: PathBuf::from(row.get::<_, String>(1)?),
+                size_bytes: u64::try_from(row.get::<_, i64>(2)?).unwrap_or(0),
+                last_accessed: parse_time(&row.get::<_, String>(3)?),
+                pinned: row.get::<_, bool>(4)? || pinned_videos.contains(&key),
+                key,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let thumbnails = db
+        .prepare(
+            "SELECT url_hash, file_path, COALESCE(last_accessed, cached_at) FROM thumbnail_cache",
+        )?
+        .query_map([], |row| {
+            let key: String = row.get(0)?;
+            let path = PathBuf::from(row.get::<_, String>(1)?);
+            Ok(Entry {
+                audio: false,
+                size_bytes: fs::metadata(&path).map_or(0, |m| m.len()),
+                path,
+                last_accessed: parse_time(&row.get::<_, String>(2)?),
+                pinned: pinned_thumbnails.contains(&key),
+                key,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    entries.extend(thumbnails);
+    Ok(entries)
+}
+
+/// Delete the files kept beside stored audio under its name, such as the
+/// extractor's metadata and checksums.
+fn remove_sidecars(path: &Path) {
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return;
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Get the bytes of pinned and of evictable files, and of thumbnails alone.
+pub fn usage(db: &Connection) -> rusqlite::Result<(u64, u64, u64)> {
+    let (mut pinned, mut evictable, mut thumbnails) = (0, 0, 0);
+    for entry in entries(db)? {
+        if entry.pinned {
+            pinned += entry.size_bytes;
+        } else {
+            evictable += entry.size_bytes;
+        }
+        if !entry.audio {
+            thumbnails += entry.size_bytes;
+        }
+    }
+    Ok((pinned, evictable, thumbnails))
+}
+
+impl CacheManager {
+    /// Limit cached audio and thumbnails to `max_bytes` in total.
+    #[must_use]
+    pub fn with_size_limit(self, max_bytes: u64) -> Self {
+        self.set_size_limit(Some(max_bytes));
+        self
+    }
+
+    /// Change the size limit (`None` for no limit), evicting on the next write.
+    pub fn set_size_limit(&self, max_bytes: Option<u64>) {
+        *self.size_limit.lock() = max_bytes;
+    }
+
+    /// Get the size limit, if any.
+    pub fn size_limit(&self) -> Option<u64> {
+        *self.size_limit.lock()
+    }
+
+    /// Keep or stop keeping cached audio regardless of the size limit.
+    pub fn pin_audio(&self, video_id: &str, pinned: bool) -> Result<()> {
+        let db = self.db.lock();
+        db.execute(
+            "UPDATE audio_cache SET pinned = ? WHERE video_id = ?",
+            params![pinned, video_id],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to pin audio: {e}")))?;
+        Ok(())
+    }
+
+    /// Check whether cached audio is pinned.
+    pub fn is_audio_pinned(&self, video_id: &str) -> bool {
+        let db = self.readers.get();
+        db.query_row(
+            "SELECT pinned FROM audio_cache WHERE video_id = ?",
+            [video_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(false)
+    }
+
+    /// Get the video IDs of cached audio kept regardless of the size limit,
+    /// whether pinned itself or kept by a pin.
+    pub fn pinned_audio(&self) -> Result<HashSet<String>> {
+        let db = self.readers.get();
+        pinned_audio(&db).map_err(|e| Error::Cache(format!("Failed to list pinned audio: {e}")))
+    }
+
+    /// Delete the least recently accessed audio and thumbnails until the cache fits its limit.
+    ///
+    /// Runs after every write; does nothing without a limit. Audio registered
+    /// from outside [`CacheManager::audio_dir`] counts toward the limit but is
+    /// left to whoever wrote it, like pinned audio.
+    pub fn enforce_limits(&self) -> Result<Eviction> {
+        let Some(max_bytes) = self.size_limit() else {
+            return Ok(Eviction::default());
+        };
+        let db = self.db.lock();
+        let mut entries =
+            entries(&db).map_err(|e| Error::Cache(format!("Failed to list cache entries: {e}")))?;
+        let mut total: u64 = entries.iter().map(|entry| entry.size_bytes).sum();
+        if total <= max_bytes {
+            return Ok(Eviction::default());
+        }
+
+        let audio_dir = self.audio_dir();
+        entries
+            .retain(|entry| !entry.pinned && (!entry.audio || entry.path.starts_with(&audio_dir)));
+        entries.sort_by_key(|entry| entry.last_accessed);
+        let mut eviction = Eviction::default();
+        for entry in entries {
+            if total <= max_bytes {
+                break;
+            }
+            if let Err(e) = fs::remove_file(&entry.path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to delete {}: {e}", entry.path.display());
+                    continue;
+                }
+            }
+            let result = if entry.audio {
+                remove_sidecars(&entry.path);
+                db.execute("DELETE FROM audio_cache WHERE video_id = ?", [&entry.key])
+            } else {
+                self.memory_cache
+                    .lock()
+                    .pop(&crate::thumbnails::memory_key(&entry.key));
+                db.execute(
+                    "DELETE FROM thumbnail_cache WHERE url_hash = ?",
+                    [&entry.key],
+                )
+            };
+            result.map_err(|e| Error::Cache(format!("Failed to evict cache entry: {e}")))?;
+            let item = if entry.audio {
+                CacheItem::Audio {
+                    video_id: entry.key,
+                }
+            } else {
+                CacheItem::Thumbnail {
+                    url_hash: entry.key,
+                }
+            };
+            self.notify(CacheEvent::Evicted {
+                item,
+                freed_bytes: entry.size_bytes,
+            });
+            total -= entry.size_bytes;
+            eviction.evicted += 1;
+            eviction.freed_bytes += entry.size_bytes;
+        }
+
+        drop(db);
+
+        if total > max_bytes {
+            warn!("Pinned audio alone exceeds the cache limit of {max_bytes} bytes");
+        }
+        info!(
+            "Evicted {} cache entries, freeing {} bytes",
+            eviction.evicted, eviction.freed_bytes
+        );
+        self.auto_compact();
+        Ok(eviction)
+    }
+
+    /// Enforce the size limit after a write, logging any failure.
+    pub(crate) fn enforce_limits_after_write(&self) {
+        if let Err(e) = self.enforce_limits() {
+            warn!("Failed to enforce the cache size limit: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_accessed_are_evicted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let external = dir.path().join("external.audio");
+        fs::write(&external, [])?;
+        cache.register_audio(&crate::AudioRecord {
+            video_id: "external".to_string(),
+            format: "audio/mp4".to_string(),
+            quality: "128kbps".to_string(),
+            file_path: external.clone(),
+            size_bytes: 0,
+            duration_secs: None,
+        })?;
+        cache.store_audio("old", "audio/mp4", "128kbps", &[0; 100], None)?;
+        cache.store_audio("pinned", "audio/mp4", "128kbps", &[0; 100], None)?;
+        cache.pin_audio("pinned", true)?;
+        cache.store_audio("new", "audio/mp4", "128kbps", &[0; 100], None)?;
+        cache.store_thumbnail("https://example.com/a.jpg", &[0; 50])?;
+        let sidecar = cache.audio_dir().join("new.json");
+        fs::write(&sidecar, [])?;
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cache.get_audio_path("old");
+        assert_eq!(cache.enforce_limits()?, Eviction::default());
+
+        // Reading the oldest audio keeps it over the newer entries
+        cache.set_size_limit(Some(200));
+        let eviction = cache.enforce_limits()?;
+        assert_eq!(eviction.freed_bytes, 150);
+        assert!(cache.has_audio("old"));
+        assert!(cache.has_audio("pinned"));
+        assert!(!cache.has_audio("new") && !sidecar.exists());
+        assert_eq!(cache.thumbnail_path("https://example.com/a.jpg"), None);
+        assert!(cache.is_audio_pinned("pinned"));
+        assert_eq!(cache.pinned_audio()?, HashSet::from(["pinned".to_string()]));
+        // Audio stored elsewhere is not the cache's to delete
+        assert!(cache.has_audio("external") && external.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_writes_enforce_limit() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?.with_size_limit(150);
+        let first = cache.store_audio("a", "audio/mp4", "128kbps", &[0; 100], None)?;
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cache.store_audio("b", "audio/mp4", "128kbps", &[0; 100], None)?;
+        assert!(!cache.has_audio("a"));
+        assert!(!first.exists());
+        assert!(cache.has_audio("b"));
+        assert_eq!(cache.stats().audio_size_bytes, 100);
+        Ok(())
+    }
+}
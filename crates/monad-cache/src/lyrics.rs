@@ -0,0 +1,112 @@
+//! Lyrics of played tracks.
+//!
+//! Lyrics are kept in the metadata cache under the track's video ID and
+//! under its artist, title and duration, so another upload of the same song
+//! finds them too. They rarely change, so they are kept for a long time and
+//! replaying a track, or playing it offline, needs no lyrics request. Timing
+//! offsets set for a track are kept for good.
+
+use monad_core::{Result, Track};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::CacheManager;
+
+/// How long cached lyrics are kept, in seconds.
+pub const LYRICS_TTL_SECS: i64 = 90 * 24 * 60 * 60;
+
+/// Key of a track's lyrics by video ID.
+fn video_key(track: &Track) -> String {
+    format!("lyrics:video:{}", track.id)
+}
+
+/// Key of a track's lyrics by artist, title and duration.
+fn song_key(track: &Track) -> String {
+    format!(
+        "lyrics:song:{}:{}:{}",
+        track.artist_name().to_lowercase(),
+        track.title.to_lowercase(),
+        track.duration.0
+    )
+}
+
+/// Key of a track's lyrics timing offset.
+fn offset_key(video_id: &str) -> String {
+    format!("lyrics:offset:{video_id}")
+}
+
+impl CacheManager {
+    /// Store the lyrics of a track.
+    pub fn cache_lyrics<T: Serialize>(&self, track: &Track, lyrics: &T) -> Result<()> {
+        let value = serde_json::to_string(lyrics)?;
+        self.set_metadata(&video_key(track), &value, Some(LYRICS_TTL_SECS))?;
+        self.set_metadata(&song_key(track), &value, Some(LYRICS_TTL_SECS))
+    }
+
+    /// Get the cached lyrics of a track, by video ID or else by song.
+    pub fn cached_lyrics<T: DeserializeOwned>(&self, track: &Track) -> Option<T> {
+        let value = self
+            .get_metadata(&video_key(track))
+            .or_else(|| self.get_metadata(&song_key(track)))?;
+        serde_json::from_str(&value)
+            .map_err(|e| warn!("Ignoring invalid cached lyrics for {}: {e}", track.id))
+            .ok()
+    }
+
+    /// Get the lyrics timing offset of a track in milliseconds, or 0 if never set.
+    pub fn lyrics_offset_ms(&self, video_id: &str) -> i64 {
+        self.get_metadata(&offset_key(video_id))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Set the lyrics timing offset of a track in milliseconds.
+    pub fn set_lyrics_offset_ms(&self, video_id: &str, offset_ms: i64) -> Result<()> {
+        self.set_metadata(&offset_key(video_id), &offset_ms.to_string(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::{Duration, TrackArtist};
+
+    fn track(id: &str) -> Track {
+        let mut track = Track::new(id, "Song");
+        track.artists.push(TrackArtist::new("Artist"));
+        track.duration = Duration::from_seconds(200);
+        track
+    }
+
+    #[test]
+    fn test_lyrics_found_by_video_or_song() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert_eq!(cache.cached_lyrics::<Vec<String>>(&track("a")), None);
+
+        let lyrics = vec!["First line".to_string(), "Second line".to_string()];
+        cache.cache_lyrics(&track("a"), &lyrics)?;
+        assert_eq!(cache.cached_lyrics(&track("a")), Some(lyrics.clone()));
+
+        // Another upload of the same song shares its lyrics
+        assert_eq!(cache.cached_lyrics(&track("b")), Some(lyrics));
+
+        let mut longer = track("c");
+        longer.duration = Duration::from_seconds(260);
+        assert_eq!(cache.cached_lyrics::<Vec<String>>(&longer), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lyrics_offset_persists() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert_eq!(cache.lyrics_offset_ms("a"), 0);
+
+        cache.set_lyrics_offset_ms("a", -750)?;
+        assert_eq!(cache.lyrics_offset_ms("a"), -750);
+        assert_eq!(cache.lyrics_offset_ms("b"), 0);
+        Ok(())
+    }
+}
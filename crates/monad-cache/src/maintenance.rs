@@ -0,0 +1,239 @@
+//! Periodic cleanup of the cache.
+//!
+//! Expired metadata is only hidden when read, and files or rows can be left
+//! behind by crashes and manual deletion. A maintenance pass deletes expired
+//! metadata, files this cache wrote that no row refers to, and rows whose
+//! file is gone, then compacts the cache if auto-compaction is due.
+//! [`CacheManager::spawn_maintenance`] runs it on an interval.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
+
+use chrono::Utc;
+use monad_core::{Error, Result};
+use rusqlite::Connection;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::CacheManager;
+
+/// Files changed more recently than this may still be being written.
+const ORPHAN_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// What a maintenance pass reclaimed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Expired metadata entries deleted.
+    pub expired_metadata: usize,
+    /// Files deleted because no entry referred to them.
+    pub orphaned_files: usize,
+    /// Entries deleted because their file was gone.
+    pub missing_files: usize,
+    /// Bytes freed by deleting orphaned files.
+    pub freed_bytes: u64,
+}
+
+impl MaintenanceReport {
+    /// Check whether the pass found nothing to clean up.
+    pub const fn is_empty(&self) -> bool {
+        self.expired_metadata == 0 && self.orphaned_files == 0 && self.missing_files == 0
+    }
+}
+
+/// Get the file paths referred to by a column.
+fn referenced_paths(db: &Connection, sql: &str) -> rusqlite::Result<Vec<String>> {
+    db.prepare(sql)?.query_map([], |row| row.get(0))?.collect()
+}
+
+/// Check whether a file in the audio directory was written by this cache.
+fn is_stored_audio(path: &Path) -> bool {
+    let is_audio = |path: &Path| path.extension().is_some_and(|ext| ext == "audio");
+    is_audio(path)
+        || (path.extension().is_some_and(|ext| ext == "part")
+            && path
+                .file_stem()
+                .is_some_and(|stem| is_audio(Path::new(stem))))
+}
+
+/// Delete the files in `dir` accepted by `owned` that are not in `referenced`.
+fn delete_orphans(
+    dir: &Path,
+    owned: impl Fn(&Path) -> bool,
+    referenced: &HashSet<PathBuf>,
+    report: &mut MaintenanceReport,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let recent = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map_or(true, |age| age < ORPHAN_GRACE);
+        if !metadata.is_file() || recent || !owned(&path) || referenced.contains(&path) {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                debug!("Deleted orphaned cache file {}", path.display());
+                report.orphaned_files += 1;
+                report.freed_bytes += metadata.len();
+            }
+            Err(e) => warn!("Failed to delete {}: {e}", path.display()),
+        }
+    }
+}
+
+impl CacheManager {
+    /// Delete expired metadata, orphaned files and entries whose file is gone.
+    pub fn run_maintenance(&self) -> Result<MaintenanceReport> {
+        let mut report = MaintenanceReport::default();
+        let db = self.db.lock();
+        let map_err = |e: rusqlite::Error| Error::Cache(format!("Cache maintenance failed: {e}"));
+
+        report.expired_metadata = db
+            .execute(
+                "DELETE FROM metadata_cache WHERE expires_at IS NOT NULL AND expires_at < ?",
+                [Utc::now().to_rfc3339()],
+            )
+            .map_err(map_err)?;
+
+        let audio = referenced_paths(&db, "SELECT file_path FROM audio_cache").map_err(map_err)?;
+        let thumbnails =
+            referenced_paths(&db, "SELECT file_path FROM thumbnail_cache").map_err(map_err)?;
+        for (table, paths) in [("audio_cache", &audio), ("thumbnail_cache", &thumbnails)] {
+            for path in paths.iter().filter(|path| !Path::new(path).exists()) {
+                report.missing_files += db
+                    .execute(&format!("DELETE FROM {table} WHERE file_path = ?"), [path])
+                    .map_err(map_err)?;
+            }
+        }
+        drop(db);
+
+        let audio: HashSet<PathBuf> = audio.into_iter().map(PathBuf::from).collect();
+        delete_orphans(&self.audio_dir(), is_stored_audio, &audio, &mut report);
+        let thumbnails: HashSet<PathBuf> = thumbnails.into_iter().map(PathBuf::from).collect();
+        delete_orphans(&self.thumbnail_dir(), |_| true, &thumbnails, &mut report);
+
+        if report.is_empty() {
+            debug!("Cache maintenance found nothing to clean up");
+        } else {
+            info!(
+                "Cache maintenance deleted {} expired metadata entries, {} orphaned files ({} bytes) and {} entries without files",
+                report.expired_metadata, report.orphaned_files, report.freed_bytes, report.missing_files
+            );
+        }
+        self.auto_compact();
+        Ok(report)
+    }
+
+    /// Run maintenance every `interval`, starting now, until the cache is dropped.
+    ///
+    /// Each report is passed to `on_report`.
+    pub fn spawn_maintenance(
+        self: &Arc<Self>,
+        interval: Duration,
+        on_report: impl Fn(MaintenanceReport) + Send + 'static,
+    ) -> JoinHandle<()> {
+        let cache: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                match tokio::task::spawn_blocking(move || cache.run_maintenance()).await {
+                    Ok(Ok(report)) => on_report(report),
+                    Ok(Err(e)) => warn!("{e}"),
+                    Err(e) => warn!("Cache maintenance task failed: {e}"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make a file look older than the grace period.
+    fn age(path: &Path) -> std::io::Result<()> {
+        let old = SystemTime::now() - ORPHAN_GRACE * 2;
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(old)
+    }
+
+    #[test]
+    fn test_maintenance_reclaims() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.set_metadata("expired", "value", Some(-10))?;
+        cache.set_metadata("fresh", "value", Some(3600))?;
+        cache.set_metadata("forever", "value", None)?;
+
+        let kept = cache.store_audio("kept", "audio/mp4", "128kbps", b"audio", None)?;
+        let gone = cache.store_audio("gone", "audio/mp4", "128kbps", b"audio", None)?;
+        fs::remove_file(gone)?;
+        let orphan = cache.audio_dir().join("orphan.audio");
+        let recent = cache.audio_dir().join("recent.audio.part");
+        let foreign = cache.audio_dir().join("foreign");
+        for path in [&orphan, &recent, &foreign] {
+            fs::write(path, b"data")?;
+        }
+        age(&orphan)?;
+        age(&foreign)?;
+        age(&kept)?;
+
+        let report = cache.run_maintenance()?;
+        assert_eq!(
+            report,
+            MaintenanceReport {
+                expired_metadata: 1,
+                orphaned_files: 1,
+                missing_files: 1,
+                freed_bytes: 4,
+            }
+        );
+        assert!(kept.exists() && recent.exists() && foreign.exists());
+        assert!(!orphan.exists());
+        assert!(!cache.has_audio("gone"));
+        assert_eq!(cache.stats().metadata_count, 2);
+        assert!(cache.run_maintenance()?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spawned_maintenance_reports() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = Arc::new(CacheManager::with_path(dir.path().to_path_buf())?);
+        cache.set_metadata("expired", "value", Some(-10))?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = cache.spawn_maintenance(Duration::from_millis(10), move |report| {
+            let _ = tx.send(report);
+        });
+        assert_eq!(rx.recv().await.map(|r| r.expired_metadata), Some(1));
+        assert_eq!(rx.recv().await.map(|r| r.is_empty()), Some(true));
+
+        // The task stops once the cache is gone
+        drop(cache);
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .map_err(|e| Error::Internal(e.to_string()))?
+            .map_err(|e| Error::Internal(e.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! Versioned schema migrations for the cache database.
+//!
+//! The schema version is kept in `PRAGMA user_version`. Migrations run in
+//! order, each in its own transaction together with the version bump, so a
+//! database is always at some exact version. Caches from before versioning
+//! are at version 0; the first migration only creates what is missing, so
+//! their data is kept.
+
+use monad_core::{Error, Result};
+use rusqlite::{Connection, Transaction};
+use tracing::{info, warn};
+
+/// A schema change, applied once.
+struct Migration {
+    /// What the migration changes, for logging.
+    description: &'static str,
+    apply: fn(&Transaction<'_>) -> rusqlite::Result<()>,
+}
+
+/// Schema of the cache before versioning.
+const INITIAL_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS audio_cache (
+        id TEXT PRIMARY KEY,
+        video_id TEXT NOT NULL,
+        format TEXT NOT NULL,
+        quality TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        duration_secs REAL,
+        cached_at TEXT NOT NULL,
+        last_accessed TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS metadata_cache (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        cached_at TEXT NOT NULL,
+        expires_at TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS thumbnail_cache (
+        url_hash TEXT PRIMARY KEY,
+        url TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        cached_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS play_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        video_id TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT,
+        album TEXT,
+        played_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS artist_play_counts (
+        artist TEXT PRIMARY KEY COLLATE NOCASE,
+        play_count INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS album_play_counts (
+        artist TEXT NOT NULL COLLATE NOCASE,
+        album TEXT NOT NULL COLLATE NOCASE,
+        play_count INTEGER NOT NULL,
+        PRIMARY KEY (artist, album)
+    );
+
+    CREATE TABLE IF NOT EXISTS collection_visits (
+        collection_id TEXT PRIMARY KEY,
+        content_hash TEXT NOT NULL,
+        track_ids TEXT NOT NULL,
+        visited_at TEXT NOT NULL
+    );
+
+    -- Play counts are maintained incrementally as history is written
+    CREATE TRIGGER IF NOT EXISTS trg_play_history_artist_insert
+    AFTER INSERT ON play_history WHEN NEW.artist IS NOT NULL
+    BEGIN
+        INSERT INTO artist_play_counts (artist, play_count) VALUES (NEW.artist, 1)
+        ON CONFLICT(artist) DO UPDATE SET play_count = play_count + 1;
+    END;
+
+    -- Albums are counted per artist, with plays lacking one under an empty artist
+    CREATE TRIGGER IF NOT EXISTS trg_play_history_album_insert
+    AFTER INSERT ON play_history WHEN NEW.album IS NOT NULL
+    BEGIN
+        INSERT INTO album_play_counts (artist, album, play_count)
+            VALUES (COALESCE(NEW.artist, ''), NEW.album, 1)
+        ON CONFLICT(artist, album) DO UPDATE SET play_count = play_count + 1;
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS trg_play_history_delete
+    AFTER DELETE ON play_history
+    BEGIN
+        UPDATE artist_play_counts SET play_count = play_count - 1
+            WHERE artist = OLD.artist;
+        UPDATE album_play_counts SET play_count = play_count - 1
+            WHERE artist = COALESCE(OLD.artist, '') AND album = OLD.album;
+        DELETE FROM artist_play_counts WHERE play_count <= 0;
+        DELETE FROM album_play_counts WHERE play_count <= 0;
+    END;
+
+    CREATE INDEX IF NOT EXISTS idx_audio_video_id ON audio_cache(video_id);
+    CREATE INDEX IF NOT EXISTS idx_metadata_expires ON metadata_cache(expires_at);
+    CREATE INDEX IF NOT EXISTS idx_play_history_played_at ON play_history(played_at);
+";
+
+/// Every migration in order; a migration's version is its position, counting from 1.
+///
+/// Append new migrations at the end and never change released ones.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "initial schema",
+        apply: |tx| tx.execute_batch(INITIAL_SCHEMA),
+    },
+    Migration {
+        description: "audio pinning and thumbnail access times",
+        apply: |tx| {
+            add_column(tx, "audio_cache", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+            add_column(tx, "thumbnail_cache", "last_accessed", "TEXT")
+        },
+    },
+    Migration {
+        description: "offline search index",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+                    kind UNINDEXED, id UNINDEXED, title, artist, album, data UNINDEXED,
+                    tokenize = 'unicode61 remove_diacritics 2'
+                );",
+            )
+        },
+    },
+    Migration {
+        description: "offline pins",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS pins (
+                    kind TEXT NOT NULL,
+                    id TEXT NOT NULL,
+                    title TEXT NOT NULL,
+                    track_ids TEXT NOT NULL,
+                    thumbnail_urls TEXT NOT NULL,
+                    pinned_at TEXT NOT NULL,
+                    PRIMARY KEY (kind, id)
+                );",
+            )
+        },
+    },
+    Migration {
+        description: "local library",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS saved_items (
+                    kind TEXT NOT NULL,
+                    id TEXT NOT NULL,
+                    data TEXT NOT NULL,
+                    saved_at TEXT NOT NULL,
+                    PRIMARY KEY (kind, id)
+                );
+                CREATE INDEX IF NOT EXISTS idx_saved_items_kind ON saved_items(kind, saved_at);",
+            )
+        },
+    },
+    Migration {
+        description: "file checksums",
+        apply: |tx| {
+            add_column(tx, "audio_cache", "sha256", "TEXT")?;
+            add_column(tx, "thumbnail_cache", "sha256", "TEXT")
+        },
+    },
+    Migration {
+        description: "download state",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS downloads (
+                    video_id TEXT PRIMARY KEY,
+                    state TEXT NOT NULL,
+                    bytes_downloaded INTEGER NOT NULL DEFAULT 0,
+                    total_bytes INTEGER,
+                    error TEXT,
+                    queued_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        description: "saved queue",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS saved_queue (
+                    id INTEGER PRIMARY KEY CHECK (id = 1),
+                    queue TEXT NOT NULL,
+                    position_secs REAL NOT NULL,
+                    saved_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        description: "settings",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS settings (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        description: "scrobble journal",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS scrobbles (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    service TEXT NOT NULL,
+                    track TEXT NOT NULL,
+                    played_at TEXT NOT NULL,
+                    duration_secs REAL NOT NULL,
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    last_error TEXT
+                );
+                CREATE INDEX IF NOT EXISTS idx_scrobbles_service
+                    ON scrobbles(service, played_at);",
+            )
+        },
+    },
+    Migration {
+        description: "smart playlists",
+        apply: |tx| {
+            tx.execute_batch(
+                "CREATE TABLE IF NOT EXISTS smart_playlists (
+                    id TEXT PRIMARY KEY,
+                    data TEXT NOT NULL,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+];
+
+/// Schema version of a fully migrated database.
+#[allow(clippy::cast_possible_truncation)]
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Add a column unless it exists, as it can in databases from development builds.
+fn add_column(
+    tx: &Transaction<'_>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists = tx
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?"
+        ))?
+        .exists([column])?;
+    if !exists {
+        tx.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Get the schema version of a database.
+pub fn schema_version(db: &Connection) -> Result<u32> {
+    db.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| Error::Cache(format!("Failed to read schema version: {e}")))
+}
+
+/// Bring a database up to [`SCHEMA_VERSION`].
+pub fn migrate(db: &mut Connection) -> Result<()> {
+    let version = schema_version(db)?;
+    if version > SCHEMA_VERSION {
+        warn!(
+            "Cache schema version {version} is newer than this build supports ({SCHEMA_VERSION})"
+        );
+        return Ok(());
+    }
+
+    for (version, migration) in (1..).zip(MIGRATIONS).skip(version as usize) {
+        let result = db.transaction().and_then(|tx| {
+            (migration.apply)(&tx)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()
+        });
+        result.map_err(|e| {
+            Error::Cache(format!(
+                "Failed to migrate cache to version {version} ({}): {e}",
+                migration.description
+            ))
+        })?;
+        info!(
+            "Migrated cache to schema version {version}: {}",
+            migration.description
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_error(e: rusqlite::Error) -> Error {
+        Error::Cache(e.to_string())
+    }
+
+    #[test]
+    fn test_new_database_is_current() -> Result<()> {
+        let mut db = Connection::open_in_memory().map_err(query_error)?;
+        migrate(&mut db)?;
+        assert_eq!(schema_version(&db)?, SCHEMA_VERSION);
+        // Running again changes nothing
+        migrate(&mut db)?;
+        assert_eq!(schema_version(&db)?, SCHEMA_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unversioned_database_keeps_data() -> Result<()> {
+        let mut db = Connection::open_in_memory().map_err(query_error)?;
+        db.execute_batch(INITIAL_SCHEMA).map_err(query_error)?;
+        db.execute(
+            "INSERT INTO audio_cache VALUES ('abc', 'abc', 'audio/mp4', '128kbps', '/tmp/abc', 10, NULL, 'now', 'now')",
+            [],
+        )
+        .map_err(query_error)?;
+        assert_eq!(schema_version(&db)?, 0);
+
+        migrate(&mut db)?;
+        assert_eq!(schema_version(&db)?, SCHEMA_VERSION);
+        let pinned: bool = db
+            .query_row(
+                "SELECT pinned FROM audio_cache WHERE video_id = 'abc'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(query_error)?;
+        assert!(!pinned);
+        Ok(())
+    }
+}
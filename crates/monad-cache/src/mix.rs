@@ -0,0 +1,160 @@
+//! Offline "instant mix" built from local play history.
+//!
+//! Without a network there are no recommendations, so the mix is drawn from
+//! tracks already on disk. Candidates by the same artist come first, then the
+//! same album, then artists the user has played back-to-back with the seed
+//! artist, then everything else. Each group is shuffled.
+
+use std::collections::{HashMap, HashSet};
+
+use monad_core::{Track, TrackAlbum, TrackArtist};
+
+use crate::PlayRecord;
+
+impl PlayRecord {
+    /// Build a track from the recorded metadata.
+    pub fn to_track(&self) -> Track {
+        let mut track = Track::new(&self.video_id, &self.title);
+        if let Some(artist) = &self.artist {
+            track.artists.push(TrackArtist::new(artist));
+        }
+        track.album = self.album.as_deref().map(TrackAlbum::new);
+        track
+    }
+}
+
+/// Compare names case-insensitively; missing or empty names never match.
+fn eq_name(a: Option<&str>, b: Option<&str>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if !a.is_empty() && a.eq_ignore_ascii_case(b))
+}
+
+/// Count how often each artist was played right before or after `artist`.
+fn related_artists(artist: Option<&str>, history: &[PlayRecord]) -> HashMap<String, usize> {
+    let mut related = HashMap::new();
+    for pair in history.windows(2) {
+        let (a, b) = (pair[0].artist.as_deref(), pair[1].artist.as_deref());
+        let other = if eq_name(a, artist) {
+            b
+        } else if eq_name(b, artist) {
+            a
+        } else {
+            None
+        };
+        if let Some(other) = other.filter(|other| !eq_name(Some(other), artist)) {
+            *related.entry(other.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+    related
+}
+
+/// Shuffle in place with a small linear congruential generator.
+fn shuffle<T>(items: &mut [T], seed: &mut u64) {
+    for i in (1..items.len()).rev() {
+        *seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        #[allow(clippy::cast_possible_truncation)]
+        let j = ((*seed >> 33) as usize) % (i + 1);
+        items.swap(i, j);
+    }
+}
+
+/// Build a mix continuing from `seed`.
+///
+/// `history` is the play history, newest first. Only tracks for which
+/// `is_available` returns true (i.e. cached audio) are included.
+pub fn instant_mix(
+    seed: &Track,
+    history: &[PlayRecord],
+    is_available: impl Fn(&str) -> bool,
+    limit: usize,
+    rng_seed: u64,
+) -> Vec<Track> {
+    let artist = Some(seed.artist_name());
+    let album = seed.album_name();
+    let related = related_artists(artist, history);
+
+    let mut seen = HashSet::from([seed.id.as_str()]);
+    let mut groups: [Vec<&PlayRecord>; 4] = Default::default();
+    for record in history {
+        if !seen.insert(record.video_id.as_str()) || !is_available(&record.video_id) {
+            continue;
+        }
+        let group = if eq_name(record.artist.as_deref(), artist) {
+            0
+        } else if eq_name(record.album.as_deref(), album) {
+            1
+        } else if record
+            .artist
+            .as_ref()
+            .is_some_and(|a| related.contains_key(&a.to_lowercase()))
+        {
+            2
+        } else {
+            3
+        };
+        groups[group].push(record);
+    }
+
+    let mut rng = rng_seed;
+    groups
+        .iter_mut()
+        .flat_map(|group| {
+            shuffle(group, &mut rng);
+            group.iter()
+        })
+        .take(limit)
+        .map(|record| record.to_track())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn record(id: &str, artist: &str, album: Option<&str>) -> PlayRecord {
+        PlayRecord {
+            video_id: id.to_string(),
+            title: id.to_string(),
+            artist: Some(artist.to_string()),
+            album: album.map(str::to_string),
+            played_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_instant_mix_orders_by_similarity() {
+        let history = [
+            record("seed", "Artist", Some("Album")),
+            record("friend", "Friend", None),
+            record("other", "Other", None),
+            record("same-album", "Various", Some("Album")),
+            record("same-artist", "artist", None),
+            record("uncached", "Artist", None),
+            record("same-artist", "Artist", None),
+        ];
+        let mut seed = Track::new("seed", "Seed");
+        seed.artists.push(TrackArtist::new("Artist"));
+        seed.album = Some(TrackAlbum::new("Album"));
+
+        let mix = instant_mix(&seed, &history, |id| id != "uncached", 10, 7);
+        let ids: Vec<&str> = mix.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["same-artist", "same-album", "friend", "other"]);
+
+        let mix = instant_mix(&seed, &history, |_| true, 2, 7);
+        assert!(mix
+            .iter()
+            .all(|t| t.artist_name().eq_ignore_ascii_case("Artist")));
+    }
+
+    #[test]
+    fn test_shuffle_keeps_items() {
+        let mut items: Vec<u32> = (0..20).collect();
+        let mut seed = 42;
+        shuffle(&mut items, &mut seed);
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,320 @@
+//! Tracks, albums and playlists pinned for offline playback.
+//!
+//! A pin records the tracks and artwork of an item when it was pinned, and
+//! adds it to the offline search index. Audio of pinned tracks and pinned
+//! artwork are never evicted by the size limit; downloading them is left to
+//! the extractor, which reads the pins back with [`CacheManager::list_pins`].
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use monad_core::{Album, Error, Playlist, Result, Track};
+use rusqlite::{params, OptionalExtension, Row};
+
+use crate::CacheManager;
+
+/// Kind of a pinned item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinKind {
+    /// A single track.
+    Track,
+    /// An album.
+    Album,
+    /// A playlist.
+    Playlist,
+}
+
+impl PinKind {
+    /// Name of the kind in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Album => "album",
+            Self::Playlist => "playlist",
+        }
+    }
+
+    /// Parse a kind stored in the database.
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "track" => Some(Self::Track),
+            "album" => Some(Self::Album),
+            "playlist" => Some(Self::Playlist),
+            _ => None,
+        }
+    }
+}
+
+/// Something that can be pinned.
+#[derive(Debug, Clone, Copy)]
+pub enum PinTarget<'a> {
+    /// A single track.
+    Track(&'a Track),
+    /// An album and all its tracks.
+    Album(&'a Album),
+    /// A playlist and all its tracks.
+    Playlist(&'a Playlist),
+}
+
+impl<'a> From<&'a Track> for PinTarget<'a> {
+    fn from(track: &'a Track) -> Self {
+        Self::Track(track)
+    }
+}
+
+impl<'a> From<&'a Album> for PinTarget<'a> {
+    fn from(album: &'a Album) -> Self {
+        Self::Album(album)
+    }
+}
+
+impl<'a> From<&'a Playlist> for PinTarget<'a> {
+    fn from(playlist: &'a Playlist) -> Self {
+        Self::Playlist(playlist)
+    }
+}
+
+/// Collect the best artwork of each track.
+fn track_thumbnails(tracks: &[Track]) -> impl Iterator<Item = String> + '_ {
+    tracks
+        .iter()
+        .filter_map(|track| track.thumbnail_url().map(str::to_string))
+}
+
+impl PinTarget<'_> {
+    /// Kind and ID of the item.
+    pub(crate) fn key(&self) -> (PinKind, &str) {
+        match self {
+            Self::Track(track) => (PinKind::Track, &track.id),
+            Self::Album(album) => (PinKind::Album, &album.id),
+            Self::Playlist(playlist) => (PinKind::Playlist, &playlist.id),
+        }
+    }
+
+    /// Title of the item.
+    fn title(&self) -> &str {
+        match self {
+            Self::Track(track) => &track.title,
+            Self::Album(album) => &album.title,
+            Self::Playlist(playlist) => &playlist.title,
+        }
+    }
+
+    /// Video IDs of the tracks to keep.
+    fn track_ids(&self) -> Vec<String> {
+        match self {
+            Self::Track(track) => vec![track.id.clone()],
+            Self::Album(album) => album.tracks.iter().map(|t| t.id.clone()).collect(),
+            Self::Playlist(playlist) => playlist.tracks.iter().map(|t| t.id.clone()).collect(),
+        }
+    }
+
+    /// URLs of the artwork to keep, the item's own first.
+    pub(crate) fn thumbnail_urls(&self) -> Vec<String> {
+        let (own, tracks) = match self {
+            Self::Track(track) => (track.thumbnail_url(), &[][..]),
+            Self::Album(album) => (album.thumbnail_url(), &album.tracks[..]),
+            Self::Playlist(playlist) => (playlist.thumbnail_url(), &playlist.tracks[..]),
+        };
+        let mut seen = HashSet::new();
+        own.map(str::to_string)
+            .into_iter()
+            .chain(track_thumbnails(tracks))
+            .filter(|url| seen.insert(url.clone()))
+            .collect()
+    }
+}
+
+/// A pinned item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    /// Kind of the item.
+    pub kind: PinKind,
+    /// ID of the track, album or playlist.
+    pub id: String,
+    /// Title of the item.
+    pub title: String,
+    /// Video IDs of the tracks kept for it.
+    pub track_ids: Vec<String>,
+    /// URLs of the artwork kept for it.
+    pub thumbnail_urls: Vec<String>,
+    /// When the item was pinned.
+    pub pinned_at: DateTime<Utc>,
+}
+
+impl Pin {
+    /// Read a pin from a row of the `pins` table.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Option<Self>> {
+        let kind: String = row.get(0)?;
+        let track_ids: String = row.get(3)?;
+        let thumbnail_urls: String = row.get(4)?;
+        let pinned_at: String = row.get(5)?;
+        let Some(kind) = PinKind::parse(&kind) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            kind,
+            id: row.get(1)?,
+            title: row.get(2)?,
+            track_ids: serde_json::from_str(&track_ids).unwrap_or_default(),
+            thumbnail_urls: serde_json::from_str(&thumbnail_urls).unwrap_or_default(),
+            pinned_at: DateTime::parse_from_rfc3339(&pinned_at)
+                .map_or_else(|_| Utc::now(), |at| at.with_timezone(&Utc)),
+        }))
+    }
+}
+
+impl CacheManager {
+    /// Pin an item for offline playback, replacing any earlier pin of it.
+    ///
+    /// Returns the pin, listing the audio and artwork to download.
+    pub fn pin<'a>(&self, target: impl Into<PinTarget<'a>>) -> Result<Pin> {
+        let target = target.into();
+        let (kind, id) = target.key();
+        let pin = Pin {
+            kind,
+            id: id.to_string(),
+            title: target.title().to_string(),
+            track_ids: target.track_ids(),
+            thumbnail_urls: target.thumbnail_urls(),
+            pinned_at: Utc::now(),
+        };
+
+        {
+            let db = self.db.lock();
+            db.execute(
+                "INSERT OR REPLACE INTO pins (kind, id, title, track_ids, thumbnail_urls, pinned_at)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![
+                    kind.as_str(),
+                    pin.id,
+                    pin.title,
+                    serde_json::to_string(&pin.track_ids)?,
+                    serde_json::to_string(&pin.thumbnail_urls)?,
+                    pin.pinned_at.to_rfc3339()
+                ],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to pin {}: {e}", pin.id)))?;
+        }
+
+        // The metadata stays findable offline
+        match target {
+            PinTarget::Track(track) => self.index_track(track)?,
+            PinTarget::Album(album) => self.index_album(album)?,
+            PinTarget::Playlist(playlist) => self.index_playlist(playlist)?,
+        }
+        Ok(pin)
+    }
+
+    /// Unpin an item, returning its pin if it was pinned.
+    ///
+    /// Its audio and artwork stay cached, but may now be evicted.
+    pub fn unpin(&self, kind: PinKind, id: &str) -> Result<Option<Pin>> {
+        let db = self.db.lock();
+        let pin = db
+            .query_row(
+                "DELETE FROM pins WHERE kind = ? AND id = ?
+                 RETURNING kind, id, title, track_ids, thumbnail_urls, pinned_at",
+                params![kind.as_str(), id],
+                Pin::from_row,
+            )
+            .optional()
+            .map_err(|e| Error::Cache(format!("Failed to unpin {id}: {e}")))?;
+        Ok(pin.flatten())
+    }
+
+    /// Check whether an item is pinned.
+    pub fn is_pinned(&self, kind: PinKind, id: &str) -> bool {
+        let db = self.readers.get();
+        db.query_row(
+            "SELECT 1 FROM pins WHERE kind = ? AND id = ?",
+            params![kind.as_str(), id],
+            |_| Ok(()),
+        )
+        .is_ok()
+    }
+
+    /// List every pinned item, most recently pinned first.
+    pub fn list_pins(&self) -> Result<Vec<Pin>> {
+        let db = self.readers.get();
+        let pins: Vec<Option<Pin>> = db
+            .prepare(
+                "SELECT kind, id, title, track_ids, thumbnail_urls, pinned_at FROM pins
+                 ORDER BY pinned_at DESC",
+            )
+            .and_then(|mut stmt| stmt.query_map([], Pin::from_row)?.collect())
+            .map_err(|e| Error::Cache(format!("Failed to list pins: {e}")))?;
+        Ok(pins.into_iter().flatten().collect())
+    }
+
+    /// Get the video IDs of every track kept by a pin.
+    pub fn pinned_video_ids(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .list_pins()?
+            .into_iter()
+            .flat_map(|pin| pin.track_ids)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::types::{Thumbnail, Thumbnails};
+
+    fn track(id: &str) -> Track {
+        let mut track = Track::new(id, id.to_uppercase());
+        track.thumbnails = Thumbnails::new(vec![Thumbnail::new(
+            format!("https://i.ytimg.com/{id}.jpg"),
+            120,
+            120,
+        )]);
+        track
+    }
+
+    #[test]
+    fn test_pin_and_unpin() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let mut album = Album::new("album1", "Discovery");
+        album.tracks = vec![track("a"), track("b")];
+        let pin = cache.pin(&album)?;
+        assert_eq!(pin.track_ids, ["a", "b"]);
+        assert_eq!(pin.thumbnail_urls.len(), 2);
+        cache.pin(&track("c"))?;
+
+        assert!(cache.is_pinned(PinKind::Album, "album1"));
+        assert_eq!(cache.list_pins()?.len(), 2);
+        assert_eq!(cache.pinned_video_ids()?.len(), 3);
+        assert_eq!(cache.search_offline("discovery", 10)?.len(), 1);
+
+        assert_eq!(cache.unpin(PinKind::Album, "album1")?, Some(pin));
+        assert_eq!(cache.unpin(PinKind::Album, "album1")?, None);
+        assert_eq!(cache.list_pins()?.len(), 1);
+        assert_eq!(cache.pinned_video_ids()?, HashSet::from(["c".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_content_is_not_evicted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let pinned = track("a");
+        cache.pin(&pinned)?;
+        cache.store_audio("a", "audio/mp4", "128kbps", &[0; 100], None)?;
+        cache.store_thumbnail(&pinned.thumbnails.0[0].url, &[0; 10])?;
+        cache.store_audio("b", "audio/mp4", "128kbps", &[0; 100], None)?;
+
+        cache.set_size_limit(Some(50));
+        assert_eq!(cache.enforce_limits()?.evicted, 1);
+        assert!(cache.has_audio("a"));
+        assert!(!cache.has_audio("b"));
+        assert!(cache.thumbnail_path(&pinned.thumbnails.0[0].url).is_some());
+
+        // Once unpinned it can go
+        cache.unpin(PinKind::Track, "a")?;
+        assert_eq!(cache.enforce_limits()?.evicted, 1);
+        assert!(!cache.has_audio("a"));
+        Ok(())
+    }
+}
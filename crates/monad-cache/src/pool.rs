@@ -0,0 +1,116 @@
+//! Connections to the cache database.
+//!
+//! The database runs in WAL mode, where readers see the last committed state
+//! while a write is in progress. Writes go through a single connection, and
+//! reads are spread over a small pool of read-only connections, so reading
+//! stats or history never waits behind a download registering its audio.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use monad_core::{Error, Result};
+use parking_lot::{Mutex, MutexGuard};
+use rusqlite::{Connection, OpenFlags};
+
+/// Number of read-only connections.
+pub const READ_CONNECTIONS: usize = 4;
+
+/// How long a connection waits for a lock held by another before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Open a connection to the database at `path`, with WAL mode and a busy timeout.
+pub fn open(path: &Path, read_only: bool) -> Result<Connection> {
+    let flags = if read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX
+    } else {
+        OpenFlags::default()
+    };
+    let db = Connection::open_with_flags(path, flags)
+        .map_err(|e| Error::Cache(format!("Failed to open database: {e}")))?;
+    db.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| Error::Cache(format!("Failed to set busy timeout: {e}")))?;
+    if !read_only {
+        db.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|()| db.pragma_update(None, "synchronous", "NORMAL"))
+            .map_err(|e| Error::Cache(format!("Failed to enable WAL mode: {e}")))?;
+    }
+    Ok(db)
+}
+
+/// A pool of read-only connections.
+#[derive(Debug)]
+pub struct ReadPool {
+    connections: Vec<Mutex<Connection>>,
+    /// Connection to wait for when all are busy.
+    next: AtomicUsize,
+}
+
+impl ReadPool {
+    /// Open `size` read-only connections to the database at `path`.
+    pub fn open(path: &Path, size: usize) -> Result<Self> {
+        let connections = (0..size.max(1))
+            .map(|_| open(path, true).map(Mutex::new))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            connections,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Get an idle connection, or wait for one if all are in use.
+    pub fn get(&self) -> MutexGuard<'_, Connection> {
+        if let Some(db) = self.connections.iter().find_map(Mutex::try_lock) {
+            return db;
+        }
+        let next = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+        self.connections[next].lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CacheManager;
+
+    #[test]
+    fn test_reads_do_not_wait_for_writes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.set_metadata("key", "value", None)?;
+        let mode: String = cache
+            .db
+            .lock()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .map_err(|e| Error::Cache(e.to_string()))?;
+        assert_eq!(mode, "wal");
+
+        // Reads go through the pool while the writer is held
+        let writer = cache.db.lock();
+        writer
+            .execute_batch("BEGIN; DELETE FROM metadata_cache;")
+            .map_err(|e| Error::Cache(e.to_string()))?;
+        assert_eq!(cache.get_metadata("key").as_deref(), Some("value"));
+        assert_eq!(cache.stats().metadata_count, 1);
+        writer
+            .execute_batch("COMMIT;")
+            .map_err(|e| Error::Cache(e.to_string()))?;
+        drop(writer);
+        assert_eq!(cache.get_metadata("key"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pool_hands_out_idle_connections() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cache.db");
+        drop(open(&path, false)?);
+        let pool = ReadPool::open(&path, 2)?;
+        let first = pool.get();
+        let second = pool.get();
+        assert!(pool.connections.iter().all(Mutex::is_locked));
+        drop(first);
+        assert!(second.execute_batch("CREATE TABLE t (x)").is_err());
+        Ok(())
+    }
+}
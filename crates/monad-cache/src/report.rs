@@ -0,0 +1,270 @@
+//! Weekly listening reports built from the play history.
+//!
+//! A report covers one week of local time, starting on a configurable day.
+//! Listening time only counts plays of tracks whose duration is known from
+//! the audio cache, and a discovery is a track first played that week.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use monad_core::{Error, Result};
+use rusqlite::params;
+
+use crate::CacheManager;
+
+/// Number of top tracks and artists in a report.
+pub const REPORT_TOP_LIMIT: usize = 5;
+
+/// Metadata key holding the first day of the last report shown.
+const REPORT_SHOWN_KEY: &str = "weekly_report_shown";
+
+/// Get the first day of the last full week before `today`, and the day after it ends.
+pub fn last_full_week(today: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+    let days_into_week = today.weekday().days_since(week_start);
+    let end = today - TimeDelta::days(days_into_week.into());
+    (end - TimeDelta::days(7), end)
+}
+
+/// Get the start of a local day in UTC.
+fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .map_or_else(|| midnight.and_utc(), |t| t.with_timezone(&Utc))
+}
+
+#[allow(clippy::cast_sign_loss)]
+const fn to_count(count: i64) -> u64 {
+    if count < 0 {
+        0
+    } else {
+        count as u64
+    }
+}
+
+/// A track ranked by plays in a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopTrack {
+    /// `YouTube` video ID.
+    pub video_id: String,
+    /// Track title.
+    pub title: String,
+    /// Artist name (if known).
+    pub artist: Option<String>,
+    /// Number of plays in the week.
+    pub plays: u64,
+}
+
+/// An artist ranked by plays in a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopArtist {
+    /// Artist name.
+    pub name: String,
+    /// Number of plays in the week.
+    pub plays: u64,
+}
+
+/// Summary of a week of listening.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeeklyReport {
+    /// First day of the week.
+    pub week_start: NaiveDate,
+    /// Total number of plays.
+    pub plays: u64,
+    /// Seconds listened, from plays of tracks with a known duration.
+    pub listening_secs: f64,
+    /// Most played tracks, most played first.
+    pub top_tracks: Vec<TopTrack>,
+    /// Most played artists, most played first.
+    pub top_artists: Vec<TopArtist>,
+    /// Number of tracks played for the first time.
+    pub discoveries: u64,
+}
+
+impl WeeklyReport {
+    /// Get the last day of the week.
+    pub fn week_end(&self) -> NaiveDate {
+        self.week_start + TimeDelta::days(6)
+    }
+
+    /// Get the hours listened.
+    pub fn listening_hours(&self) -> f64 {
+        self.listening_secs / 3600.0
+    }
+
+    /// Check whether nothing was played.
+    pub const fn is_empty(&self) -> bool {
+        self.plays == 0
+    }
+}
+
+impl CacheManager {
+    /// Build the listening report for the week starting on `week_start`.
+    pub fn weekly_report(&self, week_start: NaiveDate) -> Result<WeeklyReport> {
+        let from = local_midnight(week_start).to_rfc3339();
+        let to = local_midnight(week_start + TimeDelta::days(7)).to_rfc3339();
+        let query_err = |e: rusqlite::Error| Error::Cache(format!("Failed to build report: {e}"));
+        let limit = i64::try_from(REPORT_TOP_LIMIT).unwrap_or(i64::MAX);
+
+        let db = self.readers.get();
+        let (plays, listening_secs) = db
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(a.duration_secs), 0.0) FROM play_history p
+                 LEFT JOIN audio_cache a ON a.video_id = p.video_id
+                 WHERE p.played_at >= ?1 AND p.played_at < ?2",
+                params![from, to],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?)),
+            )
+            .map_err(query_err)?;
+
+        let top_tracks = db
+            .prepare(
+                "SELECT video_id, title, artist, COUNT(*) AS plays FROM play_history
+                 WHERE played_at >= ?1 AND played_at < ?2
+                 GROUP BY video_id ORDER BY plays DESC, MAX(played_at) DESC LIMIT ?3",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(params![from, to, limit], |row| {
+                    Ok(TopTrack {
+                        video_id: row.get(0)?,
+                        title: row.get(1)?,
+                        artist: row.get(2)?,
+                        plays: to_count(row.get(3)?),
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(query_err)?;
+
+        let top_artists = db
+            .prepare(
+                "SELECT artist, COUNT(*) AS plays FROM play_history
+                 WHERE artist IS NOT NULL AND played_at >= ?1 AND played_at < ?2
+                 GROUP BY artist COLLATE NOCASE ORDER BY plays DESC, MAX(played_at) DESC LIMIT ?3",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(params![from, to, limit], |row| {
+                    Ok(TopArtist {
+                        name: row.get(0)?,
+                        plays: to_count(row.get(1)?),
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(query_err)?;
+
+        let discoveries: i64 = db
+            .query_row(
+                "SELECT COUNT(*) FROM
+                    (SELECT MIN(played_at) AS first_played FROM play_history GROUP BY video_id)
+                 WHERE first_played >= ?1 AND first_played < ?2",
+                params![from, to],
+                |row| row.get(0),
+            )
+            .map_err(query_err)?;
+
+        Ok(WeeklyReport {
+            week_start,
+            plays: to_count(plays),
+            listening_secs,
+            top_tracks,
+            top_artists,
+            discoveries: to_count(discoveries),
+        })
+    }
+
+    /// Get last week's report if it has not been shown yet and anything was played.
+    pub fn due_weekly_report(
+        &self,
+        today: NaiveDate,
+        week_start: Weekday,
+    ) -> Result<Option<WeeklyReport>> {
+        let (start, _) = last_full_week(today, week_start);
+        if self.get_metadata(REPORT_SHOWN_KEY) == Some(start.to_string()) {
+            return Ok(None);
+        }
+        let report = self.weekly_report(start)?;
+        Ok((!report.is_empty()).then_some(report))
+    }
+
+    /// Remember that a report was shown, so it is not due again.
+    pub fn mark_report_shown(&self, report: &WeeklyReport) -> Result<()> {
+        self.set_metadata(REPORT_SHOWN_KEY, &report.week_start.to_string(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use monad_core::{Track, TrackArtist};
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Result<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .ok_or_else(|| Error::Parse(format!("invalid date {year}-{month}-{day}")))
+    }
+
+    #[test]
+    fn test_last_full_week() -> Result<()> {
+        // 2026-10-14 is a Wednesday
+        let today = date(2026, 10, 14)?;
+        assert_eq!(
+            last_full_week(today, Weekday::Mon),
+            (date(2026, 10, 5)?, date(2026, 10, 12)?)
+        );
+        assert_eq!(
+            last_full_week(today, Weekday::Sun),
+            (date(2026, 10, 4)?, date(2026, 10, 11)?)
+        );
+        // On the first day of a week, the report covers the week that just ended
+        assert_eq!(
+            last_full_week(date(2026, 10, 12)?, Weekday::Mon).0,
+            date(2026, 10, 5)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_weekly_report() -> Result<()> {
+        let dir = tempfile::tempdir().map_err(|e| Error::Cache(e.to_string()))?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let week_start = date(2026, 10, 5)?;
+        let midday =
+            |day: i64| local_midnight(week_start) + TimeDelta::days(day) + TimeDelta::hours(12);
+        let track = |id: &str, artist: &str| {
+            let mut track = Track::new(id, id);
+            track.artists.push(TrackArtist::new(artist));
+            track
+        };
+
+        // Played before, so not a discovery
+        cache.record_play_at(&track("old", "A"), midday(-3))?;
+        cache.record_play_at(&track("old", "A"), midday(0))?;
+        cache.record_play_at(&track("new", "B"), midday(1))?;
+        cache.record_play_at(&track("new", "b"), midday(2))?;
+        cache.record_play_at(&track("next", "C"), midday(7))?;
+        cache.register_audio(&crate::AudioRecord {
+            video_id: "new".to_string(),
+            format: "audio/mp4".to_string(),
+            quality: "unknown".to_string(),
+            file_path: dir.path().join("new"),
+            size_bytes: 1,
+            duration_secs: Some(1800.0),
+        })?;
+
+        let report = cache.weekly_report(week_start)?;
+        assert_eq!(report.plays, 3);
+        assert!((report.listening_hours() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(report.top_tracks[0].video_id, "new");
+        assert_eq!(report.top_tracks[0].plays, 2);
+        assert_eq!(report.top_artists.len(), 2);
+        assert_eq!(report.discoveries, 1);
+        assert_eq!(report.week_end(), date(2026, 10, 11)?);
+
+        let today = date(2026, 10, 13)?;
+        let due = cache.due_weekly_report(today, Weekday::Mon)?;
+        assert_eq!(due.as_ref(), Some(&report));
+        cache.mark_report_shown(&report)?;
+        assert_eq!(cache.due_weekly_report(today, Weekday::Mon)?, None);
+        Ok(())
+    }
+}
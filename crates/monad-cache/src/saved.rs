@@ -0,0 +1,188 @@
+//! The local library of saved tracks, albums and artists.
+//!
+//! Saving works without a Google account: items are kept in the cache
+//! database as they were when saved, newest first. Saved tracks and albums
+//! are also added to the offline search index. Syncing with the `YouTube`
+//! library can be layered on top later.
+
+use chrono::Utc;
+use monad_core::types::ArtistPreview;
+use monad_core::{Album, Error, Result, Track};
+use rusqlite::params;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::CacheManager;
+
+/// Kinds of saved items, as stored in the `saved_items` table.
+const TRACK: &str = "track";
+const ALBUM: &str = "album";
+const ARTIST: &str = "artist";
+
+impl CacheManager {
+    /// Save an item, keeping its original save time if it was saved before.
+    fn save_item(&self, kind: &str, id: &str, item: &impl Serialize) -> Result<()> {
+        let data = serde_json::to_string(item)?;
+        let db = self.db.lock();
+        db.execute(
+            "INSERT INTO saved_items (kind, id, data, saved_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(kind, id) DO UPDATE SET data = excluded.data",
+            params![kind, id, data, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to save {kind} {id}: {e}")))?;
+        Ok(())
+    }
+
+    /// Remove a saved item.
+    fn unsave_item(&self, kind: &str, id: &str) -> Result<()> {
+        let db = self.db.lock();
+        db.execute(
+            "DELETE FROM saved_items WHERE kind = ? AND id = ?",
+            params![kind, id],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to remove saved {kind} {id}: {e}")))?;
+        Ok(())
+    }
+
+    /// Check whether an item is saved.
+    fn is_item_saved(&self, kind: &str, id: &str) -> bool {
+        let db = self.readers.get();
+        db.query_row(
+            "SELECT 1 FROM saved_items WHERE kind = ? AND id = ?",
+            params![kind, id],
+            |_| Ok(()),
+        )
+        .is_ok()
+    }
+
+    /// List the saved items of a kind, most recently saved first.
+    fn list_saved<T: DeserializeOwned>(&self, kind: &str) -> Result<Vec<T>> {
+        let db = self.readers.get();
+        let rows: Vec<String> = db
+            .prepare(
+                "SELECT data FROM saved_items WHERE kind = ? ORDER BY saved_at DESC, rowid DESC",
+            )
+            .and_then(|mut stmt| stmt.query_map([kind], |row| row.get(0))?.collect())
+            .map_err(|e| Error::Cache(format!("Failed to list saved {kind}s: {e}")))?;
+        Ok(rows
+            .iter()
+            .filter_map(|data| {
+                serde_json::from_str(data)
+                    .inspect_err(|e| warn!("Skipping unreadable saved {kind}: {e}"))
+                    .ok()
+            })
+            .collect())
+    }
+
+    /// Save a track to the local library.
+    pub fn save_track(&self, track: &Track) -> Result<()> {
+        self.save_item(TRACK, &track.id, track)?;
+        self.index_track(track)
+    }
+
+    /// Remove a track from the local library.
+    pub fn unsave_track(&self, video_id: &str) -> Result<()> {
+        self.unsave_item(TRACK, video_id)
+    }
+
+    /// Check whether a track is in the local library.
+    pub fn is_track_saved(&self, video_id: &str) -> bool {
+        self.is_item_saved(TRACK, video_id)
+    }
+
+    /// List saved tracks, most recently saved first.
+    pub fn list_saved_tracks(&self) -> Result<Vec<Track>> {
+        self.list_saved(TRACK)
+    }
+
+    /// Save an album to the local library.
+    pub fn save_album(&self, album: &Album) -> Result<()> {
+        self.save_item(ALBUM, &album.id, album)?;
+        self.index_album(album)
+    }
+
+    /// Remove an album from the local library.
+    pub fn unsave_album(&self, album_id: &str) -> Result<()> {
+        self.unsave_item(ALBUM, album_id)
+    }
+
+    /// Check whether an album is in the local library.
+    pub fn is_album_saved(&self, album_id: &str) -> bool {
+        self.is_item_saved(ALBUM, album_id)
+    }
+
+    /// List saved albums, most recently saved first.
+    pub fn list_saved_albums(&self) -> Result<Vec<Album>> {
+        self.list_saved(ALBUM)
+    }
+
+    /// Save an artist to the local library.
+    pub fn save_artist(&self, artist: &ArtistPreview) -> Result<()> {
+        self.save_item(ARTIST, &artist.id, artist)
+    }
+
+    /// Remove an artist from the local library.
+    pub fn unsave_artist(&self, artist_id: &str) -> Result<()> {
+        self.unsave_item(ARTIST, artist_id)
+    }
+
+    /// Check whether an artist is in the local library.
+    pub fn is_artist_saved(&self, artist_id: &str) -> bool {
+        self.is_item_saved(ARTIST, artist_id)
+    }
+
+    /// List saved artists, most recently saved first.
+    pub fn list_saved_artists(&self) -> Result<Vec<ArtistPreview>> {
+        self.list_saved(ARTIST)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_tracks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let first = Track::new("a", "First");
+        let second = Track::new("b", "Second");
+        cache.save_track(&first)?;
+        cache.save_track(&second)?;
+        // Saving again updates the track but keeps its place
+        let renamed = Track::new("a", "First (Remastered)");
+        cache.save_track(&renamed)?;
+
+        assert!(cache.is_track_saved("a"));
+        assert_eq!(cache.list_saved_tracks()?, [second, renamed]);
+        assert_eq!(cache.search_offline("remastered", 10)?.len(), 1);
+
+        cache.unsave_track("a")?;
+        assert!(!cache.is_track_saved("a"));
+        assert_eq!(cache.list_saved_tracks()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_saved_albums_and_artists() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let album = Album::new("album1", "Discovery");
+        let artist = ArtistPreview::new("artist1", "Daft Punk");
+        cache.save_album(&album)?;
+        cache.save_artist(&artist)?;
+
+        assert_eq!(cache.list_saved_albums()?, [album]);
+        assert_eq!(cache.list_saved_artists()?, [artist]);
+        assert!(cache.list_saved_tracks()?.is_empty());
+        assert!(cache.is_artist_saved("artist1"));
+        assert!(!cache.is_album_saved("artist1"));
+
+        cache.unsave_album("album1")?;
+        cache.unsave_artist("artist1")?;
+        assert!(cache.list_saved_albums()?.is_empty());
+        assert!(cache.list_saved_artists()?.is_empty());
+        Ok(())
+    }
+}
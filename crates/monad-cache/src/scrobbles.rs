@@ -0,0 +1,228 @@
+//! Journal of scrobbles waiting to be submitted.
+//!
+//! Plays are recorded here once per scrobbling service (such as `lastfm` or
+//! `listenbrainz`), and stay until the service accepts them, so plays made
+//! offline or while rate-limited are submitted later. A scrobble that keeps
+//! failing stops being offered after [`MAX_SCROBBLE_ATTEMPTS`] until it is
+//! retried or purged.
+
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Result, Track};
+use rusqlite::{params, params_from_iter, Row};
+use tracing::warn;
+
+use crate::CacheManager;
+
+/// Failed submissions after which a scrobble is no longer offered.
+pub const MAX_SCROBBLE_ATTEMPTS: u32 = 5;
+
+/// A play waiting to be submitted to a scrobbling service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scrobble {
+    /// Journal entry ID.
+    pub id: i64,
+    /// Service the play is for.
+    pub service: String,
+    /// The track played.
+    pub track: Track,
+    /// When playback started.
+    pub played_at: DateTime<Utc>,
+    /// How long the track was played, in seconds.
+    pub duration_secs: f64,
+    /// Failed submissions so far.
+    pub attempts: u32,
+    /// Why the last submission failed.
+    pub last_error: Option<String>,
+}
+
+impl Scrobble {
+    /// Read a scrobble from a row of the `scrobbles` table.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Option<Self>> {
+        let track: String = row.get(2)?;
+        let played_at: String = row.get(3)?;
+        let Ok(track) = serde_json::from_str(&track) else {
+            warn!("Skipping unreadable scrobble");
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            id: row.get(0)?,
+            service: row.get(1)?,
+            track,
+            played_at: DateTime::parse_from_rfc3339(&played_at)
+                .map_or_else(|_| Utc::now(), |at| at.with_timezone(&Utc)),
+            duration_secs: row.get(4)?,
+            attempts: row.get(5)?,
+            last_error: row.get(6)?,
+        }))
+    }
+}
+
+impl CacheManager {
+    /// Record a play to submit to each of `services`.
+    pub fn record_scrobble(
+        &self,
+        services: &[&str],
+        track: &Track,
+        played_at: DateTime<Utc>,
+        duration_secs: f64,
+    ) -> Result<()> {
+        let data = serde_json::to_string(track)?;
+        let mut db = self.db.lock();
+        let map_err = |e: rusqlite::Error| Error::Cache(format!("Failed to record scrobble: {e}"));
+        let tx = db.transaction().map_err(map_err)?;
+        for service in services {
+            tx.execute(
+                "INSERT INTO scrobbles (service, track, played_at, duration_secs)
+                 VALUES (?, ?, ?, ?)",
+                params![service, data, played_at.to_rfc3339(), duration_secs],
+            )
+            .map_err(map_err)?;
+        }
+        tx.commit().map_err(map_err)
+    }
+
+    /// List the oldest scrobbles waiting for `service`, up to `limit`.
+    ///
+    /// Scrobbles that failed [`MAX_SCROBBLE_ATTEMPTS`] times are left out.
+    pub fn pending_scrobbles(&self, service: &str, limit: usize) -> Result<Vec<Scrobble>> {
+        self.query_scrobbles(
+            "WHERE service = ? AND attempts < ? ORDER BY played_at, id LIMIT ?",
+            params![
+                service,
+                MAX_SCROBBLE_ATTEMPTS,
+                i64::try_from(limit).unwrap_or(i64::MAX)
+            ],
+        )
+    }
+
+    /// List every scrobble waiting for any service, including ones that keep failing.
+    pub fn list_scrobbles(&self) -> Result<Vec<Scrobble>> {
+        self.query_scrobbles("ORDER BY played_at, id", [])
+    }
+
+    /// Query the `scrobbles` table with a filter and order.
+    fn query_scrobbles(
+        &self,
+        clause: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<Scrobble>> {
+        let db = self.readers.get();
+        let rows: Vec<Option<Scrobble>> = db
+            .prepare(&format!(
+                "SELECT id, service, track, played_at, duration_secs, attempts, last_error
+                 FROM scrobbles {clause}"
+            ))
+            .and_then(|mut stmt| stmt.query_map(params, Scrobble::from_row)?.collect())
+            .map_err(|e| Error::Cache(format!("Failed to list scrobbles: {e}")))?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// Remove scrobbles the service accepted.
+    pub fn mark_scrobbled(&self, ids: &[i64]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let db = self.db.lock();
+        db.execute(
+            &format!("DELETE FROM scrobbles WHERE id IN ({placeholders})"),
+            params_from_iter(ids),
+        )
+        .map_err(|e| Error::Cache(format!("Failed to remove submitted scrobbles: {e}")))?;
+        Ok(())
+    }
+
+    /// Record a failed submission of scrobbles.
+    pub fn mark_scrobble_failed(&self, ids: &[i64], error: &str) -> Result<()> {
+        let db = self.db.lock();
+        for id in ids {
+            db.execute(
+                "UPDATE scrobbles SET attempts = attempts + 1, last_error = ? WHERE id = ?",
+                params![error, id],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to record scrobble failure: {e}")))?;
+        }
+        Ok(())
+    }
+
+    /// Offer scrobbles that kept failing again, returning how many there were.
+    pub fn retry_scrobbles(&self, service: &str) -> Result<usize> {
+        let db = self.db.lock();
+        db.execute(
+            "UPDATE scrobbles SET attempts = 0, last_error = NULL
+             WHERE service = ? AND attempts > 0",
+            [service],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to retry scrobbles: {e}")))
+    }
+
+    /// Delete the scrobbles waiting for `service`, or for every service if `None`.
+    ///
+    /// Returns the number deleted.
+    pub fn purge_scrobbles(&self, service: Option<&str>) -> Result<usize> {
+        let db = self.db.lock();
+        db.execute(
+            "DELETE FROM scrobbles WHERE ?1 IS NULL OR service = ?1",
+            [service],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to purge scrobbles: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrobbles_wait_until_submitted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let played_at = Utc::now();
+        cache.record_scrobble(
+            &["lastfm", "listenbrainz"],
+            &Track::new("a", "A"),
+            played_at,
+            180.0,
+        )?;
+        cache.record_scrobble(&["lastfm"], &Track::new("b", "B"), played_at, 200.0)?;
+
+        let pending = cache.pending_scrobbles("lastfm", 10)?;
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].track.id, "a");
+        assert_eq!(pending[0].played_at.timestamp(), played_at.timestamp());
+        assert_eq!(cache.pending_scrobbles("lastfm", 1)?.len(), 1);
+
+        cache.mark_scrobbled(&[pending[0].id])?;
+        assert_eq!(cache.pending_scrobbles("lastfm", 10)?.len(), 1);
+        // Each service submits its own copy
+        assert_eq!(cache.pending_scrobbles("listenbrainz", 10)?.len(), 1);
+        assert_eq!(cache.purge_scrobbles(Some("listenbrainz"))?, 1);
+        assert_eq!(cache.purge_scrobbles(None)?, 1);
+        assert!(cache.list_scrobbles()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failing_scrobbles_are_held_back() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.record_scrobble(&["lastfm"], &Track::new("a", "A"), Utc::now(), 60.0)?;
+        let ids: Vec<i64> = cache
+            .pending_scrobbles("lastfm", 10)?
+            .iter()
+            .map(|scrobble| scrobble.id)
+            .collect();
+
+        for _ in 0..MAX_SCROBBLE_ATTEMPTS {
+            cache.mark_scrobble_failed(&ids, "Rate limited")?;
+        }
+        assert!(cache.pending_scrobbles("lastfm", 10)?.is_empty());
+        let listed = cache.list_scrobbles()?;
+        assert_eq!(listed[0].attempts, MAX_SCROBBLE_ATTEMPTS);
+        assert_eq!(listed[0].last_error.as_deref(), Some("Rate limited"));
+
+        assert_eq!(cache.retry_scrobbles("lastfm")?, 1);
+        assert_eq!(cache.pending_scrobbles("lastfm", 10)?.len(), 1);
+        Ok(())
+    }
+}
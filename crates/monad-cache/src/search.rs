@@ -0,0 +1,202 @@
+//! Full-text search over cached tracks, albums and playlists.
+//!
+//! Everything played or opened is added to an FTS5 index of titles, artists
+//! and albums, along with the item itself, so the search screen still finds
+//! it without a network connection. Each word of a query matches as a prefix
+//! of a word in any of the fields, ignoring case and accents.
+
+use monad_core::{Album, Error, Playlist, Result, Track};
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::CacheManager;
+
+/// Default number of offline search results.
+pub const OFFLINE_SEARCH_LIMIT: usize = 50;
+
+/// A cached item found by an offline search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OfflineResult {
+    /// A track.
+    Track(Track),
+    /// An album.
+    Album(Album),
+    /// A playlist.
+    Playlist(Playlist),
+}
+
+/// Turn user input into an FTS5 query matching every word as a prefix.
+fn match_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "")))
+        .filter(|word| word != "\"\"*")
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Add an item to the index, replacing any earlier entry for it.
+fn index(
+    db: &Connection,
+    kind: &str,
+    id: &str,
+    fields: [&str; 3],
+    item: &impl Serialize,
+) -> Result<()> {
+    let data = serde_json::to_string(item)?;
+    db.execute(
+        "DELETE FROM search_index WHERE kind = ? AND id = ?",
+        params![kind, id],
+    )
+    .and_then(|_| {
+        db.execute(
+            "INSERT INTO search_index (kind, id, title, artist, album, data)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![kind, id, fields[0], fields[1], fields[2], data],
+        )
+    })
+    .map_err(|e| Error::Cache(format!("Failed to index {kind} {id}: {e}")))?;
+    Ok(())
+}
+
+/// Add tracks to the index.
+fn index_tracks<'a>(db: &Connection, tracks: impl IntoIterator<Item = &'a Track>) -> Result<()> {
+    for track in tracks {
+        let album = track.album_name().unwrap_or_default();
+        index(
+            db,
+            "track",
+            &track.id,
+            [&track.title, &track.artists_display(), album],
+            track,
+        )?;
+    }
+    Ok(())
+}
+
+/// Parse an indexed item, logging items that no longer parse.
+fn parse<T: DeserializeOwned>(data: &str) -> Option<T> {
+    serde_json::from_str(data)
+        .inspect_err(|e| warn!("Skipping unreadable search entry: {e}"))
+        .ok()
+}
+
+impl CacheManager {
+    /// Add a track to the offline search index.
+    pub fn index_track(&self, track: &Track) -> Result<()> {
+        index_tracks(&self.db.lock(), [track])
+    }
+
+    /// Add an album and its tracks to the offline search index.
+    pub fn index_album(&self, album: &Album) -> Result<()> {
+        let mut db = self.db.lock();
+        let tx = db
+            .transaction()
+            .map_err(|e| Error::Cache(format!("Failed to index album: {e}")))?;
+        let fields = [album.title.as_str(), &album.artists_display(), &album.title];
+        index(&tx, "album", &album.id, fields, album)?;
+        index_tracks(&tx, &album.tracks)?;
+        tx.commit()
+            .map_err(|e| Error::Cache(format!("Failed to index album: {e}")))
+    }
+
+    /// Add a playlist and its tracks to the offline search index.
+    pub fn index_playlist(&self, playlist: &Playlist) -> Result<()> {
+        let mut db = self.db.lock();
+        let tx = db
+            .transaction()
+            .map_err(|e| Error::Cache(format!("Failed to index playlist: {e}")))?;
+        let author = playlist.author_name().unwrap_or_default();
+        let fields = [playlist.title.as_str(), author, ""];
+        index(&tx, "playlist", &playlist.id, fields, playlist)?;
+        index_tracks(&tx, &playlist.tracks)?;
+        tx.commit()
+            .map_err(|e| Error::Cache(format!("Failed to index playlist: {e}")))
+    }
+
+    /// Search cached tracks, albums and playlists, best matches first.
+    pub fn search_offline(&self, query: &str, limit: usize) -> Result<Vec<OfflineResult>> {
+        let query = match_query(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let db = self.readers.get();
+        let rows: Vec<(String, String)> = db
+            .prepare(
+                "SELECT kind, data FROM search_index WHERE search_index MATCH ?
+                 ORDER BY rank LIMIT ?",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(
+                    params![query, i64::try_from(limit).unwrap_or(i64::MAX)],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?
+                .collect()
+            })
+            .map_err(|e| Error::Cache(format!("Offline search failed: {e}")))?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(kind, data)| match kind.as_str() {
+                "track" => parse(&data).map(OfflineResult::Track),
+                "album" => parse(&data).map(OfflineResult::Album),
+                "playlist" => parse(&data).map(OfflineResult::Playlist),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::{TrackAlbum, TrackArtist};
+
+    fn track(id: &str, title: &str, artist: &str) -> Track {
+        let mut track = Track::new(id, title);
+        track.artists.push(TrackArtist::new(artist));
+        track
+    }
+
+    #[test]
+    fn test_match_query() {
+        assert_eq!(match_query("  daft  PUNK "), "\"daft\"* \"PUNK\"*");
+        assert_eq!(match_query("\"\" a\"b"), "\"ab\"*");
+        assert_eq!(match_query(""), "");
+    }
+
+    #[test]
+    fn test_search_offline() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let mut album = Album::new("album1", "Discovery");
+        album.artists.push(TrackArtist::new("Daft Punk"));
+        let mut one_more_time = track("a", "One More Time", "Daft Punk");
+        one_more_time.album = Some(TrackAlbum::new("Discovery"));
+        album.tracks.push(one_more_time.clone());
+        cache.index_album(&album)?;
+        cache.record_play(&track("b", "Café del Mar", "Energy 52"))?;
+        let mut playlist = Playlist::new("pl1", "Late Night");
+        playlist.tracks.push(one_more_time.clone());
+        cache.index_playlist(&playlist)?;
+
+        let results = cache.search_offline("discov", 10)?;
+        assert_eq!(results.len(), 2);
+        assert!(results.contains(&OfflineResult::Album(album)));
+        assert!(results.contains(&OfflineResult::Track(one_more_time.clone())));
+
+        // Indexing again replaces the entry
+        cache.index_track(&one_more_time)?;
+        assert_eq!(
+            cache.search_offline("daft more", 10)?,
+            [OfflineResult::Track(one_more_time)]
+        );
+        assert_eq!(cache.search_offline("cafe", 10)?.len(), 1);
+        assert_eq!(cache.search_offline("night", 10)?.len(), 1);
+        assert!(cache.search_offline("nothing", 10)?.is_empty());
+        assert!(cache.search_offline("\"", 10)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! The playback queue saved for restoring the session.
+//!
+//! The app saves its queue and playback position as it plays, so a restart
+//! or crash picks up at the same track and place. Only the latest queue is
+//! kept.
+
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Queue, Result};
+use rusqlite::{params, OptionalExtension};
+
+use crate::CacheManager;
+
+/// A saved playback queue.
+#[derive(Debug, Clone)]
+pub struct SavedQueue {
+    /// The queue, with its current track, repeat mode and shuffle order.
+    pub queue: Queue,
+    /// Playback position in the current track, in seconds.
+    pub position_secs: f64,
+    /// When the queue was saved.
+    pub saved_at: DateTime<Utc>,
+}
+
+impl CacheManager {
+    /// Save the queue and playback position, replacing any saved before.
+    pub fn save_queue(&self, queue: &Queue, position_secs: f64) -> Result<()> {
+        let data = serde_json::to_string(queue)?;
+        let db = self.db.lock();
+        db.execute(
+            "INSERT OR REPLACE INTO saved_queue (id, queue, position_secs, saved_at)
+             VALUES (1, ?, ?, ?)",
+            params![data, position_secs, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to save queue: {e}")))?;
+        Ok(())
+    }
+
+    /// Load the saved queue, if any.
+    pub fn load_queue(&self) -> Result<Option<SavedQueue>> {
+        let db = self.readers.get();
+        let row: Option<(String, f64, String)> = db
+            .query_row(
+                "SELECT queue, position_secs, saved_at FROM saved_queue WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|e| Error::Cache(format!("Failed to load queue: {e}")))?;
+        let Some((data, position_secs, saved_at)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(SavedQueue {
+            queue: serde_json::from_str(&data)?,
+            position_secs,
+            saved_at: DateTime::parse_from_rfc3339(&saved_at)
+                .map_or_else(|_| Utc::now(), |at| at.with_timezone(&Utc)),
+        }))
+    }
+
+    /// Forget the saved queue.
+    pub fn clear_saved_queue(&self) -> Result<()> {
+        let db = self.db.lock();
+        db.execute("DELETE FROM saved_queue", [])
+            .map_err(|e| Error::Cache(format!("Failed to clear saved queue: {e}")))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::{QueueItem, RepeatMode, Track};
+
+    fn queue() -> Queue {
+        let mut queue = Queue::new();
+        let items = ["a", "b", "c"]
+            .into_iter()
+            .map(|id| QueueItem::from_track(Track::new(id, id.to_uppercase())))
+            .collect();
+        queue.set(items, 1);
+        queue.set_repeat_mode(RepeatMode::All);
+        queue
+    }
+
+    #[test]
+    fn test_queue_round_trips() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert!(cache.load_queue()?.is_none());
+
+        cache.save_queue(&queue(), 42.5)?;
+        let saved = cache
+            .load_queue()?
+            .ok_or(Error::Cache("No saved queue".into()))?;
+        assert_eq!(saved.queue.items().len(), 3);
+        assert_eq!(
+            saved.queue.current().map(|item| item.track.id.as_str()),
+            Some("b")
+        );
+        assert_eq!(saved.queue.repeat_mode(), RepeatMode::All);
+        assert!((saved.position_secs - 42.5).abs() < f64::EPSILON);
+        Ok(())
+    }
+
+    #[test]
+    fn test_saving_replaces_queue() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.save_queue(&queue(), 10.0)?;
+        cache.save_queue(&Queue::new(), 0.0)?;
+        let saved = cache
+            .load_queue()?
+            .ok_or(Error::Cache("No saved queue".into()))?;
+        assert!(saved.queue.is_empty());
+
+        cache.clear_saved_queue()?;
+        assert!(cache.load_queue()?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! App settings kept in the cache database.
+//!
+//! Each setting is a JSON value under a key, read back as whatever type it
+//! was written as. Changes are announced with [`CacheEvent::SettingChanged`],
+//! so every view showing a setting stays current.
+
+use chrono::Utc;
+use monad_core::{Error, Result};
+use rusqlite::{params, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::events::CacheEvent;
+use crate::CacheManager;
+
+impl CacheManager {
+    /// Get a setting, or `None` if it was never set.
+    pub fn setting<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let db = self.readers.get();
+        let value: Option<String> = db
+            .query_row("SELECT value FROM settings WHERE key = ?", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| Error::Cache(format!("Failed to read setting {key}: {e}")))?;
+        value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Get a setting, or `default` if it was never set or cannot be read.
+    pub fn setting_or<T: DeserializeOwned>(&self, key: &str, default: T) -> T {
+        match self.setting(key) {
+            Ok(value) => value.unwrap_or(default),
+            Err(e) => {
+                warn!("Using the default for setting {key}: {e}");
+                default
+            }
+        }
+    }
+
+    /// Change a setting.
+    pub fn set_setting<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_string(value)?;
+        let db = self.db.lock();
+        let changed = db
+            .execute(
+                "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(key) DO UPDATE SET value = ?2, updated_at = ?3
+                 WHERE value != ?2",
+                params![key, value, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to save setting {key}: {e}")))?;
+        drop(db);
+        if changed > 0 {
+            self.notify(CacheEvent::SettingChanged {
+                key: key.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Reset a setting to its default.
+    pub fn remove_setting(&self, key: &str) -> Result<()> {
+        let db = self.db.lock();
+        let changed = db
+            .execute("DELETE FROM settings WHERE key = ?", [key])
+            .map_err(|e| Error::Cache(format!("Failed to remove setting {key}: {e}")))?;
+        drop(db);
+        if changed > 0 {
+            self.notify(CacheEvent::SettingChanged {
+                key: key.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Equalizer {
+        name: String,
+        bands: Vec<f32>,
+    }
+
+    #[test]
+    fn test_typed_settings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert_eq!(cache.setting::<f32>("volume")?, None);
+        assert_eq!(cache.setting_or("volume", 80_u8), 80);
+
+        let preset = Equalizer {
+            name: "Bass".to_string(),
+            bands: vec![6.0, 3.0, 0.0],
+        };
+        cache.set_setting("volume", &0.5)?;
+        cache.set_setting("equalizer", &preset)?;
+        cache.set_setting("theme", "blue")?;
+        assert_eq!(cache.setting::<f32>("volume")?, Some(0.5));
+        assert_eq!(cache.setting::<Equalizer>("equalizer")?, Some(preset));
+        assert_eq!(cache.setting_or("theme", String::new()), "blue");
+        // A value of another type is an error, or the default
+        assert!(cache.setting::<u32>("theme").is_err());
+        assert_eq!(cache.setting_or("theme", 7_u32), 7);
+
+        cache.remove_setting("volume")?;
+        assert_eq!(cache.setting::<f32>("volume")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_setting_changes_notify() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let mut events = cache.subscribe();
+        cache.set_setting("theme", "blue")?;
+        // Setting the same value again is not a change
+        cache.set_setting("theme", "blue")?;
+        cache.remove_setting("theme")?;
+        cache.remove_setting("theme")?;
+
+        let changed = CacheEvent::SettingChanged {
+            key: "theme".to_string(),
+        };
+        let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert_eq!(received, [changed.clone(), changed]);
+        Ok(())
+    }
+}
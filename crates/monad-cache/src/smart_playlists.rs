@@ -0,0 +1,438 @@
+//! Smart playlists, built from rules over the play history and library.
+//!
+//! A smart playlist is stored as its rules, not its tracks, and is evaluated
+//! on demand against the tracks the user has played or saved, so "most played
+//! this month" or "not played recently" stay current without refreshing.
+//! A track matches when it matches every rule.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use monad_core::{Error, Result, Track, TrackAlbum, TrackArtist};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::CacheManager;
+
+/// Tracks in a smart playlist unless it sets its own limit.
+pub const SMART_PLAYLIST_LIMIT: usize = 100;
+
+/// Every track played or saved, with what the rules match on.
+///
+/// Metadata comes from the latest play, or from the saved track if it was
+/// never played.
+const CANDIDATES: &str = "
+    WITH played AS (
+        SELECT video_id, title, artist, album, MAX(played_at) AS last_played,
+            MIN(played_at) AS first_played, COUNT(*) AS plays
+        FROM play_history GROUP BY video_id
+    ),
+    saved AS (
+        SELECT id AS video_id, data, saved_at FROM saved_items WHERE kind = 'track'
+    ),
+    ids AS (
+        SELECT video_id FROM played UNION SELECT video_id FROM saved
+    ),
+    tracks AS (
+        SELECT ids.video_id, saved.data, saved.saved_at,
+            COALESCE(played.title, json_extract(saved.data, '$.title')) AS title,
+            COALESCE(played.artist, json_extract(saved.data, '$.artists[0].name')) AS artist,
+            COALESCE(played.album, json_extract(saved.data, '$.album.name')) AS album,
+            played.first_played, played.last_played, COALESCE(played.plays, 0) AS plays
+        FROM ids
+        LEFT JOIN played ON played.video_id = ids.video_id
+        LEFT JOIN saved ON saved.video_id = ids.video_id
+    )
+    SELECT video_id, data, title, artist, album FROM tracks";
+
+/// A condition on tracks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SmartRule {
+    /// By an artist (case-insensitive).
+    Artist {
+        /// Artist name.
+        name: String,
+    },
+    /// On an album (case-insensitive).
+    Album {
+        /// Album name.
+        name: String,
+    },
+    /// Played within the last `days` days.
+    PlayedWithin {
+        /// Days to look back.
+        days: u32,
+    },
+    /// Played at least `count` times, within the last `within_days` days if set.
+    PlayedAtLeast {
+        /// Minimum number of plays.
+        count: u32,
+        /// Days to look back, or `None` for all time.
+        within_days: Option<u32>,
+    },
+    /// Saved to the library, or first played, within the last `days` days.
+    AddedWithin {
+        /// Days to look back.
+        days: u32,
+    },
+    /// Saved to the library.
+    Saved,
+    /// Audio cached for offline playback.
+    Cached,
+    /// Not matching another rule.
+    Not {
+        /// The rule to negate.
+        rule: Box<Self>,
+    },
+}
+
+impl SmartRule {
+    /// Negate a rule.
+    pub fn negate(rule: Self) -> Self {
+        Self::Not {
+            rule: Box::new(rule),
+        }
+    }
+
+    /// Build the SQL condition on `tracks`, adding its parameters to `params`.
+    fn to_sql(&self, now: DateTime<Utc>, params: &mut Vec<Value>) -> String {
+        let since = |days: u32| Value::Text((now - TimeDelta::days(days.into())).to_rfc3339());
+        match self {
+            Self::Artist { name } => {
+                params.push(Value::Text(name.clone()));
+                "artist = ? COLLATE NOCASE".to_string()
+            }
+            Self::Album { name } => {
+                params.push(Value::Text(name.clone()));
+                "album = ? COLLATE NOCASE".to_string()
+            }
+            Self::PlayedWithin { days } => {
+                params.push(since(*days));
+                "last_played >= ?".to_string()
+            }
+            Self::PlayedAtLeast {
+                count,
+                within_days: None,
+            } => {
+                params.push(Value::Integer((*count).into()));
+                "plays >= ?".to_string()
+            }
+            Self::PlayedAtLeast {
+                count,
+                within_days: Some(days),
+            } => {
+                params.push(since(*days));
+                params.push(Value::Integer((*count).into()));
+                "(SELECT COUNT(*) FROM play_history h
+                  WHERE h.video_id = tracks.video_id AND h.played_at >= ?) >= ?"
+                    .to_string()
+            }
+            Self::AddedWithin { days } => {
+                params.push(since(*days));
+                "COALESCE(saved_at, first_played) >= ?".to_string()
+            }
+            Self::Saved => "saved_at IS NOT NULL".to_string(),
+            Self::Cached => {
+                "EXISTS (SELECT 1 FROM audio_cache a WHERE a.video_id = tracks.video_id)"
+                    .to_string()
+            }
+            // Unknown artists and albums match neither a rule nor its negation
+            // without the COALESCE
+            Self::Not { rule } => format!("NOT COALESCE(({}), 0)", rule.to_sql(now, params)),
+        }
+    }
+}
+
+/// Order of the tracks in a smart playlist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "order", rename_all = "snake_case")]
+pub enum SmartOrder {
+    /// Most played first, counting plays within the last `within_days` days if set.
+    MostPlayed {
+        /// Days to look back, or `None` for all time.
+        within_days: Option<u32>,
+    },
+    /// Most recently played first.
+    #[default]
+    RecentlyPlayed,
+    /// Most recently saved or first played first.
+    RecentlyAdded,
+    /// By title.
+    Title,
+    /// Shuffled each time the playlist is evaluated.
+    Random,
+}
+
+impl SmartOrder {
+    /// Build the SQL `ORDER BY` terms, adding their parameters to `params`.
+    fn to_sql(self, now: DateTime<Utc>, params: &mut Vec<Value>) -> String {
+        match self {
+            Self::MostPlayed { within_days: None } => "plays DESC, last_played DESC".to_string(),
+            Self::MostPlayed {
+                within_days: Some(days),
+            } => {
+                params.push(Value::Text(
+                    (now - TimeDelta::days(days.into())).to_rfc3339(),
+                ));
+                "(SELECT COUNT(*) FROM play_history h
+                  WHERE h.video_id = tracks.video_id AND h.played_at >= ?) DESC,
+                 last_played DESC"
+                    .to_string()
+            }
+            Self::RecentlyPlayed => "last_played DESC NULLS LAST".to_string(),
+            Self::RecentlyAdded => "COALESCE(saved_at, first_played) DESC".to_string(),
+            Self::Title => "title COLLATE NOCASE".to_string(),
+            Self::Random => "RANDOM()".to_string(),
+        }
+    }
+}
+
+/// A playlist of the tracks matching a set of rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartPlaylist {
+    /// Unique ID.
+    pub id: String,
+    /// Display name.
+    pub name: String,
+    /// Rules a track must all match.
+    pub rules: Vec<SmartRule>,
+    /// Order of the tracks.
+    pub order: SmartOrder,
+    /// Maximum number of tracks.
+    pub limit: usize,
+}
+
+impl SmartPlaylist {
+    /// Create a smart playlist matching every played or saved track.
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            rules: Vec::new(),
+            order: SmartOrder::default(),
+            limit: SMART_PLAYLIST_LIMIT,
+        }
+    }
+
+    /// Add a rule.
+    #[must_use]
+    pub fn with_rule(mut self, rule: SmartRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Set the order of the tracks.
+    #[must_use]
+    pub const fn with_order(mut self, order: SmartOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set the maximum number of tracks.
+    #[must_use]
+    pub const fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl CacheManager {
+    /// Save a smart playlist, replacing any with the same ID.
+    pub fn save_smart_playlist(&self, playlist: &SmartPlaylist) -> Result<()> {
+        let data = serde_json::to_string(playlist)?;
+        let now = Utc::now().to_rfc3339();
+        let db = self.db.lock();
+        db.execute(
+            "INSERT INTO smart_playlists (id, data, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)
+             ON CONFLICT(id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+            params![playlist.id, data, now],
+        )
+        .map_err(|e| {
+            Error::Cache(format!(
+                "Failed to save smart playlist {}: {e}",
+                playlist.id
+            ))
+        })?;
+        Ok(())
+    }
+
+    /// Remove a smart playlist.
+    pub fn remove_smart_playlist(&self, id: &str) -> Result<()> {
+        let db = self.db.lock();
+        db.execute("DELETE FROM smart_playlists WHERE id = ?", [id])
+            .map_err(|e| Error::Cache(format!("Failed to remove smart playlist {id}: {e}")))?;
+        Ok(())
+    }
+
+    /// Get a smart playlist by ID.
+    pub fn smart_playlist(&self, id: &str) -> Result<Option<SmartPlaylist>> {
+        let db = self.readers.get();
+        let data: Option<String> = db
+            .query_row(
+                "SELECT data FROM smart_playlists WHERE id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| Error::Cache(format!("Failed to read smart playlist {id}: {e}")))?;
+        data.map(|data| serde_json::from_str(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// List smart playlists, oldest first.
+    pub fn list_smart_playlists(&self) -> Result<Vec<SmartPlaylist>> {
+        let db = self.readers.get();
+        let rows: Vec<String> = db
+            .prepare("SELECT data FROM smart_playlists ORDER BY created_at, rowid")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(|e| Error::Cache(format!("Failed to list smart playlists: {e}")))?;
+        Ok(rows
+            .iter()
+            .filter_map(|data| {
+                serde_json::from_str(data)
+                    .inspect_err(|e| warn!("Skipping unreadable smart playlist: {e}"))
+                    .ok()
+            })
+            .collect())
+    }
+
+    /// Get the tracks currently in a smart playlist.
+    pub fn smart_playlist_tracks(&self, playlist: &SmartPlaylist) -> Result<Vec<Track>> {
+        let now = Utc::now();
+        let mut params = Vec::new();
+        let mut sql = CANDIDATES.to_string();
+        if !playlist.rules.is_empty() {
+            let conditions: Vec<String> = playlist
+                .rules
+                .iter()
+                .map(|rule| format!("({})", rule.to_sql(now, &mut params)))
+                .collect();
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY ");
+        sql.push_str(&playlist.order.to_sql(now, &mut params));
+        sql.push_str(", video_id LIMIT ?");
+        params.push(Value::Integer(
+            i64::try_from(playlist.limit).unwrap_or(i64::MAX),
+        ));
+
+        let db = self.readers.get();
+        db.prepare(&sql)
+            .and_then(|mut stmt| {
+                stmt.query_map(params_from_iter(params), |row| {
+                    let data: Option<String> = row.get(1)?;
+                    if let Some(track) = data.and_then(|data| serde_json::from_str(&data).ok()) {
+                        return Ok(track);
+                    }
+                    let mut track = Track::new(row.get::<_, String>(0)?, row.get::<_, String>(2)?);
+                    if let Some(artist) = row.get::<_, Option<String>>(3)? {
+                        track.artists.push(TrackArtist::new(artist));
+                    }
+                    track.album = row.get::<_, Option<String>>(4)?.map(TrackAlbum::new);
+                    Ok(track)
+                })?
+                .collect()
+            })
+            .map_err(|e| {
+                Error::Cache(format!(
+                    "Failed to evaluate smart playlist {}: {e}",
+                    playlist.id
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str, artist: &str) -> Track {
+        let mut track = Track::new(id, id.to_uppercase());
+        track.artists.push(TrackArtist::new(artist));
+        track
+    }
+
+    fn ids(tracks: &[Track]) -> Vec<&str> {
+        tracks.iter().map(|track| track.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_rules_match_history_and_library() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let now = Utc::now();
+        let long_ago = now - TimeDelta::days(90);
+        cache.record_play_at(&track("old", "Artist"), long_ago)?;
+        cache.record_play_at(&track("recent", "Artist"), now)?;
+        for _ in 0..3 {
+            cache.record_play_at(&track("favourite", "Other"), now)?;
+        }
+        cache.record_play_at(&track("favourite", "Other"), long_ago)?;
+        cache.save_track(&track("saved", "Artist"))?;
+
+        let most_played = SmartPlaylist::new("top", "Most played this month")
+            .with_rule(SmartRule::PlayedWithin { days: 30 })
+            .with_order(SmartOrder::MostPlayed {
+                within_days: Some(30),
+            });
+        assert_eq!(
+            ids(&cache.smart_playlist_tracks(&most_played)?),
+            ["favourite", "recent"]
+        );
+
+        let forgotten = SmartPlaylist::new("forgotten", "Artist, not played recently")
+            .with_rule(SmartRule::Artist {
+                name: "artist".to_string(),
+            })
+            .with_rule(SmartRule::negate(SmartRule::PlayedWithin { days: 30 }))
+            .with_order(SmartOrder::Title);
+        assert_eq!(
+            ids(&cache.smart_playlist_tracks(&forgotten)?),
+            ["old", "saved"]
+        );
+
+        let added = SmartPlaylist::new("added", "Added in the last 30 days")
+            .with_rule(SmartRule::AddedWithin { days: 30 })
+            .with_order(SmartOrder::Title);
+        assert_eq!(
+            ids(&cache.smart_playlist_tracks(&added)?),
+            ["recent", "saved"]
+        );
+
+        let heavy = SmartPlaylist::new("heavy", "Played 3 times this month")
+            .with_rule(SmartRule::PlayedAtLeast {
+                count: 3,
+                within_days: Some(30),
+            })
+            .with_limit(1);
+        assert_eq!(ids(&cache.smart_playlist_tracks(&heavy)?), ["favourite"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_smart_playlists_are_stored() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let playlist = SmartPlaylist::new("offline", "Saved and offline")
+            .with_rule(SmartRule::Saved)
+            .with_rule(SmartRule::Cached)
+            .with_order(SmartOrder::Random);
+        cache.save_smart_playlist(&playlist)?;
+        cache.save_smart_playlist(&SmartPlaylist::new("all", "Everything"))?;
+
+        assert_eq!(cache.smart_playlist("offline")?, Some(playlist.clone()));
+        assert_eq!(cache.list_smart_playlists()?.len(), 2);
+
+        cache.save_track(&track("a", "Artist"))?;
+        cache.save_track(&track("b", "Artist"))?;
+        cache.store_audio("b", "audio/mp4", "128kbps", &[0; 10], None)?;
+        assert_eq!(ids(&cache.smart_playlist_tracks(&playlist)?), ["b"]);
+
+        cache.remove_smart_playlist("offline")?;
+        assert_eq!(cache.smart_playlist("offline")?, None);
+        Ok(())
+    }
+}
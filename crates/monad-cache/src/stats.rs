@@ -0,0 +1,154 @@
+//! Storage breakdowns and lookup counters for cache statistics.
+//!
+//! Breakdowns are read from the database when statistics are requested.
+//! Hits and misses of audio, metadata and thumbnail lookups are counted in
+//! memory since the cache was opened, or since the counters were reset.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+
+use crate::CacheManager;
+
+/// Cached audio of one format or quality.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageBreakdown {
+    /// Format (such as `audio/webm`) or quality (such as `160kbps`).
+    pub name: String,
+    /// Number of audio files.
+    pub count: usize,
+    /// Total size in bytes.
+    pub size_bytes: u64,
+}
+
+/// Counts of cache lookups that were and were not served from the cache.
+#[derive(Debug, Default)]
+pub struct LookupCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LookupCounters {
+    /// Count a lookup.
+    pub fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the hits and misses counted so far.
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Start counting from zero.
+    fn reset(&self) {
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Break cached audio down by a column of `audio_cache`, largest first.
+pub fn breakdown(db: &Connection, column: &str) -> Vec<StorageBreakdown> {
+    let rows: rusqlite::Result<Vec<(String, i64, i64)>> = db
+        .prepare(&format!(
+            "SELECT {column}, COUNT(*), COALESCE(SUM(size_bytes), 0) FROM audio_cache
+             GROUP BY {column} ORDER BY 3 DESC, 1"
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect()
+        });
+    rows.unwrap_or_default()
+        .into_iter()
+        .map(|(name, count, size_bytes)| StorageBreakdown {
+            name,
+            count: usize::try_from(count).unwrap_or(0),
+            size_bytes: u64::try_from(size_bytes).unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Get when the oldest and newest cached audio was stored.
+pub fn cached_at_range(db: &Connection) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let parse = |time: Option<String>| {
+        DateTime::parse_from_rfc3339(&time?)
+            .ok()
+            .map(|at| at.with_timezone(&Utc))
+    };
+    db.query_row(
+        "SELECT MIN(cached_at), MAX(cached_at) FROM audio_cache",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_or((None, None), |(oldest, newest)| {
+        (parse(oldest), parse(newest))
+    })
+}
+
+impl CacheManager {
+    /// Reset the hit and miss counters reported by [`CacheManager::stats`].
+    pub fn reset_lookup_counters(&self) {
+        self.lookups.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::{Result, Track};
+
+    #[tokio::test]
+    async fn test_storage_breakdown() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        cache.store_audio("a", "audio/webm", "160kbps", &[0; 300], None)?;
+        cache.store_audio("b", "audio/webm", "128kbps", &[0; 100], None)?;
+        cache.store_audio("c", "audio/mp4", "128kbps", &[0; 50], None)?;
+        cache.store_thumbnail("https://i.ytimg.com/a.jpg", &[0; 20])?;
+        cache.pin(&Track::new("c", "C"))?;
+
+        let stats = cache.stats();
+        assert_eq!(stats.by_format.len(), 2);
+        assert_eq!(
+            stats.by_format[0],
+            StorageBreakdown {
+                name: "audio/webm".to_string(),
+                count: 2,
+                size_bytes: 400
+            }
+        );
+        assert_eq!(stats.by_quality[0].name, "160kbps");
+        assert_eq!(stats.by_quality[1].size_bytes, 150);
+        assert_eq!(stats.thumbnail_size_bytes, 20);
+        assert_eq!((stats.pinned_bytes, stats.evictable_bytes), (50, 420));
+        assert_eq!(stats.total_size_bytes(), 470);
+        assert!(stats.oldest_cached_at <= stats.newest_cached_at);
+        assert!(stats.newest_cached_at.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lookup_counters() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert_eq!(cache.stats().hit_rate(), None);
+
+        cache.store_audio("a", "audio/webm", "160kbps", b"audio", None)?;
+        cache.set_metadata("key", "value", None)?;
+        assert!(cache.read_audio("a").await?.is_some());
+        assert!(cache.get_audio_path("missing").is_none());
+        assert!(cache.get_metadata("key").is_some());
+        assert!(cache.get_metadata("other").is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(stats.hit_rate(), Some(0.5));
+        cache.reset_lookup_counters();
+        assert_eq!(cache.stats().hits, 0);
+        Ok(())
+    }
+}
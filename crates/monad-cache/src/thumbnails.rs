@@ -0,0 +1,244 @@
+//! Downloaded thumbnails and cover art.
+//!
+//! Images are stored as files in a `thumbnails` directory under the cache
+//! directory, named by the hash of their URL, and registered in the
+//! `thumbnail_cache` table so they can be found by URL while offline.
+//! Recently used images are also kept in the in-memory LRU, and images
+//! cached too long ago are evicted so changed artwork is fetched again.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Result};
+use rusqlite::{params, OptionalExtension};
+use tracing::{debug, info};
+
+use crate::events::{CacheEvent, CacheItem};
+use crate::integrity::sha256;
+use crate::CacheManager;
+
+/// Default age after which cached thumbnails are evicted.
+pub const DEFAULT_THUMBNAIL_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Key of a thumbnail in the in-memory cache.
+pub fn memory_key(url_hash: &str) -> String {
+    format!("thumbnail:{url_hash}")
+}
+
+impl CacheManager {
+    /// Get the directory holding cached thumbnails.
+    pub fn thumbnail_dir(&self) -> PathBuf {
+        self.cache_dir.join("thumbnails")
+    }
+
+    /// Store the image downloaded from `url`, returning the file it was written to.
+    pub fn store_thumbnail(&self, url: &str, data: &[u8]) -> Result<PathBuf> {
+        let dir = self.thumbnail_dir();
+        fs::create_dir_all(&dir)?;
+        let url_hash = Self::hash_url(url);
+        let path = dir.join(&url_hash);
+        fs::write(&path, data)?;
+
+        let db = self.db.lock();
+        db.execute(
+            "INSERT OR REPLACE INTO thumbnail_cache (url_hash, url, file_path, cached_at, sha256)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                url_hash,
+                url,
+                path.to_string_lossy(),
+                Utc::now().to_rfc3339(),
+                sha256(data)
+            ],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to register thumbnail: {e}")))?;
+        drop(db);
+        self.memory_cache
+            .lock()
+            .put(memory_key(&url_hash), Bytes::copy_from_slice(data));
+        self.notify(CacheEvent::ThumbnailAdded {
+            url: url.to_string(),
+        });
+        self.enforce_limits_after_write();
+        Ok(path)
+    }
+
+    /// Get the file of a cached thumbnail, if it is still on disk.
+    pub fn thumbnail_path(&self, url: &str) -> Option<PathBuf> {
+        let db = self.readers.get();
+        let path: String = db
+            .query_row(
+                "SELECT file_path FROM thumbnail_cache WHERE url_hash = ?",
+                [Self::hash_url(url)],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()??;
+        let path = PathBuf::from(path);
+        path.exists().then_some(path)
+    }
+
+    /// Get the image at `url` from memory, disk or the network, caching it on the way.
+    pub async fn get_thumbnail(&self, url: &str) -> Result<Bytes> {
+        let url_hash = Self::hash_url(url);
+        let key = memory_key(&url_hash);
+        let cached = self.memory_cache.lock().get(&key).cloned();
+        if let Some(data) = cached {
+            self.lookups.record(true);
+            self.touch_thumbnail(&url_hash);
+            return Ok(data);
+        }
+
+        if let Some(path) = self.thumbnail_path(url) {
+            let data = Bytes::from(tokio::fs::read(path).await?);
+            self.memory_cache.lock().put(key, data.clone());
+            self.lookups.record(true);
+            self.touch_thumbnail(&url_hash);
+            return Ok(data);
+        }
+
+        self.lookups.record(false);
+        self.download_thumbnail(url).await
+    }
+
+    /// Download the image at `url` and cache it.
+    pub(crate) async fn download_thumbnail(&self, url: &str) -> Result<Bytes> {
+        debug!("Downloading thumbnail {url}");
+        let data = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| Error::Network(format!("Thumbnail request failed: {e}")))?
+            .bytes()
+            .await
+            .map_err(|e| Error::Network(format!("Failed to download thumbnail: {e}")))?;
+        self.store_thumbnail(url, &data)?;
+        Ok(data)
+    }
+
+    /// Mark a thumbnail as recently used.
+    fn touch_thumbnail(&self, url_hash: &str) {
+        let db = self.db.lock();
+        if let Err(e) = db.execute(
+            "UPDATE thumbnail_cache SET last_accessed = ? WHERE url_hash = ?",
+            params![Utc::now().to_rfc3339(), url_hash],
+        ) {
+            debug!("Failed to update thumbnail access time: {e}");
+        }
+    }
+
+    /// Delete thumbnails cached more than `max_age` ago, and entries whose file is gone.
+    ///
+    /// Returns the number of thumbnails evicted.
+    pub fn evict_stale_thumbnails(&self, max_age: Duration) -> Result<usize> {
+        let cutoff = chrono::Duration::from_std(max_age)
+            .ok()
+            .and_then(|max_age| Utc::now().checked_sub_signed(max_age));
+        let db = self.db.lock();
+        let rows: Vec<(String, String, String)> = db
+            .prepare("SELECT url_hash, file_path, cached_at FROM thumbnail_cache")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                    .collect()
+            })
+            .map_err(|e| Error::Cache(format!("Failed to query thumbnails: {e}")))?;
+
+        let mut evicted = 0;
+        for (url_hash, file_path, cached_at) in rows {
+            let cached_at = DateTime::parse_from_rfc3339(&cached_at).ok();
+            let stale = cached_at
+                .zip(cutoff)
+                .is_some_and(|(at, cutoff)| at < cutoff);
+            if !stale && Path::new(&file_path).exists() {
+                continue;
+            }
+            let freed_bytes = fs::metadata(&file_path).map_or(0, |m| m.len());
+            let _ = fs::remove_file(&file_path);
+            db.execute(
+                "DELETE FROM thumbnail_cache WHERE url_hash = ?",
+                [&url_hash],
+            )
+            .map_err(|e| Error::Cache(format!("Failed to evict thumbnail: {e}")))?;
+            self.memory_cache.lock().pop(&memory_key(&url_hash));
+            self.notify(CacheEvent::Evicted {
+                item: CacheItem::Thumbnail { url_hash },
+                freed_bytes,
+            });
+            evicted += 1;
+        }
+
+        if evicted > 0 {
+            info!("Evicted {evicted} stale thumbnails");
+        }
+        Ok(evicted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// Serve `body` to a single HTTP request.
+    fn serve_once(body: &'static [u8]) -> std::io::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/thumbnail.jpg", listener.local_addr()?);
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.read(&mut [0; 4096]);
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+        Ok(url)
+    }
+
+    #[test]
+    fn test_store_thumbnail() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let url = "https://i.ytimg.com/vi/abc/maxresdefault.jpg";
+        assert_eq!(cache.thumbnail_path(url), None);
+
+        let path = cache.store_thumbnail(url, b"jpeg")?;
+        assert_eq!(cache.thumbnail_path(url), Some(path.clone()));
+        assert_eq!(fs::read(&path)?, b"jpeg");
+        assert_eq!(cache.stats().thumbnail_count, 1);
+
+        // A deleted file is no longer reported, and its entry is evicted
+        fs::remove_file(&path)?;
+        assert_eq!(cache.thumbnail_path(url), None);
+        assert_eq!(cache.evict_stale_thumbnails(DEFAULT_THUMBNAIL_MAX_AGE)?, 1);
+        assert_eq!(cache.stats().thumbnail_count, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_thumbnail() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = serve_once(b"jpeg")?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert_eq!(&cache.get_thumbnail(&url).await?[..], b"jpeg");
+        // The server is gone, so this comes from memory
+        assert_eq!(&cache.get_thumbnail(&url).await?[..], b"jpeg");
+
+        // A fresh manager reads it from disk
+        let reopened = CacheManager::with_path(dir.path().to_path_buf())?;
+        assert_eq!(&reopened.get_thumbnail(&url).await?[..], b"jpeg");
+
+        assert_eq!(reopened.evict_stale_thumbnails(Duration::ZERO)?, 1);
+        assert!(reopened.get_thumbnail(&url).await.is_err());
+        assert!(fs::read_dir(reopened.thumbnail_dir())?.next().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Album and playlist contents seen on the last visit.
+//!
+//! Each visit stores the collection's track IDs and a hash of them. On the
+//! next visit the current tracks are diffed against the stored ones, so the
+//! library can badge collections with "+3 new tracks" and queue only those.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use monad_core::{Error, Result};
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+
+use crate::CacheManager;
+
+/// Hash the track IDs of a collection, in order.
+pub fn content_hash(track_ids: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for id in track_ids {
+        hasher.update(id.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Changes to a collection since the last visit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollectionDiff {
+    /// Whether the collection had not been visited before.
+    pub first_visit: bool,
+    /// IDs of tracks added since the last visit, in collection order.
+    pub added: Vec<String>,
+    /// IDs of tracks removed since the last visit.
+    pub removed: Vec<String>,
+}
+
+impl CollectionDiff {
+    /// Diff the track IDs of two visits.
+    pub fn between(previous: &[String], current: &[String]) -> Self {
+        let before: HashSet<&String> = previous.iter().collect();
+        let after: HashSet<&String> = current.iter().collect();
+        Self {
+            first_visit: false,
+            added: current
+                .iter()
+                .filter(|id| !before.contains(id))
+                .cloned()
+                .collect(),
+            removed: previous
+                .iter()
+                .filter(|id| !after.contains(id))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Check whether any tracks were added or removed.
+    pub fn has_changes(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty()
+    }
+}
+
+/// A stored visit to a collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionVisit {
+    /// Track IDs at the time of the visit.
+    pub track_ids: Vec<String>,
+    /// Hash of the track IDs.
+    pub content_hash: String,
+    /// When the collection was visited.
+    pub visited_at: DateTime<Utc>,
+}
+
+impl CacheManager {
+    /// Get the last visit to an album or playlist.
+    pub fn last_visit(&self, collection_id: &str) -> Result<Option<CollectionVisit>> {
+        let db = self.readers.get();
+        let row = db
+            .query_row(
+                "SELECT track_ids, content_hash, visited_at FROM collection_visits
+                 WHERE collection_id = ?",
+                [collection_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| Error::Cache(format!("Failed to query visits: {e}")))?;
+
+        row.map(|(track_ids, content_hash, visited_at)| {
+            Ok(CollectionVisit {
+                track_ids: serde_json::from_str(&track_ids)?,
+                content_hash,
+                visited_at: DateTime::parse_from_rfc3339(&visited_at)
+                    .map_or_else(|_| Utc::now(), |t| t.with_timezone(&Utc)),
+            })
+        })
+        .transpose()
+    }
+
+    /// Get the changes to a collection since the last visit, without recording a visit.
+    pub fn collection_diff(
+        &self,
+        collection_id: &str,
+        track_ids: &[String],
+    ) -> Result<CollectionDiff> {
+        let Some(visit) = self.last_visit(collection_id)? else {
+            return Ok(CollectionDiff {
+                first_visit: true,
+                ..CollectionDiff::default()
+            });
+        };
+        if visit.content_hash == content_hash(track_ids) {
+            return Ok(CollectionDiff::default());
+        }
+        Ok(CollectionDiff::between(&visit.track_ids, track_ids))
+    }
+
+    /// Record a visit to a collection, returning the changes since the last one.
+    pub fn record_visit(
+        &self,
+        collection_id: &str,
+        track_ids: &[String],
+    ) -> Result<CollectionDiff> {
+        let diff = self.collection_diff(collection_id, track_ids)?;
+
+        let db = self.db.lock();
+        db.execute(
+            "INSERT INTO collection_visits (collection_id, content_hash, track_ids, visited_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(collection_id) DO UPDATE SET
+                content_hash = excluded.content_hash,
+                track_ids = excluded.track_ids,
+                visited_at = excluded.visited_at",
+            rusqlite::params![
+                collection_id,
+                content_hash(track_ids),
+                serde_json::to_string(track_ids)?,
+                Utc::now().to_rfc3339()
+            ],
+        )
+        .map_err(|e| Error::Cache(format!("Failed to record visit: {e}")))?;
+
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_diff_between() {
+        let diff = CollectionDiff::between(&ids(&["a", "b", "c"]), &ids(&["d", "a", "c", "e"]));
+        assert_eq!(diff.added, ["d", "e"]);
+        assert_eq!(diff.removed, ["b"]);
+        assert!(diff.has_changes());
+        assert_ne!(content_hash(&ids(&["a", "b"])), content_hash(&ids(&["ab"])));
+    }
+
+    #[test]
+    fn test_record_visit() -> Result<()> {
+        let dir = tempfile::tempdir().map_err(|e| Error::Cache(e.to_string()))?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+
+        let first = cache.record_visit("PL1", &ids(&["a", "b"]))?;
+        assert!(first.first_visit && !first.has_changes());
+
+        let current = ids(&["a", "b", "c", "d"]);
+        assert_eq!(cache.collection_diff("PL1", &current)?.added, ["c", "d"]);
+        assert_eq!(cache.record_visit("PL1", &current)?.added, ["c", "d"]);
+        assert_eq!(
+            cache.record_visit("PL1", &current)?,
+            CollectionDiff::default()
+        );
+        assert_eq!(cache.last_visit("PL1")?.map(|v| v.track_ids), Some(current));
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! Cache warming for albums and playlists.
+//!
+//! Warming stores an album or playlist, each of its tracks and all their
+//! artwork in one pass, so opening it offline, such as after pinning it,
+//! shows full titles, artists, durations and artwork straight away. Audio is
+//! left to downloads. The stored metadata does not expire.
+
+use chrono::Utc;
+use monad_core::{Album, Error, Playlist, Result, Track};
+use rusqlite::params;
+use serde::de::DeserializeOwned;
+use tracing::{debug, warn};
+
+use crate::pins::{PinKind, PinTarget};
+use crate::CacheManager;
+
+/// Outcome of warming an album, playlist or track.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Warming {
+    /// Metadata entries stored, for the item and its tracks.
+    pub metadata_entries: usize,
+    /// Thumbnails downloaded.
+    pub thumbnails_fetched: usize,
+    /// Thumbnails that were already cached.
+    pub thumbnails_cached: usize,
+    /// Thumbnails that could not be downloaded.
+    pub thumbnails_failed: usize,
+}
+
+/// Key of an item's metadata in the `metadata_cache` table.
+fn metadata_key(kind: PinKind, id: &str) -> String {
+    format!("{}:{id}", kind.as_str())
+}
+
+impl CacheManager {
+    /// Store the metadata and artwork of an item and its tracks, but not their audio.
+    ///
+    /// Artwork that fails to download is skipped and counted; warming again
+    /// retries it.
+    pub async fn warm<'a>(&self, target: impl Into<PinTarget<'a>>) -> Result<Warming> {
+        let target = target.into();
+        let (kind, id) = target.key();
+        let (item, tracks) = match target {
+            PinTarget::Track(track) => (serde_json::to_string(track)?, &[][..]),
+            PinTarget::Album(album) => (serde_json::to_string(album)?, &album.tracks[..]),
+            PinTarget::Playlist(playlist) => {
+                (serde_json::to_string(playlist)?, &playlist.tracks[..])
+            }
+        };
+        let mut entries = vec![(metadata_key(kind, id), item)];
+        for track in tracks {
+            entries.push((
+                metadata_key(PinKind::Track, &track.id),
+                serde_json::to_string(track)?,
+            ));
+        }
+
+        {
+            let now = Utc::now().to_rfc3339();
+            let mut db = self.db.lock();
+            let map_err = |e: rusqlite::Error| Error::Cache(format!("Failed to warm {id}: {e}"));
+            let tx = db.transaction().map_err(map_err)?;
+            for (key, value) in &entries {
+                tx.execute(
+                    "INSERT OR REPLACE INTO metadata_cache (key, value, cached_at, expires_at)
+                     VALUES (?, ?, ?, NULL)",
+                    params![key, value, now],
+                )
+                .map_err(map_err)?;
+            }
+            tx.commit().map_err(map_err)?;
+        }
+        match target {
+            PinTarget::Track(track) => self.index_track(track)?,
+            PinTarget::Album(album) => self.index_album(album)?,
+            PinTarget::Playlist(playlist) => self.index_playlist(playlist)?,
+        }
+
+        let mut warming = Warming {
+            metadata_entries: entries.len(),
+            ..Warming::default()
+        };
+        for url in target.thumbnail_urls() {
+            if self.thumbnail_path(&url).is_some() {
+                warming.thumbnails_cached += 1;
+                continue;
+            }
+            match self.download_thumbnail(&url).await {
+                Ok(_) => warming.thumbnails_fetched += 1,
+                Err(e) => {
+                    warn!("Failed to warm thumbnail {url}: {e}");
+                    warming.thumbnails_failed += 1;
+                }
+            }
+        }
+        debug!("Warmed {id}: {warming:?}");
+        Ok(warming)
+    }
+
+    /// Get the stored metadata of an item.
+    fn cached_item<T: DeserializeOwned>(&self, kind: PinKind, id: &str) -> Option<T> {
+        let value = self.get_metadata(&metadata_key(kind, id))?;
+        serde_json::from_str(&value)
+            .inspect_err(|e| warn!("Ignoring unreadable metadata of {id}: {e}"))
+            .ok()
+    }
+
+    /// Get a track stored by warming.
+    pub fn cached_track(&self, video_id: &str) -> Option<Track> {
+        self.cached_item(PinKind::Track, video_id)
+    }
+
+    /// Get an album stored by warming.
+    pub fn cached_album(&self, album_id: &str) -> Option<Album> {
+        self.cached_item(PinKind::Album, album_id)
+    }
+
+    /// Get a playlist stored by warming.
+    pub fn cached_playlist(&self, playlist_id: &str) -> Option<Playlist> {
+        self.cached_item(PinKind::Playlist, playlist_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::types::{Thumbnail, Thumbnails};
+
+    /// A URL nothing listens on, so downloads fail at once.
+    const UNREACHABLE: &str = "http://127.0.0.1:9";
+
+    fn track(id: &str) -> Track {
+        let mut track = Track::new(id, id.to_uppercase());
+        track.thumbnails = Thumbnails::new(vec![Thumbnail::new(
+            format!("{UNREACHABLE}/{id}.jpg"),
+            120,
+            120,
+        )]);
+        track
+    }
+
+    #[tokio::test]
+    async fn test_warming_stores_playlist_and_tracks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let mut playlist = Playlist::new("PL1", "Road Trip");
+        playlist.tracks = vec![track("a"), track("b")];
+        cache.store_thumbnail(&format!("{UNREACHABLE}/a.jpg"), &[0; 10])?;
+
+        let warming = cache.warm(&playlist).await?;
+        assert_eq!(warming.metadata_entries, 3);
+        assert_eq!(warming.thumbnails_cached, 1);
+        assert_eq!(warming.thumbnails_failed, 1);
+        assert_eq!(cache.cached_playlist("PL1"), Some(playlist));
+        assert_eq!(cache.cached_track("b"), Some(track("b")));
+        assert_eq!(cache.search_offline("road trip", 10)?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unwarmed_items_are_missing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CacheManager::with_path(dir.path().to_path_buf())?;
+        let album = Album::new("album1", "Discovery");
+        cache.warm(&album).await?;
+
+        assert_eq!(cache.cached_album("album1"), Some(album));
+        // Metadata is kept per kind
+        assert_eq!(cache.cached_playlist("album1"), None);
+        assert_eq!(cache.cached_track("a"), None);
+        Ok(())
+    }
+}
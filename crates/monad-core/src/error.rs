@@ -50,6 +50,13 @@ pub enum Error {
     #[error("Extraction failed: {0}")]
     ExtractionFailed(String),
 
+    #[error("{tool} {found} is outdated, version {required} or newer is required")]
+    ToolOutdated {
+        tool: String,
+        found: String,
+        required: String,
+    },
+
     // Cache errors
     #[error("Cache error: {0}")]
     Cache(String),
@@ -127,5 +134,15 @@ mod tests {
     fn test_error_display() {
         let err = Error::InnerTube("test error".into());
         assert_eq!(err.to_string(), "InnerTube API error: test error");
+
+        let err = Error::ToolOutdated {
+            tool: "yt-dlp".into(),
+            found: "2023.01.06".into(),
+            required: "2025.11.12".into(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "yt-dlp 2023.01.06 is outdated, version 2025.11.12 or newer is required"
+        );
     }
 }
@@ -0,0 +1,244 @@
+//! Formatting and parsing of durations, counts and sizes for display.
+//!
+//! Durations come in two styles: clock (`3:45`) for tracks and long
+//! (`1 hr 5 min`) for albums and playlists. Numbers use the separators of a
+//! [`Locale`], read from the environment by default.
+
+/// Number formatting conventions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    /// Separator between the integer and fractional parts.
+    pub decimal: char,
+    /// Separator between groups of thousands.
+    pub group: char,
+}
+
+/// Languages that write decimals with a comma and group with a period.
+const COMMA_DECIMAL_LANGUAGES: &[&str] = &[
+    "da", "de", "el", "es", "id", "it", "nl", "pt", "ro", "sl", "sr", "tr", "vi",
+];
+
+/// Languages that write decimals with a comma and group with a space.
+const SPACE_GROUP_LANGUAGES: &[&str] = &[
+    "bg", "cs", "fi", "fr", "hu", "lt", "lv", "nb", "no", "pl", "ru", "sk", "sv", "uk",
+];
+
+impl Locale {
+    /// English conventions: `1,234.5`.
+    pub const EN: Self = Self {
+        decimal: '.',
+        group: ',',
+    };
+
+    /// Get the conventions for a locale tag such as `de_DE.UTF-8` or `fr-FR`.
+    pub fn from_tag(tag: &str) -> Self {
+        let language = tag
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if COMMA_DECIMAL_LANGUAGES.contains(&language.as_str()) {
+            Self {
+                decimal: ',',
+                group: '.',
+            }
+        } else if SPACE_GROUP_LANGUAGES.contains(&language.as_str()) {
+            Self {
+                decimal: ',',
+                group: '\u{a0}',
+            }
+        } else {
+            Self::EN
+        }
+    }
+
+    /// Get the conventions of the user's locale from `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    pub fn system() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|tag| !tag.is_empty())
+            .map_or(Self::EN, |tag| Self::from_tag(&tag))
+    }
+
+    /// Format a number with grouped thousands and `decimals` fractional digits.
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = formatted
+            .split_once('.')
+            .map_or((formatted.as_str(), None), |(i, f)| (i, Some(f)));
+
+        let mut result = String::new();
+        if value < 0.0 && formatted.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+            result.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                result.push(self.group);
+            }
+            result.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            result.push(self.decimal);
+            result.push_str(fraction);
+        }
+        result
+    }
+
+    /// Format a count compactly, such as `950`, `12K` or `1.2M`.
+    pub fn format_count(&self, count: u64) -> String {
+        const UNITS: &[(u64, &str)] = &[(1_000_000_000, "B"), (1_000_000, "M"), (1_000, "K")];
+        let Some(&(scale, suffix)) = UNITS.iter().find(|(scale, _)| count >= *scale) else {
+            return count.to_string();
+        };
+        let value = count as f64 / scale as f64;
+        // One decimal below 10 (1.2M), none above (12M)
+        let decimals = usize::from(value < 10.0 && count % scale != 0);
+        let number = self.format_number(value, decimals);
+        let number = number
+            .strip_suffix(&format!("{}0", self.decimal))
+            .unwrap_or(&number);
+        format!("{number}{suffix}")
+    }
+
+    /// Format a size in bytes, such as `512 B`, `1.5 MB` or `2.3 GB`.
+    pub fn format_size(&self, bytes: u64) -> String {
+        const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+        if bytes < 1024 {
+            return format!("{bytes} B");
+        }
+        let mut value = bytes as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        let decimals = usize::from(value < 100.0);
+        format!("{} {}", self.format_number(value, decimals), UNITS[unit])
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::EN
+    }
+}
+
+/// Format seconds as a clock: `3:45` or `1:02:03`.
+pub fn format_clock(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let seconds = seconds % 60;
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Format seconds in words, to the minute past an hour: `45 sec`, `3 min 45 sec`, `1 hr 5 min`.
+pub fn format_duration_long(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let seconds = seconds % 60;
+
+    match (hours, minutes, seconds) {
+        (0, 0, s) => format!("{s} sec"),
+        (0, m, 0) => format!("{m} min"),
+        (0, m, s) => format!("{m} min {s} sec"),
+        (h, 0, _) => format!("{h} hr"),
+        (h, m, _) => format!("{h} hr {m} min"),
+    }
+}
+
+/// Parse a clock such as `3:45` or `1:23:45` into seconds.
+pub fn parse_clock(text: &str) -> Option<u64> {
+    let parts = text
+        .trim()
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match parts.as_slice() {
+        [minutes, seconds] => Some(minutes * 60 + seconds),
+        [hours, minutes, seconds] => Some(hours * 3600 + minutes * 60 + seconds),
+        _ => None,
+    }
+}
+
+/// Parse a count such as `1.2M subscribers`, `12K` or `1,234 views`.
+pub fn parse_count(text: &str) -> Option<u64> {
+    let number = text.split_whitespace().next()?.replace(',', "");
+    let (digits, scale) = match number.char_indices().last()? {
+        (i, 'K' | 'k') => (&number[..i], 1_000.0),
+        (i, 'M' | 'm') => (&number[..i], 1_000_000.0),
+        (i, 'B' | 'b') => (&number[..i], 1_000_000_000.0),
+        _ => (number.as_str(), 1.0),
+    };
+    let value: f64 = digits.parse().ok()?;
+    (value >= 0.0).then(|| (value * scale).round() as u64)
+}
+
+/// Extract the count from subscriber text such as `Artist • 1.2M subscribers`.
+pub fn subscriber_count(text: &str) -> Option<String> {
+    text.split('•')
+        .map(str::trim)
+        .find(|part| part.contains("subscriber"))
+        .and_then(|part| part.split_whitespace().next())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(format_clock(225), "3:45");
+        assert_eq!(format_clock(3723), "1:02:03");
+        assert_eq!(format_duration_long(45), "45 sec");
+        assert_eq!(format_duration_long(225), "3 min 45 sec");
+        assert_eq!(format_duration_long(3900), "1 hr 5 min");
+        assert_eq!(format_duration_long(7200), "2 hr");
+
+        assert_eq!(parse_clock("3:45"), Some(225));
+        assert_eq!(parse_clock("1:23:45"), Some(5025));
+        assert_eq!(parse_clock("0:30"), Some(30));
+        assert_eq!(parse_clock("soon"), None);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let de = Locale::from_tag("de_DE.UTF-8");
+        assert_eq!(Locale::EN.format_number(1_234_567.891, 2), "1,234,567.89");
+        assert_eq!(de.format_number(1_234.5, 1), "1.234,5");
+        assert_eq!(
+            Locale::from_tag("fr-FR").format_number(1_234.0, 0),
+            "1\u{a0}234"
+        );
+
+        assert_eq!(Locale::EN.format_count(950), "950");
+        assert_eq!(Locale::EN.format_count(1_200_000), "1.2M");
+        assert_eq!(Locale::EN.format_count(3_000), "3K");
+        assert_eq!(Locale::EN.format_count(12_345), "12K");
+        assert_eq!(de.format_count(1_500), "1,5K");
+
+        assert_eq!(Locale::EN.format_size(512), "512 B");
+        assert_eq!(Locale::EN.format_size(1_572_864), "1.5 MB");
+        assert_eq!(Locale::EN.format_size(2 * 1024 * 1024 * 1024), "2.0 GB");
+    }
+
+    #[test]
+    fn test_parse_counts() {
+        assert_eq!(parse_count("1.2M subscribers"), Some(1_200_000));
+        assert_eq!(parse_count("12K"), Some(12_000));
+        assert_eq!(parse_count("1,234 views"), Some(1_234));
+        assert_eq!(parse_count("many"), None);
+
+        assert_eq!(
+            subscriber_count("Artist • 1.2M subscribers").as_deref(),
+            Some("1.2M")
+        );
+        assert_eq!(subscriber_count("Artist • Album"), None);
+    }
+}
@@ -3,7 +3,13 @@
 //! Core types, traits, and error handling for the Monad `YouTube` Music client.
 
 pub mod error;
+pub mod format;
+pub mod progress;
+pub mod snapshot;
 pub mod types;
 
 pub use error::{Error, HttpError, Result};
+pub use format::Locale;
+pub use progress::{NoProgress, Progress, ProgressSink, ProgressUnit};
+pub use snapshot::{FileSink, SnapshotSink, SnapshotTrack, WidgetSnapshot};
 pub use types::*;
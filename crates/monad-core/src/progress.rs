@@ -0,0 +1,140 @@
+//! Progress reporting for long-running operations.
+//!
+//! Downloads, cache maintenance and batch jobs all report a [`Progress`]
+//! to a [`ProgressSink`], so the UI can show any of them the same way.
+
+use serde::{Deserialize, Serialize};
+
+/// What a [`Progress`] counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProgressUnit {
+    /// Bytes transferred or processed.
+    #[default]
+    Bytes,
+    /// Whole items, such as tracks.
+    Items,
+}
+
+/// Progress of a long-running operation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// Amount done so far.
+    pub completed: u64,
+    /// Total amount (if known).
+    pub total: Option<u64>,
+    /// What `completed` and `total` count.
+    pub unit: ProgressUnit,
+    /// Units per second (if known).
+    pub rate: Option<f64>,
+    /// Item being worked on (if any).
+    pub current: Option<String>,
+}
+
+impl Progress {
+    /// Create progress counted in bytes.
+    pub const fn bytes(completed: u64, total: Option<u64>) -> Self {
+        Self {
+            completed,
+            total,
+            unit: ProgressUnit::Bytes,
+            rate: None,
+            current: None,
+        }
+    }
+
+    /// Create progress counted in items.
+    pub const fn items(completed: u64, total: u64) -> Self {
+        Self {
+            completed,
+            total: Some(total),
+            unit: ProgressUnit::Items,
+            rate: None,
+            current: None,
+        }
+    }
+
+    /// Set the rate in units per second.
+    #[must_use]
+    pub const fn with_rate(mut self, rate: Option<f64>) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the item being worked on.
+    #[must_use]
+    pub fn with_current(mut self, current: impl Into<String>) -> Self {
+        self.current = Some(current.into());
+        self
+    }
+
+    /// Get the fraction done, 0.0 to 1.0 (if the total is known).
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> Option<f64> {
+        self.total
+            .filter(|&total| total > 0)
+            .map(|total| (self.completed as f64 / total as f64).min(1.0))
+    }
+
+    /// Get the percentage done, 0.0 to 100.0 (if the total is known).
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn percent(&self) -> Option<f32> {
+        self.fraction().map(|fraction| (fraction * 100.0) as f32)
+    }
+
+    /// Check whether the total is known and reached.
+    pub fn is_complete(&self) -> bool {
+        self.total.is_some_and(|total| self.completed >= total)
+    }
+}
+
+/// Receives progress updates from a long-running operation.
+pub trait ProgressSink: Send + Sync {
+    /// Report the latest progress.
+    fn report(&self, progress: Progress);
+}
+
+impl<F: Fn(Progress) + Send + Sync> ProgressSink for F {
+    fn report(&self, progress: Progress) {
+        self(progress);
+    }
+}
+
+/// A sink that discards progress.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn report(&self, _progress: Progress) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_percent() {
+        let progress = Progress::bytes(512, Some(2048));
+        assert_eq!(progress.percent(), Some(25.0));
+        assert!(!progress.is_complete());
+        assert_eq!(Progress::bytes(512, None).percent(), None);
+        assert_eq!(Progress::bytes(0, Some(0)).fraction(), None);
+        assert!(Progress::items(3, 3).is_complete());
+        assert_eq!(Progress::items(4, 3).fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn test_closure_sink() {
+        let reports = Mutex::new(Vec::new());
+        let sink = |progress: Progress| {
+            if let Ok(mut reports) = reports.lock() {
+                reports.push(progress);
+            }
+        };
+        sink.report(Progress::items(1, 2).with_current("abc"));
+        NoProgress.report(Progress::items(2, 2));
+        let reports = reports.into_inner().unwrap_or_default();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].current.as_deref(), Some("abc"));
+    }
+}
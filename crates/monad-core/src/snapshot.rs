@@ -0,0 +1,156 @@
+//! Playback snapshots for external widgets.
+//!
+//! A [`WidgetSnapshot`] summarises what is playing, what is next, and what was
+//! played recently. Snapshots are handed to [`SnapshotSink`]s, so OS widgets,
+//! conky, or rainmeter setups can show Monad state without embedding the app.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, Track};
+
+/// A track as shown by a widget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotTrack {
+    /// `YouTube` video ID.
+    pub id: String,
+    /// Track title.
+    pub title: String,
+    /// Artist name(s).
+    pub artist: String,
+    /// Album name (if known).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// Artwork URL (if known).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artwork_url: Option<String>,
+    /// When the track was played (recent history only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub played_at: Option<DateTime<Utc>>,
+}
+
+impl From<&Track> for SnapshotTrack {
+    fn from(track: &Track) -> Self {
+        Self {
+            id: track.id.clone(),
+            title: track.title.clone(),
+            artist: track.artists_display(),
+            album: track.album_name().map(str::to_string),
+            artwork_url: Some(track.hq_thumbnail_url()),
+            played_at: None,
+        }
+    }
+}
+
+/// Point-in-time summary of playback state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WidgetSnapshot {
+    /// When the snapshot was taken.
+    pub generated_at: DateTime<Utc>,
+    /// Playback status (`playing`, `paused`, `buffering`, or `stopped`).
+    pub status: String,
+    /// Position in the current track in seconds.
+    pub position_secs: f64,
+    /// Duration of the current track in seconds (if known).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// The current track.
+    pub now_playing: Option<SnapshotTrack>,
+    /// Tracks queued to play next.
+    pub next_up: Vec<SnapshotTrack>,
+    /// Recently played tracks, most recent first.
+    pub recent: Vec<SnapshotTrack>,
+}
+
+impl WidgetSnapshot {
+    /// Create an empty, stopped snapshot.
+    pub fn new(status: impl Into<String>) -> Self {
+        Self {
+            generated_at: Utc::now(),
+            status: status.into(),
+            position_secs: 0.0,
+            duration_secs: None,
+            now_playing: None,
+            next_up: Vec::new(),
+            recent: Vec::new(),
+        }
+    }
+
+    /// Serialize to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Destination for widget snapshots.
+pub trait SnapshotSink: Send + Sync {
+    /// Name of the sink, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Publish a snapshot.
+    fn publish(&self, snapshot: &WidgetSnapshot) -> Result<()>;
+}
+
+/// Writes each snapshot to a JSON file, replacing it atomically.
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    /// Create a sink writing to `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Get the snapshot file path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl SnapshotSink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn publish(&self, snapshot: &WidgetSnapshot) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Readers polling the file never see a partial write
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, snapshot.to_json()?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TrackArtist;
+
+    #[test]
+    fn test_file_sink_writes_snapshot() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sink = FileSink::new(dir.path().join("widgets/now-playing.json"));
+
+        let mut track = Track::new("abc", "Song");
+        track.artists.push(TrackArtist::new("Artist"));
+
+        let mut snapshot = WidgetSnapshot::new("playing");
+        snapshot.now_playing = Some(SnapshotTrack::from(&track));
+        snapshot.position_secs = 12.5;
+        sink.publish(&snapshot)?;
+
+        let written: WidgetSnapshot = serde_json::from_str(&fs::read_to_string(sink.path())?)?;
+        assert_eq!(written, snapshot);
+        assert!(!sink.path().with_extension("json.tmp").exists());
+        Ok(())
+    }
+}
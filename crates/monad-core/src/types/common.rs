@@ -49,7 +49,7 @@ impl Thumbnails {
             .min_by_key(|t| (i64::from(t.width * t.height) - i64::from(target_area)).abs())
     }
 
-    pub const fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 }
@@ -77,16 +77,17 @@ impl Duration {
 
     /// Format as MM:SS or HH:MM:SS.
     pub fn format(&self) -> String {
-        let total_secs = self.0;
-        let hours = total_secs / 3600;
-        let minutes = (total_secs % 3600) / 60;
-        let seconds = total_secs % 60;
-
-        if hours > 0 {
-            format!("{hours}:{minutes:02}:{seconds:02}")
-        } else {
-            format!("{minutes}:{seconds:02}")
-        }
+        crate::format::format_clock(self.0)
+    }
+
+    /// Format in words, such as `3 min 45 sec` or `1 hr 5 min`.
+    pub fn format_long(&self) -> String {
+        crate::format::format_duration_long(self.0)
+    }
+
+    /// Parse MM:SS or HH:MM:SS.
+    pub fn parse(text: &str) -> Option<Self> {
+        crate::format::parse_clock(text).map(Self)
     }
 }
 
@@ -146,7 +147,7 @@ impl Volume {
     pub const MAX: Self = Self(1.0);
     pub const DEFAULT: Self = Self(1.0);
 
-    pub const fn new(value: f32) -> Self {
+    pub fn new(value: f32) -> Self {
         Self(value.clamp(0.0, 1.0))
     }
 
@@ -217,6 +218,11 @@ mod tests {
         assert_eq!(Duration::from_seconds(65).format(), "1:05");
         assert_eq!(Duration::from_seconds(3661).format(), "1:01:01");
         assert_eq!(Duration::from_seconds(0).format(), "0:00");
+        assert_eq!(Duration::from_seconds(3900).format_long(), "1 hr 5 min");
+        assert_eq!(
+            Duration::parse("1:23:45"),
+            Some(Duration::from_seconds(5025))
+        );
     }
 
     #[test]
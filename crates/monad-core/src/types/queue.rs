@@ -85,12 +85,12 @@ impl Queue {
     }
 
     /// Get the number of items in the queue.
-    pub const fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.items.len()
     }
 
     /// Check if the queue is empty.
-    pub const fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
 
@@ -262,6 +262,34 @@ impl Queue {
         self.shuffle
     }
 
+    /// Get up to `limit` items that will play after the current one, in play order.
+    pub fn upcoming(&self, limit: usize) -> Vec<&QueueItem> {
+        let Some(current) = self.current_index else {
+            return Vec::new();
+        };
+
+        let order: Vec<usize> = if self.shuffle {
+            self.shuffle_order.clone()
+        } else {
+            (0..self.items.len()).collect()
+        };
+        let position = order.iter().position(|&i| i == current).unwrap_or(0);
+
+        // Repeating the whole queue wraps around to the start
+        let wrapped = if self.repeat_mode == RepeatMode::All {
+            &order[..position]
+        } else {
+            &[]
+        };
+
+        order[position + 1..]
+            .iter()
+            .chain(wrapped)
+            .take(limit)
+            .filter_map(|&i| self.items.get(i))
+            .collect()
+    }
+
     fn next_sequential_index(&self) -> Option<usize> {
         let current = self.current_index.unwrap_or(0);
 
@@ -408,6 +436,29 @@ mod tests {
         Track::new(id, format!("Track {id}"))
     }
 
+    #[test]
+    fn test_queue_upcoming() {
+        let mut queue = Queue::new();
+        let items = ["1", "2", "3"]
+            .iter()
+            .map(|id| QueueItem::from_track(make_track(id)))
+            .collect();
+        queue.set(items, 1);
+
+        let ids = |queue: &Queue| -> Vec<String> {
+            queue
+                .upcoming(5)
+                .iter()
+                .map(|item| item.track.id.clone())
+                .collect()
+        };
+        assert_eq!(ids(&queue), ["3"]);
+
+        queue.set_repeat_mode(RepeatMode::All);
+        assert_eq!(ids(&queue), ["3", "1"]);
+        assert_eq!(queue.upcoming(1).len(), 1);
+    }
+
     #[test]
     fn test_queue_push_and_current() {
         let mut queue = Queue::new();
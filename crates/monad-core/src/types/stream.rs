@@ -5,15 +5,60 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::progress::Progress;
+
 /// Chunk of streaming audio data used for streaming playback.
 #[derive(Debug, Clone)]
 pub enum StreamChunk {
     /// Audio data chunk (compressed audio bytes).
     Data(Vec<u8>),
+    /// Download progress update, in bytes.
+    Progress(Progress),
     /// Download completed successfully.
     Complete,
     /// Error occurred during download.
     Error(String),
+    /// Download stopped receiving data or ran past its time limit, and was abandoned.
+    Stalled(String),
+}
+
+impl StreamChunk {
+    /// Create a progress chunk from bytes downloaded and speed in bytes per second.
+    pub const fn progress(bytes: u64, total: Option<u64>, speed: Option<f64>) -> Self {
+        Self::Progress(Progress::bytes(bytes, total).with_rate(speed))
+    }
+}
+
+/// Part of a track to play, used to skip intros and outros of music videos.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlaybackSpan {
+    /// Start position in seconds.
+    pub start: f64,
+    /// End position in seconds (plays to the end if `None`).
+    pub end: Option<f64>,
+}
+
+impl PlaybackSpan {
+    /// Create a span from `start` to `end`.
+    pub const fn new(start: f64, end: Option<f64>) -> Self {
+        Self { start, end }
+    }
+
+    /// Check whether the span covers the whole track.
+    pub fn is_full(&self) -> bool {
+        self.start <= 0.0 && self.end.is_none()
+    }
+}
+
+/// A section of a track to skip, such as a spoken intro in a music video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkipSegment {
+    /// Start position in seconds.
+    pub start: f64,
+    /// End position in seconds.
+    pub end: f64,
+    /// Category of the segment, e.g. `music_offtopic`.
+    pub category: String,
 }
 
 /// Information about an audio stream.
@@ -237,7 +282,7 @@ impl StreamCollection {
         streams
     }
 
-    pub const fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.streams.is_empty()
     }
 }
@@ -269,6 +314,18 @@ mod tests {
         assert!(opus_high.quality_score() > mp3_max.quality_score());
     }
 
+    #[test]
+    fn test_stream_chunk_progress_percent() {
+        let chunk = StreamChunk::progress(512, Some(2048), Some(1024.0));
+        assert!(matches!(
+            chunk,
+            StreamChunk::Progress(ref p) if p.percent() == Some(25.0) && p.rate == Some(1024.0)
+        ));
+
+        let chunk = StreamChunk::progress(512, None, None);
+        assert!(matches!(chunk, StreamChunk::Progress(p) if p.percent().is_none()));
+    }
+
     #[test]
     fn test_stream_collection_best() {
         let collection = StreamCollection::new(vec![
@@ -0,0 +1,274 @@
+//! Cover art for cached tracks.
+//!
+//! The highest-resolution thumbnail of a track is downloaded during
+//! extraction and kept in the cache database's thumbnail cache, so artwork
+//! shows while offline. Optionally it is also embedded into the cached audio
+//! with ffmpeg: as an attached picture in M4A, or a Matroska attachment in
+//! `WebM`. Embedding happens once both the audio and its artwork are cached.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use monad_core::{Error, Result};
+use tokio::process::Command as AsyncCommand;
+use tracing::{debug, info, warn};
+
+use crate::cache::AudioCache;
+use crate::detect_audio_mime;
+use crate::metadata::TrackMetadata;
+use crate::network::NetworkSettings;
+
+/// Detect the MIME type of an image from its first bytes.
+fn image_mime(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG") {
+        Some("image/png")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// ffmpeg arguments embedding `image` into `audio`, written to `output`.
+///
+/// Returns `None` if the container cannot hold the image.
+fn embed_args(
+    audio: &Path,
+    audio_mime: &str,
+    image: &Path,
+    image_mime: &str,
+    output: &Path,
+) -> Option<Vec<String>> {
+    let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error", "-y", "-i"]
+        .map(String::from)
+        .to_vec();
+    args.push(audio.display().to_string());
+    match (audio_mime, image_mime) {
+        ("audio/mp4", "image/jpeg" | "image/png") => {
+            args.extend(["-i".to_string(), image.display().to_string()]);
+            args.extend(
+                [
+                    "-map",
+                    "0:a",
+                    "-map",
+                    "1:v",
+                    "-c",
+                    "copy",
+                    "-disposition:v:0",
+                    "attached_pic",
+                    "-f",
+                    "mp4",
+                ]
+                .map(String::from),
+            );
+        }
+        ("audio/webm", _) => {
+            args.extend(["-map", "0:a", "-c", "copy", "-attach"].map(String::from));
+            args.push(image.display().to_string());
+            args.extend([
+                "-metadata:s:t".to_string(),
+                format!("mimetype={image_mime}"),
+                "-f".to_string(),
+                "matroska".to_string(),
+            ]);
+        }
+        _ => return None,
+    }
+    args.push(output.display().to_string());
+    Some(args)
+}
+
+/// Download an image.
+async fn fetch_image(network: &NetworkSettings, url: &str) -> Result<Vec<u8>> {
+    let response = network
+        .http_client()?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| Error::Network(format!("Artwork request failed: {e}")))?
+        .error_for_status()
+        .map_err(|e| Error::Network(format!("Artwork request failed: {e}")))?;
+    let image = response
+        .bytes()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to download artwork: {e}")))?;
+    Ok(image.to_vec())
+}
+
+/// Download a track's cover art into the thumbnail cache, unless it is already there.
+///
+/// Needs a cache database, which holds the thumbnail cache. Returns the image file.
+pub async fn cache_artwork(
+    cache: &AudioCache,
+    network: &NetworkSettings,
+    video_id: &str,
+    metadata: &TrackMetadata,
+) -> Option<PathBuf> {
+    let index = cache.index()?;
+    let url = metadata.thumbnail_url.as_deref()?;
+    if let Some(path) = index.thumbnail_path(url) {
+        return Some(path);
+    }
+
+    let image = fetch_image(network, url)
+        .await
+        .inspect_err(|e| debug!("No artwork for {video_id}: {e}"))
+        .ok()?;
+    let path = index
+        .store_thumbnail(url, &image)
+        .inspect_err(|e| warn!("Failed to cache artwork for {video_id}: {e}"))
+        .ok()?;
+    debug!("Cached {} bytes of artwork for {video_id}", image.len());
+
+    // Audio cached before its artwork arrived is embedded now
+    if cache.contains(video_id) {
+        cache.schedule_processing(video_id);
+    }
+    Some(path)
+}
+
+impl AudioCache {
+    /// Get the cached cover art of a track.
+    pub fn artwork_path(&self, video_id: &str) -> Option<PathBuf> {
+        let url = self.read_metadata(video_id)?.thumbnail_url?;
+        self.index()?.thumbnail_path(&url)
+    }
+
+    /// Embed a track's cached cover art into its cached audio.
+    ///
+    /// Returns whether the audio was replaced.
+    pub async fn embed_artwork(&self, video_id: &str) -> Result<bool> {
+        let Some(ffmpeg) = self.artwork_ffmpeg() else {
+            return Ok(false);
+        };
+        let Some(image) = self.artwork_path(video_id) else {
+            return Ok(false);
+        };
+        let _rewrite = self.lock_rewrite().await;
+        let audio = self.path(video_id);
+        // The track may have been evicted since it was cached
+        let Ok(head) = read_head(&audio) else {
+            return Ok(false);
+        };
+        let audio_mime = detect_audio_mime(&head);
+        let Some(image_mime) = read_head(&image).ok().as_deref().and_then(image_mime) else {
+            debug!("Not embedding artwork of {video_id}, unknown image format");
+            return Ok(false);
+        };
+        let output = audio.with_extension("embed");
+        let Some(args) = embed_args(&audio, &audio_mime, &image, image_mime, &output) else {
+            debug!("Not embedding {image_mime} artwork in {audio_mime} audio of {video_id}");
+            return Ok(false);
+        };
+
+        let result = AsyncCommand::new(ffmpeg)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| Error::ExtractionFailed(format!("Failed to run ffmpeg: {e}")))?;
+        let embedded = fs::read(&output);
+        let _ = fs::remove_file(&output);
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(Error::ExtractionFailed(format!(
+                "ffmpeg failed: {}",
+                stderr.lines().next().unwrap_or("Unknown error")
+            )));
+        }
+        let embedded = embedded?;
+        if embedded.is_empty() {
+            return Err(Error::ExtractionFailed(
+                "ffmpeg returned empty data".to_string(),
+            ));
+        }
+
+        self.replace_audio(video_id, &embedded)?;
+        info!("Embedded artwork in {video_id}");
+        Ok(true)
+    }
+}
+
+/// Read the first bytes of a file, enough to detect its format.
+fn read_head(path: &Path) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut head = Vec::with_capacity(16);
+    fs::File::open(path)?.take(16).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_args() {
+        let (audio, image, output) = (Path::new("a"), Path::new("i"), Path::new("o"));
+        let m4a = embed_args(audio, "audio/mp4", image, "image/jpeg", output).unwrap_or_default();
+        assert!(m4a
+            .windows(2)
+            .any(|w| w == ["-disposition:v:0", "attached_pic"]));
+        let webm = embed_args(audio, "audio/webm", image, "image/webp", output).unwrap_or_default();
+        assert!(webm
+            .windows(2)
+            .any(|w| w == ["-metadata:s:t", "mimetype=image/webp"]));
+        assert_eq!(webm.last().map(String::as_str), Some("o"));
+        // MP4 cover art must be JPEG or PNG
+        assert_eq!(
+            embed_args(audio, "audio/mp4", image, "image/webp", output),
+            None
+        );
+        assert_eq!(image_mime(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("image/jpeg"));
+        assert_eq!(image_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_embed_artwork() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir()?;
+        // Stands in for ffmpeg, appending the attachment to the audio
+        let fake_ffmpeg = dir.path().join("ffmpeg");
+        fs::write(
+            &fake_ffmpeg,
+            "#!/bin/sh\nfor last; do :; done\ncat \"$6\" \"${12}\" > \"$last\"\n",
+        )?;
+        fs::set_permissions(&fake_ffmpeg, fs::Permissions::from_mode(0o755))?;
+
+        let mut cache = AudioCache::new(dir.path().join("audio"), 1024 * 1024);
+        let index = monad_cache::CacheManager::with_path(dir.path().join("db"))?;
+        cache.set_index(Arc::new(index));
+        let webm = [0x1A, 0x45, 0xDF, 0xA3, 0, 0, 0, 0, 0, 0, 0, 0];
+        cache.write("abc", &webm);
+        // Nothing to embed without artwork, or with embedding disabled
+        assert!(!cache.embed_artwork("abc").await?);
+
+        let url = "https://i.ytimg.com/vi/abc/maxresdefault.jpg";
+        let metadata = TrackMetadata {
+            thumbnail_url: Some(url.to_string()),
+            ..TrackMetadata::default()
+        };
+        cache.write_metadata("abc", &metadata);
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0];
+        if let Some(index) = cache.index() {
+            index.store_thumbnail(url, &jpeg)?;
+        }
+        assert!(cache.artwork_path("abc").is_some());
+        assert!(!cache.embed_artwork("abc").await?);
+
+        cache.set_artwork_ffmpeg(Some(fake_ffmpeg));
+        assert!(cache.embed_artwork("abc").await?);
+        let expected = [&webm[..], &jpeg[..]].concat();
+        assert_eq!(cache.read("abc").as_deref(), Some(&expected[..]));
+        Ok(())
+    }
+}
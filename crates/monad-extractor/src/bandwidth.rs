@@ -0,0 +1,196 @@
+//! Stream quality chosen from the measured bandwidth.
+//!
+//! The throughput of every finished streaming download feeds a rolling
+//! estimate, saved next to the audio cache so it survives restarts. When the
+//! connection cannot keep up with 256 kbps AAC, a lower-bitrate format is
+//! requested instead, and on very slow links pre-caching is skipped so it
+//! does not compete with playback. A fixed quality turns this off.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use monad_core::AudioQuality;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Weight of the newest download in the rolling estimate.
+const SMOOTHING: f64 = 0.3;
+
+/// Downloads smaller than this say too little about the connection.
+const MIN_SAMPLE_BYTES: u64 = 256 * 1024;
+
+/// Throughput needed per unit of bitrate for playback to keep ahead.
+const HEADROOM: f64 = 2.0;
+
+/// Throughput in bytes per second needed to stream a quality.
+fn required_rate(quality: AudioQuality) -> f64 {
+    f64::from(quality.bitrate_estimate()) * 1000.0 / 8.0 * HEADROOM
+}
+
+/// Rolling estimate of download throughput.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BandwidthEstimate {
+    /// Smoothed throughput in bytes per second.
+    pub bytes_per_sec: f64,
+    /// Number of downloads measured.
+    pub samples: u64,
+}
+
+impl BandwidthEstimate {
+    /// Add the throughput of a download.
+    fn record(&mut self, bytes_per_sec: f64) {
+        self.bytes_per_sec = if self.samples == 0 {
+            bytes_per_sec
+        } else {
+            SMOOTHING.mul_add(bytes_per_sec, (1.0 - SMOOTHING) * self.bytes_per_sec)
+        };
+        self.samples += 1;
+    }
+
+    /// Best quality the connection can stream, or `None` before any measurement.
+    pub fn quality(&self) -> Option<AudioQuality> {
+        if self.samples == 0 {
+            return None;
+        }
+        let quality = [AudioQuality::High, AudioQuality::Medium]
+            .into_iter()
+            .find(|&quality| self.bytes_per_sec >= required_rate(quality));
+        Some(quality.unwrap_or(AudioQuality::Low))
+    }
+
+    /// Check whether the connection is fast enough to download tracks ahead of time.
+    pub fn allows_precaching(&self) -> bool {
+        self.samples == 0 || self.bytes_per_sec >= required_rate(AudioQuality::Low)
+    }
+}
+
+/// Measures download throughput and picks the stream quality from it.
+#[derive(Debug, Clone, Default)]
+pub struct Bandwidth {
+    /// File the estimate is saved to.
+    path: Option<PathBuf>,
+    estimate: Arc<Mutex<BandwidthEstimate>>,
+    /// Fixed quality, overriding the estimate.
+    fixed: Option<AudioQuality>,
+}
+
+impl Bandwidth {
+    /// Load the estimate saved at `path`, if any.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let estimate = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            estimate: Arc::new(Mutex::new(estimate)),
+            fixed: None,
+        }
+    }
+
+    /// Always stream `quality` (or follow the estimate for `None`).
+    pub const fn set_fixed(&mut self, quality: Option<AudioQuality>) {
+        self.fixed = quality;
+    }
+
+    /// Get the current estimate.
+    pub fn estimate(&self) -> BandwidthEstimate {
+        *self.estimate.lock()
+    }
+
+    /// Record a finished download of `bytes` that took `elapsed`, and save the estimate.
+    pub fn record(&self, bytes: u64, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if bytes < MIN_SAMPLE_BYTES || secs <= 0.0 {
+            return;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let rate = bytes as f64 / secs;
+        let estimate = {
+            let mut estimate = self.estimate.lock();
+            estimate.record(rate);
+            *estimate
+        };
+        debug!(
+            "Download ran at {:.0} KB/s, estimate now {:.0} KB/s",
+            rate / 1024.0,
+            estimate.bytes_per_sec / 1024.0
+        );
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string(&estimate)
+            .map_err(std::io::Error::other)
+            .and_then(|json| fs::write(path, json));
+        if let Err(e) = result {
+            warn!("Failed to save bandwidth estimate: {e}");
+        }
+    }
+
+    /// Quality to stream new downloads at.
+    pub fn quality(&self) -> AudioQuality {
+        self.fixed
+            .or_else(|| self.estimate().quality())
+            .unwrap_or_default()
+    }
+
+    /// Check whether tracks should be downloaded ahead of time.
+    pub fn allows_precaching(&self) -> bool {
+        self.fixed.is_some() || self.estimate().allows_precaching()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_follows_estimate() {
+        let mut estimate = BandwidthEstimate::default();
+        assert_eq!(estimate.quality(), None);
+        assert!(estimate.allows_precaching());
+
+        // Fast enough for 256 kbps with headroom
+        estimate.record(100_000.0);
+        assert_eq!(estimate.quality(), Some(AudioQuality::High));
+
+        // A few slow downloads pull the estimate down
+        for _ in 0..10 {
+            estimate.record(20_000.0);
+        }
+        assert_eq!(estimate.quality(), Some(AudioQuality::Low));
+        assert!(estimate.allows_precaching());
+        for _ in 0..10 {
+            estimate.record(5_000.0);
+        }
+        assert!(!estimate.allows_precaching());
+    }
+
+    #[test]
+    fn test_estimate_is_saved() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bandwidth.json");
+        let bandwidth = Bandwidth::load(Some(path.clone()));
+        assert_eq!(bandwidth.quality(), AudioQuality::High);
+
+        // Small downloads are ignored
+        bandwidth.record(1024, Duration::from_secs(1));
+        assert_eq!(bandwidth.estimate().samples, 0);
+        bandwidth.record(MIN_SAMPLE_BYTES * 4, Duration::from_secs(2 * 60));
+
+        let mut reloaded = Bandwidth::load(Some(path));
+        assert_eq!(reloaded.estimate(), bandwidth.estimate());
+        assert_eq!(reloaded.quality(), AudioQuality::Low);
+        assert!(!reloaded.allows_precaching());
+
+        reloaded.set_fixed(Some(AudioQuality::High));
+        assert_eq!(reloaded.quality(), AudioQuality::High);
+        assert!(reloaded.allows_precaching());
+        Ok(())
+    }
+}
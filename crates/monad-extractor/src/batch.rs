@@ -0,0 +1,359 @@
+//! Batch downloads of whole playlists into the cache.
+//!
+//! Backs "make available offline": every track not already cached is
+//! downloaded through the same streaming path as playback, so interrupted
+//! downloads resume and native extraction falls back to yt-dlp. A failed
+//! track is recorded and the batch moves on.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use monad_core::{NoProgress, Progress, ProgressSink, Result, StreamChunk, Track};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{download_stream, Extractor};
+
+/// Default number of tracks downloaded at once.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 2;
+
+/// Bytes downloaded between updates of a track's progress in the cache index.
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// How a batch download runs.
+#[derive(Clone)]
+pub struct BatchOptions {
+    /// Number of tracks downloaded at once.
+    pub concurrency: usize,
+    /// Receives progress in tracks finished, naming the last one.
+    pub progress: Arc<dyn ProgressSink>,
+    /// Stops the batch when cancelled. Unfinished downloads resume next time.
+    pub cancel: CancellationToken,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_BATCH_CONCURRENCY,
+            progress: Arc::new(NoProgress),
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+impl BatchOptions {
+    /// Download the given number of tracks at once (at least one).
+    #[must_use]
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Report progress to a sink.
+    #[must_use]
+    pub fn with_progress(mut self, progress: impl ProgressSink + 'static) -> Self {
+        self.progress = Arc::new(progress);
+        self
+    }
+
+    /// Stop the batch when `cancel` is cancelled.
+    #[must_use]
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+
+/// A track that could not be downloaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackFailure {
+    /// `YouTube` video ID.
+    pub video_id: String,
+    /// Why the download failed.
+    pub error: String,
+}
+
+/// Outcome of a batch download.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchDownload {
+    /// Tracks downloaded into the cache.
+    pub downloaded: Vec<String>,
+    /// Tracks that were already cached.
+    pub skipped: Vec<String>,
+    /// Tracks that failed to download.
+    pub failed: Vec<TrackFailure>,
+    /// Whether the batch was cancelled before every track finished.
+    pub cancelled: bool,
+}
+
+impl BatchDownload {
+    /// Check whether every track is now cached.
+    pub fn is_complete(&self) -> bool {
+        !self.cancelled && self.failed.is_empty()
+    }
+}
+
+/// Follow a download's chunks, discarding the audio, until it finishes.
+///
+/// Progress is passed to `on_progress` every [`PROGRESS_STEP`] bytes.
+async fn finish(
+    mut rx: mpsc::Receiver<StreamChunk>,
+    on_progress: impl Fn(&Progress),
+) -> std::result::Result<(), String> {
+    let mut reported = 0;
+    while let Some(chunk) = rx.recv().await {
+        match chunk {
+            StreamChunk::Data(_) => {}
+            StreamChunk::Progress(progress) => {
+                if progress.completed >= reported + PROGRESS_STEP {
+                    reported = progress.completed;
+                    on_progress(&progress);
+                }
+            }
+            StreamChunk::Complete => return Ok(()),
+            StreamChunk::Error(e) | StreamChunk::Stalled(e) => return Err(e),
+        }
+    }
+    Err("Download ended unexpectedly".to_string())
+}
+
+impl Extractor {
+    /// Download every track of a playlist that is not already cached.
+    ///
+    /// Duplicate tracks are downloaded once. Fails only if no download
+    /// backend is available; individual failures are listed in the result.
+    /// With a cache index attached, downloads are tracked in it until they
+    /// finish, so [`Extractor::resume_downloads`] can pick them up later.
+    pub async fn download_playlist(
+        &self,
+        tracks: &[Track],
+        options: BatchOptions,
+    ) -> Result<BatchDownload> {
+        let mut seen = HashSet::new();
+        let video_ids: Vec<&str> = tracks
+            .iter()
+            .map(|track| track.id.as_str())
+            .filter(|id| seen.insert(*id))
+            .collect();
+        let total = video_ids.len() as u64;
+        let mut batch = BatchDownload::default();
+        let mut done = 0;
+
+        let (cached, missing): (Vec<&str>, Vec<&str>) =
+            video_ids.into_iter().partition(|id| self.is_cached(id));
+        for video_id in cached {
+            done += 1;
+            options
+                .progress
+                .report(Progress::items(done, total).with_current(video_id));
+            self.cache.finish_download(video_id, &Ok(()));
+            batch.skipped.push(video_id.to_string());
+        }
+        if missing.is_empty() {
+            return Ok(batch);
+        }
+        self.ensure_backend()?;
+        self.cache.queue_downloads(&missing);
+        info!("Downloading {} of {total} playlist tracks", missing.len());
+
+        let slots = Arc::new(Semaphore::new(options.concurrency.max(1)));
+        let mut downloads = JoinSet::new();
+        for video_id in missing {
+            // A prefetch of this track would write the same partial file
+            self.prefetcher.cancel(video_id);
+            let (yt_dlp, native, cache, sources, timeouts, slots) = (
+                self.yt_dlp(),
+                self.native.clone(),
+                self.cache.clone(),
+                self.sources_for(video_id),
+                self.stream_timeouts,
+                Arc::clone(&slots),
+            );
+            let video_id = video_id.to_string();
+            downloads.spawn(async move {
+                let Ok(_permit) = slots.acquire_owned().await else {
+                    return (video_id, Err("Batch stopped".to_string()));
+                };
+                let (tx, rx) = mpsc::channel(64);
+                let index = cache.clone();
+                let download = download_stream(
+                    yt_dlp,
+                    native,
+                    cache,
+                    sources,
+                    video_id.clone(),
+                    timeouts,
+                    tx,
+                );
+                let progress = finish(rx, |progress| index.record_download(&video_id, progress));
+                let ((), result) = tokio::join!(download, progress);
+                index.finish_download(&video_id, &result);
+                (video_id, result)
+            });
+        }
+
+        loop {
+            let joined = tokio::select! {
+                biased;
+                () = options.cancel.cancelled() => {
+                    // Dropping the tasks keeps their partial files for later
+                    downloads.abort_all();
+                    batch.cancelled = true;
+                    break;
+                }
+                joined = downloads.join_next() => joined,
+            };
+            let Some(joined) = joined else {
+                break;
+            };
+            let Ok((video_id, result)) = joined else {
+                continue;
+            };
+            done += 1;
+            options
+                .progress
+                .report(Progress::items(done, total).with_current(&video_id));
+            match result {
+                Ok(()) => batch.downloaded.push(video_id),
+                Err(error) => {
+                    warn!("Failed to download {video_id}: {error}");
+                    batch.failed.push(TrackFailure { video_id, error });
+                }
+            }
+        }
+
+        info!(
+            "Playlist download finished: {} downloaded, {} cached, {} failed",
+            batch.downloaded.len(),
+            batch.skipped.len(),
+            batch.failed.len()
+        );
+        Ok(batch)
+    }
+
+    /// Resume downloads left unfinished in the cache index, such as by quitting mid-sync.
+    ///
+    /// Failed downloads are left for the user to retry.
+    pub async fn resume_downloads(&self, options: BatchOptions) -> Result<BatchDownload> {
+        let Some(index) = self.cache.index() else {
+            return Ok(BatchDownload::default());
+        };
+        let tracks: Vec<Track> = index
+            .pending_downloads()?
+            .into_iter()
+            .map(|video_id| Track::new(video_id, ""))
+            .collect();
+        if !tracks.is_empty() {
+            info!("Resuming {} unfinished downloads", tracks.len());
+        }
+        self.download_playlist(&tracks, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioCache;
+    use parking_lot::Mutex;
+
+    fn extractor(dir: &std::path::Path) -> Extractor {
+        Extractor {
+            yt_dlp_path: dir.join("yt-dlp"),
+            cache: AudioCache::new(dir.join("audio"), 1024 * 1024),
+            ..Extractor::new().with_native_extraction(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_tracks_are_skipped() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let extractor = extractor(dir.path());
+        extractor.cache.write("a", b"audio a");
+        extractor.cache.write("b", b"audio b");
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let options = BatchOptions::default().with_progress({
+            let reports = Arc::clone(&reports);
+            move |progress: Progress| reports.lock().push(progress)
+        });
+        let tracks = [
+            Track::new("a", "A"),
+            Track::new("b", "B"),
+            Track::new("a", "A"),
+        ];
+        let batch = extractor.download_playlist(&tracks, options).await?;
+
+        assert_eq!(batch.skipped, ["a", "b"]);
+        assert!(batch.is_complete());
+        assert_eq!(
+            reports.lock().last(),
+            Some(&Progress::items(2, 2).with_current("b"))
+        );
+
+        // Without any backend, tracks to download are an error
+        let missing = [Track::new("c", "C")];
+        assert!(extractor
+            .download_playlist(&missing, BatchOptions::default())
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_downloads_resume() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index = Arc::new(monad_cache::CacheManager::with_path(dir.path().join("db"))?);
+        let extractor = extractor(dir.path()).with_cache_index(Arc::clone(&index));
+        assert_eq!(
+            extractor
+                .resume_downloads(BatchOptions::default())
+                .await?
+                .skipped
+                .len(),
+            0
+        );
+
+        // Left queued by an earlier run, then finished by another writer
+        index.queue_downloads(&["a"])?;
+        extractor.cache.write("a", b"audio a");
+        let batch = extractor.resume_downloads(BatchOptions::default()).await?;
+        assert_eq!(batch.skipped, ["a"]);
+        assert!(index.list_downloads()?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failures_are_collected() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let extractor = extractor(dir.path());
+        // Stands in for a yt-dlp that cannot download anything
+        fs::write(
+            &extractor.yt_dlp_path,
+            "#!/bin/sh\ncase \"$1\" in\n\
+             --version) echo 2025.12.01 ;;\n\
+             --help) echo '--js-runtimes --remote-components' ;;\n\
+             *) echo 'ERROR: Video unavailable' >&2; exit 1 ;;\n\
+             esac\n",
+        )?;
+        fs::set_permissions(&extractor.yt_dlp_path, fs::Permissions::from_mode(0o755))?;
+        extractor.cache.write("a", b"audio a");
+
+        let tracks = [Track::new("a", "A"), Track::new("b", "B")];
+        let batch = extractor
+            .download_playlist(&tracks, BatchOptions::default())
+            .await?;
+        assert_eq!(batch.skipped, ["a"]);
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.failed[0].video_id, "b");
+        // yt-dlp's own explanation is included
+        assert!(batch.failed[0].error.contains("Video unavailable"));
+        assert!(!batch.is_complete());
+        Ok(())
+    }
+}
@@ -0,0 +1,654 @@
+//! Size-capped disk cache for extracted audio.
+//!
+//! Each track is stored as `{video_id}.audio`. The file's modification time is
+//! bumped on every read and serves as its last-access time, so eviction removes
+//! the least recently played tracks first. Pinned tracks (marked by an empty
+//! `{video_id}.pin` file) are never evicted. Track metadata is kept beside the
+//! audio as `{video_id}.json` and evicted with it, as is the audio's checksum.
+//! Optionally the cache is kept in a [`CacheManager`]'s audio directory and
+//! stored through it, so both describe one store; pins are then kept in its
+//! database alone, where its own eviction sees them too. Tracks can also be
+//! re-encoded to Opus and given embedded cover art after they are cached.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use bytes::Bytes;
+use monad_cache::CacheManager;
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
+use tracing::{debug, info, warn};
+
+use crate::integrity::{Checksum, CHECKSUM_EXTENSION};
+use crate::metadata::TrackMetadata;
+use crate::mmap::read_file;
+use crate::transcode::TranscodeSettings;
+
+/// Default maximum cache size (2 GB).
+pub const DEFAULT_MAX_CACHE_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+const AUDIO_EXTENSION: &str = "audio";
+const PIN_EXTENSION: &str = "pin";
+const METADATA_EXTENSION: &str = "json";
+
+/// Current disk usage of the audio cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    /// Total size of cached audio in bytes.
+    pub total_bytes: u64,
+    /// Number of cached tracks.
+    pub file_count: usize,
+    /// Size of pinned tracks in bytes (excluded from eviction).
+    pub pinned_bytes: u64,
+    /// Number of pinned tracks.
+    pub pinned_count: usize,
+    /// Configured maximum cache size in bytes.
+    pub max_bytes: u64,
+}
+
+/// A cached audio file found on disk.
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    last_access: SystemTime,
+    pinned: bool,
+}
+
+/// Disk cache of extracted audio with least-recently-played eviction.
+#[derive(Debug, Clone)]
+pub struct AudioCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Database mirroring the cached tracks, if attached.
+    index: Option<Arc<CacheManager>>,
+    /// How newly cached tracks are re-encoded, if at all.
+    transcode: Option<TranscodeSettings>,
+    /// ffmpeg binary embedding cover art into cached tracks, if enabled.
+    artwork_ffmpeg: Option<PathBuf>,
+    /// Held while cached audio is rewritten in place, one track at a time.
+    rewrite: Arc<AsyncMutex<()>>,
+}
+
+impl AudioCache {
+    /// Create a cache in the given directory.
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        // Ensure cache directory exists
+        let _ = fs::create_dir_all(&dir);
+        Self {
+            dir,
+            max_bytes,
+            index: None,
+            transcode: None,
+            artwork_ffmpeg: None,
+            rewrite: Arc::default(),
+        }
+    }
+
+    /// Keep the cache in a database's audio directory, reconciling it with the disk.
+    ///
+    /// Tracks cached elsewhere before are moved there, and their pins and
+    /// checksums moved into the database.
+    pub fn set_index(&mut self, index: Arc<CacheManager>) {
+        self.move_to(index.audio_dir());
+        self.index = Some(index);
+        self.reconcile_index();
+        self.import_sidecars();
+    }
+
+    /// Move the pins and checksums kept in files beside the audio into the
+    /// index, removing the files.
+    fn import_sidecars(&self) {
+        let Some(index) = self.index() else {
+            return;
+        };
+        for entry in fs::read_dir(&self.dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let (Some(video_id), Some(extension)) = (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.extension().and_then(|e| e.to_str()),
+            ) else {
+                continue;
+            };
+            let result = match extension {
+                PIN_EXTENSION => index.pin_audio(video_id, true),
+                CHECKSUM_EXTENSION => match self.checksum_file(video_id) {
+                    Some(checksum) => index.record_audio_checksum(video_id, &checksum.sha256),
+                    None => Ok(()),
+                },
+                _ => continue,
+            };
+            match result {
+                Ok(()) => {
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) => warn!("Failed to move {video_id}.{extension} into the cache index: {e}"),
+            }
+        }
+    }
+
+    /// Move the cache to another directory, bringing its files along.
+    fn move_to(&mut self, dir: PathBuf) {
+        if dir == self.dir {
+            return;
+        }
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!("Failed to create cache directory {}: {e}", dir.display());
+            return;
+        }
+        let mut moved = 0;
+        for entry in fs::read_dir(&self.dir).into_iter().flatten().flatten() {
+            let target = dir.join(entry.file_name());
+            if !entry.file_type().is_ok_and(|t| t.is_file()) || target.exists() {
+                continue;
+            }
+            match fs::rename(entry.path(), &target) {
+                Ok(()) => moved += 1,
+                Err(e) => warn!("Failed to move {:?} into the cache: {e}", entry.path()),
+            }
+        }
+        if moved > 0 {
+            info!("Moved {moved} cache files to {}", dir.display());
+        }
+        let _ = fs::remove_dir(&self.dir);
+        self.dir = dir;
+    }
+
+    /// Get the database mirroring the cached tracks, if attached.
+    pub fn index(&self) -> Option<&CacheManager> {
+        self.index.as_deref()
+    }
+
+    /// Re-encode newly cached tracks with the given settings, or keep them as downloaded.
+    pub fn set_transcode(&mut self, settings: Option<TranscodeSettings>) {
+        self.transcode = settings;
+    }
+
+    /// Get how newly cached tracks are re-encoded, if at all.
+    pub const fn transcode(&self) -> Option<&TranscodeSettings> {
+        self.transcode.as_ref()
+    }
+
+    /// Embed cover art into cached tracks with the ffmpeg binary at the given path.
+    pub fn set_artwork_ffmpeg(&mut self, ffmpeg_path: Option<PathBuf>) {
+        self.artwork_ffmpeg = ffmpeg_path;
+    }
+
+    /// Get the ffmpeg binary embedding cover art, if enabled.
+    pub const fn artwork_ffmpeg(&self) -> Option<&PathBuf> {
+        self.artwork_ffmpeg.as_ref()
+    }
+
+    /// Wait for other rewrites of cached audio to finish.
+    pub async fn lock_rewrite(&self) -> AsyncMutexGuard<'_, ()> {
+        self.rewrite.lock().await
+    }
+
+    /// Get the cache directory.
+    pub const fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Get the maximum cache size in bytes.
+    pub const fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Set the maximum cache size in bytes, evicting if now over the limit.
+    pub fn set_max_bytes(&mut self, max_bytes: u64) {
+        self.max_bytes = max_bytes;
+        self.evict();
+    }
+
+    /// Get cache file path for a video ID.
+    pub fn path(&self, video_id: &str) -> PathBuf {
+        self.dir.join(format!("{video_id}.{AUDIO_EXTENSION}"))
+    }
+
+    fn pin_path(&self, video_id: &str) -> PathBuf {
+        self.dir.join(format!("{video_id}.{PIN_EXTENSION}"))
+    }
+
+    fn metadata_path(&self, video_id: &str) -> PathBuf {
+        self.dir.join(format!("{video_id}.{METADATA_EXTENSION}"))
+    }
+
+    /// Get the file yt-dlp prints raw metadata to, before it is imported.
+    pub fn yt_dlp_metadata_path(&self, video_id: &str) -> PathBuf {
+        self.dir
+            .join(format!("{video_id}.info.{METADATA_EXTENSION}"))
+    }
+
+    /// Check if audio is cached for a video ID.
+    ///
+    /// Audio shorter or longer than its checksum records is not counted.
+    pub fn contains(&self, video_id: &str) -> bool {
+        fs::metadata(self.path(video_id)).is_ok_and(|m| {
+            m.len() > 0 && self.checksum(video_id).map_or(true, |c| c.len == m.len())
+        })
+    }
+
+    /// List the video IDs of cached tracks.
+    pub fn video_ids(&self) -> Vec<String> {
+        self.entries()
+            .into_iter()
+            .filter_map(|entry| Some(entry.path.file_stem()?.to_str()?.to_string()))
+            .collect()
+    }
+
+    /// Read cached audio, marking it as recently played.
+    ///
+    /// Large files are memory-mapped rather than copied into memory.
+    /// Corrupted audio is deleted and treated as not cached.
+    pub fn read(&self, video_id: &str) -> Option<Bytes> {
+        let path = self.path(video_id);
+        if !path.exists() {
+            return None;
+        }
+
+        match read_file(&path) {
+            Ok(data) if data.is_empty() => {
+                let _ = fs::remove_file(&path);
+                self.unregister(video_id);
+                None
+            }
+            Ok(data) if !self.verify(video_id, &data) => {
+                self.remove_corrupted(video_id);
+                None
+            }
+            Ok(data) => {
+                let data = self.open(video_id, data)?;
+                self.touch(video_id);
+                Some(data)
+            }
+            Err(e) => {
+                warn!("Failed to read cache: {e}");
+                None
+            }
+        }
+    }
+
+    /// Write audio to the cache, then evict old entries if over the size limit.
+    pub fn write(&self, video_id: &str, data: &[u8]) {
+        // Recorded first, so audio truncated by a crash mid-write is caught
+        self.record_checksum(video_id, data);
+        if let Err(e) = self.store(video_id, data) {
+            warn!("Failed to write cache: {e}");
+            return;
+        }
+        debug!("Cached {} bytes to {:?}", data.len(), self.path(video_id));
+        self.discard_partial(video_id);
+        self.evict();
+        self.schedule_processing(video_id);
+    }
+
+    /// Replace the audio of a cached track, such as with a re-encoded copy.
+    pub fn replace_audio(&self, video_id: &str, data: &[u8]) -> std::io::Result<()> {
+        self.record_checksum(video_id, data);
+        self.store(video_id, data).map_err(std::io::Error::other)
+    }
+
+    /// Store audio as a cached track, through the index if one is attached.
+    ///
+    /// The audio is written beside the track and moved over it, so readers
+    /// never see half a file, even one they have memory-mapped.
+    fn store(&self, video_id: &str, data: &[u8]) -> monad_core::Result<()> {
+        if let Some(index) = self.index() {
+            return self.store_indexed(index, video_id, data);
+        }
+        let path = self.path(video_id);
+        let temp_path = path.with_extension("tmp");
+        if let Err(e) = fs::write(&temp_path, data).and_then(|()| fs::rename(&temp_path, &path)) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Check whether the index encrypts the audio stored through it.
+    fn is_sealed(&self) -> bool {
+        self.index()
+            .is_some_and(CacheManager::is_encryption_enabled)
+    }
+
+    /// Record the checksum of audio about to be stored.
+    ///
+    /// An attached index records it as it stores the audio instead.
+    fn record_checksum(&self, video_id: &str, data: &[u8]) {
+        if self.index().is_none() {
+            self.write_checksum(video_id, &Checksum::of(data));
+        }
+    }
+
+    /// Decrypt audio read from the cache if the index encrypted it.
+    fn open(&self, video_id: &str, data: Bytes) -> Option<Bytes> {
+        let Some(index) = self.index().filter(|_| monad_cache::is_encrypted(&data)) else {
+            return Some(data);
+        };
+        index
+            .open_audio(video_id, data.to_vec())
+            .map(Bytes::from)
+            .inspect_err(|e| warn!("Failed to decrypt cached audio: {e}"))
+            .ok()
+    }
+
+    /// Re-encode a newly cached track and embed its artwork in the background, if enabled.
+    ///
+    /// Encrypted audio is kept as stored.
+    pub fn schedule_processing(&self, video_id: &str) {
+        if (self.transcode.is_none() && self.artwork_ffmpeg.is_none()) || self.is_sealed() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            debug!("No runtime to process {video_id} on");
+            return;
+        };
+        let cache = self.clone();
+        let video_id = video_id.to_string();
+        runtime.spawn(async move {
+            if let Err(e) = cache.transcode_cached(&video_id).await {
+                warn!("Failed to transcode {video_id}: {e}");
+            }
+            if let Err(e) = cache.embed_artwork(&video_id).await {
+                warn!("Failed to embed artwork in {video_id}: {e}");
+            }
+        });
+    }
+
+    /// Save the metadata of a track.
+    pub fn write_metadata(&self, video_id: &str, metadata: &TrackMetadata) {
+        let result = serde_json::to_vec(metadata)
+            .map_err(std::io::Error::other)
+            .and_then(|json| fs::write(self.metadata_path(video_id), json));
+        if let Err(e) = result {
+            warn!("Failed to write metadata for {video_id}: {e}");
+        } else if self.path(video_id).exists() {
+            // Update the duration and quality in the index
+            self.register(video_id);
+        }
+    }
+
+    /// Read the saved metadata of a track.
+    pub fn read_metadata(&self, video_id: &str) -> Option<TrackMetadata> {
+        let json = fs::read(self.metadata_path(video_id)).ok()?;
+        serde_json::from_slice(&json)
+            .inspect_err(|e| warn!("Ignoring invalid metadata for {video_id}: {e}"))
+            .ok()
+    }
+
+    /// Convert metadata printed by yt-dlp into the saved format.
+    pub fn import_yt_dlp_metadata(&self, video_id: &str) -> Option<TrackMetadata> {
+        let path = self.yt_dlp_metadata_path(video_id);
+        let json = fs::read_to_string(&path).ok()?;
+        let _ = fs::remove_file(&path);
+
+        // yt-dlp appends one line per video; the last one is the newest
+        let line = json.lines().rev().find(|line| !line.trim().is_empty())?;
+        match TrackMetadata::from_yt_dlp_json(line) {
+            Ok(metadata) => {
+                self.write_metadata(video_id, &metadata);
+                Some(metadata)
+            }
+            Err(e) => {
+                warn!("Failed to import metadata for {video_id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Update the last-access time of a cached track.
+    fn touch(&self, video_id: &str) {
+        let result = File::options()
+            .write(true)
+            .open(self.path(video_id))
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        if let Err(e) = result {
+            debug!("Failed to update cache access time: {e}");
+        }
+        self.touch_index(video_id);
+    }
+
+    /// Pin a track so it is never evicted.
+    ///
+    /// With an index attached, only tracks already cached can be pinned.
+    pub fn pin(&self, video_id: &str) -> std::io::Result<()> {
+        match self.index() {
+            Some(index) => index
+                .pin_audio(video_id, true)
+                .map_err(std::io::Error::other),
+            None => File::create(self.pin_path(video_id)).map(|_| ()),
+        }
+    }
+
+    /// Unpin a track, making it eligible for eviction again.
+    ///
+    /// A track kept by an offline pin in the index stays pinned.
+    pub fn unpin(&self, video_id: &str) -> std::io::Result<()> {
+        if let Some(index) = self.index() {
+            return index
+                .pin_audio(video_id, false)
+                .map_err(std::io::Error::other);
+        }
+        match fs::remove_file(self.pin_path(video_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check if a track is pinned.
+    pub fn is_pinned(&self, video_id: &str) -> bool {
+        match self.index() {
+            Some(_) => self.pinned_ids().contains(video_id),
+            None => self.pin_path(video_id).exists(),
+        }
+    }
+
+    /// Get the video IDs of the tracks the index keeps pinned.
+    fn pinned_ids(&self) -> HashSet<String> {
+        let Some(index) = self.index() else {
+            return HashSet::new();
+        };
+        index.pinned_audio().unwrap_or_else(|e| {
+            warn!("Failed to read pins from the cache index: {e}");
+            HashSet::new()
+        })
+    }
+
+    /// Get the current cache usage.
+    pub fn usage(&self) -> CacheUsage {
+        let mut usage = CacheUsage {
+            max_bytes: self.max_bytes,
+            ..CacheUsage::default()
+        };
+
+        for entry in self.entries() {
+            usage.total_bytes += entry.size;
+            usage.file_count += 1;
+            if entry.pinned {
+                usage.pinned_bytes += entry.size;
+                usage.pinned_count += 1;
+            }
+        }
+
+        usage
+    }
+
+    /// Evict least recently played, unpinned tracks until under the size limit.
+    ///
+    /// Returns the number of bytes freed.
+    pub fn evict(&self) -> u64 {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|e| e.size).sum();
+        if total <= self.max_bytes {
+            return 0;
+        }
+
+        entries.retain(|e| !e.pinned);
+        entries.sort_by_key(|e| e.last_access);
+
+        let mut freed = 0;
+        for entry in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(&entry.path) {
+                Ok(()) => {
+                    let _ = fs::remove_file(entry.path.with_extension(METADATA_EXTENSION));
+                    let _ = fs::remove_file(entry.path.with_extension(CHECKSUM_EXTENSION));
+                    if let Some(video_id) = entry.path.file_stem().and_then(|s| s.to_str()) {
+                        self.unregister(video_id);
+                    }
+                    debug!("Evicted {:?} ({} bytes)", entry.path, entry.size);
+                    total -= entry.size;
+                    freed += entry.size;
+                }
+                Err(e) => warn!("Failed to evict {:?}: {e}", entry.path),
+            }
+        }
+
+        if freed > 0 {
+            info!("Evicted {} KB from audio cache", freed / 1024);
+        }
+        freed
+    }
+
+    /// Remove all cached audio, including pinned tracks.
+    pub fn clear(&self) {
+        for video_id in self.video_ids() {
+            self.unregister(&video_id);
+        }
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            warn!("Failed to clear cache: {e}");
+        }
+        let _ = fs::create_dir_all(&self.dir);
+        info!("Audio cache cleared");
+    }
+
+    /// List cached audio files, with their pins read from the index if attached.
+    fn entries(&self) -> Vec<CacheEntry> {
+        let Ok(read_dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let pinned_ids = self.pinned_ids();
+
+        read_dir
+            .filter_map(Result::ok)
+            .filter_map(|dir_entry| {
+                let path = dir_entry.path();
+                if path.extension()? != AUDIO_EXTENSION {
+                    return None;
+                }
+                let metadata = dir_entry.metadata().ok()?;
+                let pinned = if self.index.is_some() {
+                    path.file_stem()
+                        .and_then(|s| s.to_str())
+                        .is_some_and(|video_id| pinned_ids.contains(video_id))
+                } else {
+                    path.with_extension(PIN_EXTENSION).exists()
+                };
+                Some(CacheEntry {
+                    size: metadata.len(),
+                    last_access: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    pinned,
+                    path,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Write a cache entry with an explicit last-access time.
+    fn write_aged(cache: &AudioCache, video_id: &str, size: usize, age_secs: u64) {
+        let path = cache.path(video_id);
+        let _ = fs::write(&path, vec![0u8; size]);
+        let time = SystemTime::now() - Duration::from_secs(age_secs);
+        let _ = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(time));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_played() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = AudioCache::new(dir.path().to_path_buf(), 250);
+        write_aged(&cache, "old", 100, 300);
+        write_aged(&cache, "mid", 100, 200);
+        write_aged(&cache, "new", 100, 100);
+
+        assert_eq!(cache.evict(), 100);
+        assert!(!cache.contains("old"));
+        assert!(cache.contains("mid"));
+        assert!(cache.contains("new"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_refreshes_access_time() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = AudioCache::new(dir.path().to_path_buf(), 250);
+        write_aged(&cache, "old", 100, 300);
+        write_aged(&cache, "mid", 100, 200);
+
+        assert!(cache.read("old").is_some());
+        cache.write("new", &[0u8; 100]);
+
+        assert!(cache.contains("old"));
+        assert!(!cache.contains("mid"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_tracks_are_not_evicted() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = AudioCache::new(dir.path().to_path_buf(), 150);
+        write_aged(&cache, "old", 100, 300);
+        write_aged(&cache, "new", 100, 100);
+        cache.pin("old")?;
+
+        let usage = cache.usage();
+        assert_eq!(usage.total_bytes, 200);
+        assert_eq!(usage.pinned_bytes, 100);
+        assert_eq!(usage.file_count, 2);
+
+        cache.evict();
+        assert!(cache.contains("old"));
+        assert!(!cache.contains("new"));
+
+        cache.unpin("old")?;
+        assert!(!cache.is_pinned("old"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_is_evicted_with_audio() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = AudioCache::new(dir.path().to_path_buf(), 150);
+        write_aged(&cache, "old", 100, 300);
+        let metadata = TrackMetadata {
+            title: Some("Song".to_string()),
+            ..TrackMetadata::default()
+        };
+        cache.write_metadata("old", &metadata);
+        assert_eq!(cache.read_metadata("old"), Some(metadata));
+
+        fs::write(
+            cache.yt_dlp_metadata_path("new"),
+            r#"{"title": "New Song", "uploader": "Artist - Topic"}"#,
+        )?;
+        assert!(cache.import_yt_dlp_metadata("new").is_some());
+        assert!(!cache.yt_dlp_metadata_path("new").exists());
+
+        cache.write("new", &[0u8; 100]);
+        assert_eq!(cache.read_metadata("old"), None);
+        let imported = cache.read_metadata("new");
+        assert_eq!(imported.and_then(|m| m.artist), Some("Artist".to_string()));
+        Ok(())
+    }
+}
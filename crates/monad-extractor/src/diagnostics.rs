@@ -0,0 +1,111 @@
+//! Diagnostics from yt-dlp's stderr during streaming extraction.
+//!
+//! Audio goes to stdout, so everything yt-dlp has to say arrives on stderr
+//! mixed with progress lines. The last few other lines are kept in a
+//! bounded buffer and included in the error when yt-dlp fails, and signs of
+//! rate limiting or throttling are logged as soon as they appear.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tracing::{debug, warn};
+
+/// Number of stderr lines kept for error reports.
+pub const STDERR_TAIL_LINES: usize = 20;
+
+/// Number of trailing lines included in an error.
+const ERROR_TAIL_LINES: usize = 3;
+
+/// Longest stderr line kept, in characters.
+const MAX_LINE_CHARS: usize = 500;
+
+/// Markers of lines worth logging as they happen.
+const NOTABLE_MARKERS: &[&str] = &[
+    "ERROR:",
+    "WARNING:",
+    "HTTP Error 429",
+    "Too Many Requests",
+    "throttl",
+    "rate-limit",
+];
+
+/// Check whether a stderr line reports a problem.
+fn is_notable(line: &str) -> bool {
+    NOTABLE_MARKERS.iter().any(|marker| line.contains(marker))
+}
+
+/// The last lines yt-dlp wrote to stderr, other than progress lines.
+#[derive(Debug, Clone, Default)]
+pub struct StderrTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl StderrTail {
+    /// Record a line, logging it if it reports a problem.
+    pub fn push(&self, video_id: &str, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        if is_notable(line) {
+            warn!("yt-dlp ({video_id}): {line}");
+        } else {
+            debug!("yt-dlp ({video_id}): {line}");
+        }
+
+        let line: String = line.chars().take(MAX_LINE_CHARS).collect();
+        let mut lines = self.lines.lock();
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Describe a failure with the last lines, if any were written.
+    pub fn describe(&self, failure: &str) -> String {
+        let lines = self.lines.lock();
+        if lines.is_empty() {
+            return failure.to_string();
+        }
+        let skip = lines.len().saturating_sub(ERROR_TAIL_LINES);
+        let tail: Vec<&str> = lines.iter().skip(skip).map(String::as_str).collect();
+        format!("{failure}: {}", tail.join(" | "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_is_bounded() {
+        let tail = StderrTail::default();
+        assert_eq!(tail.describe("failed"), "failed");
+        for i in 0..STDERR_TAIL_LINES + 5 {
+            tail.push("abc", &format!("line {i}"));
+        }
+        tail.push("abc", "  ");
+        tail.push(
+            "abc",
+            "ERROR: [youtube] abc: HTTP Error 429: Too Many Requests",
+        );
+        assert_eq!(tail.lines.lock().len(), STDERR_TAIL_LINES);
+        assert_eq!(
+            tail.describe("yt-dlp failed"),
+            "yt-dlp failed: line 23 | line 24 | ERROR: [youtube] abc: HTTP Error 429: Too Many Requests"
+        );
+    }
+
+    #[test]
+    fn test_long_lines_are_truncated() {
+        let tail = StderrTail::default();
+        tail.push("abc", &"x".repeat(MAX_LINE_CHARS * 2));
+        assert_eq!(
+            tail.lines.lock().front().map(String::len),
+            Some(MAX_LINE_CHARS)
+        );
+        assert!(is_notable("WARNING: throttled"));
+        assert!(!is_notable("[download] Destination: -"));
+    }
+}
@@ -0,0 +1,293 @@
+//! Registration of cached audio in monad-cache's `audio_cache` table.
+//!
+//! With an index attached, tracks are stored through it into its audio
+//! directory. The files on disk stay authoritative; the table mirrors them so that
+//! `CacheManager::has_audio` and `CacheManager::stats` describe the same
+//! cache the extractor plays from. Every write, access and removal is
+//! mirrored, and the table is reconciled with the disk when it is attached.
+//! Entries for audio stored outside the extractor's directory are left alone.
+//! Downloads for offline use are tracked in its `downloads` table as well.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+
+use monad_cache::{AudioRecord, CacheManager};
+use monad_core::Progress;
+use tracing::{info, warn};
+
+use crate::cache::AudioCache;
+use crate::detect_audio_mime;
+
+/// Label the quality of audio by its average bitrate.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn quality_label(size_bytes: u64, duration_secs: Option<f64>) -> String {
+    match duration_secs.filter(|&secs| secs > 0.0) {
+        Some(secs) => format!(
+            "{}kbps",
+            (size_bytes as f64 * 8.0 / secs / 1000.0).round() as u64
+        ),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Log a failed index update; the cache keeps working without the index.
+fn log_failure(action: &str, video_id: &str, result: monad_core::Result<()>) {
+    if let Err(e) = result {
+        warn!("Failed to {action} {video_id} in the cache index: {e}");
+    }
+}
+
+impl AudioCache {
+    /// Register a cached track in the index, with its format, quality, size and duration.
+    pub fn register(&self, video_id: &str) {
+        let Some(index) = self.index() else {
+            return;
+        };
+        let path = self.path(video_id);
+        let Ok(size_bytes) = fs::metadata(&path).map(|m| m.len()) else {
+            return;
+        };
+
+        let mut header = Vec::with_capacity(12);
+        if let Ok(file) = File::open(&path) {
+            let _ = file.take(12).read_to_end(&mut header);
+        }
+        let duration_secs = self.read_metadata(video_id).and_then(|m| m.duration);
+
+        let audio = AudioRecord {
+            video_id: video_id.to_string(),
+            format: detect_audio_mime(&header),
+            quality: quality_label(size_bytes, duration_secs),
+            file_path: path,
+            size_bytes,
+            duration_secs,
+        };
+        // Registering again forgets the checksum, which still holds for the same file
+        let checksum = index
+            .audio_checksum(video_id)
+            .filter(|&(len, _)| len == size_bytes);
+        log_failure("register", video_id, index.register_audio(&audio));
+        if let Some((_, sha256)) = checksum {
+            let result = index.record_audio_checksum(video_id, &sha256);
+            log_failure("record the checksum of", video_id, result);
+        }
+    }
+
+    /// Store audio through the index, registering it with its format, quality and checksum.
+    pub(crate) fn store_indexed(
+        &self,
+        index: &CacheManager,
+        video_id: &str,
+        data: &[u8],
+    ) -> monad_core::Result<()> {
+        let duration_secs = self.read_metadata(video_id).and_then(|m| m.duration);
+        let quality = quality_label(data.len() as u64, duration_secs);
+        index.store_audio(
+            video_id,
+            &detect_audio_mime(data),
+            &quality,
+            data,
+            duration_secs,
+        )?;
+        Ok(())
+    }
+
+    /// Remove a track from the index.
+    pub fn unregister(&self, video_id: &str) {
+        if let Some(index) = self.index() {
+            log_failure("remove", video_id, index.remove_audio(video_id));
+        }
+    }
+
+    /// Mark a track as recently played in the index.
+    pub fn touch_index(&self, video_id: &str) {
+        if let Some(index) = self.index() {
+            log_failure("touch", video_id, index.touch_audio(video_id));
+        }
+    }
+
+    /// Queue downloads of tracks in the index.
+    pub fn queue_downloads(&self, video_ids: &[&str]) {
+        if let Some(Err(e)) = self.index().map(|index| index.queue_downloads(video_ids)) {
+            warn!("Failed to queue downloads in the cache index: {e}");
+        }
+    }
+
+    /// Record the bytes downloaded of a track in the index.
+    pub fn record_download(&self, video_id: &str, progress: &Progress) {
+        if let Some(index) = self.index() {
+            let result = index.update_download(video_id, progress.completed, progress.total);
+            log_failure("update the download of", video_id, result);
+        }
+    }
+
+    /// Record how the download of a track ended in the index.
+    ///
+    /// Finished downloads are removed; failed ones keep their error.
+    pub fn finish_download(&self, video_id: &str, result: &Result<(), String>) {
+        if let Some(index) = self.index() {
+            let result = match result {
+                Ok(()) => index.remove_download(video_id),
+                Err(error) => index.fail_download(video_id, error),
+            };
+            log_failure("finish the download of", video_id, result);
+        }
+    }
+
+    /// Make the index match the files on disk.
+    pub fn reconcile_index(&self) {
+        let Some(index) = self.index() else {
+            return;
+        };
+        let indexed: HashSet<String> = match index.audio_files() {
+            Ok(files) => files
+                .into_iter()
+                .filter(|(_, path)| path.starts_with(self.dir()))
+                .map(|(video_id, _)| video_id)
+                .collect(),
+            Err(e) => {
+                warn!("Failed to read the cache index: {e}");
+                return;
+            }
+        };
+        let cached: HashSet<String> = self.video_ids().into_iter().collect();
+
+        for video_id in cached.difference(&indexed) {
+            self.register(video_id);
+        }
+        for video_id in indexed.difference(&cached) {
+            self.unregister(video_id);
+        }
+        info!("Cache index reconciled: {} tracks on disk", cached.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::metadata::TrackMetadata;
+
+    #[test]
+    fn test_quality_label() {
+        assert_eq!(quality_label(1_600_000, Some(100.0)), "128kbps");
+        assert_eq!(quality_label(1_600_000, None), "unknown");
+        assert_eq!(quality_label(1_600_000, Some(0.0)), "unknown");
+    }
+
+    #[test]
+    fn test_index_mirrors_cache() -> monad_core::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index = Arc::new(CacheManager::with_path(dir.path().join("db"))?);
+        let mut cache = AudioCache::new(dir.path().join("audio"), 150);
+
+        // Tracks cached before the index was attached are picked up
+        cache.write("old", &[0u8; 100]);
+        let record = |video_id: &str, file_path| AudioRecord {
+            video_id: video_id.to_string(),
+            format: "audio/mp4".to_string(),
+            quality: "unknown".to_string(),
+            file_path,
+            size_bytes: 10,
+            duration_secs: None,
+        };
+        index.register_audio(&record("gone", index.audio_dir().join("gone.audio")))?;
+        // Audio stored elsewhere is not the extractor's to reconcile
+        index.register_audio(&record("other", dir.path().join("other.audio")))?;
+        cache.set_index(Arc::clone(&index));
+        assert_eq!(cache.dir(), &index.audio_dir());
+        assert!(index.has_audio("old"));
+        assert!(!index.has_audio("gone"));
+        assert!(index.has_audio("other"));
+
+        cache.write("new", &[0u8; 100]);
+        cache.write_metadata(
+            "new",
+            &TrackMetadata {
+                duration: Some(10.0),
+                ..TrackMetadata::default()
+            },
+        );
+        assert!(!index.has_audio("old"));
+        assert!(index.has_audio("new"));
+        assert_eq!(index.audio_files()?.len(), 2);
+
+        cache.clear();
+        assert_eq!(index.audio_video_ids()?, ["other"]);
+
+        // Audio the index encrypts is decrypted on read
+        index.set_encryption(Some(monad_cache::EncryptionKey::generate()));
+        cache.write("sealed", b"sealed audio");
+        assert!(monad_cache::is_encrypted(&fs::read(cache.path("sealed"))?));
+        assert_eq!(cache.read("sealed").as_deref(), Some(&b"sealed audio"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksums_are_kept_in_index() -> monad_core::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index = Arc::new(CacheManager::with_path(dir.path().join("db"))?);
+        let mut cache = AudioCache::new(dir.path().join("audio"), 1024);
+        cache.write("old", b"old audio");
+        cache.set_index(Arc::clone(&index));
+
+        // Checksum files from before the index are moved into it
+        let old = crate::integrity::Checksum::of(b"old audio");
+        assert_eq!(index.audio_checksum("old"), Some((old.len, old.sha256)));
+        assert!(!cache.dir().join("old.sha256").exists());
+
+        cache.write("new", b"new audio");
+        cache.write_metadata("new", &TrackMetadata::default());
+        assert!(!cache.dir().join("new.sha256").exists());
+        assert!(index.audio_checksum("new").is_some());
+        fs::write(cache.path("new"), b"bad audio")?;
+        assert_eq!(cache.read("new"), None);
+        assert!(!index.has_audio("new"));
+
+        // Sealed audio is checked against the checksum taken as it was stored
+        index.set_encryption(Some(monad_cache::EncryptionKey::generate()));
+        cache.write("sealed", b"sealed audio");
+        let mut sealed = fs::read(cache.path("sealed"))?;
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        fs::write(cache.path("sealed"), &sealed)?;
+        assert_eq!(cache.read("sealed"), None);
+        assert!(!cache.path("sealed").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pins_are_kept_in_index() -> monad_core::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index = Arc::new(CacheManager::with_path(dir.path().join("db"))?);
+        let mut cache = AudioCache::new(dir.path().join("audio"), 250);
+        cache.write("old", &[0u8; 100]);
+        cache.pin("old")?;
+        cache.set_index(Arc::clone(&index));
+
+        // Pin files from before the index are moved into it
+        assert!(index.is_audio_pinned("old"));
+        assert!(!cache.dir().join("old.pin").exists());
+
+        // Pins made through either side keep the track from both evictors
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cache.write("mid", &[0u8; 100]);
+        index.pin_audio("mid", true)?;
+        assert!(cache.is_pinned("mid"));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cache.write("new", &[0u8; 100]);
+        assert!(cache.contains("old") && cache.contains("mid"));
+        assert!(!cache.contains("new"));
+        assert!(!cache.dir().join("mid.pin").exists());
+
+        cache.unpin("mid")?;
+        assert!(!index.is_audio_pinned("mid"));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cache.write("new", &[0u8; 100]);
+        assert!(!cache.contains("mid"));
+        assert!(cache.contains("old") && cache.contains("new"));
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Checksums of cached audio, for detecting corrupted files.
+//!
+//! Each cached track has a `{video_id}.sha256` file holding the SHA-256 and
+//! byte length of its audio. Audio is checked against it when loaded, and
+//! corrupted tracks (such as files truncated by a crash) are deleted so they
+//! are downloaded again instead of playing as garbled audio. Tracks cached
+//! before checksums existed are trusted and have one recorded on first read.
+//! With an index attached, the checksum it records as it stores the audio is
+//! used instead, and no checksum files are written.
+
+use std::fs;
+
+use monad_core::{Progress, ProgressSink};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::cache::AudioCache;
+
+/// Extension of checksum files.
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// SHA-256 and length of a cached audio file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// Hex-encoded SHA-256 of the audio.
+    pub sha256: String,
+    /// Length of the audio in bytes.
+    pub len: u64,
+}
+
+impl Checksum {
+    /// Compute the checksum of audio data.
+    pub fn of(data: &[u8]) -> Self {
+        Self {
+            sha256: hex::encode(Sha256::digest(data)),
+            len: data.len() as u64,
+        }
+    }
+
+    /// Parse a checksum saved as `{sha256} {len}`.
+    fn parse(contents: &str) -> Option<Self> {
+        let (sha256, len) = contents.trim().split_once(' ')?;
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(Self {
+            sha256: sha256.to_ascii_lowercase(),
+            len: len.parse().ok()?,
+        })
+    }
+}
+
+/// Result of verifying every track in the cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheVerification {
+    /// Number of tracks checked.
+    pub checked: usize,
+    /// Video IDs of corrupted tracks, which were deleted.
+    pub corrupted: Vec<String>,
+}
+
+impl AudioCache {
+    fn checksum_path(&self, video_id: &str) -> std::path::PathBuf {
+        self.dir().join(format!("{video_id}.{CHECKSUM_EXTENSION}"))
+    }
+
+    /// Get the recorded checksum of a cached track.
+    pub fn checksum(&self, video_id: &str) -> Option<Checksum> {
+        if let Some(index) = self.index() {
+            let (len, sha256) = index.audio_checksum(video_id)?;
+            return Some(Checksum { sha256, len });
+        }
+        self.checksum_file(video_id)
+    }
+
+    /// Read the checksum file of a cached track.
+    pub(crate) fn checksum_file(&self, video_id: &str) -> Option<Checksum> {
+        let contents = fs::read_to_string(self.checksum_path(video_id)).ok()?;
+        Checksum::parse(&contents)
+    }
+
+    /// Record the checksum of a cached track.
+    pub fn write_checksum(&self, video_id: &str, checksum: &Checksum) {
+        if let Some(index) = self.index() {
+            if let Err(e) = index.record_audio_checksum(video_id, &checksum.sha256) {
+                warn!("Failed to record checksum for {video_id}: {e}");
+            }
+            return;
+        }
+        let contents = format!("{} {}\n", checksum.sha256, checksum.len);
+        if let Err(e) = fs::write(self.checksum_path(video_id), contents) {
+            warn!("Failed to write checksum for {video_id}: {e}");
+        }
+    }
+
+    /// Forget the checksum file of a cached track.
+    pub fn remove_checksum(&self, video_id: &str) {
+        let _ = fs::remove_file(self.checksum_path(video_id));
+    }
+
+    /// Check audio read from the cache against its recorded checksum.
+    ///
+    /// Audio without a checksum is trusted and has one recorded.
+    pub fn verify(&self, video_id: &str, data: &[u8]) -> bool {
+        let actual = Checksum::of(data);
+        let Some(expected) = self.checksum(video_id) else {
+            debug!("Recording missing checksum for {video_id}");
+            self.write_checksum(video_id, &actual);
+            return true;
+        };
+        expected == actual
+    }
+
+    /// Delete a corrupted track so it is downloaded again.
+    pub fn remove_corrupted(&self, video_id: &str) {
+        warn!("Cached audio for {video_id} is corrupted, deleting it");
+        let _ = fs::remove_file(self.path(video_id));
+        self.remove_checksum(video_id);
+        self.unregister(video_id);
+    }
+
+    /// Verify every cached track, deleting corrupted ones.
+    ///
+    /// Reads all cached audio, so this is slow for a large cache. Progress is
+    /// reported in tracks checked.
+    pub fn verify_all(&self, progress: &dyn ProgressSink) -> CacheVerification {
+        let mut verification = CacheVerification::default();
+        let video_ids = self.video_ids();
+        let total = video_ids.len() as u64;
+        for (done, video_id) in (1..).zip(video_ids) {
+            progress.report(Progress::items(done, total).with_current(&video_id));
+            let Ok(data) = fs::read(self.path(&video_id)) else {
+                continue;
+            };
+            verification.checked += 1;
+            if data.is_empty() || !self.verify(&video_id, &data) {
+                self.remove_corrupted(&video_id);
+                verification.corrupted.push(video_id);
+            }
+        }
+
+        info!(
+            "Verified {} cached tracks, {} corrupted",
+            verification.checked,
+            verification.corrupted.len()
+        );
+        verification
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_roundtrip() {
+        let checksum = Checksum::of(b"hello");
+        assert_eq!(checksum.len, 5);
+        let saved = format!("{} {}\n", checksum.sha256, checksum.len);
+        assert_eq!(Checksum::parse(&saved), Some(checksum));
+        assert_eq!(Checksum::parse("abc 5"), None);
+        assert_eq!(Checksum::parse(""), None);
+    }
+
+    #[test]
+    fn test_corrupted_audio_is_deleted() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = AudioCache::new(dir.path().to_path_buf(), 1024);
+        cache.write("good", b"complete audio");
+        cache.write("bad", b"complete audio");
+
+        // Truncate one file, as an interrupted write would
+        fs::write(cache.path("bad"), b"complete")?;
+        assert!(!cache.contains("bad"));
+        assert_eq!(cache.read("bad"), None);
+        assert!(!cache.path("bad").exists());
+
+        cache.write("bad", b"complete audio");
+        fs::write(cache.path("bad"), b"garbled audio!")?;
+        let reports = parking_lot::Mutex::new(Vec::new());
+        let verification = cache.verify_all(&|progress: Progress| reports.lock().push(progress));
+        assert_eq!(verification.checked, 2);
+        assert!(reports.lock().last().is_some_and(Progress::is_complete));
+        assert_eq!(verification.corrupted, ["bad"]);
+        assert_eq!(cache.read("good").as_deref(), Some(&b"complete audio"[..]));
+        Ok(())
+    }
+}
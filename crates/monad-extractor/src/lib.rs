@@ -1,31 +1,137 @@
 //! # monad-extractor
 //!
-//! `YouTube` audio extraction for Monad using yt-dlp.
+//! `YouTube` audio extraction for Monad.
+//!
+//! Audio is fetched natively through the `InnerTube` player endpoint when
+//! possible, falling back to yt-dlp when that fails.
 //!
 //! Features:
-//! - Disk caching for instant repeated plays
+//! - Disk caching for instant repeated plays, capped in size with LRU eviction
+//! - Large cached tracks memory-mapped instead of copied into memory
+//! - Checksums of cached audio, with corrupted tracks deleted and downloaded again
+//! - Cached tracks registered in monad-cache's database when one is attached
+//! - Optional re-encoding of cached audio to Opus with ffmpeg, to save disk space
+//! - Track metadata (title, artist, album, chapters) saved with cached audio
+//! - Cover art kept in the thumbnail cache for offline use, optionally embedded in the audio
 //! - Downloads audio directly to avoid session-bound URL issues
-//! - Streaming extraction for playback before download completes
-
-use std::fs;
+//! - Streaming extraction for playback before download completes, abandoned if it stalls
+//! - Interrupted downloads resume from a partial file with HTTP range requests
+//! - Background prefetching of upcoming tracks into the cache
+//! - Batch downloads of whole playlists for offline listening, resumed after restarts
+//! - Pinned tracks, albums and playlists kept downloaded with their artwork
+//! - Pluggable [`AudioSource`]s tried in priority order, for audio from elsewhere
+//! - Proxy and download rate limiting for metered connections
+//! - Lower-bitrate formats on slow connections, picked from measured bandwidth
+//! - Intro and outro skipping for music videos
+//! - `SponsorBlock` segments fetched during extraction for auto-skipping
+//! - Proof-of-origin tokens from a pluggable [`PoTokenProvider`]
+//! - yt-dlp version checks, reporting outdated binaries instead of cryptic failures
+
+mod artwork;
+mod bandwidth;
+mod batch;
+mod cache;
+mod diagnostics;
+mod index;
+mod integrity;
+mod metadata;
+mod mmap;
+mod native;
+mod network;
+mod offline;
+mod partial;
+mod po_token;
+mod prefetch;
+mod source;
+mod span;
+mod sponsorblock;
+mod stall;
+mod transcode;
+mod version;
+
+pub use bandwidth::BandwidthEstimate;
+pub use batch::{BatchDownload, BatchOptions, TrackFailure, DEFAULT_BATCH_CONCURRENCY};
+pub use cache::{AudioCache, CacheUsage, DEFAULT_MAX_CACHE_SIZE};
+pub use integrity::{CacheVerification, Checksum};
+pub use metadata::{Chapter, TrackMetadata};
+pub use native::NativeExtractor;
+pub use network::NetworkSettings;
+
+use native::{RangeDownload, StreamChoice};
+pub use partial::{PartialDownload, PartialMeta};
+pub use po_token::{
+    CachedPoTokenProvider, CommandPoTokenProvider, PoToken, PoTokenProvider, StaticPoTokenProvider,
+    DEFAULT_PO_TOKEN_TIMEOUT, DEFAULT_PO_TOKEN_TTL,
+};
+pub use prefetch::PrefetchStatus;
+pub use source::{AudioSource, NATIVE_SOURCE_PRIORITY, YT_DLP_SOURCE_PRIORITY};
+pub use span::SpanResolver;
+pub use sponsorblock::{Segments, SkipCategory, SponsorBlock, DEFAULT_SKIP_CATEGORIES};
+pub use stall::{Stall, StreamTimeouts, DEFAULT_INACTIVITY_TIMEOUT, DEFAULT_TOTAL_TIMEOUT};
+pub use transcode::{TranscodeSettings, DEFAULT_TRANSCODE_BITRATE};
+pub use version::{YtDlpVersion, MIN_YT_DLP_VERSION};
+
+use artwork::cache_artwork;
+use bandwidth::Bandwidth;
+use diagnostics::StderrTail;
+use metadata::YT_DLP_METADATA_TEMPLATE;
+use po_token::fetch_po_token;
+use prefetch::Prefetcher;
+use stall::Watchdog;
+use version::VersionCheck;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::process::Command;
-use tokio::io::AsyncReadExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::process::{Child as AsyncChild, Command as AsyncCommand};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+pub use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
-use monad_core::{Error, Result};
+use monad_cache::CacheManager;
+use monad_core::{AudioQuality, Error, ProgressSink, Result, SkipSegment};
 
 // Re-export StreamChunk for convenience
 pub use monad_core::StreamChunk;
 
+/// Prefix of the machine-readable progress lines requested from yt-dlp.
+const PROGRESS_PREFIX: &str = "monad-progress:";
+
+/// yt-dlp progress template: format ID, downloaded, total and estimated total bytes.
+const PROGRESS_TEMPLATE: &str = "download:monad-progress:%(info.format_id)s:%(progress.downloaded_bytes)s:%(progress.total_bytes)s:%(progress.total_bytes_estimate)s";
+
+/// Emit a progress chunk every 256KB of downloaded data.
+const PROGRESS_INTERVAL_BYTES: u64 = 256 * 1024;
+
+/// Give up on a blocking extraction after this long.
+const EXTRACT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Preferred audio formats, best first.
+const AUDIO_FORMAT: &str = "141/140/bestaudio[ext=m4a]/bestaudio[ext=webm]/bestaudio";
+
+/// yt-dlp format selection for a quality, best first.
+const fn audio_format(quality: AudioQuality) -> &'static str {
+    match quality {
+        AudioQuality::Low => "249/139/250/worstaudio",
+        AudioQuality::Medium => "140/250/bestaudio[abr<=160]/worstaudio",
+        AudioQuality::High | AudioQuality::Max => AUDIO_FORMAT,
+    }
+}
+
 /// Authentication method for yt-dlp.
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum AuthMethod {
     /// Use cookies from a browser (recommended for YouTube Premium).
     BrowserCookies(String),
+    /// Use cookies exported to a Netscape-format `cookies.txt` file.
+    CookiesFile(PathBuf),
+    /// Send an `OAuth` bearer token with every request.
+    OAuth(String),
     /// No authentication.
     None,
 }
@@ -36,26 +142,85 @@ impl Default for AuthMethod {
     }
 }
 
+impl std::fmt::Debug for AuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BrowserCookies(browser) => {
+                f.debug_tuple("BrowserCookies").field(browser).finish()
+            }
+            Self::CookiesFile(path) => f.debug_tuple("CookiesFile").field(path).finish(),
+            // Never log the token itself
+            Self::OAuth(_) => f.debug_tuple("OAuth").field(&"<redacted>").finish(),
+            Self::None => f.write_str("None"),
+        }
+    }
+}
+
 impl AuthMethod {
     fn to_args(&self) -> Vec<String> {
         match self {
             Self::BrowserCookies(browser) => {
                 vec!["--cookies-from-browser".to_string(), browser.clone()]
             }
+            Self::CookiesFile(path) => {
+                vec!["--cookies".to_string(), path.display().to_string()]
+            }
+            Self::OAuth(token) => {
+                vec![
+                    "--add-headers".to_string(),
+                    format!("Authorization:Bearer {token}"),
+                ]
+            }
             Self::None => vec![],
         }
     }
+
+    /// Check that the credentials can be used, e.g. that a cookies file is readable.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::CookiesFile(path) => {
+                let contents = std::fs::read_to_string(path).map_err(|e| {
+                    Error::ExtractionFailed(format!(
+                        "Cannot read cookies file {}: {e}",
+                        path.display()
+                    ))
+                })?;
+                if is_netscape_cookies(&contents) {
+                    Ok(())
+                } else {
+                    Err(Error::ExtractionFailed(format!(
+                        "{} is not a Netscape-format cookies file",
+                        path.display()
+                    )))
+                }
+            }
+            Self::OAuth(token) if token.trim().is_empty() => {
+                Err(Error::ExtractionFailed("OAuth token is empty".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check for a Netscape cookies file: a header comment or tab-separated cookie lines.
+fn is_netscape_cookies(contents: &str) -> bool {
+    let mut lines = contents.lines().map(str::trim).filter(|l| !l.is_empty());
+    lines.next().is_some_and(|first| {
+        first.starts_with("# Netscape HTTP Cookie File")
+            || first.starts_with("# HTTP Cookie File")
+            || first.split('\t').count() == 7
+    })
 }
 
 /// Extracted audio data from YouTube.
 #[derive(Debug, Clone)]
 pub struct ExtractedAudio {
-    /// Raw audio data (opus, m4a, etc.)
-    pub data: Vec<u8>,
+    /// Raw audio data (opus, m4a, etc.), memory-mapped for large cached files
+    pub data: Bytes,
     /// MIME type of the audio
     pub mime_type: String,
-    /// Title of the track (if available)
-    pub title: Option<String>,
+    /// Track metadata (empty if unavailable)
+    pub metadata: TrackMetadata,
 }
 
 /// Handle to a streaming extraction in progress.
@@ -67,6 +232,11 @@ pub struct StreamingExtraction {
 }
 
 impl StreamingExtraction {
+    /// Wrap the receiver of a stream and the task sending to it.
+    pub const fn new(rx: mpsc::Receiver<StreamChunk>, task: JoinHandle<()>) -> Self {
+        Self { rx, task }
+    }
+
     /// Abort the streaming extraction.
     pub fn abort(&self) {
         self.task.abort();
@@ -80,12 +250,29 @@ impl Drop for StreamingExtraction {
     }
 }
 
-/// `YouTube` audio extractor using yt-dlp with disk caching.
+/// `YouTube` audio extractor with disk caching.
 #[allow(clippy::module_name_repetitions)]
 pub struct Extractor {
     yt_dlp_path: PathBuf,
-    cache_dir: PathBuf,
+    /// Compatibility check of the yt-dlp binary, run before its first use.
+    yt_dlp_version: Arc<VersionCheck>,
+    cache: AudioCache,
     auth_method: AuthMethod,
+    /// Native extraction backend, tried before yt-dlp.
+    native: Option<NativeExtractor>,
+    /// Source of PO tokens for both extraction paths.
+    po_token: Option<Arc<dyn PoTokenProvider>>,
+    /// Source of segments to skip, saved with each track's metadata.
+    sponsorblock: SponsorBlock,
+    /// Proxy and rate limit for both download paths.
+    network: NetworkSettings,
+    /// Inactivity and total timeouts for streaming downloads.
+    stream_timeouts: StreamTimeouts,
+    /// Measured throughput, which picks the stream quality.
+    bandwidth: Bandwidth,
+    /// Sources added alongside the built-in ones, with their priorities.
+    custom_sources: Vec<(i32, Arc<dyn AudioSource>)>,
+    prefetcher: Prefetcher,
 }
 
 impl Extractor {
@@ -103,16 +290,152 @@ impl Extractor {
             .map(|d| d.cache_dir().join("audio"))
             .unwrap_or_else(|| PathBuf::from(".cache/audio"));
 
-        // Ensure cache directory exists
-        let _ = fs::create_dir_all(&cache_dir);
-
         Self {
             yt_dlp_path,
-            cache_dir,
+            yt_dlp_version: Arc::new(VersionCheck::new(false)),
+            cache: AudioCache::new(cache_dir, DEFAULT_MAX_CACHE_SIZE),
             auth_method: AuthMethod::default(),
+            native: NativeExtractor::new()
+                .inspect_err(|e| warn!("Native extraction unavailable: {e}"))
+                .ok(),
+            po_token: None,
+            sponsorblock: SponsorBlock::new(),
+            network: NetworkSettings::default(),
+            stream_timeouts: StreamTimeouts::default(),
+            bandwidth: Bandwidth::load(dirs.map(|d| d.cache_dir().join("bandwidth.json"))),
+            custom_sources: Vec::new(),
+            prefetcher: Prefetcher::default(),
         }
     }
 
+    /// Keep the disk cache in a cache database's audio directory, storing tracks through it.
+    #[must_use]
+    pub fn with_cache_index(mut self, index: Arc<CacheManager>) -> Self {
+        self.cache.set_index(index);
+        self
+    }
+
+    /// Enable or disable native extraction (yt-dlp is used exclusively when disabled).
+    #[must_use]
+    pub fn with_native_extraction(mut self, enabled: bool) -> Self {
+        self.native = if enabled {
+            self.native.or_else(|| {
+                NativeExtractor::new()
+                    .and_then(|native| native.with_network(&self.network))
+                    .ok()
+                    .map(|native| native.with_po_token_provider(self.po_token.clone()))
+            })
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Attach PO tokens from `provider` to native and yt-dlp requests.
+    #[must_use]
+    pub fn with_po_token_provider(mut self, provider: impl PoTokenProvider + 'static) -> Self {
+        let provider: Arc<dyn PoTokenProvider> = Arc::new(provider);
+        self.native = self
+            .native
+            .map(|native| native.with_po_token_provider(Some(provider.clone())));
+        self.po_token = Some(provider);
+        self
+    }
+
+    /// Update an outdated yt-dlp with `yt-dlp -U` instead of failing.
+    #[must_use]
+    pub fn with_yt_dlp_auto_update(mut self, enabled: bool) -> Self {
+        self.yt_dlp_version = Arc::new(VersionCheck::new(enabled));
+        self
+    }
+
+    /// Set the `SponsorBlock` categories to skip (none disables lookups).
+    #[must_use]
+    pub fn with_skip_categories(mut self, categories: Vec<SkipCategory>) -> Self {
+        self.sponsorblock = self.sponsorblock.with_categories(categories);
+        self
+    }
+
+    /// Route downloads through a proxy, such as `socks5://127.0.0.1:1080`.
+    #[must_use]
+    pub fn with_proxy(mut self, url: impl Into<String>) -> Self {
+        let previous = self.network.proxy.replace(url.into());
+        if let Err(e) = self.apply_network() {
+            warn!("{e}");
+            self.network.proxy = previous;
+        }
+        self
+    }
+
+    /// Limit the download rate in bytes per second.
+    #[must_use]
+    pub fn with_rate_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.network.rate_limit = Some(bytes_per_sec);
+        if let Err(e) = self.apply_network() {
+            warn!("{e}");
+        }
+        self
+    }
+
+    /// Apply the network settings to native extraction.
+    fn apply_network(&mut self) -> Result<()> {
+        if let Some(native) = &self.native {
+            self.native = Some(native.with_network(&self.network)?);
+        } else {
+            // Validate the settings even when only yt-dlp is used
+            self.network.http_client()?;
+        }
+        Ok(())
+    }
+
+    /// Set how long streaming downloads may go without data, and take in total.
+    #[must_use]
+    pub const fn with_stream_timeouts(mut self, timeouts: StreamTimeouts) -> Self {
+        self.stream_timeouts = timeouts;
+        self
+    }
+
+    /// Always download `quality`, or pick it from the measured bandwidth for `None`.
+    ///
+    /// A fixed quality also keeps pre-caching on over slow connections.
+    #[must_use]
+    pub const fn with_audio_quality(mut self, quality: Option<AudioQuality>) -> Self {
+        self.bandwidth.set_fixed(quality);
+        self
+    }
+
+    /// Get the measured download throughput.
+    pub fn bandwidth_estimate(&self) -> BandwidthEstimate {
+        self.bandwidth.estimate()
+    }
+
+    /// Set the maximum disk cache size in bytes.
+    #[must_use]
+    pub fn with_max_cache_size(mut self, max_bytes: u64) -> Self {
+        self.cache.set_max_bytes(max_bytes);
+        self
+    }
+
+    /// Re-encode downloaded audio to Opus before keeping it in the cache.
+    ///
+    /// Tracks play from the original download; the cached copy is replaced
+    /// in the background once ffmpeg finishes.
+    #[must_use]
+    pub fn with_transcode(mut self, settings: TranscodeSettings) -> Self {
+        self.cache.set_transcode(Some(settings));
+        self
+    }
+
+    /// Embed cover art into cached tracks with ffmpeg from `PATH`.
+    ///
+    /// Artwork is cached for offline use either way, when a cache index is attached.
+    #[must_use]
+    pub fn with_embedded_artwork(mut self, enabled: bool) -> Self {
+        self.cache
+            .set_artwork_ffmpeg(enabled.then(|| PathBuf::from("ffmpeg")));
+        self
+    }
+
     /// Set the authentication method.
     #[must_use]
     pub fn with_auth_method(mut self, auth_method: AuthMethod) -> Self {
@@ -127,6 +450,24 @@ impl Extractor {
         self
     }
 
+    /// Authenticate with a Netscape-format cookies file.
+    #[must_use]
+    pub fn with_cookies_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let auth_method = AuthMethod::CookiesFile(path.into());
+        if let Err(e) = auth_method.validate() {
+            warn!("{e}");
+        }
+        self.auth_method = auth_method;
+        self
+    }
+
+    /// Authenticate with an `OAuth` bearer token.
+    #[must_use]
+    pub fn with_oauth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_method = AuthMethod::OAuth(token.into());
+        self
+    }
+
     /// Get the current authentication method.
     pub fn auth_method(&self) -> &AuthMethod {
         &self.auth_method
@@ -134,65 +475,152 @@ impl Extractor {
 
     /// Clear the disk cache.
     pub fn clear_cache(&self) {
-        if let Err(e) = fs::remove_dir_all(&self.cache_dir) {
-            warn!("Failed to clear cache: {e}");
-        }
-        let _ = fs::create_dir_all(&self.cache_dir);
-        info!("Audio cache cleared");
+        self.cache.clear();
     }
 
-    /// Check if audio is cached for a video ID.
-    pub fn is_cached(&self, video_id: &str) -> bool {
-        let path = self.cache_path(video_id);
-        path.exists() && fs::metadata(&path).map_or(false, |m| m.len() > 0)
+    /// Get the current disk cache usage.
+    pub fn cache_usage(&self) -> CacheUsage {
+        self.cache.usage()
     }
 
-    /// Get cache file path for a video ID.
-    fn cache_path(&self, video_id: &str) -> PathBuf {
-        self.cache_dir.join(format!("{video_id}.audio"))
+    /// Pin a cached track so it is never evicted.
+    pub fn pin(&self, video_id: &str) -> Result<()> {
+        Ok(self.cache.pin(video_id)?)
     }
 
-    /// Check if audio is cached and load it.
-    fn load_from_cache(&self, video_id: &str) -> Option<ExtractedAudio> {
-        let path = self.cache_path(video_id);
-        if !path.exists() {
-            return None;
-        }
+    /// Unpin a track, making it eligible for eviction again.
+    pub fn unpin(&self, video_id: &str) -> Result<()> {
+        Ok(self.cache.unpin(video_id)?)
+    }
 
-        match fs::read(&path) {
-            Ok(data) => {
-                if data.is_empty() {
-                    let _ = fs::remove_file(&path);
-                    return None;
-                }
-                let mime_type = detect_audio_mime(&data);
-                info!("Loaded {} bytes from cache ({})", data.len(), mime_type);
-                Some(ExtractedAudio {
-                    data,
-                    mime_type,
-                    title: None,
-                })
-            }
-            Err(e) => {
-                warn!("Failed to read cache: {e}");
-                None
+    /// Verify every cached track, deleting corrupted ones and prefetching them again.
+    pub async fn verify_cache(
+        &self,
+        progress: impl ProgressSink + 'static,
+    ) -> Result<CacheVerification> {
+        let cache = self.cache.clone();
+        let verification = tokio::task::spawn_blocking(move || cache.verify_all(&progress))
+            .await
+            .map_err(|e| Error::Cache(format!("Cache verification failed: {e}")))?;
+
+        for video_id in &verification.corrupted {
+            if let Err(e) = self.prefetch(video_id) {
+                warn!("Failed to download {video_id} again: {e}");
             }
         }
+        Ok(verification)
     }
 
-    /// Save audio to cache.
-    fn save_to_cache(&self, video_id: &str, data: &[u8]) {
-        let path = self.cache_path(video_id);
-        if let Err(e) = fs::write(&path, data) {
-            warn!("Failed to write cache: {e}");
-        } else {
-            debug!("Cached {} bytes to {:?}", data.len(), path);
+    /// Check if a track is pinned in the cache.
+    pub fn is_pinned(&self, video_id: &str) -> bool {
+        self.cache.is_pinned(video_id)
+    }
+
+    /// Check if audio is cached for a video ID, or an added source has it locally.
+    pub fn is_cached(&self, video_id: &str) -> bool {
+        self.cache.contains(video_id) || self.source_has(video_id)
+    }
+
+    /// Start downloading a track into the cache in the background.
+    ///
+    /// No audio is returned; playing the track later reads it from the cache.
+    /// Prefetches run one at a time, queued behind earlier ones. Does nothing
+    /// if the track is already cached or being prefetched, or if the measured
+    /// bandwidth is too low to spare.
+    pub fn prefetch(&self, video_id: &str) -> Result<()> {
+        if self.is_cached(video_id) || self.prefetcher.is_active(video_id) {
+            return Ok(());
+        }
+        if !self.bandwidth.allows_precaching() {
+            debug!("Connection too slow, not prefetching {video_id}");
+            return Ok(());
+        }
+        self.ensure_backend()?;
+
+        let yt_dlp = self.yt_dlp();
+        let native = self.native.clone();
+        let cache = self.cache.clone();
+        let sources = self.sources_for(video_id);
+        let id = video_id.to_string();
+        let timeouts = self.stream_timeouts;
+        self.prefetcher.start(video_id, move |tx| {
+            download_stream(yt_dlp, native, cache, sources, id, timeouts, tx)
+        });
+        Ok(())
+    }
+
+    /// Get the prefetch status of a track (`Cached` for any cached track).
+    pub fn prefetch_status(&self, video_id: &str) -> Option<PrefetchStatus> {
+        self.prefetcher
+            .status(video_id)
+            .or_else(|| self.is_cached(video_id).then_some(PrefetchStatus::Cached))
+    }
+
+    /// Cancel a prefetch. Its partial download is kept and resumed later.
+    pub fn cancel_prefetch(&self, video_id: &str) {
+        self.prefetcher.cancel(video_id);
+    }
+
+    /// Check that some download backend is available.
+    fn ensure_backend(&self) -> Result<()> {
+        // Without a native backend or added source yt-dlp is required up front
+        if self.native.is_none() && self.custom_sources.is_empty() && !self.yt_dlp_path.exists() {
+            return Err(Error::ExtractionFailed(format!(
+                "yt-dlp not found at {:?}",
+                self.yt_dlp_path
+            )));
         }
+        Ok(())
+    }
+
+    /// Check if audio is cached and load it.
+    fn load_from_cache(&self, video_id: &str) -> Option<ExtractedAudio> {
+        let data = self.cache.read(video_id)?;
+        let mime_type = detect_audio_mime(&data);
+        info!("Loaded {} bytes from cache ({})", data.len(), mime_type);
+        let mut metadata = self.cache.read_metadata(video_id).unwrap_or_default();
+        self.sponsorblock
+            .retain_configured(&mut metadata.skip_segments);
+        Some(ExtractedAudio {
+            data,
+            mime_type,
+            metadata,
+        })
     }
 
     /// Download audio for a video ID.
     /// Returns cached audio instantly if available.
+    ///
+    /// Dropping the returned future kills any yt-dlp process it started.
     pub async fn extract(&self, video_id: &str) -> Result<ExtractedAudio> {
+        self.extract_cancellable(video_id, &CancellationToken::new())
+            .await
+    }
+
+    /// Download audio for a video ID, stopping early if `cancel` is cancelled.
+    ///
+    /// Extraction also fails after a timeout. In both cases the download is
+    /// abandoned and yt-dlp is killed.
+    pub async fn extract_cancellable(
+        &self,
+        video_id: &str,
+        cancel: &CancellationToken,
+    ) -> Result<ExtractedAudio> {
+        tokio::select! {
+            biased;
+            () = cancel.cancelled() => Err(Error::ExtractionFailed(format!(
+                "Extraction of {video_id} cancelled"
+            ))),
+            result = self.download(video_id) => result,
+            () = tokio::time::sleep(EXTRACT_TIMEOUT) => Err(Error::ExtractionFailed(format!(
+                "Extraction of {video_id} timed out after {}s",
+                EXTRACT_TIMEOUT.as_secs()
+            ))),
+        }
+    }
+
+    /// Download audio for a video ID, from the cache if possible.
+    async fn download(&self, video_id: &str) -> Result<ExtractedAudio> {
         // Check cache first - instant return if cached
         if let Some(cached) = self.load_from_cache(video_id) {
             info!("Cache hit for {video_id}");
@@ -200,267 +628,704 @@ impl Extractor {
         }
 
         info!("Cache miss - downloading {video_id}");
-        let url = format!("https://www.youtube.com/watch?v={video_id}");
+        self.prefetcher.cancel(video_id);
 
-        if !self.yt_dlp_path.exists() {
-            return Err(Error::ExtractionFailed(format!(
-                "yt-dlp not found at {:?}",
-                self.yt_dlp_path
-            )));
+        let mut last_error = None;
+        for source in self.sources_for(video_id) {
+            match source.extract(video_id).await {
+                Ok(audio) => return Ok(audio),
+                Err(e) => {
+                    warn!("{} extraction of {video_id} failed: {e}", source.name());
+                    last_error = Some(e);
+                }
+            }
         }
+        Err(last_error
+            .unwrap_or_else(|| Error::ExtractionFailed(format!("No audio source for {video_id}"))))
+    }
+
+    /// Get the cached cover art of a track, if any.
+    pub fn artwork_path(&self, video_id: &str) -> Option<PathBuf> {
+        self.cache.artwork_path(video_id)
+    }
+
+    /// Get the saved metadata of a cached track.
+    pub fn metadata(&self, video_id: &str) -> Option<TrackMetadata> {
+        self.cache.read_metadata(video_id)
+    }
+
+    /// Get the sections of a track to skip.
+    ///
+    /// Cached tracks use the segments saved with their metadata, so this works
+    /// offline; other tracks are looked up.
+    pub async fn skip_segments(&self, video_id: &str) -> Vec<SkipSegment> {
+        let saved = self
+            .cache
+            .read_metadata(video_id)
+            .filter(|_| self.is_cached(video_id));
+        let Some(mut metadata) = saved else {
+            return self.sponsorblock.skip_segments(video_id).await;
+        };
+        self.sponsorblock
+            .retain_configured(&mut metadata.skip_segments);
+        metadata.skip_segments
+    }
 
-        // Build yt-dlp args
+    /// Common yt-dlp arguments: authentication, network limits and the JS runtime for signatures.
+    fn base_args(&self) -> Vec<String> {
         let mut args = self.auth_method.to_args();
+        args.extend(self.network.to_args());
         args.extend([
             "--no-warnings".to_string(),
-            "--no-progress".to_string(),
             "--js-runtimes".to_string(),
             "node".to_string(),
             "--remote-components".to_string(),
             "ejs:github".to_string(),
-            "-f".to_string(),
-            "141/140/bestaudio[ext=m4a]/bestaudio[ext=webm]/bestaudio".to_string(),
-            "-o".to_string(),
-            "-".to_string(),
-            url,
         ]);
+        args
+    }
 
-        debug!("Running yt-dlp");
-
-        let output = Command::new(&self.yt_dlp_path)
-            .args(&args)
-            .output()
-            .map_err(|e| Error::ExtractionFailed(format!("Failed to run yt-dlp: {e}")))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            warn!("yt-dlp stderr: {}", stderr);
-            return Err(Error::ExtractionFailed(format!(
-                "yt-dlp failed: {}",
-                stderr.lines().next().unwrap_or("Unknown error")
-            )));
-        }
-
-        let data = output.stdout;
-        if data.is_empty() {
-            return Err(Error::ExtractionFailed(
-                "yt-dlp returned empty data".to_string(),
-            ));
+    /// yt-dlp invocation settings for this extractor.
+    fn yt_dlp(&self) -> YtDlp {
+        YtDlp {
+            path: self.yt_dlp_path.clone(),
+            version: Arc::clone(&self.yt_dlp_version),
+            base_args: self.base_args(),
+            po_token: self.po_token.clone(),
+            network: self.network.clone(),
+            bandwidth: self.bandwidth.clone(),
         }
-
-        // Save to cache for next time
-        self.save_to_cache(video_id, &data);
-
-        let mime_type = detect_audio_mime(&data);
-        info!("Downloaded {} bytes ({})", data.len(), mime_type);
-
-        Ok(ExtractedAudio {
-            data,
-            mime_type,
-            title: None,
-        })
     }
 
     /// Start streaming extraction for a video ID.
     /// Returns immediately with a receiver for streaming chunks.
     /// Audio data is sent as it's downloaded, enabling playback before download completes.
+    ///
+    /// Downloads are written to a partial file as they arrive. If an earlier
+    /// download of the same video was interrupted, the rest of the stream is
+    /// requested with an HTTP range request instead of starting over.
+    ///
+    /// A download that receives no data for the inactivity timeout, or runs
+    /// past the total timeout, is abandoned with [`StreamChunk::Stalled`].
     pub fn extract_streaming(&self, video_id: &str) -> Result<StreamingExtraction> {
         // Check cache first - if cached, return complete data immediately
         if let Some(cached) = self.load_from_cache(video_id) {
             info!("Cache hit for {video_id} - returning immediately");
             let (tx, rx) = mpsc::channel(16);
-            let data = cached.data;
+            let data = Vec::from(cached.data);
             let task = tokio::spawn(async move {
                 // Send cached data as a single chunk
+                let len = data.len() as u64;
                 let _ = tx.send(StreamChunk::Data(data)).await;
+                let _ = tx.send(StreamChunk::progress(len, Some(len), None)).await;
                 let _ = tx.send(StreamChunk::Complete).await;
             });
             return Ok(StreamingExtraction { rx, task });
         }
 
         info!("Cache miss - starting streaming extraction for {video_id}");
-        let url = format!("https://www.youtube.com/watch?v={video_id}");
 
-        if !self.yt_dlp_path.exists() {
-            return Err(Error::ExtractionFailed(format!(
-                "yt-dlp not found at {:?}",
-                self.yt_dlp_path
-            )));
+        self.ensure_backend()?;
+
+        // A prefetch of this track would write the same partial file
+        self.prefetcher.cancel(video_id);
+
+        let (tx, rx) = mpsc::channel(64);
+        let task = tokio::spawn(download_stream(
+            self.yt_dlp(),
+            self.native.clone(),
+            self.cache.clone(),
+            self.sources_for(video_id),
+            video_id.to_string(),
+            self.stream_timeouts,
+            tx,
+        ));
+
+        Ok(StreamingExtraction { rx, task })
+    }
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// yt-dlp binary and the arguments every invocation needs.
+#[derive(Clone)]
+struct YtDlp {
+    path: PathBuf,
+    version: Arc<VersionCheck>,
+    base_args: Vec<String>,
+    po_token: Option<Arc<dyn PoTokenProvider>>,
+    /// Settings for downloading URLs resolved by yt-dlp.
+    network: NetworkSettings,
+    /// Picks the quality of new downloads and records their throughput.
+    bandwidth: Bandwidth,
+}
+
+impl YtDlp {
+    /// Fail with [`Error::ToolOutdated`] if the binary is too old.
+    async fn check(&self) -> Result<()> {
+        self.version.ensure(&self.path).await
+    }
+
+    /// Arguments for a request for `video_id`, including any PO token.
+    async fn args(&self, video_id: &str) -> Vec<String> {
+        let mut args = self.base_args.clone();
+        if let Some(token) = fetch_po_token(self.po_token.as_ref(), video_id).await {
+            args.extend(["--extractor-args".to_string(), token.extractor_args()]);
         }
+        args
+    }
+}
 
-        // Build yt-dlp args
-        let mut args = self.auth_method.to_args();
-        args.extend([
-            "--no-warnings".to_string(),
-            "--no-progress".to_string(),
-            "--js-runtimes".to_string(),
-            "node".to_string(),
-            "--remote-components".to_string(),
-            "ejs:github".to_string(),
-            "-f".to_string(),
-            "141/140/bestaudio[ext=m4a]/bestaudio[ext=webm]/bestaudio".to_string(),
-            "-o".to_string(),
-            "-".to_string(),
-            url,
-        ]);
+/// yt-dlp arguments printing track metadata to a file for the cache to import.
+fn print_metadata_args(cache: &AudioCache, video_id: &str) -> [String; 3] {
+    [
+        "--print-to-file".to_string(),
+        YT_DLP_METADATA_TEMPLATE.to_string(),
+        cache.yt_dlp_metadata_path(video_id).display().to_string(),
+    ]
+}
 
-        let yt_dlp_path = self.yt_dlp_path.clone();
-        let cache_path = self.cache_path(video_id);
-        let video_id_owned = video_id.to_string();
+/// Collect a track's metadata, skip segments and artwork and save them to the cache.
+///
+/// Metadata comes from the player response when `native` is given, and from
+/// the file printed by yt-dlp otherwise. Failures leave fields empty.
+async fn save_track_info(
+    cache: &AudioCache,
+    sponsorblock: &SponsorBlock,
+    network: &NetworkSettings,
+    native: Option<&NativeExtractor>,
+    video_id: &str,
+) -> TrackMetadata {
+    let metadata = async {
+        match native {
+            Some(native) => native.metadata(video_id).await.unwrap_or_else(|e| {
+                warn!("Failed to get metadata for {video_id}: {e}");
+                TrackMetadata::default()
+            }),
+            None => cache.import_yt_dlp_metadata(video_id).unwrap_or_default(),
+        }
+    };
+    let (mut metadata, segments) = tokio::join!(metadata, sponsorblock.skip_segments(video_id));
+    metadata.skip_segments = segments;
+    cache.write_metadata(video_id, &metadata);
+    cache_artwork(cache, network, video_id, &metadata).await;
+    metadata
+}
 
-        let (tx, rx) = mpsc::channel(64);
+/// Watch URL for a video ID.
+fn watch_url(video_id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={video_id}")
+}
 
-        let task = tokio::spawn(async move {
-            debug!("Spawning yt-dlp for streaming extraction");
-
-            // Use null for stderr to avoid blocking if yt-dlp writes too much
-            let mut child: AsyncChild = match AsyncCommand::new(&yt_dlp_path)
-                .args(&args)
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::null())
-                .spawn()
-            {
-                Ok(child) => child,
-                Err(e) => {
-                    if tx
-                        .send(StreamChunk::Error(format!("Failed to spawn yt-dlp: {e}")))
-                        .await
-                        .is_err()
-                    {
-                        warn!("Failed to send error notification");
-                    }
-                    return;
+/// Download a stream into the cache, sending chunks as they arrive.
+///
+/// Resumes a partial download if possible, then streams from the first of
+/// `sources` that can open the track.
+async fn download_stream(
+    yt_dlp: YtDlp,
+    native: Option<NativeExtractor>,
+    cache: AudioCache,
+    sources: Vec<Arc<dyn AudioSource>>,
+    video_id: String,
+    timeouts: StreamTimeouts,
+    tx: mpsc::Sender<StreamChunk>,
+) {
+    if let Some((existing, meta)) = cache.load_partial(&video_id) {
+        let offset = existing.len() as u64;
+        match open_remaining(native.as_ref(), &yt_dlp, &video_id, &meta, offset).await {
+            Ok(download) => {
+                let partial = cache
+                    .resume_partial(&video_id)
+                    .inspect_err(|e| warn!("Failed to reopen partial download: {e}"))
+                    .ok();
+                let sink = DownloadSink::new(tx, partial, offset, yt_dlp.bandwidth.clone());
+                // Data already on disk is played first
+                if sink.send_existing(existing).await {
+                    stream_range(&cache, sink, download, Watchdog::start(timeouts)).await;
                 }
-            };
-
-            let mut stdout = match child.stdout.take() {
-                Some(stdout) => stdout,
-                None => {
-                    if tx
-                        .send(StreamChunk::Error(
-                            "Failed to capture yt-dlp stdout".to_string(),
-                        ))
-                        .await
-                        .is_err()
-                    {
-                        warn!("Failed to send error notification");
-                    }
-                    return;
-                }
-            };
-
-            // Accumulate all data for caching
-            let mut all_data = Vec::new();
-            let mut buffer = vec![0u8; 65536]; // 64KB chunks
-            let mut total_sent = 0usize;
-            let mut last_logged = 0usize;
-
-            loop {
-                match stdout.read(&mut buffer).await {
-                    Ok(0) => {
-                        // EOF - download complete
-                        debug!(
-                            "Streaming extraction complete: {} bytes total",
-                            all_data.len()
-                        );
+                return;
+            }
+            Err(e) => warn!("Cannot resume download of {video_id}, restarting: {e}"),
+        }
+    }
+
+    let mut last_error = None;
+    for source in sources {
+        match source.extract_streaming(&video_id).await {
+            Ok(mut extraction) => {
+                // Dropping the extraction stops the source once the receiver is gone
+                while let Some(chunk) = extraction.rx.recv().await {
+                    if tx.send(chunk).await.is_err() {
                         break;
                     }
-                    Ok(n) => {
-                        // Extend all_data directly from buffer to avoid double copy
-                        all_data.extend_from_slice(&buffer[..n]);
-                        let chunk = buffer[..n].to_vec();
-                        total_sent += n;
-
-                        if tx.send(StreamChunk::Data(chunk)).await.is_err() {
-                            debug!("Receiver dropped, aborting streaming extraction");
-                            if let Err(e) = child.kill().await {
-                                warn!("Failed to kill yt-dlp: {e}");
-                            }
-                            // Reap the zombie process
-                            let _ = child.wait().await;
-                            return;
-                        }
-
-                        // Log progress every 256KB using threshold-based approach
-                        if total_sent >= last_logged + 256 * 1024 {
-                            last_logged = total_sent;
-                            debug!("Streaming: {} KB sent so far", total_sent / 1024);
-                        }
-                    }
-                    Err(e) => {
-                        if tx
-                            .send(StreamChunk::Error(format!("Read error: {e}")))
-                            .await
-                            .is_err()
-                        {
-                            warn!("Failed to send error notification");
-                        }
-                        return;
-                    }
                 }
+                return;
+            }
+            Err(e) => {
+                warn!("{} cannot stream {video_id}: {e}", source.name());
+                last_error = Some(e);
             }
+        }
+    }
+    let message = last_error.map_or_else(
+        || format!("No audio source for {video_id}"),
+        |e| e.to_string(),
+    );
+    send_error(&tx, message).await;
+}
 
-            // Check exit status
-            match child.wait().await {
-                Ok(status) if status.success() => {
-                    if all_data.is_empty() {
-                        if tx
-                            .send(StreamChunk::Error("yt-dlp returned empty data".to_string()))
-                            .await
-                            .is_err()
-                        {
-                            warn!("Failed to send error notification");
-                        }
-                        return;
+/// Download a stream from scratch by piping yt-dlp's output.
+async fn stream_yt_dlp(
+    yt_dlp: &YtDlp,
+    cache: &AudioCache,
+    sponsorblock: &SponsorBlock,
+    video_id: &str,
+    watchdog: Watchdog,
+    tx: mpsc::Sender<StreamChunk>,
+) {
+    debug!("Spawning yt-dlp for streaming extraction");
+
+    // Progress lines go to stderr since audio goes to stdout
+    let mut args = yt_dlp.args(video_id).await;
+    args.extend([
+        "--progress".to_string(),
+        "--newline".to_string(),
+        "--progress-template".to_string(),
+        PROGRESS_TEMPLATE.to_string(),
+        "-f".to_string(),
+        audio_format(yt_dlp.bandwidth.quality()).to_string(),
+    ]);
+    args.extend(print_metadata_args(cache, video_id));
+    args.extend(["-o".to_string(), "-".to_string(), watch_url(video_id)]);
+
+    let mut child: AsyncChild = match AsyncCommand::new(&yt_dlp.path)
+        .args(&args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            send_error(&tx, format!("Failed to spawn yt-dlp: {e}")).await;
+            return;
+        }
+    };
+
+    let Some(mut stdout) = child.stdout.take() else {
+        send_error(&tx, "Failed to capture yt-dlp stdout".to_string()).await;
+        return;
+    };
+
+    let partial = match cache.begin_partial(video_id) {
+        Ok(partial) => Some(partial),
+        Err(e) => {
+            warn!("Failed to create partial download, it cannot be resumed: {e}");
+            None
+        }
+    };
+
+    // Drain stderr continuously so yt-dlp never blocks on a full pipe, picking
+    // up the total size and format from its progress lines and keeping the
+    // rest for diagnostics.
+    let total_bytes = Arc::new(AtomicU64::new(0));
+    let stderr_tail = StderrTail::default();
+    let stderr_task = child.stderr.take().map(|stderr| {
+        let total_bytes = total_bytes.clone();
+        let stderr_tail = stderr_tail.clone();
+        let video_id = video_id.to_string();
+        let meta_path = partial.as_ref().map(|p| p.meta_path().to_path_buf());
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            let mut saved_meta = None;
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Some(progress) = parse_progress_line(&line) else {
+                    stderr_tail.push(&video_id, &line);
+                    continue;
+                };
+                if let Some(total) = progress.total {
+                    total_bytes.store(total, Ordering::Relaxed);
+                }
+                // Record what is needed to resume this exact stream later
+                if let (Some(path), Some(format_id)) = (&meta_path, progress.format_id) {
+                    let meta = PartialMeta {
+                        format_id,
+                        total: progress.total,
+                    };
+                    if saved_meta.as_ref() != Some(&meta) {
+                        meta.save(path);
+                        saved_meta = Some(meta);
                     }
+                }
+            }
+        })
+    });
 
-                    // Cache the complete download
-                    if let Err(e) = fs::write(&cache_path, &all_data) {
-                        warn!("Failed to cache audio: {e}");
-                    } else {
-                        debug!(
-                            "Cached {} bytes for {} at {:?}",
-                            all_data.len(),
-                            video_id_owned,
-                            cache_path
-                        );
-                    }
+    let mut sink = DownloadSink::new(tx, partial, 0, yt_dlp.bandwidth.clone());
+    let mut buffer = vec![0u8; 65536]; // 64KB chunks
 
-                    if tx.send(StreamChunk::Complete).await.is_err() {
-                        warn!("Failed to send completion notification");
-                    }
+    loop {
+        let read = match watchdog.read(stdout.read(&mut buffer)).await {
+            Ok(read) => read,
+            Err(stall) => {
+                if let Err(e) = child.kill().await {
+                    warn!("Failed to kill yt-dlp: {e}");
                 }
-                Ok(status) => {
-                    let error_msg =
-                        format!("yt-dlp exited with error (exit code: {:?})", status.code());
-                    if tx.send(StreamChunk::Error(error_msg)).await.is_err() {
-                        warn!("Failed to send error notification");
+                sink.stall(stall).await;
+                return;
+            }
+        };
+        match read {
+            Ok(0) => {
+                // EOF - download complete
+                debug!(
+                    "Streaming extraction complete: {} bytes total",
+                    sink.bytes()
+                );
+                break;
+            }
+            Ok(n) => {
+                let total = Some(total_bytes.load(Ordering::Relaxed)).filter(|&t| t > 0);
+                if !sink.push(buffer[..n].to_vec(), total).await {
+                    debug!("Receiver dropped, aborting streaming extraction");
+                    if let Err(e) = child.kill().await {
+                        warn!("Failed to kill yt-dlp: {e}");
                     }
+                    // Reap the zombie process
+                    let _ = child.wait().await;
+                    return;
                 }
-                Err(e) => {
-                    if tx
-                        .send(StreamChunk::Error(format!(
-                            "Failed to wait for yt-dlp: {e}"
-                        )))
-                        .await
-                        .is_err()
-                    {
-                        warn!("Failed to send error notification");
-                    }
+            }
+            Err(e) => {
+                sink.fail(format!("Read error: {e}")).await;
+                return;
+            }
+        }
+    }
+
+    // Check exit status
+    let status = child.wait().await;
+    if let Some(stderr_task) = stderr_task {
+        // Metadata must not be rewritten after the partial file is finished
+        let _ = stderr_task.await;
+    }
+
+    match status {
+        Ok(status) if status.success() => {
+            if sink.bytes() == 0 {
+                cache.discard_partial(video_id);
+                sink.fail("yt-dlp returned empty data".to_string()).await;
+                return;
+            }
+            sink.complete(cache).await;
+            save_track_info(cache, sponsorblock, &yt_dlp.network, None, video_id).await;
+        }
+        Ok(status) => {
+            let failure = format!("yt-dlp exited with error (exit code: {:?})", status.code());
+            sink.fail(stderr_tail.describe(&failure)).await;
+        }
+        Err(e) => {
+            sink.fail(format!("Failed to wait for yt-dlp: {e}")).await;
+        }
+    }
+}
+
+/// Request the rest of a partially downloaded stream with HTTP range requests.
+async fn open_remaining(
+    native: Option<&NativeExtractor>,
+    yt_dlp: &YtDlp,
+    video_id: &str,
+    meta: &PartialMeta,
+    offset: u64,
+) -> Result<RangeDownload> {
+    // Stream URLs expire, so resolve a fresh one for the same format
+    let native_download = if let Some((native, itag)) = native.zip(meta.format_id.parse().ok()) {
+        native
+            .open(video_id, StreamChoice::Itag(itag), offset)
+            .await
+            .inspect_err(|e| debug!("Native stream lookup failed, asking yt-dlp: {e}"))
+            .ok()
+    } else {
+        None
+    };
+    let download = if let Some((_, download)) = native_download {
+        download
+    } else {
+        let url = yt_dlp_stream_url(yt_dlp, video_id, &meta.format_id).await?;
+        RangeDownload::open(&yt_dlp.network.http_client()?, url, offset)
+            .await?
+            .with_rate_limit(yt_dlp.network.rate_limit)
+    };
+
+    // A different total size means this is not the stream we started
+    if let (Some(expected), Some(actual)) = (meta.total, download.total()) {
+        if expected != actual {
+            return Err(Error::ExtractionFailed(format!(
+                "Stream size changed ({expected} -> {actual} bytes)"
+            )));
+        }
+    }
+
+    Ok(download)
+}
+
+/// Resolve the URL of a stream format with `yt-dlp --get-url`.
+async fn yt_dlp_stream_url(yt_dlp: &YtDlp, video_id: &str, format_id: &str) -> Result<String> {
+    yt_dlp.check().await?;
+    let mut args = yt_dlp.args(video_id).await;
+    args.extend([
+        "--get-url".to_string(),
+        "-f".to_string(),
+        format_id.to_string(),
+        watch_url(video_id),
+    ]);
+
+    let output = AsyncCommand::new(&yt_dlp.path)
+        .args(&args)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Error::ExtractionFailed(format!("Failed to run yt-dlp: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::ExtractionFailed(
+            "yt-dlp could not resolve the stream URL".to_string(),
+        ));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| Error::ExtractionFailed("yt-dlp returned no stream URL".to_string()))
+}
+
+/// Stream a range download into the sink, caching it once complete.
+async fn stream_range(
+    cache: &AudioCache,
+    mut sink: DownloadSink,
+    mut download: RangeDownload,
+    watchdog: Watchdog,
+) {
+    let total = download.total();
+    loop {
+        let chunk = match watchdog.read(download.chunk()).await {
+            Ok(chunk) => chunk,
+            Err(stall) => {
+                sink.stall(stall).await;
+                return;
+            }
+        };
+        match chunk {
+            Ok(Some(data)) => {
+                if !sink.push(data, total).await {
+                    debug!("Receiver dropped, aborting download");
+                    return;
                 }
             }
-        });
+            Ok(None) => break,
+            Err(e) => {
+                sink.fail(format!("Read error: {e}")).await;
+                return;
+            }
+        }
+    }
 
-        Ok(StreamingExtraction { rx, task })
+    debug!("Download complete: {} bytes total", sink.bytes());
+    sink.complete(cache).await;
+}
+
+/// Sends downloaded audio to the stream receiver and appends it to the partial file.
+struct DownloadSink {
+    tx: mpsc::Sender<StreamChunk>,
+    partial: Option<PartialDownload>,
+    progress: ProgressTracker,
+    /// Receives the throughput of the finished download.
+    bandwidth: Bandwidth,
+}
+
+impl DownloadSink {
+    fn new(
+        tx: mpsc::Sender<StreamChunk>,
+        partial: Option<PartialDownload>,
+        offset: u64,
+        bandwidth: Bandwidth,
+    ) -> Self {
+        Self {
+            tx,
+            partial,
+            progress: ProgressTracker::starting_at(offset),
+            bandwidth,
+        }
+    }
+
+    /// Total bytes downloaded, including resumed data.
+    const fn bytes(&self) -> u64 {
+        self.progress.bytes()
+    }
+
+    /// Send data that was already downloaded. Returns false if the receiver is gone.
+    async fn send_existing(&self, data: Vec<u8>) -> bool {
+        self.tx.send(StreamChunk::Data(data)).await.is_ok()
+    }
+
+    /// Save and send newly downloaded data. Returns false if the receiver is gone.
+    async fn push(&mut self, data: Vec<u8>, total: Option<u64>) -> bool {
+        if let Some(partial) = &mut self.partial {
+            if let Err(e) = partial.append(&data) {
+                warn!("Failed to write partial download, it will not be cached: {e}");
+                self.partial = None;
+            }
+        }
+
+        let progress_chunk = self.progress.record(data.len() as u64, total);
+        if self.tx.send(StreamChunk::Data(data)).await.is_err() {
+            return false;
+        }
+        if let Some(progress_chunk) = progress_chunk {
+            debug!("Streaming: {} KB sent so far", self.progress.bytes() / 1024);
+            return self.tx.send(progress_chunk).await.is_ok();
+        }
+        true
+    }
+
+    /// Report a failure. The partial file is kept so a retry can resume.
+    async fn fail(&self, message: String) {
+        send_error(&self.tx, message).await;
+    }
+
+    /// Report an abandoned download. The partial file is kept so a retry can resume.
+    async fn stall(&self, stall: Stall) {
+        warn!("Download stalled: {stall}");
+        if self
+            .tx
+            .send(StreamChunk::Stalled(stall.to_string()))
+            .await
+            .is_err()
+        {
+            warn!("Failed to send stall notification");
+        }
+    }
+
+    /// Move the download into the cache and signal completion.
+    async fn complete(self, cache: &AudioCache) {
+        let (downloaded, elapsed) = self.progress.sample();
+        self.bandwidth.record(downloaded, elapsed);
+        if let Some(partial) = self.partial {
+            if let Err(e) = cache.finish_partial(partial) {
+                warn!("Failed to cache download: {e}");
+            }
+        }
+
+        // Final progress so the UI can show a full bar
+        let _ = self.tx.send(self.progress.finish()).await;
+
+        if self.tx.send(StreamChunk::Complete).await.is_err() {
+            warn!("Failed to send completion notification");
+        }
     }
 }
 
-impl Default for Extractor {
-    fn default() -> Self {
-        Self::new()
+async fn send_error(tx: &mpsc::Sender<StreamChunk>, message: String) {
+    if tx.send(StreamChunk::Error(message)).await.is_err() {
+        warn!("Failed to send error notification");
+    }
+}
+
+/// Tracks downloaded bytes and throughput for `StreamChunk::Progress` reporting.
+struct ProgressTracker {
+    started: Instant,
+    /// Bytes already on disk when the download started (resumed downloads).
+    offset: u64,
+    bytes: u64,
+    last_reported: u64,
+}
+
+impl ProgressTracker {
+    /// Track a download that already has `offset` bytes.
+    fn starting_at(offset: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            offset,
+            bytes: offset,
+            last_reported: offset,
+        }
+    }
+
+    /// Total bytes recorded so far.
+    const fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Record `n` downloaded bytes, returning a progress chunk once per interval.
+    fn record(&mut self, n: u64, total: Option<u64>) -> Option<StreamChunk> {
+        self.bytes += n;
+        if self.bytes < self.last_reported + PROGRESS_INTERVAL_BYTES {
+            return None;
+        }
+        self.last_reported = self.bytes;
+        Some(StreamChunk::progress(self.bytes, total, self.speed()))
+    }
+
+    /// Bytes downloaded by this download, excluding resumed data, and the time taken.
+    fn sample(&self) -> (u64, Duration) {
+        (self.bytes - self.offset, self.started.elapsed())
+    }
+
+    /// Final progress chunk: the download size is now exact.
+    fn finish(&self) -> StreamChunk {
+        StreamChunk::progress(self.bytes, Some(self.bytes), self.speed())
+    }
+
+    /// Average download speed in bytes per second, excluding resumed data.
+    #[allow(clippy::cast_precision_loss)]
+    fn speed(&self) -> Option<f64> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        (elapsed > 0.0).then(|| (self.bytes - self.offset) as f64 / elapsed)
     }
 }
 
+/// Fields of a yt-dlp progress line produced by `PROGRESS_TEMPLATE`.
+#[derive(Debug, PartialEq, Eq)]
+struct ProgressLine {
+    /// Format ID of the stream being downloaded.
+    format_id: Option<String>,
+    /// Total size in bytes, or yt-dlp's estimate if the exact size is unknown.
+    total: Option<u64>,
+}
+
+/// Parse a yt-dlp progress line produced by `PROGRESS_TEMPLATE`.
+///
+/// yt-dlp prints `NA` for unknown fields and may format numbers as floats.
+fn parse_progress_line(line: &str) -> Option<ProgressLine> {
+    let rest = line.trim().strip_prefix(PROGRESS_PREFIX)?;
+    let mut fields = rest.split(':');
+
+    let format_id = fields
+        .next()
+        .map(str::trim)
+        .filter(|f| !f.is_empty() && *f != "NA")
+        .map(str::to_string);
+    fields.next(); // downloaded bytes are counted locally
+
+    let parse = |field: Option<&str>| -> Option<u64> {
+        let value: f64 = field?.trim().parse().ok()?;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        (value > 0.0).then_some(value as u64)
+    };
+
+    let total = parse(fields.next());
+    let estimate = parse(fields.next());
+    Some(ProgressLine {
+        format_id,
+        total: total.or(estimate),
+    })
+}
+
 /// Detect audio MIME type from magic bytes.
 fn detect_audio_mime(data: &[u8]) -> String {
     if data.len() < 12 {
@@ -494,6 +1359,16 @@ fn detect_audio_mime(data: &[u8]) -> String {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_extract_cancelled() {
+        let extractor = Extractor::new().with_native_extraction(false);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let result = extractor.extract_cancellable("dQw4w9WgXcQ", &cancel).await;
+        assert!(matches!(result, Err(Error::ExtractionFailed(e)) if e.contains("cancelled")));
+    }
+
     #[test]
     fn test_extractor_creation() {
         let extractor = Extractor::new();
@@ -503,6 +1378,73 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_auth_method_args() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cookies.txt");
+        std::fs::write(
+            &path,
+            "# Netscape HTTP Cookie File\n.youtube.com\tTRUE\t/\tTRUE\t0\tSID\tabc\n",
+        )?;
+
+        let cookies = AuthMethod::CookiesFile(path.clone());
+        assert_eq!(
+            cookies.to_args(),
+            ["--cookies", path.to_str().unwrap_or_default()]
+        );
+        assert!(cookies.validate().is_ok());
+
+        std::fs::write(&path, "{\"not\": \"cookies\"}")?;
+        assert!(cookies.validate().is_err());
+        assert!(AuthMethod::CookiesFile(dir.path().join("missing"))
+            .validate()
+            .is_err());
+
+        let oauth = AuthMethod::OAuth("secret".to_string());
+        assert_eq!(
+            oauth.to_args(),
+            ["--add-headers", "Authorization:Bearer secret"]
+        );
+        assert!(!format!("{oauth:?}").contains("secret"));
+        let json = serde_json::to_string(&cookies)?;
+        assert_eq!(
+            serde_json::from_str::<AuthMethod>(&json)?.to_args(),
+            cookies.to_args()
+        );
+        assert!(AuthMethod::OAuth(String::new()).validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_progress_line() {
+        let total = |line| parse_progress_line(line).and_then(|p| p.total);
+        assert_eq!(total("monad-progress:140:1024:4096:NA"), Some(4096));
+        assert_eq!(total("monad-progress:140:1024:NA:5000.5"), Some(5000));
+        assert_eq!(total("monad-progress:140:1024:NA:NA"), None);
+        assert_eq!(
+            parse_progress_line("monad-progress:251:0:NA:NA").and_then(|p| p.format_id),
+            Some("251".to_string())
+        );
+        assert_eq!(parse_progress_line("[youtube] abc: Downloading"), None);
+    }
+
+    #[test]
+    fn test_progress_tracker_interval() {
+        let mut tracker = ProgressTracker::starting_at(0);
+        assert!(tracker.record(1024, None).is_none());
+        let chunk = tracker.record(PROGRESS_INTERVAL_BYTES, Some(PROGRESS_INTERVAL_BYTES * 4));
+        assert!(matches!(
+            chunk,
+            Some(StreamChunk::Progress(p))
+                if p.completed == PROGRESS_INTERVAL_BYTES + 1024 && p.percent().is_some()
+        ));
+        assert!(tracker.record(1, None).is_none());
+        assert!(matches!(
+            tracker.finish(),
+            StreamChunk::Progress(p) if p.is_complete()
+        ));
+    }
+
     #[test]
     fn test_mime_detection() {
         assert_eq!(
@@ -0,0 +1,170 @@
+//! Track metadata collected alongside the audio.
+//!
+//! yt-dlp prints a subset of its info dict next to the download, and native
+//! extraction reads the `InnerTube` player response. Either way the result is
+//! stored beside the cached audio so the offline library keeps its titles.
+
+use monad_core::{Error, Result, SkipSegment};
+use monad_innertube::types::VideoDetails;
+use serde::{Deserialize, Serialize};
+
+/// yt-dlp `--print-to-file` template selecting the fields metadata is read from.
+pub const YT_DLP_METADATA_TEMPLATE: &str =
+    "%(.{title,track,artist,creator,uploader,channel,album,duration,chapters,thumbnail})j";
+
+/// Suffix `YouTube` adds to auto-generated artist channels.
+const TOPIC_SUFFIX: &str = " - Topic";
+
+/// A chapter of a video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    /// Chapter title.
+    pub title: String,
+    /// Start time in seconds.
+    pub start: f64,
+    /// End time in seconds.
+    pub end: f64,
+}
+
+/// Metadata of an extracted track.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackMetadata {
+    /// Track title.
+    pub title: Option<String>,
+    /// Artist name(s).
+    pub artist: Option<String>,
+    /// Album name.
+    pub album: Option<String>,
+    /// Duration in seconds.
+    pub duration: Option<f64>,
+    /// Chapters, in order.
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    /// Thumbnail URL.
+    pub thumbnail_url: Option<String>,
+    /// `SponsorBlock` segments to skip, in the order returned.
+    #[serde(default)]
+    pub skip_segments: Vec<SkipSegment>,
+}
+
+/// The fields of yt-dlp's info dict selected by [`YT_DLP_METADATA_TEMPLATE`].
+#[derive(Deserialize)]
+struct YtDlpInfo {
+    title: Option<String>,
+    track: Option<String>,
+    artist: Option<String>,
+    creator: Option<String>,
+    uploader: Option<String>,
+    channel: Option<String>,
+    album: Option<String>,
+    duration: Option<f64>,
+    chapters: Option<Vec<YtDlpChapter>>,
+    thumbnail: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct YtDlpChapter {
+    title: String,
+    start_time: f64,
+    end_time: f64,
+}
+
+/// Strip the " - Topic" suffix of auto-generated artist channels.
+fn channel_artist(channel: String) -> String {
+    match channel.strip_suffix(TOPIC_SUFFIX) {
+        Some(artist) => artist.to_string(),
+        None => channel,
+    }
+}
+
+impl TrackMetadata {
+    /// Parse the JSON printed by yt-dlp with [`YT_DLP_METADATA_TEMPLATE`].
+    pub fn from_yt_dlp_json(json: &str) -> Result<Self> {
+        let info: YtDlpInfo = serde_json::from_str(json)
+            .map_err(|e| Error::Parse(format!("Invalid yt-dlp metadata: {e}")))?;
+
+        // Music metadata fields are preferred over the generic video ones
+        let artist = info
+            .artist
+            .or(info.creator)
+            .or_else(|| info.uploader.or(info.channel).map(channel_artist));
+        Ok(Self {
+            title: info.track.or(info.title),
+            artist,
+            album: info.album,
+            duration: info.duration,
+            chapters: info
+                .chapters
+                .unwrap_or_default()
+                .into_iter()
+                .map(|c| Chapter {
+                    title: c.title,
+                    start: c.start_time,
+                    end: c.end_time,
+                })
+                .collect(),
+            thumbnail_url: info.thumbnail,
+            skip_segments: Vec::new(),
+        })
+    }
+
+    /// Build metadata from the video details of a player response.
+    pub fn from_video_details(details: &VideoDetails) -> Self {
+        let thumbnail_url = details
+            .thumbnail
+            .as_ref()
+            .and_then(|t| t.thumbnails.as_ref())
+            .and_then(|thumbnails| thumbnails.iter().max_by_key(|t| t.width.unwrap_or(0)))
+            .map(|t| t.url.clone());
+        Self {
+            title: Some(details.title.clone()),
+            artist: details.author.clone().map(channel_artist),
+            album: None,
+            duration: details
+                .length_seconds
+                .as_deref()
+                .and_then(|s| s.parse().ok()),
+            chapters: Vec::new(),
+            thumbnail_url,
+            skip_segments: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_yt_dlp_json() -> Result<()> {
+        let json = r#"{
+            "title": "Song (Official Video)",
+            "track": "Song",
+            "artist": "Artist",
+            "uploader": "Artist - Topic",
+            "album": "Album",
+            "duration": 215.0,
+            "chapters": [{"title": "Intro", "start_time": 0.0, "end_time": 12.5}],
+            "thumbnail": "https://i.ytimg.com/vi/abc/maxresdefault.jpg"
+        }"#;
+        let metadata = TrackMetadata::from_yt_dlp_json(json)?;
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.artist.as_deref(), Some("Artist"));
+        assert_eq!(metadata.album.as_deref(), Some("Album"));
+        assert_eq!(metadata.duration, Some(215.0));
+        assert_eq!(metadata.chapters.len(), 1);
+        assert!(metadata.thumbnail_url.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_yt_dlp_json_falls_back_to_channel() -> Result<()> {
+        let metadata =
+            TrackMetadata::from_yt_dlp_json(r#"{"title": "Song", "uploader": "Artist - Topic"}"#)?;
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.artist.as_deref(), Some("Artist"));
+        assert!(metadata.chapters.is_empty());
+        assert!(TrackMetadata::from_yt_dlp_json("not json").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+//! Memory-mapped reads of large cached files.
+//!
+//! Long tracks and mixes can be hundreds of megabytes. Mapping them instead
+//! of reading them into the heap means replaying one costs no copy: the
+//! returned [`Bytes`] borrow the page cache until the last clone is dropped.
+//! Cache files are replaced by renaming over them, never rewritten in place,
+//! so a mapping always sees a complete file.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use bytes::Bytes;
+use memmap2::Mmap;
+use tracing::debug;
+
+/// Files at least this large are memory-mapped (8 MB).
+pub const MMAP_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// Read a whole file, memory-mapping it if it is large.
+pub fn read_file(path: &Path) -> io::Result<Bytes> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    if len >= MMAP_THRESHOLD {
+        // SAFETY: cache files are replaced by renaming, never modified in
+        // place, so the mapped file doesn't change under the mapping
+        #[allow(unsafe_code)]
+        match unsafe { Mmap::map(&file) } {
+            Ok(mapping) => return Ok(Bytes::from_owner(mapping)),
+            Err(e) => debug!("Failed to map {}, reading it: {e}", path.display()),
+        }
+    }
+
+    let mut data = Vec::with_capacity(usize::try_from(len).unwrap_or(0));
+    file.read_to_end(&mut data)?;
+    Ok(Bytes::from(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_file() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let small = dir.path().join("small");
+        std::fs::write(&small, b"small file")?;
+        assert_eq!(read_file(&small)?, &b"small file"[..]);
+
+        // Large enough to be mapped
+        let large = dir.path().join("large");
+        let data: Vec<u8> = (0..MMAP_THRESHOLD + 3)
+            .map(|i| u8::try_from(i % 251).unwrap_or(0))
+            .collect();
+        std::fs::write(&large, &data)?;
+        let mapped = read_file(&large)?;
+        assert_eq!(mapped.len(), data.len());
+        assert!(mapped == data);
+
+        // The mapping stays valid after the file is replaced
+        std::fs::write(dir.path().join("new"), b"replacement")?;
+        std::fs::rename(dir.path().join("new"), &large)?;
+        assert_eq!(mapped.slice(..3), &data[..3]);
+        Ok(())
+    }
+}
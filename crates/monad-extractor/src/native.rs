@@ -0,0 +1,516 @@
+//! Native extraction through the `InnerTube` player endpoint, without yt-dlp.
+//!
+//! Streams are requested as the `YouTube` Music iOS client, whose URLs come
+//! without a cipher and download at full speed without the `n` parameter
+//! transformed, which `monad-innertube` can't do for web clients. Audio is
+//! downloaded directly over HTTP in fixed-size range requests, since
+//! `YouTube` throttles long single requests. When the stream length is known,
+//! a small first range gets playback going while the rest is fetched in
+//! several ranges at once and handed over in order.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use monad_core::{AudioQuality, Error, HttpError, Result, StreamInfo};
+use monad_innertube::{ClientContext, InnerTubeClient};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::metadata::TrackMetadata;
+use crate::network::{NetworkSettings, Throttle};
+use crate::po_token::{fetch_po_token, PoTokenProvider};
+
+/// Size of each range request.
+const RANGE_CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Size of the first range request, which playback starts from.
+const HEAD_SIZE: u64 = 512 * 1024;
+
+/// Size of each range fetched in parallel after the first.
+const SEGMENT_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Number of ranges fetched at once after the first.
+const PARALLEL_SEGMENTS: usize = 4;
+
+/// Preferred audio itags for a quality, best first (matching the yt-dlp format selection).
+const fn preferred_itags(quality: AudioQuality) -> &'static [u32] {
+    match quality {
+        AudioQuality::Low => &[249, 139, 250],
+        AudioQuality::Medium => &[140, 250],
+        AudioQuality::High | AudioQuality::Max => &[141, 140],
+    }
+}
+
+/// Which stream of a video to download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamChoice {
+    /// The stream with this itag, e.g. to resume a download.
+    Itag(u32),
+    /// The preferred stream for this quality.
+    Quality(AudioQuality),
+}
+
+/// Resolves and downloads audio streams without spawning yt-dlp.
+#[derive(Clone)]
+pub struct NativeExtractor {
+    innertube: InnerTubeClient,
+    http: reqwest::Client,
+    po_token: Option<Arc<dyn PoTokenProvider>>,
+    /// Maximum download rate in bytes per second.
+    rate_limit: Option<u64>,
+}
+
+impl NativeExtractor {
+    /// Create a native extractor.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            innertube: InnerTubeClient::with_context(ClientContext::music_ios())?,
+            http: reqwest::Client::new(),
+            po_token: None,
+            rate_limit: None,
+        })
+    }
+
+    /// Download through the proxy and under the rate limit of `network`.
+    pub fn with_network(&self, network: &NetworkSettings) -> Result<Self> {
+        Ok(Self {
+            http: network.http_client()?,
+            rate_limit: network.rate_limit,
+            ..self.clone()
+        })
+    }
+
+    /// Attest player requests with tokens from a PO token provider.
+    #[must_use]
+    pub fn with_po_token_provider(mut self, provider: Option<Arc<dyn PoTokenProvider>>) -> Self {
+        self.po_token = provider;
+        self
+    }
+
+    /// Resolve a stream and start downloading it at `offset`.
+    ///
+    /// Returns the itag of the chosen stream along with the download.
+    pub(crate) async fn open(
+        &self,
+        video_id: &str,
+        choice: StreamChoice,
+        offset: u64,
+    ) -> Result<(u32, RangeDownload)> {
+        let po_token = fetch_po_token(self.po_token.as_ref(), video_id).await;
+        let streams = self
+            .innertube
+            .get_streams_with_po_token(video_id, po_token.as_ref().map(|t| t.token.as_str()))
+            .await?
+            .streams;
+        let stream = match choice {
+            StreamChoice::Itag(itag) => streams.iter().find(|s| stream_itag(s) == Some(itag)),
+            StreamChoice::Quality(quality) => choose_stream(&streams, quality),
+        }
+        .ok_or_else(|| Error::ContentNotAvailable("No matching audio stream".to_string()))?;
+
+        let itag = stream_itag(stream)
+            .ok_or_else(|| Error::StreamExtraction("Stream URL has no itag".to_string()))?;
+        debug!("Native extraction of {video_id} using itag {itag}");
+
+        let download = RangeDownload::open(&self.http, stream.url.clone(), offset)
+            .await?
+            .with_rate_limit(self.rate_limit);
+        Ok((itag, download))
+    }
+
+    /// Fetch the metadata of a video from its player response.
+    pub async fn metadata(&self, video_id: &str) -> Result<TrackMetadata> {
+        let response = self.innertube.get_player_response(video_id).await?;
+        response
+            .video_details
+            .as_ref()
+            .map(TrackMetadata::from_video_details)
+            .ok_or_else(|| Error::Parse("Player response has no video details".to_string()))
+    }
+
+    /// Download a complete stream of the given quality into memory.
+    pub async fn download(&self, video_id: &str, quality: AudioQuality) -> Result<Vec<u8>> {
+        let (_, mut download) = self
+            .open(video_id, StreamChoice::Quality(quality), 0)
+            .await?;
+        let mut data = Vec::with_capacity(download.total().unwrap_or(0).try_into().unwrap_or(0));
+        while let Some(chunk) = download.chunk().await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+}
+
+/// Pick the preferred audio-only stream for a quality: known itags, then any M4A, then the best.
+///
+/// Below high quality, the lowest-quality stream is the fallback instead.
+fn choose_stream(streams: &[StreamInfo], quality: AudioQuality) -> Option<&StreamInfo> {
+    let audio: Vec<&StreamInfo> = streams
+        .iter()
+        .filter(|s| {
+            s.mime_type
+                .as_deref()
+                .is_some_and(|m| m.starts_with("audio/"))
+        })
+        .collect();
+
+    preferred_itags(quality)
+        .iter()
+        .find_map(|&itag| audio.iter().find(|s| stream_itag(s) == Some(itag)))
+        .or_else(|| {
+            (quality < AudioQuality::High)
+                .then(|| audio.iter().min_by_key(|s| s.quality))
+                .flatten()
+        })
+        .or_else(|| {
+            audio.iter().find(|s| {
+                s.mime_type
+                    .as_deref()
+                    .is_some_and(|m| m.starts_with("audio/mp4"))
+            })
+        })
+        .or_else(|| audio.first())
+        .copied()
+}
+
+/// Read the `itag` query parameter of a stream URL.
+fn stream_itag(stream: &StreamInfo) -> Option<u32> {
+    let url = url::Url::parse(&stream.url).ok()?;
+    let (_, itag) = url.query_pairs().find(|(key, _)| key == "itag")?;
+    itag.parse().ok()
+}
+
+/// A download fetched as a sequence of HTTP range requests.
+///
+/// Without a rate limit, and once the length is known, the ranges after the
+/// first are fetched in parallel.
+pub struct RangeDownload {
+    http: reqwest::Client,
+    url: String,
+    offset: u64,
+    total: Option<u64>,
+    chunk_size: u64,
+    response: Option<reqwest::Response>,
+    throttle: Option<Throttle>,
+    segment_size: u64,
+    /// Ranges being fetched in parallel, with their first byte, in stream order.
+    segments: VecDeque<(u64, JoinHandle<Result<Bytes>>)>,
+    /// First byte of the next parallel range, if the server honours ranges.
+    next_segment: Option<u64>,
+}
+
+impl RangeDownload {
+    /// Start downloading `url` at `offset`.
+    pub(crate) async fn open(http: &reqwest::Client, url: String, offset: u64) -> Result<Self> {
+        Self::with_chunk_size(http, url, offset, RANGE_CHUNK_SIZE).await
+    }
+
+    async fn with_chunk_size(
+        http: &reqwest::Client,
+        url: String,
+        offset: u64,
+        chunk_size: u64,
+    ) -> Result<Self> {
+        let head_size = HEAD_SIZE.min(chunk_size);
+        let mut download = Self {
+            http: http.clone(),
+            url,
+            offset,
+            total: None,
+            chunk_size,
+            response: None,
+            throttle: None,
+            segment_size: SEGMENT_SIZE.min(chunk_size),
+            segments: VecDeque::new(),
+            next_segment: None,
+        };
+        if !download.request_next(head_size).await? {
+            return Err(Error::StreamExtraction(format!(
+                "Nothing to download from byte {offset}"
+            )));
+        }
+        if download
+            .response
+            .as_ref()
+            .is_some_and(|response| response.status() == StatusCode::PARTIAL_CONTENT)
+        {
+            download.next_segment = Some(offset + head_size);
+        }
+        Ok(download)
+    }
+
+    /// Limit the download rate in bytes per second.
+    #[must_use]
+    pub(crate) fn with_rate_limit(mut self, rate_limit: Option<u64>) -> Self {
+        self.throttle = Throttle::new(rate_limit);
+        self
+    }
+
+    /// Total size of the stream in bytes (if known).
+    pub(crate) const fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// Request the next `len` bytes. Returns false once past the end of the stream.
+    async fn request_next(&mut self, len: u64) -> Result<bool> {
+        let end = self.offset + len - 1;
+        let response = self
+            .http
+            .get(&self.url)
+            .header(RANGE, format!("bytes={}-{end}", self.offset))
+            .send()
+            .await
+            .map_err(|e| Error::Network(e.to_string()))?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let total = response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_content_range_total);
+                self.total = total.or(self.total);
+            }
+            // The server ignored the range and is sending the whole stream
+            StatusCode::OK if self.offset == 0 => {
+                self.total = response.content_length();
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE if self.offset > 0 => return Ok(false),
+            status => {
+                return Err(Error::Http(HttpError::StatusError {
+                    status: status.as_u16(),
+                    message: format!("Range request from byte {} failed", self.offset),
+                }));
+            }
+        }
+
+        self.response = Some(response);
+        Ok(true)
+    }
+
+    /// Start fetching ranges in parallel, up to [`PARALLEL_SEGMENTS`] at once.
+    fn fill_segments(&mut self) {
+        let (Some(total), None) = (self.total, &self.throttle) else {
+            return;
+        };
+        while self.segments.len() < PARALLEL_SEGMENTS {
+            let Some(start) = self.next_segment.filter(|&start| start < total) else {
+                break;
+            };
+            let end = (start + self.segment_size).min(total) - 1;
+            let fetch = fetch_range(self.http.clone(), self.url.clone(), start, end);
+            self.segments.push_back((start, tokio::spawn(fetch)));
+            self.next_segment = Some(end + 1);
+        }
+    }
+
+    /// Read the next piece of the stream, or `None` at the end.
+    pub(crate) async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        self.fill_segments();
+        loop {
+            if let Some(response) = &mut self.response {
+                if let Some(bytes) = response
+                    .chunk()
+                    .await
+                    .map_err(|e| Error::Network(e.to_string()))?
+                {
+                    self.offset += bytes.len() as u64;
+                    if let Some(throttle) = &mut self.throttle {
+                        tokio::time::sleep(throttle.delay(bytes.len())).await;
+                    }
+                    return Ok(Some(bytes.to_vec()));
+                }
+                self.response = None;
+            }
+
+            // Parallel ranges continue exactly where the first one ended
+            if let Some((start, segment)) = self.segments.pop_front() {
+                if start != self.offset {
+                    return Err(Error::StreamExtraction(format!(
+                        "Range response ended early at byte {}",
+                        self.offset
+                    )));
+                }
+                let data = segment
+                    .await
+                    .map_err(|e| Error::Internal(format!("Range request task failed: {e}")))??;
+                self.offset += data.len() as u64;
+                self.fill_segments();
+                return Ok(Some(data.to_vec()));
+            }
+
+            // This range is exhausted; continue unless the end has been reached
+            if self.total.is_some_and(|total| self.offset >= total) {
+                return Ok(None);
+            }
+            if !self.request_next(self.chunk_size).await? {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl Drop for RangeDownload {
+    fn drop(&mut self) {
+        for (_, segment) in &self.segments {
+            segment.abort();
+        }
+    }
+}
+
+/// Fetch the bytes `start..=end` of a stream in full.
+async fn fetch_range(http: reqwest::Client, url: String, start: u64, end: u64) -> Result<Bytes> {
+    let response = http
+        .get(&url)
+        .header(RANGE, format!("bytes={start}-{end}"))
+        .send()
+        .await
+        .map_err(|e| Error::Network(e.to_string()))?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(Error::Http(HttpError::StatusError {
+            status: response.status().as_u16(),
+            message: format!("Range request from byte {start} failed"),
+        }));
+    }
+
+    let data = response
+        .bytes()
+        .await
+        .map_err(|e| Error::Network(e.to_string()))?;
+    if data.len() as u64 != end - start + 1 {
+        return Err(Error::StreamExtraction(format!(
+            "Range from byte {start} returned {} bytes",
+            data.len()
+        )));
+    }
+    Ok(data)
+}
+
+/// Parse the full length from a `Content-Range: bytes start-end/total` header.
+pub fn parse_content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve `body` over HTTP, honouring `Range: bytes=start-end` requests.
+    fn serve_ranges(body: &'static [u8]) -> std::io::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/audio", listener.local_addr()?);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(&stream);
+                let mut range = (0, body.len() - 1);
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.make_ascii_lowercase();
+                    if let Some(value) = line.trim().strip_prefix("range: bytes=") {
+                        if let Some((start, end)) = value.split_once('-') {
+                            range.0 = start.parse().unwrap_or(0);
+                            range.1 = end.parse::<usize>().unwrap_or(range.1).min(body.len() - 1);
+                        }
+                    }
+                    line.clear();
+                }
+
+                let response = if range.0 >= body.len() {
+                    b"HTTP/1.1 416 Range Not Satisfiable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_vec()
+                } else {
+                    let part = &body[range.0..=range.1];
+                    let mut response = format!(
+                        "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes {}-{}/{}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                        range.0,
+                        range.1,
+                        body.len(),
+                        part.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(part);
+                    response
+                };
+                let _ = (&stream).write_all(&response);
+            }
+        });
+        Ok(url)
+    }
+
+    async fn read_all(download: &mut RangeDownload) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        while let Some(chunk) = download.chunk().await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+
+    #[tokio::test]
+    async fn test_range_download_in_chunks() -> Result<()> {
+        let url = serve_ranges(b"hello range world")?;
+        let http = reqwest::Client::new();
+
+        let mut download = RangeDownload::with_chunk_size(&http, url.clone(), 0, 4).await?;
+        assert_eq!(download.total(), Some(17));
+        // The rest is requested while the first range is read
+        let mut data = download.chunk().await?.unwrap_or_default();
+        assert_eq!(download.segments.len(), PARALLEL_SEGMENTS);
+        data.extend(read_all(&mut download).await?);
+        assert_eq!(data, b"hello range world");
+
+        let mut resumed = RangeDownload::with_chunk_size(&http, url.clone(), 6, 4).await?;
+        assert_eq!(read_all(&mut resumed).await?, b"range world");
+
+        // Rate-limited downloads fetch one range at a time
+        let mut throttled = RangeDownload::with_chunk_size(&http, url.clone(), 0, 4)
+            .await?
+            .with_rate_limit(Some(1024 * 1024));
+        let mut data = throttled.chunk().await?.unwrap_or_default();
+        assert!(throttled.segments.is_empty());
+        data.extend(read_all(&mut throttled).await?);
+        assert_eq!(data, b"hello range world");
+
+        assert!(RangeDownload::open(&http, url, 17).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_stream_prefers_aac() {
+        let stream = |itag: u32, mime: &str| {
+            let mut stream = StreamInfo::new(
+                format!("https://example.com/videoplayback?itag={itag}"),
+                monad_core::AudioFormat::from_mime(mime),
+                AudioQuality::High,
+            );
+            stream.mime_type = Some(mime.to_string());
+            stream
+        };
+
+        let high = AudioQuality::High;
+        let streams = vec![
+            stream(251, "audio/webm; codecs=\"opus\""),
+            stream(140, "audio/mp4; codecs=\"mp4a.40.2\""),
+            stream(249, "audio/webm; codecs=\"opus\""),
+        ];
+        assert_eq!(
+            choose_stream(&streams, high).and_then(stream_itag),
+            Some(140)
+        );
+        // Slow connections get the low-bitrate Opus stream
+        assert_eq!(
+            choose_stream(&streams, AudioQuality::Low).and_then(stream_itag),
+            Some(249)
+        );
+
+        let streams = vec![stream(18, "video/mp4"), stream(251, "audio/webm")];
+        assert_eq!(
+            choose_stream(&streams, high).and_then(stream_itag),
+            Some(251)
+        );
+        assert_eq!(parse_content_range_total("bytes 0-3/17"), Some(17));
+    }
+}
@@ -0,0 +1,111 @@
+//! Proxy and bandwidth limits for audio downloads.
+//!
+//! The same settings are passed to yt-dlp as arguments and applied to the
+//! native HTTP downloads, so pre-caching never uses more of a metered link
+//! than allowed, whichever path fetches the audio.
+
+use std::time::{Duration, Instant};
+
+use monad_core::{Error, Result};
+
+/// Proxy and rate limit applied to downloads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkSettings {
+    /// Proxy URL, such as `socks5://127.0.0.1:1080`.
+    pub proxy: Option<String>,
+    /// Maximum download rate in bytes per second.
+    pub rate_limit: Option<u64>,
+}
+
+impl NetworkSettings {
+    /// Convert to yt-dlp command-line arguments.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(proxy) = &self.proxy {
+            args.extend(["--proxy".to_string(), proxy.clone()]);
+        }
+        if let Some(rate_limit) = self.rate_limit {
+            args.extend(["--limit-rate".to_string(), rate_limit.to_string()]);
+        }
+        args
+    }
+
+    /// Build an HTTP client that uses the proxy.
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| Error::Network(format!("Invalid proxy {proxy}: {e}")))?;
+            builder = builder.proxy(proxy);
+        }
+        builder
+            .build()
+            .map_err(|e| Error::Network(format!("Failed to create HTTP client: {e}")))
+    }
+}
+
+/// Keeps the average rate of a download under a limit.
+#[derive(Debug)]
+pub struct Throttle {
+    bytes_per_sec: u64,
+    started: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    /// Create a throttle, or `None` for an unlimited (or zero) rate.
+    pub fn new(rate_limit: Option<u64>) -> Option<Self> {
+        rate_limit
+            .filter(|&rate| rate > 0)
+            .map(|bytes_per_sec| Self {
+                bytes_per_sec,
+                started: Instant::now(),
+                bytes: 0,
+            })
+    }
+
+    /// Record `bytes` more downloaded, returning how long to wait to stay under the limit.
+    pub fn delay(&mut self, bytes: usize) -> Duration {
+        self.bytes += bytes as u64;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64);
+        due.saturating_sub(self.started.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_args() {
+        assert!(NetworkSettings::default().to_args().is_empty());
+
+        let settings = NetworkSettings {
+            proxy: Some("socks5://127.0.0.1:1080".to_string()),
+            rate_limit: Some(500_000),
+        };
+        assert_eq!(
+            settings.to_args(),
+            [
+                "--proxy",
+                "socks5://127.0.0.1:1080",
+                "--limit-rate",
+                "500000"
+            ]
+        );
+        assert!(settings.http_client().is_ok());
+    }
+
+    #[test]
+    fn test_throttle_delay() {
+        assert!(Throttle::new(None).is_none());
+        assert!(Throttle::new(Some(0)).is_none());
+
+        let mut throttle = Throttle::new(Some(1000));
+        let first = throttle.as_mut().map(|t| t.delay(500));
+        assert!(first
+            .is_some_and(|d| d > Duration::from_millis(400) && d <= Duration::from_millis(500)));
+        let second = throttle.as_mut().map(|t| t.delay(500));
+        assert!(second > first);
+    }
+}
@@ -0,0 +1,140 @@
+//! Keeping pinned tracks, albums and playlists available offline.
+//!
+//! Pins live in monad-cache's database, where the disk cache reads its pins
+//! too, so both size limits keep the tracks of pinned items. Making an item
+//! available offline pins it there and downloads whatever audio and artwork
+//! is missing. Syncing repeats the downloads for every pin, picking up where
+//! an earlier attempt failed or was cancelled.
+
+use std::collections::HashSet;
+
+use monad_cache::{CacheManager, Pin, PinKind, PinTarget};
+use monad_core::{Error, Result, Track};
+use tracing::{info, warn};
+
+use crate::{BatchDownload, BatchOptions, Extractor};
+
+impl Extractor {
+    /// Get the attached cache database, which holds the pins.
+    fn pin_index(&self) -> Result<&CacheManager> {
+        self.cache
+            .index()
+            .ok_or_else(|| Error::Cache("Offline pins need a cache index".to_string()))
+    }
+
+    /// Pin an item and download its audio and artwork.
+    pub async fn make_available_offline<'a>(
+        &self,
+        target: impl Into<PinTarget<'a>>,
+        options: BatchOptions,
+    ) -> Result<BatchDownload> {
+        let pin = self.pin_index()?.pin(target)?;
+        info!("Making {} available offline", pin.title);
+        self.download_pins(&[pin], options).await
+    }
+
+    /// Stop keeping an item offline; its tracks may be evicted again.
+    ///
+    /// Tracks that another pin also keeps stay pinned.
+    pub fn remove_offline(&self, kind: PinKind, id: &str) -> Result<()> {
+        let Some(pin) = self.pin_index()?.unpin(kind, id)? else {
+            return Ok(());
+        };
+        info!("{} is no longer kept offline", pin.title);
+        Ok(())
+    }
+
+    /// Download whatever audio and artwork of pinned items is missing.
+    pub async fn sync_offline(&self, options: BatchOptions) -> Result<BatchDownload> {
+        let pins = self.pin_index()?.list_pins()?;
+        self.download_pins(&pins, options).await
+    }
+
+    /// Download the audio and artwork of `pins` that is missing.
+    async fn download_pins(&self, pins: &[Pin], options: BatchOptions) -> Result<BatchDownload> {
+        let mut seen = HashSet::new();
+        let tracks: Vec<Track> = pins
+            .iter()
+            .flat_map(|pin| &pin.track_ids)
+            .filter(|id| seen.insert(id.as_str()))
+            .map(|id| Track::new(id.clone(), String::new()))
+            .collect();
+
+        let batch = self.download_playlist(&tracks, options).await?;
+        if let Some(index) = self.cache.index() {
+            for url in pins.iter().flat_map(|pin| &pin.thumbnail_urls) {
+                if let Err(e) = index.get_thumbnail(url).await {
+                    warn!("Failed to download artwork {url}: {e}");
+                }
+            }
+        }
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioCache;
+    use monad_core::Album;
+    use std::sync::Arc;
+
+    fn extractor(dir: &std::path::Path) -> Result<Extractor> {
+        let index = Arc::new(CacheManager::with_path(dir.join("index"))?);
+        let mut cache = AudioCache::new(dir.join("audio"), 1024 * 1024);
+        cache.set_index(index);
+        Ok(Extractor {
+            yt_dlp_path: dir.join("yt-dlp"),
+            cache,
+            ..Extractor::new().with_native_extraction(false)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pinned_tracks_stay_in_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let extractor = extractor(dir.path())?;
+        extractor.cache.write("a", b"audio a");
+        extractor.cache.write("b", b"audio b");
+        let mut album = Album::new("album1", "Album");
+        album.tracks = vec![Track::new("a", "A"), Track::new("b", "B")];
+        let single = Track::new("a", "A");
+
+        let batch = extractor
+            .make_available_offline(&album, BatchOptions::default())
+            .await?;
+        assert_eq!(batch.skipped, ["a", "b"]);
+        extractor
+            .make_available_offline(&single, BatchOptions::default())
+            .await?;
+        assert!(extractor.is_pinned("a") && extractor.is_pinned("b"));
+
+        // The single keeps its track when the album goes
+        extractor.remove_offline(PinKind::Album, "album1")?;
+        assert!(extractor.is_pinned("a"));
+        assert!(!extractor.is_pinned("b"));
+        assert_eq!(
+            extractor
+                .sync_offline(BatchOptions::default())
+                .await?
+                .skipped,
+            ["a"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_offline_needs_index() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let extractor = Extractor {
+            cache: AudioCache::new(dir.path().join("audio"), 1024),
+            ..Extractor::new().with_native_extraction(false)
+        };
+        assert!(extractor
+            .sync_offline(BatchOptions::default())
+            .await
+            .is_err());
+        assert!(extractor.remove_offline(PinKind::Track, "a").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! Partially downloaded audio, persisted so interrupted downloads can resume.
+//!
+//! Data is appended to `{video_id}.partial` as it arrives, so the file length is
+//! always the byte offset to resume from. `{video_id}.partial.meta` records the
+//! yt-dlp format ID and total size needed to request the rest of the same stream.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, info, warn};
+
+use crate::cache::AudioCache;
+use crate::integrity::Checksum;
+
+/// Format and size of a partially downloaded stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialMeta {
+    /// yt-dlp format ID of the stream being downloaded.
+    pub format_id: String,
+    /// Total size of the stream in bytes (if known).
+    pub total: Option<u64>,
+}
+
+impl PartialMeta {
+    /// Parse metadata saved by [`PartialMeta::save`].
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        let format_id = lines.next()?.trim();
+        if format_id.is_empty() {
+            return None;
+        }
+        let total = lines.next().and_then(|l| l.trim().parse().ok());
+        Some(Self {
+            format_id: format_id.to_string(),
+            total,
+        })
+    }
+
+    /// Save metadata next to a partial download.
+    pub fn save(&self, path: &Path) {
+        let total = self.total.map(|t| t.to_string()).unwrap_or_default();
+        if let Err(e) = fs::write(path, format!("{}\n{total}\n", self.format_id)) {
+            warn!("Failed to save partial download metadata: {e}");
+        }
+    }
+}
+
+/// A download being written to disk.
+pub struct PartialDownload {
+    video_id: String,
+    path: PathBuf,
+    meta_path: PathBuf,
+    file: File,
+    bytes: u64,
+}
+
+impl PartialDownload {
+    /// Number of bytes written so far, including any resumed data.
+    pub const fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Path of the metadata file for this download.
+    pub fn meta_path(&self) -> &Path {
+        &self.meta_path
+    }
+
+    /// Append downloaded data.
+    pub fn append(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data)?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+}
+
+impl AudioCache {
+    fn partial_path(&self, video_id: &str) -> PathBuf {
+        self.dir().join(format!("{video_id}.partial"))
+    }
+
+    fn partial_meta_path(&self, video_id: &str) -> PathBuf {
+        self.dir().join(format!("{video_id}.partial.meta"))
+    }
+
+    /// Load a resumable partial download: its data so far and stream metadata.
+    pub fn load_partial(&self, video_id: &str) -> Option<(Vec<u8>, PartialMeta)> {
+        let meta = fs::read_to_string(self.partial_meta_path(video_id)).ok()?;
+        let meta = PartialMeta::parse(&meta)?;
+        let data = fs::read(self.partial_path(video_id)).ok()?;
+        if data.is_empty() || meta.total.is_some_and(|total| data.len() as u64 >= total) {
+            return None;
+        }
+        Some((data, meta))
+    }
+
+    /// Start a new partial download, discarding any previous one.
+    pub fn begin_partial(&self, video_id: &str) -> std::io::Result<PartialDownload> {
+        self.discard_partial(video_id);
+        let path = self.partial_path(video_id);
+        Ok(PartialDownload {
+            video_id: video_id.to_string(),
+            file: File::create(&path)?,
+            meta_path: self.partial_meta_path(video_id),
+            path,
+            bytes: 0,
+        })
+    }
+
+    /// Reopen a partial download to append the rest of the stream.
+    pub fn resume_partial(&self, video_id: &str) -> std::io::Result<PartialDownload> {
+        let path = self.partial_path(video_id);
+        let file = File::options().append(true).open(&path)?;
+        let bytes = file.metadata()?.len();
+        info!("Resuming download of {video_id} at {} KB", bytes / 1024);
+        Ok(PartialDownload {
+            video_id: video_id.to_string(),
+            file,
+            meta_path: self.partial_meta_path(video_id),
+            path,
+            bytes,
+        })
+    }
+
+    /// Move a completed download into the cache.
+    pub fn finish_partial(&self, download: PartialDownload) -> std::io::Result<()> {
+        let PartialDownload {
+            video_id,
+            path,
+            meta_path,
+            mut file,
+            bytes,
+        } = download;
+
+        file.flush()?;
+        drop(file);
+        self.write_checksum(&video_id, &Checksum::of(&fs::read(&path)?));
+        fs::rename(&path, self.path(&video_id))?;
+        let _ = fs::remove_file(meta_path);
+        debug!("Cached {bytes} bytes for {video_id}");
+        self.register(&video_id);
+
+        self.evict();
+        self.schedule_processing(&video_id);
+        Ok(())
+    }
+
+    /// Remove any partial download for a video ID.
+    pub fn discard_partial(&self, video_id: &str) {
+        let _ = fs::remove_file(self.partial_path(video_id));
+        let _ = fs::remove_file(self.partial_meta_path(video_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_meta_roundtrip() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("meta");
+        let meta = PartialMeta {
+            format_id: "140".to_string(),
+            total: Some(4096),
+        };
+        meta.save(&path);
+        assert_eq!(PartialMeta::parse(&fs::read_to_string(&path)?), Some(meta));
+        assert_eq!(PartialMeta::parse("251\n\n").map(|m| m.total), Some(None));
+        assert_eq!(PartialMeta::parse(""), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_and_finish_partial() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = AudioCache::new(dir.path().to_path_buf(), u64::MAX);
+
+        let mut download = cache.begin_partial("abc")?;
+        download.append(b"hello ")?;
+        PartialMeta {
+            format_id: "140".to_string(),
+            total: Some(11),
+        }
+        .save(download.meta_path());
+        drop(download);
+
+        assert!(matches!(
+            cache.load_partial("abc"),
+            Some((data, meta)) if data == b"hello " && meta.format_id == "140"
+        ));
+
+        let mut download = cache.resume_partial("abc")?;
+        assert_eq!(download.bytes(), 6);
+        download.append(b"world")?;
+        cache.finish_partial(download)?;
+
+        assert_eq!(cache.read("abc").as_deref(), Some(&b"hello world"[..]));
+        assert!(cache.load_partial("abc").is_none());
+        Ok(())
+    }
+}
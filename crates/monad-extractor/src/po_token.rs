@@ -0,0 +1,361 @@
+//! Proof-of-origin (PO) tokens.
+//!
+//! `YouTube` increasingly rejects unauthenticated stream requests with 403s
+//! unless they carry a PO token minted by its attestation scripts. Monad does
+//! not mint tokens itself; a [`PoTokenProvider`] supplies them, and they are
+//! sent with native player requests (which also append them to stream URLs)
+//! and passed to yt-dlp through its `--extractor-args`.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use monad_core::{Error, Result};
+use parking_lot::Mutex;
+use tracing::{debug, warn};
+
+/// Default lifetime of a cached token (tokens stay valid for roughly half a day).
+pub const DEFAULT_PO_TOKEN_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Default time a generator command gets to print a token.
+pub const DEFAULT_PO_TOKEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to check whether a generator command has exited.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Placeholder in command arguments replaced by the video ID.
+const VIDEO_ID_PLACEHOLDER: &str = "{video_id}";
+
+/// yt-dlp client whose requests the token is attached to.
+const YT_DLP_CLIENT: &str = "web_music";
+
+/// A proof-of-origin token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoToken {
+    /// The token itself.
+    pub token: String,
+    /// Visitor data the token was minted for (if session-bound).
+    pub visitor_data: Option<String>,
+}
+
+impl PoToken {
+    /// Create a token that is not bound to visitor data.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            visitor_data: None,
+        }
+    }
+
+    /// Set the visitor data the token was minted for.
+    #[must_use]
+    pub fn with_visitor_data(mut self, visitor_data: impl Into<String>) -> Self {
+        self.visitor_data = Some(visitor_data.into());
+        self
+    }
+
+    /// Value for yt-dlp's `--extractor-args`.
+    pub fn extractor_args(&self) -> String {
+        let mut args = format!(
+            "youtube:player_client={YT_DLP_CLIENT};po_token={YT_DLP_CLIENT}.gvs+{token},{YT_DLP_CLIENT}.player+{token}",
+            token = self.token
+        );
+        if let Some(visitor_data) = &self.visitor_data {
+            args.push_str(";visitor_data=");
+            args.push_str(visitor_data);
+        }
+        args
+    }
+}
+
+/// Source of PO tokens.
+///
+/// Providers may block (for example while running an external generator),
+/// so the extractor calls them off the async runtime.
+pub trait PoTokenProvider: Send + Sync {
+    /// Name of the provider, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Get a token for a video, or `None` if the provider has none to offer.
+    fn token(&self, video_id: &str) -> Result<Option<PoToken>>;
+}
+
+/// A fixed token, e.g. one copied from a browser session.
+#[derive(Debug, Clone)]
+pub struct StaticPoTokenProvider {
+    token: PoToken,
+}
+
+impl StaticPoTokenProvider {
+    /// Create a provider that always returns `token`.
+    pub const fn new(token: PoToken) -> Self {
+        Self { token }
+    }
+}
+
+impl PoTokenProvider for StaticPoTokenProvider {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    fn token(&self, _video_id: &str) -> Result<Option<PoToken>> {
+        Ok(Some(self.token.clone()))
+    }
+}
+
+/// Runs an external generator command and reads the token from its output.
+///
+/// The first line of stdout is the token and an optional second line is the
+/// visitor data. `{video_id}` in the arguments is replaced by the video ID.
+/// Commands still running after the timeout are killed.
+#[derive(Debug, Clone)]
+pub struct CommandPoTokenProvider {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandPoTokenProvider {
+    /// Create a provider running `program`.
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            timeout: DEFAULT_PO_TOKEN_TIMEOUT,
+        }
+    }
+
+    /// Set the command arguments.
+    #[must_use]
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set how long the command may run before it is killed.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl PoTokenProvider for CommandPoTokenProvider {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn token(&self, video_id: &str) -> Result<Option<PoToken>> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(VIDEO_ID_PLACEHOLDER, video_id));
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::ExtractionFailed(format!("Failed to run {}: {e}", self.program)))?;
+
+        // Read on another thread, so a generator filling the pipe can't stall
+        let mut pipe = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::Internal("PO token generator has no stdout".to_string()))?;
+        let (output_tx, output_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = output_tx.send(pipe.read_to_end(&mut output).map(|_| output));
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::ExtractionFailed(format!(
+                    "PO token generator timed out after {:?}",
+                    self.timeout
+                )));
+            }
+            std::thread::sleep(COMMAND_POLL_INTERVAL);
+        };
+
+        if !status.success() {
+            return Err(Error::ExtractionFailed(format!(
+                "PO token generator exited with error (exit code: {:?})",
+                status.code()
+            )));
+        }
+
+        // Anything it left running may hold the pipe open, so don't wait for
+        // that past the deadline either
+        let output = output_rx
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .map_err(|_| {
+                Error::ExtractionFailed("PO token generator output not closed".to_string())
+            })??;
+        let stdout = String::from_utf8_lossy(&output);
+        let mut lines = stdout.lines().map(str::trim).filter(|l| !l.is_empty());
+        Ok(lines.next().map(|token| PoToken {
+            token: token.to_string(),
+            visitor_data: lines.next().map(str::to_string),
+        }))
+    }
+}
+
+/// Caches tokens from another provider, since minting them is slow.
+///
+/// Tokens are bound to a session rather than a video, so one token is reused
+/// for every video until it expires.
+pub struct CachedPoTokenProvider {
+    inner: Box<dyn PoTokenProvider>,
+    ttl: Duration,
+    cached: Mutex<Option<(PoToken, Instant)>>,
+}
+
+impl CachedPoTokenProvider {
+    /// Cache tokens from `inner` for [`DEFAULT_PO_TOKEN_TTL`].
+    pub fn new(inner: impl PoTokenProvider + 'static) -> Self {
+        Self {
+            inner: Box::new(inner),
+            ttl: DEFAULT_PO_TOKEN_TTL,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Set how long tokens are reused.
+    #[must_use]
+    pub const fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Drop the cached token, e.g. after it was rejected.
+    pub fn invalidate(&self) {
+        *self.cached.lock() = None;
+    }
+}
+
+impl PoTokenProvider for CachedPoTokenProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn token(&self, video_id: &str) -> Result<Option<PoToken>> {
+        let mut cached = self.cached.lock();
+        if let Some((token, minted)) = cached.as_ref() {
+            if minted.elapsed() < self.ttl {
+                return Ok(Some(token.clone()));
+            }
+        }
+
+        let token = self.inner.token(video_id)?;
+        *cached = token.clone().map(|token| (token, Instant::now()));
+        Ok(token)
+    }
+}
+
+/// Get a token from a provider without blocking the async runtime.
+///
+/// Failures are logged and treated as having no token, so extraction can
+/// still be attempted without one.
+pub async fn fetch_po_token(
+    provider: Option<&Arc<dyn PoTokenProvider>>,
+    video_id: &str,
+) -> Option<PoToken> {
+    let provider = Arc::clone(provider?);
+    let name = provider.name();
+    let video_id = video_id.to_string();
+
+    match tokio::task::spawn_blocking(move || provider.token(&video_id)).await {
+        Ok(Ok(token)) => {
+            debug!("PO token from {name} provider: {}", token.is_some());
+            token
+        }
+        Ok(Err(e)) => {
+            warn!("PO token provider {name} failed: {e}");
+            None
+        }
+        Err(e) => {
+            warn!("PO token provider {name} panicked: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider(AtomicUsize);
+
+    impl PoTokenProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn token(&self, _video_id: &str) -> Result<Option<PoToken>> {
+            let n = self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(Some(PoToken::new(format!("token{n}"))))
+        }
+    }
+
+    #[test]
+    fn test_cached_provider_reuses_token() -> Result<()> {
+        let provider = CachedPoTokenProvider::new(CountingProvider(AtomicUsize::new(0)));
+        assert_eq!(provider.token("a")?, Some(PoToken::new("token0")));
+        assert_eq!(provider.token("b")?, Some(PoToken::new("token0")));
+
+        provider.invalidate();
+        assert_eq!(provider.token("a")?, Some(PoToken::new("token1")));
+
+        let provider = provider.with_ttl(Duration::ZERO);
+        assert_eq!(provider.token("a")?, Some(PoToken::new("token2")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extractor_args() {
+        let token = PoToken::new("abc").with_visitor_data("vis");
+        assert_eq!(
+            token.extractor_args(),
+            "youtube:player_client=web_music;po_token=web_music.gvs+abc,web_music.player+abc;visitor_data=vis"
+        );
+        assert!(!PoToken::new("abc")
+            .extractor_args()
+            .contains("visitor_data"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_provider() -> Result<()> {
+        let provider = CommandPoTokenProvider::new("printf").with_args(["tok-{video_id}\\nvis\\n"]);
+        let token = provider.token("xyz")?;
+        assert_eq!(
+            token,
+            Some(PoToken::new("tok-xyz").with_visitor_data("vis"))
+        );
+
+        let failing = CommandPoTokenProvider::new("false");
+        assert!(failing.token("xyz").is_err());
+
+        let hung = CommandPoTokenProvider::new("sleep")
+            .with_args(["10"])
+            .with_timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        assert!(hung.token("xyz").is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Background prefetching of upcoming tracks into the disk cache.
+//!
+//! Prefetches run one at a time so they never compete with each other for
+//! bandwidth. Playing a track that is being prefetched cancels the prefetch;
+//! its partial file is then resumed by the playback download.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use monad_core::Progress;
+use parking_lot::Mutex;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::StreamChunk;
+
+/// Number of prefetches downloading at once.
+const MAX_CONCURRENT_PREFETCHES: usize = 1;
+
+/// Status of a prefetch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrefetchStatus {
+    /// Waiting for an earlier prefetch to finish.
+    Queued,
+    /// Downloading into the cache.
+    Downloading(Progress),
+    /// The track is in the cache.
+    Cached,
+    /// The download failed.
+    Failed(String),
+}
+
+struct Prefetch {
+    status: PrefetchStatus,
+    task: Option<JoinHandle<()>>,
+}
+
+/// Tracks prefetches by video ID.
+#[derive(Clone)]
+pub struct Prefetcher {
+    prefetches: Arc<Mutex<HashMap<String, Prefetch>>>,
+    slots: Arc<Semaphore>,
+}
+
+impl Default for Prefetcher {
+    fn default() -> Self {
+        Self {
+            prefetches: Arc::new(Mutex::new(HashMap::new())),
+            slots: Arc::new(Semaphore::new(MAX_CONCURRENT_PREFETCHES)),
+        }
+    }
+}
+
+impl Prefetcher {
+    /// Get the status of a tracked prefetch.
+    pub fn status(&self, video_id: &str) -> Option<PrefetchStatus> {
+        self.prefetches
+            .lock()
+            .get(video_id)
+            .map(|prefetch| prefetch.status.clone())
+    }
+
+    /// Check whether a prefetch is queued or downloading.
+    pub fn is_active(&self, video_id: &str) -> bool {
+        matches!(
+            self.status(video_id),
+            Some(PrefetchStatus::Queued | PrefetchStatus::Downloading(_))
+        )
+    }
+
+    /// Start a prefetch; `download` streams the track into the cache.
+    pub fn start<F, D>(&self, video_id: &str, download: D)
+    where
+        D: FnOnce(mpsc::Sender<StreamChunk>) -> F + Send + 'static,
+        F: std::future::Future<Output = ()> + Send,
+    {
+        let mut prefetches = self.prefetches.lock();
+        let this = self.clone();
+        let id = video_id.to_string();
+
+        let task = tokio::spawn(async move {
+            // Low priority: wait for earlier prefetches to finish first
+            let Ok(_permit) = this.slots.clone().acquire_owned().await else {
+                return;
+            };
+            debug!("Prefetching {id}");
+            this.set_status(&id, PrefetchStatus::Downloading(Progress::bytes(0, None)));
+
+            let (tx, rx) = mpsc::channel(64);
+            let ((), status) = tokio::join!(download(tx), this.track(&id, rx));
+
+            match status {
+                PrefetchStatus::Cached => info!("Prefetched {id}"),
+                PrefetchStatus::Failed(ref e) => warn!("Prefetch of {id} failed: {e}"),
+                _ => {}
+            }
+            this.set_status(&id, status);
+            if let Some(prefetch) = this.prefetches.lock().get_mut(&id) {
+                prefetch.task = None;
+            }
+        });
+
+        prefetches.insert(
+            video_id.to_string(),
+            Prefetch {
+                status: PrefetchStatus::Queued,
+                task: Some(task),
+            },
+        );
+    }
+
+    /// Cancel a prefetch, keeping its partial download for resuming.
+    pub fn cancel(&self, video_id: &str) {
+        // Dropping the prefetch aborts its task
+        if self.prefetches.lock().remove(video_id).is_some() {
+            debug!("Cancelled prefetch of {video_id}");
+        }
+    }
+
+    fn set_status(&self, video_id: &str, status: PrefetchStatus) {
+        if let Some(prefetch) = self.prefetches.lock().get_mut(video_id) {
+            prefetch.status = status;
+        }
+    }
+
+    /// Follow a download's chunks, discarding the audio, until it finishes.
+    async fn track(&self, video_id: &str, mut rx: mpsc::Receiver<StreamChunk>) -> PrefetchStatus {
+        while let Some(chunk) = rx.recv().await {
+            match chunk {
+                StreamChunk::Data(_) => {}
+                StreamChunk::Progress(progress) => {
+                    self.set_status(video_id, PrefetchStatus::Downloading(progress));
+                }
+                StreamChunk::Complete => return PrefetchStatus::Cached,
+                StreamChunk::Error(e) | StreamChunk::Stalled(e) => {
+                    return PrefetchStatus::Failed(e);
+                }
+            }
+        }
+        PrefetchStatus::Failed("Download ended unexpectedly".to_string())
+    }
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn wait_for(prefetcher: &Prefetcher, video_id: &str, status: &PrefetchStatus) -> bool {
+        for _ in 0..100 {
+            if prefetcher.status(video_id).as_ref() == Some(status) {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_runs_one_at_a_time() {
+        let prefetcher = Prefetcher::default();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+
+        prefetcher.start("a", move |tx| async move {
+            let _ = tx.send(StreamChunk::progress(10, Some(20), None)).await;
+            let _ = release_rx.await;
+            let _ = tx.send(StreamChunk::Complete).await;
+        });
+        prefetcher.start("b", |tx| async move {
+            let _ = tx.send(StreamChunk::Error("gone".to_string())).await;
+        });
+
+        let downloading = PrefetchStatus::Downloading(Progress::bytes(10, Some(20)));
+        assert!(wait_for(&prefetcher, "a", &downloading).await);
+        assert_eq!(prefetcher.status("b"), Some(PrefetchStatus::Queued));
+
+        let _ = release_tx.send(());
+        assert!(wait_for(&prefetcher, "a", &PrefetchStatus::Cached).await);
+        let failed = PrefetchStatus::Failed("gone".to_string());
+        assert!(wait_for(&prefetcher, "b", &failed).await);
+        assert!(!prefetcher.is_active("b"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_prefetch() {
+        let prefetcher = Prefetcher::default();
+        prefetcher.start("a", |_tx| std::future::pending());
+        assert!(prefetcher.is_active("a"));
+
+        prefetcher.cancel("a");
+        assert_eq!(prefetcher.status("a"), None);
+    }
+}
@@ -0,0 +1,401 @@
+//! Pluggable sources of audio.
+//!
+//! An [`AudioSource`] downloads or streams the audio of a track. After the
+//! disk cache, the extractor tries its sources in priority order: native
+//! `InnerTube` extraction, then yt-dlp, along with any registered through
+//! [`Extractor::with_source`], such as local files or other services.
+
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use monad_core::{Error, Result};
+use tokio::process::Command as AsyncCommand;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::bandwidth::Bandwidth;
+use crate::native::StreamChoice;
+use crate::network::NetworkSettings;
+use crate::stall::Watchdog;
+use crate::{
+    audio_format, detect_audio_mime, print_metadata_args, save_track_info, stream_range,
+    stream_yt_dlp, watch_url, AudioCache, DownloadSink, ExtractedAudio, Extractor, NativeExtractor,
+    PartialMeta, SponsorBlock, StreamTimeouts, StreamingExtraction, YtDlp,
+};
+
+/// Priority of native extraction through the `InnerTube` player endpoint.
+pub const NATIVE_SOURCE_PRIORITY: i32 = 200;
+
+/// Priority of extraction with yt-dlp.
+pub const YT_DLP_SOURCE_PRIORITY: i32 = 100;
+
+/// A source of audio for tracks.
+///
+/// The built-in sources keep what they download in the extractor's disk
+/// cache; other sources cache their audio themselves, if at all.
+#[async_trait]
+pub trait AudioSource: Send + Sync {
+    /// Name of the source, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Get the complete audio of a track.
+    async fn extract(&self, video_id: &str) -> Result<ExtractedAudio>;
+
+    /// Start streaming the audio of a track.
+    ///
+    /// Returns once the stream is open; failing before then lets the next
+    /// source try instead.
+    async fn extract_streaming(&self, video_id: &str) -> Result<StreamingExtraction>;
+
+    /// Check whether the track is available without the network.
+    fn is_cached(&self, _video_id: &str) -> bool {
+        false
+    }
+}
+
+impl Extractor {
+    /// Add a source of audio, tried before the sources of lower priority.
+    ///
+    /// Built-in sources use [`NATIVE_SOURCE_PRIORITY`] and
+    /// [`YT_DLP_SOURCE_PRIORITY`]; added sources go first on a tie.
+    #[must_use]
+    pub fn with_source(mut self, source: impl AudioSource + 'static, priority: i32) -> Self {
+        self.custom_sources.push((priority, Arc::new(source)));
+        self
+    }
+
+    /// Check whether any added source has a track without the network.
+    pub(crate) fn source_has(&self, video_id: &str) -> bool {
+        self.custom_sources
+            .iter()
+            .any(|(_, source)| source.is_cached(video_id))
+    }
+
+    /// Sources to try for a track: any holding it locally, then the rest by priority.
+    pub(crate) fn sources_for(&self, video_id: &str) -> Vec<Arc<dyn AudioSource>> {
+        let mut sources = self.custom_sources.clone();
+        if let Some(native) = &self.native {
+            let source = NativeSource {
+                native: native.clone(),
+                cache: self.cache.clone(),
+                sponsorblock: self.sponsorblock.clone(),
+                network: self.network.clone(),
+                bandwidth: self.bandwidth.clone(),
+                timeouts: self.stream_timeouts,
+            };
+            sources.push((NATIVE_SOURCE_PRIORITY, Arc::new(source)));
+        }
+        let source = YtDlpSource {
+            yt_dlp: self.yt_dlp(),
+            cache: self.cache.clone(),
+            sponsorblock: self.sponsorblock.clone(),
+            timeouts: self.stream_timeouts,
+        };
+        sources.push((YT_DLP_SOURCE_PRIORITY, Arc::new(source)));
+
+        sources.sort_by_key(|(priority, source)| (!source.is_cached(video_id), Reverse(*priority)));
+        sources.into_iter().map(|(_, source)| source).collect()
+    }
+}
+
+/// Native extraction through the `InnerTube` player endpoint.
+struct NativeSource {
+    native: NativeExtractor,
+    cache: AudioCache,
+    sponsorblock: SponsorBlock,
+    network: NetworkSettings,
+    bandwidth: Bandwidth,
+    timeouts: StreamTimeouts,
+}
+
+#[async_trait]
+impl AudioSource for NativeSource {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    async fn extract(&self, video_id: &str) -> Result<ExtractedAudio> {
+        let (data, metadata) = tokio::join!(
+            self.native.download(video_id, self.bandwidth.quality()),
+            save_track_info(
+                &self.cache,
+                &self.sponsorblock,
+                &self.network,
+                Some(&self.native),
+                video_id
+            )
+        );
+        let data = data?;
+        if data.is_empty() {
+            return Err(Error::ExtractionFailed(
+                "Native extraction returned no data".to_string(),
+            ));
+        }
+
+        self.cache.write(video_id, &data);
+        let mime_type = detect_audio_mime(&data);
+        info!("Downloaded {} bytes natively ({})", data.len(), mime_type);
+        Ok(ExtractedAudio {
+            data: data.into(),
+            mime_type,
+            metadata,
+        })
+    }
+
+    async fn extract_streaming(&self, video_id: &str) -> Result<StreamingExtraction> {
+        let choice = StreamChoice::Quality(self.bandwidth.quality());
+        let (itag, download) = self.native.open(video_id, choice, 0).await?;
+
+        // Metadata is fetched while the audio downloads
+        tokio::spawn({
+            let (native, cache, sponsorblock, network, video_id) = (
+                self.native.clone(),
+                self.cache.clone(),
+                self.sponsorblock.clone(),
+                self.network.clone(),
+                video_id.to_string(),
+            );
+            async move {
+                save_track_info(&cache, &sponsorblock, &network, Some(&native), &video_id).await;
+            }
+        });
+
+        let partial = self
+            .cache
+            .begin_partial(video_id)
+            .inspect_err(|e| warn!("Failed to create partial download: {e}"))
+            .ok();
+        if let Some(partial) = &partial {
+            PartialMeta {
+                format_id: itag.to_string(),
+                total: download.total(),
+            }
+            .save(partial.meta_path());
+        }
+
+        let (tx, rx) = mpsc::channel(64);
+        let sink = DownloadSink::new(tx, partial, 0, self.bandwidth.clone());
+        let (cache, watchdog) = (self.cache.clone(), Watchdog::start(self.timeouts));
+        let task = tokio::spawn(async move {
+            stream_range(&cache, sink, download, watchdog).await;
+        });
+        Ok(StreamingExtraction::new(rx, task))
+    }
+}
+
+/// Extraction with the yt-dlp binary.
+struct YtDlpSource {
+    yt_dlp: YtDlp,
+    cache: AudioCache,
+    sponsorblock: SponsorBlock,
+    timeouts: StreamTimeouts,
+}
+
+impl YtDlpSource {
+    /// Fail unless the binary exists and is recent enough.
+    async fn check(&self) -> Result<()> {
+        if !self.yt_dlp.path.exists() {
+            return Err(Error::ExtractionFailed(format!(
+                "yt-dlp not found at {}",
+                self.yt_dlp.path.display()
+            )));
+        }
+        self.yt_dlp.check().await
+    }
+}
+
+#[async_trait]
+impl AudioSource for YtDlpSource {
+    fn name(&self) -> &'static str {
+        "yt-dlp"
+    }
+
+    async fn extract(&self, video_id: &str) -> Result<ExtractedAudio> {
+        self.check().await?;
+
+        let mut args = self.yt_dlp.args(video_id).await;
+        args.extend([
+            "--no-progress".to_string(),
+            "-f".to_string(),
+            audio_format(self.yt_dlp.bandwidth.quality()).to_string(),
+        ]);
+        args.extend(print_metadata_args(&self.cache, video_id));
+        args.extend(["-o".to_string(), "-".to_string(), watch_url(video_id)]);
+
+        debug!("Running yt-dlp");
+
+        // yt-dlp is killed if extraction is cancelled while it runs
+        let output = AsyncCommand::new(&self.yt_dlp.path)
+            .args(&args)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| Error::ExtractionFailed(format!("Failed to run yt-dlp: {e}")))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("yt-dlp stderr: {}", stderr);
+            return Err(Error::ExtractionFailed(format!(
+                "yt-dlp failed: {}",
+                stderr.lines().next().unwrap_or("Unknown error")
+            )));
+        }
+
+        let data = output.stdout;
+        if data.is_empty() {
+            return Err(Error::ExtractionFailed(
+                "yt-dlp returned empty data".to_string(),
+            ));
+        }
+
+        // Artwork is fetched first, so it can be embedded once the audio is cached
+        let metadata = save_track_info(
+            &self.cache,
+            &self.sponsorblock,
+            &self.yt_dlp.network,
+            None,
+            video_id,
+        )
+        .await;
+
+        // Save to cache for next time
+        self.cache.write(video_id, &data);
+        let mime_type = detect_audio_mime(&data);
+        info!("Downloaded {} bytes ({})", data.len(), mime_type);
+
+        Ok(ExtractedAudio {
+            data: data.into(),
+            mime_type,
+            metadata,
+        })
+    }
+
+    async fn extract_streaming(&self, video_id: &str) -> Result<StreamingExtraction> {
+        self.check().await?;
+
+        let (tx, rx) = mpsc::channel(64);
+        let (yt_dlp, cache, sponsorblock, video_id) = (
+            self.yt_dlp.clone(),
+            self.cache.clone(),
+            self.sponsorblock.clone(),
+            video_id.to_string(),
+        );
+        let watchdog = Watchdog::start(self.timeouts);
+        let task = tokio::spawn(async move {
+            stream_yt_dlp(&yt_dlp, &cache, &sponsorblock, &video_id, watchdog, tx).await;
+        });
+        Ok(StreamingExtraction::new(rx, task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_core::StreamChunk;
+
+    /// A source serving fixed audio, or failing without any.
+    struct FakeSource {
+        name: &'static str,
+        audio: Option<&'static [u8]>,
+        cached: bool,
+    }
+
+    #[async_trait]
+    impl AudioSource for FakeSource {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn extract(&self, _video_id: &str) -> Result<ExtractedAudio> {
+            let data = self
+                .audio
+                .ok_or_else(|| Error::ContentNotAvailable(self.name.to_string()))?;
+            Ok(ExtractedAudio {
+                data: data.into(),
+                mime_type: "audio/mp4".to_string(),
+                metadata: crate::TrackMetadata::default(),
+            })
+        }
+
+        async fn extract_streaming(&self, video_id: &str) -> Result<StreamingExtraction> {
+            let audio = self.extract(video_id).await?;
+            let (tx, rx) = mpsc::channel(4);
+            let task = tokio::spawn(async move {
+                let _ = tx.send(StreamChunk::Data(audio.data.to_vec())).await;
+                let _ = tx.send(StreamChunk::Complete).await;
+            });
+            Ok(StreamingExtraction::new(rx, task))
+        }
+
+        fn is_cached(&self, _video_id: &str) -> bool {
+            self.cached
+        }
+    }
+
+    fn extractor(dir: &std::path::Path) -> Extractor {
+        Extractor {
+            yt_dlp_path: dir.join("yt-dlp"),
+            cache: AudioCache::new(dir.join("audio"), 1024 * 1024),
+            ..Extractor::new().with_native_extraction(false)
+        }
+    }
+
+    fn names(sources: &[Arc<dyn AudioSource>]) -> Vec<&'static str> {
+        sources.iter().map(|source| source.name()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sources_in_priority_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let failing = FakeSource {
+            name: "failing",
+            audio: None,
+            cached: false,
+        };
+        let remote = FakeSource {
+            name: "remote",
+            audio: Some(b"remote audio"),
+            cached: false,
+        };
+        let extractor = extractor(dir.path())
+            .with_source(remote, 50)
+            .with_source(failing, 300);
+        assert_eq!(
+            names(&extractor.sources_for("abc")),
+            ["failing", "yt-dlp", "remote"]
+        );
+
+        // Failed sources fall through to the next one
+        let audio = extractor.extract("abc").await?;
+        assert_eq!(&audio.data[..], b"remote audio");
+        assert!(!extractor.is_cached("abc"));
+
+        let mut extraction = extractor.extract_streaming("abc")?;
+        let mut data = Vec::new();
+        while let Some(chunk) = extraction.rx.recv().await {
+            match chunk {
+                StreamChunk::Data(chunk) => data.extend(chunk),
+                StreamChunk::Complete => break,
+                other => return Err(Error::Internal(format!("Unexpected chunk {other:?}"))),
+            }
+        }
+        assert_eq!(data, b"remote audio");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_sources_go_first() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let local = FakeSource {
+            name: "local",
+            audio: Some(b"local audio"),
+            cached: true,
+        };
+        let extractor = extractor(dir.path()).with_source(local, 0);
+        assert_eq!(names(&extractor.sources_for("abc")), ["local", "yt-dlp"]);
+        assert!(extractor.is_cached("abc"));
+        assert_eq!(&extractor.extract("abc").await?.data[..], b"local audio");
+        Ok(())
+    }
+}
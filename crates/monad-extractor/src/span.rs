@@ -0,0 +1,169 @@
+//! Intro and outro skipping for music videos.
+//!
+//! Music videos often open with dialogue or close with credits that the song
+//! itself doesn't have. The playable span comes from `SponsorBlock`
+//! `music_offtopic` segments when the video has them, and otherwise from the
+//! duration of the matching song (ATV) version, whose extra length is treated
+//! as an intro.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use monad_core::{PlaybackSpan, Result, Track};
+use monad_innertube::{InnerTubeClient, SearchFilter};
+use parking_lot::Mutex;
+use tracing::{debug, warn};
+
+use crate::sponsorblock::{SkipCategory, SponsorBlock};
+
+/// Segments this close to either end of the video count as touching it.
+const EDGE_TOLERANCE: f64 = 1.5;
+
+/// Duration differences smaller than this are encoding noise, not an intro.
+const MIN_INTRO_SECS: u64 = 5;
+
+/// Longer differences mean the song is probably a different edit.
+const MAX_INTRO_SECS: u64 = 120;
+
+/// Build a span from off-topic segments at the start and end of a video.
+fn span_from_segments(segments: &[(f64, f64)], duration: f64) -> PlaybackSpan {
+    let mut span = PlaybackSpan::default();
+    for &(start, end) in segments {
+        if start <= EDGE_TOLERANCE {
+            span.start = span.start.max(end);
+        } else if duration > 0.0 && end >= duration - EDGE_TOLERANCE {
+            span.end = Some(span.end.map_or(start, |e: f64| e.min(start)));
+        }
+    }
+    span
+}
+
+/// Build a span from the durations of a video and its song version.
+#[allow(clippy::cast_precision_loss)]
+fn span_from_song_duration(video_secs: u64, song_secs: u64) -> PlaybackSpan {
+    let intro = video_secs.saturating_sub(song_secs);
+    if song_secs == 0 || !(MIN_INTRO_SECS..=MAX_INTRO_SECS).contains(&intro) {
+        return PlaybackSpan::default();
+    }
+    PlaybackSpan::new(intro as f64, None)
+}
+
+/// Check whether a search result is the song version of a track.
+fn is_song_version(song: &Track, track: &Track) -> bool {
+    song.title.eq_ignore_ascii_case(&track.title)
+        && song.artist_name().eq_ignore_ascii_case(track.artist_name())
+}
+
+/// Works out which part of a track to play, caching results by video ID.
+#[derive(Clone)]
+pub struct SpanResolver {
+    sponsorblock: SponsorBlock,
+    innertube: InnerTubeClient,
+    spans: Arc<Mutex<HashMap<String, PlaybackSpan>>>,
+}
+
+impl SpanResolver {
+    /// Create a span resolver.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            sponsorblock: SponsorBlock::new(),
+            innertube: InnerTubeClient::new()?,
+            spans: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Get the span of a track to play.
+    ///
+    /// Lookup failures are logged and fall back to the whole track.
+    pub async fn resolve(&self, track: &Track) -> PlaybackSpan {
+        if let Some(span) = self.spans.lock().get(&track.id) {
+            return *span;
+        }
+
+        let span = match self.sponsorblock_span(track).await {
+            Ok(Some(span)) => span,
+            Ok(None) => self.song_span(track).await.unwrap_or_else(|e| {
+                warn!("Song version lookup for {} failed: {e}", track.id);
+                PlaybackSpan::default()
+            }),
+            Err(e) => {
+                warn!("SponsorBlock lookup for {} failed: {e}", track.id);
+                PlaybackSpan::default()
+            }
+        };
+
+        if !span.is_full() {
+            debug!(
+                "Playing {} from {:.1}s to {:?}",
+                track.id, span.start, span.end
+            );
+        }
+        self.spans.lock().insert(track.id.clone(), span);
+        span
+    }
+
+    /// Get the span from `SponsorBlock`, or `None` if the video has no segments.
+    async fn sponsorblock_span(&self, track: &Track) -> Result<Option<PlaybackSpan>> {
+        let segments = self
+            .sponsorblock
+            .segments_in(&track.id, &[SkipCategory::MusicOfftopic])
+            .await?;
+        if segments.segments.is_empty() {
+            return Ok(None);
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let duration = segments
+            .video_duration
+            .unwrap_or(track.duration.as_seconds() as f64);
+        let bounds: Vec<_> = segments.segments.iter().map(|s| (s.start, s.end)).collect();
+        Ok(Some(span_from_segments(&bounds, duration)))
+    }
+
+    /// Get the span from the duration of the track's song version.
+    async fn song_span(&self, track: &Track) -> Result<PlaybackSpan> {
+        let query = format!("{} {}", track.title, track.artist_name());
+        let results = self.innertube.search(&query, SearchFilter::Songs).await?;
+
+        // A track that is itself the song version plays in full
+        let Some(song) = results
+            .songs
+            .iter()
+            .find(|song| is_song_version(song, track))
+        else {
+            return Ok(PlaybackSpan::default());
+        };
+        if song.id == track.id {
+            return Ok(PlaybackSpan::default());
+        }
+        Ok(span_from_song_duration(
+            track.duration.as_seconds(),
+            song.duration.as_seconds(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_from_segments() {
+        let span = span_from_segments(&[(0.0, 12.5), (200.0, 240.0)], 240.5);
+        assert_eq!(span, PlaybackSpan::new(12.5, Some(200.0)));
+
+        // Segments in the middle of the video are left alone
+        assert!(span_from_segments(&[(60.0, 70.0)], 240.0).is_full());
+    }
+
+    #[test]
+    fn test_span_from_song_duration() {
+        assert_eq!(
+            span_from_song_duration(230, 200),
+            PlaybackSpan::new(30.0, None)
+        );
+        assert!(span_from_song_duration(202, 200).is_full());
+        assert!(span_from_song_duration(600, 200).is_full());
+        assert!(span_from_song_duration(180, 200).is_full());
+    }
+}
@@ -0,0 +1,229 @@
+//! `SponsorBlock` segments for auto-skipping non-music sections.
+//!
+//! Segments are crowd-sourced per video ID. They are fetched while a track
+//! is extracted and saved with its metadata, so cached tracks skip the same
+//! sections offline.
+
+use std::time::Duration;
+
+use monad_core::{Error, Result, SkipSegment};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+const API_URL: &str = "https://sponsor.ajay.app/api/skipSegments";
+
+/// Give up on a lookup after this long, so extraction is never held up for long.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Categories skipped by default: non-music sections, intros and outros.
+pub const DEFAULT_SKIP_CATEGORIES: &[SkipCategory] = &[
+    SkipCategory::MusicOfftopic,
+    SkipCategory::Intro,
+    SkipCategory::Outro,
+];
+
+/// A `SponsorBlock` segment category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipCategory {
+    /// Non-music section of a music video.
+    MusicOfftopic,
+    /// Intro animation or talking before the content.
+    Intro,
+    /// Credits or end cards after the content.
+    Outro,
+    /// Paid promotion.
+    Sponsor,
+    /// Unpaid self-promotion.
+    SelfPromo,
+    /// Reminder to like or subscribe.
+    Interaction,
+    /// Preview or recap.
+    Preview,
+    /// Tangents and jokes.
+    Filler,
+}
+
+impl SkipCategory {
+    /// Get the API name of the category.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::MusicOfftopic => "music_offtopic",
+            Self::Intro => "intro",
+            Self::Outro => "outro",
+            Self::Sponsor => "sponsor",
+            Self::SelfPromo => "selfpromo",
+            Self::Interaction => "interaction",
+            Self::Preview => "preview",
+            Self::Filler => "filler",
+        }
+    }
+}
+
+/// A segment as returned by the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSegment {
+    segment: (f64, f64),
+    category: String,
+    video_duration: Option<f64>,
+}
+
+/// Segments of a video.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Segments {
+    /// Segments, in the order returned.
+    pub segments: Vec<SkipSegment>,
+    /// Duration of the video the segments were submitted for (if known).
+    pub video_duration: Option<f64>,
+}
+
+/// Build the `categories` query parameter (a JSON array).
+fn categories_param(categories: &[SkipCategory]) -> String {
+    let names: Vec<String> = categories
+        .iter()
+        .map(|c| format!("\"{}\"", c.as_str()))
+        .collect();
+    format!("[{}]", names.join(","))
+}
+
+/// `SponsorBlock` API client.
+#[derive(Debug, Clone)]
+pub struct SponsorBlock {
+    http: reqwest::Client,
+    categories: Vec<SkipCategory>,
+}
+
+impl SponsorBlock {
+    /// Create a client fetching the [`DEFAULT_SKIP_CATEGORIES`].
+    pub fn new() -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            http,
+            categories: DEFAULT_SKIP_CATEGORIES.to_vec(),
+        }
+    }
+
+    /// Set the categories to skip (none disables lookups).
+    #[must_use]
+    pub fn with_categories(mut self, categories: Vec<SkipCategory>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    /// Get the categories to skip.
+    pub fn categories(&self) -> &[SkipCategory] {
+        &self.categories
+    }
+
+    /// Drop segments whose category is not configured.
+    pub fn retain_configured(&self, segments: &mut Vec<SkipSegment>) {
+        segments.retain(|s| self.categories.iter().any(|c| c.as_str() == s.category));
+    }
+
+    /// Fetch segments of the configured categories.
+    pub async fn segments(&self, video_id: &str) -> Result<Segments> {
+        self.segments_in(video_id, &self.categories).await
+    }
+
+    /// Fetch segments of the given categories.
+    pub async fn segments_in(
+        &self,
+        video_id: &str,
+        categories: &[SkipCategory],
+    ) -> Result<Segments> {
+        if categories.is_empty() {
+            return Ok(Segments::default());
+        }
+
+        let response = self
+            .http
+            .get(API_URL)
+            .query(&[
+                ("videoID", video_id.to_string()),
+                ("categories", categories_param(categories)),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::Network(format!("SponsorBlock request failed: {e}")))?;
+
+        // Videos without segments are a 404
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Segments::default());
+        }
+        let raw: Vec<RawSegment> = response
+            .error_for_status()
+            .map_err(|e| Error::Network(format!("SponsorBlock request failed: {e}")))?
+            .json()
+            .await
+            .map_err(|e| Error::Parse(format!("Invalid SponsorBlock response: {e}")))?;
+
+        debug!("{} SponsorBlock segments for {video_id}", raw.len());
+        Ok(Segments {
+            video_duration: raw.iter().find_map(|s| s.video_duration),
+            segments: raw
+                .into_iter()
+                .map(|s| SkipSegment {
+                    start: s.segment.0,
+                    end: s.segment.1,
+                    category: s.category,
+                })
+                .collect(),
+        })
+    }
+
+    /// Fetch segments of the configured categories, logging failures.
+    pub async fn skip_segments(&self, video_id: &str) -> Vec<SkipSegment> {
+        match self.segments(video_id).await {
+            Ok(segments) => segments.segments,
+            Err(e) => {
+                warn!("Failed to fetch SponsorBlock segments for {video_id}: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl Default for SponsorBlock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories_param() {
+        assert_eq!(
+            categories_param(DEFAULT_SKIP_CATEGORIES),
+            r#"["music_offtopic","intro","outro"]"#
+        );
+        assert_eq!(categories_param(&[]), "[]");
+    }
+
+    #[tokio::test]
+    async fn test_no_categories_skips_lookup() -> Result<()> {
+        let client = SponsorBlock::new().with_categories(Vec::new());
+        assert_eq!(client.segments("dQw4w9WgXcQ").await?, Segments::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retain_configured() {
+        let segment = |category: &str| SkipSegment {
+            start: 0.0,
+            end: 10.0,
+            category: category.to_string(),
+        };
+        let mut segments = vec![segment("intro"), segment("sponsor"), segment("outro")];
+        SponsorBlock::new()
+            .with_categories(vec![SkipCategory::Intro])
+            .retain_configured(&mut segments);
+        assert_eq!(segments, [segment("intro")]);
+    }
+}
@@ -0,0 +1,113 @@
+//! Stall detection for streaming downloads.
+//!
+//! A download that stops receiving data (a dropped connection, heavy
+//! throttling, a hung yt-dlp) would otherwise wait forever. Each read must
+//! return within the inactivity timeout and the whole download must finish
+//! within the total timeout, or the download is abandoned as stalled.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Default time a download may go without receiving data.
+pub const DEFAULT_INACTIVITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time limit for a whole download.
+pub const DEFAULT_TOTAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Timeouts for streaming downloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTimeouts {
+    /// Longest time to wait for the next piece of data.
+    pub inactivity: Duration,
+    /// Longest time the whole download may take.
+    pub total: Duration,
+}
+
+impl Default for StreamTimeouts {
+    fn default() -> Self {
+        Self {
+            inactivity: DEFAULT_INACTIVITY_TIMEOUT,
+            total: DEFAULT_TOTAL_TIMEOUT,
+        }
+    }
+}
+
+/// Why a download was abandoned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stall {
+    /// No data arrived within the inactivity timeout.
+    Inactive(Duration),
+    /// The download took longer than the total timeout.
+    TimedOut(Duration),
+}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inactive(after) => write!(f, "no data received for {}s", after.as_secs()),
+            Self::TimedOut(after) => write!(f, "download exceeded {}s", after.as_secs()),
+        }
+    }
+}
+
+/// Enforces [`StreamTimeouts`] on the reads of one download.
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog {
+    timeouts: StreamTimeouts,
+    deadline: Instant,
+}
+
+impl Watchdog {
+    /// Start timing a download.
+    pub fn start(timeouts: StreamTimeouts) -> Self {
+        Self {
+            timeouts,
+            deadline: Instant::now() + timeouts.total,
+        }
+    }
+
+    /// Wait for a read, failing if it takes too long.
+    pub async fn read<T>(&self, read: impl Future<Output = T>) -> Result<T, Stall> {
+        let idle_deadline = Instant::now() + self.timeouts.inactivity;
+        let stall = if idle_deadline < self.deadline {
+            Stall::Inactive(self.timeouts.inactivity)
+        } else {
+            Stall::TimedOut(self.timeouts.total)
+        };
+        tokio::time::timeout_at(idle_deadline.min(self.deadline), read)
+            .await
+            .map_err(|_| stall)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watchdog() {
+        let watchdog = Watchdog::start(StreamTimeouts {
+            inactivity: Duration::from_secs(5),
+            total: Duration::from_secs(12),
+        });
+
+        assert_eq!(watchdog.read(async { 1 }).await, Ok(1));
+        let slow = tokio::time::sleep(Duration::from_secs(6));
+        assert_eq!(
+            watchdog.read(slow).await,
+            Err(Stall::Inactive(Duration::from_secs(5)))
+        );
+
+        // Each read gets the full inactivity timeout, until the total runs out
+        let read = tokio::time::sleep(Duration::from_secs(4));
+        assert_eq!(watchdog.read(read).await, Ok(()));
+        let pending = std::future::pending::<()>();
+        assert_eq!(
+            watchdog.read(pending).await,
+            Err(Stall::TimedOut(Duration::from_secs(12)))
+        );
+    }
+}
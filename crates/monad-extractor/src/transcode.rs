@@ -0,0 +1,190 @@
+//! Re-encoding of cached audio to Opus, to save disk space.
+//!
+//! Downloads are cached and played in their original format, so playback is
+//! never delayed. The cached copy is then re-encoded by ffmpeg in the
+//! background and replaced only if the result is smaller.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use monad_core::{Error, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command as AsyncCommand;
+use tracing::{debug, info};
+
+use crate::cache::AudioCache;
+
+/// Default Opus bitrate in kbps.
+pub const DEFAULT_TRANSCODE_BITRATE: u32 = 96;
+
+/// How cached audio is re-encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeSettings {
+    /// Path to the ffmpeg binary.
+    pub ffmpeg_path: PathBuf,
+    /// Opus bitrate in kbps.
+    pub bitrate_kbps: u32,
+}
+
+impl Default for TranscodeSettings {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRANSCODE_BITRATE)
+    }
+}
+
+impl TranscodeSettings {
+    /// Re-encode with ffmpeg from `PATH` at the given bitrate.
+    pub fn new(bitrate_kbps: u32) -> Self {
+        Self {
+            ffmpeg_path: PathBuf::from("ffmpeg"),
+            bitrate_kbps,
+        }
+    }
+
+    /// Use the ffmpeg binary at the given path.
+    #[must_use]
+    pub fn with_ffmpeg_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ffmpeg_path = path.into();
+        self
+    }
+
+    /// ffmpeg arguments to re-encode stdin to Opus in `WebM` on stdout.
+    fn args(&self) -> Vec<String> {
+        [
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-i",
+            "pipe:0",
+            "-vn",
+            "-c:a",
+            "libopus",
+            "-b:a",
+            &format!("{}k", self.bitrate_kbps),
+            "-f",
+            "webm",
+            "pipe:1",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    /// Re-encode audio to Opus.
+    pub async fn transcode(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        let mut child = AsyncCommand::new(&self.ffmpeg_path)
+            .args(self.args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::ExtractionFailed(format!("Failed to run ffmpeg: {e}")))?;
+
+        let (Some(mut stdin), Some(mut stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(Error::ExtractionFailed(
+                "ffmpeg pipes unavailable".to_string(),
+            ));
+        };
+
+        // Feed the input while reading the output, so neither pipe fills up
+        let feed = async move {
+            let result = stdin.write_all(&data).await;
+            drop(stdin);
+            result
+        };
+        let mut output = Vec::new();
+        let (fed, read) = tokio::join!(feed, stdout.read_to_end(&mut output));
+        read?;
+
+        let result = child.wait_with_output().await?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(Error::ExtractionFailed(format!(
+                "ffmpeg failed: {}",
+                stderr.lines().next().unwrap_or("Unknown error")
+            )));
+        }
+        fed?;
+        if output.is_empty() {
+            return Err(Error::ExtractionFailed(
+                "ffmpeg returned empty data".to_string(),
+            ));
+        }
+        Ok(output)
+    }
+}
+
+impl AudioCache {
+    /// Re-encode a cached track, replacing it if the result is smaller.
+    ///
+    /// Returns whether the track was replaced.
+    pub async fn transcode_cached(&self, video_id: &str) -> Result<bool> {
+        let Some(settings) = self.transcode() else {
+            return Ok(false);
+        };
+        let _rewrite = self.lock_rewrite().await;
+        // The track may have been evicted since it was cached
+        let Ok(original) = fs::read(self.path(video_id)) else {
+            return Ok(false);
+        };
+        let original_len = original.len();
+        let transcoded = settings.transcode(original).await?;
+        if transcoded.len() >= original_len {
+            debug!("Keeping original audio for {video_id}, transcoding saved nothing");
+            return Ok(false);
+        }
+
+        self.replace_audio(video_id, &transcoded)?;
+
+        info!(
+            "Transcoded {video_id} to Opus: {original_len} -> {} bytes",
+            transcoded.len()
+        );
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let args = TranscodeSettings::new(64).args();
+        assert!(args.windows(2).any(|w| w == ["-b:a", "64k"]));
+        assert!(args.windows(2).any(|w| w == ["-c:a", "libopus"]));
+        assert_eq!(args.last().map(String::as_str), Some("pipe:1"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transcode_replaces_smaller() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        // Stands in for ffmpeg, "encoding" to the first half of the input
+        let fake_ffmpeg = dir.path().join("ffmpeg");
+        fs::write(&fake_ffmpeg, "#!/bin/sh\nhead -c 8\ncat > /dev/null\n")?;
+        fs::set_permissions(&fake_ffmpeg, fs::Permissions::from_mode(0o755))?;
+
+        let mut cache = AudioCache::new(dir.path().join("audio"), 1024);
+        // Enabled after writing, so no background transcode races the test
+        cache.write("abc", b"original audio!!");
+        cache.set_transcode(Some(
+            TranscodeSettings::default().with_ffmpeg_path(&fake_ffmpeg),
+        ));
+
+        assert!(cache.transcode_cached("abc").await?);
+        assert_eq!(cache.read("abc").as_deref(), Some(&b"original"[..]));
+        // No longer smaller, so kept as is
+        assert!(!cache.transcode_cached("abc").await?);
+
+        cache.set_transcode(Some(
+            TranscodeSettings::default().with_ffmpeg_path(dir.path().join("missing")),
+        ));
+        assert!(cache.transcode_cached("abc").await.is_err());
+        assert!(cache.contains("abc"));
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+//! yt-dlp version compatibility checks.
+//!
+//! Extraction passes flags that only recent yt-dlp releases understand, and
+//! older binaries reject them with cryptic errors. The binary is probed once
+//! with `--version` and `--help`, and an outdated one is reported as
+//! [`Error::ToolOutdated`], optionally after trying `yt-dlp -U` first.
+
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use monad_core::{Error, Result};
+use tokio::process::Command as AsyncCommand;
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
+
+/// Oldest yt-dlp release with the flags extraction relies on.
+pub const MIN_YT_DLP_VERSION: YtDlpVersion = YtDlpVersion::new(2025, 11, 12);
+
+/// Flags passed on every yt-dlp invocation, which must be supported.
+const REQUIRED_FLAGS: &[&str] = &["--js-runtimes", "--remote-components"];
+
+/// Time allowed for probing yt-dlp.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Time allowed for yt-dlp to update itself.
+const UPDATE_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// A yt-dlp release version, such as `2025.11.12` or nightly `2025.11.12.232906`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct YtDlpVersion {
+    year: u16,
+    month: u8,
+    day: u8,
+    /// Build number of nightly releases (0 for stable ones).
+    build: u32,
+}
+
+impl YtDlpVersion {
+    /// Create a stable release version.
+    pub const fn new(year: u16, month: u8, day: u8) -> Self {
+        Self {
+            year,
+            month,
+            day,
+            build: 0,
+        }
+    }
+
+    /// Parse the output of `yt-dlp --version`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().split('.');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        let build = match parts.next() {
+            Some(build) => build.parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            year,
+            month,
+            day,
+            build,
+        })
+    }
+}
+
+impl fmt::Display for YtDlpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}.{:02}", self.year, self.month, self.day)?;
+        if self.build != 0 {
+            write!(f, ".{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check whether a binary's `--version` and `--help` output meet the requirements.
+///
+/// Versions that do not parse (custom builds) are judged by their flags alone.
+fn is_compatible(version: &str, help: &str) -> bool {
+    YtDlpVersion::parse(version).map_or(true, |v| v >= MIN_YT_DLP_VERSION)
+        && REQUIRED_FLAGS.iter().all(|flag| help.contains(flag))
+}
+
+/// Run yt-dlp with the given arguments and return its stdout.
+async fn run(path: &Path, args: &[&str], timeout: Duration) -> Result<String> {
+    let output = tokio::time::timeout(
+        timeout,
+        AsyncCommand::new(path)
+            .args(args)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| Error::ExtractionFailed(format!("yt-dlp {} timed out", args.join(" "))))?
+    .map_err(|e| Error::ExtractionFailed(format!("Failed to run yt-dlp: {e}")))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::ExtractionFailed(format!(
+            "yt-dlp {} failed: {}",
+            args.join(" "),
+            stderr.lines().next().unwrap_or("Unknown error")
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Probe yt-dlp, returning its version if it is outdated.
+async fn probe(path: &Path) -> Result<Option<String>> {
+    let version = run(path, &["--version"], PROBE_TIMEOUT).await?;
+    let version = version.trim().to_string();
+    let help = run(path, &["--help"], PROBE_TIMEOUT).await?;
+    if is_compatible(&version, &help) {
+        debug!("yt-dlp {version} is compatible");
+        Ok(None)
+    } else {
+        Ok(Some(version))
+    }
+}
+
+/// Checks the yt-dlp binary once, remembering the outcome.
+#[derive(Debug, Default)]
+pub struct VersionCheck {
+    /// Run `yt-dlp -U` when the binary is outdated.
+    auto_update: bool,
+    /// The outdated version found, if any.
+    outdated: OnceCell<Option<String>>,
+}
+
+impl VersionCheck {
+    /// Create a check, optionally updating outdated binaries.
+    pub const fn new(auto_update: bool) -> Self {
+        Self {
+            auto_update,
+            outdated: OnceCell::const_new(),
+        }
+    }
+
+    /// Check that the binary at `path` is recent enough, probing it the first time.
+    ///
+    /// Failures to run the binary are not remembered, so the next call probes again.
+    pub async fn ensure(&self, path: &Path) -> Result<()> {
+        let outdated = self
+            .outdated
+            .get_or_try_init(|| self.probe_and_update(path))
+            .await?;
+        match outdated {
+            None => Ok(()),
+            Some(found) => Err(Error::ToolOutdated {
+                tool: "yt-dlp".to_string(),
+                found: found.clone(),
+                required: MIN_YT_DLP_VERSION.to_string(),
+            }),
+        }
+    }
+
+    /// Probe the binary, updating it if it is outdated and updates are enabled.
+    async fn probe_and_update(&self, path: &Path) -> Result<Option<String>> {
+        let Some(found) = probe(path).await? else {
+            return Ok(None);
+        };
+        if !self.auto_update {
+            warn!("yt-dlp {found} is outdated, {MIN_YT_DLP_VERSION} or newer is required");
+            return Ok(Some(found));
+        }
+
+        info!("yt-dlp {found} is outdated, updating it");
+        if let Err(e) = run(path, &["-U"], UPDATE_TIMEOUT).await {
+            warn!("Failed to update yt-dlp: {e}");
+            return Ok(Some(found));
+        }
+        probe(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_parse() {
+        let stable = YtDlpVersion::parse("2025.11.12\n");
+        assert_eq!(stable, Some(YtDlpVersion::new(2025, 11, 12)));
+        let nightly = YtDlpVersion::parse("2025.11.12.232906");
+        assert!(nightly > stable);
+        assert_eq!(
+            nightly.map(|v| v.to_string()).as_deref(),
+            Some("2025.11.12.232906")
+        );
+        assert!(YtDlpVersion::parse("2023.01.06") < Some(MIN_YT_DLP_VERSION));
+        assert_eq!(YtDlpVersion::parse("not a version"), None);
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let help = "  --js-runtimes RUNTIMES\n  --remote-components COMPONENTS\n";
+        assert!(is_compatible("2025.12.01", help));
+        assert!(is_compatible("custom-build", help));
+        assert!(!is_compatible("2023.01.06", help));
+        assert!(!is_compatible("2025.12.01", "  --js-runtimes RUNTIMES\n"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_ensure_updates_outdated() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        // Stands in for yt-dlp, reporting the version in a file that -U bumps
+        let version = dir.path().join("version");
+        fs::write(&version, "2023.01.06")?;
+        let fake = dir.path().join("yt-dlp");
+        fs::write(
+            &fake,
+            format!(
+                "#!/bin/sh\ncase \"$1\" in\n\
+                 --version) cat {v} ;;\n\
+                 --help) echo '--js-runtimes --remote-components' ;;\n\
+                 -U) echo 2025.12.01 > {v} ;;\n\
+                 esac\n",
+                v = version.display()
+            ),
+        )?;
+        fs::set_permissions(&fake, fs::Permissions::from_mode(0o755))?;
+
+        let check = VersionCheck::new(false);
+        assert!(matches!(
+            check.ensure(&fake).await,
+            Err(Error::ToolOutdated { found, .. }) if found == "2023.01.06"
+        ));
+
+        VersionCheck::new(true).ensure(&fake).await?;
+        assert_eq!(fs::read_to_string(&version)?.trim(), "2025.12.01");
+        // The outcome of the first check is remembered
+        assert!(check.ensure(&fake).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Signature deciphering using `YouTube`'s player script.
+//!
+//! Formats returned by web clients may carry a `signatureCipher` instead of a
+//! URL. The signature has to be transformed by a short sequence of reverse,
+//! splice, and swap operations defined in the player's `base.js` before the
+//! stream can be fetched. The player's signature timestamp must also be sent
+//! with the player request so the returned ciphers match this script. The
+//! `n` throttling parameter is not transformed, so streams carrying it are
+//! throttled; callers should fetch those another way.
+
+use monad_core::{Error, Result};
+
+/// URL of the iframe API script, which references the current player version.
+pub const IFRAME_API_URL: &str = "https://www.youtube.com/iframe_api";
+
+/// One step of the signature transform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherOp {
+    /// Reverse the signature.
+    Reverse,
+    /// Drop the first `n` characters.
+    Splice(usize),
+    /// Swap the first character with the one at `n % len`.
+    Swap(usize),
+}
+
+/// Parsed player script.
+#[derive(Debug, Clone)]
+pub struct PlayerScript {
+    /// Player version ID.
+    pub id: String,
+    /// Signature timestamp to send with player requests.
+    pub signature_timestamp: Option<u32>,
+    /// Signature transform, if it could be found.
+    ops: Option<Vec<CipherOp>>,
+}
+
+impl PlayerScript {
+    /// Parse a player's `base.js`.
+    pub fn parse(id: impl Into<String>, js: &str) -> Self {
+        Self {
+            id: id.into(),
+            signature_timestamp: parse_signature_timestamp(js),
+            ops: parse_cipher_ops(js),
+        }
+    }
+
+    /// URL of the `base.js` script for a player version.
+    pub fn url(id: &str) -> String {
+        format!("https://www.youtube.com/s/player/{id}/player_ias.vflset/en_US/base.js")
+    }
+
+    /// Find the player version referenced by the iframe API script.
+    pub fn id_from_iframe_api(js: &str) -> Option<String> {
+        let start = js.find("player\\/")? + "player\\/".len();
+        let id: String = js[start..]
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect();
+        (!id.is_empty()).then_some(id)
+    }
+
+    /// Apply the signature transform.
+    pub fn decipher(&self, signature: &str) -> Result<String> {
+        let ops = self.ops.as_ref().ok_or_else(|| {
+            Error::StreamExtraction(format!("No signature cipher in player {}", self.id))
+        })?;
+
+        let mut chars: Vec<char> = signature.chars().collect();
+        for op in ops {
+            match *op {
+                CipherOp::Reverse => chars.reverse(),
+                CipherOp::Splice(n) => {
+                    chars.drain(..n.min(chars.len()));
+                }
+                CipherOp::Swap(n) => {
+                    if !chars.is_empty() {
+                        let len = chars.len();
+                        chars.swap(0, n % len);
+                    }
+                }
+            }
+        }
+        Ok(chars.into_iter().collect())
+    }
+
+    /// Check whether a stream URL carries the `n` parameter, which would
+    /// have to be transformed for the stream to download at full speed.
+    pub fn is_throttled(url: &str) -> bool {
+        url::Url::parse(url).is_ok_and(|url| url.query_pairs().any(|(key, _)| key == "n"))
+    }
+
+    /// Build a playable URL from a format's `signatureCipher` value.
+    pub fn decipher_url(&self, signature_cipher: &str) -> Result<String> {
+        let mut url = None;
+        let mut signature = None;
+        let mut param = "signature".to_string();
+
+        for (key, value) in url::form_urlencoded::parse(signature_cipher.as_bytes()) {
+            match key.as_ref() {
+                "url" => url = Some(value.into_owned()),
+                "s" => signature = Some(value.into_owned()),
+                "sp" => param = value.into_owned(),
+                _ => {}
+            }
+        }
+
+        let url = url.ok_or_else(|| Error::StreamExtraction("Cipher has no URL".to_string()))?;
+        let signature = signature
+            .ok_or_else(|| Error::StreamExtraction("Cipher has no signature".to_string()))?;
+
+        let mut url = url::Url::parse(&url)
+            .map_err(|e| Error::StreamExtraction(format!("Invalid stream URL: {e}")))?;
+        url.query_pairs_mut()
+            .append_pair(&param, &self.decipher(&signature)?);
+        Ok(url.into())
+    }
+}
+
+/// Find `signatureTimestamp:NNNNN` (or `sts:NNNNN`) in the player script.
+fn parse_signature_timestamp(js: &str) -> Option<u32> {
+    ["signatureTimestamp:", "sts:"].iter().find_map(|key| {
+        let start = js.find(key)? + key.len();
+        let digits: String = js[start..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.parse().ok()
+    })
+}
+
+/// Find the signature transform function and resolve its helper object.
+///
+/// The transform looks like
+/// `function(a){a=a.split("");Xy.ab(a,3);Xy.cd(a);return a.join("")}`.
+fn parse_cipher_ops(js: &str) -> Option<Vec<CipherOp>> {
+    js.match_indices(".split(\"\");")
+        .find_map(|(index, matched)| {
+            let var = identifier_before(&js[..index])?;
+            let body_start = index + matched.len();
+            let body_end = body_start + js[body_start..].find(".join(\"\")")?;
+            let body = &js[body_start..body_end];
+
+            let calls = body
+                .strip_suffix(&format!("return {var}"))?
+                .split(';')
+                .filter(|s| !s.is_empty())
+                .map(|statement| parse_call(statement, var))
+                .collect::<Option<Vec<_>>>()?;
+
+            let object = calls.first()?.0;
+            if calls.iter().any(|(obj, _, _)| *obj != object) {
+                return None;
+            }
+
+            let methods = parse_helper_object(js, object)?;
+            calls
+                .into_iter()
+                .map(|(_, method, arg)| {
+                    let kind = methods.iter().find(|(name, _)| *name == method)?.1;
+                    Some(match kind {
+                        MethodKind::Reverse => CipherOp::Reverse,
+                        MethodKind::Splice => CipherOp::Splice(arg),
+                        MethodKind::Swap => CipherOp::Swap(arg),
+                    })
+                })
+                .collect()
+        })
+}
+
+/// Parse `Obj.method(a,N)` or `Obj["method"](a,N)`.
+fn parse_call<'a>(statement: &'a str, var: &str) -> Option<(&'a str, &'a str, usize)> {
+    let open = statement.find('(')?;
+    let callee = &statement[..open];
+    let args = statement[open + 1..].strip_suffix(')')?;
+
+    let (object, method) = match callee.split_once('[') {
+        Some((object, rest)) => (object, rest.strip_suffix(']')?.trim_matches('"')),
+        None => callee.split_once('.')?,
+    };
+
+    let mut args = args.split(',');
+    if args.next()? != var {
+        return None;
+    }
+    let arg = args.next().map_or(Some(0), |n| n.trim().parse().ok())?;
+    Some((object, method, arg))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MethodKind {
+    Reverse,
+    Splice,
+    Swap,
+}
+
+/// Parse the methods of `var Obj={name:function(a,b){...},...}`.
+fn parse_helper_object<'a>(js: &'a str, object: &str) -> Option<Vec<(&'a str, MethodKind)>> {
+    let definition = format!("{object}={{");
+    let start = js.match_indices(&definition).find_map(|(index, _)| {
+        let boundary = js[..index]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !is_identifier_char(c));
+        boundary.then_some(index + definition.len())
+    })?;
+
+    let body = &js[start..start + matching_brace(&js[start..])?];
+    let methods = split_top_level(body)
+        .into_iter()
+        .filter_map(|method| {
+            let (name, function) = method.split_once(':')?;
+            let kind = if function.contains("reverse") {
+                MethodKind::Reverse
+            } else if function.contains("splice") {
+                MethodKind::Splice
+            } else {
+                MethodKind::Swap
+            };
+            Some((name.trim().trim_matches('"'), kind))
+        })
+        .collect::<Vec<_>>();
+
+    (!methods.is_empty()).then_some(methods)
+}
+
+/// Byte offset of the `}` closing an object whose `{` precedes `s`.
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split object members at commas that are not nested in braces or parens.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn identifier_before(s: &str) -> Option<&str> {
+    let start = s
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_char(*c))
+        .last()?
+        .0;
+    Some(&s[start..])
+}
+
+const fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const PLAYER_JS: &str = concat!(
+        r#"var x=1;var Wq={Ab:function(a,b){a.splice(0,b)},"#,
+        r#"cD:function(a){a.reverse()},"#,
+        r#"Ef:function(a,b){var c=a[0];a[0]=a[b%a.length];a[b%a.length]=c}};"#,
+        r#"Xy=function(a){a=a.split("");Wq.Ab(a,2);Wq.cD(a,41);Wq["Ef"](a,3);return a.join("")};"#,
+        r#"var cfg={signatureTimestamp:20073,other:1};"#,
+    );
+
+    #[test]
+    fn test_parse_player_script() {
+        let player = PlayerScript::parse("abc123", PLAYER_JS);
+        assert_eq!(player.signature_timestamp, Some(20073));
+        assert_eq!(
+            player.ops,
+            Some(vec![
+                CipherOp::Splice(2),
+                CipherOp::Reverse,
+                CipherOp::Swap(3)
+            ])
+        );
+
+        // "abcdefg" -> "cdefg" -> "gfedc" -> "dfegc"
+        assert_eq!(player.decipher("abcdefg").unwrap(), "dfegc");
+    }
+
+    #[test]
+    fn test_decipher_url() {
+        let player = PlayerScript::parse("abc123", PLAYER_JS);
+        let cipher = "s=abcdefg&sp=sig&url=https%3A%2F%2Fexample.com%2Fvideoplayback%3Fitag%3D140";
+        assert_eq!(
+            player.decipher_url(cipher).unwrap(),
+            "https://example.com/videoplayback?itag=140&sig=dfegc"
+        );
+        assert!(!PlayerScript::is_throttled(
+            "https://example.com/videoplayback?itag=140&sig=dfegc"
+        ));
+        assert!(PlayerScript::is_throttled(
+            "https://example.com/videoplayback?itag=140&n=aBcD"
+        ));
+
+        let without_cipher = PlayerScript::parse("abc123", "var a=1;");
+        assert!(without_cipher.decipher_url(cipher).is_err());
+    }
+
+    #[test]
+    fn test_player_id_from_iframe_api() {
+        let js = r"var scriptUrl = 'https:\/\/www.youtube.com\/s\/player\/8d287e4d\/www-widgetapi.vflset\/www-widgetapi.js';";
+        assert_eq!(
+            PlayerScript::id_from_iframe_api(js).as_deref(),
+            Some("8d287e4d")
+        );
+        assert_eq!(PlayerScript::id_from_iframe_api("nothing"), None);
+    }
+}
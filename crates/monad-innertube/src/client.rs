@@ -11,7 +11,9 @@ use serde::{de::DeserializeOwned, Serialize};
 use sha2::{Digest, Sha256};
 use tracing::{debug, warn};
 
+use crate::cipher::PlayerScript;
 use crate::context::ClientContext;
+use crate::search_cache::SearchCache;
 
 const BASE_URL: &str = "https://music.youtube.com/youtubei/v1";
 const ORIGIN: &str = "https://music.youtube.com";
@@ -28,20 +30,20 @@ const BASE_RETRY_DELAY_MS: u64 = 500;
 
 /// Cache entry with expiration.
 #[derive(Debug, Clone)]
-struct CacheEntry<T> {
-    value: T,
+pub(crate) struct CacheEntry<T> {
+    pub(crate) value: T,
     expires_at: std::time::Instant,
 }
 
 impl<T> CacheEntry<T> {
-    fn new(value: T, ttl: Duration) -> Self {
+    pub(crate) fn new(value: T, ttl: Duration) -> Self {
         Self {
             value,
             expires_at: std::time::Instant::now() + ttl,
         }
     }
 
-    fn is_expired(&self) -> bool {
+    pub(crate) fn is_expired(&self) -> bool {
         std::time::Instant::now() >= self.expires_at
     }
 }
@@ -57,8 +59,14 @@ pub struct InnerTubeClient {
     cache: Arc<DashMap<String, CacheEntry<Vec<u8>>>>,
     /// Cache TTL for API responses.
     cache_ttl: Duration,
+    /// Recent search results, for stale-while-revalidate searches.
+    pub(crate) search_cache: SearchCache,
     /// Rate limiter state.
     rate_limit_state: Arc<RwLock<RateLimitState>>,
+    /// Current player version, looked up from the iframe API on demand.
+    pub(crate) player_version: Arc<RwLock<Option<CacheEntry<String>>>>,
+    /// Player scripts used to decipher stream signatures, by script URL.
+    pub(crate) player_scripts: Arc<DashMap<String, Arc<PlayerScript>>>,
 }
 
 #[derive(Debug, Default)]
@@ -151,7 +159,10 @@ impl InnerTubeClient {
             context,
             cache: Arc::new(DashMap::new()),
             cache_ttl: Duration::from_secs(300), // 5 minutes default
+            search_cache: SearchCache::default(),
             rate_limit_state: Arc::new(RwLock::new(RateLimitState::default())),
+            player_version: Arc::new(RwLock::new(None)),
+            player_scripts: Arc::new(DashMap::new()),
         })
     }
 
@@ -163,6 +174,22 @@ impl InnerTubeClient {
 
     /// Make a POST request to an `InnerTube` endpoint.
     pub(crate) async fn post<T, R>(&self, endpoint: &str, body: &T) -> Result<R>
+    where
+        T: Serialize,
+        R: DeserializeOwned,
+    {
+        self.post_with_cache(endpoint, body, true).await
+    }
+
+    /// Make a POST request, optionally skipping cached responses.
+    ///
+    /// The response is cached either way.
+    pub(crate) async fn post_with_cache<T, R>(
+        &self,
+        endpoint: &str,
+        body: &T,
+        use_cache: bool,
+    ) -> Result<R>
     where
         T: Serialize,
         R: DeserializeOwned,
@@ -174,7 +201,7 @@ impl InnerTubeClient {
         let cache_key = self.cache_key(endpoint, &body_bytes);
 
         // Check cache first
-        if let Some(cached) = self.get_cached(&cache_key) {
+        if let Some(cached) = use_cache.then(|| self.get_cached(&cache_key)).flatten() {
             debug!("Cache hit for {endpoint}");
             return serde_json::from_slice(&cached).map_err(|e| Error::ParseError(e.to_string()));
         }
@@ -241,6 +268,29 @@ impl InnerTubeClient {
         Err(last_error.unwrap_or_else(|| Error::Network("Request failed".to_string())))
     }
 
+    /// Fetch a text resource (e.g. a player script) with a plain GET request.
+    pub(crate) async fn get_text(&self, url: &str) -> Result<String> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| Error::Network(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Http(monad_core::HttpError::StatusError {
+                status: status.as_u16(),
+                message: format!("GET {url} failed"),
+            }));
+        }
+
+        response
+            .text()
+            .await
+            .map_err(|e| Error::Network(format!("Failed to read response body: {e}")))
+    }
+
     async fn do_request(&self, url: &str, body: &[u8]) -> Result<Vec<u8>> {
         let response = self
             .http
@@ -318,9 +368,10 @@ impl InnerTubeClient {
         self.cache.retain(|_, entry| !entry.is_expired());
     }
 
-    /// Clear the cache.
+    /// Clear the cache, including recent search results.
     pub fn clear_cache(&self) {
         self.cache.clear();
+        self.search_cache.clear();
     }
 
     /// Get the number of cached entries.
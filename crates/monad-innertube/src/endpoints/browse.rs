@@ -263,7 +263,7 @@ fn parse_album_track(item: &serde_json::Value, album: &Album, _index: usize) ->
             .and_then(|r| r.get("text"))
             .and_then(|t| t.as_str())
         {
-            track.duration = parse_duration_str(duration_text);
+            track.duration = Duration::parse(duration_text).unwrap_or_default();
         }
     }
 
@@ -500,7 +500,7 @@ fn parse_artist_track(item: &serde_json::Value) -> Option<Track> {
             .and_then(|r| r.get("text"))
             .and_then(|t| t.as_str())
         {
-            track.duration = parse_duration_str(duration_text);
+            track.duration = Duration::parse(duration_text).unwrap_or_default();
         }
     }
 
@@ -793,7 +793,7 @@ fn parse_playlist_track(item: &serde_json::Value) -> Option<Track> {
             .and_then(|r| r.get("text"))
             .and_then(|t| t.as_str())
         {
-            track.duration = parse_duration_str(duration_text);
+            track.duration = Duration::parse(duration_text).unwrap_or_default();
         }
     }
 
@@ -819,22 +819,3 @@ fn parse_thumbnail_array(thumbs: &[serde_json::Value]) -> Thumbnails {
             .collect(),
     )
 }
-
-fn parse_duration_str(s: &str) -> Duration {
-    let parts: Vec<&str> = s.split(':').collect();
-    let seconds: u64 = match parts.len() {
-        2 => {
-            let mins: u64 = parts[0].parse().unwrap_or(0);
-            let secs: u64 = parts[1].parse().unwrap_or(0);
-            mins * 60 + secs
-        }
-        3 => {
-            let hours: u64 = parts[0].parse().unwrap_or(0);
-            let mins: u64 = parts[1].parse().unwrap_or(0);
-            let secs: u64 = parts[2].parse().unwrap_or(0);
-            hours * 3600 + mins * 60 + secs
-        }
-        _ => 0,
-    };
-    Duration::from_seconds(seconds)
-}
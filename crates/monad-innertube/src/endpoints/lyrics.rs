@@ -0,0 +1,138 @@
+//! Lyrics endpoint implementation.
+//!
+//! The watch page of a track links to a lyrics browse page when `YouTube`
+//! Music has lyrics for it. Browsed as the Android client, the page carries
+//! line-timed lyrics where available; otherwise it has the plain text.
+
+use monad_core::{Error, Result};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    types::{BrowsePayload, InnerTubeRequest, NextPayload, RawBrowseResponse},
+    ClientContext, InnerTubeClient,
+};
+
+/// Prefix of lyrics browse IDs.
+const LYRICS_BROWSE_PREFIX: &str = "MPLY";
+
+/// A line of timed lyrics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedLyricsLine {
+    /// Line text.
+    pub text: String,
+    /// Start time in milliseconds.
+    pub start_ms: u64,
+    /// End time in milliseconds.
+    pub end_ms: u64,
+}
+
+/// Lyrics of a track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackLyrics {
+    /// Lines with timing.
+    Timed(Vec<TimedLyricsLine>),
+    /// Text without timing.
+    Plain(String),
+}
+
+impl InnerTubeClient {
+    /// Get the lyrics of a track, or `None` if `YouTube` Music has none.
+    pub async fn get_lyrics(&self, video_id: &str) -> Result<Option<TrackLyrics>> {
+        let Some(browse_id) = self.lyrics_browse_id(video_id).await? else {
+            debug!("No lyrics tab for {video_id}");
+            return Ok(None);
+        };
+
+        let payload = BrowsePayload {
+            browse_id,
+            params: None,
+            continuation: None,
+        };
+        // Only the mobile clients are sent timed lyrics
+        let request = InnerTubeRequest::new(ClientContext::music_android(), payload);
+        let response: RawBrowseResponse = self
+            .post("browse", &request)
+            .await
+            .map_err(|e| Error::InnerTube(format!("Lyrics request failed: {e}")))?;
+
+        let Some(contents) = &response.contents else {
+            return Ok(None);
+        };
+        Ok(parse_timed_lyrics(contents).or_else(|| parse_plain_lyrics(contents)))
+    }
+
+    /// Get the browse ID of a track's lyrics from its watch page.
+    async fn lyrics_browse_id(&self, video_id: &str) -> Result<Option<String>> {
+        let payload = NextPayload {
+            video_id: video_id.to_string(),
+            playlist_id: None,
+            params: None,
+            playlist_set_video_id: None,
+            continuation: None,
+            is_audio_only: Some(true),
+        };
+        let request = InnerTubeRequest::new(self.context.clone(), payload);
+        let response: Value = self
+            .post("next", &request)
+            .await
+            .map_err(|e| Error::InnerTube(format!("Next request failed: {e}")))?;
+
+        let tabs = response
+            .pointer(
+                "/contents/singleColumnMusicWatchNextResultsRenderer/tabbedRenderer\
+                 /watchNextTabbedResultsRenderer/tabs",
+            )
+            .and_then(Value::as_array);
+        Ok(tabs
+            .into_iter()
+            .flatten()
+            .filter_map(|tab| {
+                tab.pointer("/tabRenderer/endpoint/browseEndpoint/browseId")
+                    .and_then(Value::as_str)
+            })
+            .find(|id| id.starts_with(LYRICS_BROWSE_PREFIX))
+            .map(str::to_string))
+    }
+}
+
+/// Parse timed lyrics from the mobile lyrics page.
+fn parse_timed_lyrics(contents: &Value) -> Option<TrackLyrics> {
+    let lines = contents
+        .pointer(
+            "/elementRenderer/newElement/type/componentType/model\
+             /timedLyricsModel/lyricsData/timedLyricsData",
+        )?
+        .as_array()?;
+    // Times are sent as strings of milliseconds
+    let millis = |line: &Value, key: &str| {
+        line.pointer(&format!("/cueRange/{key}"))
+            .and_then(|ms| ms.as_str().and_then(|ms| ms.parse().ok()).or(ms.as_u64()))
+    };
+    let lines: Vec<TimedLyricsLine> = lines
+        .iter()
+        .filter_map(|line| {
+            Some(TimedLyricsLine {
+                text: line.get("lyricLine")?.as_str()?.to_string(),
+                start_ms: millis(line, "startTimeMilliseconds")?,
+                end_ms: millis(line, "endTimeMilliseconds")?,
+            })
+        })
+        .collect();
+    (!lines.is_empty()).then_some(TrackLyrics::Timed(lines))
+}
+
+/// Parse plain lyrics from the description shelf of the lyrics page.
+fn parse_plain_lyrics(contents: &Value) -> Option<TrackLyrics> {
+    contents
+        .pointer("/sectionListRenderer/contents")?
+        .as_array()?
+        .iter()
+        .find_map(|section| {
+            section
+                .pointer("/musicDescriptionShelfRenderer/description/runs/0/text")?
+                .as_str()
+        })
+        .filter(|text| !text.trim().is_empty())
+        .map(|text| TrackLyrics::Plain(text.to_string()))
+}
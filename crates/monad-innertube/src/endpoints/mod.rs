@@ -1,6 +1,7 @@
 //! `InnerTube` API endpoint implementations.
 
 pub mod browse;
+pub mod lyrics;
 pub mod player;
 pub mod search;
 
@@ -1,15 +1,27 @@
 //! Player endpoint implementation for stream URL extraction.
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use monad_core::{
     types::{AudioFormat, AudioQuality, StreamInfo},
     Error, Result, StreamCollection,
 };
+use tracing::{debug, warn};
 
 use crate::{
-    types::{Format, InnerTubeRequest, PlayerPayload, RawPlayerResponse},
+    cipher::{PlayerScript, IFRAME_API_URL},
+    client::CacheEntry,
+    types::{
+        Format, InnerTubeRequest, PlaybackContext, PlayerPayload, RawPlayerResponse,
+        ServiceIntegrityDimensions,
+    },
     InnerTubeClient,
 };
 
+/// How long the player version looked up from the iframe API is used.
+const PLAYER_VERSION_TTL: Duration = Duration::from_secs(60 * 60);
+
 impl InnerTubeClient {
     /// Get stream information for a video.
     ///
@@ -17,13 +29,46 @@ impl InnerTubeClient {
     /// * `video_id` - The `YouTube` video ID
     ///
     /// # Returns
-    /// Collection of available audio streams.
+    /// Collection of available audio streams. Ciphered stream URLs are
+    /// deciphered with the current player script.
     pub async fn get_streams(&self, video_id: &str) -> Result<StreamCollection> {
+        self.get_streams_with_po_token(video_id, None).await
+    }
+
+    /// Get stream information for a video, attesting the request with a PO token.
+    ///
+    /// The token is sent with the player request and appended to every stream
+    /// URL as the `pot` parameter, which `YouTube` requires before serving them.
+    pub async fn get_streams_with_po_token(
+        &self,
+        video_id: &str,
+        po_token: Option<&str>,
+    ) -> Result<StreamCollection> {
+        // Web clients return ciphered URLs tied to the player's signature timestamp
+        let player = if self.context.client.client_name.starts_with("WEB") {
+            match self.player_script().await {
+                Ok(player) => Some(player),
+                Err(e) => {
+                    warn!("Failed to load player script: {e}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         let payload = PlayerPayload {
             video_id: video_id.to_string(),
             playlist_id: None,
             content_check_ok: Some(true),
             racy_check_ok: Some(true),
+            playback_context: player
+                .as_ref()
+                .and_then(|p| p.signature_timestamp)
+                .map(PlaybackContext::with_signature_timestamp),
+            service_integrity_dimensions: po_token.map(|token| ServiceIntegrityDimensions {
+                po_token: token.to_string(),
+            }),
         };
 
         let request = InnerTubeRequest::new(self.context.clone(), payload);
@@ -41,8 +86,24 @@ impl InnerTubeClient {
             }
         }
 
-        // Extract streams
-        let streams = parse_streams(&response)?;
+        // Extract streams, deciphering signatures where needed
+        let mut streams = parse_streams(&response)?;
+        streams.retain_mut(|stream| {
+            let Some(cipher) = &stream.signature_cipher else {
+                return true;
+            };
+            match player.as_ref().map(|p| p.decipher_url(cipher)) {
+                Some(Ok(url)) => {
+                    stream.url = url;
+                    true
+                }
+                Some(Err(e)) => {
+                    debug!("Dropping stream with undecipherable signature: {e}");
+                    false
+                }
+                None => false,
+            }
+        });
 
         if streams.is_empty() {
             return Err(Error::ContentNotAvailable(
@@ -50,6 +111,15 @@ impl InnerTubeClient {
             ));
         }
 
+        if let Some(token) = po_token {
+            for stream in &mut streams {
+                if let Ok(mut url) = url::Url::parse(&stream.url) {
+                    url.query_pairs_mut().append_pair("pot", token);
+                    stream.url = url.into();
+                }
+            }
+        }
+
         Ok(StreamCollection::new(streams))
     }
 
@@ -60,6 +130,8 @@ impl InnerTubeClient {
             playlist_id: None,
             content_check_ok: Some(true),
             racy_check_ok: Some(true),
+            playback_context: None,
+            service_integrity_dimensions: None,
         };
 
         let request = InnerTubeRequest::new(self.context.clone(), payload);
@@ -68,6 +140,39 @@ impl InnerTubeClient {
             .await
             .map_err(|e| Error::InnerTube(format!("Player request failed: {e}")))
     }
+
+    /// Get the current player script.
+    ///
+    /// The player version is looked up at most once an hour, and the script
+    /// of each version is fetched once.
+    pub async fn player_script(&self) -> Result<Arc<PlayerScript>> {
+        let id = self.player_version().await?;
+        let url = PlayerScript::url(&id);
+        if let Some(player) = self.player_scripts.get(&url) {
+            return Ok(Arc::clone(&player));
+        }
+
+        debug!("Fetching player script {id}");
+        let js = self.get_text(&url).await?;
+        let player = Arc::new(PlayerScript::parse(id, &js));
+        self.player_scripts.insert(url, Arc::clone(&player));
+        Ok(player)
+    }
+
+    /// Get the current player version from the iframe API.
+    async fn player_version(&self) -> Result<String> {
+        if let Some(version) = self.player_version.read().as_ref() {
+            if !version.is_expired() {
+                return Ok(version.value.clone());
+            }
+        }
+
+        let iframe_api = self.get_text(IFRAME_API_URL).await?;
+        let id = PlayerScript::id_from_iframe_api(&iframe_api)
+            .ok_or_else(|| Error::Parse("Player version not found in iframe API".to_string()))?;
+        *self.player_version.write() = Some(CacheEntry::new(id.clone(), PLAYER_VERSION_TTL));
+        Ok(id)
+    }
 }
 
 fn parse_streams(response: &RawPlayerResponse) -> Result<Vec<StreamInfo>> {
@@ -121,11 +226,13 @@ fn parse_streams(response: &RawPlayerResponse) -> Result<Vec<StreamInfo>> {
 }
 
 fn parse_format(format: &Format, expires_at: u64) -> Option<StreamInfo> {
-    // Need either URL or signature cipher
-    let url = format.url.clone().or({
-        // If no direct URL, we need signature decryption (handled elsewhere)
-        None
-    })?;
+    // Need either URL or signature cipher (the URL is filled in once deciphered)
+    let signature_cipher = format.signature_cipher.clone().or(format.cipher.clone());
+    let url = match (&format.url, &signature_cipher) {
+        (Some(url), _) => url.clone(),
+        (None, Some(_)) => String::new(),
+        (None, None) => return None,
+    };
 
     let audio_format = AudioFormat::from_mime(&format.mime_type);
     let quality = format.bitrate.map_or(AudioQuality::Medium, |b| {
@@ -139,7 +246,7 @@ fn parse_format(format: &Format, expires_at: u64) -> Option<StreamInfo> {
     stream.channels = format.audio_channels;
     stream.content_length = format.content_length_u64();
     stream.mime_type = Some(format.mime_type.clone());
-    stream.signature_cipher = format.signature_cipher.clone().or(format.cipher.clone());
+    stream.signature_cipher = signature_cipher;
     stream.expires_at = Some(expires_at);
 
     Some(stream)
@@ -217,3 +324,23 @@ pub const KNOWN_AUDIO_ITAGS: &[ItagInfo] = &[
         sample_rate: 22050,
     },
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_player_script_is_cached() -> Result<()> {
+        let client = InnerTubeClient::new()?;
+        let player = Arc::new(PlayerScript::parse("abc123", "var a=1;"));
+        *client.player_version.write() =
+            Some(CacheEntry::new("abc123".to_string(), PLAYER_VERSION_TTL));
+        client
+            .player_scripts
+            .insert(PlayerScript::url("abc123"), Arc::clone(&player));
+
+        // Served without fetching the iframe API or the script again
+        assert!(Arc::ptr_eq(&client.player_script().await?, &player));
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
 //! Search endpoint implementation.
 
 use monad_core::{Error, Result};
+use tokio::sync::mpsc;
 use tracing::debug;
 
 use crate::{
     parser::parse_search_results,
+    search_cache::SearchUpdate,
     types::{InnerTubeRequest, RawSearchResponse, SearchFilter, SearchPayload, SearchResults},
     InnerTubeClient,
 };
@@ -19,6 +21,44 @@ impl InnerTubeClient {
     /// # Returns
     /// Search results containing songs, videos, albums, artists, and playlists.
     pub async fn search(&self, query: &str, filter: SearchFilter) -> Result<SearchResults> {
+        self.fetch_search(query, filter, true).await
+    }
+
+    /// Search, sending cached results of a repeated query while refreshing them.
+    ///
+    /// The receiver yields [`SearchUpdate::Cached`] immediately if the query
+    /// was searched recently, then [`SearchUpdate::Fresh`] (or the error) once
+    /// the search completes.
+    pub fn search_revalidating(
+        &self,
+        query: &str,
+        filter: SearchFilter,
+    ) -> mpsc::UnboundedReceiver<Result<SearchUpdate>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cached = self.search_cache.get(query, filter);
+        let stale = cached.is_some();
+        if let Some(results) = cached {
+            debug!("Showing cached results for {query:?} while refreshing");
+            let _ = tx.send(Ok(SearchUpdate::Cached(results)));
+        }
+
+        let client = self.clone();
+        let query = query.to_string();
+        tokio::spawn(async move {
+            // Stale results are refreshed from the network, not the response cache
+            let result = client.fetch_search(&query, filter, !stale).await;
+            let _ = tx.send(result.map(SearchUpdate::Fresh));
+        });
+        rx
+    }
+
+    /// Run a search and remember its results.
+    async fn fetch_search(
+        &self,
+        query: &str,
+        filter: SearchFilter,
+        use_cache: bool,
+    ) -> Result<SearchResults> {
         let payload = SearchPayload {
             query: query.to_string(),
             params: filter.params().map(String::from),
@@ -28,7 +68,7 @@ impl InnerTubeClient {
         let request = InnerTubeRequest::new(self.context.clone(), payload);
 
         let response: RawSearchResponse = self
-            .post("search", &request)
+            .post_with_cache("search", &request, use_cache)
             .await
             .map_err(|e| Error::InnerTube(format!("Search request failed: {e}")))?;
 
@@ -91,7 +131,9 @@ impl InnerTubeClient {
             }
         }
 
-        Ok(parse_search_results(&response))
+        let results = parse_search_results(&response);
+        self.search_cache.insert(query, filter, results.clone());
+        Ok(results)
     }
 
     /// Continue a search with a continuation token.
@@ -5,12 +5,17 @@
 //! This crate provides a Rust implementation of the `InnerTube` protocol
 //! used by `YouTube` Music for searching, browsing, and retrieving stream URLs.
 
+pub mod cipher;
 pub mod client;
 pub mod context;
 pub mod endpoints;
 pub mod parser;
+pub mod search_cache;
 pub mod types;
 
+pub use cipher::PlayerScript;
 pub use client::InnerTubeClient;
 pub use context::ClientContext;
+pub use endpoints::lyrics::{TimedLyricsLine, TrackLyrics};
+pub use search_cache::{SearchCache, SearchUpdate};
 pub use types::{SearchFilter, SearchResults};
@@ -1,6 +1,7 @@
 //! Response parsers for `InnerTube` API responses.
 
 use monad_core::{
+    format::subscriber_count,
     types::{ArtistPreview, Thumbnail, Thumbnails, TrackAlbum, TrackArtist},
     Album, AlbumType, Duration, Playlist, PlaylistAuthor, Track,
 };
@@ -350,7 +351,7 @@ fn parse_track_from_renderer(renderer: &MusicResponsiveListItemRenderer) -> Opti
             {
                 if let Some(text) = &col_renderer.text {
                     let duration_str = text.text();
-                    track.duration = parse_duration(&duration_str);
+                    track.duration = Duration::parse(&duration_str).unwrap_or_default();
                 }
             }
         }
@@ -469,10 +470,7 @@ fn parse_artist_from_renderer(renderer: &MusicResponsiveListItemRenderer) -> Opt
     if let Some(second_col) = columns.get(1) {
         if let Some(col_renderer) = &second_col.music_responsive_list_item_flex_column_renderer {
             if let Some(text) = &col_renderer.text {
-                let sub_text = text.text();
-                if sub_text.contains("subscriber") {
-                    artist.subscriber_count = Some(sub_text.replace(" subscribers", ""));
-                }
+                artist.subscriber_count = subscriber_count(&text.text());
             }
         }
     }
@@ -606,10 +604,7 @@ fn parse_artist_from_two_row(renderer: &MusicTwoRowItemRenderer) -> Option<Artis
     let mut artist = ArtistPreview::new(browse_id, name);
 
     if let Some(subtitle) = &renderer.subtitle {
-        let sub_text = subtitle.text();
-        if sub_text.contains("subscriber") {
-            artist.subscriber_count = Some(sub_text.replace(" subscribers", ""));
-        }
+        artist.subscriber_count = subscriber_count(&subtitle.text());
     }
 
     artist.thumbnails = parse_thumbnails_from_two_row(&renderer.thumbnail_renderer);
@@ -677,35 +672,3 @@ fn parse_thumbnails_from_renderer(thumb: &Option<ThumbnailRenderer>) -> Thumbnai
 fn parse_thumbnails_from_two_row(thumb: &Option<ThumbnailRenderer>) -> Thumbnails {
     parse_thumbnails_from_renderer(thumb)
 }
-
-/// Parse a duration string like "3:45" or "1:23:45" into Duration.
-fn parse_duration(s: &str) -> Duration {
-    let parts: Vec<&str> = s.split(':').collect();
-    let seconds: u64 = match parts.len() {
-        2 => {
-            let mins: u64 = parts[0].parse().unwrap_or(0);
-            let secs: u64 = parts[1].parse().unwrap_or(0);
-            mins * 60 + secs
-        }
-        3 => {
-            let hours: u64 = parts[0].parse().unwrap_or(0);
-            let mins: u64 = parts[1].parse().unwrap_or(0);
-            let secs: u64 = parts[2].parse().unwrap_or(0);
-            hours * 3600 + mins * 60 + secs
-        }
-        _ => 0,
-    };
-    Duration::from_seconds(seconds)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_duration() {
-        assert_eq!(parse_duration("3:45").as_seconds(), 225);
-        assert_eq!(parse_duration("1:23:45").as_seconds(), 5025);
-        assert_eq!(parse_duration("0:30").as_seconds(), 30);
-    }
-}
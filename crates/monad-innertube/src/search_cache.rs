@@ -0,0 +1,133 @@
+//! Recent search results, for showing repeated searches instantly.
+//!
+//! Results are keyed by filter and normalized query, so `"Daft  Punk"` and
+//! `"daft punk"` share an entry. A repeated search returns the cached results
+//! straight away while fresh ones are fetched (stale-while-revalidate).
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::types::{SearchFilter, SearchResults};
+
+/// Number of searches kept.
+pub const SEARCH_CACHE_SIZE: usize = 50;
+
+/// Normalize a query for caching: trimmed, lowercase, single spaces.
+pub fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Results of a stale-while-revalidate search.
+#[derive(Debug, Clone)]
+pub enum SearchUpdate {
+    /// Results cached from an earlier search, sent immediately.
+    Cached(SearchResults),
+    /// Results fetched for this search.
+    Fresh(SearchResults),
+}
+
+impl SearchUpdate {
+    /// Get the results.
+    pub fn into_results(self) -> SearchResults {
+        match self {
+            Self::Cached(results) | Self::Fresh(results) => results,
+        }
+    }
+
+    /// Check whether these are cached results still being refreshed.
+    pub const fn is_stale(&self) -> bool {
+        matches!(self, Self::Cached(_))
+    }
+}
+
+/// Least recently used cache of search results, shared between clones.
+#[derive(Debug, Clone, Default)]
+pub struct SearchCache {
+    entries: Arc<Mutex<VecDeque<(String, SearchResults)>>>,
+}
+
+impl SearchCache {
+    fn key(query: &str, filter: SearchFilter) -> String {
+        format!("{filter:?}:{}", normalize_query(query))
+    }
+
+    /// Get the cached results of a search, marking it as recently used.
+    pub fn get(&self, query: &str, filter: SearchFilter) -> Option<SearchResults> {
+        let key = Self::key(query, filter);
+        let mut entries = self.entries.lock();
+        let index = entries.iter().position(|(k, _)| *k == key)?;
+        let entry = entries.remove(index)?;
+        let results = entry.1.clone();
+        entries.push_back(entry);
+        Some(results)
+    }
+
+    /// Cache the results of a search, dropping the least recently used once full.
+    pub fn insert(&self, query: &str, filter: SearchFilter, results: SearchResults) {
+        let key = Self::key(query, filter);
+        let mut entries = self.entries.lock();
+        entries.retain(|(k, _)| *k != key);
+        if entries.len() == SEARCH_CACHE_SIZE {
+            entries.pop_front();
+        }
+        entries.push_back((key, results));
+    }
+
+    /// Remove all cached searches.
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    /// Get the number of cached searches.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Check whether no searches are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(continuation: &str) -> SearchResults {
+        SearchResults {
+            continuation: Some(continuation.to_string()),
+            ..SearchResults::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_query() {
+        assert_eq!(normalize_query("  Daft   Punk "), "daft punk");
+        assert_eq!(normalize_query(""), "");
+    }
+
+    #[test]
+    fn test_search_cache() {
+        let cache = SearchCache::default();
+        cache.insert("Daft Punk", SearchFilter::All, results("a"));
+
+        let cached = cache.get("daft  punk", SearchFilter::All);
+        assert_eq!(cached.and_then(|r| r.continuation).as_deref(), Some("a"));
+        assert!(cache.get("Daft Punk", SearchFilter::Songs).is_none());
+
+        for i in 0..SEARCH_CACHE_SIZE {
+            // Keep the first search recently used while others fill the cache
+            cache.get("daft punk", SearchFilter::All);
+            cache.insert(&format!("query {i}"), SearchFilter::All, results("b"));
+        }
+        assert_eq!(cache.len(), SEARCH_CACHE_SIZE);
+        assert!(cache.get("daft punk", SearchFilter::All).is_some());
+        assert!(cache.get("query 0", SearchFilter::All).is_none());
+    }
+}
@@ -44,7 +44,7 @@ pub struct SearchResults {
 }
 
 impl SearchResults {
-    pub const fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.songs.is_empty()
             && self.videos.is_empty()
             && self.albums.is_empty()
@@ -99,6 +99,41 @@ pub struct PlayerPayload {
     pub content_check_ok: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub racy_check_ok: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playback_context: Option<PlaybackContext>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_integrity_dimensions: Option<ServiceIntegrityDimensions>,
+}
+
+/// Playback context sent with player requests.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackContext {
+    pub content_playback_context: ContentPlaybackContext,
+}
+
+impl PlaybackContext {
+    /// Create a playback context for a player script's signature timestamp.
+    pub const fn with_signature_timestamp(signature_timestamp: u32) -> Self {
+        Self {
+            content_playback_context: ContentPlaybackContext {
+                signature_timestamp,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentPlaybackContext {
+    pub signature_timestamp: u32,
+}
+
+/// Proof-of-origin token sent with player requests.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceIntegrityDimensions {
+    pub po_token: String,
 }
 
 /// Next (queue/related) request payload.
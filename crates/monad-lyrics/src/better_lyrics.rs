@@ -0,0 +1,134 @@
+//! The Better Lyrics API, serving word-synced TTML lyrics.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::provider::{LyricsProvider, LyricsQuery};
+use crate::{
+    http_client, parser, rate_limit_error, urlencoding, Lyrics, LyricsError, DEFAULT_USER_AGENT,
+};
+
+const API_BASE_URL: &str = "https://lyrics-api.boidu.dev";
+
+/// API response containing TTML lyrics.
+#[derive(Debug, Deserialize)]
+struct TtmlResponse {
+    ttml: String,
+}
+
+/// Lyrics from the Better Lyrics API.
+#[derive(Debug, Clone)]
+pub struct BetterLyrics {
+    client: Client,
+    name: String,
+    base_url: String,
+}
+
+impl Default for BetterLyrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BetterLyrics {
+    /// Create a provider for the public Better Lyrics API.
+    pub fn new() -> Self {
+        Self::with_base_url("Better Lyrics", API_BASE_URL)
+    }
+
+    /// Create a provider for a mirror of the Better Lyrics API at `base_url`.
+    pub fn with_base_url(name: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self {
+            client: http_client(DEFAULT_USER_AGENT),
+            name: name.into(),
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for BetterLyrics {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+        let LyricsQuery {
+            artist,
+            song,
+            album,
+            duration,
+            ..
+        } = *query;
+        let mut url = format!(
+            "{}/getLyrics?a={}&s={}",
+            self.base_url,
+            urlencoding::encode(artist),
+            urlencoding::encode(song)
+        );
+
+        if let Some(album) = album {
+            use std::fmt::Write;
+            let _ = write!(url, "&al={}", urlencoding::encode(album));
+        }
+
+        if let Some(dur) = duration {
+            use std::fmt::Write;
+            let _ = write!(url, "&d={dur}");
+        }
+
+        debug!("Requesting: {}", url);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| LyricsError::ProviderUnavailable(e.to_string()))?;
+
+        if let Some(e) = rate_limit_error(&response) {
+            return Err(e);
+        }
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(LyricsError::NotFound(format!(
+                "No lyrics for {artist} - {song}"
+            )));
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(LyricsError::ProviderUnavailable(format!(
+                "Lyrics API returned {}: {}",
+                status, body
+            )));
+        }
+
+        let ttml_response: TtmlResponse = response
+            .json()
+            .await
+            .map_err(|e| LyricsError::ParseFailed(e.to_string()))?;
+
+        let lyrics = parser::parse_ttml(&ttml_response.ttml, artist, song)?;
+
+        info!(
+            "Fetched {} lyric lines for {} - {}",
+            lyrics.lines.len(),
+            artist,
+            song
+        );
+
+        Ok(lyrics)
+    }
+
+    fn with_user_agent(&self, user_agent: &str) -> Option<Arc<dyn LyricsProvider>> {
+        Some(Arc::new(Self {
+            client: http_client(user_agent),
+            ..self.clone()
+        }))
+    }
+}
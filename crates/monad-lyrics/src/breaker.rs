@@ -0,0 +1,208 @@
+//! Circuit breaker guarding a lyrics provider.
+//!
+//! After `failure_threshold` consecutive failures the breaker opens and
+//! requests are refused without touching the network. Once `cooldown` has
+//! passed, a single probe request is let through (half-open): success closes
+//! the breaker again, failure reopens it for another cooldown.
+
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+/// Default number of consecutive failures before a provider is skipped.
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// Default time a provider is skipped before it is probed again.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// State of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Requests flow normally.
+    Closed,
+    /// The provider is failing; requests are refused.
+    Open,
+    /// The cooldown has passed; a probe request decides the next state.
+    HalfOpen,
+}
+
+impl BreakerState {
+    /// Get the display name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Closed => "OK",
+            Self::Open => "Down",
+            Self::HalfOpen => "Probing",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// Consecutive-failure circuit breaker.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker.
+    pub const fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    /// Create a closed breaker with the same threshold and cooldown.
+    pub const fn renewed(&self) -> Self {
+        Self::new(self.failure_threshold, self.cooldown)
+    }
+
+    /// Get the current state.
+    pub fn state(&self) -> BreakerState {
+        self.state_at(Instant::now())
+    }
+
+    /// Get the number of consecutive failures.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.inner.lock().consecutive_failures
+    }
+
+    /// Check whether a request may be sent, claiming the probe if half-open.
+    pub fn allow_request(&self) -> bool {
+        self.allow_request_at(Instant::now())
+    }
+
+    /// Record a successful request.
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock();
+        if inner.state != BreakerState::Closed {
+            info!("Lyrics provider recovered, closing circuit");
+        }
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_in_flight = false;
+    }
+
+    /// Record a failed request.
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn state_at(&self, now: Instant) -> BreakerState {
+        let inner = self.inner.lock();
+        match inner.state {
+            BreakerState::Open if self.cooldown_elapsed(&inner, now) => BreakerState::HalfOpen,
+            state => state,
+        }
+    }
+
+    fn allow_request_at(&self, now: Instant) -> bool {
+        let mut inner = self.inner.lock();
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::Open if self.cooldown_elapsed(&inner, now) => {
+                inner.state = BreakerState::HalfOpen;
+                inner.probe_in_flight = true;
+                true
+            }
+            BreakerState::Open => false,
+            BreakerState::HalfOpen if inner.probe_in_flight => false,
+            BreakerState::HalfOpen => {
+                inner.probe_in_flight = true;
+                true
+            }
+        }
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut inner = self.inner.lock();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        inner.probe_in_flight = false;
+
+        let reopen = inner.state == BreakerState::HalfOpen;
+        if reopen || inner.consecutive_failures >= self.failure_threshold {
+            if inner.state == BreakerState::Closed {
+                warn!(
+                    "Lyrics provider failed {} times in a row, opening circuit",
+                    inner.consecutive_failures
+                );
+            }
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(now);
+        }
+    }
+
+    fn cooldown_elapsed(&self, inner: &Inner, now: Instant) -> bool {
+        inner
+            .opened_at
+            .is_some_and(|opened| now.duration_since(opened) >= self.cooldown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        breaker.record_success();
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        assert!(breaker.allow_request_at(now));
+
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.state_at(now), BreakerState::Open);
+        assert!(!breaker.allow_request_at(now + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_half_open_allows_single_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let start = Instant::now();
+        breaker.record_failure_at(start);
+
+        let later = start + Duration::from_secs(60);
+        assert_eq!(breaker.state_at(later), BreakerState::HalfOpen);
+        assert!(breaker.allow_request_at(later));
+        assert!(!breaker.allow_request_at(later));
+
+        // A failed probe reopens for another full cooldown
+        breaker.record_failure_at(later);
+        assert!(!breaker.allow_request_at(later + Duration::from_secs(59)));
+
+        let probe = later + Duration::from_secs(60);
+        assert!(breaker.allow_request_at(probe));
+        breaker.record_success();
+        assert_eq!(breaker.state_at(probe), BreakerState::Closed);
+        assert_eq!(breaker.consecutive_failures(), 0);
+    }
+}
@@ -0,0 +1,126 @@
+//! Error type for lyrics lookups.
+//!
+//! Callers react differently to each case: a song no provider has is not
+//! worth asking for again soon, a rate limit passes after a while, and an
+//! unavailable provider may be back by the next track. Errors are cheap to
+//! clone, so a fetch shared between callers can hand each its own copy.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+/// Error fetching, parsing or saving lyrics.
+#[derive(Error, Debug, Clone)]
+pub enum LyricsError {
+    /// No lyrics were found for the song.
+    #[error("Lyrics not found: {0}")]
+    NotFound(String),
+
+    /// A provider asked for fewer requests, for `retry_after` if it said.
+    #[error("Rate limited, retry after {retry_after:?}")]
+    RateLimited { retry_after: Option<Duration> },
+
+    /// A provider could not be reached, timed out or refused the request.
+    #[error("Lyrics provider unavailable: {0}")]
+    ProviderUnavailable(String),
+
+    /// A provider's response or a lyrics file could not be understood.
+    #[error("Failed to parse lyrics: {0}")]
+    ParseFailed(String),
+
+    /// The fetch was cancelled before it finished.
+    #[error("Lyrics fetch cancelled")]
+    Cancelled,
+
+    /// Reading or writing a lyrics file failed.
+    #[error("IO error: {0}")]
+    Io(Arc<io::Error>),
+}
+
+impl LyricsError {
+    /// Returns true if asking the provider again may succeed.
+    pub const fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::ProviderUnavailable(_) | Self::RateLimited { .. }
+        )
+    }
+
+    /// Returns true if the provider itself is unhealthy, rather than just
+    /// without the song.
+    pub const fn is_provider_failure(&self) -> bool {
+        matches!(self, Self::ProviderUnavailable(_) | Self::ParseFailed(_))
+    }
+}
+
+impl From<io::Error> for LyricsError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(Arc::new(error))
+    }
+}
+
+impl From<serde_json::Error> for LyricsError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::ParseFailed(error.to_string())
+    }
+}
+
+impl From<reqwest::Error> for LyricsError {
+    fn from(error: reqwest::Error) -> Self {
+        Self::ProviderUnavailable(error.to_string())
+    }
+}
+
+impl From<monad_core::Error> for LyricsError {
+    fn from(error: monad_core::Error) -> Self {
+        use monad_core::Error;
+        match error {
+            Error::ContentNotAvailable(message) => Self::NotFound(message),
+            Error::RateLimited { retry_after_secs } => Self::RateLimited {
+                retry_after: retry_after_secs.map(Duration::from_secs),
+            },
+            Error::Parse(message) | Error::ParseError(message) => Self::ParseFailed(message),
+            Error::Json(e) => e.into(),
+            Error::Io(e) => e.into(),
+            Error::Cancelled => Self::Cancelled,
+            error => Self::ProviderUnavailable(error.to_string()),
+        }
+    }
+}
+
+impl From<LyricsError> for monad_core::Error {
+    fn from(error: LyricsError) -> Self {
+        match error {
+            LyricsError::NotFound(message) => Self::ContentNotAvailable(message),
+            LyricsError::RateLimited { retry_after } => Self::RateLimited {
+                retry_after_secs: retry_after.map(|delay| delay.as_secs()),
+            },
+            LyricsError::ProviderUnavailable(message) => Self::Network(message),
+            LyricsError::ParseFailed(message) => Self::Parse(message),
+            LyricsError::Cancelled => Self::Cancelled,
+            LyricsError::Io(e) => Self::Io(io::Error::new(e.kind(), e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_errors_convert() {
+        let error = LyricsError::from(monad_core::Error::ContentNotAvailable("Song".into()));
+        assert!(matches!(error, LyricsError::NotFound(_)));
+        let error = LyricsError::from(monad_core::Error::RateLimited {
+            retry_after_secs: Some(30),
+        });
+        assert!(matches!(
+            error,
+            LyricsError::RateLimited { retry_after: Some(delay) } if delay.as_secs() == 30
+        ));
+        assert!(LyricsError::from(monad_core::Error::InnerTube("Down".into())).is_retryable());
+        assert!(!LyricsError::NotFound("Song".into()).is_provider_failure());
+    }
+}
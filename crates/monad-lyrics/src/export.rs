@@ -0,0 +1,274 @@
+//! Saving lyrics to files.
+//!
+//! Lyrics can be exported as LRC or TTML for other players, or as plain
+//! text. The offset set for the lyrics is applied to the saved timings, so
+//! the file stays in sync with the audio as it was shown. LRC keeps line
+//! timings only; TTML also keeps word timings.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use quick_xml::escape::escape;
+use tracing::info;
+
+use crate::{LyricLine, Lyrics, LyricsError};
+
+/// File format to save lyrics in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricsFormat {
+    /// Line-synced LRC, such as `[01:02.50] text`.
+    Lrc,
+    /// Timed Text Markup Language, with word timings if there are any.
+    Ttml,
+    /// Plain text without timings.
+    Text,
+}
+
+impl LyricsFormat {
+    /// Get the file extension of the format.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Lrc => "lrc",
+            Self::Ttml => "ttml",
+            Self::Text => "txt",
+        }
+    }
+
+    /// Get the format a path's extension stands for.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "lrc" => Some(Self::Lrc),
+            "ttml" | "xml" => Some(Self::Ttml),
+            "txt" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+/// Format seconds as an LRC timestamp, such as `01:02.50`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn lrc_time(seconds: f64) -> String {
+    let centis = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// Format seconds as a TTML clock time, such as `1:02.500`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn ttml_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Write the words of a line as TTML spans, keeping the spaces between them.
+fn write_spans(ttml: &mut String, line: &LyricLine, shift: f64) {
+    let mut rest = line.text.as_str();
+    for word in &line.words {
+        let trimmed = rest.trim_start();
+        if trimmed.len() < rest.len() {
+            ttml.push(' ');
+        }
+        rest = trimmed.strip_prefix(word.text.as_str()).unwrap_or(trimmed);
+        let _ = write!(
+            ttml,
+            r#"<span begin="{}" end="{}">{}</span>"#,
+            ttml_time(word.start + shift),
+            ttml_time(word.end + shift),
+            escape(word.text.as_str())
+        );
+    }
+}
+
+impl Lyrics {
+    /// Get the offset in seconds to add to every timing when saving.
+    #[allow(clippy::cast_precision_loss)]
+    fn saved_shift(&self) -> f64 {
+        self.offset_ms as f64 / 1000.0
+    }
+
+    /// Format the lyrics as LRC, with artist and title tags.
+    ///
+    /// Untimed lyrics are written as lines without timestamps.
+    pub fn to_lrc(&self) -> String {
+        let mut lrc = format!("[ar:{}]\n[ti:{}]\n", self.artist, self.title);
+        if let Some(duration) = self.duration {
+            let length = lrc_time(duration);
+            let _ = writeln!(lrc, "[length:{}]", &length[..length.len() - 3]);
+        }
+        let shift = self.saved_shift();
+        for line in &self.lines {
+            if self.is_synced() {
+                let _ = write!(lrc, "[{}]", lrc_time(line.start + shift));
+            }
+            let _ = writeln!(lrc, "{}", line.text);
+        }
+        lrc
+    }
+
+    /// Format the lyrics as TTML, with word timings if the lines have them.
+    pub fn to_ttml(&self) -> String {
+        let shift = self.saved_shift();
+        let timing = if !self.is_synced() {
+            "None"
+        } else if self.lines.iter().any(|line| !line.words.is_empty()) {
+            "Word"
+        } else {
+            "Line"
+        };
+        let lang = self
+            .language
+            .as_ref()
+            .map(|language| format!(r#" xml:lang="{}""#, escape(language.as_str())))
+            .unwrap_or_default();
+        let mut ttml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = write!(
+            ttml,
+            r#"
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata" xmlns:itunes="http://music.apple.com/lyric-ttml-internal" itunes:timing="{timing}"{lang}>
+  <head>
+    <metadata>
+      <ttm:title>{}</ttm:title>
+      <ttm:agent type="person"><ttm:name>{}</ttm:name></ttm:agent>
+    </metadata>
+  </head>
+  <body"#,
+            escape(self.title.as_str()),
+            escape(self.artist.as_str())
+        );
+        if let Some(duration) = self.duration {
+            let _ = write!(ttml, r#" dur="{}""#, ttml_time(duration));
+        }
+        ttml.push_str(">\n    <div>\n");
+        for line in &self.lines {
+            ttml.push_str("      <p");
+            if self.is_synced() {
+                let _ = write!(
+                    ttml,
+                    r#" begin="{}" end="{}""#,
+                    ttml_time(line.start + shift),
+                    ttml_time(line.end + shift)
+                );
+            }
+            ttml.push('>');
+            if self.is_synced() && !line.words.is_empty() {
+                write_spans(&mut ttml, line, shift);
+            } else {
+                ttml.push_str(&escape(line.text.as_str()));
+            }
+            ttml.push_str("</p>\n");
+        }
+        ttml.push_str("    </div>\n  </body>\n</tt>\n");
+        ttml
+    }
+
+    /// Format the lyrics in a file format.
+    pub fn to_format(&self, format: LyricsFormat) -> String {
+        match format {
+            LyricsFormat::Lrc => self.to_lrc(),
+            LyricsFormat::Ttml => self.to_ttml(),
+            LyricsFormat::Text => {
+                let mut text = self.plain_text();
+                text.push('\n');
+                text
+            }
+        }
+    }
+
+    /// Save the lyrics to a file, for other players or to keep alongside
+    /// downloaded audio.
+    pub async fn save(
+        &self,
+        path: impl AsRef<Path>,
+        format: LyricsFormat,
+    ) -> Result<(), LyricsError> {
+        let path = path.as_ref();
+        tokio::fs::write(path, self.to_format(format)).await?;
+        info!(
+            "Saved lyrics for {} - {} to {}",
+            self.artist,
+            self.title,
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser, LyricWord};
+
+    fn lyrics() -> Lyrics {
+        let mut lyrics = parser::parse_lrc(
+            "[00:01.00] Hello world\n[01:02.50] Rock & roll",
+            "Artist",
+            "Song",
+            Some(90.0),
+        );
+        lyrics.lines[0].words = vec![
+            LyricWord {
+                text: "Hello".to_string(),
+                start: 1.0,
+                end: 1.5,
+            },
+            LyricWord {
+                text: "world".to_string(),
+                start: 1.5,
+                end: 2.0,
+            },
+        ];
+        lyrics.with_offset_ms(500)
+    }
+
+    #[tokio::test]
+    async fn test_saved_lyrics_read_back() -> Result<(), LyricsError> {
+        let lyrics = lyrics();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("song.lrc");
+        lyrics
+            .save(
+                &path,
+                LyricsFormat::from_path(&path).unwrap_or(LyricsFormat::Text),
+            )
+            .await?;
+        let lrc = std::fs::read_to_string(&path)?;
+        assert!(lrc.starts_with("[ar:Artist]\n[ti:Song]\n[length:01:30]\n"));
+        let read = parser::parse_lrc(&lrc, "Artist", "Song", Some(90.0));
+        assert_eq!(read.lines.len(), 2);
+        // The offset is applied to the saved timings
+        assert!((read.lines[1].start - 63.0).abs() < 0.001);
+        assert_eq!(read.lines[1].text, "Rock & roll");
+
+        let read = parser::parse_ttml(&lyrics.to_ttml(), "Artist", "Song")?;
+        assert_eq!(read.lines[0].text, "Hello world");
+        assert_eq!(read.lines[0].words.len(), 2);
+        assert!((read.lines[0].words[1].start - 2.0).abs() < 0.001);
+        assert_eq!(read.lines[1].text, "Rock & roll");
+        assert_eq!(read.duration, Some(90.0));
+
+        assert_eq!(
+            lyrics.to_format(LyricsFormat::Text),
+            "Hello world\nRock & roll\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_untimed_lyrics_have_no_timestamps() {
+        let lyrics = Lyrics::plain("Artist", "Song", None, "Hello\nWorld");
+        assert_eq!(lyrics.to_lrc(), "[ar:Artist]\n[ti:Song]\nHello\nWorld\n");
+        assert!(lyrics.to_ttml().contains("<p>Hello</p>"));
+        assert_eq!(lrc_time(59.999), "01:00.00");
+        assert_eq!(ttml_time(62.5), "1:02.500");
+    }
+}
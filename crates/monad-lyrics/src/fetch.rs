@@ -0,0 +1,353 @@
+//! Lyrics fetches running in the background.
+//!
+//! Skipping through tracks starts a fetch per track, and a slow fetch for a
+//! track no longer playing could finish after the current one. Identical
+//! fetches started while one is running share it, and a fetch is aborted
+//! once every handle to it is cancelled or dropped. Lyrics of the next track
+//! can be prefetched, so fetching them once it plays finishes at once.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+use tracing::debug;
+
+use crate::{Lyrics, LyricsClient, LyricsError, LyricsQuery};
+
+/// Number of prefetched lyrics kept until they are asked for.
+const PREFETCH_CAPACITY: usize = 8;
+
+/// Result of a fetch, once it has finished.
+type Outcome = Option<Arc<Result<Lyrics, LyricsError>>>;
+
+/// A running fetch.
+struct Request {
+    /// Tells the request apart from later ones for the same query.
+    id: u64,
+    outcome: watch::Receiver<Outcome>,
+    task: AbortHandle,
+    /// Handles waiting for the result.
+    handles: usize,
+}
+
+/// Fetches in flight, by query.
+#[derive(Default)]
+pub struct InFlight {
+    requests: Mutex<HashMap<String, Request>>,
+    next_id: AtomicU64,
+    /// Prefetched lyrics not asked for yet, by query, oldest first.
+    prefetched: Mutex<VecDeque<(String, Lyrics)>>,
+}
+
+impl InFlight {
+    /// Forget a finished fetch.
+    fn finish(&self, key: &str, id: u64) {
+        let mut requests = self.requests.lock();
+        if requests.get(key).is_some_and(|request| request.id == id) {
+            requests.remove(key);
+        }
+    }
+
+    /// Keep prefetched lyrics, forgetting the oldest beyond capacity.
+    fn keep(&self, key: String, lyrics: Lyrics) {
+        let mut prefetched = self.prefetched.lock();
+        prefetched.retain(|(other, _)| *other != key);
+        if prefetched.len() >= PREFETCH_CAPACITY {
+            prefetched.pop_front();
+        }
+        prefetched.push_back((key, lyrics));
+    }
+
+    /// Take the prefetched lyrics of a query.
+    fn take(&self, key: &str) -> Option<Lyrics> {
+        let mut prefetched = self.prefetched.lock();
+        let index = prefetched.iter().position(|(other, _)| other == key)?;
+        prefetched.remove(index).map(|(_, lyrics)| lyrics)
+    }
+
+    /// Drop a handle to a fetch, aborting it if it was the last.
+    fn release(&self, key: &str, id: u64) {
+        let mut requests = self.requests.lock();
+        let Some(request) = requests.get_mut(key) else {
+            return;
+        };
+        if request.id != id {
+            return;
+        }
+        request.handles -= 1;
+        if request.handles == 0 {
+            debug!("Cancelling lyrics fetch no longer waited for");
+            request.task.abort();
+            requests.remove(key);
+        }
+    }
+}
+
+/// A query owning its strings, to move into a task.
+struct OwnedQuery {
+    artist: String,
+    song: String,
+    album: Option<String>,
+    duration: Option<f64>,
+    video_id: Option<String>,
+    translation_language: Option<String>,
+    path: Option<PathBuf>,
+}
+
+impl OwnedQuery {
+    fn new(query: &LyricsQuery<'_>) -> Self {
+        Self {
+            artist: query.artist.to_string(),
+            song: query.song.to_string(),
+            album: query.album.map(str::to_string),
+            duration: query.duration,
+            video_id: query.video_id.map(str::to_string),
+            translation_language: query.translation_language.map(str::to_string),
+            path: query.path.map(PathBuf::from),
+        }
+    }
+
+    fn as_query(&self) -> LyricsQuery<'_> {
+        LyricsQuery {
+            artist: &self.artist,
+            song: &self.song,
+            album: self.album.as_deref(),
+            duration: self.duration,
+            video_id: self.video_id.as_deref(),
+            translation_language: self.translation_language.as_deref(),
+            path: self.path.as_deref(),
+        }
+    }
+}
+
+/// Handle to a lyrics fetch running in the background.
+///
+/// Dropping the handle cancels the fetch, unless other handles share it.
+pub struct LyricsFetch {
+    key: String,
+    id: u64,
+    outcome: watch::Receiver<Outcome>,
+    in_flight: Arc<InFlight>,
+}
+
+impl LyricsFetch {
+    /// Wait for the lyrics.
+    pub async fn result(mut self) -> Result<Lyrics, LyricsError> {
+        let outcome = self
+            .outcome
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| LyricsError::Cancelled)?
+            .clone();
+        match outcome.as_deref() {
+            Some(Ok(lyrics)) => Ok(lyrics.clone()),
+            Some(Err(e)) => Err(e.clone()),
+            None => Err(LyricsError::Cancelled),
+        }
+    }
+
+    /// Cancel the fetch, unless other handles share it.
+    pub fn cancel(self) {
+        drop(self);
+    }
+}
+
+impl Drop for LyricsFetch {
+    fn drop(&mut self) {
+        self.in_flight.release(&self.key, self.id);
+    }
+}
+
+impl LyricsClient {
+    /// Start fetching lyrics in the background, joining an identical fetch
+    /// that is already running.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn start_fetch(&self, query: &LyricsQuery<'_>) -> LyricsFetch {
+        let key = format!("{query:?}");
+        if let Some(lyrics) = self.in_flight.take(&key) {
+            debug!(
+                "Using prefetched lyrics for {} - {}",
+                query.artist, query.song
+            );
+            let (_, outcome) = watch::channel(Some(Arc::new(Ok(lyrics))));
+            return LyricsFetch {
+                key,
+                id: self.in_flight.next_id.fetch_add(1, Ordering::Relaxed),
+                outcome,
+                in_flight: self.in_flight.clone(),
+            };
+        }
+
+        let mut requests = self.in_flight.requests.lock();
+        if let Some(request) = requests.get_mut(&key) {
+            debug!(
+                "Joining lyrics fetch in flight for {} - {}",
+                query.artist, query.song
+            );
+            request.handles += 1;
+            return LyricsFetch {
+                key,
+                id: request.id,
+                outcome: request.outcome.clone(),
+                in_flight: self.in_flight.clone(),
+            };
+        }
+
+        let id = self.in_flight.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, outcome) = watch::channel(None);
+        let client = self.clone();
+        let query = OwnedQuery::new(query);
+        let task_key = key.clone();
+        let task = tokio::spawn(async move {
+            let result = client.fetch_query(&query.as_query()).await;
+            client.in_flight.finish(&task_key, id);
+            sender.send_replace(Some(Arc::new(result)));
+        });
+        requests.insert(
+            key.clone(),
+            Request {
+                id,
+                outcome: outcome.clone(),
+                task: task.abort_handle(),
+                handles: 1,
+            },
+        );
+        LyricsFetch {
+            key,
+            id,
+            outcome,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Fetch lyrics in the background for a later [`start_fetch`] of the same
+    /// query, such as for the next track in a queue.
+    ///
+    /// The fetch runs to the end without a handle, and its lyrics are kept
+    /// until asked for. Queries already being fetched are left alone. Must be
+    /// called within a Tokio runtime.
+    ///
+    /// [`start_fetch`]: Self::start_fetch
+    pub fn prefetch(&self, query: &LyricsQuery<'_>) {
+        let key = format!("{query:?}");
+        let prefetched = self
+            .in_flight
+            .prefetched
+            .lock()
+            .iter()
+            .any(|(other, _)| *other == key);
+        if prefetched || self.in_flight.requests.lock().contains_key(&key) {
+            return;
+        }
+
+        debug!("Prefetching lyrics for {} - {}", query.artist, query.song);
+        let fetch = self.start_fetch(query);
+        let in_flight = self.in_flight.clone();
+        tokio::spawn(async move {
+            match fetch.result().await {
+                Ok(lyrics) => in_flight.keep(key, lyrics),
+                Err(e) => debug!("Prefetching lyrics failed: {e}"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LyricsKind, LyricsProvider, DEFAULT_TIMEOUT};
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    /// A provider counting the fetches it started and finished.
+    #[derive(Default)]
+    struct CountingProvider {
+        started: Arc<AtomicUsize>,
+        finished: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LyricsProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "Counting"
+        }
+
+        async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            Ok(Lyrics {
+                kind: LyricsKind::Synced,
+                ..Lyrics::plain(query.artist, query.song, None, "Hello")
+            })
+        }
+    }
+
+    fn client() -> (LyricsClient, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let provider = CountingProvider::default();
+        let (started, finished) = (provider.started.clone(), provider.finished.clone());
+        let client = LyricsClient::empty().with_provider(provider, DEFAULT_TIMEOUT);
+        (client, started, finished)
+    }
+
+    #[tokio::test]
+    async fn test_identical_fetches_are_shared() -> Result<(), LyricsError> {
+        let (client, started, _) = client();
+        let query = LyricsQuery::new("Artist", "Song");
+        let first = client.start_fetch(&query);
+        let second = client.start_fetch(&query);
+        let other = client.start_fetch(&LyricsQuery::new("Artist", "Other"));
+
+        assert_eq!(first.result().await?.title, "Song");
+        assert_eq!(second.result().await?.title, "Song");
+        assert_eq!(other.result().await?.title, "Other");
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+
+        // Finished fetches are not reused
+        client.start_fetch(&query).result().await?;
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cancelled_with_last_handle() -> Result<(), LyricsError> {
+        let (client, started, finished) = client();
+        let query = LyricsQuery::new("Artist", "Song");
+        let first = client.start_fetch(&query);
+        let second = client.start_fetch(&query);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // Still waited for by the second handle
+        first.cancel();
+        assert_eq!(second.result().await?.title, "Song");
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+
+        client.start_fetch(&query).cancel();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetched_lyrics_are_used_once() -> Result<(), LyricsError> {
+        let (client, started, finished) = client();
+        let query = LyricsQuery::new("Artist", "Next");
+        client.prefetch(&query);
+        client.prefetch(&query);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+
+        assert_eq!(client.start_fetch(&query).result().await?.title, "Next");
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        client.start_fetch(&query).result().await?;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+}
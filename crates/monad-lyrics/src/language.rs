@@ -0,0 +1,201 @@
+//! Languages and scripts of lyrics.
+//!
+//! A provider can have a song's lyrics in several versions, like the
+//! original Japanese and a romaji transcription. Providers rarely say which
+//! language a version is in, so versions are told apart by the script their
+//! lines are written in, and matched to preferred languages by the script
+//! each language is written in.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Lyrics;
+
+/// Writing system lyrics are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Script {
+    /// Latin letters, including romanized versions of other scripts.
+    Latin,
+    /// Greek.
+    Greek,
+    /// Cyrillic, as in Russian or Ukrainian.
+    Cyrillic,
+    /// Hebrew.
+    Hebrew,
+    /// Arabic, as in Arabic or Persian.
+    Arabic,
+    /// Devanagari, as in Hindi.
+    Devanagari,
+    /// Thai.
+    Thai,
+    /// Korean Hangul.
+    Hangul,
+    /// Japanese kana, with or without kanji.
+    Japanese,
+    /// Chinese characters without kana.
+    Han,
+}
+
+impl Script {
+    /// Get the script a character belongs to, if it is a letter of one.
+    const fn of_char(c: char) -> Option<Self> {
+        match c {
+            'A'..='Z' | 'a'..='z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => {
+                Some(Self::Latin)
+            }
+            '\u{0370}'..='\u{03FF}' => Some(Self::Greek),
+            '\u{0400}'..='\u{04FF}' => Some(Self::Cyrillic),
+            '\u{0590}'..='\u{05FF}' => Some(Self::Hebrew),
+            '\u{0600}'..='\u{06FF}' => Some(Self::Arabic),
+            '\u{0900}'..='\u{097F}' => Some(Self::Devanagari),
+            '\u{0E00}'..='\u{0E7F}' => Some(Self::Thai),
+            '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => {
+                Some(Self::Hangul)
+            }
+            '\u{3040}'..='\u{30FF}' | '\u{FF66}'..='\u{FF9D}' => Some(Self::Japanese),
+            '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => Some(Self::Han),
+            _ => None,
+        }
+    }
+
+    /// Detect the script most letters of a text are written in.
+    ///
+    /// Chinese characters count as Japanese in text that also has kana.
+    pub fn detect(text: &str) -> Option<Self> {
+        let mut counts: Vec<(Self, usize)> = Vec::new();
+        for script in text.chars().filter_map(Self::of_char) {
+            match counts.iter_mut().find(|(s, _)| *s == script) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((script, 1)),
+            }
+        }
+        let count = |script: Self| {
+            counts
+                .iter()
+                .find(|(s, _)| *s == script)
+                .map_or(0, |(_, count)| *count)
+        };
+        let kanji = count(Self::Han);
+        if count(Self::Japanese) > 0 {
+            counts.retain(|(s, _)| *s != Self::Han);
+            if let Some((_, kana)) = counts.iter_mut().find(|(s, _)| *s == Self::Japanese) {
+                *kana += kanji;
+            }
+        }
+        // The first of equally common scripts wins
+        counts
+            .iter()
+            .fold(
+                None,
+                |best: Option<(Self, usize)>, &(script, count)| match best {
+                    Some((_, best_count)) if best_count >= count => best,
+                    _ => Some((script, count)),
+                },
+            )
+            .map(|(script, _)| script)
+    }
+
+    /// Get the script a language is usually written in, by code like `ja`
+    /// or `pt-BR`.
+    pub fn of_language(code: &str) -> Self {
+        let primary = code.split(['-', '_']).next().unwrap_or(code);
+        match primary.to_ascii_lowercase().as_str() {
+            "ja" => Self::Japanese,
+            "ko" => Self::Hangul,
+            "zh" | "yue" => Self::Han,
+            "ru" | "uk" | "be" | "bg" | "mk" | "sr" | "kk" | "ky" | "mn" | "tg" => Self::Cyrillic,
+            "el" => Self::Greek,
+            "he" | "yi" => Self::Hebrew,
+            "ar" | "fa" | "ur" | "ps" => Self::Arabic,
+            "hi" | "mr" | "ne" | "sa" => Self::Devanagari,
+            "th" => Self::Thai,
+            _ => Self::Latin,
+        }
+    }
+}
+
+/// Whether two language codes name the same language, ignoring regions.
+fn same_language(a: &str, b: &str) -> bool {
+    let primary = |code: &str| code.split(['-', '_']).next().unwrap_or(code).to_string();
+    primary(a).eq_ignore_ascii_case(&primary(b))
+}
+
+impl Lyrics {
+    /// Detect the script the lines are written in.
+    pub fn script(&self) -> Option<Script> {
+        Script::detect(&self.plain_text())
+    }
+
+    /// Whether the lyrics are in a language, by code like `en`.
+    ///
+    /// Lyrics of unknown language match languages written in their script.
+    pub fn is_in_language(&self, code: &str) -> bool {
+        match &self.language {
+            Some(language) => same_language(language, code),
+            None => self.script() == Some(Script::of_language(code)),
+        }
+    }
+
+    /// Rank the lyrics by the first of `preferred` languages they are in,
+    /// lower being better.
+    ///
+    /// Lyrics known to be in a language rank above ones only written in its
+    /// script, and lyrics in none of the languages rank last.
+    pub fn language_rank<S: AsRef<str>>(&self, preferred: &[S]) -> usize {
+        let script = self.script();
+        preferred
+            .iter()
+            .enumerate()
+            .find_map(|(i, code)| match &self.language {
+                Some(language) => same_language(language, code.as_ref()).then_some(2 * i),
+                None => (script == Some(Script::of_language(code.as_ref()))).then_some(2 * i + 1),
+            })
+            .unwrap_or(2 * preferred.len())
+    }
+}
+
+/// Sort versions of lyrics by the first preferred language they are in,
+/// keeping the order of equally preferred ones.
+pub fn sort_by_preference<S: AsRef<str>>(variants: &mut [Lyrics], preferred: &[S]) {
+    variants.sort_by_cached_key(|lyrics| lyrics.language_rank(preferred));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_are_detected() {
+        assert_eq!(Script::detect("夜に駆ける"), Some(Script::Japanese));
+        assert_eq!(Script::detect("我爱你"), Some(Script::Han));
+        assert_eq!(Script::detect("사랑해 사랑해 baby"), Some(Script::Hangul));
+        assert_eq!(Script::detect("Привет"), Some(Script::Cyrillic));
+        assert_eq!(Script::detect("Yoru ni kakeru"), Some(Script::Latin));
+        assert_eq!(Script::detect("♪ 123"), None);
+        assert_eq!(Script::of_language("pt-BR"), Script::Latin);
+        assert_eq!(Script::of_language("ja"), Script::Japanese);
+    }
+
+    #[test]
+    fn test_variants_sorted_by_preference() {
+        let original = Lyrics::plain(
+            "YOASOBI",
+            "Yoru ni Kakeru",
+            None,
+            "沈むように溶けてゆくように",
+        );
+        let romaji = Lyrics::plain("YOASOBI", "Yoru ni Kakeru", None, "Shizumu you ni");
+        let spanish = Lyrics {
+            language: Some("es".to_string()),
+            ..Lyrics::plain("YOASOBI", "Yoru ni Kakeru", None, "Como hundiéndome")
+        };
+
+        let mut variants = vec![original.clone(), romaji.clone(), spanish.clone()];
+        sort_by_preference(&mut variants, &["ja", "es"]);
+        assert_eq!(variants, [original.clone(), spanish, romaji.clone()]);
+
+        // Lyrics of unknown language match by script
+        sort_by_preference(&mut variants, &["en"]);
+        assert_eq!(variants[0], romaji);
+        assert_eq!(original.language_rank::<&str>(&[]), 0);
+    }
+}
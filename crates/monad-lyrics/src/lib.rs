@@ -1,15 +1,99 @@
 //! Lyrics fetching and parsing for Monad.
 //!
-//! Uses the Better Lyrics API to fetch synchronized lyrics.
+//! Lyrics come from a chain of [`LyricsProvider`]s, tried in order: files
+//! stored with local tracks, `YouTube` Music for the exact track played, the
+//! Better Lyrics API for word-synced lyrics, then LRCLIB. Each provider has
+//! its own timeout and is guarded by a circuit breaker, so a provider that is
+//! down or rate-limited is skipped instead of delaying every track change.
+//! Requests to each provider are spaced out, and transient failures are
+//! retried with exponential backoff, honoring `Retry-After`. Failures come
+//! back as a [`LyricsError`] telling missing songs from unhealthy providers.
+//! Noise in video titles, like "(Official Video)", is stripped before asking
+//! providers. Fetches started in the background are shared between identical
+//! requests and can be cancelled through their [`LyricsFetch`] handle, and
+//! the next track's lyrics can be prefetched with [`LyricsClient::prefetch`].
+//! Lyrics can be saved as LRC, TTML or plain text with [`Lyrics::save`].
+//! Songs with lyrics in several languages or scripts are returned in the
+//! preferred language, and [`LyricsClient::fetch_variants`] lists them all.
+//!
+//! With the `musixmatch` feature, Musixmatch can be added with a user's
+//! token. With the `romanize` feature, lines in Hangul, kana or Cyrillic also
+//! get a romanized version.
 
+mod better_lyrics;
+mod breaker;
+mod error;
+mod export;
+mod fetch;
+mod language;
+mod local;
+mod lrclib;
+mod matching;
+#[cfg(feature = "musixmatch")]
+mod musixmatch;
 mod parser;
+mod provider;
+mod rate_limit;
+#[cfg(feature = "romanize")]
+mod romanize;
+mod ytmusic;
+
+pub use better_lyrics::BetterLyrics;
+pub use breaker::{BreakerState, CircuitBreaker, DEFAULT_COOLDOWN, DEFAULT_FAILURE_THRESHOLD};
+pub use error::LyricsError;
+pub use export::LyricsFormat;
+pub use fetch::LyricsFetch;
+pub use language::Script;
+pub use local::LocalLyrics;
+pub use lrclib::Lrclib;
+#[cfg(feature = "musixmatch")]
+pub use musixmatch::Musixmatch;
+pub use provider::{LyricsProvider, LyricsQuery};
+pub use rate_limit::{RateLimiter, DEFAULT_MIN_INTERVAL};
+#[cfg(feature = "romanize")]
+pub use romanize::romanize;
+pub use ytmusic::YouTubeMusicLyrics;
+
+use std::sync::Arc;
+use std::time::Duration;
 
-use monad_core::Error;
-use reqwest::Client;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Default time to wait for a provider before trying the next.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of times a transient failure is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Default wait before the first retry, doubled for each one after.
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(250);
 
-const API_BASE_URL: &str = "https://lyrics-api.boidu.dev";
+/// User agent lyrics APIs are called with by default.
+pub const DEFAULT_USER_AGENT: &str = "Monad/1.0";
+
+/// Get the error for a response asking to slow down, with the wait it asks for.
+pub(crate) fn rate_limit_error(response: &reqwest::Response) -> Option<LyricsError> {
+    (response.status() == StatusCode::TOO_MANY_REQUESTS).then(|| LyricsError::RateLimited {
+        // Retry-After may also be a date, which is rare enough to ignore
+        retry_after: response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs),
+    })
+}
+
+/// Build the HTTP client providers use.
+pub(crate) fn http_client(user_agent: &str) -> Client {
+    Client::builder()
+        .user_agent(user_agent)
+        .build()
+        .unwrap_or_default()
+}
 
 /// A single word in the lyrics with timing information.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -33,8 +117,58 @@ pub struct LyricLine {
     pub end: f64,
     /// Individual words with timing (for word-level sync).
     pub words: Vec<LyricWord>,
+    /// The line in Latin script, if it is written in another.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub romanized: Option<String>,
+    /// The line translated into the requested language, if a provider has it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+}
+
+/// Get how far `position` is from `start` to `end`, from 0.0 to 1.0.
+fn progress(start: f64, end: f64, position: f64) -> f64 {
+    if end <= start {
+        return if position >= start { 1.0 } else { 0.0 };
+    }
+    ((position - start) / (end - start)).clamp(0.0, 1.0)
+}
+
+impl LyricWord {
+    /// Get how far through the word the position is, from 0.0 to 1.0.
+    pub fn progress_at(&self, position: f64) -> f64 {
+        progress(self.start, self.end, position)
+    }
+}
+
+impl LyricLine {
+    /// Get how far through the line the position is, from 0.0 to 1.0.
+    pub fn progress_at(&self, position: f64) -> f64 {
+        progress(self.start, self.end, position)
+    }
+
+    /// Get the word sung at the given position, if the line has word timings.
+    pub fn word_at(&self, position: f64) -> Option<&LyricWord> {
+        self.words
+            .iter()
+            .find(|word| position >= word.start && position < word.end)
+    }
+}
+
+/// Whether lyrics have timing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LyricsKind {
+    /// Lines are timed, and possibly their words.
+    #[default]
+    Synced,
+    /// Lines have no timing, so no line is ever current.
+    Plain,
+    /// The song is instrumental and has no lines.
+    Instrumental,
 }
 
+/// Lines standing for a lack of lyrics, after dropping symbols like `♪`.
+const INSTRUMENTAL_MARKERS: &[&str] = &["", "instrumental", "instrumental break", "no lyrics"];
+
 /// Complete lyrics for a song.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Lyrics {
@@ -44,25 +178,118 @@ pub struct Lyrics {
     pub artist: String,
     /// Total duration in seconds (if available).
     pub duration: Option<f64>,
+    /// Whether the lines are timed.
+    #[serde(default)]
+    pub kind: LyricsKind,
+    /// Milliseconds added to every timing, for lyrics out of sync with the audio.
+    #[serde(default)]
+    pub offset_ms: i64,
+    /// Language translations were asked for, whether or not any were found.
+    #[serde(default)]
+    pub translation_language: Option<String>,
+    /// Language code of the lines, if the provider gave one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
     /// All lyric lines with timing.
     pub lines: Vec<LyricLine>,
 }
 
 impl Lyrics {
+    /// Create untimed lyrics from text, one line per non-empty line.
+    pub fn plain(artist: &str, song: &str, duration: Option<f64>, text: &str) -> Self {
+        Self {
+            title: song.to_string(),
+            artist: artist.to_string(),
+            duration,
+            kind: LyricsKind::Plain,
+            offset_ms: 0,
+            translation_language: None,
+            language: None,
+            lines: text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| LyricLine {
+                    text: line.trim().to_string(),
+                    start: 0.0,
+                    end: 0.0,
+                    words: Vec::new(),
+                    romanized: None,
+                    translation: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Create lyrics for an instrumental song, without lines.
+    pub fn instrumental(artist: &str, song: &str, duration: Option<f64>) -> Self {
+        Self {
+            kind: LyricsKind::Instrumental,
+            lines: Vec::new(),
+            ..Self::plain(artist, song, duration, "")
+        }
+    }
+
+    /// Whether the lines are timed.
+    pub fn is_synced(&self) -> bool {
+        self.kind == LyricsKind::Synced
+    }
+
+    /// Whether the song is instrumental.
+    pub fn is_instrumental(&self) -> bool {
+        self.kind == LyricsKind::Instrumental
+    }
+
+    /// Whether the lyrics stand for an instrumental song without saying so,
+    /// having no lines or only ones like "♪ Instrumental ♪".
+    pub fn looks_instrumental(&self) -> bool {
+        self.lines.iter().all(|line| {
+            let words: Vec<String> = line
+                .text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect();
+            INSTRUMENTAL_MARKERS.contains(&words.join(" ").as_str())
+        })
+    }
+
+    /// Shift every timing by `offset_ms`; positive values show lyrics later.
+    #[must_use]
+    pub const fn with_offset_ms(mut self, offset_ms: i64) -> Self {
+        self.offset_ms = offset_ms;
+        self
+    }
+
+    /// Convert a playback position to the lyrics' own timings, undoing the offset.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn synced_position(&self, position: f64) -> f64 {
+        position - self.offset_ms as f64 / 1000.0
+    }
+
     /// Get the lyric line active at the given position (in seconds).
     pub fn line_at(&self, position: f64) -> Option<&LyricLine> {
-        self.lines
-            .iter()
-            .find(|line| position >= line.start && position < line.end)
+        self.line_index_at(position).map(|index| &self.lines[index])
     }
 
     /// Get the index of the lyric line active at the given position.
+    ///
+    /// Plain lyrics have no active line.
     pub fn line_index_at(&self, position: f64) -> Option<usize> {
+        if !self.is_synced() {
+            return None;
+        }
+        let position = self.synced_position(position);
         self.lines
             .iter()
             .position(|line| position >= line.start && position < line.end)
     }
 
+    /// Get the word sung at the given position, if the line has word timings.
+    pub fn word_at(&self, position: f64) -> Option<&LyricWord> {
+        self.line_at(position)?
+            .word_at(self.synced_position(position))
+    }
+
     /// Get plain text lyrics (no timing).
     pub fn plain_text(&self) -> String {
         self.lines
@@ -73,16 +300,59 @@ impl Lyrics {
     }
 }
 
-/// API response containing TTML lyrics.
-#[derive(Debug, Deserialize)]
-struct TtmlResponse {
-    ttml: String,
+/// A provider in the chain, with its timeout, circuit breaker and rate limiter.
+struct ChainedProvider {
+    provider: Arc<dyn LyricsProvider>,
+    timeout: Duration,
+    breaker: CircuitBreaker,
+    limiter: RateLimiter,
 }
 
-/// Lyrics client for fetching from the Better Lyrics API.
+impl ChainedProvider {
+    /// Chain a provider with the default breaker and rate limit.
+    fn new(provider: Arc<dyn LyricsProvider>, timeout: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            provider,
+            timeout,
+            breaker: CircuitBreaker::default(),
+            limiter: RateLimiter::default(),
+        })
+    }
+
+    /// Chain another provider in this one's place, with fresh health.
+    fn replaced(&self, provider: Arc<dyn LyricsProvider>) -> Arc<Self> {
+        Arc::new(Self {
+            provider,
+            timeout: self.timeout,
+            breaker: self.breaker.renewed(),
+            limiter: self.limiter.renewed(),
+        })
+    }
+}
+
+/// Health of a lyrics provider, for diagnostics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderHealth {
+    /// Provider name.
+    pub name: String,
+    /// Circuit breaker state.
+    pub state: BreakerState,
+    /// Failures since the last successful request.
+    pub consecutive_failures: u32,
+}
+
+/// Lyrics client fetching from a fallback chain of providers.
+///
+/// Providers are tried in order. Clones share provider health and fetches
+/// in flight.
 #[derive(Clone)]
 pub struct LyricsClient {
-    client: Client,
+    providers: Vec<Arc<ChainedProvider>>,
+    in_flight: Arc<fetch::InFlight>,
+    user_agent: String,
+    max_retries: u32,
+    retry_backoff: Duration,
+    preferred_languages: Vec<String>,
 }
 
 impl Default for LyricsClient {
@@ -92,14 +362,195 @@ impl Default for LyricsClient {
 }
 
 impl LyricsClient {
-    /// Create a new lyrics client.
+    /// Create a client trying local files, then `YouTube` Music, then Better
+    /// Lyrics, then LRCLIB.
     pub fn new() -> Self {
-        let client = Client::builder()
-            .user_agent("Monad/1.0")
-            .build()
-            .unwrap_or_default();
+        let mut client = Self::empty().with_provider(LocalLyrics::new(), DEFAULT_TIMEOUT);
+        match YouTubeMusicLyrics::new() {
+            Ok(provider) => client = client.with_provider(provider, DEFAULT_TIMEOUT),
+            Err(e) => warn!("YouTube Music lyrics unavailable: {e}"),
+        }
+        client
+            .with_provider(BetterLyrics::new(), DEFAULT_TIMEOUT)
+            .with_provider(Lrclib::new(), DEFAULT_TIMEOUT)
+    }
+
+    /// Create a client without providers, to build a chain of its own.
+    pub fn empty() -> Self {
+        Self {
+            providers: Vec::new(),
+            in_flight: Arc::default(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            preferred_languages: Vec::new(),
+        }
+    }
 
-        Self { client }
+    /// Prepare a provider for the chain, identifying as the client's user agent.
+    fn identified(&self, provider: impl LyricsProvider + 'static) -> Arc<dyn LyricsProvider> {
+        if self.user_agent == DEFAULT_USER_AGENT {
+            return Arc::new(provider);
+        }
+        provider
+            .with_user_agent(&self.user_agent)
+            .unwrap_or_else(|| Arc::new(provider))
+    }
+
+    /// Add a provider to the end of the chain, giving up on it after `timeout`.
+    #[must_use]
+    pub fn with_provider(
+        mut self,
+        provider: impl LyricsProvider + 'static,
+        timeout: Duration,
+    ) -> Self {
+        let provider = self.identified(provider);
+        self.providers.push(ChainedProvider::new(provider, timeout));
+        self
+    }
+
+    /// Replace the provider of the same name, keeping its place and timeout.
+    fn with_replaced_provider(mut self, provider: impl LyricsProvider + 'static) -> Self {
+        let provider = self.identified(provider);
+        if let Some(entry) = self
+            .providers
+            .iter_mut()
+            .find(|entry| entry.provider.name() == provider.name())
+        {
+            *entry = entry.replaced(provider);
+        }
+        self
+    }
+
+    /// Call the Better Lyrics API at `base_url`, such as a self-hosted
+    /// instance or a proxy.
+    #[must_use]
+    pub fn with_better_lyrics_url(self, base_url: impl Into<String>) -> Self {
+        self.with_replaced_provider(BetterLyrics::with_base_url("Better Lyrics", base_url))
+    }
+
+    /// Call LRCLIB at `base_url`, such as a self-hosted instance or a proxy.
+    #[must_use]
+    pub fn with_lrclib_url(self, base_url: impl Into<String>) -> Self {
+        self.with_replaced_provider(Lrclib::with_base_url(base_url))
+    }
+
+    /// Identify as `user_agent` to lyrics APIs.
+    #[must_use]
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self.providers = self
+            .providers
+            .iter()
+            .map(|entry| {
+                entry
+                    .provider
+                    .with_user_agent(&self.user_agent)
+                    .map_or_else(|| entry.clone(), |provider| entry.replaced(provider))
+            })
+            .collect();
+        self
+    }
+
+    /// Add Musixmatch with a user's token, after local files and `YouTube`
+    /// Music and ahead of the public providers.
+    #[cfg(feature = "musixmatch")]
+    #[must_use]
+    pub fn with_musixmatch(mut self, token: impl Into<String>) -> Self {
+        let index = self
+            .providers
+            .iter()
+            .position(|entry| !matches!(entry.provider.name(), "Local files" | "YouTube Music"))
+            .unwrap_or(self.providers.len());
+        let provider = self.identified(Musixmatch::new(token));
+        self.providers
+            .insert(index, ChainedProvider::new(provider, DEFAULT_TIMEOUT));
+        self
+    }
+
+    /// Reorder the chain by provider name, dropping providers not named.
+    #[must_use]
+    pub fn with_order(mut self, names: &[&str]) -> Self {
+        self.providers = names
+            .iter()
+            .filter_map(|name| {
+                self.providers
+                    .iter()
+                    .find(|entry| entry.provider.name() == *name)
+                    .cloned()
+            })
+            .collect();
+        self
+    }
+
+    /// Configure the circuit breaker of every provider.
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.providers = self
+            .providers
+            .iter()
+            .map(|entry| {
+                Arc::new(ChainedProvider {
+                    provider: entry.provider.clone(),
+                    timeout: entry.timeout,
+                    breaker: CircuitBreaker::new(failure_threshold, cooldown),
+                    limiter: entry.limiter.renewed(),
+                })
+            })
+            .collect();
+        self
+    }
+
+    /// Send each provider at most one request per `min_interval`.
+    #[must_use]
+    pub fn with_rate_limit(mut self, min_interval: Duration) -> Self {
+        self.providers = self
+            .providers
+            .iter()
+            .map(|entry| {
+                Arc::new(ChainedProvider {
+                    provider: entry.provider.clone(),
+                    timeout: entry.timeout,
+                    breaker: entry.breaker.renewed(),
+                    limiter: RateLimiter::new(min_interval),
+                })
+            })
+            .collect();
+        self
+    }
+
+    /// Retry transient failures up to `max_retries` times, waiting `backoff`
+    /// before the first retry and twice as long before each one after.
+    #[must_use]
+    pub const fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Prefer lyrics in these languages, by code like `ja`, most preferred
+    /// first, when a provider has the song in several.
+    ///
+    /// Without preferences, the version the provider ranks best is taken.
+    #[must_use]
+    pub fn with_preferred_languages<S: Into<String>>(
+        mut self,
+        languages: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.preferred_languages = languages.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Get the health of each provider, in chain order.
+    pub fn provider_health(&self) -> Vec<ProviderHealth> {
+        self.providers
+            .iter()
+            .map(|entry| ProviderHealth {
+                name: entry.provider.name().to_string(),
+                state: entry.breaker.state(),
+                consecutive_failures: entry.breaker.consecutive_failures(),
+            })
+            .collect()
     }
 
     /// Fetch lyrics for a song.
@@ -115,64 +566,205 @@ impl LyricsClient {
         song: &str,
         album: Option<&str>,
         duration: Option<f64>,
-    ) -> Result<Lyrics, Error> {
-        info!("Fetching lyrics for: {} - {}", artist, song);
-
-        let mut url = format!(
-            "{}/getLyrics?a={}&s={}",
-            API_BASE_URL,
-            urlencoding::encode(artist),
-            urlencoding::encode(song)
-        );
+    ) -> Result<Lyrics, LyricsError> {
+        let query = LyricsQuery {
+            album,
+            duration,
+            ..LyricsQuery::new(artist, song)
+        };
+        self.fetch_query(&query).await
+    }
 
-        if let Some(album) = album {
-            use std::fmt::Write;
-            let _ = write!(url, "&al={}", urlencoding::encode(album));
-        }
+    /// Fetch lyrics matching a query.
+    ///
+    /// Providers whose circuit is open are skipped. Each provider is asked
+    /// for the cleaned-up artist and title pairs of the query in turn, and a
+    /// provider without the song passes it on to the next. Plain lyrics,
+    /// lyrics whose duration is far from the query's and songs marked
+    /// instrumental are only returned if no later provider has a better match.
+    ///
+    /// Songs without lyrics come back as [`LyricsKind::Instrumental`], songs
+    /// no provider has as [`LyricsError::NotFound`], failures of every
+    /// provider as their last error, and a chain with every circuit open as
+    /// [`LyricsError::ProviderUnavailable`]. Of several versions of the
+    /// lyrics, the one in the most preferred language is returned.
+    pub async fn fetch_query(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+        self.fetch_variants(query)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| LyricsError::NotFound("No lyrics versions".to_string()))
+    }
 
-        if let Some(dur) = duration {
-            use std::fmt::Write;
-            let _ = write!(url, "&d={dur}");
-        }
+    /// Fetch every version of the lyrics, in other languages or scripts, that
+    /// the provider chosen by [`fetch_query`](Self::fetch_query) has.
+    ///
+    /// Versions are sorted by preferred language, keeping the provider's
+    /// order otherwise, so the first is the one `fetch_query` returns.
+    pub async fn fetch_variants(
+        &self,
+        query: &LyricsQuery<'_>,
+    ) -> Result<Vec<Lyrics>, LyricsError> {
+        info!("Fetching lyrics for: {} - {}", query.artist, query.song);
 
-        debug!("Requesting: {}", url);
+        let candidates = matching::candidates(query.artist, query.song);
+        let queries: Vec<LyricsQuery<'_>> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (artist, song))| LyricsQuery {
+                artist,
+                song,
+                // The video ID and path name the same track whatever its title
+                video_id: query.video_id.filter(|_| i == 0),
+                path: query.path.filter(|_| i == 0),
+                ..*query
+            })
+            .collect();
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::Network(e.to_string()))?;
+        let mut fallback: Option<Vec<Lyrics>> = None;
+        let mut not_found = None;
+        let mut last_error = None;
+        'providers: for entry in &self.providers {
+            let name = entry.provider.name();
+            if !entry.breaker.allow_request() {
+                debug!("Skipping lyrics provider {name} (circuit open)");
+                continue;
+            }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(Error::Api(format!(
-                "Lyrics API returned {}: {}",
-                status, body
-            )));
+            for candidate in &queries {
+                match self.request(entry, candidate).await {
+                    Ok(mut variants) => {
+                        entry.breaker.record_success();
+                        for lyrics in &mut variants {
+                            if !lyrics.is_instrumental() && lyrics.looks_instrumental() {
+                                *lyrics = Lyrics::instrumental(
+                                    &lyrics.artist,
+                                    &lyrics.title,
+                                    lyrics.duration,
+                                );
+                            }
+                            lyrics.translation_language =
+                                query.translation_language.map(str::to_string);
+                            #[cfg(feature = "romanize")]
+                            lyrics.romanize();
+                        }
+                        language::sort_by_preference(&mut variants, &self.preferred_languages);
+                        let lyrics = &variants[0];
+                        let in_time = matching::duration_matches(query.duration, lyrics.duration);
+                        if lyrics.is_synced() && in_time {
+                            return Ok(variants);
+                        }
+                        if lyrics.is_instrumental() {
+                            debug!("Lyrics provider {name} has the song as instrumental");
+                        } else if in_time {
+                            debug!("Lyrics provider {name} only has plain lyrics");
+                        } else {
+                            debug!("Lyrics from {name} are for a version of another length");
+                        }
+                        // Lyrics found elsewhere outweigh a song marked instrumental
+                        match &fallback {
+                            Some(previous)
+                                if previous[0].is_instrumental() && !lyrics.is_instrumental() =>
+                            {
+                                fallback = Some(variants);
+                            }
+                            Some(_) => {}
+                            None => fallback = Some(variants),
+                        }
+                        continue 'providers;
+                    }
+                    Err(e @ LyricsError::RateLimited { .. }) => {
+                        // Asked to slow down, which is not a sign of ill health
+                        debug!("Skipping lyrics provider {name}: {e}");
+                        last_error = Some(e);
+                        continue 'providers;
+                    }
+                    Err(e) if e.is_provider_failure() => {
+                        warn!("Lyrics provider {name} failed: {e}");
+                        entry.breaker.record_failure();
+                        last_error = Some(e);
+                        continue 'providers;
+                    }
+                    Err(e) => {
+                        // The provider is healthy, it just does not have the song
+                        debug!(
+                            "Lyrics provider {name} for {} - {}: {e}",
+                            candidate.artist, candidate.song
+                        );
+                        entry.breaker.record_success();
+                        not_found = Some(e);
+                    }
+                }
+            }
         }
 
-        let ttml_response: TtmlResponse = response
-            .json()
-            .await
-            .map_err(|e| Error::Parse(e.to_string()))?;
+        if let Some(variants) = fallback {
+            return Ok(variants);
+        }
+        Err(not_found.or(last_error).unwrap_or_else(|| {
+            LyricsError::ProviderUnavailable("All lyrics providers are unavailable".to_string())
+        }))
+    }
 
-        let lyrics = parser::parse_ttml(&ttml_response.ttml, artist, song)?;
+    /// Ask one provider for every version of the lyrics, waiting for the rate
+    /// limiter and retrying transient failures with exponential backoff.
+    ///
+    /// Timeouts are not retried, as the provider already held up the chain.
+    /// A provider asking for a longer wait than its timeout is passed over,
+    /// and held off for as long as it asked.
+    async fn request(
+        &self,
+        entry: &ChainedProvider,
+        query: &LyricsQuery<'_>,
+    ) -> Result<Vec<Lyrics>, LyricsError> {
+        let name = entry.provider.name();
+        let mut attempt = 0;
+        loop {
+            let wait = entry
+                .limiter
+                .reserve(entry.timeout)
+                .ok_or(LyricsError::RateLimited { retry_after: None })?;
+            tokio::time::sleep(wait).await;
 
-        info!(
-            "Fetched {} lyric lines for {} - {}",
-            lyrics.lines.len(),
-            artist,
-            song
-        );
+            let Ok(result) =
+                tokio::time::timeout(entry.timeout, entry.provider.fetch_variants(query)).await
+            else {
+                return Err(LyricsError::ProviderUnavailable(format!(
+                    "Timed out after {}s",
+                    entry.timeout.as_secs_f64()
+                )));
+            };
+            let error = match result {
+                Ok(variants) if variants.is_empty() => {
+                    return Err(LyricsError::NotFound(format!(
+                        "{name} has no lyrics for {} - {}",
+                        query.artist, query.song
+                    )));
+                }
+                Err(e) if e.is_retryable() => e,
+                result => return result,
+            };
 
-        Ok(lyrics)
+            let delay = match error {
+                LyricsError::RateLimited {
+                    retry_after: Some(delay),
+                } => delay,
+                _ => self
+                    .retry_backoff
+                    .saturating_mul(2_u32.saturating_pow(attempt)),
+            };
+            entry.limiter.hold_off(delay);
+            if attempt >= self.max_retries || delay > entry.timeout {
+                return Err(error);
+            }
+            attempt += 1;
+            debug!("Retrying lyrics provider {name} in {delay:?} after: {error}");
+        }
     }
 }
 
 /// URL encoding helper.
-mod urlencoding {
+pub(crate) mod urlencoding {
     use std::fmt::Write;
 
     pub fn encode(s: &str) -> String {
@@ -197,6 +789,354 @@ mod urlencoding {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// A provider answering after `delay`, with lyrics or without the song.
+    struct FakeProvider {
+        name: &'static str,
+        has_song: bool,
+        delay: Duration,
+        kind: LyricsKind,
+    }
+
+    #[async_trait]
+    impl LyricsProvider for FakeProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+            tokio::time::sleep(self.delay).await;
+            if !self.has_song {
+                return Err(LyricsError::NotFound(self.name.to_string()));
+            }
+            Ok(Lyrics {
+                kind: self.kind,
+                ..Lyrics::plain(self.name, query.song, None, "Hello")
+            })
+        }
+    }
+
+    fn fake(name: &'static str, has_song: bool, delay: Duration) -> FakeProvider {
+        FakeProvider {
+            name,
+            has_song,
+            delay,
+            kind: LyricsKind::Synced,
+        }
+    }
+
+    #[test]
+    fn test_word_progress() -> Result<(), LyricsError> {
+        let word = |text: &str, start: f64, end: f64| LyricWord {
+            text: text.to_string(),
+            start,
+            end,
+        };
+        let lyrics = Lyrics {
+            title: "Song".to_string(),
+            artist: "Artist".to_string(),
+            duration: None,
+            kind: LyricsKind::Synced,
+            offset_ms: 0,
+            translation_language: None,
+            language: None,
+            lines: vec![LyricLine {
+                text: "Hello world".to_string(),
+                start: 10.0,
+                end: 14.0,
+                words: vec![word("Hello", 10.0, 11.0), word("world", 12.0, 14.0)],
+                romanized: None,
+                translation: None,
+            }],
+        };
+
+        let current = lyrics
+            .word_at(10.5)
+            .ok_or(LyricsError::NotFound("No word".to_string()))?;
+        assert_eq!(current.text, "Hello");
+        assert!((current.progress_at(10.5) - 0.5).abs() < 0.001);
+        // Between words, and before and after the line
+        assert!(lyrics.word_at(11.5).is_none());
+        assert!(lyrics.word_at(15.0).is_none());
+
+        let line = &lyrics.lines[0];
+        assert!((line.progress_at(13.0) - 0.75).abs() < 0.001);
+        assert!(line.progress_at(9.0).abs() < 0.001);
+        assert!((line.progress_at(20.0) - 1.0).abs() < 0.001);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_synced_lyrics_preferred_over_plain() -> Result<(), LyricsError> {
+        let plain = FakeProvider {
+            kind: LyricsKind::Plain,
+            ..fake("Plain", true, Duration::ZERO)
+        };
+        let client = LyricsClient::empty()
+            .with_provider(plain, DEFAULT_TIMEOUT)
+            .with_provider(fake("Synced", true, Duration::ZERO), DEFAULT_TIMEOUT);
+        let lyrics = client.fetch("Artist", "Song", None, None).await?;
+        assert_eq!(lyrics.artist, "Synced");
+
+        // Plain lyrics are still better than none
+        let client = client.with_order(&["Plain"]);
+        let lyrics = client.fetch("Artist", "Song", None, None).await?;
+        assert_eq!(lyrics.kind, LyricsKind::Plain);
+
+        let lyrics = Lyrics::plain("Artist", "Song", None, "Hello\n\nWorld");
+        assert_eq!(lyrics.lines.len(), 2);
+        assert_eq!(lyrics.line_at(0.0), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instrumental_songs() -> Result<(), LyricsError> {
+        assert!(Lyrics::plain("Artist", "Song", None, "♪ Instrumental ♪").looks_instrumental());
+        assert!(Lyrics::plain("Artist", "Song", None, "").looks_instrumental());
+        assert!(!Lyrics::plain("Artist", "Song", None, "♪ Hello ♪").looks_instrumental());
+
+        let instrumental = FakeProvider {
+            kind: LyricsKind::Instrumental,
+            ..fake("Instrumental", true, Duration::ZERO)
+        };
+        let plain = FakeProvider {
+            kind: LyricsKind::Plain,
+            ..fake("Plain", true, Duration::ZERO)
+        };
+        let client = LyricsClient::empty()
+            .with_provider(instrumental, DEFAULT_TIMEOUT)
+            .with_provider(plain, DEFAULT_TIMEOUT)
+            .with_provider(fake("Missing", false, Duration::ZERO), DEFAULT_TIMEOUT);
+        let lyrics = client.fetch("Artist", "Song", None, None).await?;
+        assert_eq!(lyrics.kind, LyricsKind::Plain);
+
+        let lyrics = client
+            .with_order(&["Missing", "Instrumental"])
+            .fetch("Artist", "Song", None, None)
+            .await?;
+        assert!(lyrics.is_instrumental());
+        Ok(())
+    }
+
+    /// A provider with a song in Japanese and romaji.
+    struct BilingualProvider;
+
+    #[async_trait]
+    impl LyricsProvider for BilingualProvider {
+        fn name(&self) -> &'static str {
+            "Bilingual"
+        }
+
+        async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+            let mut variants = self.fetch_variants(query).await?;
+            Ok(variants.remove(0))
+        }
+
+        async fn fetch_variants(
+            &self,
+            query: &LyricsQuery<'_>,
+        ) -> Result<Vec<Lyrics>, LyricsError> {
+            Ok(["夜に駆ける", "Yoru ni kakeru"]
+                .into_iter()
+                .map(|text| Lyrics {
+                    kind: LyricsKind::Synced,
+                    ..Lyrics::plain(query.artist, query.song, None, text)
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preferred_language_is_picked() -> Result<(), LyricsError> {
+        let client = LyricsClient::empty().with_provider(BilingualProvider, DEFAULT_TIMEOUT);
+        let query = LyricsQuery::new("YOASOBI", "Yoru ni Kakeru");
+        assert_eq!(
+            client.fetch_query(&query).await?.script(),
+            Some(Script::Japanese)
+        );
+
+        let client = client.with_preferred_languages(["en", "ja"]);
+        let variants = client.fetch_variants(&query).await?;
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].script(), Some(Script::Latin));
+        assert_eq!(client.fetch_query(&query).await?, variants[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset_shifts_timings() {
+        let lyrics = Lyrics {
+            title: "Song".to_string(),
+            artist: "Artist".to_string(),
+            duration: None,
+            kind: LyricsKind::Synced,
+            offset_ms: 0,
+            translation_language: None,
+            language: None,
+            lines: vec![LyricLine {
+                text: "Hello".to_string(),
+                start: 10.0,
+                end: 12.0,
+                words: Vec::new(),
+                romanized: None,
+                translation: None,
+            }],
+        };
+        assert_eq!(lyrics.line_index_at(9.0), None);
+
+        // Lyrics shown a second and a half later
+        let lyrics = lyrics.with_offset_ms(1500);
+        assert_eq!(lyrics.line_index_at(11.0), None);
+        assert_eq!(lyrics.line_index_at(12.0), Some(0));
+        assert!((lyrics.synced_position(12.0) - 10.5).abs() < 0.001);
+
+        // And a second earlier
+        let lyrics = lyrics.with_offset_ms(-1000);
+        assert_eq!(lyrics.line_index_at(9.0), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_chain_falls_back_in_order() -> Result<(), LyricsError> {
+        let client = LyricsClient::empty()
+            .with_provider(fake("Missing", false, Duration::ZERO), DEFAULT_TIMEOUT)
+            .with_provider(
+                fake("Slow", true, Duration::from_secs(1)),
+                Duration::from_millis(10),
+            )
+            .with_provider(fake("Found", true, Duration::ZERO), DEFAULT_TIMEOUT);
+
+        let lyrics = client.fetch("Artist", "Song", None, None).await?;
+        assert_eq!(lyrics.artist, "Found");
+        let health = client.provider_health();
+        assert_eq!(health[0].consecutive_failures, 0);
+        assert_eq!(health[1].consecutive_failures, 1);
+
+        let reordered = client.with_order(&["Slow", "Missing"]);
+        let names: Vec<_> = reordered
+            .provider_health()
+            .into_iter()
+            .map(|health| health.name)
+            .collect();
+        assert_eq!(names, ["Slow", "Missing"]);
+        // Slow timed out before, Missing has no lyrics
+        let result = reordered.fetch("Artist", "Song", None, None).await;
+        assert!(matches!(result, Err(LyricsError::NotFound(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_providers_are_skipped() {
+        // Port 9 (discard) refuses connections, so the request fails fast
+        let client = LyricsClient::empty()
+            .with_provider(
+                BetterLyrics::with_base_url("Local", "http://127.0.0.1:9"),
+                DEFAULT_TIMEOUT,
+            )
+            .with_circuit_breaker(1, Duration::from_secs(60));
+
+        let result = client.fetch("Artist", "Song", None, None).await;
+        assert!(matches!(result, Err(LyricsError::ProviderUnavailable(_))));
+        assert_eq!(client.provider_health()[0].state, BreakerState::Open);
+
+        let result = client.fetch("Artist", "Song", None, None).await;
+        assert!(matches!(result, Err(LyricsError::ProviderUnavailable(_))));
+        assert_eq!(client.clone().provider_health()[0].consecutive_failures, 1);
+    }
+
+    /// A provider failing with `error` until `failures` run out.
+    struct FlakyProvider {
+        failures: AtomicU32,
+        calls: Arc<AtomicU32>,
+        error: fn() -> LyricsError,
+    }
+
+    #[async_trait]
+    impl LyricsProvider for FlakyProvider {
+        fn name(&self) -> &'static str {
+            "Flaky"
+        }
+
+        async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err((self.error)());
+            }
+            Ok(Lyrics {
+                kind: LyricsKind::Synced,
+                ..Lyrics::plain(query.artist, query.song, None, "Hello")
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() -> Result<(), LyricsError> {
+        let calls = Arc::new(AtomicU32::new(0));
+        let flaky = FlakyProvider {
+            failures: AtomicU32::new(2),
+            calls: calls.clone(),
+            error: || LyricsError::ProviderUnavailable("Connection reset".to_string()),
+        };
+        let client = LyricsClient::empty()
+            .with_provider(flaky, DEFAULT_TIMEOUT)
+            .with_rate_limit(Duration::ZERO)
+            .with_retries(2, Duration::from_millis(1));
+        client.fetch("Artist", "Song", None, None).await?;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(client.provider_health()[0].consecutive_failures, 0);
+
+        // Waits longer than the timeout are not sat out, nor retried
+        let calls = Arc::new(AtomicU32::new(0));
+        let limited = FlakyProvider {
+            failures: AtomicU32::new(1),
+            calls: calls.clone(),
+            error: || LyricsError::RateLimited {
+                retry_after: Some(Duration::from_secs(60)),
+            },
+        };
+        let client = LyricsClient::empty().with_provider(limited, DEFAULT_TIMEOUT);
+        let result = client.fetch("Artist", "Song", None, None).await;
+        assert!(matches!(result, Err(LyricsError::RateLimited { .. })));
+        let result = client.fetch("Artist", "Song", None, None).await;
+        assert!(matches!(result, Err(LyricsError::RateLimited { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(client.provider_health()[0].state, BreakerState::Closed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_and_user_agent_overrides() -> Result<(), LyricsError> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A server answering every request with 404, keeping the first
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await?;
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                .await?;
+            Ok::<_, std::io::Error>(String::from_utf8_lossy(&request[..len]).to_lowercase())
+        });
+
+        let client = LyricsClient::empty()
+            .with_provider(BetterLyrics::new(), DEFAULT_TIMEOUT)
+            .with_provider(Lrclib::new(), DEFAULT_TIMEOUT)
+            .with_user_agent("Test/1.0")
+            .with_better_lyrics_url(url)
+            .with_order(&["Better Lyrics"]);
+        let result = client.fetch("Artist", "Song", None, None).await;
+        assert!(matches!(result, Err(LyricsError::NotFound(_))));
+
+        let request = server.await.map_err(|_| LyricsError::Cancelled)??;
+        assert!(request.starts_with("get /getlyrics?"));
+        assert!(request.contains("user-agent: test/1.0"));
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_fetch_lyrics() {
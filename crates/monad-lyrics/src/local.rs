@@ -0,0 +1,184 @@
+//! Lyrics stored with local audio files.
+//!
+//! Sidecar files next to the audio file, `song.ttml` and then `song.lrc`,
+//! are read first, then the file's own ID3 tags: synchronised lyrics (SYLT)
+//! and, failing those, unsynchronised ones (USLT). Only queries with a file
+//! path can be answered, and no network requests are made.
+
+use std::io::ErrorKind;
+use std::path::Path;
+
+use async_trait::async_trait;
+use id3::frame::TimestampFormat;
+use id3::Tag;
+use tracing::{debug, info, warn};
+
+use crate::provider::{LyricsProvider, LyricsQuery};
+use crate::{parser, Lyrics, LyricsError};
+
+/// Lyrics from sidecar files and tags of local audio files.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalLyrics;
+
+impl LocalLyrics {
+    /// Create a provider.
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+/// Read the sidecar file of an audio file with another extension, if any.
+async fn read_sidecar(path: &Path, extension: &str) -> Option<String> {
+    let sidecar = path.with_extension(extension);
+    match tokio::fs::read_to_string(&sidecar).await {
+        Ok(text) => Some(text).filter(|text| !text.trim().is_empty()),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("Could not read {}: {e}", sidecar.display());
+            None
+        }
+    }
+}
+
+/// Read lyrics from the ID3 tags of an audio file, preferring synced ones.
+fn tag_lyrics(path: &Path, artist: &str, song: &str, duration: Option<f64>) -> Option<Lyrics> {
+    let tag = match Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => return None,
+        Err(e) => {
+            warn!("Could not read tags of {}: {e}", path.display());
+            return None;
+        }
+    };
+
+    // Timestamps in MPEG frames would need the file's frame rate
+    let synced = tag.synchronised_lyrics().find(|sylt| {
+        matches!(sylt.timestamp_format, TimestampFormat::Ms) && !sylt.content.is_empty()
+    });
+    if let Some(sylt) = synced {
+        let entries = sylt
+            .content
+            .iter()
+            .map(|(ms, text)| (f64::from(*ms) / 1000.0, text.trim().to_string()))
+            .collect();
+        let lyrics = parser::timed_lyrics(entries, artist, song, duration);
+        if !lyrics.lines.is_empty() {
+            return Some(lyrics);
+        }
+    }
+
+    let plain = tag.lyrics().find(|uslt| !uslt.text.trim().is_empty());
+    plain.map(|uslt| Lyrics::plain(artist, song, duration, &uslt.text))
+}
+
+#[async_trait]
+impl LyricsProvider for LocalLyrics {
+    fn name(&self) -> &'static str {
+        "Local files"
+    }
+
+    async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+        let not_found =
+            || LyricsError::NotFound(format!("No lyrics for {} - {}", query.artist, query.song));
+        let path = query.path.ok_or_else(not_found)?;
+
+        let found = |lyrics: &Option<Lyrics>| lyrics.as_ref().is_some_and(|l| !l.lines.is_empty());
+        let mut lyrics = None;
+        if let Some(ttml) = read_sidecar(path, "ttml").await {
+            match parser::parse_ttml(&ttml, query.artist, query.song) {
+                Ok(parsed) => lyrics = Some(parsed),
+                Err(e) => warn!("Ignoring invalid TTML next to {}: {e}", path.display()),
+            }
+        }
+        if !found(&lyrics) {
+            if let Some(lrc) = read_sidecar(path, "lrc").await {
+                let parsed = parser::parse_lrc(&lrc, query.artist, query.song, query.duration);
+                // LRC files without timestamps are plain lyrics
+                lyrics = Some(if parsed.lines.is_empty() {
+                    Lyrics::plain(query.artist, query.song, query.duration, &lrc)
+                } else {
+                    parsed
+                });
+            }
+        }
+        if !found(&lyrics) {
+            let path = path.to_path_buf();
+            let (artist, song) = (query.artist.to_string(), query.song.to_string());
+            let duration = query.duration;
+            lyrics =
+                tokio::task::spawn_blocking(move || tag_lyrics(&path, &artist, &song, duration))
+                    .await
+                    .map_err(|e| LyricsError::ProviderUnavailable(e.to_string()))?;
+        }
+
+        let lyrics = lyrics
+            .filter(|lyrics| !lyrics.lines.is_empty())
+            .ok_or_else(|| {
+                debug!("No local lyrics for {}", path.display());
+                not_found()
+            })?;
+        info!(
+            "Read {} lyric lines for {} - {} from local files",
+            lyrics.lines.len(),
+            query.artist,
+            query.song
+        );
+        Ok(lyrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::frame::{SynchronisedLyrics, SynchronisedLyricsType};
+    use id3::{TagLike, Version};
+
+    #[tokio::test]
+    async fn test_sidecar_files_are_read() -> Result<(), LyricsError> {
+        let dir = tempfile::tempdir()?;
+        let audio = dir.path().join("song.flac");
+        std::fs::write(&audio, b"")?;
+        let query = LyricsQuery::new("Artist", "Song").with_path(&audio);
+        assert!(matches!(
+            LocalLyrics::new().fetch(&query).await,
+            Err(LyricsError::NotFound(_))
+        ));
+
+        std::fs::write(
+            dir.path().join("song.lrc"),
+            "[00:01.00] Hello\n[00:03.00] World",
+        )?;
+        let lyrics = LocalLyrics::new().fetch(&query).await?;
+        assert!(lyrics.is_synced());
+        assert_eq!(lyrics.lines.len(), 2);
+
+        std::fs::write(dir.path().join("song.lrc"), "Hello\nWorld")?;
+        let lyrics = LocalLyrics::new().fetch(&query).await?;
+        assert!(!lyrics.is_synced());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_id3_lyrics_are_read() -> Result<(), LyricsError> {
+        let dir = tempfile::tempdir()?;
+        let audio = dir.path().join("song.mp3");
+        std::fs::write(&audio, b"")?;
+        let mut tag = Tag::new();
+        tag.add_frame(SynchronisedLyrics {
+            lang: "eng".to_string(),
+            timestamp_format: TimestampFormat::Ms,
+            content_type: SynchronisedLyricsType::Lyrics,
+            description: String::new(),
+            content: vec![(3000, "World".to_string()), (1500, "Hello".to_string())],
+        });
+        tag.write_to_path(&audio, Version::Id3v24)
+            .map_err(|e| LyricsError::ParseFailed(e.to_string()))?;
+
+        let query = LyricsQuery::new("Artist", "Song").with_path(&audio);
+        let lyrics = LocalLyrics::new().fetch(&query).await?;
+        assert_eq!(lyrics.lines[0].text, "Hello");
+        assert!((lyrics.lines[0].start - 1.5).abs() < 0.001);
+        assert!((lyrics.lines[0].end - 3.0).abs() < 0.001);
+        Ok(())
+    }
+}
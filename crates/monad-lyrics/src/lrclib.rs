@@ -0,0 +1,236 @@
+//! LRCLIB, a free database of line-synced lyrics.
+//!
+//! With the album and duration known, the exact match endpoint is used;
+//! otherwise the search result with synced lyrics closest to the track's
+//! duration is taken. Search results in other scripts, like a romanized
+//! version, are kept as variants. Songs with only plain lyrics get lines
+//! without timing.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::provider::{LyricsProvider, LyricsQuery};
+use crate::{
+    http_client, matching, parser, rate_limit_error, urlencoding, Lyrics, LyricsError,
+    DEFAULT_USER_AGENT,
+};
+
+const API_BASE_URL: &str = "https://lrclib.net/api";
+
+/// A song in an LRCLIB response.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LrclibTrack {
+    #[serde(default)]
+    instrumental: bool,
+    duration: Option<f64>,
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+impl LrclibTrack {
+    /// Convert to lyrics, preferring synced ones.
+    fn into_lyrics(self, artist: &str, song: &str) -> Option<Lyrics> {
+        if self.instrumental {
+            return Some(Lyrics::instrumental(artist, song, self.duration));
+        }
+        if let Some(lrc) = self.synced_lyrics.filter(|lrc| !lrc.trim().is_empty()) {
+            return Some(parser::parse_lrc(&lrc, artist, song, self.duration));
+        }
+        let plain = self.plain_lyrics.filter(|text| !text.trim().is_empty())?;
+        Some(Lyrics::plain(artist, song, self.duration, &plain))
+    }
+}
+
+/// Lyrics from LRCLIB.
+#[derive(Debug, Clone)]
+pub struct Lrclib {
+    client: Client,
+    base_url: String,
+}
+
+impl Default for Lrclib {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lrclib {
+    /// Create a provider for the public LRCLIB API.
+    pub fn new() -> Self {
+        Self::with_base_url(API_BASE_URL)
+    }
+
+    /// Create a provider for an LRCLIB instance, or a proxy, at `base_url`.
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self {
+            client: http_client(DEFAULT_USER_AGENT),
+            base_url: base_url.into(),
+        }
+    }
+
+    /// Get the exact match, or the search results best first.
+    async fn lookup(&self, query: &LyricsQuery<'_>) -> Result<Vec<LrclibTrack>, LyricsError> {
+        let artist = urlencoding::encode(query.artist);
+        let song = urlencoding::encode(query.song);
+        let url = match (query.album, query.duration) {
+            (Some(album), Some(duration)) => format!(
+                "{}/get?artist_name={artist}&track_name={song}&album_name={}&duration={duration:.0}",
+                self.base_url,
+                urlencoding::encode(album)
+            ),
+            _ => format!(
+                "{}/search?artist_name={artist}&track_name={song}",
+                self.base_url
+            ),
+        };
+        debug!("Requesting: {}", url);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| LyricsError::ProviderUnavailable(e.to_string()))?;
+        if let Some(e) = rate_limit_error(&response) {
+            return Err(e);
+        }
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(LyricsError::ProviderUnavailable(format!(
+                "LRCLIB returned {status}: {body}"
+            )));
+        }
+
+        let body = response
+            .text()
+            .await
+            .map_err(|e| LyricsError::ProviderUnavailable(e.to_string()))?;
+        if query.album.is_some() && query.duration.is_some() {
+            return serde_json::from_str(&body)
+                .map(|track| vec![track])
+                .map_err(|e| LyricsError::ParseFailed(e.to_string()));
+        }
+        let mut results: Vec<LrclibTrack> =
+            serde_json::from_str(&body).map_err(|e| LyricsError::ParseFailed(e.to_string()))?;
+        rank_results(&mut results, query.duration);
+        Ok(results)
+    }
+}
+
+/// Sort search results with synced lyrics closest to the duration first,
+/// keeping the order of equally good ones.
+fn rank_results(results: &mut [LrclibTrack], duration: Option<f64>) {
+    let score = |track: &LrclibTrack| {
+        let synced = if track.synced_lyrics.is_some() {
+            1.0
+        } else {
+            0.0
+        };
+        synced + matching::duration_score(duration, track.duration)
+    };
+    results.sort_by(|a, b| score(b).total_cmp(&score(a)));
+}
+
+#[async_trait]
+impl LyricsProvider for Lrclib {
+    fn name(&self) -> &'static str {
+        "LRCLIB"
+    }
+
+    async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+        let variants = self.fetch_variants(query).await?;
+        variants.into_iter().next().ok_or_else(|| {
+            LyricsError::NotFound(format!("No lyrics for {} - {}", query.artist, query.song))
+        })
+    }
+
+    async fn fetch_variants(&self, query: &LyricsQuery<'_>) -> Result<Vec<Lyrics>, LyricsError> {
+        let mut variants: Vec<Lyrics> = Vec::new();
+        for track in self.lookup(query).await? {
+            let Some(lyrics) = track.into_lyrics(query.artist, query.song) else {
+                continue;
+            };
+            // Results in the same script are copies of the best one
+            let script = lyrics.script();
+            if variants.iter().all(|variant| variant.script() != script) {
+                variants.push(lyrics);
+            }
+        }
+        let Some(lyrics) = variants.first() else {
+            return Err(LyricsError::NotFound(format!(
+                "No lyrics for {} - {}",
+                query.artist, query.song
+            )));
+        };
+        info!(
+            "Fetched {} lyric lines for {} - {} from LRCLIB, in {} versions",
+            lyrics.lines.len(),
+            query.artist,
+            query.song,
+            variants.len()
+        );
+        Ok(variants)
+    }
+
+    fn with_user_agent(&self, user_agent: &str) -> Option<Arc<dyn LyricsProvider>> {
+        Some(Arc::new(Self {
+            client: http_client(user_agent),
+            ..self.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synced_lyrics_are_preferred() -> Result<(), LyricsError> {
+        let track: LrclibTrack = serde_json::from_str(
+            r#"{
+                "instrumental": false,
+                "duration": 30.0,
+                "plainLyrics": "Hello\nWorld",
+                "syncedLyrics": "[00:01.00] Hello\n[00:05.50] World"
+            }"#,
+        )?;
+        let lyrics = track
+            .into_lyrics("Artist", "Song")
+            .ok_or(LyricsError::NotFound("No lyrics".into()))?;
+        assert_eq!(lyrics.lines.len(), 2);
+        assert!((lyrics.lines[1].start - 5.5).abs() < 0.001);
+        assert!((lyrics.lines[1].end - 30.0).abs() < 0.001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_and_instrumental_tracks() -> Result<(), LyricsError> {
+        let plain = LrclibTrack {
+            plain_lyrics: Some("Hello\n\nWorld\n".to_string()),
+            ..LrclibTrack::default()
+        };
+        let lyrics = plain
+            .into_lyrics("Artist", "Song")
+            .ok_or(LyricsError::NotFound("No lyrics".into()))?;
+        assert_eq!(lyrics.plain_text(), "Hello\nWorld");
+        assert_eq!(lyrics.line_at(1.0), None);
+
+        let instrumental = LrclibTrack {
+            instrumental: true,
+            ..LrclibTrack::default()
+        };
+        assert!(instrumental
+            .into_lyrics("Artist", "Song")
+            .is_some_and(|lyrics| lyrics.is_instrumental()));
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+//! Matching `YouTube` tracks to songs in lyrics databases.
+//!
+//! Video titles carry noise like "(Official Video)", "feat. X" or
+//! "Remastered 2011" that lyrics databases leave out, and topic and VEVO
+//! channels name artists their own way. Lookups try several cleaned-up
+//! artist and title pairs, and results are scored by how close their
+//! duration is to the track's.
+
+/// Words marking a bracketed or trailing part of a title as noise.
+const NOISE_WORDS: &[&str] = &[
+    "official",
+    "video",
+    "audio",
+    "lyric",
+    "lyrics",
+    "visualizer",
+    "visualiser",
+    "remaster",
+    "remastered",
+    "mv",
+    "hd",
+    "hq",
+    "4k",
+    "explicit",
+    "feat",
+    "ft",
+    "featuring",
+    "prod",
+];
+
+/// Markers of featured artists in a title, in lowercase.
+const FEATURE_MARKERS: &[&str] = &[" feat. ", " feat ", " ft. ", " ft ", " featuring "];
+
+/// Separators between artists in an artist name, in lowercase.
+const ARTIST_SEPARATORS: &[&str] = &[
+    ", ",
+    " & ",
+    " x ",
+    " feat. ",
+    " ft. ",
+    " featuring ",
+    " and ",
+];
+
+/// Seconds apart at which durations no longer match.
+pub const MAX_DURATION_DIFF_SECS: f64 = 20.0;
+
+/// Whether part of a title is noise, such as "Official Video".
+fn is_noise(text: &str) -> bool {
+    text.to_ascii_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| NOISE_WORDS.contains(&word))
+}
+
+/// Collapse runs of whitespace and trim separators left at the ends.
+fn tidy(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| c == '-' || c == '|' || c.is_whitespace())
+        .to_string()
+}
+
+/// Remove noise from a video title, leaving the song title.
+pub fn clean_title(title: &str) -> String {
+    let mut cleaned = String::with_capacity(title.len());
+    let mut rest = title;
+    while let Some(open) = rest.find(['(', '[']) {
+        let close = if rest[open..].starts_with('(') {
+            ')'
+        } else {
+            ']'
+        };
+        let Some(len) = rest[open..].find(close) else {
+            break;
+        };
+        cleaned.push_str(&rest[..open]);
+        let part = &rest[open..=open + len];
+        if !is_noise(&part[1..part.len() - 1]) {
+            cleaned.push_str(part);
+        }
+        rest = &rest[open + len + 1..];
+    }
+    cleaned.push_str(rest);
+
+    // "Song | Official Video"
+    if let Some(index) = cleaned.find(" | ") {
+        cleaned.truncate(index);
+    }
+    // ASCII lowercasing keeps byte offsets the same
+    let lower = cleaned.to_ascii_lowercase();
+    if let Some(index) = FEATURE_MARKERS
+        .iter()
+        .filter_map(|marker| lower.find(marker))
+        .min()
+    {
+        cleaned.truncate(index);
+    }
+    // "Song - Remastered 2011"
+    if let Some(index) = cleaned.rfind(" - ") {
+        if is_noise(&cleaned[index + 3..]) {
+            cleaned.truncate(index);
+        }
+    }
+    tidy(&cleaned)
+}
+
+/// Remove channel suffixes from an artist name.
+pub fn clean_artist(artist: &str) -> String {
+    let artist = artist.trim();
+    let artist = artist.strip_suffix(" - Topic").unwrap_or(artist);
+    let artist = artist.strip_suffix("VEVO").unwrap_or(artist);
+    tidy(artist)
+}
+
+/// Get the first of several artists, such as "A" of "A & B".
+pub fn primary_artist(artist: &str) -> String {
+    let lower = artist.to_ascii_lowercase();
+    let end = ARTIST_SEPARATORS
+        .iter()
+        .filter_map(|separator| lower.find(separator))
+        .min()
+        .unwrap_or(artist.len());
+    tidy(&artist[..end])
+}
+
+/// Get the artist and title pairs to look up, best first.
+pub fn candidates(artist: &str, title: &str) -> Vec<(String, String)> {
+    let artist_clean = clean_artist(artist);
+    let mut title_clean = clean_title(title);
+    let mut pairs = Vec::new();
+
+    // "Artist - Song" titles, with the artist known or not
+    if let Some(index) = title_clean.find(" - ") {
+        let (left, right) = (&title_clean[..index], &title_clean[index + 3..]);
+        if left.eq_ignore_ascii_case(&artist_clean) {
+            title_clean = right.to_string();
+        } else {
+            pairs.push((clean_artist(left), right.to_string()));
+        }
+    }
+    pairs.insert(0, (artist_clean.clone(), title_clean.clone()));
+    pairs.push((primary_artist(&artist_clean), title_clean));
+    pairs.push((artist.to_string(), title.to_string()));
+
+    let mut unique: Vec<(String, String)> = Vec::with_capacity(pairs.len());
+    for pair in pairs {
+        let duplicate = unique
+            .iter()
+            .any(|(a, t)| a.eq_ignore_ascii_case(&pair.0) && t.eq_ignore_ascii_case(&pair.1));
+        if !duplicate && !pair.0.is_empty() && !pair.1.is_empty() {
+            unique.push(pair);
+        }
+    }
+    unique
+}
+
+/// Score how well a duration matches the expected one, from 0.0 to 1.0.
+///
+/// Unknown durations score 0.5, below any close match.
+pub fn duration_score(expected: Option<f64>, actual: Option<f64>) -> f64 {
+    match (expected, actual) {
+        (Some(expected), Some(actual)) => {
+            (1.0 - (expected - actual).abs() / MAX_DURATION_DIFF_SECS).clamp(0.0, 1.0)
+        }
+        _ => 0.5,
+    }
+}
+
+/// Whether a duration could be the expected one, or either is unknown.
+pub fn duration_matches(expected: Option<f64>, actual: Option<f64>) -> bool {
+    duration_score(expected, actual) > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_titles_are_cleaned() {
+        let cases = [
+            ("Shape of You (Official Video)", "Shape of You"),
+            ("Bohemian Rhapsody (Remastered 2011)", "Bohemian Rhapsody"),
+            ("Hey Jude - Remastered 2015", "Hey Jude"),
+            ("Señorita [Official Music Video]", "Señorita"),
+            ("Stay feat. Justin Bieber", "Stay"),
+            ("Song (feat. Someone) [Lyrics]", "Song"),
+            (
+                "All Too Well (Taylor's Version)",
+                "All Too Well (Taylor's Version)",
+            ),
+            ("Dynamite | Official MV", "Dynamite"),
+        ];
+        for (title, cleaned) in cases {
+            assert_eq!(clean_title(title), cleaned, "{title}");
+        }
+        assert_eq!(clean_artist("Adele - Topic"), "Adele");
+        assert_eq!(clean_artist("AdeleVEVO"), "Adele");
+        assert_eq!(primary_artist("Daft Punk & Pharrell Williams"), "Daft Punk");
+    }
+
+    #[test]
+    fn test_candidates_and_durations() {
+        let pairs = candidates(
+            "Ed Sheeran - Topic",
+            "Ed Sheeran - Perfect (Official Video)",
+        );
+        assert_eq!(pairs[0], ("Ed Sheeran".to_string(), "Perfect".to_string()));
+
+        // Uploads by other channels name the artist in the title
+        let pairs = candidates("Some Channel", "Queen - Don't Stop Me Now");
+        assert!(pairs.contains(&("Queen".to_string(), "Don't Stop Me Now".to_string())));
+
+        assert!((duration_score(Some(200.0), Some(200.0)) - 1.0).abs() < 0.001);
+        assert!(duration_matches(Some(200.0), Some(210.0)));
+        assert!(!duration_matches(Some(200.0), Some(260.0)));
+        assert!(duration_matches(None, Some(260.0)));
+    }
+}
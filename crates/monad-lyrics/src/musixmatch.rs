@@ -0,0 +1,383 @@
+//! Musixmatch, with a user-supplied token.
+//!
+//! Musixmatch has the widest coverage of non-English songs. Tracks with
+//! richsync get word timings; others fall back to line-synced subtitles.
+//! Community translations are added when a query asks for a language. Only
+//! built with the `musixmatch` feature.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::provider::{LyricsProvider, LyricsQuery};
+use crate::{
+    http_client, parser, rate_limit_error, urlencoding, LyricLine, LyricWord, Lyrics, LyricsError,
+    LyricsKind, DEFAULT_USER_AGENT,
+};
+
+const API_BASE_URL: &str = "https://apic-desktop.musixmatch.com/ws/1.1";
+
+/// App ID the desktop API expects alongside user tokens.
+const APP_ID: &str = "web-desktop-app-v1.0";
+
+/// A line of richsync lyrics.
+#[derive(Debug, Deserialize)]
+struct RichsyncLine {
+    /// Start time in seconds.
+    ts: f64,
+    /// End time in seconds.
+    te: f64,
+    /// Words and the spaces between them.
+    l: Vec<RichsyncPart>,
+    /// Full line text.
+    x: String,
+}
+
+/// A word or space of a richsync line.
+#[derive(Debug, Deserialize)]
+struct RichsyncPart {
+    /// Text.
+    c: String,
+    /// Offset from the start of the line in seconds.
+    o: f64,
+}
+
+/// Parse richsync lyrics into lines with word timings.
+fn parse_richsync(body: &str) -> Result<Vec<LyricLine>, LyricsError> {
+    let lines: Vec<RichsyncLine> = serde_json::from_str(body)
+        .map_err(|e| LyricsError::ParseFailed(format!("Invalid richsync: {e}")))?;
+    Ok(lines
+        .into_iter()
+        .filter(|line| !line.x.trim().is_empty())
+        .map(|line| {
+            let words = line
+                .l
+                .iter()
+                .enumerate()
+                .filter(|(_, part)| !part.c.trim().is_empty())
+                .map(|(i, part)| LyricWord {
+                    text: part.c.clone(),
+                    start: line.ts + part.o,
+                    end: line.l.get(i + 1).map_or(line.te, |next| line.ts + next.o),
+                })
+                .collect();
+            LyricLine {
+                text: line.x.trim().to_string(),
+                start: line.ts,
+                end: line.te,
+                words,
+                romanized: None,
+                translation: None,
+            }
+        })
+        .collect())
+}
+
+/// Key matching a translated snippet to its line.
+fn snippet_key(text: &str) -> String {
+    text.trim().to_lowercase()
+}
+
+/// Parse translations into a map from original snippet to translated text.
+fn parse_translations(body: &Value) -> HashMap<String, String> {
+    body.get("translations_list")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let snippet = item.pointer("/translation/snippet")?.as_str()?;
+            let text = item.pointer("/translation/description")?.as_str()?;
+            Some((snippet_key(snippet), text.trim().to_string()))
+        })
+        .filter(|(_, text)| !text.is_empty())
+        .collect()
+}
+
+/// Add translations to the lines they belong to.
+fn translate_lines(lines: &mut [LyricLine], translations: &HashMap<String, String>) {
+    for line in lines {
+        line.translation = translations.get(&snippet_key(&line.text)).cloned();
+    }
+}
+
+/// Get the body of a response, or the error its status code stands for.
+fn response_body<'a>(message: &'a Value, what: &str) -> Result<&'a Value, LyricsError> {
+    let status = message
+        .pointer("/header/status_code")
+        .and_then(Value::as_u64)
+        .unwrap_or_default();
+    match status {
+        200 => message.get("body").ok_or_else(|| {
+            LyricsError::ParseFailed(format!("Musixmatch {what} response has no body"))
+        }),
+        404 => Err(LyricsError::NotFound(format!("Musixmatch has no {what}"))),
+        401 => Err(LyricsError::ProviderUnavailable(
+            "Musixmatch rejected the token or is rate limiting it".to_string(),
+        )),
+        status => Err(LyricsError::ProviderUnavailable(format!(
+            "Musixmatch {what} request returned {status}"
+        ))),
+    }
+}
+
+/// Lyrics from Musixmatch.
+#[derive(Debug, Clone)]
+pub struct Musixmatch {
+    client: Client,
+    token: String,
+    base_url: String,
+}
+
+impl Musixmatch {
+    /// Create a provider authenticating with a Musixmatch user token.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            client: http_client(DEFAULT_USER_AGENT),
+            token: token.into(),
+            base_url: API_BASE_URL.to_string(),
+        }
+    }
+
+    /// Call an API method, returning its response message.
+    async fn call(&self, method: &str, params: &[(&str, &str)]) -> Result<Value, LyricsError> {
+        let mut url = format!(
+            "{}/{method}?format=json&app_id={APP_ID}&usertoken={}",
+            self.base_url,
+            urlencoding::encode(&self.token)
+        );
+        for (key, value) in params {
+            use std::fmt::Write;
+            let _ = write!(url, "&{key}={}", urlencoding::encode(value));
+        }
+        debug!("Requesting Musixmatch {method}");
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| LyricsError::ProviderUnavailable(e.to_string()))?;
+        if let Some(e) = rate_limit_error(&response) {
+            return Err(e);
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|e| LyricsError::ProviderUnavailable(e.to_string()))?;
+        let mut json: Value = response
+            .json()
+            .await
+            .map_err(|e| LyricsError::ParseFailed(e.to_string()))?;
+        Ok(json.get_mut("message").map(Value::take).unwrap_or_default())
+    }
+
+    /// Get the word timings of a track, if it has richsync.
+    async fn richsync(&self, commontrack_id: &str) -> Result<Vec<LyricLine>, LyricsError> {
+        let message = self
+            .call("track.richsync.get", &[("commontrack_id", commontrack_id)])
+            .await?;
+        let body = response_body(&message, "richsync")?
+            .pointer("/richsync/richsync_body")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                LyricsError::ParseFailed("Musixmatch richsync has no body".to_string())
+            })?;
+        parse_richsync(body)
+    }
+
+    /// Get the translations of a track's lines into `language`.
+    async fn translations(
+        &self,
+        commontrack_id: &str,
+        language: &str,
+    ) -> Result<HashMap<String, String>, LyricsError> {
+        let message = self
+            .call(
+                "crowd.track.translations.get",
+                &[
+                    ("commontrack_id", commontrack_id),
+                    ("selected_language", language),
+                    ("comment_format", "text"),
+                    ("part", "user"),
+                ],
+            )
+            .await?;
+        Ok(parse_translations(response_body(&message, "translations")?))
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for Musixmatch {
+    fn name(&self) -> &'static str {
+        "Musixmatch"
+    }
+
+    async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+        let duration = query.duration.map(|d| format!("{d:.0}"));
+        let mut params = vec![
+            ("namespace", "lyrics_richsynched"),
+            ("subtitle_format", "lrc"),
+            ("q_artist", query.artist),
+            ("q_track", query.song),
+        ];
+        if let Some(album) = query.album {
+            params.push(("q_album", album));
+        }
+        if let Some(duration) = &duration {
+            params.push(("q_duration", duration));
+        }
+        let message = self.call("macro.subtitles.get", &params).await?;
+        let calls = response_body(&message, "match")?
+            .get("macro_calls")
+            .ok_or_else(|| {
+                LyricsError::ParseFailed("Musixmatch response has no calls".to_string())
+            })?;
+
+        let track = calls
+            .pointer("/matcher.track.get/message")
+            .map_or(Err(LyricsError::NotFound("No match".to_string())), |m| {
+                response_body(m, "track")
+            })?
+            .get("track")
+            .ok_or_else(|| LyricsError::NotFound("No match".to_string()))?;
+        let length = track.get("track_length").and_then(Value::as_f64);
+        if track.get("instrumental").and_then(Value::as_u64) == Some(1) {
+            info!(
+                "{} - {} is instrumental on Musixmatch",
+                query.artist, query.song
+            );
+            return Ok(Lyrics::instrumental(
+                query.artist,
+                query.song,
+                length.or(query.duration),
+            ));
+        }
+
+        let mut lines = Vec::new();
+        let commontrack_id = track.get("commontrack_id").and_then(Value::as_u64);
+        if let (Some(id), Some(1)) = (
+            commontrack_id,
+            track.get("has_richsync").and_then(Value::as_u64),
+        ) {
+            match self.richsync(&id.to_string()).await {
+                Ok(richsync) => lines = richsync,
+                Err(e) => debug!("Falling back to Musixmatch subtitles: {e}"),
+            }
+        }
+        if lines.is_empty() {
+            let subtitles = calls.pointer("/track.subtitles.get/message").map_or(
+                Err(LyricsError::NotFound("No subtitles".to_string())),
+                |m| response_body(m, "subtitles"),
+            )?;
+            let lrc = subtitles
+                .pointer("/subtitle_list/0/subtitle/subtitle_body")
+                .and_then(Value::as_str)
+                .ok_or_else(|| {
+                    LyricsError::NotFound(format!(
+                        "No synced lyrics for {} - {}",
+                        query.artist, query.song
+                    ))
+                })?;
+            lines = parser::parse_lrc(lrc, query.artist, query.song, length).lines;
+        }
+
+        let language = calls
+            .pointer("/track.subtitles.get/message/body/subtitle_list/0/subtitle/subtitle_language")
+            .and_then(Value::as_str)
+            .filter(|language| !language.is_empty())
+            .map(str::to_string);
+
+        if let (Some(language), Some(id)) = (query.translation_language, commontrack_id) {
+            // Lyrics without translations are still worth showing
+            match self.translations(&id.to_string(), language).await {
+                Ok(translations) => translate_lines(&mut lines, &translations),
+                Err(e) => debug!("No Musixmatch translations into {language}: {e}"),
+            }
+        }
+
+        info!(
+            "Fetched {} lyric lines for {} - {} from Musixmatch",
+            lines.len(),
+            query.artist,
+            query.song
+        );
+        Ok(Lyrics {
+            title: query.song.to_string(),
+            artist: query.artist.to_string(),
+            duration: length.or(query.duration),
+            kind: LyricsKind::Synced,
+            offset_ms: 0,
+            translation_language: None,
+            language,
+            lines,
+        })
+    }
+
+    fn with_user_agent(&self, user_agent: &str) -> Option<Arc<dyn LyricsProvider>> {
+        Some(Arc::new(Self {
+            client: http_client(user_agent),
+            ..self.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_richsync_words() -> Result<(), LyricsError> {
+        let body = r#"[
+            {"ts": 10.0, "te": 12.0, "l": [
+                {"c": "Hello", "o": 0.0}, {"c": " ", "o": 0.5}, {"c": "world", "o": 0.6}
+            ], "x": "Hello world"},
+            {"ts": 12.0, "te": 13.0, "l": [], "x": ""}
+        ]"#;
+        let lines = parse_richsync(body)?;
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "Hello world");
+        let words: Vec<_> = lines[0].words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(words, ["Hello", "world"]);
+        assert!((lines[0].words[0].end - 10.5).abs() < 0.001);
+        assert!((lines[0].words[1].start - 10.6).abs() < 0.001);
+        assert!((lines[0].words[1].end - 12.0).abs() < 0.001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_translations_match_lines() -> Result<(), LyricsError> {
+        let body = serde_json::json!({"translations_list": [
+            {"translation": {"snippet": "Hola mundo ", "description": "Hello world"}},
+            {"translation": {"snippet": "Adiós", "description": ""}}
+        ]});
+        let mut lines = parse_richsync(
+            r#"[
+                {"ts": 1.0, "te": 2.0, "l": [], "x": "hola mundo"},
+                {"ts": 2.0, "te": 3.0, "l": [], "x": "Adiós"}
+            ]"#,
+        )?;
+        translate_lines(&mut lines, &parse_translations(&body));
+        assert_eq!(lines[0].translation.as_deref(), Some("Hello world"));
+        assert_eq!(lines[1].translation, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_codes_map_to_errors() {
+        let message =
+            |status: u64| serde_json::json!({"header": {"status_code": status}, "body": {}});
+        assert!(response_body(&message(200), "track").is_ok());
+        assert!(matches!(
+            response_body(&message(404), "track"),
+            Err(LyricsError::NotFound(_))
+        ));
+        assert!(matches!(
+            response_body(&message(401), "track"),
+            Err(LyricsError::ProviderUnavailable(_))
+        ));
+    }
+}
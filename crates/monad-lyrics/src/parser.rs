@@ -1,19 +1,19 @@
-//! TTML parser for lyrics.
+//! TTML and LRC parsers for lyrics.
 
-use crate::{LyricLine, LyricWord, Lyrics};
-use monad_core::Error;
+use crate::{LyricLine, LyricWord, Lyrics, LyricsError, LyricsKind};
 use quick_xml::events::Event;
 use quick_xml::Reader;
 use tracing::debug;
 
 /// Parse TTML lyrics into our Lyrics structure.
-pub fn parse_ttml(ttml: &str, artist: &str, song: &str) -> Result<Lyrics, Error> {
+pub fn parse_ttml(ttml: &str, artist: &str, song: &str) -> Result<Lyrics, LyricsError> {
     let mut reader = Reader::from_str(ttml);
     // Don't trim text - we need spaces between spans
     reader.config_mut().trim_text(false);
 
     let mut lines = Vec::new();
     let mut duration = None;
+    let mut language = None;
 
     // Parse the body duration if present
     if let Some(dur_str) = extract_body_duration(ttml) {
@@ -32,6 +32,15 @@ pub fn parse_ttml(ttml: &str, artist: &str, song: &str) -> Result<Lyrics, Error>
                 let name_str = std::str::from_utf8(name.as_ref()).unwrap_or("");
 
                 match name_str {
+                    "tt" => {
+                        // The language the lines are written in, like xml:lang="ja"
+                        language = e
+                            .try_get_attribute("xml:lang")
+                            .ok()
+                            .flatten()
+                            .and_then(|attr| String::from_utf8(attr.value.into_owned()).ok())
+                            .filter(|lang| !lang.is_empty());
+                    }
                     "p" => {
                         // Start of a lyric line
                         let mut line = TtmlLine::default();
@@ -68,9 +77,11 @@ pub fn parse_ttml(ttml: &str, artist: &str, song: &str) -> Result<Lyrics, Error>
                 if in_span {
                     current_word.text.push_str(&text);
                 } else if let Some(ref mut line) = current_line {
-                    // Text between spans (spaces) or directly in <p>
-                    // Only add if it contains a space (not just newlines/indentation)
-                    if text.contains(' ') {
+                    if !text.trim().is_empty() {
+                        // Line-timed lyrics have their text directly in <p>
+                        line.text.push_str(&text);
+                    } else if text.contains(' ') {
+                        // Text between spans (spaces), not just newlines/indentation
                         line.text.push(' ');
                     }
                 }
@@ -103,6 +114,8 @@ pub fn parse_ttml(ttml: &str, artist: &str, song: &str) -> Result<Lyrics, Error>
                                     start: line.start,
                                     end: line.end,
                                     words: line.words,
+                                    romanized: None,
+                                    translation: None,
                                 });
                             }
                         }
@@ -124,10 +137,87 @@ pub fn parse_ttml(ttml: &str, artist: &str, song: &str) -> Result<Lyrics, Error>
         title: song.to_string(),
         artist: artist.to_string(),
         duration,
+        kind: LyricsKind::Synced,
+        offset_ms: 0,
+        translation_language: None,
+        language,
         lines,
     })
 }
 
+/// Seconds the last line of synced lyrics lasts when the song duration is unknown.
+const LAST_LINE_SECS: f64 = 5.0;
+
+/// Parse line-synced LRC lyrics, such as `[01:02.50] text`.
+///
+/// Each line ends where the next starts. Metadata tags like `[ar:Artist]`
+/// are skipped, and a line may carry several timestamps.
+pub fn parse_lrc(lrc: &str, artist: &str, song: &str, duration: Option<f64>) -> Lyrics {
+    let mut entries = Vec::new();
+    for line in lrc.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some((tag, text)) = tag.split_once(']') else {
+                break;
+            };
+            rest = text;
+            if tag.starts_with(|c: char| c.is_ascii_digit()) {
+                times.push(parse_time(tag));
+            }
+        }
+        for time in times {
+            entries.push((time, rest.trim().to_string()));
+        }
+    }
+    timed_lyrics(entries, artist, song, duration)
+}
+
+/// Build synced lyrics from lines and their start times in seconds.
+///
+/// Each line ends where the next starts, and empty lines only end the one
+/// before them.
+pub fn timed_lyrics(
+    mut entries: Vec<(f64, String)>,
+    artist: &str,
+    song: &str,
+    duration: Option<f64>,
+) -> Lyrics {
+    entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Empty lines are kept until here, as they end the line before them
+    let lines = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, text))| !text.is_empty())
+        .map(|(i, (start, text))| {
+            let end = entries.get(i + 1).map_or_else(
+                || duration.map_or(start + LAST_LINE_SECS, |d| d.max(*start)),
+                |next| next.0,
+            );
+            LyricLine {
+                text: text.clone(),
+                start: *start,
+                end,
+                words: Vec::new(),
+                romanized: None,
+                translation: None,
+            }
+        })
+        .collect();
+
+    Lyrics {
+        title: song.to_string(),
+        artist: artist.to_string(),
+        duration,
+        kind: LyricsKind::Synced,
+        offset_ms: 0,
+        translation_language: None,
+        language: None,
+        lines,
+    }
+}
+
 /// Temporary struct for parsing a line.
 #[derive(Default)]
 struct TtmlLine {
@@ -192,6 +282,26 @@ mod tests {
         assert!((parse_time("0") - 0.0).abs() < 0.001);
     }
 
+    #[test]
+    fn test_parse_lrc() {
+        let lrc = "[ar:Test Artist]\n[00:01.00] Hello\n[00:03.00]\n[00:04.50][00:08.00] World\n";
+
+        let lyrics = parse_lrc(lrc, "Test Artist", "Test Song", None);
+        let lines: Vec<_> = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.text.as_str(), line.start, line.end))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("Hello", 1.0, 3.0),
+                ("World", 4.5, 8.0),
+                ("World", 8.0, 8.0 + LAST_LINE_SECS)
+            ]
+        );
+    }
+
     #[test]
     fn test_parse_simple_ttml() {
         let ttml = r#"
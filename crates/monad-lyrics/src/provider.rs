@@ -0,0 +1,110 @@
+//! Sources of lyrics.
+//!
+//! A [`LyricsProvider`] looks up the lyrics of a song in one service. The
+//! [`LyricsClient`](crate::LyricsClient) chains providers, trying each in
+//! order until one has the song.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{Lyrics, LyricsError};
+
+/// The song to find lyrics for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LyricsQuery<'a> {
+    /// Artist name.
+    pub artist: &'a str,
+    /// Song title.
+    pub song: &'a str,
+    /// Album name, which improves matching.
+    pub album: Option<&'a str>,
+    /// Duration in seconds, which improves matching.
+    pub duration: Option<f64>,
+    /// `YouTube` video ID of the track, for providers looking up the exact version.
+    pub video_id: Option<&'a str>,
+    /// Language code to translate lines into, for providers with translations.
+    pub translation_language: Option<&'a str>,
+    /// Path of a local audio file, for providers reading lyrics stored with it.
+    pub path: Option<&'a Path>,
+}
+
+impl<'a> LyricsQuery<'a> {
+    /// Create a query for a song by an artist.
+    pub const fn new(artist: &'a str, song: &'a str) -> Self {
+        Self {
+            artist,
+            song,
+            album: None,
+            duration: None,
+            video_id: None,
+            translation_language: None,
+            path: None,
+        }
+    }
+
+    /// Set the album name.
+    #[must_use]
+    pub const fn with_album(mut self, album: &'a str) -> Self {
+        self.album = Some(album);
+        self
+    }
+
+    /// Set the duration in seconds.
+    #[must_use]
+    pub const fn with_duration(mut self, duration: f64) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Set the `YouTube` video ID of the track.
+    #[must_use]
+    pub const fn with_video_id(mut self, video_id: &'a str) -> Self {
+        self.video_id = Some(video_id);
+        self
+    }
+
+    /// Ask for lines translated into a language, such as `en`.
+    #[must_use]
+    pub const fn with_translation_language(mut self, language: &'a str) -> Self {
+        self.translation_language = Some(language);
+        self
+    }
+
+    /// Set the path of the local audio file played.
+    #[must_use]
+    pub const fn with_path(mut self, path: &'a Path) -> Self {
+        self.path = Some(path);
+        self
+    }
+}
+
+/// A source of lyrics.
+#[async_trait]
+pub trait LyricsProvider: Send + Sync {
+    /// Name of the provider, for logging and diagnostics.
+    fn name(&self) -> &str;
+
+    /// Fetch the lyrics of a song.
+    ///
+    /// Returns [`LyricsError::NotFound`] if the provider does not have the
+    /// song, so the next provider is tried without counting a failure.
+    async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError>;
+
+    /// Fetch every version of a song's lyrics the provider has, such as in
+    /// other languages or scripts, best first.
+    ///
+    /// Providers with a single version per song can leave this to [`fetch`].
+    ///
+    /// [`fetch`]: LyricsProvider::fetch
+    async fn fetch_variants(&self, query: &LyricsQuery<'_>) -> Result<Vec<Lyrics>, LyricsError> {
+        Ok(vec![self.fetch(query).await?])
+    }
+
+    /// Get a copy of the provider identifying itself as `user_agent`, if it
+    /// makes HTTP requests of its own.
+    fn with_user_agent(&self, _user_agent: &str) -> Option<Arc<dyn LyricsProvider>> {
+        None
+    }
+}
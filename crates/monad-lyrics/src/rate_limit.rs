@@ -0,0 +1,82 @@
+//! Client-side rate limiting of lyrics providers.
+//!
+//! Each provider gets at most one request per interval, so skipping through
+//! tracks or retrying does not hammer public APIs. A provider can also be
+//! held off for a while, as a `Retry-After` header asks.
+
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Default time between requests to one provider.
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Spaces out requests to a provider.
+#[derive(Debug)]
+pub struct RateLimiter {
+    min_interval: Duration,
+    /// Earliest time the next request may be sent.
+    next: Mutex<Option<Instant>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_INTERVAL)
+    }
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing one request per `min_interval`.
+    pub const fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Create a limiter with the same interval and no requests reserved.
+    pub const fn renewed(&self) -> Self {
+        Self::new(self.min_interval)
+    }
+
+    /// Reserve a slot for a request, returning how long to wait for it, or
+    /// `None` without reserving if that is longer than `max_wait`.
+    pub fn reserve(&self, max_wait: Duration) -> Option<Duration> {
+        let now = Instant::now();
+        let mut next = self.next.lock();
+        let slot = next.map_or(now, |next| next.max(now));
+        let wait = slot - now;
+        if wait > max_wait {
+            return None;
+        }
+        *next = Some(slot + self.min_interval);
+        Some(wait)
+    }
+
+    /// Send no requests for `delay` from now.
+    pub fn hold_off(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut next = self.next.lock();
+        *next = Some(next.map_or(until, |next| next.max(until)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_spaced_out() {
+        let limiter = RateLimiter::new(Duration::from_secs(1));
+        assert_eq!(limiter.reserve(Duration::ZERO), Some(Duration::ZERO));
+
+        // The second request waits for the interval, unless that is too long
+        assert_eq!(limiter.reserve(Duration::from_millis(500)), None);
+        let wait = limiter.reserve(Duration::from_secs(2)).unwrap_or_default();
+        assert!(wait > Duration::from_millis(900));
+
+        limiter.hold_off(Duration::from_secs(30));
+        assert_eq!(limiter.reserve(Duration::from_secs(10)), None);
+        assert!(limiter.renewed().reserve(Duration::ZERO).is_some());
+    }
+}
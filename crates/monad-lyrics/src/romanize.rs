@@ -0,0 +1,283 @@
+//! Romanization of lyrics in non-Latin scripts.
+//!
+//! Hangul follows the Revised Romanization of Korean, kana follows Hepburn
+//! and Cyrillic a common English transliteration. Chinese characters,
+//! including kanji, need a dictionary and are left as written. Only built
+//! with the `romanize` feature.
+
+use crate::{LyricLine, Lyrics};
+
+/// First Hangul syllable.
+const HANGUL_FIRST: u32 = 0xAC00;
+/// Last Hangul syllable.
+const HANGUL_LAST: u32 = 0xD7A3;
+
+/// Initial consonants of Hangul syllables.
+const HANGUL_INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+
+/// Vowels of Hangul syllables.
+const HANGUL_VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+
+/// Final consonants of Hangul syllables, alone and carried over to a
+/// following syllable starting with a vowel.
+const HANGUL_FINALS: [(&str, &str); 28] = [
+    ("", ""),
+    ("k", "g"),
+    ("k", "kk"),
+    ("k", "ks"),
+    ("n", "n"),
+    ("n", "nj"),
+    ("n", "n"),
+    ("t", "d"),
+    ("l", "r"),
+    ("k", "lg"),
+    ("m", "lm"),
+    ("l", "lb"),
+    ("l", "ls"),
+    ("l", "lt"),
+    ("p", "lp"),
+    ("l", "r"),
+    ("m", "m"),
+    ("p", "b"),
+    ("p", "bs"),
+    ("t", "s"),
+    ("t", "ss"),
+    ("ng", "ng"),
+    ("t", "j"),
+    ("t", "ch"),
+    ("k", "k"),
+    ("t", "t"),
+    ("p", "p"),
+    ("t", ""),
+];
+
+/// Index of the silent initial ㅇ.
+const HANGUL_SILENT_INITIAL: u32 = 11;
+/// Index of the initial ㄹ.
+const HANGUL_RIEUL_INITIAL: u32 = 5;
+/// Index of the final ㄹ.
+const HANGUL_RIEUL_FINAL: u32 = 8;
+/// Index of the final ㅇ, which is never carried over.
+const HANGUL_NG_FINAL: u32 = 21;
+
+/// Hiragana from U+3041 to U+3096.
+const HIRAGANA: [&str; 86] = [
+    "a", "a", "i", "i", "u", "u", "e", "e", "o", "o", // ぁ-お
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go", // か-ご
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo", // さ-ぞ
+    "ta", "da", "chi", "ji", "", "tsu", "zu", "te", "de", "to", "do", // た-ど
+    "na", "ni", "nu", "ne", "no", // な-の
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo",
+    "po", // は-ぽ
+    "ma", "mi", "mu", "me", "mo", // ま-も
+    "ya", "ya", "yu", "yu", "yo", "yo", // ゃ-よ
+    "ra", "ri", "ru", "re", "ro", // ら-ろ
+    "wa", "wa", "i", "e", "o", "n", "vu", "ka", "ke", // ゎ-ゖ
+];
+
+/// First hiragana.
+const HIRAGANA_FIRST: u32 = 0x3041;
+/// Offset from katakana to the matching hiragana.
+const KATAKANA_OFFSET: u32 = 0x60;
+/// Small hiragana vowels, modifying the previous kana.
+const SMALL_VOWELS: [char; 5] = ['ぁ', 'ぃ', 'ぅ', 'ぇ', 'ぉ'];
+/// Small ya, yu and yo, combining with the previous kana.
+const SMALL_Y: [char; 3] = ['ゃ', 'ゅ', 'ょ'];
+
+/// Transliterate a lowercase Cyrillic letter.
+const fn cyrillic(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'ґ' => "g",
+        'д' => "d",
+        'е' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' => "i",
+        'і' => "i",
+        'ї' => "yi",
+        'й' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ў' => "w",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ы' => "y",
+        'э' => "e",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    })
+}
+
+/// Convert katakana to hiragana, leaving other characters alone.
+fn to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' => char::from_u32(c as u32 - KATAKANA_OFFSET).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Romanize a hiragana character, if it is one.
+fn hiragana(c: char) -> Option<&'static str> {
+    let index = (c as u32).checked_sub(HIRAGANA_FIRST)?;
+    HIRAGANA.get(usize::try_from(index).ok()?).copied()
+}
+
+/// Romanize text, or `None` if it has nothing to romanize.
+pub fn romanize(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().map(to_hiragana).collect();
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut changed = false;
+    // Set by a small tsu, doubling the next consonant
+    let mut double_next = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        let code = c as u32;
+        if (HANGUL_FIRST..=HANGUL_LAST).contains(&code) {
+            changed = true;
+            let index = code - HANGUL_FIRST;
+            let (initial, vowel, last) = (index / 588, (index % 588) / 28, index % 28);
+            let next = chars
+                .get(i + 1)
+                .map(|&n| n as u32)
+                .filter(|n| (HANGUL_FIRST..=HANGUL_LAST).contains(n))
+                .map(|n| (n - HANGUL_FIRST) / 588);
+            // A final consonant before a silent initial is pronounced with the next syllable
+            let previous_carried = i.checked_sub(1).and_then(|p| {
+                let p = chars[p] as u32;
+                (HANGUL_FIRST..=HANGUL_LAST)
+                    .contains(&p)
+                    .then(|| (p - HANGUL_FIRST) % 28)
+                    .filter(|&l| l != 0 && l != HANGUL_NG_FINAL)
+            });
+            match previous_carried {
+                Some(_) if initial == HANGUL_SILENT_INITIAL => {}
+                Some(HANGUL_RIEUL_FINAL) if initial == HANGUL_RIEUL_INITIAL => out.push('l'),
+                _ => out.push_str(HANGUL_INITIALS[initial as usize]),
+            }
+            out.push_str(HANGUL_VOWELS[vowel as usize]);
+            let (alone, carried) = HANGUL_FINALS[last as usize];
+            if next == Some(HANGUL_SILENT_INITIAL) && last != HANGUL_NG_FINAL {
+                out.push_str(carried);
+            } else {
+                out.push_str(alone);
+            }
+        } else if c == 'っ' {
+            changed = true;
+            double_next = true;
+        } else if c == 'ー' {
+            changed = true;
+            if let Some(vowel) = out.chars().last().filter(|v| "aeiou".contains(*v)) {
+                out.push(vowel);
+            }
+        } else if SMALL_Y.contains(&c) && out.ends_with('i') {
+            // き + ゃ is kya, し + ゃ is sha
+            changed = true;
+            out.pop();
+            let vowel = hiragana(c)
+                .and_then(|kana| kana.get(1..))
+                .unwrap_or_default();
+            if !(out.ends_with("sh") || out.ends_with("ch") || out.ends_with('j')) {
+                out.push('y');
+            }
+            out.push_str(vowel);
+        } else if SMALL_VOWELS.contains(&c) && out.ends_with(|v| "aeiou".contains(v)) {
+            // ふ + ぁ is fa
+            changed = true;
+            out.pop();
+            out.push_str(hiragana(c).unwrap_or_default());
+        } else if let Some(kana) = hiragana(c) {
+            changed = true;
+            if std::mem::take(&mut double_next) {
+                match kana {
+                    k if k.starts_with("ch") => out.push('t'),
+                    k => out.extend(k.chars().next()),
+                }
+            }
+            out.push_str(kana);
+        } else if let Some(latin) = c.to_lowercase().next().and_then(cyrillic) {
+            changed = true;
+            if c.is_uppercase() {
+                let mut latin = latin.chars();
+                out.extend(latin.next().map(|first| first.to_ascii_uppercase()));
+                out.push_str(latin.as_str());
+            } else {
+                out.push_str(latin);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    changed.then_some(out)
+}
+
+impl Lyrics {
+    /// Add the romanization of each line in a non-Latin script.
+    pub fn romanize(&mut self) {
+        for line in &mut self.lines {
+            line.romanize();
+        }
+    }
+}
+
+impl LyricLine {
+    /// Add the romanization of the line, if it is in a non-Latin script.
+    pub fn romanize(&mut self) {
+        self.romanized = romanize(&self.text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_romanize_scripts() {
+        let cases = [
+            ("사랑해", "saranghae"),
+            ("한국어", "hangugeo"),
+            ("몰라", "molla"),
+            ("ありがとう", "arigatou"),
+            ("ちょっと", "chotto"),
+            ("きょう", "kyou"),
+            ("コーヒー", "koohii"),
+            ("Привет мир", "Privet mir"),
+            ("Щастя", "Shchastya"),
+        ];
+        for (text, romanized) in cases {
+            assert_eq!(romanize(text).as_deref(), Some(romanized), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_latin_and_han_are_left_alone() {
+        assert_eq!(romanize("Hello world"), None);
+        assert_eq!(romanize("你好"), None);
+        assert_eq!(romanize("愛してる").as_deref(), Some("愛shiteru"));
+    }
+}
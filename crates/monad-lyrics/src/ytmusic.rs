@@ -0,0 +1,127 @@
+//! Lyrics from `YouTube` Music itself.
+//!
+//! Looked up by video ID, so they belong to the exact version of the track
+//! being played. Only queries with a video ID can be answered.
+
+use async_trait::async_trait;
+use monad_innertube::{InnerTubeClient, TrackLyrics};
+use tracing::info;
+
+use crate::provider::{LyricsProvider, LyricsQuery};
+use crate::{LyricLine, Lyrics, LyricsError, LyricsKind};
+
+/// Lyrics from the `YouTube` Music lyrics browse endpoint.
+#[derive(Clone)]
+pub struct YouTubeMusicLyrics {
+    client: InnerTubeClient,
+}
+
+impl YouTubeMusicLyrics {
+    /// Create a provider using its own `InnerTube` client.
+    pub fn new() -> Result<Self, LyricsError> {
+        Ok(Self::with_client(InnerTubeClient::new()?))
+    }
+
+    /// Create a provider sharing an `InnerTube` client.
+    pub const fn with_client(client: InnerTubeClient) -> Self {
+        Self { client }
+    }
+}
+
+/// Convert `YouTube` Music lyrics.
+#[allow(clippy::cast_precision_loss)]
+fn to_lyrics(lyrics: TrackLyrics, query: &LyricsQuery<'_>) -> Lyrics {
+    match lyrics {
+        TrackLyrics::Timed(lines) => Lyrics {
+            title: query.song.to_string(),
+            artist: query.artist.to_string(),
+            duration: query.duration,
+            kind: LyricsKind::Synced,
+            offset_ms: 0,
+            translation_language: None,
+            language: None,
+            lines: lines
+                .into_iter()
+                .filter(|line| !line.text.trim().is_empty())
+                .map(|line| LyricLine {
+                    text: line.text.trim().to_string(),
+                    start: line.start_ms as f64 / 1000.0,
+                    end: line.end_ms as f64 / 1000.0,
+                    words: Vec::new(),
+                    romanized: None,
+                    translation: None,
+                })
+                .collect(),
+        },
+        TrackLyrics::Plain(text) => Lyrics::plain(query.artist, query.song, query.duration, &text),
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for YouTubeMusicLyrics {
+    fn name(&self) -> &'static str {
+        "YouTube Music"
+    }
+
+    async fn fetch(&self, query: &LyricsQuery<'_>) -> Result<Lyrics, LyricsError> {
+        let not_found =
+            || LyricsError::NotFound(format!("No lyrics for {} - {}", query.artist, query.song));
+        let video_id = query.video_id.ok_or_else(not_found)?;
+        let lyrics = self
+            .client
+            .get_lyrics(video_id)
+            .await?
+            .map(|lyrics| to_lyrics(lyrics, query))
+            .filter(|lyrics| !lyrics.lines.is_empty())
+            .ok_or_else(not_found)?;
+        info!(
+            "Fetched {} lyric lines for {} - {} from YouTube Music",
+            lyrics.lines.len(),
+            query.artist,
+            query.song
+        );
+        Ok(lyrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monad_innertube::TimedLyricsLine;
+
+    #[test]
+    fn test_timed_lyrics_convert_to_seconds() {
+        let query = LyricsQuery::new("Artist", "Song");
+        let lyrics = to_lyrics(
+            TrackLyrics::Timed(vec![
+                TimedLyricsLine {
+                    text: "Hello".to_string(),
+                    start_ms: 1500,
+                    end_ms: 3000,
+                },
+                TimedLyricsLine {
+                    text: " ".to_string(),
+                    start_ms: 3000,
+                    end_ms: 4000,
+                },
+            ]),
+            &query,
+        );
+        assert!(lyrics.is_synced());
+        assert_eq!(lyrics.lines.len(), 1);
+        assert!((lyrics.lines[0].start - 1.5).abs() < 0.001);
+        assert!((lyrics.lines[0].end - 3.0).abs() < 0.001);
+
+        let lyrics = to_lyrics(TrackLyrics::Plain("Hello\n\nWorld".to_string()), &query);
+        assert_eq!(lyrics.kind, LyricsKind::Plain);
+        assert_eq!(lyrics.lines.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_queries_without_video_id_are_passed_on() -> Result<(), LyricsError> {
+        let provider = YouTubeMusicLyrics::new()?;
+        let result = provider.fetch(&LyricsQuery::new("Artist", "Song")).await;
+        assert!(matches!(result, Err(LyricsError::NotFound(_))));
+        Ok(())
+    }
+}